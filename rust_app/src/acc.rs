@@ -0,0 +1,388 @@
+//! Assetto Corsa Competizione telemetry from its shared memory pages.
+//!
+//! ACC publishes the player's car in three memory mapped files (physics, graphics and
+//! static). Only the leading part of each page that is mapped here is declared; the
+//! layouts follow the ACC shared memory documentation. There is no session info YAML and
+//! no per car data beyond positions, so standings, gaps and everything built on the
+//! `CarIdx*` arrays stay empty. SessionTime is synthesized from wall time while the
+//! session is live, so the race clock pauses with the game.
+
+use crate::telemetry_fields::{
+    TelemetryData, FLAG_BLACK, FLAG_BLUE, FLAG_CHECKERED, FLAG_GREEN, FLAG_WHITE, FLAG_YELLOW,
+};
+use crate::telemetry_source::*;
+use std::time::{Duration, Instant};
+
+const GRAVITY_MS2: f32 = 9.81;
+const PSI_TO_KPA: f32 = 6.894757;
+// ACC reports lap times in ms, with INT_MAX when there is no time yet
+const NO_LAP_TIME_MS: i32 = i32::MAX;
+
+// ACC_STATUS
+const STATUS_OFF: i32 = 0;
+const STATUS_REPLAY: i32 = 1;
+const STATUS_LIVE: i32 = 2;
+
+// ACC_FLAG_TYPE
+const ACC_BLUE_FLAG: i32 = 1;
+const ACC_YELLOW_FLAG: i32 = 2;
+const ACC_BLACK_FLAG: i32 = 3;
+const ACC_WHITE_FLAG: i32 = 4;
+const ACC_CHECKERED_FLAG: i32 = 5;
+const ACC_GREEN_FLAG: i32 = 7;
+
+/// Leading part of SPageFilePhysics
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Physics {
+    packet_id: i32,
+    gas: f32,
+    brake: f32,
+    fuel: f32,
+    /// 0 reverse, 1 neutral, 2 first...
+    gear: i32,
+    rpms: i32,
+    steer_angle: f32,
+    speed_kmh: f32,
+    velocity: [f32; 3],
+    acc_g: [f32; 3],
+    wheel_slip: [f32; 4],
+    wheel_load: [f32; 4],
+    wheels_pressure: [f32; 4],
+    wheel_angular_speed: [f32; 4],
+    tyre_wear: [f32; 4],
+    tyre_dirty_level: [f32; 4],
+    tyre_core_temperature: [f32; 4],
+    camber_rad: [f32; 4],
+    suspension_travel: [f32; 4],
+    drs: f32,
+    tc: f32,
+    heading: f32,
+    pitch: f32,
+    roll: f32,
+    cg_height: f32,
+    car_damage: [f32; 5],
+    number_of_tyres_out: i32,
+    pit_limiter_on: i32,
+    abs: f32,
+    kers_charge: f32,
+    kers_input: f32,
+    auto_shifter_on: i32,
+    ride_height: [f32; 2],
+    turbo_boost: f32,
+    ballast: f32,
+    air_density: f32,
+    air_temp: f32,
+    road_temp: f32,
+    local_angular_vel: [f32; 3],
+    final_ff: f32,
+    performance_meter: f32,
+    engine_brake: i32,
+    ers_recovery_level: i32,
+    ers_power_level: i32,
+    ers_heat_charging: i32,
+    ers_is_charging: i32,
+    kers_current_kj: f32,
+    drs_available: i32,
+    drs_enabled: i32,
+    brake_temp: [f32; 4],
+    clutch: f32,
+}
+
+/// Leading part of SPageFileGraphic
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Graphics {
+    packet_id: i32,
+    status: i32,
+    session: i32,
+    current_time: [u16; 15],
+    last_time: [u16; 15],
+    best_time: [u16; 15],
+    split: [u16; 15],
+    completed_laps: i32,
+    position: i32,
+    i_current_time: i32,
+    i_last_time: i32,
+    i_best_time: i32,
+    session_time_left: f32,
+    distance_traveled: f32,
+    is_in_pit: i32,
+    current_sector_index: i32,
+    last_sector_time: i32,
+    number_of_laps: i32,
+    tyre_compound: [u16; 33],
+    replay_time_multiplier: f32,
+    normalized_car_position: f32,
+    active_cars: i32,
+    car_coordinates: [[f32; 3]; 60],
+    car_id: [i32; 60],
+    player_car_id: i32,
+    penalty_time: f32,
+    flag: i32,
+    penalty: i32,
+    ideal_line_on: i32,
+    is_in_pit_lane: i32,
+    surface_grip: f32,
+    mandatory_pit_done: i32,
+    wind_speed: f32,
+    wind_direction: f32,
+}
+
+/// Leading part of SPageFileStatic
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Static {
+    sm_version: [u16; 15],
+    ac_version: [u16; 15],
+    number_of_sessions: i32,
+    num_cars: i32,
+    car_model: [u16; 33],
+    track: [u16; 33],
+    player_name: [u16; 33],
+    player_surname: [u16; 33],
+    player_nick: [u16; 33],
+    sector_count: i32,
+    max_torque: f32,
+    max_power: f32,
+    max_rpm: i32,
+    max_fuel: f32,
+}
+
+#[cfg(windows)]
+mod shared_memory {
+    use std::ffi::c_void;
+
+    const FILE_MAP_READ: u32 = 0x0004;
+
+    #[link(name = "kernel32")]
+    unsafe extern "system" {
+        fn OpenFileMappingW(desired_access: u32, inherit_handle: i32, name: *const u16) -> *mut c_void;
+        fn MapViewOfFile(mapping: *mut c_void, desired_access: u32, offset_high: u32, offset_low: u32, bytes: usize) -> *mut c_void;
+        fn UnmapViewOfFile(base: *const c_void) -> i32;
+        fn CloseHandle(handle: *mut c_void) -> i32;
+    }
+
+    /// Read-only view of a named shared memory page
+    pub struct View<T> {
+        handle: *mut c_void,
+        ptr: *const T,
+    }
+
+    // The view is only read, and only from the telemetry thread that owns the source
+    unsafe impl<T> Send for View<T> {}
+
+    impl<T: Copy> View<T> {
+        pub fn open(name: &str) -> Result<Self, String> {
+            let wide: Vec<u16> = name.encode_utf16().chain(std::iter::once(0)).collect();
+            unsafe {
+                let handle = OpenFileMappingW(FILE_MAP_READ, 0, wide.as_ptr());
+                if handle.is_null() {
+                    return Err(format!("{} is not available; is ACC running?", name));
+                }
+                let ptr = MapViewOfFile(handle, FILE_MAP_READ, 0, 0, std::mem::size_of::<T>());
+                if ptr.is_null() {
+                    CloseHandle(handle);
+                    return Err(format!("Failed to map {}", name));
+                }
+                Ok(Self { handle, ptr: ptr as *const T })
+            }
+        }
+
+        pub fn read(&self) -> T {
+            unsafe { std::ptr::read_volatile(self.ptr) }
+        }
+    }
+
+    impl<T> Drop for View<T> {
+        fn drop(&mut self) {
+            unsafe {
+                UnmapViewOfFile(self.ptr as *const c_void);
+                CloseHandle(self.handle);
+            }
+        }
+    }
+}
+
+#[cfg(windows)]
+struct Pages {
+    physics: shared_memory::View<Physics>,
+    graphics: shared_memory::View<Graphics>,
+    statics: shared_memory::View<Static>,
+}
+
+// Text fields are NUL terminated UTF-16
+fn wide_string(chars: &[u16]) -> String {
+    let end = chars.iter().position(|&c| c == 0).unwrap_or(chars.len());
+    String::from_utf16_lossy(&chars[..end])
+}
+
+fn lap_time(ms: i32) -> f32 {
+    if ms <= 0 || ms == NO_LAP_TIME_MS { 0.0 } else { ms as f32 / 1000.0 }
+}
+
+/// Telemetry source for Assetto Corsa Competizione
+pub struct AccSource {
+    #[cfg(windows)]
+    pages: Option<Pages>,
+    last_packet_id: i32,
+    session_time: f64,
+    last_frame_at: Option<Instant>,
+}
+
+impl AccSource {
+    pub fn new() -> Self {
+        Self {
+            #[cfg(windows)]
+            pages: None,
+            last_packet_id: -1,
+            session_time: 0.0,
+            last_frame_at: None,
+        }
+    }
+
+    fn translate(&mut self, physics: &Physics, graphics: &Graphics, statics: &Static) -> TelemetryData {
+        let mut data = TelemetryData::default();
+        data.sim = self.name().to_string();
+        data.capabilities = self.capabilities();
+
+        // Only live driving advances the clock; pauses and replays hold it
+        let now = Instant::now();
+        if graphics.status == STATUS_LIVE && let Some(previous) = self.last_frame_at {
+            self.session_time += now.duration_since(previous).as_secs_f64();
+        }
+        self.last_frame_at = Some(now);
+        data.SessionTime = self.session_time as f32;
+        data.is_replay_playing = graphics.status == STATUS_REPLAY;
+
+        // Inputs and engine
+        data.speed_kph = physics.speed_kmh;
+        data.speed_mph = physics.speed_kmh / 1.609344;
+        data.rpm = physics.rpms as f32;
+        data.gear_num = physics.gear - 1;
+        data.gear = match data.gear_num {
+            -1 => "R".to_string(),
+            0 => "N".to_string(),
+            gear => gear.to_string(),
+        };
+        data.throttle_pct = physics.gas * 100.0;
+        data.brake_pct = physics.brake * 100.0;
+        data.clutch_pct = physics.clutch * 100.0;
+        if statics.max_rpm > 0 {
+            data.shift_indicator_pct = (physics.rpms as f32 / statics.max_rpm as f32 * 100.0).min(100.0);
+        }
+
+        // Motion; ACC only gives world velocities, so the forward speed stands in for local X
+        data.VelocityX = physics.speed_kmh / 3.6;
+        data.lateral_accel_ms2 = physics.acc_g[0] * GRAVITY_MS2;
+        data.vertical_accel_ms2 = physics.acc_g[1] * GRAVITY_MS2;
+        data.longitudinal_accel_ms2 = physics.acc_g[2] * GRAVITY_MS2;
+        data.yaw_rate_deg_s = physics.local_angular_vel[1].to_degrees();
+
+        // Tires and brakes
+        data.tire_temps_c = physics.tyre_core_temperature;
+        data.tire_pressures_kpa = physics.wheels_pressure.map(|psi| psi * PSI_TO_KPA);
+        data.wheel_rpm = physics.wheel_angular_speed.map(|rad_s| rad_s * 60.0 / std::f32::consts::TAU);
+        data.brake_temps_c = physics.brake_temp;
+        data.shock_defl_mm = physics.suspension_travel.map(|m| m * 1000.0);
+        let [front, rear] = physics.ride_height.map(|m| m * 1000.0);
+        data.ride_height_mm = [front, front, rear, rear];
+
+        // Fuel and weather
+        data.fuel_level = physics.fuel;
+        if statics.max_fuel > 0.0 {
+            data.fuel_pct = physics.fuel / statics.max_fuel;
+        }
+        data.air_temp_c = physics.air_temp;
+        data.track_temp_c = physics.road_temp;
+        data.wind_vel_ms = graphics.wind_speed;
+        data.wind_dir_rad = graphics.wind_direction;
+
+        // Timing
+        data.lap_completed = graphics.completed_laps;
+        data.position = graphics.position;
+        data.current_lap_time = lap_time(graphics.i_current_time);
+        data.last_lap_time = lap_time(graphics.i_last_time);
+        data.best_lap_time = lap_time(graphics.i_best_time);
+        data.session_time_remain = (graphics.session_time_left / 1000.0).max(0.0);
+        data.lap_dist_pct = graphics.normalized_car_position;
+        data.on_pit_road = graphics.is_in_pit_lane != 0 || graphics.is_in_pit != 0;
+
+        data.session_flags = match graphics.flag {
+            ACC_BLUE_FLAG => FLAG_BLUE,
+            ACC_YELLOW_FLAG => FLAG_YELLOW,
+            ACC_BLACK_FLAG => FLAG_BLACK,
+            ACC_WHITE_FLAG => FLAG_WHITE,
+            ACC_CHECKERED_FLAG => FLAG_CHECKERED,
+            ACC_GREEN_FLAG => FLAG_GREEN,
+            _ => 0,
+        };
+
+        data.strings = Some(
+            [
+                ("car_model", wide_string(&statics.car_model)),
+                ("track", wide_string(&statics.track)),
+                ("tyre_compound", wide_string(&graphics.tyre_compound)),
+            ]
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), value))
+            .collect(),
+        );
+        data
+    }
+}
+
+impl TelemetrySource for AccSource {
+    fn name(&self) -> &'static str {
+        "acc"
+    }
+
+    fn capabilities(&self) -> u32 {
+        CAP_INPUTS | CAP_ENGINE | CAP_TIRES | CAP_LAP_TIMING | CAP_WEATHER | CAP_FLAGS | CAP_MOTION | CAP_FUEL
+    }
+
+    #[cfg(windows)]
+    fn connect(&mut self) -> Result<(), String> {
+        self.pages = Some(Pages {
+            physics: shared_memory::View::open("Local\\acpmf_physics")?,
+            graphics: shared_memory::View::open("Local\\acpmf_graphics")?,
+            statics: shared_memory::View::open("Local\\acpmf_static")?,
+        });
+        self.last_packet_id = -1;
+        self.last_frame_at = None;
+        Ok(())
+    }
+
+    #[cfg(not(windows))]
+    fn connect(&mut self) -> Result<(), String> {
+        Err("ACC shared memory is only available on Windows".to_string())
+    }
+
+    #[cfg(windows)]
+    fn next_frame(&mut self, timeout: Duration) -> Result<Option<TelemetryData>, String> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let Some(pages) = self.pages.as_ref() else {
+                return Err("Not connected to ACC".to_string());
+            };
+            let physics = pages.physics.read();
+            let graphics = pages.graphics.read();
+            // In the menus the pages are there but nothing is published
+            if graphics.status != STATUS_OFF && physics.packet_id != self.last_packet_id {
+                let statics = pages.statics.read();
+                self.last_packet_id = physics.packet_id;
+                return Ok(Some(self.translate(&physics, &graphics, &statics)));
+            }
+            if Instant::now() >= deadline {
+                return Ok(None);
+            }
+            std::thread::sleep(Duration::from_millis(2));
+        }
+    }
+
+    #[cfg(not(windows))]
+    fn next_frame(&mut self, _timeout: Duration) -> Result<Option<TelemetryData>, String> {
+        let _ = (STATUS_OFF, Self::translate, wide_string);
+        Err("ACC shared memory is only available on Windows".to_string())
+    }
+}
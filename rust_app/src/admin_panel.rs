@@ -0,0 +1,206 @@
+//! Web admin panel for rigs administered from another room.
+//!
+//! `GET /admin` serves one page (admin_panel.html) showing whether the sim is connected,
+//! the connected clients, the health of the outputs (see sink_health.rs), the
+//! config file for editing and the tail of the log. The page reads the JSON routes below
+//! it with the API token, which they all require; without a configured token the panel is
+//! closed, like the /admin WebSocket endpoint.
+//!
+//! - `GET /admin/status`: version, sim connection, tick load, clients and output health
+//! - `GET /admin/config`, `PUT /admin/config`: the config file as TOML text. Saving checks
+//!   it first; changes apply on the next start
+//! - `GET /admin/log?lines=200`: the last lines logged (see log_file.rs)
+//!
+//! The status is also the reply to the `{"type":"server_status"}` command.
+
+use crate::config;
+use crate::http_api::{HttpHandler, HttpRequest, HttpResponse};
+use crate::log_file;
+use crate::sink_health;
+use crate::telemetry_fields::TelemetryData;
+use crate::websocket_server::{ClientDirectory, RequestHandler};
+use serde_json::{json, Value};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Without a frame for this long the sim counts as disconnected
+pub const SIM_TIMEOUT: Duration = Duration::from_secs(3);
+/// Log lines returned when the request doesn't say
+pub const DEFAULT_LOG_LINES: usize = 200;
+
+const PAGE: &str = include_str!("admin_panel.html");
+
+#[derive(Default)]
+struct StatusState {
+    last_frame: Option<Instant>,
+    frames: u64,
+    sim: String,
+    session_time: f32,
+    stale: bool,
+    shed: Vec<String>,
+    tick_time: Duration,
+    tick_budget: Duration,
+}
+
+/// What the telemetry thread reports for the panel
+#[derive(Clone, Default)]
+pub struct AdminStatus(Arc<Mutex<StatusState>>);
+
+impl AdminStatus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Note a published frame
+    pub fn frame(&self, data: &TelemetryData) {
+        let Ok(mut state) = self.0.lock() else {
+            return;
+        };
+        state.last_frame = Some(Instant::now());
+        state.frames += 1;
+        state.sim = data.sim.clone();
+        state.session_time = data.SessionTime;
+        state.stale = data.stale;
+        state.shed = data.shed.clone();
+    }
+
+    /// Note the smoothed tick time against its budget (see load_shedding.rs)
+    pub fn load(&self, tick_time: Duration, budget: Duration) {
+        if let Ok(mut state) = self.0.lock() {
+            state.tick_time = tick_time;
+            state.tick_budget = budget;
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        let Ok(state) = self.0.lock() else {
+            return Value::Null;
+        };
+        let age = state.last_frame.map(|last| last.elapsed());
+        json!({
+            "sim": {
+                "connected": age.is_some_and(|age| age < SIM_TIMEOUT),
+                "name": state.sim,
+                "last_frame_s": age.map(|age| age.as_secs_f32()),
+                "frames": state.frames,
+                "session_time": state.session_time,
+                "stale": state.stale,
+            },
+            "load": {
+                "tick_ms": state.tick_time.as_secs_f32() * 1000.0,
+                "budget_ms": state.tick_budget.as_secs_f32() * 1000.0,
+                "shed": state.shed,
+            },
+            "sinks": sink_health::snapshot(),
+        })
+    }
+}
+
+/// HTTP handler for the admin page and its routes
+pub struct AdminPanel {
+    token: Option<String>,
+    status: AdminStatus,
+    clients: ClientDirectory,
+    config_path: PathBuf,
+    started: Instant,
+}
+
+impl AdminPanel {
+    pub fn new(token: Option<String>, status: AdminStatus, clients: ClientDirectory, config_path: PathBuf) -> Self {
+        Self {
+            token,
+            status,
+            clients,
+            config_path,
+            started: Instant::now(),
+        }
+    }
+
+    fn status(&self) -> Value {
+        let mut status = self.status.to_json();
+        let clients = self.clients.list();
+        if let Value::Object(fields) = &mut status {
+            fields.insert("version".to_string(), json!(crate::version::VERSION));
+            fields.insert("uptime_s".to_string(), json!(self.started.elapsed().as_secs()));
+            fields.insert("log_file".to_string(), json!(log_file::current_file().map(|path| path.display().to_string())));
+            fields.insert("client_count".to_string(), json!(clients.len()));
+            fields.insert("clients".to_string(), Value::Array(clients));
+        }
+        status
+    }
+
+    fn read_config(&self) -> HttpResponse {
+        match fs::read_to_string(&self.config_path) {
+            Ok(text) => HttpResponse::json(200, &json!({ "path": self.config_path.display().to_string(), "exists": true, "text": text })),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                HttpResponse::json(200, &json!({ "path": self.config_path.display().to_string(), "exists": false, "text": "" }))
+            },
+            Err(e) => HttpResponse::error(500, &format!("Failed to read {}: {}", self.config_path.display(), e)),
+        }
+    }
+
+    fn write_config(&self, body: &[u8]) -> HttpResponse {
+        let Ok(text) = std::str::from_utf8(body) else {
+            return HttpResponse::error(400, "Config must be UTF-8 text");
+        };
+        let options = match config::parse_args(text) {
+            Ok(args) => args.iter().filter(|arg| arg.starts_with("--")).count(),
+            Err(e) => return HttpResponse::error(400, &format!("Invalid config: {}", e)),
+        };
+        // Write to a temporary file first so a crash never leaves half a config
+        let mut tmp_path = self.config_path.clone().into_os_string();
+        tmp_path.push(".tmp");
+        let written = fs::write(&tmp_path, text).and_then(|_| fs::rename(&tmp_path, &self.config_path));
+        match written {
+            Ok(()) => HttpResponse::json(200, &json!({ "saved": true, "options": options, "restart_required": true })),
+            Err(e) => HttpResponse::error(500, &format!("Failed to write {}: {}", self.config_path.display(), e)),
+        }
+    }
+}
+
+impl RequestHandler for AdminPanel {
+    fn handle(&self, _request: &Value) -> Value {
+        let mut reply = json!({ "type": "server_status" });
+        if let (Value::Object(reply), Value::Object(status)) = (&mut reply, self.status()) {
+            reply.extend(status);
+        }
+        reply
+    }
+}
+
+impl HttpHandler for AdminPanel {
+    fn handle(&self, request: &HttpRequest) -> Option<HttpResponse> {
+        let segments = request.segments();
+        if segments.first() != Some(&"admin") {
+            return None;
+        }
+        if self.token.is_none() {
+            return Some(HttpResponse::error(401, "The admin panel is disabled until an API token is configured"));
+        }
+        // The page holds no data; it asks for the token and sends it with every call
+        if segments.len() == 1 {
+            return Some(match request.method.as_str() {
+                "GET" => HttpResponse::html(200, PAGE),
+                _ => HttpResponse::error(405, "Method not allowed"),
+            });
+        }
+        if !request.has_token(self.token.as_deref()) {
+            return Some(HttpResponse::error(401, "Invalid or missing token"));
+        }
+
+        let response = match (request.method.as_str(), &segments[1..]) {
+            ("GET", ["status"]) => HttpResponse::json(200, &self.status()),
+            ("GET", ["config"]) => self.read_config(),
+            ("PUT", ["config"]) => self.write_config(&request.body),
+            ("GET", ["log"]) => {
+                let lines = request.query.get("lines").and_then(|value| value.parse().ok()).unwrap_or(DEFAULT_LOG_LINES);
+                HttpResponse::json(200, &json!({ "file": log_file::current_file().map(|path| path.display().to_string()), "lines": log_file::recent(lines) }))
+            },
+            (_, ["status" | "config" | "log"]) => HttpResponse::error(405, "Method not allowed"),
+            _ => HttpResponse::error(404, "Not found"),
+        };
+        Some(response)
+    }
+}
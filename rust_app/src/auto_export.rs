@@ -0,0 +1,390 @@
+//! Automatic export and upload of each session's recording.
+//!
+//! With `--auto-export bundle,csv,motec` the newest recording in the sessions folder is
+//! exported once the session is over (everyone took the checkered flag, or a new session
+//! started): a session bundle (session_bundle.rs), a CSV with one row per lap and/or the
+//! telemetry as a MoTeC i2 CSV import, written to `--export-dir` (defaults to
+//! `<sessions-dir>/exports`). Exports are encrypted when SPEEDFORGE_PASSPHRASE is set
+//! (see encryption.rs). With `--upload-url` the exported
+//! files are then stored with one of the storage backends (storage.rs): another folder, a
+//! WebDAV folder or an S3 bucket. Failed uploads are retried after `RETRY_DELAYS`. The work
+//! runs on its own thread; a `session_export` event reports the files written and uploaded,
+//! and any errors.
+
+use crate::derived::DerivedChannel;
+use crate::encryption;
+use crate::events;
+use crate::lap_summary::LapSummary;
+use crate::session_bundle::{self, SessionBundle};
+use crate::session_yaml;
+use crate::sink_health;
+use crate::storage::{self, Storage};
+use crate::telemetry_fields::TelemetryData;
+use serde_json::{json, Value};
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+/// Export tasks that can be configured
+pub const TASKS: &[&str] = &["bundle", "csv", "motec"];
+/// Folder in the sessions folder exports go to without `--export-dir`
+pub const EXPORTS_DIR: &str = "exports";
+/// Names exports and uploads report their health under (see sink_health.rs)
+pub const EXPORT_SINK: &str = "auto_export";
+pub const UPLOAD_SINK: &str = "upload";
+/// Waits before each upload retry
+pub const RETRY_DELAYS: &[Duration] = &[Duration::from_secs(30), Duration::from_secs(120), Duration::from_secs(600)];
+
+// irsdk_StateCoolDown: every car has taken the checkered flag
+const STATE_COOL_DOWN: i32 = 6;
+// Session time after cool-down starts before exporting, so the recording has caught up
+const EXPORT_DELAY_S: f32 = 15.0;
+
+/// What to export and where to upload it
+#[derive(Clone)]
+pub struct ExportConfig {
+    pub tasks: Vec<String>,
+    pub sessions_dir: PathBuf,
+    pub output_dir: PathBuf,
+    pub upload: Option<Arc<dyn Storage>>,
+}
+
+impl ExportConfig {
+    /// Config from `--auto-export` and friends; None when auto-export is off
+    pub fn from_args(args: &[String]) -> Result<Option<Self>, String> {
+        let Some(list) = crate::arg_value(args, "--auto-export") else {
+            return Ok(None);
+        };
+        let mut tasks = Vec::new();
+        for task in list.split(',').map(str::trim).filter(|task| !task.is_empty()) {
+            if !TASKS.contains(&task) {
+                return Err(format!("Unknown export task '{}' (available: {})", task, TASKS.join(", ")));
+            }
+            tasks.push(task.to_string());
+        }
+        let Some(sessions_dir) = crate::arg_value(args, "--sessions-dir").map(PathBuf::from) else {
+            return Err("--auto-export needs --sessions-dir to find the recordings".to_string());
+        };
+        let output_dir = crate::arg_value(args, "--export-dir").map(PathBuf::from).unwrap_or_else(|| sessions_dir.join(EXPORTS_DIR));
+        Ok(Some(ExportConfig {
+            tasks,
+            sessions_dir,
+            output_dir,
+            upload: crate::arg_value(args, "--upload-url").map(|url| storage::open(&url, args)).transpose()?,
+        }))
+    }
+}
+
+// Newest recording written to since the session started
+fn session_recording(dir: &Path, since: SystemTime) -> Option<PathBuf> {
+    fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().extension().is_some_and(|extension| extension == "jsonl"))
+        .filter_map(|entry| Some((entry.metadata().ok()?.modified().ok()?, entry.path())))
+        .filter(|(modified, _)| *modified >= since)
+        .max_by_key(|(modified, _)| *modified)
+        .map(|(_, path)| path)
+}
+
+fn csv_field(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(text) if text.contains([',', '"', '\n']) => format!("\"{}\"", text.replace('"', "\"\"")),
+        Value::String(text) => text.clone(),
+        Value::Array(items) => items.iter().map(csv_field).collect::<Vec<_>>().join(";"),
+        other => other.to_string(),
+    }
+}
+
+/// One row per lap, with a column per lap summary field
+pub fn laps_csv(laps: &[LapSummary]) -> String {
+    let rows: Vec<serde_json::Map<String, Value>> = laps
+        .iter()
+        .filter_map(|lap| match serde_json::to_value(lap) {
+            Ok(Value::Object(fields)) => Some(fields),
+            _ => None,
+        })
+        .collect();
+    let Some(first) = rows.first() else {
+        return String::new();
+    };
+    // Nested sections don't fit in a column
+    let columns: Vec<&String> = first.iter().filter(|(_, value)| !value.is_object()).map(|(name, _)| name).collect();
+    let mut csv = columns.iter().map(|name| name.as_str()).collect::<Vec<_>>().join(",");
+    csv.push('\n');
+    for row in &rows {
+        let fields: Vec<String> = columns.iter().map(|name| row.get(*name).map(csv_field).unwrap_or_default()).collect();
+        let _ = writeln!(csv, "{}", fields.join(","));
+    }
+    csv
+}
+
+fn motec_field(text: &str) -> String {
+    format!("\"{}\"", text.replace('"', "'"))
+}
+
+/// The bundle's telemetry in the layout MoTeC i2 imports CSV files in: a header block
+/// describing the outing, lap beacons, then one row per frame at the bundle's rate
+pub fn motec_csv(bundle: &SessionBundle) -> String {
+    const CHANNELS: &[(&str, &str)] = &[
+        ("Time", "s"),
+        ("Lap Distance", "m"),
+        ("Lap Number", ""),
+        ("Ground Speed", "km/h"),
+        ("Engine RPM", "rpm"),
+        ("Gear", ""),
+        ("Throttle Pos", "%"),
+        ("Brake Pos", "%"),
+        ("Clutch Pos", "%"),
+        ("Steered Angle", "deg"),
+        ("G Force Lat", "G"),
+        ("G Force Long", "G"),
+        ("Fuel Level", "l"),
+        ("GPS Latitude", "deg"),
+        ("GPS Longitude", "deg"),
+    ];
+    const GRAVITY_MS2: f32 = 9.80665;
+
+    let info = &bundle.session_info;
+    let player = session_yaml::scalar(info, "DriverCarIdx").and_then(|idx| idx.parse::<i32>().ok());
+    let driver = session_yaml::drivers(info).into_iter().find(|driver| Some(driver.car_idx) == player).map(|driver| driver.user_name).unwrap_or_default();
+    let rate_hz = if bundle.manifest.rate_hz > 0.0 { bundle.manifest.rate_hz } else { session_bundle::DEFAULT_RATE_HZ };
+    let duration = bundle.frames.len() as f32 / rate_hz;
+    // Lap beacons, at the row each lap was completed on
+    let beacons: Vec<String> = bundle
+        .frames
+        .windows(2)
+        .enumerate()
+        .filter(|(_, pair)| pair[1].lap_completed > pair[0].lap_completed)
+        .map(|(i, _)| format!("{:.3}", (i + 1) as f32 / rate_hz))
+        .collect();
+    let exported = chrono::DateTime::parse_from_rfc3339(&bundle.manifest.exported_at)
+        .map(|time| time.naive_local())
+        .unwrap_or_else(|_| chrono::Local::now().naive_local());
+
+    let mut csv = String::new();
+    let header: [[&str; 6]; 5] = [
+        ["Format", "MoTeC CSV File", "", "", "Workbook", ""],
+        ["Venue", &bundle.manifest.track, "", "", "Worksheet", ""],
+        ["Vehicle", &bundle.manifest.car, "", "", "Vehicle Desc", ""],
+        ["Driver", &driver, "", "", "Engine ID", ""],
+        ["Device", "speedforge", "", "", "Session", &bundle.manifest.source],
+    ];
+    for row in header {
+        let _ = writeln!(csv, "{}", row.iter().map(|field| motec_field(field)).collect::<Vec<_>>().join(","));
+    }
+    let _ = writeln!(csv, "\"Comment\",\"\",\"\",\"\",\"Origin Time\",\"0.000\",\"s\"");
+    let _ = writeln!(csv, "\"Log Date\",\"{}\",\"\",\"\",\"Start Time\",\"0.000\",\"s\"", exported.format("%d/%m/%Y"));
+    let _ = writeln!(csv, "\"Log Time\",\"{}\",\"\",\"\",\"End Time\",\"{:.3}\",\"s\"", exported.format("%H:%M:%S"), duration);
+    let _ = writeln!(csv, "\"Sample Rate\",\"{}\",\"Hz\"", rate_hz);
+    let _ = writeln!(csv, "\"Duration\",\"{:.3}\",\"s\"", duration);
+    let _ = writeln!(csv, "\"Range\",\"entire outing\"");
+    let _ = writeln!(csv, "\"Beacon Markers\",{}", beacons.join(","));
+    csv.push('\n');
+    let _ = writeln!(csv, "{}", CHANNELS.iter().map(|(name, _)| motec_field(name)).collect::<Vec<_>>().join(","));
+    let _ = writeln!(csv, "{}", CHANNELS.iter().map(|(_, unit)| motec_field(unit)).collect::<Vec<_>>().join(","));
+    csv.push_str("\n\n");
+    for (i, frame) in bundle.frames.iter().enumerate() {
+        let _ = writeln!(
+            csv,
+            "{:.3},{:.2},{},{:.2},{:.0},{},{:.1},{:.1},{:.1},{:.2},{:.3},{:.3},{:.2},{:.7},{:.7}",
+            i as f32 / rate_hz,
+            frame.lap_dist,
+            frame.lap_completed + 1,
+            frame.speed_kph,
+            frame.rpm,
+            frame.gear_num,
+            frame.throttle_pct,
+            frame.brake_pct,
+            frame.clutch_pct,
+            frame.steering_angle_deg,
+            frame.lateral_accel_ms2 / GRAVITY_MS2,
+            frame.longitudinal_accel_ms2 / GRAVITY_MS2,
+            frame.fuel_level,
+            frame.lat,
+            frame.lon,
+        );
+    }
+    csv
+}
+
+// Multipart uploads carry on from the last finished part on each retry
+fn upload_with_retry(storage: &dyn Storage, path: &Path) -> Result<String, String> {
+    let name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    let mut delays = RETRY_DELAYS.iter();
+    loop {
+        let result = storage.put_file(path, &name);
+        match &result {
+            Ok(_) => sink_health::success(UPLOAD_SINK),
+            Err(e) => sink_health::failure(UPLOAD_SINK, e),
+        }
+        match result {
+            Ok(location) => return Ok(location),
+            Err(e) => match delays.next() {
+                Some(delay) => {
+                    tracing::warn!("Upload of {} failed, retrying in {}s: {}", name, delay.as_secs(), e);
+                    std::thread::sleep(*delay);
+                },
+                None => return Err(format!("Upload of {} failed: {}", name, e)),
+            },
+        }
+    }
+}
+
+// Export the session's recording and upload the results; the report for the event
+fn export(config: &ExportConfig, session_started: SystemTime) -> Value {
+    let Some(recording) = session_recording(&config.sessions_dir, session_started) else {
+        sink_health::failure(EXPORT_SINK, "no recording of the session");
+        return json!({
+            "recording": null,
+            "files": [],
+            "uploaded": [],
+            "errors": [format!("No recording of this session in {}", config.sessions_dir.display())],
+        });
+    };
+    let mut files = Vec::new();
+    let mut uploaded = Vec::new();
+    let mut errors = Vec::new();
+
+    let stem = recording.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+    let passphrase = encryption::passphrase();
+    let written = fs::create_dir_all(&config.output_dir)
+        .map_err(|e| format!("Failed to create {}: {}", config.output_dir.display(), e))
+        .and_then(|()| SessionBundle::from_recording(&recording, session_bundle::DEFAULT_RATE_HZ).map_err(|e| e.to_string()));
+    match written {
+        Ok(bundle) => {
+            for task in &config.tasks {
+                let (path, result) = match task.as_str() {
+                    "bundle" => {
+                        let path = config.output_dir.join(format!("{}.zip", stem));
+                        let result = bundle.write(&path, passphrase.as_deref()).map_err(|e| e.to_string());
+                        (path, result)
+                    },
+                    "csv" => {
+                        let path = config.output_dir.join(format!("{}_laps.csv", stem));
+                        let result = encryption::write_file(&path, laps_csv(&bundle.laps).as_bytes(), passphrase.as_deref()).map_err(|e| e.to_string());
+                        (path, result)
+                    },
+                    "motec" => {
+                        let path = config.output_dir.join(format!("{}_motec.csv", stem));
+                        let result = encryption::write_file(&path, motec_csv(&bundle).as_bytes(), passphrase.as_deref()).map_err(|e| e.to_string());
+                        (path, result)
+                    },
+                    _ => continue,
+                };
+                match result {
+                    Ok(()) => {
+                        tracing::info!("Wrote {}", path.display());
+                        files.push(path);
+                    },
+                    Err(e) => errors.push(format!("Failed to write {}: {}", path.display(), e)),
+                }
+            }
+        },
+        Err(e) => errors.push(e),
+    }
+
+    match errors.first() {
+        Some(error) => sink_health::failure(EXPORT_SINK, error),
+        None => sink_health::success(EXPORT_SINK),
+    }
+
+    if let Some(storage) = &config.upload {
+        for path in &files {
+            match upload_with_retry(storage.as_ref(), path) {
+                Ok(location) => {
+                    tracing::info!("Uploaded {}", location);
+                    uploaded.push(location);
+                },
+                Err(e) => errors.push(e),
+            }
+        }
+    }
+    for error in &errors {
+        tracing::error!("{}", error);
+    }
+    json!({
+        "recording": recording.display().to_string(),
+        "files": files.iter().map(|path| path.display().to_string()).collect::<Vec<_>>(),
+        "uploaded": uploaded,
+        "errors": errors,
+    })
+}
+
+/// Derived channel that starts the export when a session is over and reports the result
+pub struct SessionExporter {
+    jobs: Sender<SystemTime>,
+    reports: Receiver<Value>,
+    session_started: SystemTime,
+    cool_down_since: Option<f32>,
+    // The session had live frames and hasn't been exported yet
+    pending: bool,
+}
+
+impl SessionExporter {
+    /// Start the export thread
+    pub fn new(config: ExportConfig) -> Result<Self, String> {
+        let (jobs, job_receiver) = mpsc::channel::<SystemTime>();
+        let (report_sender, reports) = mpsc::channel();
+        std::thread::Builder::new()
+            .name("auto-export".to_string())
+            .spawn(move || {
+                for session_started in job_receiver {
+                    // A panic fails this export only; the thread stays for the next session
+                    let report = sink_health::catch(EXPORT_SINK, || export(&config, session_started))
+                        .unwrap_or_else(|| json!({ "recording": null, "files": [], "uploaded": [], "errors": ["The export failed unexpectedly"] }));
+                    let _ = report_sender.send(report);
+                }
+            })
+            .map_err(|e| format!("Failed to start the export thread: {}", e))?;
+        Ok(Self {
+            jobs,
+            reports,
+            session_started: SystemTime::now(),
+            cool_down_since: None,
+            pending: false,
+        })
+    }
+
+    fn submit(&mut self) {
+        self.pending = false;
+        let _ = self.jobs.send(self.session_started);
+    }
+}
+
+impl DerivedChannel for SessionExporter {
+    fn name(&self) -> &'static str {
+        "auto_export"
+    }
+
+    fn compute(&mut self, data: &mut TelemetryData) {
+        let reports: Vec<Value> = self.reports.try_iter().collect();
+        for report in reports {
+            events::emit(data, "session_export", report);
+        }
+
+        if data.race_clock.session_reset {
+            if self.pending {
+                self.submit();
+            }
+            self.session_started = SystemTime::now();
+            self.cool_down_since = None;
+        }
+        if data.stale {
+            return;
+        }
+        if self.cool_down_since.is_none() {
+            self.pending = true;
+        }
+        if data.session_state == STATE_COOL_DOWN && self.pending {
+            let since = *self.cool_down_since.get_or_insert(data.SessionTime);
+            if data.SessionTime - since >= EXPORT_DELAY_S {
+                self.submit();
+            }
+        }
+    }
+}
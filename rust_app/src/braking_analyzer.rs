@@ -0,0 +1,246 @@
+use crate::corners::{CornerDatabase, CORNERS_PATH};
+use crate::derived::DerivedChannel;
+use crate::laps::{self, LapChange, LapTracker};
+use crate::telemetry_fields::TelemetryData;
+use serde::{Serialize, Deserialize};
+
+// Brake pressure that starts a braking zone
+const BRAKE_ON_PCT: f32 = 10.0;
+// Brake pressure below which the zone is considered released
+const BRAKE_OFF_PCT: f32 = 5.0;
+// How long the brake must stay released before the zone ends (allows brief modulation)
+const RELEASE_TIME_S: f32 = 0.25;
+// Zones shorter than this are treated as taps and ignored
+const MIN_ZONE_TIME_S: f32 = 0.3;
+// Ignore braking at very low speeds (pit lane, spins)
+const MIN_ENTRY_SPEED_KPH: f32 = 30.0;
+// Steering angle that counts as "turning" for trail braking
+const TRAIL_STEERING_DEG: f32 = 5.0;
+// Number of points the brake trace is resampled to
+const PROFILE_POINTS: usize = 10;
+// Without known corners, maximum distance (fraction of a lap) between zone starts to treat
+// them as the same corner
+const ZONE_MATCH_PCT: f32 = 0.02;
+
+/// Measurements for one braking zone
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct BrakingZone {
+    /// Index of the zone within its lap
+    pub zone: usize,
+    pub lap: i32,
+    /// Index of the corner braked for in the track's list in corners.json, and its name
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub corner_id: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub corner: Option<String>,
+    pub start_pct: f32,
+    pub end_pct: f32,
+    pub entry_speed_kph: f32,
+    pub min_speed_kph: f32,
+    pub peak_decel_g: f32,
+    pub peak_brake_pct: f32,
+    pub brake_distance_m: f32,
+    pub duration_s: f32,
+    /// Share of the zone (by time) spent braking while turning
+    pub trail_brake_pct: f32,
+    /// Brake pressure resampled to evenly spaced points through the zone
+    pub brake_profile: Vec<f32>,
+}
+
+/// Differences between a zone and the matching zone of the reference lap
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct BrakingDeltas {
+    /// Positive = braked later than the reference
+    pub brake_point_m: f32,
+    pub entry_speed_kph: f32,
+    pub min_speed_kph: f32,
+    pub peak_decel_g: f32,
+    pub brake_distance_m: f32,
+}
+
+/// Report broadcast when a braking zone finishes
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct BrakingReport {
+    pub zone: BrakingZone,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reference: Option<BrakingZone>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deltas: Option<BrakingDeltas>,
+}
+
+// Zone currently being measured
+struct ActiveZone {
+    zone: BrakingZone,
+    samples: Vec<(f32, f32)>, // (elapsed time, brake pct)
+    trail_time: f32,
+    released_for: f32,
+}
+
+/// Detects braking zones and compares them against the player's best lap; zones are
+/// matched by corner when the track is in the corner database
+pub struct BrakingAnalyzer {
+    laps: LapTracker,
+    corners: CornerDatabase,
+    active: Option<ActiveZone>,
+    current_lap_zones: Vec<BrakingZone>,
+    reference_zones: Vec<BrakingZone>,
+    reference_lap_time: Option<f32>,
+    pitted_this_lap: bool,
+}
+
+impl BrakingAnalyzer {
+    pub fn new() -> Self {
+        Self {
+            laps: LapTracker::new(),
+            corners: CornerDatabase::load(CORNERS_PATH),
+            active: None,
+            current_lap_zones: Vec::new(),
+            reference_zones: Vec::new(),
+            reference_lap_time: None,
+            pitted_this_lap: false,
+        }
+    }
+
+    // Find the reference zone for the same corner, or else the one closest to the given
+    // lap position
+    fn find_reference(&self, zone: &BrakingZone) -> Option<&BrakingZone> {
+        if zone.corner_id.is_some() {
+            return self.reference_zones.iter().find(|reference| reference.corner_id == zone.corner_id);
+        }
+        let start_pct = zone.start_pct;
+        self.reference_zones
+            .iter()
+            .map(|zone| (zone, laps::lap_pct_distance(zone.start_pct, start_pct)))
+            .filter(|(_, distance)| *distance <= ZONE_MATCH_PCT)
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+            .map(|(zone, _)| zone)
+    }
+
+    fn finish_zone(&mut self, active: ActiveZone, data: &TelemetryData) -> Option<BrakingReport> {
+        let mut zone = active.zone;
+        if zone.duration_s < MIN_ZONE_TIME_S {
+            return None;
+        }
+
+        zone.end_pct = data.lap_dist_pct;
+        zone.trail_brake_pct = active.trail_time / zone.duration_s * 100.0;
+        zone.brake_profile = resample(&active.samples, zone.duration_s, PROFILE_POINTS);
+        zone.zone = self.current_lap_zones.len();
+        self.current_lap_zones.push(zone.clone());
+
+        let track_length_m = laps::estimate_track_length(data);
+        let reference = self.find_reference(&zone).cloned();
+        let deltas = reference.as_ref().map(|reference| BrakingDeltas {
+            brake_point_m: laps::signed_lap_pct_delta(reference.start_pct, zone.start_pct) * track_length_m,
+            entry_speed_kph: zone.entry_speed_kph - reference.entry_speed_kph,
+            min_speed_kph: zone.min_speed_kph - reference.min_speed_kph,
+            peak_decel_g: zone.peak_decel_g - reference.peak_decel_g,
+            brake_distance_m: zone.brake_distance_m - reference.brake_distance_m,
+        });
+
+        Some(BrakingReport { zone, reference, deltas })
+    }
+
+    fn on_lap_completed(&mut self, lap_time: f32, clean: bool) {
+        let zones = std::mem::take(&mut self.current_lap_zones);
+        if !clean || lap_time <= 0.0 || zones.is_empty() {
+            return;
+        }
+        if self.reference_lap_time.is_none_or(|best| lap_time < best) {
+            self.reference_lap_time = Some(lap_time);
+            self.reference_zones = zones;
+        }
+    }
+}
+
+impl DerivedChannel for BrakingAnalyzer {
+    fn name(&self) -> &'static str {
+        "braking"
+    }
+
+    fn dependencies(&self) -> &'static [&'static str] {
+        &["velocity", "g_forces"]
+    }
+
+    fn compute(&mut self, data: &mut TelemetryData) {
+        let dt = data.race_clock.dt;
+
+        match self.laps.update(data) {
+            LapChange::Completed { lap_time, .. } => {
+                // In/out laps never become the reference
+                let clean = !self.pitted_this_lap;
+                self.on_lap_completed(lap_time, clean);
+                self.pitted_this_lap = false;
+            },
+            LapChange::Reset => {
+                self.active = None;
+                self.current_lap_zones.clear();
+            },
+            LapChange::None => {}
+        }
+        self.pitted_this_lap |= data.on_pit_road;
+
+        if let Some(mut active) = self.active.take() {
+            active.zone.duration_s += dt;
+            active.zone.brake_distance_m += data.velocity_ms * dt;
+            active.zone.min_speed_kph = active.zone.min_speed_kph.min(data.speed_kph);
+            active.zone.peak_decel_g = active.zone.peak_decel_g.max(-data.g_force_lon);
+            active.zone.peak_brake_pct = active.zone.peak_brake_pct.max(data.brake_pct);
+            active.samples.push((active.zone.duration_s, data.brake_pct));
+
+            if data.brake_pct > BRAKE_OFF_PCT && data.steering_angle_deg.abs() > TRAIL_STEERING_DEG {
+                active.trail_time += dt;
+            }
+
+            if data.brake_pct < BRAKE_OFF_PCT {
+                active.released_for += dt;
+            } else {
+                active.released_for = 0.0;
+            }
+
+            if active.released_for >= RELEASE_TIME_S || data.on_pit_road {
+                if let Some(report) = self.finish_zone(active, data) {
+                    data.braking_report = Some(report);
+                }
+            } else {
+                self.active = Some(active);
+            }
+        } else if data.brake_pct >= BRAKE_ON_PCT && data.speed_kph >= MIN_ENTRY_SPEED_KPH && !data.on_pit_road {
+            let track_id = data.session_metadata.as_ref().and_then(|m| m.track.as_ref()).map(|t| t.track_id);
+            let corner = track_id.and_then(|id| self.corners.corner_ahead(id, data.lap_dist_pct));
+            self.active = Some(ActiveZone {
+                zone: BrakingZone {
+                    lap: data.lap_completed,
+                    corner_id: corner.map(|(id, _)| id),
+                    corner: corner.map(|(_, corner)| corner.name.clone()),
+                    start_pct: data.lap_dist_pct,
+                    entry_speed_kph: data.speed_kph,
+                    min_speed_kph: data.speed_kph,
+                    peak_brake_pct: data.brake_pct,
+                    peak_decel_g: -data.g_force_lon,
+                    ..Default::default()
+                },
+                samples: vec![(0.0, data.brake_pct)],
+                trail_time: 0.0,
+                released_for: 0.0,
+            });
+        }
+    }
+}
+
+/// Resample (time, value) samples to evenly spaced points
+pub fn resample(samples: &[(f32, f32)], duration: f32, points: usize) -> Vec<f32> {
+    if samples.is_empty() || points == 0 {
+        return Vec::new();
+    }
+    let mut result = Vec::with_capacity(points);
+    let mut index = 0;
+    for i in 0..points {
+        let t = duration * i as f32 / (points - 1).max(1) as f32;
+        while index + 1 < samples.len() && samples[index + 1].0 <= t {
+            index += 1;
+        }
+        result.push(samples[index].1);
+    }
+    result
+}
@@ -0,0 +1,203 @@
+//! Static per-car metadata for overlays: number, class, livery colors and a stable id.
+//!
+//! CarIdx slots are reassigned between sessions (and when drivers join late), so overlays
+//! that key graphics by CarIdx show the wrong name or logo. Each car gets a `car_uid`
+//! that stays the same for the same entry: the team in team events, otherwise the driver,
+//! otherwise (AI, pace car) the car model and number. Brand names and logo identifiers
+//! come from an optional asset map, a JSON file keyed by CarPath or CarID:
+//!
+//! ```json
+//! {"mx5 mx52016": {"brand": "Mazda", "logo": "mazda"}, "145": {"brand": "Porsche"}}
+//! ```
+//!
+//! Clients ask with `{"type":"get_car_info"}`, optionally with `car_idx` or `car_uid`.
+
+use crate::derived::DerivedChannel;
+use crate::protocol;
+use crate::session_yaml::{self, DriverEntry};
+use crate::telemetry_fields::TelemetryData;
+use crate::websocket_server::RequestHandler;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// Brand and logo for a car model
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct CarAsset {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub brand: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logo: Option<String>,
+}
+
+/// Asset map keyed by CarPath or CarID
+#[derive(Clone, Debug, Default)]
+pub struct CarAssets {
+    assets: HashMap<String, CarAsset>,
+}
+
+impl CarAssets {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents = fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let assets = serde_json::from_str(&contents).map_err(|e| format!("Invalid car asset map {}: {}", path.display(), e))?;
+        Ok(Self { assets })
+    }
+
+    pub fn len(&self) -> usize {
+        self.assets.len()
+    }
+
+    fn lookup(&self, driver: &DriverEntry) -> CarAsset {
+        self.assets
+            .get(&driver.car_path)
+            .or_else(|| self.assets.get(&driver.car_id.to_string()))
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+/// Paint scheme from a CarDesignStr such as "2,ffffff,0a2f6e,ff0000"
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct Livery {
+    pub pattern: i32,
+    /// "#rrggbb"
+    pub colors: Vec<String>,
+}
+
+impl Livery {
+    pub fn parse(design: &str) -> Option<Self> {
+        let mut parts = design.split(',').map(str::trim);
+        let pattern = parts.next()?.parse().ok()?;
+        let colors = parts
+            .filter(|part| part.len() == 6 && part.chars().all(|c| c.is_ascii_hexdigit()))
+            .map(|hex| format!("#{}", hex.to_ascii_lowercase()))
+            .collect();
+        Some(Self { pattern, colors })
+    }
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct CarInfo {
+    pub car_idx: i32,
+    /// Stable across sessions for the same entry
+    pub car_uid: String,
+    pub car_number: String,
+    pub car_id: i64,
+    pub car_name: String,
+    pub car_path: String,
+    pub class_id: i32,
+    pub class_name: String,
+    pub class_color: String,
+    pub driver_name: String,
+    pub team_name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub livery: Option<Livery>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub number_design: Option<Livery>,
+    #[serde(flatten)]
+    pub asset: CarAsset,
+    pub is_pace_car: bool,
+}
+
+/// Stable id for a car entry: team, then driver, then car model and number
+pub fn car_uid(driver: &DriverEntry) -> String {
+    if driver.team_id > 0 {
+        format!("team-{}", driver.team_id)
+    } else if driver.user_id > 0 {
+        format!("driver-{}", driver.user_id)
+    } else {
+        format!("car-{}-{}", driver.car_id, driver.car_number)
+    }
+}
+
+fn car_info(driver: &DriverEntry, assets: &CarAssets) -> CarInfo {
+    CarInfo {
+        car_idx: driver.car_idx,
+        car_uid: car_uid(driver),
+        car_number: driver.car_number.clone(),
+        car_id: driver.car_id,
+        car_name: driver.car_screen_name.clone(),
+        car_path: driver.car_path.clone(),
+        class_id: driver.car_class_id,
+        class_name: driver.car_class_short_name.clone(),
+        class_color: driver.car_class_color.clone(),
+        driver_name: driver.user_name.clone(),
+        team_name: driver.team_name.clone(),
+        livery: Livery::parse(&driver.car_design),
+        number_design: Livery::parse(&driver.car_number_design),
+        asset: assets.lookup(driver),
+        is_pace_car: driver.is_pace_car,
+    }
+}
+
+/// Car metadata for the current session, shared between the telemetry thread and the server
+#[derive(Clone, Default)]
+pub struct CarInfoStore {
+    cars: Arc<Mutex<Vec<CarInfo>>>,
+}
+
+impl CarInfoStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cars(&self) -> Vec<CarInfo> {
+        self.cars.lock().map(|cars| cars.clone()).unwrap_or_default()
+    }
+}
+
+impl RequestHandler for CarInfoStore {
+    /// `{"type":"get_car_info"}`, optionally with `car_idx` or `car_uid`
+    fn handle(&self, request: &Value) -> Value {
+        let car_idx = request.get("car_idx").and_then(Value::as_i64);
+        let car_uid = request.get("car_uid").and_then(Value::as_str);
+        let cars: Vec<CarInfo> = self
+            .cars()
+            .into_iter()
+            .filter(|car| car_idx.is_none_or(|idx| car.car_idx as i64 == idx))
+            .filter(|car| car_uid.is_none_or(|uid| car.car_uid == uid))
+            .collect();
+        if cars.is_empty() && (car_idx.is_some() || car_uid.is_some()) {
+            return protocol::error(protocol::ERROR_NOT_FOUND, "No such car in this session", Some("get_car_info"));
+        }
+        json!({ "type": "car_info", "cars": cars })
+    }
+}
+
+/// Derived channel that rebuilds the car metadata when the session info changes
+pub struct CarInfoRecorder {
+    store: CarInfoStore,
+    assets: CarAssets,
+    last_session_info: String,
+}
+
+impl CarInfoRecorder {
+    pub fn new(store: CarInfoStore, assets: CarAssets) -> Self {
+        Self { store, assets, last_session_info: String::new() }
+    }
+}
+
+impl DerivedChannel for CarInfoRecorder {
+    fn name(&self) -> &'static str {
+        "car_info"
+    }
+
+    fn compute(&mut self, data: &mut TelemetryData) {
+        if data.session_info.is_empty() || data.session_info == self.last_session_info {
+            return;
+        }
+        self.last_session_info = data.session_info.clone();
+        let cars = session_yaml::drivers(&data.session_info)
+            .iter()
+            .filter(|driver| !driver.is_spectator)
+            .map(|driver| car_info(driver, &self.assets))
+            .collect();
+        if let Ok(mut current) = self.store.cars.lock() {
+            *current = cars;
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use crate::derived::DerivedChannel;
+use crate::telemetry_fields::TelemetryData;
+use serde::{Serialize, Deserialize};
+use std::collections::VecDeque;
+
+/// Seconds of position history used to fit each car's speed along the lap
+const FIT_WINDOW_S: f32 = 0.6;
+
+/// A jump larger than this (fraction of a lap) between frames is a tow or reset, not driving
+const MAX_STEP_PCT: f32 = 0.05;
+
+/// Hints for extrapolating car positions between frames
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct CarMotionHints {
+    /// SessionTime the positions in this frame were sampled at
+    pub session_time: f32,
+    /// Lap fraction per second for each CarIdx (0 when unknown or not on track)
+    pub lap_dist_pct_per_sec: Vec<f32>,
+}
+
+/// Recent positions of one car with lap wraps removed so the series is continuous
+#[derive(Default)]
+struct PositionHistory {
+    /// (session time, unwrapped lap distance pct)
+    samples: VecDeque<(f32, f32)>,
+    /// Laps added to the raw lap distance to undo wraps at the line
+    wraps: f32,
+    last_raw: Option<f32>,
+}
+
+impl PositionHistory {
+    fn clear(&mut self) {
+        self.samples.clear();
+        self.wraps = 0.0;
+        self.last_raw = None;
+    }
+
+    fn push(&mut self, session_time: f32, raw_pct: f32) {
+        if let Some(last) = self.last_raw {
+            let mut step = raw_pct - last;
+            if step < -0.5 {
+                self.wraps += 1.0;
+                step += 1.0;
+            } else if step > 0.5 {
+                // Crossed the line backwards
+                self.wraps -= 1.0;
+                step -= 1.0;
+            }
+            if step.abs() > MAX_STEP_PCT {
+                self.clear();
+            }
+        }
+        self.last_raw = Some(raw_pct);
+        self.samples.push_back((session_time, raw_pct + self.wraps));
+
+        while let Some(&(t, _)) = self.samples.front() {
+            if session_time - t > FIT_WINDOW_S {
+                self.samples.pop_front();
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Least-squares slope of position over time; smooths out jitter in single frames
+    fn rate(&self) -> f32 {
+        if self.samples.len() < 2 {
+            return 0.0;
+        }
+        let n = self.samples.len() as f32;
+        let t0 = self.samples[0].0;
+        let mean_t = self.samples.iter().map(|&(t, _)| t - t0).sum::<f32>() / n;
+        let mean_p = self.samples.iter().map(|&(_, p)| p).sum::<f32>() / n;
+        let (mut cov, mut var) = (0.0, 0.0);
+        for &(t, p) in &self.samples {
+            let dt = t - t0 - mean_t;
+            cov += dt * (p - mean_p);
+            var += dt * dt;
+        }
+        if var <= f32::EPSILON {
+            0.0
+        } else {
+            cov / var
+        }
+    }
+}
+
+/// Derived channel that estimates each car's speed along the lap from position history
+pub struct CarMotion {
+    cars: Vec<PositionHistory>,
+}
+
+impl CarMotion {
+    pub fn new() -> Self {
+        Self {
+            cars: Vec::new(),
+        }
+    }
+}
+
+impl DerivedChannel for CarMotion {
+    fn name(&self) -> &'static str {
+        "car_motion"
+    }
+
+    fn compute(&mut self, data: &mut TelemetryData) {
+        let Some(positions) = data.CarIdxLapDistPct.as_ref() else {
+            return;
+        };
+
+        // Session change or replay jump; old history no longer applies
+        if data.race_clock.session_reset || data.race_clock.jumped {
+            self.cars.iter_mut().for_each(PositionHistory::clear);
+        }
+        // Repeated frame (sim paused or not yet updated); keep the previous fit
+        let is_new_sample = data.race_clock.dt > 0.0;
+
+        if self.cars.len() < positions.len() {
+            self.cars.resize_with(positions.len(), PositionHistory::default);
+        }
+
+        let mut rates = Vec::with_capacity(positions.len());
+        for (history, &pct) in self.cars.iter_mut().zip(positions.iter()) {
+            // Cars not in the world report a negative lap distance
+            if pct < 0.0 {
+                history.clear();
+                rates.push(0.0);
+                continue;
+            }
+            if is_new_sample {
+                history.push(data.race_clock.time as f32, pct);
+            }
+            rates.push(history.rate());
+        }
+
+        data.car_motion = Some(CarMotionHints {
+            session_time: data.SessionTime,
+            lap_dist_pct_per_sec: rates,
+        });
+    }
+}
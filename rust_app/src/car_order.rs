@@ -0,0 +1,141 @@
+use crate::derived::DerivedChannel;
+use crate::session_yaml;
+use crate::telemetry_fields::TelemetryData;
+use serde::{Serialize, Deserialize};
+use std::collections::BTreeMap;
+
+/// Cars of one class in class position order
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ClassOrder {
+    pub class_id: i32,
+    pub name: String,
+    pub color: String,
+    /// CarIdx values sorted by class position
+    pub car_idxs: Vec<i32>,
+}
+
+/// Running order computed once per frame so clients don't have to sort
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct CarOrder {
+    /// CarIdx values sorted by overall position
+    pub overall: Vec<i32>,
+    /// Classes ordered by their fastest car
+    pub classes: Vec<ClassOrder>,
+}
+
+#[derive(Clone, Default)]
+struct ClassInfo {
+    name: String,
+    color: String,
+}
+
+/// Derived channel that resolves class names and colors per car and sorts the field
+pub struct CarOrdering {
+    last_session_info: String,
+    classes: BTreeMap<i32, ClassInfo>,
+    class_names: Vec<String>,
+    class_colors: Vec<String>,
+}
+
+impl CarOrdering {
+    pub fn new() -> Self {
+        Self {
+            last_session_info: String::new(),
+            classes: BTreeMap::new(),
+            class_names: Vec::new(),
+            class_colors: Vec::new(),
+        }
+    }
+
+    // Class metadata only changes with the session info, so resolve it then
+    fn update_classes(&mut self, data: &TelemetryData) {
+        if data.session_info == self.last_session_info {
+            return;
+        }
+        self.last_session_info = data.session_info.clone();
+
+        let drivers = session_yaml::drivers(&data.session_info);
+        let car_count = drivers.iter().map(|d| d.car_idx + 1).max().unwrap_or(0).max(0) as usize;
+        self.classes.clear();
+        self.class_names = vec![String::new(); car_count];
+        self.class_colors = vec![String::new(); car_count];
+        for driver in drivers {
+            let Ok(idx) = usize::try_from(driver.car_idx) else {
+                continue;
+            };
+            self.class_names[idx] = driver.car_class_short_name.clone();
+            self.class_colors[idx] = driver.car_class_color.clone();
+            self.classes.entry(driver.car_class_id).or_insert(ClassInfo {
+                name: driver.car_class_short_name,
+                color: driver.car_class_color,
+            });
+        }
+    }
+}
+
+impl DerivedChannel for CarOrdering {
+    fn name(&self) -> &'static str {
+        "car_order"
+    }
+
+    fn dependencies(&self) -> &'static [&'static str] {
+        // Positions are filled in by the gap calculator
+        &["gaps"]
+    }
+
+    fn compute(&mut self, data: &mut TelemetryData) {
+        self.update_classes(data);
+        if !self.class_names.is_empty() {
+            data.CarIdxClassName = Some(self.class_names.clone());
+            data.CarIdxClassColor = Some(self.class_colors.clone());
+        }
+
+        let Some(positions) = data.CarIdxPosition.as_ref() else {
+            return;
+        };
+        // Cars without a position (spectators, empty slots) are left out
+        let mut overall: Vec<(i32, i32)> = positions
+            .iter()
+            .enumerate()
+            .filter(|(_, position)| **position > 0)
+            .map(|(idx, &position)| (position, idx as i32))
+            .collect();
+        overall.sort();
+
+        let class_ids = data.CarIdxClass.as_ref();
+        let class_positions = data.CarIdxClassPosition.as_ref();
+        let mut by_class: BTreeMap<i32, Vec<(i32, i32, i32)>> = BTreeMap::new();
+        for &(position, idx) in &overall {
+            let class_id = class_ids.and_then(|c| c.get(idx as usize)).copied().unwrap_or(0);
+            let class_position = class_positions
+                .and_then(|c| c.get(idx as usize))
+                .copied()
+                .filter(|&p| p > 0)
+                .unwrap_or(position);
+            by_class.entry(class_id).or_default().push((class_position, position, idx));
+        }
+
+        let mut classes: Vec<ClassOrder> = by_class
+            .into_iter()
+            .map(|(class_id, mut cars)| {
+                cars.sort();
+                let info = self.classes.get(&class_id).cloned().unwrap_or_default();
+                ClassOrder {
+                    class_id,
+                    name: info.name,
+                    color: info.color,
+                    car_idxs: cars.into_iter().map(|(_, _, idx)| idx).collect(),
+                }
+            })
+            .collect();
+        // Fastest class first, i.e. the class whose leader is highest overall
+        classes.sort_by_key(|class| {
+            class.car_idxs.first().map_or(i32::MAX, |&idx| positions[idx as usize])
+        });
+
+        data.car_order = Some(CarOrder {
+            overall: overall.into_iter().map(|(_, idx)| idx).collect(),
+            classes,
+        });
+    }
+}
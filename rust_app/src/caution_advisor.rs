@@ -0,0 +1,145 @@
+use crate::derived::DerivedChannel;
+use crate::events;
+use crate::pit_stops::PitTracker;
+use crate::telemetry_fields::{TelemetryData, FLAG_CAUTION, FLAG_CAUTION_WAVING};
+use serde_json::json;
+
+// Under caution the field runs at roughly this fraction of race pace, so a stop costs
+// this fraction of the green flag pit loss in track position
+const CAUTION_PIT_LOSS_FACTOR: f32 = 0.4;
+// Cars that stopped this recently are unlikely to come in again
+const RECENT_STOP_LAPS: i32 = 5;
+// SessionLapsRemainEx value for sessions without a lap limit
+const UNLIMITED_LAPS: i32 = 32767;
+
+/// Derived channel that emits a one-shot `caution_advice` event when a caution comes out
+pub struct CautionAdvisor {
+    pits: PitTracker,
+    caution_active: bool,
+}
+
+impl CautionAdvisor {
+    pub fn new(default_pit_loss: f32) -> Self {
+        Self {
+            pits: PitTracker::new(default_pit_loss),
+            caution_active: false,
+        }
+    }
+
+    fn likely_to_pit(&self, car_idx: usize, laps_completed: i32) -> bool {
+        match self.pits.car(car_idx).and_then(|car| car.laps_since_stop(laps_completed)) {
+            Some(laps) => laps >= RECENT_STOP_LAPS,
+            // Hasn't stopped yet this session
+            None => laps_completed >= RECENT_STOP_LAPS,
+        }
+    }
+
+    fn advise(&self, data: &TelemetryData) -> Option<serde_json::Value> {
+        let gaps = data.CarIdxGapToLeader.as_ref()?;
+        let lap_dist = data.CarIdxLapDistPct.as_ref()?;
+        let laps = data.CarIdxLapCompleted.as_ref()?;
+        let me = usize::try_from(data.player_car_idx).ok()?;
+        let my_gap = *gaps.get(me)?;
+        let my_laps = *laps.get(me)?;
+
+        let pit_loss = self.pits.pit_loss();
+        let caution_loss = pit_loss * CAUTION_PIT_LOSS_FACTOR;
+
+        let mut lost_if_pit_now = 0;
+        let mut lost_if_pit_under_green = 0;
+        let mut rivals_ahead_likely_to_pit = 0;
+        let mut rivals_behind_likely_to_pit = 0;
+        for (idx, &pct) in lap_dist.iter().enumerate() {
+            // Skip ourselves and cars not in the world
+            if idx == me || pct < 0.0 {
+                continue;
+            }
+            let (Some(&gap), Some(&car_laps)) = (gaps.get(idx), laps.get(idx)) else {
+                continue;
+            };
+            let likely_to_pit = self.likely_to_pit(idx, car_laps);
+            let behind_by = gap - my_gap;
+            if behind_by < 0.0 {
+                rivals_ahead_likely_to_pit += likely_to_pit as i32;
+                continue;
+            }
+            rivals_behind_likely_to_pit += likely_to_pit as i32;
+            // Cars that pit with us keep their place behind us
+            if behind_by < caution_loss && !likely_to_pit {
+                lost_if_pit_now += 1;
+            }
+            if behind_by < pit_loss {
+                lost_if_pit_under_green += 1;
+            }
+        }
+
+        // Race distance left in laps, from the lap limit or the time remaining
+        let pace = self.pits.car(me).and_then(|car| car.pace()).unwrap_or(data.last_lap_time);
+        let laps_remaining = if data.session_laps_remain < UNLIMITED_LAPS {
+            Some(data.session_laps_remain as f32)
+        } else if pace > 0.0 && data.session_time_remain > 0.0 {
+            Some(data.session_time_remain / pace)
+        } else {
+            None
+        };
+        let fuel_laps = (data.fuel_per_lap > 0.0).then(|| data.fuel_level / data.fuel_per_lap);
+        let must_pit_for_fuel = match (fuel_laps, laps_remaining) {
+            (Some(fuel), Some(remaining)) => Some(fuel < remaining),
+            _ => None,
+        };
+
+        // Lapped cars that stay out get waved around to regain their lap
+        let leader_laps = laps.iter().zip(lap_dist.iter()).filter(|(_, pct)| **pct >= 0.0).map(|(l, _)| *l).max()?;
+        let wave_around_likely = my_laps < leader_laps;
+
+        let (recommendation, reason) = if wave_around_likely && fuel_laps.is_none_or(|laps| laps >= 2.0) {
+            ("stay_out", "Staying out keeps the wave around to get the lap back")
+        } else if must_pit_for_fuel == Some(true) && lost_if_pit_now <= lost_if_pit_under_green {
+            ("pit", "A stop is needed to finish and costs fewer places now than under green")
+        } else if must_pit_for_fuel == Some(true) {
+            ("stay_out", "A stop is needed but costs more places now than under green")
+        } else if must_pit_for_fuel == Some(false) {
+            ("stay_out", "Enough fuel to finish; pitting only gives up track position")
+        } else {
+            ("stay_out", "Fuel or race distance unknown; no stop needed to keep position")
+        };
+
+        Some(json!({
+            "recommendation": recommendation,
+            "reason": reason,
+            "position": data.position,
+            "positions_lost_if_pit_now": lost_if_pit_now,
+            "positions_lost_if_pit_under_green": lost_if_pit_under_green,
+            "rivals_ahead_likely_to_pit": rivals_ahead_likely_to_pit,
+            "rivals_behind_likely_to_pit": rivals_behind_likely_to_pit,
+            "pit_loss": pit_loss,
+            "pit_loss_under_caution": caution_loss,
+            "fuel_laps_remaining": fuel_laps,
+            "race_laps_remaining": laps_remaining,
+            "must_pit_for_fuel": must_pit_for_fuel,
+            "wave_around_likely": wave_around_likely,
+        }))
+    }
+}
+
+impl DerivedChannel for CautionAdvisor {
+    fn name(&self) -> &'static str {
+        "caution_advice"
+    }
+
+    fn dependencies(&self) -> &'static [&'static str] {
+        &["gaps", "fuel_per_lap"]
+    }
+
+    fn compute(&mut self, data: &mut TelemetryData) {
+        self.pits.update(data);
+
+        let caution = data.session_flags & (FLAG_CAUTION | FLAG_CAUTION_WAVING) != 0;
+        let caution_started = caution && !self.caution_active;
+        self.caution_active = caution;
+
+        if caution_started && let Some(advice) = self.advise(data) {
+            events::emit(data, "caution_advice", advice);
+        }
+    }
+}
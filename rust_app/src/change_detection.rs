@@ -0,0 +1,225 @@
+use crate::derived::DerivedChannel;
+use crate::events;
+use crate::telemetry_fields::TelemetryData;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+/// Per-channel dead-bands used to decide whether a value has really changed.
+///
+/// A numeric channel only counts as changed once it moves further than its dead-band away
+/// from the last value that was reported, so sensor noise doesn't register as a change.
+/// The bands apply to delta frames (see delta.rs) and to the `channel_change` events
+/// raised for the channels listed under `events`. Loaded from a TOML table such as:
+///
+/// ```toml
+/// default = 0.0
+/// events = ["fuel_level", "engine_warnings.raw_value"]
+///
+/// [channels]
+/// speed_kph = 0.05
+/// rpm = 5.0
+/// CarIdxLapDistPct = 0.0001
+/// ```
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct DeadBands {
+    /// Dead-band for numeric channels without their own entry
+    pub default: f64,
+    /// Dead-bands keyed by channel name (nested fields use dotted names, e.g. "engine_warnings.raw_value")
+    pub channels: HashMap<String, f64>,
+    /// Channels that raise a `channel_change` event when they move beyond their dead-band
+    pub events: Vec<String>,
+}
+
+impl DeadBands {
+    /// Load dead-bands from a TOML file
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let content = fs::read_to_string(path)?;
+        let bands: DeadBands = toml::from_str(&content)?;
+        Ok(bands)
+    }
+
+    /// Dead-band for a channel
+    pub fn band_for(&self, channel: &str) -> f64 {
+        self.channels.get(channel).copied().unwrap_or(self.default)
+    }
+
+    /// Whether two values of a channel differ by more than its dead-band
+    pub fn differs(&self, channel: &str, old: &Value, new: &Value) -> bool {
+        values_differ(old, new, self.band_for(channel))
+    }
+}
+
+// Compare two JSON values, treating numbers within `band` of each other as equal
+fn values_differ(old: &Value, new: &Value, band: f64) -> bool {
+    match (old, new) {
+        (Value::Number(a), Value::Number(b)) => match (a.as_f64(), b.as_f64()) {
+            (Some(a), Some(b)) => (a - b).abs() > band,
+            _ => a != b,
+        },
+        (Value::Array(a), Value::Array(b)) => {
+            a.len() != b.len() || a.iter().zip(b.iter()).any(|(x, y)| values_differ(x, y, band))
+        },
+        _ => old != new,
+    }
+}
+
+/// Tracks the last reported value of every channel and reports which ones changed
+#[derive(Default)]
+pub struct ChangeDetector {
+    bands: DeadBands,
+    last_reported: HashMap<String, Value>,
+}
+
+impl ChangeDetector {
+    pub fn new(bands: DeadBands) -> Self {
+        Self {
+            bands,
+            last_reported: HashMap::new(),
+        }
+    }
+
+    /// Compare a serialized frame against the last reported values.
+    ///
+    /// Returns the names of the channels that moved beyond their dead-band and remembers
+    /// their new values. Channels that stayed inside their band keep their old reference
+    /// value, so slow drifts still get reported once they add up.
+    pub fn update(&mut self, frame: &Value) -> Vec<String> {
+        let mut changed = Vec::new();
+        if let Value::Object(map) = frame {
+            self.update_object(map, "", &mut changed);
+        }
+        changed
+    }
+
+    /// Like `update`, with the new value of each changed channel
+    pub fn update_values(&mut self, frame: &Value) -> Map<String, Value> {
+        self.update(frame)
+            .into_iter()
+            .map(|channel| {
+                let value = self.last_reported[&channel].clone();
+                (channel, value)
+            })
+            .collect()
+    }
+
+    /// Forget the channels missing from a frame and return their names
+    pub fn remove_missing(&mut self, frame: &Value) -> Vec<String> {
+        let mut present = HashSet::new();
+        if let Value::Object(map) = frame {
+            collect_channels(map, "", &mut present);
+        }
+        let mut removed: Vec<String> = self.last_reported.keys().filter(|channel| !present.contains(*channel)).cloned().collect();
+        for channel in &removed {
+            self.last_reported.remove(channel);
+        }
+        removed.sort();
+        removed
+    }
+
+    /// Forget all reference values so the next frame reports every channel
+    pub fn reset(&mut self) {
+        self.last_reported.clear();
+    }
+
+    fn update_object(&mut self, map: &Map<String, Value>, prefix: &str, changed: &mut Vec<String>) {
+        for (key, value) in map {
+            let channel = if prefix.is_empty() {
+                key.clone()
+            } else {
+                format!("{}.{}", prefix, key)
+            };
+
+            if let Value::Object(child) = value {
+                self.update_object(child, &channel, changed);
+                continue;
+            }
+
+            let is_changed = match self.last_reported.get(&channel) {
+                Some(old) => self.bands.differs(&channel, old, value),
+                None => true,
+            };
+
+            if is_changed {
+                self.last_reported.insert(channel.clone(), value.clone());
+                changed.push(channel);
+            }
+        }
+    }
+}
+
+// Dotted names of the leaf channels in a frame, as `update` names them
+fn collect_channels(map: &Map<String, Value>, prefix: &str, channels: &mut HashSet<String>) {
+    for (key, value) in map {
+        let channel = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+        match value {
+            Value::Object(child) => collect_channels(child, &channel, channels),
+            _ => {
+                channels.insert(channel);
+            },
+        }
+    }
+}
+
+/// Derived channel raising a `channel_change` event, `{"channel":..., "previous":...,
+/// "value":...}`, whenever a channel listed under `events` in the dead-bands moves beyond
+/// its dead-band. Register it last so it sees the other derived channels' values
+pub struct ChannelChangeEvents {
+    bands: DeadBands,
+    last_reported: HashMap<String, Value>,
+}
+
+impl ChannelChangeEvents {
+    pub fn new(bands: DeadBands) -> Self {
+        Self {
+            bands,
+            last_reported: HashMap::new(),
+        }
+    }
+}
+
+impl DerivedChannel for ChannelChangeEvents {
+    fn name(&self) -> &'static str {
+        "channel_changes"
+    }
+
+    fn compute(&mut self, data: &mut TelemetryData) {
+        if data.race_clock.session_reset {
+            self.last_reported.clear();
+        }
+        // Serializing the frame isn't free; skip it unless events are configured
+        if self.bands.events.is_empty() {
+            return;
+        }
+        let Ok(frame) = serde_json::to_value(&*data) else {
+            return;
+        };
+
+        let mut changes = Vec::new();
+        for channel in &self.bands.events {
+            let Some(value) = channel.split('.').try_fold(&frame, |value, key| value.get(key)) else {
+                continue;
+            };
+            // The first value seen is the reference, not a change
+            let previous = self.last_reported.get(channel);
+            if previous.is_some_and(|previous| !self.bands.differs(channel, previous, value)) {
+                continue;
+            }
+            if let Some(previous) = previous {
+                changes.push(json!({ "channel": channel, "previous": previous, "value": value }));
+            }
+            self.last_reported.insert(channel.clone(), value.clone());
+        }
+        for change in changes {
+            events::emit(data, "channel_change", change);
+        }
+    }
+}
@@ -0,0 +1,185 @@
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+/// One output channel of a preset
+#[derive(Clone, Debug, Deserialize)]
+pub struct ChannelMapping {
+    /// Frame field, dotted for nested fields and array items (e.g. "tire_temps_c.0")
+    pub source: String,
+    /// Name written to the output
+    pub target: String,
+    #[serde(default = "unit_scale")]
+    pub scale: f64,
+    #[serde(default)]
+    pub offset: f64,
+}
+
+fn unit_scale() -> f64 {
+    1.0
+}
+
+/// Renames and scales frame channels into the layout a third-party tool expects.
+///
+/// Clients pick one with `{"type":"set_channel_preset","preset":"simhub"}`; frames for that
+/// client then carry only the mapped channels (plus the rest, if `include_unmapped`).
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ChannelPreset {
+    pub channels: Vec<ChannelMapping>,
+    /// Keep the frame's own fields next to the mapped ones
+    pub include_unmapped: bool,
+}
+
+/// Built-in presets plus any loaded from a TOML file such as:
+///
+/// ```toml
+/// [presets.my_dash]
+/// include_unmapped = false
+/// channels = [
+///     { source = "speed_kph", target = "Speed" },
+///     { source = "throttle_pct", target = "Throttle", scale = 0.01 },
+/// ]
+/// ```
+#[derive(Clone, Debug, Deserialize)]
+pub struct ChannelPresets {
+    #[serde(default)]
+    pub presets: HashMap<String, ChannelPreset>,
+}
+
+// (source, target, scale) for the built-in presets
+const SIMHUB: &[(&str, &str, f64)] = &[
+    ("speed_kph", "SpeedKmh", 1.0),
+    ("rpm", "Rpms", 1.0),
+    ("gear", "Gear", 1.0),
+    ("throttle_pct", "Throttle", 1.0),
+    ("brake_pct", "Brake", 1.0),
+    ("clutch_pct", "Clutch", 1.0),
+    ("fuel_level", "Fuel", 1.0),
+    ("position", "Position", 1.0),
+    ("lap_completed", "CompletedLaps", 1.0),
+    ("current_lap_time", "CurrentLapTime", 1.0),
+    ("last_lap_time", "LastLapTime", 1.0),
+    ("best_lap_time", "BestLapTime", 1.0),
+    ("delta_best", "DeltaToSessionBest", 1.0),
+    ("water_temp_c", "WaterTemperature", 1.0),
+    ("oil_temp_c", "OilTemperature", 1.0),
+    ("tire_temps_c.0", "TyreTemperatureFrontLeft", 1.0),
+    ("tire_temps_c.1", "TyreTemperatureFrontRight", 1.0),
+    ("tire_temps_c.2", "TyreTemperatureRearLeft", 1.0),
+    ("tire_temps_c.3", "TyreTemperatureRearRight", 1.0),
+];
+
+// Garage61 works with SDK channel names and 0..1 pedal inputs
+const GARAGE61: &[(&str, &str, f64)] = &[
+    ("SessionTime", "SessionTime", 1.0),
+    ("speed_kph", "Speed", 1.0 / 3.6),
+    ("rpm", "RPM", 1.0),
+    ("gear_num", "Gear", 1.0),
+    ("throttle_pct", "Throttle", 0.01),
+    ("brake_pct", "Brake", 0.01),
+    ("clutch_pct", "Clutch", 0.01),
+    ("steering_angle_deg", "SteeringWheelAngle", std::f64::consts::PI / 180.0),
+    ("lap_dist_pct", "LapDistPct", 1.0),
+    ("lap_dist", "LapDist", 1.0),
+    ("lat", "Lat", 1.0),
+    ("lon", "Lon", 1.0),
+    ("fuel_level", "FuelLevel", 1.0),
+    ("lap_completed", "LapCompleted", 1.0),
+];
+
+const RACELABS: &[(&str, &str, f64)] = &[
+    ("speed_kph", "speed", 1.0 / 3.6),
+    ("rpm", "rpm", 1.0),
+    ("gear_num", "gear", 1.0),
+    ("throttle_pct", "throttle", 0.01),
+    ("brake_pct", "brake", 0.01),
+    ("fuel_level", "fuelLevel", 1.0),
+    ("fuel_per_lap", "fuelPerLap", 1.0),
+    ("position", "position", 1.0),
+    ("delta_best", "deltaBest", 1.0),
+    ("session_time_remain", "sessionTimeRemain", 1.0),
+    ("CarIdxLapDistPct", "carIdxLapDistPct", 1.0),
+    ("CarIdxPosition", "carIdxPosition", 1.0),
+];
+
+impl Default for ChannelPresets {
+    fn default() -> Self {
+        let built_in = |channels: &[(&str, &str, f64)]| ChannelPreset {
+            channels: channels
+                .iter()
+                .map(|&(source, target, scale)| ChannelMapping {
+                    source: source.to_string(),
+                    target: target.to_string(),
+                    scale,
+                    offset: 0.0,
+                })
+                .collect(),
+            include_unmapped: false,
+        };
+        let mut presets = HashMap::new();
+        presets.insert("simhub".to_string(), built_in(SIMHUB));
+        presets.insert("garage61".to_string(), built_in(GARAGE61));
+        presets.insert("racelabs".to_string(), built_in(RACELABS));
+        Self { presets }
+    }
+}
+
+impl ChannelPresets {
+    /// Built-in presets plus those in a TOML file; file presets replace built-ins of the same name
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let content = fs::read_to_string(path)?;
+        let loaded: ChannelPresets = toml::from_str(&content)?;
+        let mut presets = Self::default();
+        presets.presets.extend(loaded.presets);
+        Ok(presets)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ChannelPreset> {
+        self.presets.get(name)
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.presets.keys().cloned().collect();
+        names.sort();
+        names
+    }
+}
+
+// Look up a dotted path; numeric segments index arrays
+fn lookup<'a>(frame: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.').try_fold(frame, |value, segment| match value {
+        Value::Array(items) => items.get(segment.parse::<usize>().ok()?),
+        _ => value.get(segment),
+    })
+}
+
+fn scaled(value: &Value, scale: f64, offset: f64) -> Value {
+    match value {
+        Value::Number(n) if scale != 1.0 || offset != 0.0 => n
+            .as_f64()
+            .map(|n| serde_json::json!(n * scale + offset))
+            .unwrap_or_else(|| value.clone()),
+        Value::Array(items) => Value::Array(items.iter().map(|item| scaled(item, scale, offset)).collect()),
+        _ => value.clone(),
+    }
+}
+
+impl ChannelPreset {
+    /// Frame as the target tool expects it; channels missing from the frame are left out
+    pub fn apply(&self, frame: &Value) -> Value {
+        let mut output = match (self.include_unmapped, frame) {
+            (true, Value::Object(fields)) => fields.clone(),
+            _ => Map::new(),
+        };
+        for mapping in &self.channels {
+            if let Some(value) = lookup(frame, &mapping.source) {
+                output.insert(mapping.target.clone(), scaled(value, mapping.scale, mapping.offset));
+            }
+        }
+        Value::Object(output)
+    }
+}
@@ -0,0 +1,209 @@
+//! Command line: subcommands and the main server options.
+//!
+//! `speedforge [serve] [options]` runs the server and `speedforge record [options]` runs
+//! it recording from the start; the other subcommands are the offline tools. The server's
+//! common options are declared here so `--help` lists them and bad values are refused up
+//! front. Every other server option (see the `// --flag` notes in main.rs) is passed
+//! through as given and read where it is used, the same way options from the config file
+//! are; names that aren't in `SERVER_OPTIONS` are refused, so a typo doesn't go unnoticed.
+
+use crate::{determinism, diagnostics, lap_diff, preflight, replay, retention, scenario, session_bundle, session_compare, setup_wizard, storage, version};
+use clap::error::ErrorKind;
+use clap::{Args, CommandFactory, Parser, Subcommand};
+use std::path::PathBuf;
+
+#[derive(Parser)]
+#[command(name = "speedforge", about = "Sim racing telemetry server", args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Print the version and build details
+    #[arg(short = 'V', long)]
+    version: bool,
+    /// With --version, print them as JSON
+    #[arg(long, requires = "version")]
+    json: bool,
+    #[command(flatten)]
+    serve: ServeArgs,
+}
+
+/// Server options read outside this file, as passed through in `ServeArgs::options`
+pub const SERVER_OPTIONS: &[&str] = &[
+    "--allow", "--allow-origin", "--api-token", "--auto-export", "--bind", "--car-assets",
+    "--channel-presets", "--check-updates", "--config", "--data-api-email", "--deadbands",
+    "--drive-time-max-pct", "--drive-time-min-pct", "--drive-time-warning-min", "--driver-db",
+    "--export-dir", "--finish-sim", "--finish-sim-interval", "--finish-sim-iterations",
+    "--float-precision", "--gap-history-laps", "--handshake-timeout-ms", "--highlights-dir",
+    "--hotkeys", "--ibt-dir", "--instance", "--keep-best-laps", "--log-dir", "--log-keep",
+    "--log-max-mb", "--log-rotate", "--marker-buttons", "--marker-debounce-ms",
+    "--max-connections-per-ip", "--max-message-kb", "--max-messages-per-sec",
+    "--no-load-shedding", "--no-log-file", "--no-rounding", "--no-session-dump",
+    "--otlp-endpoint", "--otlp-sample", "--pit-loss", "--port", "--priority-config",
+    "--profiles-dir", "--record", "--record-max-mb", "--replay", "--replay-bookmarks",
+    "--replay-once", "--replay-speed", "--retention-max-age-days", "--retention-max-size-mb",
+    "--runtime-cores", "--runtime-workers", "--s3-endpoint", "--s3-region", "--scenario",
+    "--sessions-dir", "--sim", "--string-channels", "--telemetry-cores", "--telemetry-priority",
+    "--tick-rate", "--upload-url", "--upload-user", "--verbose",
+];
+
+/// The first option in `args` no part of the server reads, if any
+pub fn unknown_option(args: &[String]) -> Option<&str> {
+    args.iter()
+        .filter(|arg| arg.starts_with("--"))
+        .map(|arg| arg.split_once('=').map_or(arg.as_str(), |(name, _)| name))
+        .find(|name| !SERVER_OPTIONS.contains(name))
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run the telemetry server (the default)
+    Serve(ServeArgs),
+    /// Run the server, recording to the sessions folder from the start
+    Record(ServeArgs),
+    /// Run the server playing back a recording instead of reading the sim
+    Replay(ToolArgs),
+    /// Compare two recordings lap by lap
+    Compare(ToolArgs),
+    /// Compare two laps of a recording channel by channel, aligned on distance
+    #[command(name = "lapdiff")]
+    LapDiff(ToolArgs),
+    /// Pack a recording into a shareable bundle
+    ExportSession(ToolArgs),
+    /// Turn a bundle back into a recording
+    ImportSession(ToolArgs),
+    /// Upload recordings or exports to a folder, WebDAV or S3
+    Upload(ToolArgs),
+    /// Apply the retention policy to the sessions folder
+    Prune(ToolArgs),
+    /// Run a scenario through the derived channels and check its expected events
+    RunScenario(ToolArgs),
+    /// Run a recording through the derived channels reproducibly, for regression tests
+    Analyze(ToolArgs),
+    /// Write a config file interactively
+    Init(ToolArgs),
+    /// Validate the setup without connecting to the sim
+    Check(ToolArgs),
+    /// Write a zip with logs, redacted config and system info for bug reports
+    Diagnose(ToolArgs),
+}
+
+/// Arguments handed to a tool as given
+#[derive(Args)]
+struct ToolArgs {
+    #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+    args: Vec<String>,
+}
+
+/// Common server options
+#[derive(Args)]
+struct ServeArgs {
+    /// Config file, defaults to ./speedforge.toml when present
+    #[arg(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+    /// WebSocket and HTTP port [default: 8080]
+    #[arg(long)]
+    port: Option<u16>,
+    /// Addresses to listen on, comma separated [default: 0.0.0.0]
+    #[arg(long, value_name = "ADDR,...")]
+    bind: Option<String>,
+    /// iRacing samples per second [default: 20]
+    #[arg(long, alias = "tick-rate", value_name = "HZ")]
+    rate: Option<f32>,
+    /// Don't log or keep copies of the session info YAML
+    #[arg(long)]
+    no_session_dump: bool,
+    /// Verbose logging
+    #[arg(short, long)]
+    verbose: bool,
+    /// Further server options, e.g. --sessions-dir <dir> --finish-sim
+    #[arg(trailing_var_arg = true, allow_hyphen_values = true, value_name = "OPTIONS")]
+    options: Vec<String>,
+}
+
+impl ServeArgs {
+    // Back to the flag list the server reads options from
+    fn into_args(self, program: String) -> Vec<String> {
+        let mut args = vec![program];
+        if let Some(config) = self.config {
+            args.push("--config".to_string());
+            args.push(config.to_string_lossy().to_string());
+        }
+        if let Some(port) = self.port {
+            args.push("--port".to_string());
+            args.push(port.to_string());
+        }
+        if let Some(bind) = self.bind {
+            args.push("--bind".to_string());
+            args.push(bind);
+        }
+        if let Some(rate) = self.rate {
+            args.push("--tick-rate".to_string());
+            args.push(rate.to_string());
+        }
+        if self.no_session_dump {
+            args.push("--no-session-dump".to_string());
+        }
+        if self.verbose {
+            args.push("--verbose".to_string());
+        }
+        args.extend(self.options);
+        args
+    }
+}
+
+/// What the command line asks for
+pub enum Invocation {
+    /// Run the server with these arguments (program name first)
+    Serve(Vec<String>),
+    /// A tool ran; exit with this code
+    Exit(i32),
+}
+
+/// Parse the command line, running a tool subcommand right away. `--help` and usage
+/// errors print and exit here
+pub fn parse(args: Vec<String>) -> Invocation {
+    let program = args.first().cloned().unwrap_or_else(|| "speedforge".to_string());
+    let cli = Cli::parse_from(args);
+    if cli.version {
+        println!("{}", version::version_text(cli.json));
+        return Invocation::Exit(0);
+    }
+    let code = match cli.command {
+        None => return serve(cli.serve.into_args(program)),
+        Some(Command::Serve(args)) => return serve(args.into_args(program)),
+        Some(Command::Record(args)) => {
+            let mut args = args.into_args(program);
+            args.push("--record".to_string());
+            return serve(args);
+        },
+        Some(Command::Replay(tool)) => match replay::server_args(&tool.args) {
+            Ok(args) => return serve(std::iter::once(program).chain(args).collect()),
+            Err(usage) => {
+                eprintln!("{}", usage);
+                2
+            },
+        },
+        Some(Command::Compare(tool)) => session_compare::run_cli(&tool.args),
+        Some(Command::LapDiff(tool)) => lap_diff::run_cli(&tool.args),
+        Some(Command::ExportSession(tool)) => session_bundle::run_export_cli(&tool.args),
+        Some(Command::ImportSession(tool)) => session_bundle::run_import_cli(&tool.args),
+        Some(Command::Upload(tool)) => storage::run_cli(&tool.args),
+        Some(Command::Prune(tool)) => retention::run_prune_cli(&tool.args),
+        Some(Command::RunScenario(tool)) => scenario::run_cli(&tool.args),
+        Some(Command::Analyze(tool)) => determinism::run_cli(&tool.args),
+        Some(Command::Init(tool)) => setup_wizard::run_cli(&tool.args),
+        Some(Command::Check(tool)) => preflight::run_cli(&tool.args),
+        Some(Command::Diagnose(tool)) => diagnostics::run_cli(&tool.args),
+    };
+    Invocation::Exit(code)
+}
+
+// Run the server, unless an option is misspelled
+fn serve(args: Vec<String>) -> Invocation {
+    if let Some(option) = unknown_option(&args[1..]) {
+        Cli::command()
+            .error(ErrorKind::UnknownArgument, format!("unknown option '{}'", option))
+            .exit();
+    }
+    Invocation::Serve(args)
+}
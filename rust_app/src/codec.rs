@@ -0,0 +1,184 @@
+//! Wire encodings for streamed messages.
+//!
+//! Every stream message (telemetry frames, events) is built once as a JSON value and then
+//! encoded with the codec each client negotiated in `hello` (`"encoding": "cbor"`) or in
+//! the connection URL (`/telemetry?encoding=msgpack`).
+//! Broadcasting keeps one `EncodedCache` per tick so each (preset, codec) combination is
+//! encoded once however many clients share it. A new encoding only needs a `Codec` impl
+//! and an entry in `CODECS`. JSON goes out as text messages, the others as binary.
+//! Request replies stay JSON whatever the encoding.
+
+use serde_json::Value;
+use std::collections::HashMap;
+use tokio_tungstenite::tungstenite::Message;
+
+pub trait Codec: Send + Sync {
+    /// Name clients ask for in `hello`
+    fn name(&self) -> &'static str;
+    fn encode(&self, value: &Value) -> Result<Message, String>;
+}
+
+pub struct JsonCodec;
+
+impl Codec for JsonCodec {
+    fn name(&self) -> &'static str {
+        "json"
+    }
+
+    fn encode(&self, value: &Value) -> Result<Message, String> {
+        Ok(Message::Text(value.to_string()))
+    }
+}
+
+/// CBOR (RFC 8949), the same structure as the JSON
+pub struct CborCodec;
+
+impl Codec for CborCodec {
+    fn name(&self) -> &'static str {
+        "cbor"
+    }
+
+    fn encode(&self, value: &Value) -> Result<Message, String> {
+        let mut bytes = Vec::new();
+        ciborium::into_writer(value, &mut bytes).map_err(|e| e.to_string())?;
+        Ok(Message::Binary(bytes))
+    }
+}
+
+/// MessagePack, the same structure as the JSON
+pub struct MessagePackCodec;
+
+impl Codec for MessagePackCodec {
+    fn name(&self) -> &'static str {
+        "msgpack"
+    }
+
+    fn encode(&self, value: &Value) -> Result<Message, String> {
+        rmp_serde::to_vec(value).map(Message::Binary).map_err(|e| e.to_string())
+    }
+}
+
+/// Protocol Buffers as the well-known `google.protobuf.Value` type, which any protobuf
+/// library can decode without a speedforge-specific schema
+pub struct ProtobufCodec;
+
+impl Codec for ProtobufCodec {
+    fn name(&self) -> &'static str {
+        "protobuf"
+    }
+
+    fn encode(&self, value: &Value) -> Result<Message, String> {
+        let mut bytes = Vec::new();
+        put_protobuf_value(&mut bytes, value);
+        Ok(Message::Binary(bytes))
+    }
+}
+
+/// Available codecs; the first is the default
+pub const CODECS: &[&dyn Codec] = &[&JsonCodec, &CborCodec, &MessagePackCodec, &ProtobufCodec];
+
+pub fn by_name(name: &str) -> Option<&'static dyn Codec> {
+    CODECS.iter().copied().find(|codec| codec.name() == name)
+}
+
+pub fn names() -> Vec<&'static str> {
+    CODECS.iter().map(|codec| codec.name()).collect()
+}
+
+/// Messages already encoded during one broadcast, by variant (e.g. the channel preset) and codec
+#[derive(Default)]
+pub struct EncodedCache {
+    messages: HashMap<(String, &'static str), Option<Message>>,
+}
+
+impl EncodedCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `variant` encoded with the named codec, building the value with `build` the first
+    /// time. None when the codec is unknown or encoding failed (reported once)
+    pub fn get(&mut self, variant: &str, codec: &str, build: impl FnOnce() -> Value) -> Option<Message> {
+        let codec = by_name(codec)?;
+        self.messages
+            .entry((variant.to_string(), codec.name()))
+            .or_insert_with(|| encode(codec, variant, &build()))
+            .clone()
+    }
+}
+
+/// Encode a message for one client only; None when encoding failed (reported)
+pub fn encode_once(codec: &str, label: &str, value: &Value) -> Option<Message> {
+    encode(by_name(codec)?, label, value)
+}
+
+fn encode(codec: &dyn Codec, label: &str, value: &Value) -> Option<Message> {
+    match codec.encode(value) {
+        Ok(message) => Some(message),
+        Err(e) => {
+            tracing::error!("Error encoding {} as {}: {}", label, codec.name(), e);
+            None
+        }
+    }
+}
+
+// Protobuf wire format for google.protobuf.Value / Struct / ListValue
+
+fn put_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+fn put_length_delimited(bytes: &mut Vec<u8>, field: u64, contents: &[u8]) {
+    put_varint(bytes, field << 3 | 2);
+    put_varint(bytes, contents.len() as u64);
+    bytes.extend_from_slice(contents);
+}
+
+fn put_protobuf_value(bytes: &mut Vec<u8>, value: &Value) {
+    match value {
+        // null_value = 1 (enum NULL_VALUE = 0)
+        Value::Null => {
+            put_varint(bytes, 1 << 3);
+            put_varint(bytes, 0);
+        },
+        // number_value = 2 (double)
+        Value::Number(number) => {
+            put_varint(bytes, 2 << 3 | 1);
+            bytes.extend_from_slice(&number.as_f64().unwrap_or_default().to_le_bytes());
+        },
+        // string_value = 3
+        Value::String(text) => put_length_delimited(bytes, 3, text.as_bytes()),
+        // bool_value = 4
+        Value::Bool(flag) => {
+            put_varint(bytes, 4 << 3);
+            put_varint(bytes, *flag as u64);
+        },
+        // struct_value = 5, a Struct with map<string, Value> fields = 1
+        Value::Object(fields) => {
+            let mut message = Vec::new();
+            for (key, value) in fields {
+                let mut entry = Vec::new();
+                put_length_delimited(&mut entry, 1, key.as_bytes());
+                let mut encoded = Vec::new();
+                put_protobuf_value(&mut encoded, value);
+                put_length_delimited(&mut entry, 2, &encoded);
+                put_length_delimited(&mut message, 1, &entry);
+            }
+            put_length_delimited(bytes, 5, &message);
+        },
+        // list_value = 6, a ListValue with repeated Value values = 1
+        Value::Array(values) => {
+            let mut message = Vec::new();
+            for value in values {
+                let mut encoded = Vec::new();
+                put_protobuf_value(&mut encoded, value);
+                put_length_delimited(&mut message, 1, &encoded);
+            }
+            put_length_delimited(bytes, 6, &message);
+        },
+    }
+}
@@ -0,0 +1,112 @@
+//! Settings file, so a rig's options don't have to be typed on every start.
+//!
+//! Each top-level key is a command line flag without the leading dashes:
+//!
+//! ```toml
+//! port = 8080
+//! bind = ["0.0.0.0", "::1"]
+//! tick-rate = 20
+//! verbose = false
+//! output-dir = "sessions"
+//! api-token = "..."
+//! finish-sim = true
+//! ```
+//!
+//! Underscores work as well as dashes (`tick_rate`), and a few keys have friendlier names
+//! than their flags (see `ALIASES`). `true` turns a switch on, `false` leaves it off and
+//! arrays are joined with commas; keys that aren't server options are refused. The file
+//! is read from `--config <file>`, or `speedforge.toml` in the working directory when
+//! present. Flags given on the command line win over the file. `speedforge init` writes
+//! one.
+
+use std::fs;
+use std::path::Path;
+use toml::Value;
+
+/// File read when no `--config` is given
+pub const DEFAULT_CONFIG_FILE: &str = "speedforge.toml";
+
+/// Config keys that stand for a differently named flag
+pub const ALIASES: &[(&str, &str)] = &[
+    ("output-dir", "sessions-dir"),
+    ("bind-address", "bind"),
+];
+
+// Flag name for a config key
+fn flag_name(key: &str) -> String {
+    let key = key.replace('_', "-");
+    match ALIASES.iter().find(|(alias, _)| *alias == key) {
+        Some((_, flag)) => format!("--{}", flag),
+        None => format!("--{}", key),
+    }
+}
+
+fn flag_value(key: &str, value: &Value) -> Result<Option<String>, String> {
+    Ok(Some(match value {
+        Value::String(text) => text.clone(),
+        Value::Integer(number) => number.to_string(),
+        Value::Float(number) => number.to_string(),
+        Value::Boolean(_) => return Ok(None),
+        Value::Array(items) => items
+            .iter()
+            .map(|item| match flag_value(key, item)? {
+                Some(text) => Ok(text),
+                None => Err(format!("'{}' can't hold a list of switches", key)),
+            })
+            .collect::<Result<Vec<_>, _>>()?
+            .join(","),
+        _ => return Err(format!("'{}' must be a string, number, switch or list", key)),
+    }))
+}
+
+/// The flags a config file stands for, e.g. `["--port", "8080", "--finish-sim"]`
+pub fn load_args(path: &Path) -> Result<Vec<String>, String> {
+    let content = fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    parse_args(&content).map_err(|e| format!("Invalid config {}: {}", path.display(), e))
+}
+
+/// The flags the contents of a config file stand for
+pub fn parse_args(content: &str) -> Result<Vec<String>, String> {
+    let table: toml::Table = toml::from_str(content).map_err(|e| e.to_string())?;
+    let mut args = Vec::new();
+    for (key, value) in &table {
+        let flag = flag_name(key);
+        if !crate::cli::SERVER_OPTIONS.contains(&flag.as_str()) {
+            return Err(format!("unknown setting '{}'", key));
+        }
+        match (value, flag_value(key, value)?) {
+            (Value::Boolean(true), _) => args.push(flag),
+            (_, Some(text)) => {
+                args.push(flag);
+                args.push(text);
+            },
+            _ => {},
+        }
+    }
+    Ok(args)
+}
+
+/// File named with `--config`, if any
+pub fn explicit_file(args: &[String]) -> Result<Option<String>, String> {
+    args.iter()
+        .enumerate()
+        .find_map(|(i, arg)| match arg.strip_prefix("--config") {
+            Some("") => Some(args.get(i + 1).cloned().ok_or_else(|| "--config needs a file".to_string())),
+            Some(rest) => rest.strip_prefix('=').map(|path| Ok(path.to_string())),
+            None => None,
+        })
+        .transpose()
+}
+
+/// Command line arguments followed by those from the config file, if any. Options are
+/// looked up by first occurrence, so the command line takes precedence
+pub fn with_config_file(mut args: Vec<String>) -> Result<Vec<String>, String> {
+    let path = match explicit_file(&args)? {
+        Some(path) => path,
+        None if Path::new(DEFAULT_CONFIG_FILE).exists() => DEFAULT_CONFIG_FILE.to_string(),
+        None => return Ok(args),
+    };
+    let file_args = load_args(Path::new(&path))?;
+    args.extend(file_args);
+    Ok(args)
+}
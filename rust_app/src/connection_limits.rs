@@ -0,0 +1,169 @@
+//! Protection against misbehaving or hostile clients.
+//!
+//! The server binds to all interfaces by default, so anything on the network can connect.
+//! These limits cap connections per IP address, give up on clients that don't finish the
+//! handshake in time, bound the size of inbound messages and how many a client may send
+//! per second, and optionally refuse addresses outside an allowlist.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_MAX_CONNECTIONS_PER_IP: usize = 16;
+pub const DEFAULT_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+pub const DEFAULT_MAX_MESSAGE_BYTES: usize = 64 * 1024;
+pub const DEFAULT_MAX_MESSAGES_PER_SEC: u32 = 50;
+/// Over-limit messages tolerated before the client is disconnected
+pub const MAX_RATE_VIOLATIONS: u32 = 100;
+
+/// An address or CIDR block, e.g. "192.168.1.0/24" or "::1"
+#[derive(Clone, Debug, PartialEq)]
+pub struct AllowedNet {
+    addr: IpAddr,
+    prefix: u32,
+}
+
+impl AllowedNet {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let (addr, prefix) = match text.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (text, None),
+        };
+        let addr: IpAddr = addr.trim().parse().map_err(|_| format!("Invalid address '{}'", text))?;
+        let max_prefix = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix.trim().parse().ok().filter(|&p| p <= max_prefix).ok_or_else(|| format!("Invalid prefix in '{}'", text))?,
+            None => max_prefix,
+        };
+        Ok(Self { addr, prefix })
+    }
+
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        // IPv4 clients of a dual-stack listener show up as ::ffff:a.b.c.d
+        let ip = match ip {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(ip),
+            v4 => v4,
+        };
+        match (self.addr, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix).unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            },
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix).unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            },
+            _ => false,
+        }
+    }
+}
+
+/// Limits applied to inbound connections
+#[derive(Clone, Debug)]
+pub struct ConnectionLimits {
+    pub max_connections_per_ip: usize,
+    pub handshake_timeout: Duration,
+    pub max_message_bytes: usize,
+    pub max_messages_per_sec: u32,
+    /// Only these addresses may connect; everyone when empty
+    pub allowlist: Vec<AllowedNet>,
+}
+
+impl Default for ConnectionLimits {
+    fn default() -> Self {
+        Self {
+            max_connections_per_ip: DEFAULT_MAX_CONNECTIONS_PER_IP,
+            handshake_timeout: DEFAULT_HANDSHAKE_TIMEOUT,
+            max_message_bytes: DEFAULT_MAX_MESSAGE_BYTES,
+            max_messages_per_sec: DEFAULT_MAX_MESSAGES_PER_SEC,
+            allowlist: Vec::new(),
+        }
+    }
+}
+
+impl ConnectionLimits {
+    /// Parse a comma separated allowlist such as "127.0.0.1,192.168.1.0/24"
+    pub fn parse_allowlist(list: &str) -> Result<Vec<AllowedNet>, String> {
+        list.split(',').map(str::trim).filter(|entry| !entry.is_empty()).map(AllowedNet::parse).collect()
+    }
+
+    pub fn is_allowed(&self, ip: IpAddr) -> bool {
+        self.allowlist.is_empty() || self.allowlist.iter().any(|net| net.contains(ip))
+    }
+}
+
+/// Open connections per IP address
+#[derive(Clone, Default)]
+pub struct ConnectionTracker {
+    counts: Arc<Mutex<HashMap<IpAddr, usize>>>,
+}
+
+/// Held for the lifetime of a connection; frees the slot when dropped
+pub struct ConnectionSlot {
+    tracker: ConnectionTracker,
+    ip: IpAddr,
+}
+
+impl ConnectionTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Take a slot for `ip`, or None when it already has `max` connections open
+    pub fn acquire(&self, ip: IpAddr, max: usize) -> Option<ConnectionSlot> {
+        let mut counts = self.counts.lock().unwrap();
+        let count = counts.entry(ip).or_insert(0);
+        if *count >= max {
+            return None;
+        }
+        *count += 1;
+        Some(ConnectionSlot { tracker: self.clone(), ip })
+    }
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        let mut counts = self.tracker.counts.lock().unwrap();
+        if let Some(count) = counts.get_mut(&self.ip) {
+            *count = count.saturating_sub(1);
+            if *count == 0 {
+                counts.remove(&self.ip);
+            }
+        }
+    }
+}
+
+/// Token bucket for inbound messages: a second's worth of burst, refilled continuously
+pub struct MessageRateLimiter {
+    rate: f64,
+    tokens: f64,
+    last: Instant,
+    /// Messages refused so far
+    pub violations: u32,
+}
+
+impl MessageRateLimiter {
+    pub fn new(per_second: u32) -> Self {
+        Self {
+            rate: per_second as f64,
+            tokens: per_second as f64,
+            last: Instant::now(),
+            violations: 0,
+        }
+    }
+
+    /// Whether another message may be processed now
+    pub fn allow(&mut self) -> bool {
+        let now = Instant::now();
+        self.tokens = (self.tokens + now.duration_since(self.last).as_secs_f64() * self.rate).min(self.rate);
+        self.last = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            self.violations += 1;
+            false
+        }
+    }
+}
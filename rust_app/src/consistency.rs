@@ -0,0 +1,229 @@
+use crate::derived::DerivedChannel;
+use crate::laps;
+use crate::telemetry_fields::TelemetryData;
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+
+// Laps slower than this multiple of the median are outliers (traffic, spins, cautions)
+const OUTLIER_FACTOR: f32 = 1.07;
+// Lap times kept per car
+const MAX_LAPS_PER_CAR: usize = 50;
+// Pedal travel rate (percent per second) at which the smoothness index drops to ~37
+const SMOOTHNESS_SCALE: f32 = 150.0;
+// Braking points closer than this (fraction of a lap) belong to the same corner
+const BRAKING_POINT_MATCH_PCT: f32 = 0.02;
+// Braking points kept per corner
+const MAX_BRAKING_POINTS: usize = 30;
+
+/// Smoothness index for a pedal from its total travel over a time span.
+///
+/// 100 means the pedal never moved; the index decays exponentially with the average
+/// travel rate, so jerky, sawing inputs score low.
+pub fn smoothness_index(total_travel_pct: f32, duration_s: f32) -> f32 {
+    if duration_s <= 0.0 {
+        return 0.0;
+    }
+    let rate = total_travel_pct / duration_s;
+    100.0 * (-rate / SMOOTHNESS_SCALE).exp()
+}
+
+/// Mean and standard deviation of lap times after removing outliers
+pub fn lap_time_spread(lap_times: &[f32]) -> Option<(f32, f32, usize)> {
+    let mut valid: Vec<f32> = lap_times.iter().copied().filter(|&t| t > 0.0).collect();
+    if valid.len() < 2 {
+        return None;
+    }
+    valid.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let median = valid[valid.len() / 2];
+    valid.retain(|&t| t <= median * OUTLIER_FACTOR);
+    if valid.len() < 2 {
+        return None;
+    }
+    let (mean, std_dev) = mean_and_std_dev(&valid);
+    Some((mean, std_dev, valid.len()))
+}
+
+fn mean_and_std_dev(values: &[f32]) -> (f32, f32) {
+    let count = values.len() as f32;
+    let mean = values.iter().sum::<f32>() / count;
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f32>() / count;
+    (mean, variance.sqrt())
+}
+
+/// Lap time consistency of one car (i.e. whoever is driving it)
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct CarConsistency {
+    pub car_idx: i32,
+    pub laps_counted: usize,
+    pub mean_lap_time: f32,
+    pub lap_time_std_dev: f32,
+}
+
+/// Spread of the player's braking point for one corner
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct BrakingPointSpread {
+    /// Average lap position of the braking point
+    pub lap_dist_pct: f32,
+    pub samples: usize,
+    pub std_dev_m: f32,
+}
+
+/// Consistency metrics for the player
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct PlayerConsistency {
+    pub laps_counted: usize,
+    pub mean_lap_time: f32,
+    pub lap_time_std_dev: f32,
+    pub throttle_smoothness: f32,
+    pub brake_smoothness: f32,
+    pub braking_points: Vec<BrakingPointSpread>,
+}
+
+/// Consistency metrics published whenever a lap is completed
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ConsistencyData {
+    pub player: PlayerConsistency,
+    pub cars: Vec<CarConsistency>,
+}
+
+/// Tracks lap time consistency for every car plus input smoothness and braking point
+/// variance for the player
+pub struct Consistency {
+    player_laps: Vec<f32>,
+    throttle_smoothness: Vec<f32>,
+    brake_smoothness: Vec<f32>,
+    braking_points: Vec<Vec<f32>>, // per corner, start pct of each braking zone
+    car_laps: HashMap<i32, Vec<f32>>,
+    last_car_lap_times: Vec<f32>,
+}
+
+impl Consistency {
+    pub fn new() -> Self {
+        Self {
+            player_laps: Vec::new(),
+            throttle_smoothness: Vec::new(),
+            brake_smoothness: Vec::new(),
+            braking_points: Vec::new(),
+            car_laps: HashMap::new(),
+            last_car_lap_times: Vec::new(),
+        }
+    }
+
+    fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    fn add_braking_point(&mut self, start_pct: f32) {
+        let corner = self.braking_points.iter_mut().find(|points| {
+            let mean = points.iter().sum::<f32>() / points.len() as f32;
+            laps::lap_pct_distance(mean, start_pct) <= BRAKING_POINT_MATCH_PCT
+        });
+        match corner {
+            Some(points) => {
+                points.push(start_pct);
+                if points.len() > MAX_BRAKING_POINTS {
+                    points.remove(0);
+                }
+            },
+            None => self.braking_points.push(vec![start_pct]),
+        }
+    }
+
+    // Record new lap times for all cars from CarIdxLastLapTime
+    fn update_cars(&mut self, data: &TelemetryData) -> bool {
+        let Some(last_lap_times) = &data.CarIdxLastLapTime else {
+            return false;
+        };
+        let mut changed = false;
+        for (car_idx, &lap_time) in last_lap_times.iter().enumerate() {
+            let previous = self.last_car_lap_times.get(car_idx).copied().unwrap_or(-1.0);
+            if lap_time > 0.0 && lap_time != previous {
+                let laps = self.car_laps.entry(car_idx as i32).or_default();
+                laps.push(lap_time);
+                if laps.len() > MAX_LAPS_PER_CAR {
+                    laps.remove(0);
+                }
+                changed = true;
+            }
+        }
+        self.last_car_lap_times = last_lap_times.clone();
+        changed
+    }
+
+    fn snapshot(&self, track_length_m: f32) -> ConsistencyData {
+        let mut player = PlayerConsistency::default();
+        if let Some((mean, std_dev, count)) = lap_time_spread(&self.player_laps) {
+            player.laps_counted = count;
+            player.mean_lap_time = mean;
+            player.lap_time_std_dev = std_dev;
+        }
+        if !self.throttle_smoothness.is_empty() {
+            player.throttle_smoothness = mean_and_std_dev(&self.throttle_smoothness).0;
+            player.brake_smoothness = mean_and_std_dev(&self.brake_smoothness).0;
+        }
+        player.braking_points = self.braking_points
+            .iter()
+            .filter(|points| points.len() >= 2)
+            .map(|points| {
+                // Measure around the first point so the start/finish wrap can't skew the spread
+                let reference = points[0];
+                let offsets: Vec<f32> = points.iter().map(|&p| laps::signed_lap_pct_delta(reference, p)).collect();
+                let (mean_offset, std_dev) = mean_and_std_dev(&offsets);
+                BrakingPointSpread {
+                    lap_dist_pct: (reference + mean_offset).rem_euclid(1.0),
+                    samples: points.len(),
+                    std_dev_m: std_dev * track_length_m,
+                }
+            })
+            .collect();
+
+        let mut cars: Vec<CarConsistency> = self.car_laps
+            .iter()
+            .filter_map(|(&car_idx, laps)| {
+                lap_time_spread(laps).map(|(mean, std_dev, count)| CarConsistency {
+                    car_idx,
+                    laps_counted: count,
+                    mean_lap_time: mean,
+                    lap_time_std_dev: std_dev,
+                })
+            })
+            .collect();
+        cars.sort_by_key(|car| car.car_idx);
+
+        ConsistencyData { player, cars }
+    }
+}
+
+impl DerivedChannel for Consistency {
+    fn name(&self) -> &'static str {
+        "consistency"
+    }
+
+    fn dependencies(&self) -> &'static [&'static str] {
+        &["lap_summary", "braking"]
+    }
+
+    fn compute(&mut self, data: &mut TelemetryData) {
+        if data.race_clock.session_reset {
+            self.reset();
+        }
+
+        let mut changed = self.update_cars(data);
+
+        if let Some(report) = &data.braking_report {
+            let start_pct = report.zone.start_pct;
+            self.add_braking_point(start_pct);
+        }
+
+        if let Some(lap) = &data.lap_summary && lap.complete && !lap.pitted {
+            self.player_laps.push(lap.lap_time);
+            self.throttle_smoothness.push(lap.throttle_smoothness);
+            self.brake_smoothness.push(lap.brake_smoothness);
+            changed = true;
+        }
+
+        if changed {
+            data.consistency = Some(self.snapshot(laps::estimate_track_length(data)));
+        }
+    }
+}
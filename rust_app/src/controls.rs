@@ -0,0 +1,69 @@
+use std::sync::mpsc::{self, Receiver, Sender};
+
+/// Actions that can be triggered from outside the telemetry loop (hotkeys, integrations)
+#[derive(Clone, Debug, PartialEq)]
+pub enum ControlAction {
+    ToggleRecording,
+    DropMarker { label: String, source: String },
+    ToggleVerbose,
+    CycleFocusCar,
+    /// Show a message on the overlays
+    Alert { message: String },
+    /// Pit service request, e.g. "fuel 40" (see sim_commands)
+    PitCommand { command: String },
+}
+
+impl ControlAction {
+    /// Parse the name of an action that needs no details, as used in hotkey mappings
+    pub fn parse(name: &str, source: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "toggle_recording" | "record" => Some(Self::ToggleRecording),
+            "marker" | "drop_marker" => Some(Self::DropMarker {
+                label: "marker".to_string(),
+                source: source.to_string(),
+            }),
+            "toggle_verbose" | "verbose" => Some(Self::ToggleVerbose),
+            "cycle_focus" | "cycle_focus_car" => Some(Self::CycleFocusCar),
+            _ => None,
+        }
+    }
+}
+
+/// Sending side handed to hotkey listeners and integrations
+pub type ControlSender = Sender<ControlAction>;
+
+/// Create the channel used to deliver control actions to the telemetry loop
+pub fn channel() -> (ControlSender, Receiver<ControlAction>) {
+    mpsc::channel()
+}
+
+/// Car the overlays should focus on; None follows the player's car
+#[derive(Default)]
+pub struct FocusCar {
+    car_idx: Option<i32>,
+}
+
+impl FocusCar {
+    pub fn car_idx(&self) -> Option<i32> {
+        self.car_idx
+    }
+
+    /// Move focus to the next car on track (by CarIdx), wrapping back to the player
+    pub fn cycle(&mut self, lap_dist_pct: Option<&Vec<f32>>) {
+        // Cars not in the world report a negative lap distance
+        let active: Vec<i32> = lap_dist_pct
+            .map(|pcts| {
+                pcts.iter()
+                    .enumerate()
+                    .filter(|(_, pct)| **pct >= 0.0)
+                    .map(|(idx, _)| idx as i32)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        self.car_idx = match self.car_idx {
+            None => active.first().copied(),
+            Some(current) => active.iter().copied().find(|&idx| idx > current),
+        };
+    }
+}
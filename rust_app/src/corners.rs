@@ -0,0 +1,106 @@
+//! Named corners per track, loaded from a JSON file when one is present.
+//!
+//! The session info has no corner names, so these come from a user maintained
+//! `corners.json` keyed by TrackID:
+//! `{"123": [{"name": "T1", "start_pct": 0.08, "end_pct": 0.11}, ...]}`
+
+use crate::determinism;
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Corner file, relative to the working directory
+pub const CORNERS_PATH: &str = "corners.json";
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Corner {
+    pub name: String,
+    pub start_pct: f32,
+    /// May be smaller than start_pct for a corner spanning the start/finish line
+    pub end_pct: f32,
+}
+
+impl Corner {
+    pub fn contains(&self, lap_dist_pct: f32) -> bool {
+        if self.start_pct <= self.end_pct {
+            lap_dist_pct >= self.start_pct && lap_dist_pct <= self.end_pct
+        } else {
+            lap_dist_pct >= self.start_pct || lap_dist_pct <= self.end_pct
+        }
+    }
+}
+
+/// Corner lists by TrackID
+#[derive(Default)]
+pub struct CornerDatabase {
+    tracks: HashMap<i64, Vec<Corner>>,
+}
+
+impl CornerDatabase {
+    /// Load the database; a missing file gives an empty one, and so does deterministic mode
+    pub fn load(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+        if !path.exists() || determinism::enabled() {
+            return Self::default();
+        }
+        Self::try_load(path).unwrap_or_else(|e| {
+            tracing::warn!("Ignoring {}", e);
+            Self::default()
+        })
+    }
+
+    /// Load the database, failing on a missing or unreadable file
+    pub fn try_load(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path).map_err(|e| format!("corner database {}: {}", path.display(), e))?;
+        let tracks = serde_json::from_str(&contents).map_err(|e| format!("unreadable corner database {}: {}", path.display(), e))?;
+        Ok(Self { tracks })
+    }
+
+    /// Number of tracks with corners
+    pub fn track_count(&self) -> usize {
+        self.tracks.len()
+    }
+
+    /// Entries that can never match, as "track 123 corner T1: ..." descriptions
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        for (track_id, corners) in &self.tracks {
+            for corner in corners {
+                if corner.name.trim().is_empty() {
+                    problems.push(format!("track {}: a corner has no name", track_id));
+                }
+                for pct in [corner.start_pct, corner.end_pct] {
+                    if !(0.0..=1.0).contains(&pct) {
+                        problems.push(format!("track {} corner {}: lap position {} is outside 0-1", track_id, corner.name, pct));
+                    }
+                }
+            }
+        }
+        problems
+    }
+
+    /// Corners of a track, empty when it isn't known
+    pub fn corners(&self, track_id: i64) -> &[Corner] {
+        self.tracks.get(&track_id).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Corner at a lap position, if the track is known
+    pub fn corner_at(&self, track_id: i64, lap_dist_pct: f32) -> Option<&Corner> {
+        self.tracks.get(&track_id)?.iter().find(|corner| corner.contains(lap_dist_pct))
+    }
+
+    /// Corner at a lap position or else the next one along the lap, with its index in the
+    /// track's list; what a braking zone starting there brakes for
+    pub fn corner_ahead(&self, track_id: i64, lap_dist_pct: f32) -> Option<(usize, &Corner)> {
+        let corners = self.tracks.get(&track_id)?;
+        if let Some(found) = corners.iter().enumerate().find(|(_, corner)| corner.contains(lap_dist_pct)) {
+            return Some(found);
+        }
+        corners
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| (a.start_pct - lap_dist_pct).rem_euclid(1.0).total_cmp(&(b.start_pct - lap_dist_pct).rem_euclid(1.0)))
+    }
+}
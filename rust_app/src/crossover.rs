@@ -0,0 +1,219 @@
+//! Wet/slick crossover estimate for changing conditions.
+//!
+//! Sector times of cars on wet tires are compared with those of cars on slicks, and the
+//! trend of the difference gives the time until one becomes faster than the other.
+//! Compound types come from the player's `DriverTires` list, so other cars are assumed to
+//! share the player's tire indices (true in single-make and most multiclass fields).
+//! Medians over all cars mix fast and slow drivers; the trend matters more than the level.
+
+use crate::derived::DerivedChannel;
+use crate::session_yaml;
+use crate::telemetry_fields::TelemetryData;
+use serde::{Serialize, Deserialize};
+use std::collections::VecDeque;
+
+/// Equal lap sections compared between compounds
+pub const SECTOR_COUNT: usize = 3;
+
+// Sector times older than this no longer describe the current conditions
+const SAMPLE_WINDOW_S: f64 = 600.0;
+// Sector times per compound before a sector is compared
+const MIN_SAMPLES: usize = 3;
+// Lap delta history used for the trend
+const TREND_INTERVAL_S: f64 = 30.0;
+const TREND_WINDOW_S: f64 = 1200.0;
+const MIN_TREND_POINTS: usize = 4;
+
+/// Median sector times per compound for one sector
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct SectorComparison {
+    pub sector: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dry_median_s: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wet_median_s: Option<f32>,
+    pub dry_samples: usize,
+    pub wet_samples: usize,
+}
+
+/// How wet tires compare with slicks right now and where that is heading
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct CrossoverEstimate {
+    pub sectors: Vec<SectorComparison>,
+    /// Wet minus slick lap time over the compared sectors; positive when slicks are faster
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lap_delta_s: Option<f32>,
+    /// "dry" or "wet"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub faster: Option<String>,
+    /// Change of the lap delta per minute
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trend_s_per_min: Option<f32>,
+    /// Race time until the other compound becomes faster at the current trend
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub crossover_in_s: Option<f32>,
+}
+
+/// Timing state of one car
+#[derive(Clone, Debug, Default)]
+struct CarSector {
+    sector: Option<usize>,
+    /// Race time the car crossed into the sector; None when it wasn't seen crossing
+    entered_at: Option<f64>,
+    wet: Option<bool>,
+    touched_pits: bool,
+}
+
+/// Derived channel that publishes `crossover_estimate` once both compounds are running
+pub struct CrossoverEstimator {
+    last_session_info: String,
+    wet_compounds: Vec<i32>,
+    cars: Vec<CarSector>,
+    /// (race time, sector, wet, sector time)
+    samples: VecDeque<(f64, usize, bool, f32)>,
+    /// (race time, lap delta)
+    trend: VecDeque<(f64, f32)>,
+}
+
+impl CrossoverEstimator {
+    pub fn new() -> Self {
+        Self {
+            last_session_info: String::new(),
+            wet_compounds: Vec::new(),
+            cars: Vec::new(),
+            samples: VecDeque::new(),
+            trend: VecDeque::new(),
+        }
+    }
+
+    fn compare(&self, sector: usize) -> SectorComparison {
+        let times = |wet: bool| -> Vec<f32> {
+            self.samples.iter().filter(|s| s.1 == sector && s.2 == wet).map(|s| s.3).collect()
+        };
+        let (dry, wet) = (times(false), times(true));
+        SectorComparison {
+            sector,
+            dry_median_s: (dry.len() >= MIN_SAMPLES).then(|| median(dry.clone())),
+            wet_median_s: (wet.len() >= MIN_SAMPLES).then(|| median(wet.clone())),
+            dry_samples: dry.len(),
+            wet_samples: wet.len(),
+        }
+    }
+}
+
+fn median(mut values: Vec<f32>) -> f32 {
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    values[values.len() / 2]
+}
+
+// Least squares slope of (x, y) points
+fn slope(points: &[(f64, f32)]) -> Option<f64> {
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|p| p.0).sum::<f64>() / n;
+    let mean_y = points.iter().map(|p| p.1 as f64).sum::<f64>() / n;
+    let var_x: f64 = points.iter().map(|p| (p.0 - mean_x).powi(2)).sum();
+    let cov: f64 = points.iter().map(|p| (p.0 - mean_x) * (p.1 as f64 - mean_y)).sum();
+    (var_x > 0.0).then(|| cov / var_x)
+}
+
+impl DerivedChannel for CrossoverEstimator {
+    fn name(&self) -> &'static str {
+        "crossover_estimate"
+    }
+
+    fn compute(&mut self, data: &mut TelemetryData) {
+        let clock = data.race_clock;
+        if clock.session_reset {
+            self.cars.clear();
+            self.samples.clear();
+            self.trend.clear();
+        }
+        if clock.jumped {
+            self.cars.clear();
+        }
+        if data.session_info != self.last_session_info {
+            self.last_session_info = data.session_info.clone();
+            self.wet_compounds = session_yaml::tire_compounds(&data.session_info)
+                .into_iter()
+                .filter(|(_, compound)| compound.to_lowercase().contains("wet"))
+                .map(|(index, _)| index)
+                .collect();
+        }
+        // Nothing to compare on cars without wet tires
+        if self.wet_compounds.is_empty() {
+            return;
+        }
+        let (Some(positions), Some(compounds)) = (data.CarIdxLapDistPct.as_ref(), data.CarIdxTireCompound.as_ref()) else {
+            return;
+        };
+        if self.cars.len() < positions.len() {
+            self.cars.resize_with(positions.len(), CarSector::default);
+        }
+
+        for (idx, car) in self.cars.iter_mut().enumerate() {
+            let pct = positions.get(idx).copied().unwrap_or(-1.0);
+            let compound = compounds.get(idx).copied().unwrap_or(-1);
+            if pct < 0.0 || compound < 0 {
+                *car = CarSector::default();
+                continue;
+            }
+            let wet = self.wet_compounds.contains(&compound);
+            let in_pits = data.CarIdxOnPitRoad.as_ref().and_then(|p| p.get(idx)).copied().unwrap_or(false);
+            let sector = ((pct * SECTOR_COUNT as f32) as usize).min(SECTOR_COUNT - 1);
+
+            match car.sector {
+                Some(previous) if previous == sector => {},
+                Some(previous) if (previous + 1) % SECTOR_COUNT == sector => {
+                    // Only sectors driven start to finish on one compound, away from the pits
+                    if let (Some(entered_at), Some(car_wet)) = (car.entered_at, car.wet) && car_wet == wet && !car.touched_pits && !in_pits {
+                        self.samples.push_back((clock.time, previous, wet, (clock.time - entered_at) as f32));
+                    }
+                    car.entered_at = Some(clock.time);
+                    car.touched_pits = false;
+                },
+                // First sighting, or going backwards / skipping a sector
+                _ => car.entered_at = None,
+            }
+            car.sector = Some(sector);
+            car.wet = Some(wet);
+            car.touched_pits |= in_pits;
+        }
+        while self.samples.front().is_some_and(|s| clock.time - s.0 > SAMPLE_WINDOW_S) {
+            self.samples.pop_front();
+        }
+
+        let sectors: Vec<SectorComparison> = (0..SECTOR_COUNT).map(|sector| self.compare(sector)).collect();
+        if sectors.iter().all(|s| s.dry_samples == 0 || s.wet_samples == 0) {
+            return;
+        }
+        let deltas: Vec<f32> = sectors
+            .iter()
+            .filter_map(|s| Some(s.wet_median_s? - s.dry_median_s?))
+            .collect();
+        // A lap delta needs every sector, otherwise the sectors compared would change over time
+        let lap_delta = (deltas.len() == SECTOR_COUNT).then(|| deltas.iter().sum::<f32>());
+
+        if let Some(delta) = lap_delta && self.trend.back().is_none_or(|&(t, _)| clock.time - t >= TREND_INTERVAL_S) {
+            self.trend.push_back((clock.time, delta));
+        }
+        while self.trend.front().is_some_and(|&(t, _)| clock.time - t > TREND_WINDOW_S) {
+            self.trend.pop_front();
+        }
+        let trend = (self.trend.len() >= MIN_TREND_POINTS)
+            .then(|| slope(&self.trend.iter().copied().collect::<Vec<_>>()))
+            .flatten();
+        // Heading towards zero: the delta changes sign after delta / -slope seconds
+        let crossover_in = match (lap_delta, trend) {
+            (Some(delta), Some(slope)) if slope != 0.0 && (delta as f64) * slope < 0.0 => Some((-(delta as f64) / slope) as f32),
+            _ => None,
+        };
+
+        data.crossover_estimate = Some(CrossoverEstimate {
+            sectors,
+            lap_delta_s: lap_delta,
+            faster: lap_delta.map(|delta| if delta > 0.0 { "dry" } else { "wet" }.to_string()),
+            trend_s_per_min: trend.map(|slope| (slope * 60.0) as f32),
+            crossover_in_s: crossover_in,
+        });
+    }
+}
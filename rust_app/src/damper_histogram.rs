@@ -0,0 +1,213 @@
+//! Damper velocity histograms per wheel per stint, the standard setup-engineering view.
+//!
+//! The histograms need every damper velocity sample (the 360 Hz `_ST` channels), which is
+//! far too much to stream, so they are built here and fetched on demand:
+//! `{"type":"get_damper_histogram"}` returns the current and recent stints,
+//! `{"type":"get_damper_histogram","stint":3}` a single one. Each wheel reports the share
+//! of time spent in each velocity bin (mm/s, negative is rebound), plus the bump/rebound
+//! split and the share above the high-speed knee.
+
+use crate::derived::DerivedChannel;
+use crate::protocol;
+use crate::telemetry_fields::TelemetryData;
+use crate::websocket_server::RequestHandler;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+
+/// Width of a velocity bin (mm/s)
+pub const BIN_WIDTH_MM_S: f32 = 10.0;
+/// Velocities beyond this (either way) land in the outermost bins (mm/s)
+pub const MAX_VELOCITY_MM_S: f32 = 300.0;
+/// Boundary between low- and high-speed damping (mm/s)
+pub const HIGH_SPEED_KNEE_MM_S: f32 = 50.0;
+
+// Number of finished stints kept for reporting
+const MAX_STINT_HISTORY: usize = 10;
+
+const BIN_COUNT: usize = (2.0 * MAX_VELOCITY_MM_S / BIN_WIDTH_MM_S) as usize;
+const WHEELS: [&str; 4] = ["lf", "rf", "lr", "rr"];
+
+#[derive(Clone, Debug)]
+struct WheelCounts {
+    bins: Vec<u64>,
+    bump: u64,
+    rebound: u64,
+    high_speed: u64,
+}
+
+impl Default for WheelCounts {
+    fn default() -> Self {
+        Self { bins: vec![0; BIN_COUNT], bump: 0, rebound: 0, high_speed: 0 }
+    }
+}
+
+impl WheelCounts {
+    fn add(&mut self, velocity_mm_s: f32) {
+        let bin = ((velocity_mm_s + MAX_VELOCITY_MM_S) / BIN_WIDTH_MM_S).floor();
+        self.bins[bin.clamp(0.0, (BIN_COUNT - 1) as f32) as usize] += 1;
+        if velocity_mm_s > 0.0 {
+            self.bump += 1;
+        } else if velocity_mm_s < 0.0 {
+            self.rebound += 1;
+        }
+        if velocity_mm_s.abs() > HIGH_SPEED_KNEE_MM_S {
+            self.high_speed += 1;
+        }
+    }
+
+    fn total(&self) -> u64 {
+        self.bins.iter().sum()
+    }
+}
+
+/// Time share per velocity bin for one wheel
+#[derive(Serialize, Debug)]
+pub struct WheelHistogram {
+    pub samples: u64,
+    /// Fraction of samples in each bin, in the order of `bin_edges_mm_s`
+    pub bins: Vec<f32>,
+    pub bump_pct: f32,
+    pub rebound_pct: f32,
+    pub high_speed_pct: f32,
+}
+
+impl From<&WheelCounts> for WheelHistogram {
+    fn from(counts: &WheelCounts) -> Self {
+        let total = counts.total();
+        let share = |count: u64| if total > 0 { count as f32 / total as f32 } else { 0.0 };
+        Self {
+            samples: total,
+            bins: counts.bins.iter().map(|count| share(*count)).collect(),
+            bump_pct: share(counts.bump) * 100.0,
+            rebound_pct: share(counts.rebound) * 100.0,
+            high_speed_pct: share(counts.high_speed) * 100.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+struct Stint {
+    stint: u32,
+    start_lap: i32,
+    wheels: [WheelCounts; 4],
+}
+
+impl Stint {
+    fn is_empty(&self) -> bool {
+        self.wheels.iter().all(|wheel| wheel.total() == 0)
+    }
+
+    fn to_json(&self) -> Value {
+        let wheels: serde_json::Map<String, Value> = WHEELS
+            .iter()
+            .zip(&self.wheels)
+            .map(|(name, counts)| (name.to_string(), json!(WheelHistogram::from(counts))))
+            .collect();
+        json!({ "stint": self.stint, "start_lap": self.start_lap, "wheels": wheels })
+    }
+}
+
+#[derive(Default)]
+struct Histograms {
+    current: Stint,
+    previous_stints: Vec<Stint>,
+}
+
+/// Damper histograms shared between the telemetry thread and the server
+#[derive(Clone, Default)]
+pub struct DamperHistogramStore {
+    histograms: Arc<Mutex<Histograms>>,
+}
+
+impl DamperHistogramStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+// Lower edge of each bin, plus the upper edge of the last
+fn bin_edges() -> Vec<f32> {
+    (0..=BIN_COUNT).map(|bin| bin as f32 * BIN_WIDTH_MM_S - MAX_VELOCITY_MM_S).collect()
+}
+
+impl RequestHandler for DamperHistogramStore {
+    fn handle(&self, request: &Value) -> Value {
+        let Ok(histograms) = self.histograms.lock() else {
+            return protocol::error(protocol::ERROR_FAILED, "Histograms unavailable", Some("get_damper_histogram"));
+        };
+        let stints: Vec<&Stint> = histograms
+            .previous_stints
+            .iter()
+            .chain(std::iter::once(&histograms.current))
+            .filter(|stint| !stint.is_empty())
+            .collect();
+        let stints: Vec<Value> = match request.get("stint").and_then(Value::as_u64) {
+            Some(number) => match stints.iter().find(|stint| stint.stint as u64 == number) {
+                Some(stint) => vec![stint.to_json()],
+                None => return protocol::error(protocol::ERROR_NOT_FOUND, format!("No data for stint {}", number), Some("get_damper_histogram")),
+            },
+            None => stints.iter().map(|stint| stint.to_json()).collect(),
+        };
+        json!({
+            "type": "damper_histogram",
+            "bin_edges_mm_s": bin_edges(),
+            "high_speed_knee_mm_s": HIGH_SPEED_KNEE_MM_S,
+            "stints": stints,
+        })
+    }
+}
+
+/// Derived channel that bins the damper velocities of each frame.
+///
+/// A stint starts when the car leaves pit road and ends when it enters it again.
+pub struct DamperHistogramRecorder {
+    store: DamperHistogramStore,
+    in_stint: bool,
+}
+
+impl DamperHistogramRecorder {
+    pub fn new(store: DamperHistogramStore) -> Self {
+        Self { store, in_stint: false }
+    }
+}
+
+impl DerivedChannel for DamperHistogramRecorder {
+    fn name(&self) -> &'static str {
+        "damper_histogram"
+    }
+
+    fn compute(&mut self, data: &mut TelemetryData) {
+        let Ok(mut histograms) = self.store.histograms.lock() else {
+            return;
+        };
+        if data.race_clock.session_reset {
+            *histograms = Histograms::default();
+            self.in_stint = false;
+        }
+
+        if data.on_pit_road {
+            self.in_stint = false;
+        } else if !self.in_stint {
+            let finished = std::mem::take(&mut histograms.current);
+            histograms.current = Stint { stint: finished.stint + 1, start_lap: data.lap_completed, ..Default::default() };
+            if !finished.is_empty() {
+                histograms.previous_stints.push(finished);
+                if histograms.previous_stints.len() > MAX_STINT_HISTORY {
+                    histograms.previous_stints.remove(0);
+                }
+            }
+            self.in_stint = true;
+        }
+
+        // Parked or replaying laps already binned would only skew the distribution
+        if !self.in_stint || data.is_replay_playing || data.speed_kph < 1.0 {
+            return;
+        }
+        for (wheel, samples) in data.shock_vel_samples.iter().enumerate() {
+            for velocity in samples {
+                histograms.current.wheels[wheel].add(velocity * 1000.0);
+            }
+        }
+    }
+}
@@ -0,0 +1,440 @@
+//! Optional enrichment from the official iRacing /data web API.
+//!
+//! With the driver's own iRacing credentials, career stats of the drivers in the session,
+//! the series name and, once the race is over, the official results are fetched on a
+//! background thread and cached in a JSON file, so each driver and result is downloaded
+//! only once. Live frames carry what is in the cache as `data_api`. Requires building
+//! with the `data-api` feature and is off unless credentials are given.
+
+use crate::derived::DerivedChannel;
+use crate::race_phase::Phase;
+use crate::session_yaml;
+use crate::telemetry_fields::TelemetryData;
+use serde::{Serialize, Deserialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+
+/// Cache file name, placed in the instance directory
+pub const DEFAULT_CACHE_PATH: &str = "data_api_cache.json";
+
+/// iRacing account used for the /data API
+#[derive(Clone)]
+pub struct Credentials {
+    pub email: String,
+    pub password: String,
+}
+
+/// Career totals in one license category
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct CareerStats {
+    pub category: String,
+    pub starts: u32,
+    pub wins: u32,
+    pub top5: u32,
+    pub poles: u32,
+    pub avg_finish_position: f32,
+    pub avg_incidents: f32,
+    pub win_percentage: f32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct SeriesInfo {
+    pub series_id: i64,
+    pub series_name: String,
+    pub category: String,
+}
+
+/// One driver's line of the official results
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct OfficialFinish {
+    pub cust_id: i64,
+    /// 1 based
+    pub finish_position: i32,
+    pub old_i_rating: i32,
+    pub new_i_rating: i32,
+    pub incidents: i32,
+}
+
+/// Official results of a race subsession
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct OfficialResult {
+    pub subsession_id: i64,
+    pub series_name: String,
+    pub season_name: String,
+    pub strength_of_field: i32,
+    pub official: bool,
+    pub results: Vec<OfficialFinish>,
+}
+
+/// Career stats of the driver in one car
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct EnrichedDriver {
+    pub car_idx: i32,
+    pub cust_id: i64,
+    pub career: Vec<CareerStats>,
+}
+
+/// Web API data for the current session, published with every frame
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct DataApiEnrichment {
+    pub drivers: Vec<EnrichedDriver>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub series: Option<SeriesInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub official_result: Option<OfficialResult>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+struct CacheFile {
+    careers: BTreeMap<i64, Vec<CareerStats>>,
+    series: BTreeMap<i64, SeriesInfo>,
+    results: BTreeMap<i64, OfficialResult>,
+}
+
+/// Work for the background fetch thread
+#[cfg_attr(not(feature = "data-api"), allow(dead_code))]
+enum Request {
+    Careers(Vec<i64>),
+    Series,
+    Result(i64),
+}
+
+/// Handle to the cache and the fetch thread
+#[derive(Clone)]
+pub struct DataApi {
+    cache: Arc<Mutex<CacheFile>>,
+    path: PathBuf,
+    /// Bumped whenever the cache changes
+    generation: Arc<AtomicU64>,
+    requests: Sender<Request>,
+}
+
+impl DataApi {
+    #[cfg_attr(not(feature = "data-api"), allow(dead_code))]
+    fn load_cache(path: &Path) -> CacheFile {
+        match fs::read_to_string(path) {
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+                tracing::warn!("Ignoring unreadable data API cache {}: {}", path.display(), e);
+                CacheFile::default()
+            }),
+            Err(_) => CacheFile::default(),
+        }
+    }
+
+    // Same temporary file and rename as the metadata cache
+    #[cfg_attr(not(feature = "data-api"), allow(dead_code))]
+    fn save(&self, cache: &CacheFile) {
+        if let Some(parent) = self.path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            let _ = fs::create_dir_all(parent);
+        }
+        let tmp = self.path.with_extension(format!("{}.tmp", std::process::id()));
+        let result = serde_json::to_string_pretty(cache)
+            .map_err(|e| e.to_string())
+            .and_then(|contents| fs::write(&tmp, contents).map_err(|e| e.to_string()))
+            .and_then(|()| fs::rename(&tmp, &self.path).map_err(|e| e.to_string()));
+        if let Err(e) = result {
+            tracing::error!("Failed to write data API cache {}: {}", self.path.display(), e);
+        }
+    }
+
+    // Apply a change made by the fetch thread and persist it
+    #[cfg_attr(not(feature = "data-api"), allow(dead_code))]
+    fn update(&self, change: impl FnOnce(&mut CacheFile)) {
+        if let Ok(mut cache) = self.cache.lock() {
+            change(&mut cache);
+            self.save(&cache);
+            self.generation.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn request(&self, request: Request) {
+        let _ = self.requests.send(request);
+    }
+}
+
+/// Load the cache and start fetching on a background thread
+#[cfg(feature = "data-api")]
+pub fn start(credentials: Credentials, cache_path: impl AsRef<Path>) -> Result<DataApi, String> {
+    let path = cache_path.as_ref().to_path_buf();
+    let (requests, receiver) = std::sync::mpsc::channel();
+    let api = DataApi {
+        cache: Arc::new(Mutex::new(DataApi::load_cache(&path))),
+        path,
+        generation: Arc::new(AtomicU64::new(0)),
+        requests,
+    };
+    let worker = api.clone();
+    std::thread::Builder::new()
+        .name("data-api".to_string())
+        .spawn(move || client::run(credentials, receiver, worker))
+        .map(|_| api)
+        .map_err(|e| format!("Failed to start data API thread: {}", e))
+}
+
+#[cfg(not(feature = "data-api"))]
+pub fn start(_credentials: Credentials, _cache_path: impl AsRef<Path>) -> Result<DataApi, String> {
+    Err("The iRacing data API requires building with the \"data-api\" feature".to_string())
+}
+
+#[cfg(feature = "data-api")]
+mod client {
+    use super::{CareerStats, Credentials, DataApi, OfficialFinish, OfficialResult, Request, SeriesInfo};
+    use base64::Engine;
+    use serde_json::Value;
+    use sha2::{Digest, Sha256};
+    use std::sync::mpsc::Receiver;
+    use std::time::Duration;
+
+    const BASE_URL: &str = "https://members-ng.iracing.com";
+    // Stay well under the API's rate limit
+    const REQUEST_INTERVAL: Duration = Duration::from_millis(500);
+
+    struct Client {
+        http: reqwest::blocking::Client,
+        credentials: Credentials,
+        authenticated: bool,
+    }
+
+    impl Client {
+        fn authenticate(&mut self) -> Result<(), String> {
+            // The API takes the password hashed with the lower case email as salt
+            let hash = Sha256::digest(format!("{}{}", self.credentials.password, self.credentials.email.to_lowercase()));
+            let body = serde_json::json!({
+                "email": self.credentials.email,
+                "password": base64::engine::general_purpose::STANDARD.encode(hash),
+            });
+            let response = self.http.post(format!("{}/auth", BASE_URL)).json(&body).send().map_err(|e| e.to_string())?;
+            if !response.status().is_success() {
+                return Err(format!("Authentication failed ({})", response.status()));
+            }
+            self.authenticated = true;
+            Ok(())
+        }
+
+        // Most endpoints answer with a link to the actual data on a CDN
+        fn get(&mut self, path: &str) -> Result<Value, String> {
+            for attempt in 0..2 {
+                if !self.authenticated {
+                    self.authenticate()?;
+                }
+                std::thread::sleep(REQUEST_INTERVAL);
+                let response = self.http.get(format!("{}/data/{}", BASE_URL, path)).send().map_err(|e| e.to_string())?;
+                if response.status() == reqwest::StatusCode::UNAUTHORIZED && attempt == 0 {
+                    self.authenticated = false;
+                    continue;
+                }
+                if !response.status().is_success() {
+                    return Err(format!("{} returned {}", path, response.status()));
+                }
+                let value: Value = response.json().map_err(|e| e.to_string())?;
+                return match value.get("link").and_then(Value::as_str) {
+                    Some(link) => self.http.get(link).send().and_then(|r| r.json()).map_err(|e| e.to_string()),
+                    None => Ok(value),
+                };
+            }
+            Err(format!("{} still unauthorized after signing in again", path))
+        }
+    }
+
+    fn career(value: &Value) -> Vec<CareerStats> {
+        value
+            .get("stats")
+            .and_then(Value::as_array)
+            .map(|stats| stats.iter().filter_map(|s| serde_json::from_value(s.clone()).ok()).collect())
+            .unwrap_or_default()
+    }
+
+    fn official_result(value: &Value) -> OfficialResult {
+        let int = |v: &Value, key: &str| v.get(key).and_then(Value::as_i64).unwrap_or(0);
+        let text = |key: &str| value.get(key).and_then(Value::as_str).unwrap_or_default().to_string();
+        let race = value
+            .get("session_results")
+            .and_then(Value::as_array)
+            .and_then(|sessions| sessions.iter().find(|s| s.get("simsession_type_name").and_then(Value::as_str) == Some("Race")));
+        let results = race
+            .and_then(|race| race.get("results"))
+            .and_then(Value::as_array)
+            .map(|results| {
+                results
+                    .iter()
+                    .map(|r| OfficialFinish {
+                        cust_id: int(r, "cust_id"),
+                        finish_position: int(r, "finish_position") as i32 + 1,
+                        old_i_rating: int(r, "oldi_rating") as i32,
+                        new_i_rating: int(r, "newi_rating") as i32,
+                        incidents: int(r, "incidents") as i32,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        OfficialResult {
+            subsession_id: int(value, "subsession_id"),
+            series_name: text("series_name"),
+            season_name: text("season_name"),
+            strength_of_field: int(value, "event_strength_of_field") as i32,
+            official: value.get("official_session").and_then(Value::as_bool).unwrap_or(false),
+            results,
+        }
+    }
+
+    pub fn run(credentials: Credentials, requests: Receiver<Request>, api: DataApi) {
+        let http = match reqwest::blocking::Client::builder().cookie_store(true).build() {
+            Ok(http) => http,
+            Err(e) => {
+                tracing::error!("{}", e);
+                return;
+            }
+        };
+        let mut client = Client { http, credentials, authenticated: false };
+
+        while let Ok(request) = requests.recv() {
+            match request {
+                Request::Careers(cust_ids) => {
+                    for cust_id in cust_ids {
+                        match client.get(&format!("stats/member_career?cust_id={}", cust_id)) {
+                            Ok(value) => api.update(|cache| {
+                                cache.careers.insert(cust_id, career(&value));
+                            }),
+                            Err(e) => tracing::warn!("Career stats of {}: {}", cust_id, e),
+                        }
+                    }
+                },
+                Request::Series => match client.get("series/get") {
+                    Ok(Value::Array(series)) => api.update(|cache| {
+                        for info in series.into_iter().filter_map(|s| serde_json::from_value::<SeriesInfo>(s).ok()) {
+                            cache.series.insert(info.series_id, info);
+                        }
+                    }),
+                    Ok(_) => tracing::warn!("Unexpected series list"),
+                    Err(e) => tracing::warn!("Series list: {}", e),
+                },
+                // Results show up a few minutes after the race; the enricher asks again later
+                Request::Result(subsession_id) => match client.get(&format!("results/get?subsession_id={}", subsession_id)) {
+                    Ok(value) => api.update(|cache| {
+                        cache.results.insert(subsession_id, official_result(&value));
+                    }),
+                    Err(e) => tracing::warn!("Results of {}: {}", subsession_id, e),
+                },
+            }
+        }
+    }
+}
+
+// Time between requests for official results that aren't published yet
+const RESULT_RETRY_S: f64 = 120.0;
+
+/// Derived channel that asks for the session's data and publishes what is cached
+pub struct DataApiEnricher {
+    api: DataApi,
+    last_session_info: String,
+    drivers: Vec<(i32, i64)>,
+    series_id: i64,
+    subsession_id: i64,
+    series_requested: bool,
+    /// Race time of the last request for official results
+    result_requested_at: Option<f64>,
+    generation: Option<u64>,
+    enrichment: DataApiEnrichment,
+}
+
+impl DataApiEnricher {
+    pub fn new(api: DataApi) -> Self {
+        Self {
+            api,
+            last_session_info: String::new(),
+            drivers: Vec::new(),
+            series_id: 0,
+            subsession_id: 0,
+            series_requested: false,
+            result_requested_at: None,
+            generation: None,
+            enrichment: DataApiEnrichment::default(),
+        }
+    }
+
+    fn rebuild(&mut self) {
+        let Ok(cache) = self.api.cache.lock() else {
+            return;
+        };
+        self.enrichment = DataApiEnrichment {
+            drivers: self
+                .drivers
+                .iter()
+                .filter_map(|&(car_idx, cust_id)| {
+                    let career = cache.careers.get(&cust_id)?.clone();
+                    Some(EnrichedDriver { car_idx, cust_id, career })
+                })
+                .collect(),
+            series: cache.series.get(&self.series_id).cloned(),
+            official_result: cache.results.get(&self.subsession_id).cloned(),
+        };
+    }
+}
+
+impl DerivedChannel for DataApiEnricher {
+    fn name(&self) -> &'static str {
+        "data_api"
+    }
+
+    fn dependencies(&self) -> &'static [&'static str] {
+        &["race_phase"]
+    }
+
+    fn compute(&mut self, data: &mut TelemetryData) {
+        if data.session_info != self.last_session_info {
+            self.last_session_info = data.session_info.clone();
+            self.drivers = session_yaml::drivers(&data.session_info)
+                .into_iter()
+                .filter(|d| d.user_id > 0)
+                .map(|d| (d.car_idx, d.user_id))
+                .collect();
+            self.series_id = session_yaml::number(&data.session_info, "SeriesID").map_or(0, |id| id as i64);
+            let subsession_id = session_yaml::number(&data.session_info, "SubSessionID").map_or(0, |id| id as i64);
+            if subsession_id != self.subsession_id {
+                self.subsession_id = subsession_id;
+                self.result_requested_at = None;
+            }
+
+            let missing: Vec<i64> = match self.api.cache.lock() {
+                Ok(cache) => self.drivers.iter().map(|d| d.1).filter(|id| !cache.careers.contains_key(id)).collect(),
+                Err(_) => Vec::new(),
+            };
+            if !missing.is_empty() {
+                self.api.request(Request::Careers(missing));
+            }
+            if self.series_id > 0 && !self.series_requested {
+                self.series_requested = true;
+                self.api.request(Request::Series);
+            }
+            self.generation = None;
+        }
+
+        // Official results exist once a hosted or official race is over
+        let race_over = data.race_phase.is_some_and(|phase| phase.phase == Phase::CoolDown);
+        if race_over && self.subsession_id > 0 && self.enrichment.official_result.is_none() {
+            let now = data.race_clock.time;
+            if self.result_requested_at.is_none_or(|at| now - at >= RESULT_RETRY_S || now < at) {
+                self.result_requested_at = Some(now);
+                self.api.request(Request::Result(self.subsession_id));
+            }
+        }
+
+        let generation = self.api.generation.load(Ordering::Relaxed);
+        if self.generation != Some(generation) {
+            self.generation = Some(generation);
+            self.rebuild();
+        }
+        if !self.enrichment.drivers.is_empty() || self.enrichment.series.is_some() || self.enrichment.official_result.is_some() {
+            data.data_api = Some(self.enrichment.clone());
+        }
+    }
+}
@@ -0,0 +1,72 @@
+//! Delta broadcast mode: only the channels that changed since the last frame sent.
+//!
+//! Full frames carry every channel, including the session info YAML, on every tick. A client
+//! that sends `"delta": true` in its `hello` instead gets a keyframe (the normal frame with
+//! `"keyframe": true`) followed by messages holding only what changed:
+//!
+//! ```json
+//! {"type":"delta","seq":1,"changed":{"speed_kph":143.2,"engine_warnings.raw_value":0},"removed":["radar"]}
+//! ```
+//!
+//! Nested fields use dotted names as in the dead-bands file (see change_detection.rs), arrays
+//! are sent whole, and numeric channels only count as changed once they move beyond their
+//! dead-band. Clients apply `removed` before `changed`. A new keyframe is sent every
+//! `KEYFRAME_INTERVAL` frames, when the client's frame layout changes (schema version or
+//! channel preset), after a new `hello`, and on `{"type":"keyframe"}`, which clients send
+//! when they notice a gap in `seq`.
+
+use crate::change_detection::{ChangeDetector, DeadBands};
+use serde_json::{json, Value};
+
+/// Frames between keyframes
+pub const KEYFRAME_INTERVAL: u32 = 100;
+
+/// What to send a delta client for a frame
+pub enum DeltaFrame {
+    /// The full frame
+    Keyframe,
+    /// A `delta` message
+    Delta(Value),
+}
+
+/// Delta state of one client: the values it was last sent
+pub struct DeltaEncoder {
+    detector: ChangeDetector,
+    // Variant of the frames the references came from; None until the first keyframe
+    layout: Option<String>,
+    seq: u32,
+}
+
+impl DeltaEncoder {
+    pub fn new(bands: DeadBands) -> Self {
+        Self {
+            detector: ChangeDetector::new(bands),
+            layout: None,
+            seq: 0,
+        }
+    }
+
+    /// Keyframe or delta for `frame`; `layout` names the frame variant (e.g. the channel preset)
+    pub fn next(&mut self, layout: &str, frame: &Value) -> DeltaFrame {
+        if self.layout.as_deref() != Some(layout) || self.seq >= KEYFRAME_INTERVAL {
+            self.detector.reset();
+            self.detector.update(frame);
+            self.layout = Some(layout.to_string());
+            self.seq = 0;
+            return DeltaFrame::Keyframe;
+        }
+        self.seq += 1;
+        let removed = self.detector.remove_missing(frame);
+        let changed = self.detector.update_values(frame);
+        DeltaFrame::Delta(json!({ "type": "delta", "seq": self.seq, "changed": changed, "removed": removed }))
+    }
+}
+
+/// The full frame marked as a keyframe
+pub fn keyframe(frame: &Value) -> Value {
+    let mut keyframe = frame.clone();
+    if let Value::Object(fields) = &mut keyframe {
+        fields.insert("keyframe".to_string(), Value::Bool(true));
+    }
+    keyframe
+}
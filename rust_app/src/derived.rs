@@ -0,0 +1,450 @@
+use crate::gap_calculator;
+use crate::race_clock::RaceClockTracker;
+use crate::telemetry_fields::TelemetryData;
+use crate::wheel_slip::WheelSlip;
+use crate::braking_analyzer::BrakingAnalyzer;
+use crate::traction_analyzer::TractionAnalyzer;
+use crate::engine_stress::EngineStress;
+use crate::lap_summary::LapSummaries;
+use crate::consistency::Consistency;
+use crate::ghost_car::GhostCarTracker;
+use crate::car_motion::CarMotion;
+use crate::car_order::CarOrdering;
+use crate::sector_hazards::SectorHazardMap;
+use crate::local_yellow::LocalYellowLocator;
+use crate::metadata::Metadata;
+use crate::shift_lights::ShiftLightCalculator;
+use crate::gear_chart::GearChartBuilder;
+use crate::engine_maps::EngineMapTracker;
+use crate::hybrid::HybridTracker;
+use crate::overtakes::OvertakeDetector;
+use crate::time_of_day::SunTracker;
+use crate::crossover::CrossoverEstimator;
+use crate::tire_strategy::TireStrategyTracker;
+use crate::race_phase::RacePhaseDetector;
+use crate::restart_order::RestartOrderResolver;
+use crate::radar::ProximityRadar;
+use crate::map_state::MapStateBuilder;
+use crate::session_info::SessionInfoParser;
+use crate::auto_export::{ExportConfig, SessionExporter};
+use crate::car_info::{CarAssets, CarInfoRecorder, CarInfoStore};
+use crate::caution_advisor::CautionAdvisor;
+use crate::change_detection::{ChannelChangeEvents, DeadBands};
+use crate::damper_histogram::{DamperHistogramRecorder, DamperHistogramStore};
+use crate::data_api::{DataApi, DataApiEnricher};
+use crate::diagnostics::SessionInfoKeeper;
+use crate::drive_time::{DriveTimeRules, DriveTimeTracker};
+use crate::driver_db::{DriverDatabase, DriverStatsAnnotator};
+use crate::effective_standings::EffectiveStandingsProjector;
+use crate::finish_probabilities::{FinishSimRecorder, FinishSimulator};
+use crate::gap_history::{self, GapHistoryRecorder, GapHistoryStore};
+use crate::highlights::HighlightReel;
+use crate::pit_stops;
+use crate::replay_bookmarks::{ReplayBookmarker, ReplayBookmarks};
+use crate::session_clock::{ClockStore, SessionClockRecorder};
+use crate::strength_of_field::{StrengthOfFieldRecorder, StrengthOfFieldStore};
+use crate::units::{Bootstrap, UnitsRecorder};
+use crate::wind_tunnel::{WindTunnel, WindTunnelRecorder};
+use std::collections::HashMap;
+use std::f32::consts::PI;
+
+/// Standard gravity used for g-force conversion
+const GRAVITY_MS2: f32 = 9.8;
+
+/// Number of recent laps averaged for fuel per lap
+const FUEL_LAPS_AVERAGED: usize = 5;
+
+/// A computed channel that fills fields of `TelemetryData` from already extracted values.
+///
+/// Channels declare the other derived channels they read from; the pipeline evaluates
+/// them so that every dependency has run before the channels that use it.
+pub trait DerivedChannel: Send {
+    /// Unique name other channels use to depend on this one
+    fn name(&self) -> &'static str;
+
+    /// Names of derived channels that must be computed first
+    fn dependencies(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// Compute the channel for the current frame
+    fn compute(&mut self, data: &mut TelemetryData);
+}
+
+/// Ordered set of derived channels evaluated once per frame
+pub struct DerivedPipeline {
+    nodes: Vec<Box<dyn DerivedChannel>>,
+    order: Vec<usize>,
+    // For each channel, the channels that read it
+    dependents: Vec<Vec<usize>>,
+    clock: RaceClockTracker,
+}
+
+impl DerivedPipeline {
+    /// Create an empty pipeline
+    pub fn new() -> Self {
+        Self {
+            nodes: Vec::new(),
+            order: Vec::new(),
+            dependents: Vec::new(),
+            clock: RaceClockTracker::new(),
+        }
+    }
+
+    /// Create a pipeline with all built-in derived channels registered
+    pub fn standard() -> Result<Self, String> {
+        let mut pipeline = Self::new();
+        pipeline.register(Box::new(SessionInfoParser::new()));
+        pipeline.register(Box::new(VelocityMagnitude));
+        pipeline.register(Box::new(GForces));
+        pipeline.register(Box::new(SlipAngle));
+        pipeline.register(Box::new(FuelPerLap::default()));
+        pipeline.register(Box::new(Gaps));
+        pipeline.register(Box::new(Metadata::new()));
+        pipeline.register(Box::new(WheelSlip::new()));
+        pipeline.register(Box::new(BrakingAnalyzer::new()));
+        pipeline.register(Box::new(TractionAnalyzer::new()));
+        pipeline.register(Box::new(EngineStress::new()));
+        pipeline.register(Box::new(LapSummaries::new()));
+        pipeline.register(Box::new(Consistency::new()));
+        pipeline.register(Box::new(GhostCarTracker::new()));
+        pipeline.register(Box::new(CarMotion::new()));
+        pipeline.register(Box::new(CarOrdering::new()));
+        pipeline.register(Box::new(SectorHazardMap::new()));
+        pipeline.register(Box::new(LocalYellowLocator::new()));
+        pipeline.register(Box::new(ShiftLightCalculator));
+        pipeline.register(Box::new(GearChartBuilder::new()));
+        pipeline.register(Box::new(EngineMapTracker::new()));
+        pipeline.register(Box::new(HybridTracker::new()));
+        pipeline.register(Box::new(OvertakeDetector::new()));
+        pipeline.register(Box::new(SunTracker::new()));
+        pipeline.register(Box::new(CrossoverEstimator::new()));
+        pipeline.register(Box::new(TireStrategyTracker::new()));
+        pipeline.register(Box::new(RacePhaseDetector));
+        pipeline.register(Box::new(RestartOrderResolver::new()));
+        pipeline.register(Box::new(ProximityRadar::new()));
+        pipeline.register(Box::new(MapStateBuilder::new()));
+        pipeline.build()?;
+        Ok(pipeline)
+    }
+
+    /// The built-in channels plus the ones the server adds, in the order the server runs
+    /// them. Shared by the server and `analyze` (determinism.rs), so both derive the same
+    pub fn server(channels: ServerChannels) -> Result<Self, String> {
+        let mut pipeline = Self::standard()?;
+        pipeline.register(Box::new(GapHistoryRecorder::new(channels.gap_history)));
+        if let Some(store) = channels.session_clock {
+            pipeline.register(Box::new(SessionClockRecorder::new(store)));
+        }
+        pipeline.register(Box::new(UnitsRecorder::new(channels.bootstrap)));
+        pipeline.register(Box::new(CarInfoRecorder::new(channels.car_info, channels.car_assets)));
+        if channels.session_info_keeper {
+            pipeline.register(Box::new(SessionInfoKeeper::new()));
+        }
+        pipeline.register(Box::new(WindTunnelRecorder::new(channels.wind_tunnel)));
+        pipeline.register(Box::new(DamperHistogramRecorder::new(channels.damper_histograms)));
+        pipeline.register(Box::new(StrengthOfFieldRecorder::new(channels.strength_of_field)));
+        if let Some(db) = channels.driver_db {
+            pipeline.register(Box::new(DriverStatsAnnotator::new(db)));
+        }
+        if let Some(api) = channels.data_api {
+            pipeline.register(Box::new(DataApiEnricher::new(api)));
+        }
+        pipeline.register(Box::new(EffectiveStandingsProjector::new(channels.pit_loss)));
+        pipeline.register(Box::new(CautionAdvisor::new(channels.pit_loss)));
+        if let Some(simulator) = channels.finish_simulator {
+            pipeline.register(Box::new(FinishSimRecorder::new(simulator, channels.pit_loss)));
+        }
+        pipeline.register(Box::new(DriveTimeTracker::new(channels.drive_time_rules)));
+        if let Some(bookmarks) = channels.bookmarks {
+            pipeline.register(Box::new(ReplayBookmarker::new(bookmarks)));
+        }
+        if let Some(dir) = channels.highlights_dir {
+            pipeline.register(Box::new(HighlightReel::new(dir)));
+        }
+        if let Some(config) = channels.auto_export {
+            match SessionExporter::new(config) {
+                Ok(exporter) => pipeline.register(Box::new(exporter)),
+                Err(e) => tracing::error!("{}", e),
+            }
+        }
+        // Last, so it sees every other channel's values
+        pipeline.register(Box::new(ChannelChangeEvents::new(channels.dead_bands)));
+        pipeline.build()?;
+        Ok(pipeline)
+    }
+
+    /// Add a channel; call `build` afterwards to recompute the evaluation order
+    pub fn register(&mut self, node: Box<dyn DerivedChannel>) {
+        self.nodes.push(node);
+        self.order.clear();
+    }
+
+    /// Names of the registered channels in evaluation order
+    pub fn channel_names(&self) -> Vec<&'static str> {
+        self.order.iter().map(|&i| self.nodes[i].name()).collect()
+    }
+
+    /// Resolve the evaluation order, failing on unknown dependencies or cycles
+    pub fn build(&mut self) -> Result<(), String> {
+        let mut index_by_name = HashMap::new();
+        for (i, node) in self.nodes.iter().enumerate() {
+            if index_by_name.insert(node.name(), i).is_some() {
+                return Err(format!("Derived channel '{}' registered twice", node.name()));
+            }
+        }
+
+        // Kahn's algorithm, keeping registration order among independent channels
+        let mut remaining_deps = vec![0usize; self.nodes.len()];
+        let mut dependents: Vec<Vec<usize>> = vec![Vec::new(); self.nodes.len()];
+        for (i, node) in self.nodes.iter().enumerate() {
+            for dep in node.dependencies() {
+                let &dep_index = index_by_name.get(dep).ok_or_else(|| {
+                    format!("Derived channel '{}' depends on unknown channel '{}'", node.name(), dep)
+                })?;
+                remaining_deps[i] += 1;
+                dependents[dep_index].push(i);
+            }
+        }
+
+        let mut order = Vec::with_capacity(self.nodes.len());
+        let mut ready: Vec<usize> = (0..self.nodes.len()).filter(|&i| remaining_deps[i] == 0).collect();
+        while !ready.is_empty() {
+            let current = ready.remove(0);
+            order.push(current);
+            for &dependent in &dependents[current] {
+                remaining_deps[dependent] -= 1;
+                if remaining_deps[dependent] == 0 {
+                    ready.push(dependent);
+                }
+            }
+            ready.sort_unstable();
+        }
+
+        if order.len() != self.nodes.len() {
+            let cyclic: Vec<&str> = (0..self.nodes.len())
+                .filter(|i| !order.contains(i))
+                .map(|i| self.nodes[i].name())
+                .collect();
+            return Err(format!("Dependency cycle between derived channels: {}", cyclic.join(", ")));
+        }
+
+        self.order = order;
+        self.dependents = dependents;
+        Ok(())
+    }
+
+    /// Compute every derived channel for a frame
+    pub fn evaluate(&mut self, data: &mut TelemetryData) {
+        data.race_clock = self.clock.update(data);
+        for &i in &self.order {
+            self.nodes[i].compute(data);
+        }
+    }
+
+    /// Compute the derived channels for a frame except those `skip` picks, returning the
+    /// names of the skipped ones. A channel another channel that runs depends on is never
+    /// skipped (see load_shedding.rs)
+    pub fn evaluate_except(&mut self, data: &mut TelemetryData, skip: impl Fn(&str) -> bool) -> Vec<&'static str> {
+        // Dependents come later in the order, so walking it backwards settles them first
+        let mut runs = vec![false; self.nodes.len()];
+        for &i in self.order.iter().rev() {
+            runs[i] = !skip(self.nodes[i].name()) || self.dependents[i].iter().any(|&dependent| runs[dependent]);
+        }
+
+        data.race_clock = self.clock.update(data);
+        let mut skipped = Vec::new();
+        for &i in &self.order {
+            if runs[i] {
+                self.nodes[i].compute(data);
+            } else {
+                skipped.push(self.nodes[i].name());
+            }
+        }
+        skipped
+    }
+}
+
+/// What the channels the server adds work with: the stores they share with the request
+/// handlers serving them, and their options. The optional parts read the wall clock or do
+/// IO, so deterministic runs leave them out
+pub struct ServerChannels {
+    pub gap_history: GapHistoryStore,
+    pub bootstrap: Bootstrap,
+    pub car_info: CarInfoStore,
+    pub car_assets: CarAssets,
+    pub wind_tunnel: WindTunnel,
+    pub damper_histograms: DamperHistogramStore,
+    pub strength_of_field: StrengthOfFieldStore,
+    /// Pit loss assumed until stops have been measured
+    pub pit_loss: f32,
+    pub finish_simulator: Option<FinishSimulator>,
+    pub drive_time_rules: DriveTimeRules,
+    pub bookmarks: Option<ReplayBookmarks>,
+    pub dead_bands: DeadBands,
+    pub session_clock: Option<ClockStore>,
+    /// Keep a copy of the session info for diagnostic bundles
+    pub session_info_keeper: bool,
+    pub driver_db: Option<DriverDatabase>,
+    pub data_api: Option<DataApi>,
+    pub highlights_dir: Option<String>,
+    pub auto_export: Option<ExportConfig>,
+}
+
+impl Default for ServerChannels {
+    /// Fresh stores and default options, without the wall clock and IO parts
+    fn default() -> Self {
+        Self {
+            gap_history: GapHistoryStore::new(gap_history::DEFAULT_HISTORY_LAPS),
+            bootstrap: Bootstrap::new(),
+            car_info: CarInfoStore::new(),
+            car_assets: CarAssets::default(),
+            wind_tunnel: WindTunnel::new(),
+            damper_histograms: DamperHistogramStore::new(),
+            strength_of_field: StrengthOfFieldStore::new(),
+            pit_loss: pit_stops::DEFAULT_PIT_LOSS_S,
+            finish_simulator: None,
+            drive_time_rules: DriveTimeRules::default(),
+            bookmarks: None,
+            dead_bands: DeadBands::default(),
+            session_clock: None,
+            session_info_keeper: false,
+            driver_db: None,
+            data_api: None,
+            highlights_dir: None,
+            auto_export: None,
+        }
+    }
+}
+
+/// Magnitude of the 3D velocity vector
+struct VelocityMagnitude;
+
+impl DerivedChannel for VelocityMagnitude {
+    fn name(&self) -> &'static str {
+        "velocity"
+    }
+
+    fn compute(&mut self, data: &mut TelemetryData) {
+        let (vx, vy, vz) = (data.VelocityX, data.VelocityY, data.VelocityZ);
+        data.velocity_ms = (vx * vx + vy * vy + vz * vz).sqrt();
+    }
+}
+
+/// Lateral and longitudinal acceleration expressed in g
+struct GForces;
+
+impl DerivedChannel for GForces {
+    fn name(&self) -> &'static str {
+        "g_forces"
+    }
+
+    fn compute(&mut self, data: &mut TelemetryData) {
+        data.g_force_lat = data.lateral_accel_ms2 / GRAVITY_MS2;
+        data.g_force_lon = data.longitudinal_accel_ms2 / GRAVITY_MS2;
+    }
+}
+
+/// Body slip angle from the local velocity components
+struct SlipAngle;
+
+impl DerivedChannel for SlipAngle {
+    fn name(&self) -> &'static str {
+        "slip_angle"
+    }
+
+    fn compute(&mut self, data: &mut TelemetryData) {
+        // Only meaningful when the car is actually moving forwards or backwards
+        data.car_slip_angle_deg = if data.VelocityX.abs() > 0.1 {
+            (data.VelocityY / data.VelocityX).atan() * 180.0 / PI
+        } else {
+            0.0
+        };
+    }
+}
+
+/// Average fuel used per lap over the last few completed laps
+#[derive(Default)]
+struct FuelPerLap {
+    last_lap: Option<i32>,
+    fuel_at_lap_start: f32,
+    recent_laps: Vec<f32>,
+    // The current lap was at least partly run behind the pace car
+    lap_paced: bool,
+}
+
+impl DerivedChannel for FuelPerLap {
+    fn name(&self) -> &'static str {
+        "fuel_per_lap"
+    }
+
+    fn dependencies(&self) -> &'static [&'static str] {
+        &["race_phase"]
+    }
+
+    fn compute(&mut self, data: &mut TelemetryData) {
+        let lap = data.lap_completed;
+        let fuel = data.fuel_level;
+
+        match self.last_lap {
+            Some(last_lap) if lap == last_lap + 1 => {
+                let used = self.fuel_at_lap_start - fuel;
+                // Laps with a refuel, no consumption or spent pacing don't tell us anything
+                if used > 0.0 && !data.on_pit_road && !self.lap_paced {
+                    self.recent_laps.push(used);
+                    if self.recent_laps.len() > FUEL_LAPS_AVERAGED {
+                        self.recent_laps.remove(0);
+                    }
+                }
+                self.fuel_at_lap_start = fuel;
+                self.lap_paced = false;
+            },
+            Some(last_lap) if lap == last_lap => {
+                // Refueling mid-lap restarts the measurement for this lap
+                if fuel > self.fuel_at_lap_start {
+                    self.fuel_at_lap_start = fuel;
+                }
+            },
+            _ => {
+                // First frame, a skipped lap or a new session
+                self.fuel_at_lap_start = fuel;
+                self.lap_paced = false;
+                if self.last_lap.is_some_and(|last_lap| lap < last_lap) {
+                    self.recent_laps.clear();
+                }
+            }
+        }
+        self.last_lap = Some(lap);
+        self.lap_paced |= data.race_phase.is_some_and(|phase| phase.is_pacing());
+
+        data.fuel_per_lap = if self.recent_laps.is_empty() {
+            0.0
+        } else {
+            self.recent_laps.iter().sum::<f32>() / self.recent_laps.len() as f32
+        };
+    }
+}
+
+/// Gaps and positions from checkpoint timing (see gap_calculator)
+struct Gaps;
+
+impl DerivedChannel for Gaps {
+    fn name(&self) -> &'static str {
+        "gaps"
+    }
+
+    fn dependencies(&self) -> &'static [&'static str] {
+        &["race_phase"]
+    }
+
+    fn compute(&mut self, data: &mut TelemetryData) {
+        // Order on formation laps is the grid, not track progress
+        if data.race_phase.is_some_and(|phase| phase.suppresses_gaps()) {
+            return;
+        }
+        // The gap calculator needs both per-car progress arrays
+        if data.CarIdxLapDistPct.is_some() && data.CarIdxLapCompleted.is_some() {
+            gap_calculator::calculate_gaps(data);
+        }
+    }
+}
@@ -0,0 +1,147 @@
+//! Deterministic mode, for regression testing the derived channels against recordings.
+//!
+//! `speedforge analyze <recording> [--out <file>] [--groups timing,track]` runs a
+//! recording (JSON lines of frames, or an .ibt file) through the derived channels and
+//! writes each resulting frame as one JSON line, then prints a digest of the output. In
+//! this mode the channels only see the recorded frames:
+//!
+//! - random sampling (finish_probabilities.rs) starts from `SEED`
+//! - simulations run inline every interval of race time instead of on a worker thread
+//! - the local metadata cache and corner database are neither read nor written
+//! - nothing reads the wall clock; time comes from the frames through the race clock
+//!
+//! The channels are the server's (`DerivedPipeline::server`) with their defaults, finish
+//! probabilities and replay bookmarks included, less those that read the wall clock or do
+//! IO: the session clock, highlights, auto-export, the driver database, the data API and
+//! the session info copy for diagnostics.
+//!
+//! Analyzing the same recording twice therefore gives byte-identical output, on any
+//! machine, and a changed digest means the strategy, gap or sector channels behave
+//! differently. Objects are written with sorted keys so map order can't differ.
+
+use crate::derived::{DerivedPipeline, ServerChannels};
+use crate::field_groups;
+use crate::finish_probabilities::{self, FinishSimulator};
+use crate::replay_bookmarks::ReplayBookmarks;
+use crate::session_compare;
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+/// Seed for every random number generator while deterministic
+pub const SEED: u64 = 0x5EED_F0A6_E2A1_C3D7;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Switch the process to deterministic mode; channels created afterwards follow it
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Whether derived channels must only depend on the frames they are given
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+// FNV-1a, stable across builds unlike std's hasher
+struct Digest(u64);
+
+impl Digest {
+    fn new() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ byte as u64).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+/// The server's channels with their defaults, without the wall clock and IO ones; also
+/// what `run-scenario` runs
+pub fn pipeline() -> Result<DerivedPipeline, String> {
+    let simulator = FinishSimulator::new(finish_probabilities::DEFAULT_ITERATIONS);
+    simulator.run_inline(Duration::from_secs(finish_probabilities::DEFAULT_INTERVAL_S));
+    DerivedPipeline::server(ServerChannels {
+        finish_simulator: Some(simulator),
+        bookmarks: Some(ReplayBookmarks::new()),
+        ..ServerChannels::default()
+    })
+}
+
+fn analyze(path: &Path, groups: &[String], out: &mut dyn Write) -> Result<(usize, u64), Box<dyn Error>> {
+    let mut pipeline = pipeline()?;
+    let mut digest = Digest::new();
+    let mut frames = 0usize;
+    let mut session_info = String::new();
+    for frame in session_compare::read_frames(path)? {
+        let mut frame = frame?;
+        // Recordings carry the session info on the frames where it changed only
+        if frame.session_info.is_empty() {
+            frame.session_info = session_info.clone();
+        } else {
+            session_info = frame.session_info.clone();
+        }
+        // Outputs attached only on some frames, recorded from an earlier run
+        frame.events.clear();
+        frame.finish_probabilities = None;
+
+        pipeline.evaluate(&mut frame);
+        // Through Value so every object has sorted keys
+        let value = field_groups::filter(&serde_json::to_value(&frame)?, groups);
+        let mut line = serde_json::to_vec(&value)?;
+        line.push(b'\n');
+        digest.update(&line);
+        out.write_all(&line)?;
+        frames += 1;
+    }
+    out.flush()?;
+    if frames == 0 {
+        return Err(format!("{} contains no telemetry frames", path.display()).into());
+    }
+    Ok((frames, digest.0))
+}
+
+/// `speedforge analyze <recording> [--out <file>] [--groups a,b]`: run a recording through
+/// the derived channels in deterministic mode; exits 1 when it can't be read
+pub fn run_cli(args: &[String]) -> i32 {
+    let Some(path) = args.first().filter(|arg| !arg.starts_with("--")) else {
+        eprintln!("Usage: speedforge analyze <recording.jsonl|file.ibt> [--out <file>] [--groups <group,...>]");
+        return 2;
+    };
+    let out_path = crate::arg_value(args, "--out");
+    let groups: Vec<String> = crate::arg_value(args, "--groups")
+        .map(|list| list.split(',').map(str::trim).filter(|group| !group.is_empty()).map(str::to_string).collect())
+        .unwrap_or_default();
+    if let Some(unknown) = groups.iter().find(|group| !field_groups::is_known(group)) {
+        eprintln!("Unknown field group '{}' (available: {})", unknown, field_groups::names().join(", "));
+        return 2;
+    }
+
+    enable();
+    let result = match out_path {
+        Some(out_path) => match File::create(&out_path) {
+            Ok(file) => analyze(Path::new(path), &groups, &mut BufWriter::new(file)),
+            Err(e) => {
+                eprintln!("Failed to create {}: {}", out_path, e);
+                return 1;
+            }
+        },
+        None => analyze(Path::new(path), &groups, &mut BufWriter::new(io::stdout().lock())),
+    };
+    match result {
+        Ok((frames, digest)) => {
+            // On stderr, so it stays out of output written to stdout
+            eprintln!("{} frames, digest {:016x}", frames, digest);
+            0
+        },
+        Err(e) => {
+            eprintln!("Failed to analyze {}: {}", path, e);
+            1
+        }
+    }
+}
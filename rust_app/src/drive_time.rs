@@ -0,0 +1,192 @@
+use crate::derived::DerivedChannel;
+use crate::events;
+use crate::session_yaml;
+use crate::telemetry_fields::TelemetryData;
+use serde::{Serialize, Deserialize};
+use serde_json::json;
+
+/// Fair-share rules for team events
+#[derive(Clone, Debug)]
+pub struct DriveTimeRules {
+    /// Minimum share of the race each driver must drive (percent)
+    pub min_share_pct: f32,
+    /// Maximum share of the race a single driver may drive (percent), if limited
+    pub max_share_pct: Option<f32>,
+    /// How early to warn before a limit can no longer be met
+    pub warning_margin_s: f32,
+}
+
+impl Default for DriveTimeRules {
+    fn default() -> Self {
+        Self {
+            min_share_pct: 25.0,
+            max_share_pct: None,
+            warning_margin_s: 600.0,
+        }
+    }
+}
+
+/// Drive time of one driver of the player's team
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct DriverTime {
+    pub user_name: String,
+    pub user_id: i64,
+    pub seconds: f32,
+    /// Share of the race duration driven so far
+    pub share_pct: f32,
+    /// Driving still needed to reach the minimum share
+    pub seconds_needed: f32,
+    /// "ok", "needs_time", "at_risk", "near_maximum" or "over_maximum"
+    pub status: String,
+}
+
+/// Drive time per driver against the fair-share rules
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct DriveTimeData {
+    pub current_driver: String,
+    /// Elapsed plus remaining race time the shares are measured against
+    pub race_duration_s: f32,
+    pub min_share_pct: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_share_pct: Option<f32>,
+    pub drivers: Vec<DriverTime>,
+}
+
+/// Derived channel that accumulates drive time for each driver of the player's car
+pub struct DriveTimeTracker {
+    rules: DriveTimeRules,
+    drivers: Vec<DriverTime>,
+    current_driver: Option<usize>,
+    team_session: bool,
+    last_session_info: String,
+    start_time: Option<f64>,
+}
+
+impl DriveTimeTracker {
+    pub fn new(rules: DriveTimeRules) -> Self {
+        Self {
+            rules,
+            drivers: Vec::new(),
+            current_driver: None,
+            team_session: false,
+            last_session_info: String::new(),
+            start_time: None,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.drivers.clear();
+        self.current_driver = None;
+        self.start_time = None;
+    }
+
+    // Re-read who is in the player's car when the session info changes (driver swaps).
+    // Returns the new driver's name when the car changed hands
+    fn update_driver(&mut self, data: &TelemetryData) -> Option<String> {
+        if data.session_info == self.last_session_info {
+            return None;
+        }
+        self.last_session_info = data.session_info.clone();
+        self.team_session = session_yaml::number(&data.session_info, "TeamRacing").is_some_and(|team| team > 0.0);
+
+        let entry = session_yaml::drivers(&data.session_info)
+            .into_iter()
+            .find(|driver| driver.car_idx == data.player_car_idx && !driver.user_name.is_empty())?;
+
+        let index = match self.drivers.iter().position(|d| d.user_id == entry.user_id && d.user_name == entry.user_name) {
+            Some(index) => index,
+            None => {
+                self.drivers.push(DriverTime {
+                    user_name: entry.user_name,
+                    user_id: entry.user_id,
+                    ..DriverTime::default()
+                });
+                self.drivers.len() - 1
+            }
+        };
+        let previous = self.current_driver.replace(index);
+        if previous.is_some() && previous != Some(index) {
+            Some(self.drivers[index].user_name.clone())
+        } else {
+            None
+        }
+    }
+
+    // Work out each driver's share and status. Returns drivers whose status got worse
+    fn update_statuses(&mut self, elapsed: f32, remaining: f32) -> Vec<(String, String)> {
+        let race_duration = elapsed + remaining;
+        let min_seconds = race_duration * self.rules.min_share_pct / 100.0;
+        let max_seconds = self.rules.max_share_pct.map(|pct| race_duration * pct / 100.0);
+
+        // Time everyone still needs must fit in what's left of the race
+        let total_needed: f32 = self.drivers.iter().map(|d| (min_seconds - d.seconds).max(0.0)).sum();
+        let schedule_tight = total_needed > remaining - self.rules.warning_margin_s;
+
+        let mut worsened = Vec::new();
+        for (index, driver) in self.drivers.iter_mut().enumerate() {
+            driver.share_pct = if race_duration > 0.0 { driver.seconds / race_duration * 100.0 } else { 0.0 };
+            driver.seconds_needed = (min_seconds - driver.seconds).max(0.0);
+
+            let is_driving = self.current_driver == Some(index);
+            let status = match max_seconds {
+                Some(max) if driver.seconds >= max => "over_maximum",
+                Some(max) if is_driving && driver.seconds >= max - self.rules.warning_margin_s => "near_maximum",
+                _ if driver.seconds_needed > 0.0 && schedule_tight => "at_risk",
+                _ if driver.seconds_needed > 0.0 => "needs_time",
+                _ => "ok",
+            };
+            if status != driver.status && status != "ok" && status != "needs_time" {
+                worsened.push((driver.user_name.clone(), status.to_string()));
+            }
+            driver.status = status.to_string();
+        }
+        worsened
+    }
+}
+
+impl DerivedChannel for DriveTimeTracker {
+    fn name(&self) -> &'static str {
+        "drive_time"
+    }
+
+    fn compute(&mut self, data: &mut TelemetryData) {
+        if data.race_clock.session_reset {
+            self.reset();
+        }
+        let delta = data.race_clock.dt;
+
+        if let Some(driver) = self.update_driver(data) {
+            events::emit(data, "driver_swap", json!({ "driver": driver }));
+        }
+        if !self.team_session {
+            return;
+        }
+        let Some(current) = self.current_driver else {
+            return;
+        };
+
+        // Count time while the car is in the world (including pit stops)
+        let in_world = data.CarIdxLapDistPct
+            .as_ref()
+            .and_then(|pcts| pcts.get(data.player_car_idx as usize))
+            .is_some_and(|&pct| pct >= 0.0);
+        let start_time = *self.start_time.get_or_insert(data.race_clock.time);
+        if in_world {
+            self.drivers[current].seconds += delta;
+        }
+
+        let elapsed = (data.race_clock.time - start_time) as f32;
+        let remaining = data.session_time_remain.max(0.0);
+        for (driver, status) in self.update_statuses(elapsed, remaining) {
+            events::emit(data, "drive_time_warning", json!({ "driver": driver, "status": status }));
+        }
+
+        data.drive_time = Some(DriveTimeData {
+            current_driver: self.drivers[current].user_name.clone(),
+            race_duration_s: elapsed + remaining,
+            min_share_pct: self.rules.min_share_pct,
+            max_share_pct: self.rules.max_share_pct,
+            drivers: self.drivers.clone(),
+        });
+    }
+}
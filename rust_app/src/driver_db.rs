@@ -0,0 +1,277 @@
+//! Drivers met in earlier sessions and how the player fared against them.
+//!
+//! Every driver in the session info is recorded by customer ID with their name, iRating
+//! and license. When a race session finishes, the final positions are compared with the
+//! player's to count head-to-head results. The database is a JSON file like the metadata
+//! cache; live frames carry a `driver_stats` annotation for each car in the session.
+
+use crate::derived::DerivedChannel;
+use crate::file_io;
+use crate::race_phase::Phase;
+use crate::session_yaml;
+use crate::telemetry_fields::TelemetryData;
+use serde::{Serialize, Deserialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Database file name, placed in the instance directory
+pub const DEFAULT_DRIVER_DB_PATH: &str = "drivers.json";
+
+/// Everything known about one driver
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct DriverRecord {
+    pub user_id: i64,
+    pub name: String,
+    pub last_i_rating: i32,
+    pub average_i_rating: f32,
+    pub last_license: String,
+    /// Sessions the driver was seen in, and races that finished with both of you classified
+    pub sessions: u32,
+    pub races_together: u32,
+    /// Races the player finished ahead / behind
+    pub wins: u32,
+    pub losses: u32,
+    /// "YYYY-MM-DD"
+    pub first_seen: String,
+    pub last_seen: String,
+    last_subsession_id: i64,
+}
+
+/// History with the driver of one car, published with every frame
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DriverAnnotation {
+    pub car_idx: i32,
+    pub user_id: i64,
+    pub sessions: u32,
+    pub races_together: u32,
+    pub wins: u32,
+    pub losses: u32,
+    pub average_i_rating: f32,
+    /// e.g. "You've raced this driver 12 times, 7 wins"
+    pub summary: String,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+struct DatabaseFile {
+    drivers: BTreeMap<i64, DriverRecord>,
+}
+
+/// JSON backed database of drivers met in earlier sessions
+pub struct DriverDatabase {
+    path: PathBuf,
+    db: DatabaseFile,
+}
+
+impl DriverDatabase {
+    /// Load the database, starting empty when the file doesn't exist or can't be read
+    pub fn load(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref().to_path_buf();
+        let db = match fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+                tracing::warn!("Ignoring unreadable driver database {}: {}", path.display(), e);
+                DatabaseFile::default()
+            }),
+            Err(_) => DatabaseFile::default(),
+        };
+        Self { path, db }
+    }
+
+    // Same temporary file and rename as the metadata cache, instances may share the file
+    fn save(&self) {
+        match serde_json::to_string_pretty(&self.db) {
+            Ok(contents) => file_io::write_atomic(self.path.clone(), contents, "driver database"),
+            Err(e) => tracing::error!("Failed to write driver database {}: {}", self.path.display(), e),
+        }
+    }
+
+    pub fn driver(&self, user_id: i64) -> Option<&DriverRecord> {
+        self.db.drivers.get(&user_id)
+    }
+
+    /// Record the drivers of a session; each is counted once per subsession
+    pub fn record_session(&mut self, drivers: &[session_yaml::DriverEntry], subsession_id: i64, player_user_id: i64) {
+        let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+        let mut changed = false;
+        for entry in drivers {
+            // Pace car and spectators have no customer ID of their own
+            if entry.user_id <= 0 || entry.user_id == player_user_id {
+                continue;
+            }
+            let record = self.db.drivers.entry(entry.user_id).or_insert_with(|| DriverRecord {
+                user_id: entry.user_id,
+                first_seen: today.clone(),
+                ..DriverRecord::default()
+            });
+            if record.last_subsession_id == subsession_id && subsession_id != 0 {
+                continue;
+            }
+            record.last_subsession_id = subsession_id;
+            record.name = entry.user_name.clone();
+            record.last_license = entry.license.clone();
+            record.sessions += 1;
+            if entry.i_rating > 0 {
+                record.last_i_rating = entry.i_rating;
+                record.average_i_rating += (entry.i_rating as f32 - record.average_i_rating) / record.sessions as f32;
+            }
+            record.last_seen = today.clone();
+            changed = true;
+        }
+        if changed {
+            self.save();
+        }
+    }
+
+    /// Count head-to-head results of a finished race from (user_id, finishing position)
+    pub fn record_race(&mut self, results: &[(i64, i32)], player_user_id: i64) {
+        let Some(&(_, player_position)) = results.iter().find(|(user_id, _)| *user_id == player_user_id) else {
+            return;
+        };
+        for &(user_id, position) in results {
+            let Some(record) = self.db.drivers.get_mut(&user_id) else {
+                continue;
+            };
+            if user_id == player_user_id {
+                continue;
+            }
+            record.races_together += 1;
+            if player_position < position {
+                record.wins += 1;
+            } else {
+                record.losses += 1;
+            }
+        }
+        self.save();
+    }
+}
+
+impl DriverRecord {
+    fn summary(&self) -> String {
+        match self.races_together {
+            0 if self.sessions <= 1 => "First time you've met this driver".to_string(),
+            0 => format!("Seen in {} sessions, never raced", self.sessions),
+            1 => format!("You've raced this driver once, {}", if self.wins > 0 { "a win" } else { "a loss" }),
+            races => format!("You've raced this driver {} times, {} wins", races, self.wins),
+        }
+    }
+}
+
+/// Derived channel that keeps the database up to date and annotates each car's driver
+pub struct DriverStatsAnnotator {
+    db: DriverDatabase,
+    last_session_info: String,
+    drivers: Vec<session_yaml::DriverEntry>,
+    annotations: Vec<DriverAnnotation>,
+    /// Races already scored, by subsession and session number
+    scored: HashSet<(i64, i32)>,
+    /// Finishing order held while the checkered flag is out: (car_idx, position)
+    finishing: Option<Vec<(i32, i32)>>,
+    session_num: i32,
+}
+
+impl DriverStatsAnnotator {
+    pub fn new(db: DriverDatabase) -> Self {
+        Self {
+            db,
+            last_session_info: String::new(),
+            drivers: Vec::new(),
+            annotations: Vec::new(),
+            scored: HashSet::new(),
+            finishing: None,
+            session_num: -1,
+        }
+    }
+
+    fn player_user_id(&self, player_car_idx: i32) -> i64 {
+        self.drivers.iter().find(|d| d.car_idx == player_car_idx).map_or(0, |d| d.user_id)
+    }
+
+    fn annotate(&mut self) {
+        self.annotations = self
+            .drivers
+            .iter()
+            .filter_map(|entry| {
+                let record = self.db.driver(entry.user_id)?;
+                Some(DriverAnnotation {
+                    car_idx: entry.car_idx,
+                    user_id: entry.user_id,
+                    sessions: record.sessions,
+                    races_together: record.races_together,
+                    wins: record.wins,
+                    losses: record.losses,
+                    average_i_rating: record.average_i_rating,
+                    summary: record.summary(),
+                })
+            })
+            .collect();
+    }
+
+    // Score the held finishing order once the race is over
+    fn score_race(&mut self, subsession_id: i64, player_car_idx: i32) {
+        let Some(finishing) = self.finishing.take() else {
+            return;
+        };
+        if !self.scored.insert((subsession_id, self.session_num)) {
+            return;
+        }
+        let results: Vec<(i64, i32)> = finishing
+            .iter()
+            .filter_map(|&(car_idx, position)| {
+                self.drivers.iter().find(|d| d.car_idx == car_idx).map(|d| (d.user_id, position))
+            })
+            .collect();
+        let player = self.player_user_id(player_car_idx);
+        self.db.record_race(&results, player);
+        self.annotate();
+    }
+}
+
+impl DerivedChannel for DriverStatsAnnotator {
+    fn name(&self) -> &'static str {
+        "driver_stats"
+    }
+
+    fn dependencies(&self) -> &'static [&'static str] {
+        &["race_phase", "gaps"]
+    }
+
+    fn compute(&mut self, data: &mut TelemetryData) {
+        let subsession_id = session_yaml::number(&data.session_info, "SubSessionID").map_or(0, |id| id as i64);
+        if data.session_num != self.session_num || data.race_clock.session_reset {
+            self.score_race(subsession_id, data.player_car_idx);
+            self.finishing = None;
+            self.session_num = data.session_num;
+        }
+        if data.session_info != self.last_session_info {
+            self.last_session_info = data.session_info.clone();
+            self.drivers = session_yaml::drivers(&data.session_info);
+            let player = self.player_user_id(data.player_car_idx);
+            self.db.record_session(&self.drivers, subsession_id, player);
+            self.annotate();
+        }
+
+        let is_race = session_yaml::session_type(&data.session_info, data.session_num) == Some("Race");
+        match data.race_phase.map(|phase| phase.phase) {
+            Some(Phase::Checkered) if is_race => {
+                if let Some(positions) = data.CarIdxPosition.as_ref() {
+                    self.finishing = Some(
+                        positions
+                            .iter()
+                            .enumerate()
+                            .filter(|&(_, &position)| position > 0)
+                            .map(|(idx, &position)| (idx as i32, position))
+                            .collect(),
+                    );
+                }
+            },
+            Some(Phase::CoolDown) => self.score_race(subsession_id, data.player_car_idx),
+            _ => {},
+        }
+
+        if !self.annotations.is_empty() {
+            data.driver_stats = Some(self.annotations.clone());
+        }
+    }
+}
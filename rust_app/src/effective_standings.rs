@@ -0,0 +1,140 @@
+use crate::derived::DerivedChannel;
+use crate::pit_stops::PitTracker;
+use crate::telemetry_fields::{TelemetryData, FLAG_GREEN};
+use serde::{Serialize, Deserialize};
+
+// A pit cycle that hasn't finished after this many laps (of typical pace) is abandoned
+const CYCLE_TIMEOUT_LAPS: f32 = 6.0;
+// Lap time used to size the timeout when no pace is known yet
+const FALLBACK_LAP_TIME_S: f32 = 120.0;
+
+/// Projected running order of one car once everyone has made their stop
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct EffectiveStanding {
+    pub car_idx: i32,
+    pub position: i32,
+    pub effective_position: i32,
+    /// Gap to the leader plus the pit loss still to come
+    pub effective_gap: f32,
+    pub pitted_in_cycle: bool,
+}
+
+/// Standings projected through a pit cycle, published while one is in progress
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct EffectiveStandings {
+    /// SessionTime the cycle started (first stop under green)
+    pub cycle_start: f32,
+    /// Pit loss applied to cars that still have to stop
+    pub pit_loss: f32,
+    /// True when the pit loss was measured from stops in this session
+    pub pit_loss_measured: bool,
+    pub cars: Vec<EffectiveStanding>,
+}
+
+/// Derived channel that projects positions during pit cycles
+pub struct EffectiveStandingsProjector {
+    pits: PitTracker,
+    cycle_start: Option<f32>,
+}
+
+impl EffectiveStandingsProjector {
+    pub fn new(default_pit_loss: f32) -> Self {
+        Self {
+            pits: PitTracker::new(default_pit_loss),
+            cycle_start: None,
+        }
+    }
+
+    fn typical_lap_time(&self) -> f32 {
+        let mut paces: Vec<f32> = self.pits.cars().iter().filter_map(|car| car.pace()).collect();
+        if paces.is_empty() {
+            return FALLBACK_LAP_TIME_S;
+        }
+        paces.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        paces[paces.len() / 2]
+    }
+
+    fn pitted_in_cycle(&self, car_idx: usize, cycle_start: f32) -> bool {
+        self.pits
+            .car(car_idx)
+            .and_then(|car| car.last_entry_time)
+            .is_some_and(|entry| entry >= cycle_start)
+    }
+}
+
+impl DerivedChannel for EffectiveStandingsProjector {
+    fn name(&self) -> &'static str {
+        "effective_standings"
+    }
+
+    fn dependencies(&self) -> &'static [&'static str] {
+        &["gaps"]
+    }
+
+    fn compute(&mut self, data: &mut TelemetryData) {
+        let entries = self.pits.update(data);
+
+        // Stops under caution bunch the field up and aren't a strategy cycle
+        let is_green = data.session_flags & FLAG_GREEN != 0;
+        if self.cycle_start.is_none() && is_green && !entries.is_empty() {
+            self.cycle_start = Some(data.SessionTime);
+        }
+        let Some(cycle_start) = self.cycle_start else {
+            return;
+        };
+
+        let (Some(lap_dist), Some(positions), Some(gaps)) = (
+            data.CarIdxLapDistPct.as_ref(),
+            data.CarIdxPosition.as_ref(),
+            data.CarIdxGapToLeader.as_ref(),
+        ) else {
+            return;
+        };
+
+        // Cars not in the world report a negative lap distance
+        let running: Vec<usize> = lap_dist
+            .iter()
+            .enumerate()
+            .filter(|(idx, pct)| **pct >= 0.0 && positions.get(*idx).is_some_and(|&p| p > 0))
+            .map(|(idx, _)| idx)
+            .collect();
+
+        let everyone_pitted = running.iter().all(|&idx| self.pitted_in_cycle(idx, cycle_start));
+        let timed_out = data.SessionTime - cycle_start > CYCLE_TIMEOUT_LAPS * self.typical_lap_time();
+        if everyone_pitted || timed_out {
+            self.cycle_start = None;
+            return;
+        }
+
+        let pit_loss = self.pits.pit_loss();
+        let mut cars: Vec<EffectiveStanding> = running
+            .iter()
+            .map(|&idx| {
+                let pitted = self.pitted_in_cycle(idx, cycle_start);
+                let gap = gaps.get(idx).copied().unwrap_or(0.0);
+                EffectiveStanding {
+                    car_idx: idx as i32,
+                    position: positions[idx],
+                    effective_position: 0,
+                    effective_gap: if pitted { gap } else { gap + pit_loss },
+                    pitted_in_cycle: pitted,
+                }
+            })
+            .collect();
+        cars.sort_by(|a, b| a.effective_gap.partial_cmp(&b.effective_gap).unwrap_or(std::cmp::Ordering::Equal));
+
+        // Express gaps relative to the projected leader
+        let leader_gap = cars.first().map_or(0.0, |car| car.effective_gap);
+        for (rank, car) in cars.iter_mut().enumerate() {
+            car.effective_position = rank as i32 + 1;
+            car.effective_gap -= leader_gap;
+        }
+
+        data.effective_standings = Some(EffectiveStandings {
+            cycle_start,
+            pit_loss,
+            pit_loss_measured: self.pits.is_measured(),
+            cars,
+        });
+    }
+}
@@ -0,0 +1,251 @@
+//! Passphrase encryption for recordings and session bundles.
+//!
+//! Encrypted files start with a magic header, then the Argon2 salt and the
+//! ChaCha20-Poly1305 nonce, then the sealed contents. Files written over time, such as
+//! live recordings, use a second header followed by the salt and a series of sealed
+//! chunks (`SealedWriter`), so they can be appended to without holding them in memory.
+//! Readers check the header, so plain and encrypted files can sit side by side in a
+//! shared folder. The passphrase comes from the SPEEDFORGE_PASSPHRASE environment
+//! variable so it never shows up in a command line. Only available when built with the
+//! `encryption` feature.
+
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Cursor, Write};
+use std::path::Path;
+
+/// Environment variable holding the passphrase
+pub const PASSPHRASE_ENV: &str = "SPEEDFORGE_PASSPHRASE";
+
+const MAGIC: &[u8; 8] = b"SFENC1\0\0";
+const CHUNKED_MAGIC: &[u8; 8] = b"SFENC2\0\0";
+/// Plaintext collected before `SealedWriter` seals a chunk
+pub const CHUNK_BYTES: usize = 64 * 1024;
+#[cfg_attr(not(feature = "encryption"), allow(dead_code))]
+const SALT_LEN: usize = 16;
+#[cfg_attr(not(feature = "encryption"), allow(dead_code))]
+const NONCE_LEN: usize = 12;
+
+/// Passphrase from the environment, if set
+pub fn passphrase() -> Option<String> {
+    std::env::var(PASSPHRASE_ENV).ok().filter(|value| !value.is_empty())
+}
+
+pub fn is_encrypted(contents: &[u8]) -> bool {
+    contents.starts_with(MAGIC) || contents.starts_with(CHUNKED_MAGIC)
+}
+
+#[cfg(feature = "encryption")]
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<chacha20poly1305::Key, String> {
+    let mut key = chacha20poly1305::Key::default();
+    argon2::Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| format!("Key derivation failed: {}", e))?;
+    Ok(key)
+}
+
+/// Seal `plaintext` with a key derived from `passphrase`
+#[cfg(feature = "encryption")]
+pub fn encrypt(plaintext: &[u8], passphrase: &str) -> Result<Vec<u8>, String> {
+    use chacha20poly1305::aead::{Aead, AeadCore, OsRng, rand_core::RngCore};
+    use chacha20poly1305::{ChaCha20Poly1305, KeyInit};
+
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let cipher = ChaCha20Poly1305::new(&derive_key(passphrase, &salt)?);
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let sealed = cipher.encrypt(&nonce, plaintext).map_err(|_| "Encryption failed".to_string())?;
+
+    let mut output = Vec::with_capacity(MAGIC.len() + SALT_LEN + NONCE_LEN + sealed.len());
+    output.extend_from_slice(MAGIC);
+    output.extend_from_slice(&salt);
+    output.extend_from_slice(&nonce);
+    output.extend_from_slice(&sealed);
+    Ok(output)
+}
+
+#[cfg(not(feature = "encryption"))]
+pub fn encrypt(_plaintext: &[u8], _passphrase: &str) -> Result<Vec<u8>, String> {
+    Err("Encryption needs a build with the encryption feature".to_string())
+}
+
+/// Open the output of `encrypt`; a wrong passphrase and a damaged file both fail here
+#[cfg(feature = "encryption")]
+pub fn decrypt(contents: &[u8], passphrase: &str) -> Result<Vec<u8>, String> {
+    use chacha20poly1305::aead::Aead;
+    use chacha20poly1305::{ChaCha20Poly1305, KeyInit, Nonce};
+
+    let header_len = MAGIC.len() + SALT_LEN + NONCE_LEN;
+    if !contents.starts_with(MAGIC) || contents.len() < header_len {
+        return Err("Not an encrypted speedforge file".to_string());
+    }
+    let salt = &contents[MAGIC.len()..MAGIC.len() + SALT_LEN];
+    let nonce = Nonce::from_slice(&contents[MAGIC.len() + SALT_LEN..header_len]);
+    let cipher = ChaCha20Poly1305::new(&derive_key(passphrase, salt)?);
+    cipher
+        .decrypt(nonce, &contents[header_len..])
+        .map_err(|_| "Wrong passphrase or damaged file".to_string())
+}
+
+#[cfg(not(feature = "encryption"))]
+pub fn decrypt(_contents: &[u8], _passphrase: &str) -> Result<Vec<u8>, String> {
+    Err("File is encrypted; decrypting needs a build with the encryption feature".to_string())
+}
+
+// Key for a new chunked file, with the salt it was derived with
+#[cfg(feature = "encryption")]
+fn new_chunk_key(passphrase: &str) -> Result<([u8; SALT_LEN], [u8; 32]), String> {
+    use chacha20poly1305::aead::{OsRng, rand_core::RngCore};
+
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    Ok((salt, derive_key(passphrase, &salt)?.into()))
+}
+
+#[cfg(not(feature = "encryption"))]
+fn new_chunk_key(_passphrase: &str) -> Result<([u8; SALT_LEN], [u8; 32]), String> {
+    Err("Encryption needs a build with the encryption feature".to_string())
+}
+
+// One chunk: nonce, sealed length (u32 LE) and the sealed bytes. The chunk's index is
+// authenticated too, so chunks can't be reordered
+#[cfg(feature = "encryption")]
+fn seal_chunk(key: &[u8; 32], index: u64, plaintext: &[u8]) -> Result<Vec<u8>, String> {
+    use chacha20poly1305::aead::{Aead, AeadCore, OsRng, Payload};
+    use chacha20poly1305::{ChaCha20Poly1305, KeyInit};
+
+    let cipher = ChaCha20Poly1305::new(key.into());
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let sealed = cipher
+        .encrypt(&nonce, Payload { msg: plaintext, aad: &index.to_le_bytes() })
+        .map_err(|_| "Encryption failed".to_string())?;
+    let mut chunk = Vec::with_capacity(NONCE_LEN + 4 + sealed.len());
+    chunk.extend_from_slice(&nonce);
+    chunk.extend_from_slice(&(sealed.len() as u32).to_le_bytes());
+    chunk.extend_from_slice(&sealed);
+    Ok(chunk)
+}
+
+#[cfg(not(feature = "encryption"))]
+fn seal_chunk(_key: &[u8; 32], _index: u64, _plaintext: &[u8]) -> Result<Vec<u8>, String> {
+    Err("Encryption needs a build with the encryption feature".to_string())
+}
+
+/// Open a file written by `SealedWriter`. A chunk cut short at the end, as a crash
+/// leaves it, is left out; any other damage fails
+#[cfg(feature = "encryption")]
+pub fn decrypt_chunked(contents: &[u8], passphrase: &str) -> Result<Vec<u8>, String> {
+    use chacha20poly1305::aead::{Aead, Payload};
+    use chacha20poly1305::{ChaCha20Poly1305, KeyInit, Nonce};
+
+    let header_len = CHUNKED_MAGIC.len() + SALT_LEN;
+    if !contents.starts_with(CHUNKED_MAGIC) || contents.len() < header_len {
+        return Err("Not an encrypted speedforge file".to_string());
+    }
+    let cipher = ChaCha20Poly1305::new(&derive_key(passphrase, &contents[CHUNKED_MAGIC.len()..header_len])?);
+    let mut plaintext = Vec::new();
+    let mut rest = &contents[header_len..];
+    let mut index: u64 = 0;
+    while rest.len() >= NONCE_LEN + 4 {
+        let sealed_len = u32::from_le_bytes([rest[NONCE_LEN], rest[NONCE_LEN + 1], rest[NONCE_LEN + 2], rest[NONCE_LEN + 3]]) as usize;
+        let Some(sealed) = rest.get(NONCE_LEN + 4..NONCE_LEN + 4 + sealed_len) else {
+            break;
+        };
+        let opened = cipher
+            .decrypt(Nonce::from_slice(&rest[..NONCE_LEN]), Payload { msg: sealed, aad: &index.to_le_bytes() })
+            .map_err(|_| "Wrong passphrase or damaged file".to_string())?;
+        plaintext.extend_from_slice(&opened);
+        rest = &rest[NONCE_LEN + 4 + sealed_len..];
+        index += 1;
+    }
+    Ok(plaintext)
+}
+
+#[cfg(not(feature = "encryption"))]
+pub fn decrypt_chunked(_contents: &[u8], _passphrase: &str) -> Result<Vec<u8>, String> {
+    Err("File is encrypted; decrypting needs a build with the encryption feature".to_string())
+}
+
+/// Writer sealing what is written to it in chunks of `CHUNK_BYTES`, for files that grow
+/// over time. `flush` seals what has been collected so far, so flush rarely
+pub struct SealedWriter {
+    file: File,
+    key: [u8; 32],
+    buffer: Vec<u8>,
+    chunks: u64,
+}
+
+impl SealedWriter {
+    /// Start a new encrypted file at `path`
+    pub fn create(path: &Path, passphrase: &str) -> Result<Self, String> {
+        let (salt, key) = new_chunk_key(passphrase)?;
+        let mut file = File::options().create_new(true).write(true).open(path).map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+        file.write_all(CHUNKED_MAGIC)
+            .and_then(|()| file.write_all(&salt))
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        Ok(Self { file, key, buffer: Vec::with_capacity(CHUNK_BYTES), chunks: 0 })
+    }
+
+    fn seal(&mut self) -> io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        let chunk = seal_chunk(&self.key, self.chunks, &self.buffer).map_err(io::Error::other)?;
+        self.file.write_all(&chunk)?;
+        self.buffer.clear();
+        self.chunks += 1;
+        Ok(())
+    }
+}
+
+impl Write for SealedWriter {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(bytes);
+        if self.buffer.len() >= CHUNK_BYTES {
+            self.seal()?;
+        }
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.seal()?;
+        self.file.flush()
+    }
+}
+
+impl Drop for SealedWriter {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+/// Contents of a file, decrypted with the environment passphrase when it is encrypted
+pub fn read_file(path: &Path) -> Result<Vec<u8>, Box<dyn Error>> {
+    let contents = fs::read(path)?;
+    if !is_encrypted(&contents) {
+        return Ok(contents);
+    }
+    let passphrase = passphrase()
+        .ok_or_else(|| format!("{} is encrypted; set {} to read it", path.display(), PASSPHRASE_ENV))?;
+    let opened = if contents.starts_with(CHUNKED_MAGIC) { decrypt_chunked(&contents, &passphrase) } else { decrypt(&contents, &passphrase) };
+    Ok(opened.map_err(|e| format!("{}: {}", path.display(), e))?)
+}
+
+/// Line reader over a plain or encrypted file. Plain files are streamed; encrypted ones
+/// have to be read whole to check the authentication tag
+pub fn open_lines(path: &Path) -> Result<Box<dyn BufRead>, Box<dyn Error>> {
+    let mut file = BufReader::new(fs::File::open(path)?);
+    if !is_encrypted(file.fill_buf()?) {
+        return Ok(Box::new(file));
+    }
+    Ok(Box::new(Cursor::new(read_file(path)?)))
+}
+
+/// Write `contents`, encrypted when a passphrase is given
+pub fn write_file(path: &Path, contents: &[u8], passphrase: Option<&str>) -> Result<(), Box<dyn Error>> {
+    match passphrase {
+        Some(passphrase) => fs::write(path, encrypt(contents, passphrase)?)?,
+        None => fs::write(path, contents)?,
+    }
+    Ok(())
+}
@@ -0,0 +1,99 @@
+//! WebSocket endpoints, chosen by the request path of the upgrade.
+//!
+//! - `/telemetry` streams frames; only the session commands (`hello`, `time_sync`,
+//!   `set_channel_preset`, `keyframe`, `subscribe`, `set_rate`) are accepted.
+//! - `/events` streams just the events raised each frame, for overlays and bots that
+//!   don't need the full telemetry.
+//! - `/admin` is the command channel: every registered command, no stream unless asked
+//!   for with `hello`, and the API token is required (`Authorization: Bearer <token>` or
+//!   `?token=`). Without a configured token it is closed. Plain HTTP requests to `/admin`
+//!   get the web admin panel instead (see admin_panel.rs).
+//! - `/` keeps the original behavior (frames and every command) for existing clients.
+//!
+//! A plain `GET /events` (no upgrade) gets the same messages as Server-Sent Events, for
+//! browser overlays using `EventSource` and deployments behind proxies that don't pass
+//! WebSockets. Each message is one event whose data is the JSON a WebSocket client would
+//! get; the stream starts with `welcome`. It carries frames, events and the session info
+//! unless the query string picks otherwise, with the names `hello` takes:
+//! `?topics=telemetry,events&groups=timing&rate_hz=10&schema_version=3&channel_preset=simhub`.
+//!
+//! Any endpoint takes `?encoding=<codec>` (e.g. `msgpack`) to stream in that encoding from
+//! the first frame. The stream endpoints get a lower inbound message rate than the command
+//! channels.
+
+use crate::connection_limits::ConnectionLimits;
+use crate::frame_schema::{MIN_SCHEMA_VERSION, SCHEMA_VERSION};
+use crate::protocol::ClientOptions;
+
+/// Commands every endpoint answers; the server handles them itself
+pub const SESSION_COMMANDS: &[&str] = &["hello", "time_sync", "set_channel_preset", "keyframe", "subscribe", "set_rate"];
+/// Inbound message rate for the stream endpoints, which only need the session commands
+pub const STREAM_MAX_MESSAGES_PER_SEC: u32 = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Endpoint {
+    /// `/`, everything as before endpoints existed
+    Legacy,
+    Telemetry,
+    Events,
+    Admin,
+    /// `GET /events` without an upgrade, streamed as Server-Sent Events
+    EventStream,
+}
+
+impl Endpoint {
+    /// Endpoint for a request path, None when nothing is served there
+    pub fn from_path(path: &str) -> Option<Self> {
+        match path.trim_end_matches('/') {
+            "" => Some(Endpoint::Legacy),
+            "/telemetry" => Some(Endpoint::Telemetry),
+            "/events" => Some(Endpoint::Events),
+            "/admin" => Some(Endpoint::Admin),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Endpoint::Legacy => "/",
+            Endpoint::Telemetry => "/telemetry",
+            Endpoint::Events => "/events",
+            Endpoint::Admin => "/admin",
+            Endpoint::EventStream => "/events (SSE)",
+        }
+    }
+
+    pub fn requires_token(self) -> bool {
+        self == Endpoint::Admin
+    }
+
+    /// Whether registered commands (beyond the session commands) are answered here
+    pub fn allows_commands(self) -> bool {
+        matches!(self, Endpoint::Legacy | Endpoint::Admin)
+    }
+
+    /// Options a new client starts with; `hello` can change them
+    pub fn client_options(self) -> ClientOptions {
+        let topics = match self {
+            Endpoint::Legacy => vec!["telemetry".to_string()],
+            // Current frames leave the session info out, so it comes as its own message
+            Endpoint::Telemetry => vec!["telemetry".to_string(), "session_info".to_string()],
+            Endpoint::EventStream => vec!["telemetry".to_string(), "events".to_string(), "session_info".to_string()],
+            Endpoint::Events => vec!["events".to_string()],
+            Endpoint::Admin => Vec::new(),
+        };
+        // Clients of `/` predate schema negotiation and expect the original field names
+        let schema_version = match self {
+            Endpoint::Legacy => MIN_SCHEMA_VERSION,
+            _ => SCHEMA_VERSION,
+        };
+        ClientOptions { topics, schema_version, ..ClientOptions::default() }
+    }
+
+    pub fn max_messages_per_sec(self, limits: &ConnectionLimits) -> u32 {
+        match self {
+            Endpoint::Telemetry | Endpoint::Events | Endpoint::EventStream => limits.max_messages_per_sec.min(STREAM_MAX_MESSAGES_PER_SEC),
+            Endpoint::Legacy | Endpoint::Admin => limits.max_messages_per_sec,
+        }
+    }
+}
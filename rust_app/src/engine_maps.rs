@@ -0,0 +1,124 @@
+use crate::derived::DerivedChannel;
+use crate::laps;
+use crate::telemetry_fields::TelemetryData;
+use serde::{Serialize, Deserialize};
+use std::collections::BTreeMap;
+
+// Per-frame lap progress above this is a teleport (tow, reset), not driving
+const MAX_FRAME_PROGRESS: f32 = 0.05;
+// Distance in a map needed before its burn rate is reported
+const MIN_DISTANCE_LAPS: f32 = 0.5;
+
+/// Usage of one fuel mixture setting
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct EngineMapUsage {
+    pub map: i32,
+    /// Time in this map during the current stint
+    pub stint_time_s: f32,
+    /// Time in this map during the session
+    pub session_time_s: f32,
+    pub distance_laps: f32,
+    pub fuel_used_l: f32,
+    /// Observed burn rate; None until enough distance has been covered
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fuel_per_lap: Option<f32>,
+    /// Burn rate relative to the most used map, e.g. -0.04 burns 4% less
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub burn_vs_primary: Option<f32>,
+}
+
+/// Time and consumption per fuel mixture setting
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct EngineMapStats {
+    pub current_map: i32,
+    pub stint: u32,
+    pub maps: Vec<EngineMapUsage>,
+}
+
+/// Derived channel that attributes driving time and fuel burn to the selected fuel mixture
+pub struct EngineMapTracker {
+    maps: BTreeMap<i32, EngineMapUsage>,
+    stint: u32,
+    in_stint: bool,
+    last: Option<(f32, f32)>, // lap_dist_pct, fuel_level
+}
+
+impl EngineMapTracker {
+    pub fn new() -> Self {
+        Self {
+            maps: BTreeMap::new(),
+            stint: 0,
+            in_stint: false,
+            last: None,
+        }
+    }
+}
+
+impl DerivedChannel for EngineMapTracker {
+    fn name(&self) -> &'static str {
+        "engine_map_stats"
+    }
+
+    fn compute(&mut self, data: &mut TelemetryData) {
+        // Cars without an adjustable mixture don't report one
+        if data.fuel_mixture < 0 {
+            return;
+        }
+        if data.race_clock.session_reset {
+            *self = Self::new();
+        }
+        let previous = self.last.replace((data.lap_dist_pct, data.fuel_level));
+
+        if data.on_pit_road {
+            self.in_stint = false;
+        } else if !self.in_stint {
+            self.in_stint = true;
+            self.stint += 1;
+            for usage in self.maps.values_mut() {
+                usage.stint_time_s = 0.0;
+            }
+        }
+
+        let usage = self.maps.entry(data.fuel_mixture).or_insert_with(|| EngineMapUsage {
+            map: data.fuel_mixture,
+            ..EngineMapUsage::default()
+        });
+        if let Some((pct, fuel)) = previous {
+            let dt = data.race_clock.dt;
+            let progress = laps::signed_lap_pct_delta(pct, data.lap_dist_pct);
+            let burned = fuel - data.fuel_level;
+            if self.in_stint && dt > 0.0 {
+                usage.stint_time_s += dt;
+                usage.session_time_s += dt;
+                // Refuelling and resets would skew the burn rate, so skip those frames
+                if progress > 0.0 && progress < MAX_FRAME_PROGRESS && burned >= 0.0 && data.lap_dist_pct >= 0.0 {
+                    usage.distance_laps += progress;
+                    usage.fuel_used_l += burned;
+                }
+            }
+        }
+
+        let mut maps: Vec<EngineMapUsage> = self.maps.values().cloned().collect();
+        for usage in &mut maps {
+            usage.fuel_per_lap = (usage.distance_laps >= MIN_DISTANCE_LAPS)
+                .then(|| usage.fuel_used_l / usage.distance_laps);
+        }
+        let primary = maps
+            .iter()
+            .filter(|usage| usage.fuel_per_lap.is_some())
+            .max_by(|a, b| a.distance_laps.partial_cmp(&b.distance_laps).unwrap_or(std::cmp::Ordering::Equal))
+            .and_then(|usage| usage.fuel_per_lap)
+            .filter(|&rate| rate > 0.0);
+        if let Some(primary) = primary {
+            for usage in &mut maps {
+                usage.burn_vs_primary = usage.fuel_per_lap.map(|rate| rate / primary - 1.0);
+            }
+        }
+
+        data.engine_map_stats = Some(EngineMapStats {
+            current_map: data.fuel_mixture,
+            stint: self.stint,
+            maps,
+        });
+    }
+}
@@ -0,0 +1,161 @@
+use crate::derived::DerivedChannel;
+use crate::telemetry_fields::TelemetryData;
+use serde::{Serialize, Deserialize};
+
+// Temperatures above which the engine is considered to be running hot (°C)
+const OIL_TEMP_HOT_C: f32 = 120.0;
+const WATER_TEMP_HOT_C: f32 = 105.0;
+
+// Weights of each stress source; at 100% of stint time in every category the score is 100
+const WEIGHT_ABOVE_SHIFT: f32 = 0.25;
+const WEIGHT_REV_LIMITER: f32 = 0.30;
+const WEIGHT_WARNINGS: f32 = 0.25;
+const WEIGHT_OIL_HOT: f32 = 0.10;
+const WEIGHT_WATER_HOT: f32 = 0.10;
+
+// Number of finished stints kept for reporting
+const MAX_STINT_HISTORY: usize = 10;
+
+/// Time spent in each stressful state during a stint, with the resulting score
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct StintEngineStress {
+    pub stint: u32,
+    pub start_lap: i32,
+    pub driving_time_s: f32,
+    pub above_shift_rpm_s: f32,
+    pub rev_limiter_s: f32,
+    pub warnings_active_s: f32,
+    pub oil_temp_high_s: f32,
+    pub water_temp_high_s: f32,
+    pub max_oil_temp_c: f32,
+    pub max_water_temp_c: f32,
+    /// 0 (gentle) to 100 (abused)
+    pub score: f32,
+}
+
+impl StintEngineStress {
+    fn update_score(&mut self) {
+        if self.driving_time_s <= 0.0 {
+            self.score = 0.0;
+            return;
+        }
+        let weighted = WEIGHT_ABOVE_SHIFT * self.above_shift_rpm_s
+            + WEIGHT_REV_LIMITER * self.rev_limiter_s
+            + WEIGHT_WARNINGS * self.warnings_active_s
+            + WEIGHT_OIL_HOT * self.oil_temp_high_s
+            + WEIGHT_WATER_HOT * self.water_temp_high_s;
+        self.score = (weighted / self.driving_time_s * 100.0).clamp(0.0, 100.0);
+    }
+}
+
+/// Engine stress for the current stint plus recently finished stints
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct EngineStressData {
+    pub current: StintEngineStress,
+    pub previous_stints: Vec<StintEngineStress>,
+}
+
+/// Aggregates engine abuse per stint for engine-saving strategies.
+///
+/// A stint starts when the car leaves pit road and ends when it enters it again.
+pub struct EngineStress {
+    data: EngineStressData,
+    in_stint: bool,
+    shift_rpm: Option<f32>,
+}
+
+impl EngineStress {
+    pub fn new() -> Self {
+        Self {
+            data: EngineStressData::default(),
+            in_stint: false,
+            shift_rpm: None,
+        }
+    }
+
+    fn start_stint(&mut self, lap: i32) {
+        let finished = std::mem::take(&mut self.data.current);
+        if finished.driving_time_s > 0.0 {
+            self.data.previous_stints.push(finished.clone());
+            if self.data.previous_stints.len() > MAX_STINT_HISTORY {
+                self.data.previous_stints.remove(0);
+            }
+        }
+        self.data.current = StintEngineStress {
+            stint: finished.stint + 1,
+            start_lap: lap,
+            ..Default::default()
+        };
+        self.in_stint = true;
+    }
+
+    // Above the car's shift point, using the session's shift RPM when available
+    fn is_above_shift(&self, data: &TelemetryData) -> bool {
+        match self.shift_rpm {
+            Some(shift_rpm) if shift_rpm > 0.0 => data.rpm >= shift_rpm,
+            _ => data.shift_indicator_pct >= 100.0,
+        }
+    }
+}
+
+impl DerivedChannel for EngineStress {
+    fn name(&self) -> &'static str {
+        "engine_stress"
+    }
+
+    fn dependencies(&self) -> &'static [&'static str] {
+        // Shift RPM comes from the metadata cache when the session doesn't report it
+        &["metadata"]
+    }
+
+    fn compute(&mut self, data: &mut TelemetryData) {
+        if data.race_clock.session_reset {
+            self.data = EngineStressData::default();
+            self.in_stint = false;
+        }
+        let dt = data.race_clock.dt;
+
+        // The fallback session info reports 0, so keep looking until a real value shows up
+        if self.shift_rpm.is_none_or(|rpm| rpm <= 0.0) {
+            self.shift_rpm = data.session_metadata
+                .as_ref()
+                .and_then(|metadata| metadata.car.as_ref())
+                .and_then(|car| car.shift_rpm);
+        }
+
+        if data.on_pit_road {
+            self.in_stint = false;
+        } else if !self.in_stint {
+            self.start_stint(data.lap_completed);
+        }
+
+        if self.in_stint && dt > 0.0 && data.speed_kph > 1.0 {
+            let above_shift = self.is_above_shift(data);
+            let warnings = &data.engine_warnings;
+            let stint = &mut self.data.current;
+            stint.driving_time_s += dt;
+
+            if above_shift {
+                stint.above_shift_rpm_s += dt;
+            }
+            if warnings.rev_limiter_active {
+                stint.rev_limiter_s += dt;
+            }
+            if warnings.water_temp_warning || warnings.oil_temp_warning
+                || warnings.oil_pressure_warning || warnings.fuel_pressure_warning {
+                stint.warnings_active_s += dt;
+            }
+            if data.oil_temp_c > OIL_TEMP_HOT_C {
+                stint.oil_temp_high_s += dt;
+            }
+            if data.water_temp_c > WATER_TEMP_HOT_C {
+                stint.water_temp_high_s += dt;
+            }
+            stint.max_oil_temp_c = stint.max_oil_temp_c.max(data.oil_temp_c);
+            stint.max_water_temp_c = stint.max_water_temp_c.max(data.water_temp_c);
+            stint.update_score();
+        }
+
+        data.engine_stress = Some(self.data.clone());
+    }
+}
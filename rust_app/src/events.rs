@@ -0,0 +1,78 @@
+use serde::{Serialize, Deserialize};
+use crate::telemetry_fields::TelemetryData;
+
+/// A discrete event detected while processing telemetry (lockup, overtake, marker...).
+///
+/// Events are attached to the frame in which they were detected and sent to clients
+/// as part of that frame's `events` array.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TelemetryEvent {
+    /// Event type, e.g. "wheel_lock"
+    pub kind: String,
+    /// SessionTime at which the event was detected
+    pub session_time: f32,
+    /// Player lap number at the time of the event
+    pub lap: i32,
+    /// Car the event refers to, when it isn't the player's car
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub car_idx: Option<i32>,
+    /// Event specific details
+    #[serde(skip_serializing_if = "serde_json::Value::is_null")]
+    pub data: serde_json::Value,
+}
+
+impl TelemetryEvent {
+    /// Create an event stamped with the frame's session time and lap
+    pub fn new(kind: &str, frame: &TelemetryData, data: serde_json::Value) -> Self {
+        Self {
+            kind: kind.to_string(),
+            session_time: frame.SessionTime,
+            lap: frame.lap_completed,
+            car_idx: None,
+            data,
+        }
+    }
+
+    /// Attach the car the event refers to
+    pub fn for_car(mut self, car_idx: i32) -> Self {
+        self.car_idx = Some(car_idx);
+        self
+    }
+}
+
+/// Add an event to the frame being processed
+pub fn emit(frame: &mut TelemetryData, kind: &str, data: serde_json::Value) {
+    let event = TelemetryEvent::new(kind, frame, data);
+    frame.events.push(event);
+}
+
+/// Events raised outside the telemetry thread (button boxes, hotkeys, HTTP calls)
+/// waiting to be stamped and attached to the next frame
+#[derive(Clone, Default)]
+pub struct EventQueue {
+    pending: std::sync::Arc<std::sync::Mutex<Vec<(String, serde_json::Value)>>>,
+}
+
+impl EventQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue an event for the next frame
+    pub fn push(&self, kind: &str, data: serde_json::Value) {
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.push((kind.to_string(), data));
+        }
+    }
+
+    /// Attach all queued events to the frame being processed
+    pub fn drain_into(&self, frame: &mut TelemetryData) {
+        let drained = match self.pending.lock() {
+            Ok(mut pending) => std::mem::take(&mut *pending),
+            Err(_) => return,
+        };
+        for (kind, data) in drained {
+            emit(frame, &kind, data);
+        }
+    }
+}
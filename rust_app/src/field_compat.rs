@@ -0,0 +1,70 @@
+//! Renamed and deprecated frame fields.
+//!
+//! Frames are built under their original names (schema 1). Clients on schema 2 and later
+//! get the renames below applied and the deprecated fields dropped; clients that ask for schema 1
+//! in `hello` (and clients of the original `/` endpoint, which never asked for anything)
+//! keep receiving the original names. To rename a field, add it to `RENAMES`; to retire
+//! one, add it to `DEPRECATED`. Overlays migrate at their own pace by switching schema.
+//! From schema 3 the session info is no longer in frames but sent as its own message when
+//! it changes (`MOVED`).
+
+use crate::frame_schema;
+use serde_json::Value;
+
+/// (schema 1 name, current name)
+pub const RENAMES: &[(&str, &str)] = &[
+    ("PlayerTrackSurface", "player_track_surface"),
+    ("BrakeABSactive", "brake_abs_active"),
+    ("VelocityX", "velocity_x_ms"),
+    ("VelocityY", "velocity_y_ms"),
+    ("VelocityZ", "velocity_z_ms"),
+    ("SessionTime", "session_time"),
+];
+
+/// Schema 1 fields no longer sent to current clients
+pub const DEPRECATED: &[&str] = &[
+    // Convert speed_kph with the units preference instead
+    "speed_mph",
+];
+
+/// Fields sent as their own message from schema 3, with the topic that carries them
+pub const MOVED: &[(&str, &str)] = &[
+    ("session_info", "session_info"),
+    ("session_info_parsed", "session_info"),
+];
+
+/// A serialized frame as seen by a client on `schema_version`
+pub fn for_schema(frame: &Value, schema_version: u32) -> Value {
+    let mut frame = frame.clone();
+    if schema_version < 2 {
+        return frame;
+    }
+    if let Value::Object(fields) = &mut frame {
+        for (old, new) in RENAMES {
+            if let Some(value) = fields.remove(*old) {
+                fields.insert(new.to_string(), value);
+            }
+        }
+        for name in DEPRECATED {
+            fields.remove(*name);
+        }
+        if schema_version >= 3 {
+            let names = frame_schema::field_names();
+            let mut cleared = frame_schema::FieldMask::default();
+            for (name, _) in MOVED {
+                fields.remove(*name);
+                if let Some(bit) = names.iter().position(|field| field == name) {
+                    cleared[bit / 32] |= 1 << (bit % 32);
+                }
+            }
+            if let Some(Value::Array(words)) = fields.get_mut("field_mask") {
+                for (word, cleared) in words.iter_mut().zip(cleared) {
+                    if let Some(bits) = word.as_u64() {
+                        *word = Value::from(bits & !u64::from(cleared));
+                    }
+                }
+            }
+        }
+    }
+    frame
+}
@@ -0,0 +1,87 @@
+//! Field groups clients can subscribe to instead of whole frames.
+//!
+//! A client sends `{"subscribe":["timing","tires","caridx"]}` (or the same list as `groups`
+//! in `hello`) and from then on its frames only carry the fields of those groups, plus the
+//! ones every frame keeps (`ALWAYS`). An empty list goes back to whole frames. Groups are
+//! listed under the schema 1 field names and applied before renames (see field_compat.rs),
+//! so they work on every schema. An entry ending in `*` matches every field it prefixes.
+
+use serde_json::{Map, Value};
+
+/// Fields sent whatever the groups
+pub const ALWAYS: &[&str] = &[
+    "SessionTime", "player_car_idx", "field_mask", "server_time_ms", "stale", "frames_skipped", "sim", "capabilities", "shed",
+];
+
+/// (group, fields)
+pub const GROUPS: &[(&str, &[&str])] = &[
+    ("timing", &[
+        "lap_dist_pct", "lap_dist", "current_lap_time", "last_lap_time", "best_lap_time", "lap_completed",
+        "delta_best", "delta_session_best", "delta_optimal", "position", "incident_count", "session_laps_remain",
+        "session_time_remain", "gap_data", "lap_summary", "consistency", "effective_standings", "car_order",
+        "strength_of_field", "finish_probabilities", "session_clock",
+    ]),
+    ("tires", &[
+        "tire_temps_c", "tire_pressures_kpa", "tire_sets_available", "tire_sets_used", "wheel_rpm", "wheel_slip",
+        "tire_strategy", "crossover_estimate",
+    ]),
+    ("caridx", &["CarIdx*", "focus_car_idx"]),
+    ("inputs", &["throttle_pct", "brake_pct", "clutch_pct", "steering_angle_deg", "BrakeABSactive"]),
+    ("car", &[
+        "speed_kph", "speed_mph", "rpm", "gear", "gear_num", "velocity_ms", "Velocity*", "shift_indicator_pct",
+        "shift_lights", "gear_chart", "engine_warnings", "engine_stress", "engine_map_stats", "water_temp_c",
+        "oil_temp_c", "lateral_accel_ms2", "longitudinal_accel_ms2", "vertical_accel_ms2", "yaw_rate_deg_s",
+        "g_force_lat", "g_force_lon", "car_slip_angle_deg", "ride_height_mm", "shock_defl_mm", "brake_temps_c",
+        "braking_report", "traction_report", "repair_required_sec", "opt_repair_sec",
+    ]),
+    ("fuel", &["fuel_*", "mguk_*", "ers_battery_pct", "hybrid_stats"]),
+    ("session", &[
+        "session_num", "session_state", "session_flags", "active_flags", "warnings", "session_time_of_day",
+        "pace_mode", "display_units", "is_replay_playing", "replay_frame_num", "on_pit_road", "track_surface",
+        "PlayerTrackSurface", "car_left_right", "car_left_right_raw", "session_metadata", "session_info",
+        "session_info_parsed", "race_phase", "restart_order", "time_of_day", "sun", "events",
+    ]),
+    ("weather", &[
+        "track_temp_c", "air_temp_c", "humidity_pct", "fog_level_pct", "wind_vel_ms", "wind_dir_rad", "skies",
+        "track_wetness",
+    ]),
+    ("track", &["lat", "lon", "sector_hazards", "local_yellow", "radar", "ghost_car", "car_motion", "pit_board", "drive_time"]),
+    ("extras", &["raw_values", "strings", "driver_stats", "data_api"]),
+];
+
+pub fn names() -> Vec<&'static str> {
+    GROUPS.iter().map(|(name, _)| *name).collect()
+}
+
+pub fn is_known(name: &str) -> bool {
+    GROUPS.iter().any(|(group, _)| *group == name)
+}
+
+fn matches(entry: &str, field: &str) -> bool {
+    match entry.strip_suffix('*') {
+        Some(prefix) => field.starts_with(prefix),
+        None => entry == field,
+    }
+}
+
+/// The frame with only the fields of `groups` and `ALWAYS`; all of it when `groups` is empty
+pub fn filter(frame: &Value, groups: &[String]) -> Value {
+    let Value::Object(fields) = frame else {
+        return frame.clone();
+    };
+    if groups.is_empty() {
+        return frame.clone();
+    }
+    let entries: Vec<&str> = GROUPS
+        .iter()
+        .filter(|(name, _)| groups.iter().any(|group| group == name))
+        .flat_map(|(_, entries)| entries.iter().copied())
+        .chain(ALWAYS.iter().copied())
+        .collect();
+    let kept: Map<String, Value> = fields
+        .iter()
+        .filter(|(field, _)| entries.iter().any(|entry| matches(entry, field)))
+        .map(|(field, value)| (field.clone(), value.clone()))
+        .collect();
+    Value::Object(kept)
+}
@@ -0,0 +1,112 @@
+//! Dedicated thread for file writes made while processing telemetry.
+//!
+//! Caches, the driver database and highlight exports are written from derived channels,
+//! which run on the telemetry thread. A slow disk (antivirus scan, a sleeping network
+//! drive) would then hold up sampling and broadcasting, so the server starts a single
+//! I/O thread and those writes are queued to it. Writes to one file keep their order.
+//! Without the thread (CLI tools, tests) the write happens inline, so short-lived commands
+//! don't exit with writes still queued. The queue depth and failed writes are reported to
+//! sink_health.rs; a job that panics doesn't take the thread with it, and a thread that
+//! is gone anyway is started again on the next write.
+
+use crate::sink_health;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::Mutex;
+
+/// Name the I/O thread reports its health under (see sink_health.rs)
+pub const SINK: &str = "file_io";
+
+type Job = Box<dyn FnOnce() -> Result<(), String> + Send>;
+
+static QUEUE: OnceLock<Mutex<Sender<Job>>> = OnceLock::new();
+// Jobs queued and not finished yet
+static PENDING: AtomicUsize = AtomicUsize::new(0);
+
+// Spawn the I/O thread; None when the thread couldn't be started
+fn spawn() -> Option<Sender<Job>> {
+    let (sender, receiver) = mpsc::channel::<Job>();
+    let spawned = std::thread::Builder::new()
+        .name("file-io".to_string())
+        .spawn(move || {
+            for job in receiver {
+                // A panicking job is reported and the thread carries on with the next one
+                sink_health::run_guarded(SINK, job);
+                let pending = PENDING.fetch_sub(1, Ordering::Relaxed).saturating_sub(1);
+                sink_health::set_queue_depth(SINK, pending);
+            }
+        });
+    match spawned {
+        Ok(_) => Some(sender),
+        Err(e) => {
+            tracing::error!("Failed to start the I/O thread, writing inline: {}", e);
+            sink_health::failure(SINK, &format!("thread not running: {}", e));
+            None
+        }
+    }
+}
+
+/// Start the I/O thread; later calls do nothing
+pub fn start() {
+    QUEUE.get_or_init(|| {
+        // Without a thread the dropped receiver makes every send fail, so jobs run inline
+        Mutex::new(spawn().unwrap_or_else(|| mpsc::channel().0))
+    });
+}
+
+// Run `job` on the I/O thread, restarting the thread if it has gone away; inline when it
+// can't run or the thread isn't started
+fn queue(job: Job) {
+    let job = match QUEUE.get().and_then(|queue| queue.lock().ok()) {
+        Some(mut sender) => {
+            PENDING.fetch_add(1, Ordering::Relaxed);
+            match sender.send(job) {
+                Ok(()) => return,
+                Err(mpsc::SendError(job)) => {
+                    PENDING.fetch_sub(1, Ordering::Relaxed);
+                    match spawn() {
+                        Some(restarted) => {
+                            sink_health::restarted(SINK);
+                            *sender = restarted;
+                            PENDING.fetch_add(1, Ordering::Relaxed);
+                            match sender.send(job) {
+                                Ok(()) => return,
+                                Err(mpsc::SendError(job)) => job,
+                            }
+                        },
+                        None => job,
+                    }
+                },
+            }
+        },
+        None => job,
+    };
+    sink_health::run_guarded(SINK, job);
+}
+
+/// Run `job` on the I/O thread, or right away when it isn't running
+pub fn submit(job: impl FnOnce() + Send + 'static) {
+    queue(Box::new(move || {
+        job();
+        Ok(())
+    }));
+}
+
+/// Write through a per-process temporary file and rename it, so readers (and other
+/// instances sharing the file) never see a partial write. `what` names the file in errors
+pub fn write_atomic(path: PathBuf, contents: String, what: &'static str) {
+    queue(Box::new(move || {
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            let _ = fs::create_dir_all(parent);
+        }
+        let tmp = path.with_extension(format!("{}.tmp", std::process::id()));
+        fs::write(&tmp, contents).and_then(|()| fs::rename(&tmp, &path)).map_err(|e| {
+            let message = format!("Failed to write {} {}: {}", what, path.display(), e);
+            tracing::error!("{}", message);
+            message
+        })
+    }));
+}
@@ -0,0 +1,346 @@
+//! Monte Carlo simulation of the rest of the race, giving each car's chance of finishing
+//! in every position.
+//!
+//! The telemetry thread keeps a snapshot of the race (distance covered, pace and spread
+//! of recent clean laps, laps since the last stop); a worker thread simulates the
+//! remaining distance from it every few seconds. A car whose stint would run past the
+//! fuel range pays the pit loss for each extra stop. The range is the player's full tank
+//! divided by their fuel per lap, applied to every car, since other cars' fuel isn't known.
+//!
+//! Results are attached as `finish_probabilities` to the first frame after each run and
+//! answered on `{"type":"get_finish_probabilities"}`; sending `iterations` changes the
+//! number of simulated races per run.
+//!
+//! In deterministic mode (see determinism.rs) the runs happen inline every interval of
+//! race time instead, with a fixed seed.
+
+use crate::derived::DerivedChannel;
+use crate::determinism;
+use crate::pit_stops::PitTracker;
+use crate::session_yaml;
+use crate::telemetry_fields::TelemetryData;
+use crate::websocket_server::RequestHandler;
+use serde::{Serialize, Deserialize};
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Seconds between simulation runs
+pub const DEFAULT_INTERVAL_S: u64 = 10;
+/// Simulated races per run
+pub const DEFAULT_ITERATIONS: u32 = 2000;
+const MAX_ITERATIONS: u32 = 100_000;
+
+// Spread used when a car has too few clean laps to measure one
+const FALLBACK_STD_DEV_S: f32 = 1.0;
+// Floor on the lap time spread, so two identical laps don't make a car certain
+const MIN_STD_DEV_S: f32 = 0.2;
+// SessionLapsRemainEx in timed races
+const UNLIMITED_LAPS: i32 = 32767;
+
+/// Finish position chances of one car
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct CarFinishProbabilities {
+    pub car_idx: i32,
+    pub position: i32,
+    pub expected_position: f32,
+    /// Chance of finishing in each position, index 0 is P1
+    pub probabilities: Vec<f32>,
+}
+
+/// Outcome of one simulation run
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct FinishProbabilities {
+    /// SessionTime of the snapshot the run started from
+    pub session_time: f32,
+    pub iterations: u32,
+    /// Race distance in laps the simulation ran to
+    pub finish_laps: f32,
+    pub pit_loss: f32,
+    /// Laps on a full tank; None when unknown and no stops are simulated
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stint_laps: Option<f32>,
+    /// Most likely winner first
+    pub cars: Vec<CarFinishProbabilities>,
+}
+
+#[derive(Clone, Debug)]
+struct CarInput {
+    car_idx: i32,
+    position: i32,
+    /// Laps completed plus the fraction of the current one
+    distance: f32,
+    pace: Option<f32>,
+    std_dev: f32,
+    laps_since_stop: f32,
+}
+
+#[derive(Clone, Debug)]
+struct RaceInput {
+    session_time: f32,
+    cars: Vec<CarInput>,
+    laps_remaining: Option<i32>,
+    time_remaining: f32,
+    pit_loss: f32,
+    stint_laps: Option<f32>,
+}
+
+struct Shared {
+    iterations: u32,
+    input: Option<RaceInput>,
+    result: Option<FinishProbabilities>,
+    // Bumped for each new result so the recorder attaches it once
+    generation: u64,
+    // Race seconds between runs made by the recorder itself, instead of the worker
+    inline_interval: Option<f64>,
+}
+
+// xorshift64*, good enough for sampling lap times
+struct Rng(u64);
+
+impl Rng {
+    fn seeded() -> Self {
+        if determinism::enabled() {
+            return Self(determinism::SEED | 1);
+        }
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos() as u64).unwrap_or(0);
+        Self(nanos | 1)
+    }
+
+    fn next_f64(&mut self) -> f64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        (self.0.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    // Standard normal sample (Box-Muller)
+    fn normal(&mut self) -> f32 {
+        let u1 = self.next_f64().max(f64::MIN_POSITIVE);
+        let u2 = self.next_f64();
+        ((-2.0 * u1.ln()).sqrt() * (std::f64::consts::TAU * u2).cos()) as f32
+    }
+}
+
+fn median(mut values: Vec<f32>) -> Option<f32> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(f32::total_cmp);
+    Some(values[values.len() / 2])
+}
+
+fn simulate(input: &RaceInput, iterations: u32) -> Option<FinishProbabilities> {
+    let field_pace = median(input.cars.iter().filter_map(|car| car.pace).collect())?;
+    let leader = input.cars.iter().max_by(|a, b| a.distance.total_cmp(&b.distance))?;
+    let leader_pace = leader.pace.unwrap_or(field_pace);
+    let finish_laps = match input.laps_remaining {
+        Some(laps) => leader.distance.floor() + laps as f32,
+        // Timed race: the leader finishes the lap they are on when the clock runs out
+        None => (leader.distance + input.time_remaining / leader_pace).ceil(),
+    };
+
+    let count = input.cars.len();
+    let mut finishes = vec![vec![0u32; count]; count];
+    let mut rng = Rng::seeded();
+    let mut times: Vec<(usize, f32)> = Vec::with_capacity(count);
+    for _ in 0..iterations {
+        times.clear();
+        for (i, car) in input.cars.iter().enumerate() {
+            let remaining = (finish_laps - car.distance).max(0.0);
+            let pace = car.pace.unwrap_or(field_pace);
+            let mut time = remaining * pace + remaining.sqrt() * car.std_dev * rng.normal();
+            if let Some(stint_laps) = input.stint_laps {
+                let stops = ((car.laps_since_stop + remaining - stint_laps) / stint_laps).ceil().max(0.0);
+                time += stops * input.pit_loss;
+            }
+            times.push((i, time));
+        }
+        times.sort_by(|a, b| a.1.total_cmp(&b.1));
+        for (position, (i, _)) in times.iter().enumerate() {
+            finishes[*i][position] += 1;
+        }
+    }
+
+    let mut cars: Vec<CarFinishProbabilities> = input.cars
+        .iter()
+        .zip(finishes)
+        .map(|(car, counts)| {
+            let probabilities: Vec<f32> = counts.iter().map(|&n| n as f32 / iterations as f32).collect();
+            CarFinishProbabilities {
+                car_idx: car.car_idx,
+                position: car.position,
+                expected_position: probabilities.iter().enumerate().map(|(p, chance)| (p + 1) as f32 * chance).sum(),
+                probabilities,
+            }
+        })
+        .collect();
+    cars.sort_by(|a, b| a.expected_position.total_cmp(&b.expected_position));
+    Some(FinishProbabilities {
+        session_time: input.session_time,
+        iterations,
+        finish_laps,
+        pit_loss: input.pit_loss,
+        stint_laps: input.stint_laps,
+        cars,
+    })
+}
+
+/// Race snapshot and latest result, shared between the telemetry thread, the worker and the server
+#[derive(Clone)]
+pub struct FinishSimulator {
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl FinishSimulator {
+    pub fn new(iterations: u32) -> Self {
+        Self {
+            shared: Arc::new(Mutex::new(Shared {
+                iterations: iterations.clamp(1, MAX_ITERATIONS),
+                input: None,
+                result: None,
+                generation: 0,
+                inline_interval: None,
+            })),
+        }
+    }
+
+    /// Simulate the latest snapshot every `interval` of race time on the telemetry thread,
+    /// so results only depend on the frames (see determinism.rs)
+    pub fn run_inline(&self, interval: Duration) {
+        if let Ok(mut shared) = self.shared.lock() {
+            shared.inline_interval = Some(interval.as_secs_f64());
+        }
+    }
+
+    /// Simulate the latest snapshot every `interval` on a background thread
+    pub fn start_worker(&self, interval: Duration) {
+        let shared = self.shared.clone();
+        std::thread::spawn(move || loop {
+            std::thread::sleep(interval);
+            // Copy the snapshot out so the telemetry thread isn't held up by the run
+            let Some((input, iterations)) = shared.lock().ok().and_then(|s| Some((s.input.clone()?, s.iterations))) else {
+                continue;
+            };
+            if let Some(result) = simulate(&input, iterations) && let Ok(mut shared) = shared.lock() {
+                shared.result = Some(result);
+                shared.generation += 1;
+            }
+        });
+    }
+}
+
+impl RequestHandler for FinishSimulator {
+    fn handle(&self, request: &Value) -> Value {
+        let Ok(mut shared) = self.shared.lock() else {
+            return json!({ "type": "finish_probabilities", "finish_probabilities": null });
+        };
+        if let Some(iterations) = request.get("iterations").and_then(Value::as_u64) {
+            shared.iterations = (iterations as u32).clamp(1, MAX_ITERATIONS);
+        }
+        json!({
+            "type": "finish_probabilities",
+            "iterations": shared.iterations,
+            "finish_probabilities": shared.result,
+        })
+    }
+}
+
+/// Derived channel that keeps the race snapshot current and publishes new results
+pub struct FinishSimRecorder {
+    simulator: FinishSimulator,
+    pits: PitTracker,
+    published: u64,
+    is_race: bool,
+    last_session: Option<(i32, usize)>, // session_num, session info length
+    last_inline_run: Option<f64>,
+}
+
+impl FinishSimRecorder {
+    pub fn new(simulator: FinishSimulator, default_pit_loss: f32) -> Self {
+        Self {
+            simulator,
+            pits: PitTracker::new(default_pit_loss),
+            published: 0,
+            is_race: false,
+            last_session: None,
+            last_inline_run: None,
+        }
+    }
+
+    fn snapshot(&self, data: &TelemetryData) -> Option<RaceInput> {
+        let (positions, laps, lap_dist) = (data.CarIdxPosition.as_ref()?, data.CarIdxLapCompleted.as_ref()?, data.CarIdxLapDistPct.as_ref()?);
+        let cars: Vec<CarInput> = positions
+            .iter()
+            .enumerate()
+            .filter(|&(idx, &position)| position > 0 && laps.get(idx).is_some_and(|&lap| lap >= 0))
+            .map(|(idx, &position)| {
+                let pit = self.pits.car(idx);
+                let spread = pit.and_then(|car| car.pace_spread());
+                let distance = laps[idx] as f32 + lap_dist.get(idx).copied().unwrap_or(0.0).max(0.0);
+                CarInput {
+                    car_idx: idx as i32,
+                    position,
+                    distance,
+                    pace: spread.map(|(mean, _)| mean),
+                    std_dev: spread.map_or(FALLBACK_STD_DEV_S, |(_, std_dev)| std_dev.max(MIN_STD_DEV_S)),
+                    laps_since_stop: pit.and_then(|car| car.laps_since_stop(laps[idx])).unwrap_or(laps[idx]) as f32,
+                }
+            })
+            .collect();
+        if cars.is_empty() {
+            return None;
+        }
+        let full_tank = data.session_metadata.as_ref().and_then(|m| m.car.as_ref()).and_then(|car| car.fuel_max_ltr);
+        Some(RaceInput {
+            session_time: data.SessionTime,
+            cars,
+            laps_remaining: (data.session_laps_remain >= 0 && data.session_laps_remain < UNLIMITED_LAPS).then_some(data.session_laps_remain),
+            time_remaining: data.session_time_remain.max(0.0),
+            pit_loss: self.pits.pit_loss(),
+            stint_laps: full_tank.filter(|_| data.fuel_per_lap > 0.0).map(|fuel| fuel / data.fuel_per_lap),
+        })
+    }
+}
+
+impl DerivedChannel for FinishSimRecorder {
+    fn name(&self) -> &'static str {
+        "finish_probabilities"
+    }
+
+    fn dependencies(&self) -> &'static [&'static str] {
+        &["metadata", "fuel_per_lap"]
+    }
+
+    fn compute(&mut self, data: &mut TelemetryData) {
+        let session = (data.session_num, data.session_info.len());
+        if self.last_session != Some(session) {
+            self.last_session = Some(session);
+            self.is_race = session_yaml::session_type(&data.session_info, data.session_num) == Some("Race");
+        }
+        self.pits.update(data);
+        let input = if self.is_race { self.snapshot(data) } else { None };
+
+        let Ok(mut shared) = self.simulator.shared.lock() else {
+            return;
+        };
+        if data.race_clock.session_reset || !self.is_race {
+            shared.result = None;
+        }
+        shared.input = input;
+        if let (Some(interval), Some(input)) = (shared.inline_interval, shared.input.as_ref()) {
+            let now = data.race_clock.time;
+            if data.race_clock.session_reset || self.last_inline_run.is_none_or(|last| now - last >= interval) {
+                self.last_inline_run = Some(now);
+                if let Some(result) = simulate(input, shared.iterations) {
+                    shared.result = Some(result);
+                    shared.generation += 1;
+                }
+            }
+        }
+        if shared.generation != self.published {
+            self.published = shared.generation;
+            data.finish_probabilities = shared.result.clone();
+        }
+    }
+}
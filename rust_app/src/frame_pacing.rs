@@ -0,0 +1,67 @@
+//! Sampling cadence of the SDK loop and detection of frames lost to a stall.
+//!
+//! The loop samples every `SAMPLE_INTERVAL` unless `--tick-rate` sets another rate. When processing a frame takes longer than
+//! that (history pruning, a slow disk flush) the next sample is taken straight away
+//! instead of sleeping a full interval on top, so the SDK's latest frame is processed and
+//! broadcast rather than one that is already old. The SDK ticks missed beyond the normal
+//! cadence are reported on the next frame as `frames_skipped`.
+
+use crate::race_clock;
+use std::time::{Duration, Instant};
+
+/// Time between SDK samples by default
+pub const SAMPLE_INTERVAL: Duration = Duration::from_millis(50);
+/// SDK update rate
+pub const SDK_TICK_RATE_HZ: f32 = 60.0;
+/// Lowest `--tick-rate`; below it a few samples span a race clock jump (see race_clock.rs)
+pub const MIN_TICK_RATE_HZ: f32 = 4.0 / race_clock::MAX_STEP_S;
+
+/// Keeps the SDK loop on its cadence and counts the ticks a stall skipped
+pub struct FramePacer {
+    interval: Duration,
+    last_tick: Option<i32>,
+    sampled_at: Option<Instant>,
+    total_skipped: u64,
+}
+
+impl FramePacer {
+    /// Sample every `interval`; anything shorter than an SDK tick samples every tick
+    pub fn with_interval(interval: Duration) -> Self {
+        Self {
+            interval: interval.max(Duration::from_secs_f32(1.0 / SDK_TICK_RATE_HZ)),
+            last_tick: None,
+            sampled_at: None,
+            total_skipped: 0,
+        }
+    }
+
+    /// Note a new sample; returns the SDK ticks skipped since the previous one beyond
+    /// those the sampling interval skips anyway
+    pub fn sampled(&mut self, session_tick: i32) -> u32 {
+        self.sampled_at = Some(Instant::now());
+        let expected = (self.interval.as_secs_f32() * SDK_TICK_RATE_HZ).ceil() as i32;
+        let skipped = match self.last_tick.replace(session_tick) {
+            // A lower tick is a new session or a reconnect, not a stall
+            Some(last) if session_tick > last => (session_tick - last - expected).max(0) as u32,
+            _ => 0,
+        };
+        self.total_skipped += skipped as u64;
+        skipped
+    }
+
+    /// Ticks skipped since the loop started
+    pub fn total_skipped(&self) -> u64 {
+        self.total_skipped
+    }
+
+    /// How long to sleep before the next sample; zero when processing overran the interval
+    pub fn wait(&self) -> Duration {
+        self.sampled_at.map_or(self.interval, |at| self.interval.saturating_sub(at.elapsed()))
+    }
+
+    /// Forget the last tick, e.g. after reconnecting
+    pub fn reset(&mut self) {
+        self.last_tick = None;
+        self.sampled_at = None;
+    }
+}
@@ -0,0 +1,134 @@
+//! Presence mask for the optional parts of a telemetry frame.
+//!
+//! Each frame carries `field_mask`, an array of 32-bit words where bit N (bit N % 32 of
+//! word N / 32) is set when the Nth field of `field_names()` is present. Words rather than
+//! one number because JS clients only hold integers up to 2^53 exactly and do bitwise
+//! operations on 32 bits; `(mask[n >> 5] >>> (n & 31)) & 1` tests field n. Native clients fetch the names once with
+//! `{"type":"get_frame_schema"}` and can then decode frames without probing for keys.
+//! Bits are only ever appended so existing clients keep working; bump
+//! `SCHEMA_VERSION` if that rule has to be broken. Version 2 renamed and retired some
+//! top-level fields (see field_compat.rs); none of them has a bit. Version 3 moved the
+//! session info into its own message; its bit is never set for those clients.
+
+use crate::field_compat::{DEPRECATED, MOVED, RENAMES};
+use crate::telemetry_fields::TelemetryData;
+use crate::websocket_server::RequestHandler;
+use serde_json::{json, Value};
+
+/// Version of the frame layout: the bit assignment below and the field names
+pub const SCHEMA_VERSION: u32 = 3;
+/// Oldest schema still served
+pub const MIN_SCHEMA_VERSION: u32 = 1;
+/// Number of optional fields, i.e. bits in use
+pub const FIELD_COUNT: usize = 66;
+/// 32-bit words in `field_mask`; raise it when the fields outgrow it
+pub const MASK_WORDS: usize = 3;
+const _: () = assert!(FIELD_COUNT <= MASK_WORDS * 32, "field_mask can't hold every optional field; raise MASK_WORDS");
+
+/// Presence bits of a frame, lowest bits in the first word
+pub type FieldMask = [u32; MASK_WORDS];
+
+// Optional fields in bit order, with whether the frame has them
+fn optional_fields(data: &TelemetryData) -> [(&'static str, bool); FIELD_COUNT] {
+    [
+        ("raw_values", !data.raw_values.is_empty()),
+        ("CarIdxPosition", data.CarIdxPosition.is_some()),
+        ("CarIdxLapDistPct", data.CarIdxLapDistPct.is_some()),
+        ("CarIdxLap", data.CarIdxLap.is_some()),
+        ("CarIdxLapCompleted", data.CarIdxLapCompleted.is_some()),
+        ("CarIdxF2Time", data.CarIdxF2Time.is_some()),
+        ("CarIdxGapToLeader", data.CarIdxGapToLeader.is_some()),
+        ("CarIdxClassPosition", data.CarIdxClassPosition.is_some()),
+        ("CarIdxClass", data.CarIdxClass.is_some()),
+        ("CarIdxGear", data.CarIdxGear.is_some()),
+        ("CarIdxRPM", data.CarIdxRPM.is_some()),
+        ("CarIdxOnPitRoad", data.CarIdxOnPitRoad.is_some()),
+        ("CarIdxP2P_Count", data.CarIdxP2P_Count.is_some()),
+        ("CarIdxP2P_Status", data.CarIdxP2P_Status.is_some()),
+        ("CarIdxBestLapNum", data.CarIdxBestLapNum.is_some()),
+        ("CarIdxBestLapTime", data.CarIdxBestLapTime.is_some()),
+        ("CarIdxLastLapTime", data.CarIdxLastLapTime.is_some()),
+        ("CarIdxEstTime", data.CarIdxEstTime.is_some()),
+        ("CarIdxFastRepairsUsed", data.CarIdxFastRepairsUsed.is_some()),
+        ("CarIdxPaceFlags", data.CarIdxPaceFlags.is_some()),
+        ("CarIdxPaceLine", data.CarIdxPaceLine.is_some()),
+        ("CarIdxPaceRow", data.CarIdxPaceRow.is_some()),
+        ("CarIdxQualTireCompound", data.CarIdxQualTireCompound.is_some()),
+        ("CarIdxQualTireCompoundLocked", data.CarIdxQualTireCompoundLocked.is_some()),
+        ("CarIdxSteer", data.CarIdxSteer.is_some()),
+        ("CarIdxTireCompound", data.CarIdxTireCompound.is_some()),
+        ("CarIdxTrackSurface", data.CarIdxTrackSurface.is_some()),
+        ("CarIdxTrackSurfaceMaterial", data.CarIdxTrackSurfaceMaterial.is_some()),
+        ("gap_data", data.gap_data.is_some()),
+        ("wheel_slip", data.wheel_slip.is_some()),
+        ("braking_report", data.braking_report.is_some()),
+        ("traction_report", data.traction_report.is_some()),
+        ("engine_stress", data.engine_stress.is_some()),
+        ("lap_summary", data.lap_summary.is_some()),
+        ("consistency", data.consistency.is_some()),
+        ("ghost_car", data.ghost_car.is_some()),
+        ("car_motion", data.car_motion.is_some()),
+        ("effective_standings", data.effective_standings.is_some()),
+        ("drive_time", data.drive_time.is_some()),
+        ("pit_board", data.pit_board.is_some()),
+        ("focus_car_idx", data.focus_car_idx.is_some()),
+        ("events", !data.events.is_empty()),
+        ("CarIdxClassName", data.CarIdxClassName.is_some()),
+        ("CarIdxClassColor", data.CarIdxClassColor.is_some()),
+        ("car_order", data.car_order.is_some()),
+        ("sector_hazards", data.sector_hazards.is_some()),
+        ("local_yellow", data.local_yellow.is_some()),
+        ("session_metadata", data.session_metadata.is_some()),
+        ("shift_lights", data.shift_lights.is_some()),
+        ("gear_chart", data.gear_chart.is_some()),
+        ("engine_map_stats", data.engine_map_stats.is_some()),
+        ("session_clock", data.session_clock.is_some()),
+        ("strings", data.strings.is_some()),
+        ("time_of_day", data.time_of_day.is_some()),
+        ("sun", data.sun.is_some()),
+        ("crossover_estimate", data.crossover_estimate.is_some()),
+        ("tire_strategy", data.tire_strategy.is_some()),
+        ("race_phase", data.race_phase.is_some()),
+        ("restart_order", data.restart_order.is_some()),
+        ("driver_stats", data.driver_stats.is_some()),
+        ("data_api", data.data_api.is_some()),
+        ("hybrid_stats", data.hybrid_stats.is_some()),
+        ("strength_of_field", data.strength_of_field.is_some()),
+        ("finish_probabilities", data.finish_probabilities.is_some()),
+        ("radar", data.radar.is_some()),
+        ("session_info_parsed", data.session_info_parsed.is_some()),
+    ]
+}
+
+/// Field name for each bit of the mask
+pub fn field_names() -> Vec<&'static str> {
+    optional_fields(&TelemetryData::default()).into_iter().map(|(name, _)| name).collect()
+}
+
+/// Bitmap of the optional fields present in the frame
+pub fn presence_mask(data: &TelemetryData) -> FieldMask {
+    let mut mask = FieldMask::default();
+    for (bit, (_, present)) in optional_fields(data).into_iter().enumerate() {
+        if present {
+            mask[bit / 32] |= 1 << (bit % 32);
+        }
+    }
+    mask
+}
+
+/// Answers `get_frame_schema` requests
+pub struct FrameSchema;
+
+impl RequestHandler for FrameSchema {
+    fn handle(&self, _request: &Value) -> Value {
+        json!({
+            "type": "frame_schema",
+            "version": SCHEMA_VERSION,
+            "min_version": MIN_SCHEMA_VERSION,
+            "fields": field_names(),
+            "renamed": RENAMES.iter().map(|(old, new)| json!({ "old": old, "new": new })).collect::<Vec<_>>(),
+            "deprecated": DEPRECATED,
+            "moved": MOVED.iter().map(|(field, topic)| json!({ "field": field, "topic": topic })).collect::<Vec<_>>(),
+        })
+    }
+}
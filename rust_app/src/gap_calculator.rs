@@ -6,22 +6,17 @@ const CHECKPOINT_INTERVAL: f32 = 0.05;
 
 thread_local! {
     static CHECKPOINT_HISTORY: RefCell<HashMap<i32, HashMap<i32, f32>>> = RefCell::new(HashMap::new());
-    static LAST_SESSION_TIME: RefCell<f32> = RefCell::new(0.0);
 }
 
 pub fn calculate_gaps(telemetry_data: &mut TelemetryData) {
     let lap_dist = telemetry_data.CarIdxLapDistPct.as_ref().unwrap();
     let laps_done = telemetry_data.CarIdxLapCompleted.as_ref().unwrap();
-    let t = telemetry_data.SessionTime;
+    let t = telemetry_data.race_clock.time as f32;
 
     // clear on new session
-    LAST_SESSION_TIME.with(|lt| {
-        let mut last = lt.borrow_mut();
-        if t < *last {
-            CHECKPOINT_HISTORY.with(|h| h.borrow_mut().clear());
-        }
-        *last = t;
-    });
+    if telemetry_data.race_clock.session_reset {
+        CHECKPOINT_HISTORY.with(|h| h.borrow_mut().clear());
+    }
 
     // ensure output arrays
     let n = lap_dist.len().max(64);
@@ -0,0 +1,151 @@
+use crate::derived::DerivedChannel;
+use crate::lap_summary::SECTOR_COUNT;
+use crate::telemetry_fields::TelemetryData;
+use crate::websocket_server::RequestHandler;
+use serde::{Serialize, Deserialize};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+/// Laps of history kept per car unless configured otherwise
+pub const DEFAULT_HISTORY_LAPS: usize = 10;
+
+/// Gap to the leader when a car crossed a sector boundary
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GapSample {
+    /// Laps the car had completed
+    pub lap: i32,
+    /// Sector the car entered (0 = start of the lap)
+    pub sector: usize,
+    pub session_time: f32,
+    pub gap_to_leader: f32,
+}
+
+#[derive(Default)]
+struct CarGapHistory {
+    samples: VecDeque<GapSample>,
+    last_key: Option<(i32, usize)>,
+}
+
+/// Rolling gap-to-leader history shared between the telemetry thread and the server,
+/// so it survives client reconnects
+#[derive(Clone)]
+pub struct GapHistoryStore {
+    max_laps: usize,
+    cars: Arc<Mutex<BTreeMap<i32, CarGapHistory>>>,
+}
+
+impl GapHistoryStore {
+    pub fn new(max_laps: usize) -> Self {
+        Self {
+            max_laps: max_laps.max(1),
+            cars: Arc::new(Mutex::new(BTreeMap::new())),
+        }
+    }
+
+    fn clear(&self) {
+        if let Ok(mut cars) = self.cars.lock() {
+            cars.clear();
+        }
+    }
+
+    /// Snapshot of the history, optionally for a single car and limited to recent laps
+    pub fn snapshot(&self, car_idx: Option<i32>, laps: Option<usize>) -> BTreeMap<i32, Vec<GapSample>> {
+        let Ok(cars) = self.cars.lock() else {
+            return BTreeMap::new();
+        };
+        cars.iter()
+            .filter(|(idx, _)| car_idx.is_none_or(|wanted| **idx == wanted))
+            .map(|(idx, history)| {
+                let latest_lap = history.samples.back().map_or(0, |s| s.lap);
+                let first_lap = laps.map_or(i32::MIN, |laps| latest_lap - laps as i32 + 1);
+                let samples = history.samples.iter().filter(|s| s.lap >= first_lap).cloned().collect();
+                (*idx, samples)
+            })
+            .collect()
+    }
+}
+
+impl RequestHandler for GapHistoryStore {
+    /// `{"type":"get_gap_history","car_idx":3,"laps":5}`, both filters optional
+    fn handle(&self, request: &Value) -> Value {
+        let car_idx = request.get("car_idx").and_then(Value::as_i64).map(|idx| idx as i32);
+        let laps = request.get("laps").and_then(Value::as_u64).map(|laps| laps as usize);
+        json!({
+            "type": "gap_history",
+            "max_laps": self.max_laps,
+            "cars": self.snapshot(car_idx, laps),
+        })
+    }
+}
+
+/// Derived channel that samples each car's gap to the leader at every sector boundary
+pub struct GapHistoryRecorder {
+    store: GapHistoryStore,
+}
+
+impl GapHistoryRecorder {
+    pub fn new(store: GapHistoryStore) -> Self {
+        Self {
+            store,
+        }
+    }
+}
+
+impl DerivedChannel for GapHistoryRecorder {
+    fn name(&self) -> &'static str {
+        "gap_history"
+    }
+
+    fn dependencies(&self) -> &'static [&'static str] {
+        &["gaps"]
+    }
+
+    fn compute(&mut self, data: &mut TelemetryData) {
+        if data.race_clock.session_reset {
+            self.store.clear();
+        }
+
+        let (Some(positions), Some(laps), Some(gaps)) = (
+            data.CarIdxLapDistPct.as_ref(),
+            data.CarIdxLapCompleted.as_ref(),
+            data.CarIdxGapToLeader.as_ref(),
+        ) else {
+            return;
+        };
+
+        let Ok(mut cars) = self.store.cars.lock() else {
+            return;
+        };
+        for (idx, &pct) in positions.iter().enumerate() {
+            // Cars not in the world report a negative lap distance
+            if pct < 0.0 {
+                continue;
+            }
+            let (Some(&lap), Some(&gap)) = (laps.get(idx), gaps.get(idx)) else {
+                continue;
+            };
+            let sector = ((pct * SECTOR_COUNT as f32) as usize).min(SECTOR_COUNT - 1);
+            let key = (lap, sector);
+
+            let history = cars.entry(idx as i32).or_default();
+            // Only record boundary crossings; the first sighting is somewhere mid-sector
+            let crossed = history.last_key.is_some_and(|last| last != key);
+            history.last_key = Some(key);
+            if !crossed {
+                continue;
+            }
+
+            history.samples.push_back(GapSample {
+                lap,
+                sector,
+                session_time: data.SessionTime,
+                gap_to_leader: gap,
+            });
+            let first_lap = lap - self.store.max_laps as i32 + 1;
+            while history.samples.front().is_some_and(|s| s.lap < first_lap) {
+                history.samples.pop_front();
+            }
+        }
+    }
+}
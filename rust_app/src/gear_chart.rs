@@ -0,0 +1,99 @@
+use crate::derived::DerivedChannel;
+use crate::telemetry_fields::TelemetryData;
+use serde::{Serialize, Deserialize};
+
+// Ignore samples with the clutch in or off throttle, where speed doesn't follow RPM
+const MIN_THROTTLE_PCT: f32 = 50.0;
+const MAX_CLUTCH_PCT: f32 = 10.0;
+
+/// What has been observed in one forward gear
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct GearStats {
+    pub gear: i32,
+    pub max_speed_kph: f32,
+    pub max_rpm: f32,
+    /// Speed per 1000 RPM, the gear's effective ratio
+    pub kph_per_krpm: f32,
+    pub upshifts: u32,
+    /// Average RPM and speed just before shifting up out of this gear
+    pub avg_upshift_rpm: f32,
+    pub avg_upshift_speed_kph: f32,
+}
+
+/// Gear vs speed table for the player's car, built from this session's driving
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct GearChart {
+    pub gears: Vec<GearStats>,
+}
+
+/// Derived channel that collects per-gear speeds and shift points
+pub struct GearChartBuilder {
+    chart: GearChart,
+    last_gear: i32,
+    last_rpm: f32,
+    last_speed_kph: f32,
+}
+
+impl GearChartBuilder {
+    pub fn new() -> Self {
+        Self {
+            chart: GearChart::default(),
+            last_gear: 0,
+            last_rpm: 0.0,
+            last_speed_kph: 0.0,
+        }
+    }
+
+    fn gear_mut(&mut self, gear: i32) -> &mut GearStats {
+        let index = match self.chart.gears.binary_search_by_key(&gear, |stats| stats.gear) {
+            Ok(index) => index,
+            Err(index) => {
+                self.chart.gears.insert(index, GearStats { gear, ..GearStats::default() });
+                index
+            }
+        };
+        &mut self.chart.gears[index]
+    }
+}
+
+impl DerivedChannel for GearChartBuilder {
+    fn name(&self) -> &'static str {
+        "gear_chart"
+    }
+
+    fn compute(&mut self, data: &mut TelemetryData) {
+        // A new session may well be in a different car
+        if data.race_clock.session_reset {
+            *self = Self::new();
+        }
+
+        let gear = data.gear_num;
+        if gear > 0 && !data.on_pit_road {
+            if self.last_gear > 0 && gear == self.last_gear + 1 {
+                let (rpm, speed) = (self.last_rpm, self.last_speed_kph);
+                let from = self.gear_mut(gear - 1);
+                from.upshifts += 1;
+                let n = from.upshifts as f32;
+                from.avg_upshift_rpm += (rpm - from.avg_upshift_rpm) / n;
+                from.avg_upshift_speed_kph += (speed - from.avg_upshift_speed_kph) / n;
+            }
+
+            if data.throttle_pct >= MIN_THROTTLE_PCT && data.clutch_pct <= MAX_CLUTCH_PCT && data.rpm > 0.0 {
+                let (rpm, speed) = (data.rpm, data.speed_kph);
+                let stats = self.gear_mut(gear);
+                stats.max_rpm = stats.max_rpm.max(rpm);
+                if speed > stats.max_speed_kph {
+                    stats.max_speed_kph = speed;
+                    stats.kph_per_krpm = speed / rpm * 1000.0;
+                }
+            }
+        }
+        self.last_gear = gear;
+        self.last_rpm = data.rpm;
+        self.last_speed_kph = data.speed_kph;
+
+        if !self.chart.gears.is_empty() {
+            data.gear_chart = Some(self.chart.clone());
+        }
+    }
+}
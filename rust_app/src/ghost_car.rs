@@ -0,0 +1,127 @@
+use crate::derived::DerivedChannel;
+use crate::laps::{LapChange, LapTracker};
+use crate::telemetry_fields::TelemetryData;
+use serde::{Serialize, Deserialize};
+
+/// Position of the player's lap as a function of lap time
+#[derive(Clone, Debug, Default)]
+pub struct ReferenceLapTrace {
+    pub lap_time: f32,
+    /// (current lap time, lap distance pct), sorted by time
+    samples: Vec<(f32, f32)>,
+}
+
+impl ReferenceLapTrace {
+    /// Lap position the reference lap had reached after `lap_time` seconds
+    pub fn position_at(&self, lap_time: f32) -> Option<f32> {
+        let first = self.samples.first()?;
+        let last = self.samples.last()?;
+        if lap_time <= first.0 {
+            return Some(first.1);
+        }
+        if lap_time >= last.0 {
+            return Some(last.1);
+        }
+
+        let index = self.samples.partition_point(|&(t, _)| t <= lap_time);
+        let (t0, p0) = self.samples[index - 1];
+        let (t1, p1) = self.samples[index];
+        if t1 <= t0 {
+            return Some(p0);
+        }
+        Some(p0 + (p1 - p0) * (lap_time - t0) / (t1 - t0))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+}
+
+/// Where the best lap would be right now, for drawing a ghost car on track maps
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct GhostCar {
+    pub lap_dist_pct: f32,
+    /// Lap time of the reference lap being replayed
+    pub reference_lap_time: f32,
+    /// Lap number the reference lap was driven on
+    pub reference_lap: i32,
+}
+
+/// Records the player's best lap and replays it as a ghost position
+pub struct GhostCarTracker {
+    laps: LapTracker,
+    recording: ReferenceLapTrace,
+    recording_complete: bool,
+    pitted_this_lap: bool,
+    reference: ReferenceLapTrace,
+    reference_lap: i32,
+}
+
+impl GhostCarTracker {
+    pub fn new() -> Self {
+        Self {
+            laps: LapTracker::new(),
+            recording: ReferenceLapTrace::default(),
+            recording_complete: false,
+            pitted_this_lap: false,
+            reference: ReferenceLapTrace::default(),
+            reference_lap: 0,
+        }
+    }
+
+    fn start_recording(&mut self, data: &TelemetryData) {
+        self.recording = ReferenceLapTrace::default();
+        // A lap joined part way through can't be used as a reference
+        self.recording_complete = data.lap_dist_pct < 0.05;
+        self.pitted_this_lap = false;
+    }
+}
+
+impl DerivedChannel for GhostCarTracker {
+    fn name(&self) -> &'static str {
+        "ghost_car"
+    }
+
+    fn compute(&mut self, data: &mut TelemetryData) {
+        match self.laps.update(data) {
+            LapChange::Completed { lap, lap_time } => {
+                let is_best = self.reference.is_empty() || lap_time < self.reference.lap_time;
+                if self.recording_complete && !self.pitted_this_lap && lap_time > 0.0 && is_best {
+                    let mut trace = std::mem::take(&mut self.recording);
+                    trace.lap_time = lap_time;
+                    // Close the trace at the line so interpolation reaches 100%
+                    trace.samples.push((lap_time, 1.0));
+                    self.reference = trace;
+                    self.reference_lap = lap;
+                }
+                self.start_recording(data);
+            },
+            LapChange::Reset => {
+                self.reference = ReferenceLapTrace::default();
+                self.start_recording(data);
+            },
+            LapChange::None => {
+                if self.laps.current_lap().is_some() && self.recording.is_empty() && !self.recording_complete {
+                    self.start_recording(data);
+                }
+            }
+        }
+
+        self.pitted_this_lap |= data.on_pit_road;
+
+        // Only keep samples that move forward in time and along the lap
+        let sample = (data.current_lap_time, data.lap_dist_pct);
+        let is_forward = self.recording.samples.last().is_none_or(|&(t, p)| sample.0 > t && sample.1 >= p);
+        if is_forward {
+            self.recording.samples.push(sample);
+        }
+
+        if let Some(lap_dist_pct) = self.reference.position_at(data.current_lap_time) {
+            data.ghost_car = Some(GhostCar {
+                lap_dist_pct,
+                reference_lap_time: self.reference.lap_time,
+                reference_lap: self.reference_lap,
+            });
+        }
+    }
+}
@@ -0,0 +1,181 @@
+//! Filtering of physically impossible jumps in the raw channels.
+//!
+//! The SDK occasionally reports a one-off bad value: a speed spike, a lap position that
+//! jumps backwards, a lap counter that skips. Fed straight into the derived channels these
+//! become bogus laps, gaps and fuel figures, so each frame is checked against the previous
+//! one before the pipeline runs and a bad value is replaced by the last good one. A value
+//! that stays put for `MAX_HELD_FRAMES` frames is real (a reset, a tow, a session change)
+//! and is accepted. Replays are left alone since scrubbing jumps around legitimately.
+//!
+//! Corrections are counted per kind and answered on `{"type":"get_glitch_stats"}`.
+
+use crate::laps;
+use crate::telemetry_fields::TelemetryData;
+use crate::websocket_server::RequestHandler;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+/// Fastest believable change in speed (kph per second), about 6 g
+pub const MAX_SPEED_CHANGE_KPH_S: f32 = 210.0;
+/// Backwards movement along the lap allowed for jitter (fraction of a lap)
+pub const BACKWARD_TOLERANCE_PCT: f32 = 0.001;
+/// Frames a suspect value is held off before it is accepted as real
+pub const MAX_HELD_FRAMES: u32 = 3;
+
+// Gaps between frames longer than this make rate checks meaningless
+const MAX_CHECKED_DT_S: f32 = 1.0;
+
+/// Corrections made so far, by kind
+#[derive(Clone, Default)]
+pub struct GlitchCounters {
+    counts: Arc<Mutex<BTreeMap<&'static str, u64>>>,
+}
+
+impl GlitchCounters {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn add(&self, kind: &'static str) {
+        if let Ok(mut counts) = self.counts.lock() {
+            *counts.entry(kind).or_insert(0) += 1;
+        }
+    }
+}
+
+impl RequestHandler for GlitchCounters {
+    fn handle(&self, _request: &Value) -> Value {
+        let counts = self.counts.lock().map(|counts| counts.clone()).unwrap_or_default();
+        json!({ "type": "glitch_stats", "total": counts.values().sum::<u64>(), "corrections": counts })
+    }
+}
+
+// Last accepted value of a channel and how long a differing one has been held off
+#[derive(Clone, Copy)]
+struct Held<T> {
+    value: T,
+    held_frames: u32,
+}
+
+impl<T: Copy> Held<T> {
+    fn new(value: T) -> Self {
+        Self { value, held_frames: 0 }
+    }
+
+    // Returns the value to use: the new one when plausible or persistent, else the last good one
+    fn check(&mut self, value: T, plausible: bool) -> (T, bool) {
+        if plausible || self.held_frames >= MAX_HELD_FRAMES {
+            *self = Self::new(value);
+            (value, false)
+        } else {
+            self.held_frames += 1;
+            (self.value, true)
+        }
+    }
+}
+
+fn moved_backwards(from: f32, to: f32) -> bool {
+    from >= 0.0 && to >= 0.0 && laps::signed_lap_pct_delta(from, to) < -BACKWARD_TOLERANCE_PCT
+}
+
+/// Replaces implausible channel values with the last good ones before derived channels run
+pub struct GlitchFilter {
+    counters: GlitchCounters,
+    session_num: Option<i32>,
+    session_time: Option<f32>,
+    speed: Option<Held<f32>>,
+    lap_dist_pct: Option<Held<f32>>,
+    lap_completed: Option<Held<i32>>,
+    car_lap_dist_pct: Vec<Held<f32>>,
+}
+
+impl GlitchFilter {
+    pub fn new(counters: GlitchCounters) -> Self {
+        Self {
+            counters,
+            session_num: None,
+            session_time: None,
+            speed: None,
+            lap_dist_pct: None,
+            lap_completed: None,
+            car_lap_dist_pct: Vec::new(),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.speed = None;
+        self.lap_dist_pct = None;
+        self.lap_completed = None;
+        self.car_lap_dist_pct.clear();
+    }
+
+    pub fn apply(&mut self, data: &mut TelemetryData) {
+        let dt = self.session_time.replace(data.SessionTime).map(|last| data.SessionTime - last);
+        if self.session_num.replace(data.session_num) != Some(data.session_num) || data.is_replay_playing {
+            self.reset();
+        }
+        // Rate checks only make sense between consecutive ticks
+        if !dt.is_some_and(|dt| dt > 0.0 && dt <= MAX_CHECKED_DT_S) {
+            self.reset();
+        }
+        if data.is_replay_playing {
+            return;
+        }
+        let dt = dt.unwrap_or(0.0);
+        // Towing and resets move the car through the pits or out of the world
+        let relocating = data.on_pit_road || data.lap_dist_pct < 0.0;
+
+        if let Some(speed) = &mut self.speed {
+            let plausible = (data.speed_kph - speed.value).abs() <= MAX_SPEED_CHANGE_KPH_S * dt;
+            let (value, corrected) = speed.check(data.speed_kph, plausible);
+            if corrected {
+                data.speed_kph = value;
+                data.speed_mph = value / 1.609_344;
+                self.counters.add("speed_spike");
+            }
+        } else {
+            self.speed = Some(Held::new(data.speed_kph));
+        }
+
+        if let Some(pct) = &mut self.lap_dist_pct {
+            let plausible = relocating || !moved_backwards(pct.value, data.lap_dist_pct);
+            let (value, corrected) = pct.check(data.lap_dist_pct, plausible);
+            if corrected {
+                data.lap_dist_pct = value;
+                self.counters.add("lap_dist_backwards");
+            }
+        } else {
+            self.lap_dist_pct = Some(Held::new(data.lap_dist_pct));
+        }
+
+        if let Some(lap) = &mut self.lap_completed {
+            let step = data.lap_completed - lap.value;
+            let plausible = relocating || step == 0 || step == 1;
+            let (value, corrected) = lap.check(data.lap_completed, plausible);
+            if corrected {
+                data.lap_completed = value;
+                self.counters.add("lap_count_jump");
+            }
+        } else {
+            self.lap_completed = Some(Held::new(data.lap_completed));
+        }
+
+        if let Some(positions) = data.CarIdxLapDistPct.as_mut() {
+            if self.car_lap_dist_pct.len() != positions.len() {
+                self.car_lap_dist_pct = positions.iter().map(|&pct| Held::new(pct)).collect();
+                return;
+            }
+            let on_pit_road = data.CarIdxOnPitRoad.as_ref();
+            for (idx, (pct, held)) in positions.iter_mut().zip(&mut self.car_lap_dist_pct).enumerate() {
+                let in_pits = on_pit_road.and_then(|p| p.get(idx)).copied().unwrap_or(false);
+                let plausible = in_pits || !moved_backwards(held.value, *pct);
+                let (value, corrected) = held.check(*pct, plausible);
+                if corrected {
+                    *pct = value;
+                    self.counters.add("car_lap_dist_backwards");
+                }
+            }
+        }
+    }
+}
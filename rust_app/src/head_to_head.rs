@@ -0,0 +1,153 @@
+//! Live head-to-head between two cars for battle graphics.
+//!
+//! A client sends `{"type":"compare","cars":[3,12]}` and from then on receives, with each
+//! frame, a compact `compare` message: the gap between the two cars, each car's recent
+//! lap times and their trend, the delta over the last sector both have completed and
+//! whether either is in the pits. `{"type":"compare","stop":true}` ends it. Sector and lap
+//! times come from the gap history, so they are available from the first crossing after
+//! the server started.
+
+use crate::gap_history::{GapHistoryStore, GapSample};
+use crate::lap_summary::SECTOR_COUNT;
+use crate::telemetry_fields::TelemetryData;
+use crate::websocket_server::StreamProvider;
+use serde::Serialize;
+use serde_json::{json, Value};
+
+/// Laps of lap times sent for the trend
+const TREND_LAPS: usize = 5;
+
+#[derive(Serialize, Debug, Default)]
+struct CarSide {
+    car_idx: i32,
+    position: Option<i32>,
+    lap: Option<i32>,
+    last_lap_time: Option<f32>,
+    best_lap_time: Option<f32>,
+    /// Most recent first
+    recent_laps: Vec<f32>,
+    /// Seconds per lap the pace is changing by; negative is getting faster
+    trend: Option<f32>,
+    on_pit_road: bool,
+}
+
+/// Live head-to-head stream
+pub struct HeadToHead {
+    gap_history: GapHistoryStore,
+}
+
+impl HeadToHead {
+    pub fn new(gap_history: GapHistoryStore) -> Self {
+        Self { gap_history }
+    }
+}
+
+// Value of a per-car channel
+fn car_value<T: Copy>(values: Option<&Vec<T>>, car_idx: i32) -> Option<T> {
+    values.and_then(|values| values.get(car_idx as usize).copied())
+}
+
+fn positive(value: Option<f32>) -> Option<f32> {
+    value.filter(|v| *v > 0.0)
+}
+
+// Lap times from consecutive crossings of the line, most recent first
+fn lap_times(samples: &[GapSample]) -> Vec<f32> {
+    let starts: Vec<&GapSample> = samples.iter().filter(|s| s.sector == 0).collect();
+    starts
+        .windows(2)
+        .rev()
+        .filter(|pair| pair[1].lap == pair[0].lap + 1)
+        .map(|pair| pair[1].session_time - pair[0].session_time)
+        .filter(|time| *time > 0.0)
+        .take(TREND_LAPS)
+        .collect()
+}
+
+// Least squares slope of lap time against lap number
+fn trend(recent_laps: &[f32]) -> Option<f32> {
+    if recent_laps.len() < 3 {
+        return None;
+    }
+    // recent_laps is most recent first, so lap number counts down
+    let n = recent_laps.len() as f32;
+    let points: Vec<(f32, f32)> = recent_laps.iter().enumerate().map(|(i, t)| (-(i as f32), *t)).collect();
+    let mean_x = points.iter().map(|p| p.0).sum::<f32>() / n;
+    let mean_y = points.iter().map(|p| p.1).sum::<f32>() / n;
+    let covariance: f32 = points.iter().map(|(x, y)| (x - mean_x) * (y - mean_y)).sum();
+    let variance: f32 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+    (variance > 0.0).then(|| covariance / variance)
+}
+
+// Most recent time for each sector, from consecutive boundary crossings
+fn sector_times(samples: &[GapSample]) -> Vec<Option<(f32, f32)>> {
+    let mut times = vec![None; SECTOR_COUNT];
+    for pair in samples.windows(2) {
+        if pair[1].sector == (pair[0].sector + 1) % SECTOR_COUNT {
+            // (time, when it was completed)
+            times[pair[0].sector] = Some((pair[1].session_time - pair[0].session_time, pair[1].session_time));
+        }
+    }
+    times
+}
+
+impl StreamProvider for HeadToHead {
+    fn subscribe(&self, request: &Value) -> Result<Value, String> {
+        let cars: Vec<i64> = request
+            .get("cars")
+            .and_then(Value::as_array)
+            .map(|cars| cars.iter().filter_map(Value::as_i64).collect())
+            .unwrap_or_default();
+        match cars.as_slice() {
+            [a, b] if a != b && *a >= 0 && *b >= 0 => Ok(json!({ "cars": [a, b] })),
+            _ => Err("compare needs \"cars\": two different CarIdx values".to_string()),
+        }
+    }
+
+    fn message(&self, telemetry: &TelemetryData, params: &Value) -> Option<Value> {
+        let cars: Vec<i32> = params.get("cars")?.as_array()?.iter().filter_map(Value::as_i64).map(|idx| idx as i32).collect();
+        let [a, b] = cars[..] else {
+            return None;
+        };
+        let history = self.gap_history.snapshot(None, Some(TREND_LAPS + 1));
+        let samples = |car_idx: i32| history.get(&car_idx).map(Vec::as_slice).unwrap_or_default();
+
+        let side = |car_idx: i32| {
+            let recent_laps = lap_times(samples(car_idx));
+            CarSide {
+                car_idx,
+                position: car_value(telemetry.CarIdxPosition.as_ref(), car_idx).filter(|p| *p > 0),
+                lap: car_value(telemetry.CarIdxLap.as_ref(), car_idx).filter(|lap| *lap >= 0),
+                last_lap_time: positive(car_value(telemetry.CarIdxLastLapTime.as_ref(), car_idx)),
+                best_lap_time: positive(car_value(telemetry.CarIdxBestLapTime.as_ref(), car_idx)),
+                trend: trend(&recent_laps),
+                recent_laps,
+                on_pit_road: car_value(telemetry.CarIdxOnPitRoad.as_ref(), car_idx).unwrap_or(false),
+            }
+        };
+
+        // Positive when b is behind a
+        let gap = match (car_value(telemetry.CarIdxGapToLeader.as_ref(), a), car_value(telemetry.CarIdxGapToLeader.as_ref(), b)) {
+            (Some(gap_a), Some(gap_b)) => Some(gap_b - gap_a),
+            _ => None,
+        };
+
+        // Delta over the sector both cars completed most recently; positive when b was slower
+        let (sectors_a, sectors_b) = (sector_times(samples(a)), sector_times(samples(b)));
+        let last_sector = (0..SECTOR_COUNT)
+            .filter_map(|sector| match (sectors_a[sector], sectors_b[sector]) {
+                (Some((time_a, _)), Some((time_b, when_b))) => Some((sector, time_b - time_a, when_b)),
+                _ => None,
+            })
+            .max_by(|x, y| x.2.total_cmp(&y.2))
+            .map(|(sector, delta, _)| json!({ "sector": sector, "delta": delta }));
+
+        Some(json!({
+            "type": "compare",
+            "session_time": telemetry.SessionTime,
+            "gap": gap,
+            "last_sector": last_sector,
+            "cars": [side(a), side(b)],
+        }))
+    }
+}
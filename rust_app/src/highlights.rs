@@ -0,0 +1,271 @@
+//! Highlight markers for editing stream recordings.
+//!
+//! Notable moments (overtakes by the player or for the lead, incidents, fastest laps,
+//! a close final lap, manual markers) are collected during a session and written
+//! out as CSV, an EDL with timeline markers (DaVinci Resolve, Premiere) and YouTube
+//! chapters when the checkered flag falls or a new session starts.
+//!
+//! Stream timestamps count from the last `recording_toggle` that started a recording,
+//! or from the start of the session when recording was never toggled.
+
+use crate::derived::DerivedChannel;
+use crate::events::TelemetryEvent;
+use crate::file_io;
+use crate::telemetry_fields::{TelemetryData, FLAG_CHECKERED, FLAG_WHITE};
+use chrono::{DateTime, Local};
+use std::fmt::Write as _;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Instant;
+
+// Gap to a neighbour that makes the final lap a battle (seconds)
+const BATTLE_GAP_S: f32 = 1.0;
+// Frame rate used for EDL timecodes
+const EDL_FPS: u64 = 30;
+
+/// One moment worth jumping to while editing
+#[derive(Clone, Debug)]
+pub struct Highlight {
+    pub kind: String,
+    pub description: String,
+    pub session_time: f32,
+    pub lap: i32,
+    pub wall_clock: DateTime<Local>,
+    /// Seconds into the stream recording
+    pub stream_offset_s: f64,
+}
+
+/// Derived channel that collects highlights and exports them at the end of a session
+pub struct HighlightReel {
+    output_dir: PathBuf,
+    highlights: Vec<Highlight>,
+    stream_start: Instant,
+    session_started: DateTime<Local>,
+    exported: bool,
+    last_incidents: Option<i32>,
+    fastest_lap: f32,
+    final_lap_battle: bool,
+}
+
+impl HighlightReel {
+    pub fn new(output_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            output_dir: output_dir.into(),
+            highlights: Vec::new(),
+            stream_start: Instant::now(),
+            session_started: Local::now(),
+            exported: false,
+            last_incidents: None,
+            fastest_lap: 0.0,
+            final_lap_battle: false,
+        }
+    }
+
+    fn add(&mut self, kind: &str, description: String, data: &TelemetryData) {
+        self.highlights.push(Highlight {
+            kind: kind.to_string(),
+            description,
+            session_time: data.SessionTime,
+            lap: data.lap_completed,
+            wall_clock: Local::now(),
+            stream_offset_s: self.stream_start.elapsed().as_secs_f64(),
+        });
+    }
+
+    fn start_session(&mut self) {
+        if !self.exported {
+            self.export();
+        }
+        let stream_start = self.stream_start;
+        *self = Self::new(std::mem::take(&mut self.output_dir));
+        // A recording in progress carries over to the next session
+        self.stream_start = stream_start;
+    }
+
+    // Turn a frame event into a highlight when it's interesting to viewers
+    fn from_event(&mut self, event: &TelemetryEvent, data: &TelemetryData) {
+        match event.kind.as_str() {
+            "overtake" => {
+                let car = event.data["car_idx"].as_i64().unwrap_or(-1);
+                let passed = event.data["passed_car_idx"].as_i64().unwrap_or(-1);
+                let position = event.data["position"].as_i64().unwrap_or(0);
+                let player = data.player_car_idx as i64;
+                let corner = event.data["corner"].as_str().map(|c| format!(" at {}", c)).unwrap_or_default();
+                let description = if car == player {
+                    format!("Overtook #{} for P{}{}", passed, position, corner)
+                } else if passed == player {
+                    format!("Overtaken by #{}{}", car, corner)
+                } else if position == 1 {
+                    format!("Lead change: #{} passes #{}{}", car, passed, corner)
+                } else {
+                    return;
+                };
+                self.add("overtake", description, data);
+            },
+            "marker" => {
+                let label = event.data["label"].as_str().unwrap_or("marker").to_string();
+                self.add("marker", label, data);
+            },
+            "recording_toggle" => {
+                if event.data["recording"].as_bool() == Some(true) {
+                    self.stream_start = Instant::now();
+                }
+            },
+            _ => {},
+        }
+    }
+
+    fn final_lap_gap(data: &TelemetryData) -> Option<f32> {
+        let positions = data.CarIdxPosition.as_ref()?;
+        let gaps = data.CarIdxGapToLeader.as_ref()?;
+        let player = usize::try_from(data.player_car_idx).ok()?;
+        let position = *positions.get(player)?;
+        let player_gap = *gaps.get(player)?;
+        positions
+            .iter()
+            .enumerate()
+            .filter(|&(_, &p)| p > 0 && (p == position - 1 || p == position + 1))
+            .filter_map(|(idx, _)| gaps.get(idx))
+            .map(|gap| (gap - player_gap).abs())
+            .min_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
+    }
+
+    fn export(&self) {
+        if self.highlights.is_empty() {
+            return;
+        }
+        let stem = format!("highlights_{}", self.session_started.format("%Y%m%d_%H%M%S"));
+        let files = [
+            (format!("{}.csv", stem), self.to_csv()),
+            (format!("{}.edl", stem), self.to_edl()),
+            (format!("{}_chapters.txt", stem), self.to_chapters()),
+        ];
+        let output_dir = self.output_dir.clone();
+        file_io::submit(move || {
+            if let Err(e) = fs::create_dir_all(&output_dir) {
+                tracing::error!("Failed to create highlights directory {}: {}", output_dir.display(), e);
+                return;
+            }
+            for (name, contents) in files {
+                let path = output_dir.join(name);
+                match fs::write(&path, contents) {
+                    Ok(()) => tracing::info!("Wrote {}", path.display()),
+                    Err(e) => tracing::error!("Failed to write {}: {}", path.display(), e),
+                }
+            }
+        });
+    }
+
+    fn to_csv(&self) -> String {
+        let mut csv = String::from("wall_clock,stream_time,session_time,lap,kind,description\n");
+        for h in &self.highlights {
+            let _ = writeln!(
+                csv,
+                "{},{},{:.3},{},{},\"{}\"",
+                h.wall_clock.to_rfc3339(),
+                format_clock(h.stream_offset_s),
+                h.session_time,
+                h.lap,
+                h.kind,
+                h.description.replace('"', "\"\"")
+            );
+        }
+        csv
+    }
+
+    fn to_edl(&self) -> String {
+        let mut edl = String::from("TITLE: Highlights\nFCM: NON-DROP FRAME\n\n");
+        for (i, h) in self.highlights.iter().enumerate() {
+            let start = timecode(h.stream_offset_s);
+            let end = timecode(h.stream_offset_s + 1.0 / EDL_FPS as f64);
+            let _ = writeln!(edl, "{:03}  001      V     C        {} {} {} {}", i + 1, start, end, start, end);
+            let _ = writeln!(edl, " |C:ResolveColorBlue |M:{} |D:1\n", h.description);
+        }
+        edl
+    }
+
+    fn to_chapters(&self) -> String {
+        // YouTube needs the first chapter at 0:00
+        let mut chapters = String::from("0:00 Start\n");
+        let mut last_second = 0;
+        for h in &self.highlights {
+            let second = h.stream_offset_s as u64;
+            // Chapters must be at least 10 seconds apart
+            if second < last_second + 10 {
+                continue;
+            }
+            last_second = second;
+            let _ = writeln!(chapters, "{} {}", format_clock(h.stream_offset_s), h.description);
+        }
+        chapters
+    }
+}
+
+fn format_clock(seconds: f64) -> String {
+    let total = seconds.max(0.0) as u64;
+    let (hours, minutes, secs) = (total / 3600, total / 60 % 60, total % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, secs)
+    } else {
+        format!("{}:{:02}", minutes, secs)
+    }
+}
+
+fn timecode(seconds: f64) -> String {
+    let frames = (seconds.max(0.0) * EDL_FPS as f64) as u64;
+    let total = frames / EDL_FPS;
+    format!("{:02}:{:02}:{:02}:{:02}", total / 3600, total / 60 % 60, total % 60, frames % EDL_FPS)
+}
+
+impl DerivedChannel for HighlightReel {
+    fn name(&self) -> &'static str {
+        "highlights"
+    }
+
+    fn dependencies(&self) -> &'static [&'static str] {
+        &["overtakes"]
+    }
+
+    fn compute(&mut self, data: &mut TelemetryData) {
+        if data.race_clock.session_reset {
+            self.start_session();
+        }
+
+        let events = std::mem::take(&mut data.events);
+        for event in &events {
+            self.from_event(event, data);
+        }
+        data.events = events;
+
+        if let Some(last) = self.last_incidents.replace(data.incident_count) && data.incident_count > last {
+            self.add("incident", format!("Incident ({}x)", data.incident_count - last), data);
+        }
+
+        let session_best = data
+            .CarIdxBestLapTime
+            .as_ref()
+            .and_then(|times| {
+                times.iter().enumerate().filter(|&(_, &t)| t > 0.0).min_by(|a, b| {
+                    a.1.partial_cmp(b.1).unwrap_or(std::cmp::Ordering::Equal)
+                })
+            })
+            .map(|(idx, &time)| (idx, time));
+        if let Some((idx, time)) = session_best && (self.fastest_lap <= 0.0 || time < self.fastest_lap) {
+            // The first lap set isn't worth a marker on its own
+            if self.fastest_lap > 0.0 {
+                self.add("fastest_lap", format!("Fastest lap by #{}: {:.3}", idx, time), data);
+            }
+            self.fastest_lap = time;
+        }
+
+        if !self.final_lap_battle && data.session_flags & FLAG_WHITE != 0 && Self::final_lap_gap(data).is_some_and(|gap| gap < BATTLE_GAP_S) {
+            self.final_lap_battle = true;
+            self.add("final_lap_battle", "Final lap battle".to_string(), data);
+        }
+
+        if !self.exported && data.session_flags & FLAG_CHECKERED != 0 {
+            self.exported = true;
+            self.export();
+        }
+    }
+}
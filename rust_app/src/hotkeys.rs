@@ -0,0 +1,161 @@
+//! Global hotkeys on the host PC.
+//!
+//! Disabled unless a mapping is given. Hotkeys are registered system wide, so pick
+//! combinations the sim and other tools don't use (Ctrl+Alt+letter works well).
+
+use crate::controls::{ControlAction, ControlSender};
+
+// Modifier flags used by RegisterHotKey
+const MOD_ALT: u32 = 0x0001;
+const MOD_CONTROL: u32 = 0x0002;
+const MOD_SHIFT: u32 = 0x0004;
+const MOD_WIN: u32 = 0x0008;
+
+/// A key combination bound to an action
+#[derive(Clone, Debug)]
+pub struct Hotkey {
+    pub modifiers: u32,
+    pub virtual_key: u32,
+    pub description: String,
+    pub action: ControlAction,
+}
+
+/// Parse a combination such as "Ctrl+Alt+R" into (modifiers, virtual key code)
+pub fn parse_combination(combination: &str) -> Result<(u32, u32), String> {
+    let mut modifiers = 0;
+    let mut key = None;
+    for part in combination.split('+').map(str::trim) {
+        match part.to_ascii_lowercase().as_str() {
+            "ctrl" | "control" => modifiers |= MOD_CONTROL,
+            "alt" => modifiers |= MOD_ALT,
+            "shift" => modifiers |= MOD_SHIFT,
+            "win" | "super" => modifiers |= MOD_WIN,
+            other => {
+                if key.is_some() {
+                    return Err(format!("More than one key in '{}'", combination));
+                }
+                key = Some(virtual_key_code(other).ok_or_else(|| format!("Unknown key '{}'", part))?);
+            }
+        }
+    }
+    let key = key.ok_or_else(|| format!("No key in '{}'", combination))?;
+    if modifiers == 0 {
+        return Err(format!("'{}' needs at least one modifier so it doesn't swallow normal typing", combination));
+    }
+    Ok((modifiers, key))
+}
+
+// Virtual key codes for letters, digits and function keys
+fn virtual_key_code(key: &str) -> Option<u32> {
+    let upper = key.to_ascii_uppercase();
+    let mut chars = upper.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) && (c.is_ascii_uppercase() || c.is_ascii_digit()) {
+        return Some(c as u32);
+    }
+    if let Some(number) = upper.strip_prefix('F') && let Ok(n) = number.parse::<u32>() && (1..=24).contains(&n) {
+        return Some(0x70 + n - 1);
+    }
+    None
+}
+
+/// Parse a mapping such as "Ctrl+Alt+R=toggle_recording,Ctrl+Alt+M=marker"
+pub fn parse_mapping(mapping: &str) -> Result<Vec<Hotkey>, String> {
+    let mut hotkeys = Vec::new();
+    for entry in mapping.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        let (combination, action_name) = entry
+            .split_once('=')
+            .ok_or_else(|| format!("Expected <keys>=<action> in '{}'", entry))?;
+        let (modifiers, virtual_key) = parse_combination(combination)?;
+        let action = ControlAction::parse(action_name, "hotkey")
+            .ok_or_else(|| format!("Unknown hotkey action '{}'", action_name.trim()))?;
+        hotkeys.push(Hotkey {
+            modifiers,
+            virtual_key,
+            description: combination.trim().to_string(),
+            action,
+        });
+    }
+    Ok(hotkeys)
+}
+
+#[cfg(target_os = "windows")]
+mod win32 {
+    use std::ffi::c_void;
+
+    pub const WM_HOTKEY: u32 = 0x0312;
+    pub const MOD_NOREPEAT: u32 = 0x4000;
+
+    #[repr(C)]
+    pub struct Point {
+        pub x: i32,
+        pub y: i32,
+    }
+
+    #[repr(C)]
+    pub struct Msg {
+        pub hwnd: *mut c_void,
+        pub message: u32,
+        pub w_param: usize,
+        pub l_param: isize,
+        pub time: u32,
+        pub pt: Point,
+        pub l_private: u32,
+    }
+
+    #[link(name = "user32")]
+    unsafe extern "system" {
+        pub fn RegisterHotKey(hwnd: *mut c_void, id: i32, modifiers: u32, vk: u32) -> i32;
+        pub fn GetMessageW(msg: *mut Msg, hwnd: *mut c_void, filter_min: u32, filter_max: u32) -> i32;
+    }
+}
+
+/// Register the hotkeys and forward presses to the telemetry loop from a background thread
+#[cfg(target_os = "windows")]
+pub fn start(hotkeys: Vec<Hotkey>, actions: ControlSender) -> Result<(), String> {
+    let (ready_tx, ready_rx) = std::sync::mpsc::channel();
+
+    std::thread::Builder::new()
+        .name("hotkeys".to_string())
+        .spawn(move || {
+            // Hotkeys belong to the thread that registers them, so register here
+            let mut registered = Vec::new();
+            for (id, hotkey) in hotkeys.iter().enumerate() {
+                let ok = unsafe {
+                    win32::RegisterHotKey(
+                        std::ptr::null_mut(),
+                        id as i32 + 1,
+                        hotkey.modifiers | win32::MOD_NOREPEAT,
+                        hotkey.virtual_key,
+                    )
+                };
+                if ok == 0 {
+                    tracing::error!("Failed to register {} (already in use?)", hotkey.description);
+                } else {
+                    registered.push(hotkey.description.clone());
+                }
+            }
+            let _ = ready_tx.send(registered);
+
+            let mut msg: win32::Msg = unsafe { std::mem::zeroed() };
+            while unsafe { win32::GetMessageW(&mut msg, std::ptr::null_mut(), 0, 0) } > 0 {
+                if msg.message == win32::WM_HOTKEY && let Some(hotkey) = hotkeys.get(msg.w_param.wrapping_sub(1)) && actions.send(hotkey.action.clone()).is_err() {
+                    // Telemetry loop is gone
+                    break;
+                }
+            }
+        })
+        .map_err(|e| format!("Failed to start hotkey thread: {}", e))?;
+
+    match ready_rx.recv() {
+        Ok(registered) if !registered.is_empty() => {
+            tracing::info!("Registered: {}", registered.join(", "));
+            Ok(())
+        },
+        _ => Err("No hotkeys could be registered".to_string()),
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+pub fn start(_hotkeys: Vec<Hotkey>, _actions: ControlSender) -> Result<(), String> {
+    Err("Global hotkeys are only supported on Windows".to_string())
+}
@@ -0,0 +1,351 @@
+//! Minimal HTTP/1.1 handling for plain requests arriving on the WebSocket port.
+//!
+//! Clients already reach the telemetry server on one port, so small REST style
+//! endpoints share it: connections that don't ask for a WebSocket upgrade are
+//! parsed here and answered by the registered handlers. Cross-origin requests get CORS
+//! headers when their origin is allowed (see origins.rs); pages served by this server are
+//! same-origin and always allowed.
+
+use crate::origins::OriginPolicy;
+use socket2::SockRef;
+use std::collections::HashMap;
+use std::io;
+use std::mem::MaybeUninit;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt, Interest};
+use tokio::net::TcpStream;
+use tokio::time::Instant;
+
+/// Largest request head we are willing to buffer
+const MAX_HEAD_BYTES: usize = 16 * 1024;
+/// Largest request body accepted
+pub const MAX_BODY_BYTES: usize = 1024 * 1024;
+/// How long a client gets to send its whole request
+const HEAD_TIMEOUT: Duration = Duration::from_secs(5);
+/// Answer to preflights, which ask before sending anything but a simple request
+const CORS_METHODS: &str = "GET, PUT, POST, DELETE, OPTIONS";
+const CORS_HEADERS: &str = "Content-Type, Authorization";
+/// How long an event stream client waits before reconnecting
+const EVENT_STREAM_RETRY_MS: u64 = 2000;
+
+/// A parsed HTTP request
+#[derive(Debug)]
+pub struct HttpRequest {
+    pub method: String,
+    /// Path without the query string, e.g. "/profiles/main"
+    pub path: String,
+    pub query: HashMap<String, String>,
+    /// Header names are lower case
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+impl HttpRequest {
+    /// Path split into its non-empty segments
+    pub fn segments(&self) -> Vec<&str> {
+        self.path.split('/').filter(|s| !s.is_empty()).collect()
+    }
+
+    /// Whether the request carries `token`, as `Authorization: Bearer <token>` or `?token=`.
+    /// Always false without a token
+    pub fn has_token(&self, token: Option<&str>) -> bool {
+        let Some(token) = token else {
+            return false;
+        };
+        let bearer = self
+            .headers
+            .get("authorization")
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(str::trim);
+        bearer == Some(token) || self.query.get("token").map(String::as_str) == Some(token)
+    }
+
+    /// Whether the page sending the request, if any, may be served: an origin the policy
+    /// allows, or a page this server served itself
+    pub fn origin_allowed(&self, origins: &OriginPolicy) -> bool {
+        origins.is_allowed(self.headers.get("origin").map(String::as_str)) || self.is_same_origin(origins)
+    }
+
+    // Whether the `Origin` is this server, i.e. a page it served itself. A page from a
+    // rebound name also matches its `Host`, so the host has to be one the policy trusts
+    fn is_same_origin(&self, origins: &OriginPolicy) -> bool {
+        let (Some(origin), Some(host)) = (self.headers.get("origin"), self.headers.get("host")) else {
+            return false;
+        };
+        origin.split_once("://").is_some_and(|(_, origin_host)| origin_host.eq_ignore_ascii_case(host))
+            && origins.is_trusted_host(host)
+    }
+}
+
+/// A response to send back to the client
+#[derive(Debug)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub content_type: String,
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    pub fn json(status: u16, value: &serde_json::Value) -> Self {
+        Self {
+            status,
+            content_type: "application/json".to_string(),
+            body: value.to_string().into_bytes(),
+        }
+    }
+
+    /// JSON body that is already serialized
+    pub fn raw_json(status: u16, body: Vec<u8>) -> Self {
+        Self {
+            status,
+            content_type: "application/json".to_string(),
+            body,
+        }
+    }
+
+    pub fn html(status: u16, body: &str) -> Self {
+        Self {
+            status,
+            content_type: "text/html; charset=utf-8".to_string(),
+            body: body.as_bytes().to_vec(),
+        }
+    }
+
+    pub fn error(status: u16, message: &str) -> Self {
+        Self::json(status, &serde_json::json!({ "error": message }))
+    }
+
+    pub fn no_content() -> Self {
+        Self {
+            status: 204,
+            content_type: "text/plain".to_string(),
+            body: Vec::new(),
+        }
+    }
+}
+
+/// Something that can answer HTTP requests. Returns None when the path isn't its own
+pub trait HttpHandler: Send + Sync {
+    fn handle(&self, request: &HttpRequest) -> Option<HttpResponse>;
+}
+
+fn find_head_end(buf: &[u8]) -> Option<usize> {
+    buf.windows(4).position(|w| w == b"\r\n\r\n").map(|pos| pos + 4)
+}
+
+// What has arrived on the connection so far, left in place; WouldBlock when nothing has
+fn peek_now(stream: &TcpStream, buf: &mut [u8]) -> io::Result<usize> {
+    // SAFETY: initialized bytes are valid MaybeUninit<u8>, and peek only writes to them
+    let uninit = unsafe { &mut *(buf as *mut [u8] as *mut [MaybeUninit<u8>]) };
+    SockRef::from(stream).peek(uninit)
+}
+
+/// Look at the start of a new connection without consuming it and report whether
+/// it is a WebSocket upgrade. Plain HTTP requests return false.
+pub async fn is_websocket_upgrade(stream: &TcpStream) -> bool {
+    let mut buf = vec![0u8; MAX_HEAD_BYTES];
+    let mut len = 0;
+    let deadline = Instant::now() + HEAD_TIMEOUT;
+    loop {
+        match tokio::time::timeout_at(deadline, stream.readable()).await {
+            Ok(Ok(())) => {},
+            Ok(Err(_)) => return true, // Let the WebSocket handshake report the failure
+            Err(_) => break,
+        }
+        // An incomplete head counts as WouldBlock, which clears the readiness, so
+        // `readable` waits for more bytes rather than returning straight away
+        let peeked = stream.try_io(Interest::READABLE, || {
+            let peeked = peek_now(stream, &mut buf)?;
+            len = peeked;
+            if peeked > 0 && peeked < buf.len() && find_head_end(&buf[..peeked]).is_none() {
+                return Err(io::ErrorKind::WouldBlock.into());
+            }
+            Ok(peeked)
+        });
+        match peeked {
+            Ok(0) => return true,
+            Ok(_) => break,
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => continue,
+            Err(_) => return true,
+        }
+    }
+    // A head that didn't complete in time is judged on what arrived
+    let head = String::from_utf8_lossy(&buf[..len]).to_ascii_lowercase();
+    head.lines().any(|line| line.starts_with("upgrade:") && line.contains("websocket"))
+}
+
+fn parse_query(query: &str) -> HashMap<String, String> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| match pair.split_once('=') {
+            Some((key, value)) => (key.to_string(), value.to_string()),
+            None => (pair.to_string(), String::new()),
+        })
+        .collect()
+}
+
+/// Read one request from the connection
+pub async fn read_request(stream: &mut TcpStream) -> Result<HttpRequest, String> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 4096];
+    // One deadline for the whole request, so trickling bytes doesn't extend it
+    let deadline = Instant::now() + HEAD_TIMEOUT;
+    let head_end = loop {
+        if let Some(end) = find_head_end(&buf) {
+            break end;
+        }
+        if buf.len() > MAX_HEAD_BYTES {
+            return Err("Request head too large".to_string());
+        }
+        let read = tokio::time::timeout_at(deadline, stream.read(&mut chunk))
+            .await
+            .map_err(|_| "Timed out reading request".to_string())?
+            .map_err(|e| format!("Failed to read request: {}", e))?;
+        if read == 0 {
+            return Err("Connection closed before request was complete".to_string());
+        }
+        buf.extend_from_slice(&chunk[..read]);
+    };
+
+    let head = String::from_utf8_lossy(&buf[..head_end]).to_string();
+    let mut lines = head.split("\r\n");
+    let request_line = lines.next().unwrap_or_default();
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().ok_or("Missing method")?.to_ascii_uppercase();
+    let target = parts.next().ok_or("Missing path")?;
+    let (path, query) = match target.split_once('?') {
+        Some((path, query)) => (path.to_string(), parse_query(query)),
+        None => (target.to_string(), HashMap::new()),
+    };
+
+    let headers: HashMap<String, String> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+        .collect();
+
+    let content_length = match headers.get("content-length") {
+        Some(value) => value.parse::<usize>().map_err(|_| "Invalid Content-Length".to_string())?,
+        None => 0,
+    };
+    if content_length > MAX_BODY_BYTES {
+        return Err(format!("Request body larger than {} bytes", MAX_BODY_BYTES));
+    }
+
+    let mut body = buf[head_end..].to_vec();
+    while body.len() < content_length {
+        let read = tokio::time::timeout_at(deadline, stream.read(&mut chunk))
+            .await
+            .map_err(|_| "Timed out reading request body".to_string())?
+            .map_err(|e| format!("Failed to read request body: {}", e))?;
+        if read == 0 {
+            return Err("Connection closed before body was complete".to_string());
+        }
+        body.extend_from_slice(&chunk[..read]);
+    }
+    body.truncate(content_length);
+
+    Ok(HttpRequest { method, path, query, headers, body })
+}
+
+fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        202 => "Accepted",
+        204 => "No Content",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Payload Too Large",
+        500 => "Internal Server Error",
+        _ => "",
+    }
+}
+
+fn cors_headers(allow_origin: Option<&str>) -> String {
+    match allow_origin {
+        Some(origin) => format!(
+            "Access-Control-Allow-Origin: {}\r\nAccess-Control-Allow-Methods: {}\r\nAccess-Control-Allow-Headers: {}\r\nAccess-Control-Max-Age: 600\r\nVary: Origin\r\n",
+            origin, CORS_METHODS, CORS_HEADERS
+        ),
+        None => "Vary: Origin\r\n".to_string(),
+    }
+}
+
+/// Write a response and close the exchange. `allow_origin` is the page origin allowed to
+/// read it (cross-origin requests only)
+pub async fn write_response(stream: &mut TcpStream, response: &HttpResponse, allow_origin: Option<&str>) -> std::io::Result<()> {
+    let cors = cors_headers(allow_origin);
+    let head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n{}Connection: close\r\n\r\n",
+        response.status,
+        reason_phrase(response.status),
+        response.content_type,
+        response.body.len(),
+        cors
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(&response.body).await?;
+    stream.flush().await
+}
+
+/// Start a Server-Sent Events response: the head, without a length since the stream
+/// stays open, and the reconnect delay. Events follow with `write_event`
+pub async fn write_event_stream_head(stream: &mut TcpStream, allow_origin: Option<&str>) -> std::io::Result<()> {
+    // X-Accel-Buffering stops nginx and similar proxies from holding events back
+    let head = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nX-Accel-Buffering: no\r\n{}Connection: keep-alive\r\n\r\nretry: {}\n\n",
+        cors_headers(allow_origin),
+        EVENT_STREAM_RETRY_MS
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.flush().await
+}
+
+/// Send one Server-Sent Event carrying `data`; a line starting with ':' instead when
+/// `data` is None, which clients ignore and which keeps idle proxies from closing the stream
+pub async fn write_event(stream: &mut TcpStream, data: Option<&str>) -> std::io::Result<()> {
+    let mut event = String::new();
+    match data {
+        Some(data) => {
+            for line in data.lines() {
+                event.push_str("data: ");
+                event.push_str(line);
+                event.push('\n');
+            }
+        },
+        None => event.push_str(":\n"),
+    }
+    event.push('\n');
+    stream.write_all(event.as_bytes()).await?;
+    stream.flush().await
+}
+
+/// Answer a single plain HTTP request, as read by `read_request`, using the registered
+/// handlers. Requests from web pages the origin policy doesn't allow are refused,
+/// preflights included
+pub async fn serve(
+    mut stream: TcpStream,
+    request: Result<HttpRequest, String>,
+    handlers: &[std::sync::Arc<dyn HttpHandler>],
+    origins: &OriginPolicy,
+) -> Result<(), String> {
+    let origin = request.as_ref().ok().and_then(|request| request.headers.get("origin").cloned());
+    let allowed = request.as_ref().map_or(origins.is_allowed(None), |request| request.origin_allowed(origins));
+    let response = match request {
+        Ok(_) if !allowed => HttpResponse::error(403, "Origin not allowed"),
+        Ok(request) if request.method == "OPTIONS" => HttpResponse::no_content(),
+        Ok(request) => handlers
+            .iter()
+            .find_map(|handler| handler.handle(&request))
+            .unwrap_or_else(|| HttpResponse::error(404, "Not found")),
+        Err(e) => HttpResponse::error(400, &e),
+    };
+    let allow_origin = origin.as_deref().filter(|_| allowed);
+    write_response(&mut stream, &response, allow_origin)
+        .await
+        .map_err(|e| format!("Failed to write response: {}", e))
+}
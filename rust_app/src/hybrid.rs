@@ -0,0 +1,176 @@
+use crate::derived::DerivedChannel;
+use crate::laps::{LapChange, LapTracker};
+use crate::telemetry_fields::TelemetryData;
+use serde::{Serialize, Deserialize};
+use std::collections::BTreeMap;
+
+// Number of completed laps kept for reporting and the lap time correlation
+const MAX_LAP_HISTORY: usize = 20;
+// Laps needed before the correlation is reported
+const MIN_CORRELATION_LAPS: usize = 3;
+
+/// Hybrid energy use over one lap
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct HybridLap {
+    pub lap: i32,
+    /// 0 while the lap is in progress
+    pub lap_time: f32,
+    pub deployed_kj: f32,
+    pub recovered_kj: f32,
+    pub battery_start_pct: f32,
+    pub battery_end_pct: f32,
+    /// Seconds spent in each deployment mode, keyed by mode
+    pub mode_time_s: BTreeMap<i32, f32>,
+}
+
+impl HybridLap {
+    fn start(lap: i32, data: &TelemetryData) -> Self {
+        Self {
+            lap,
+            battery_start_pct: data.ers_battery_pct,
+            battery_end_pct: data.ers_battery_pct,
+            ..Default::default()
+        }
+    }
+
+    // Mode the lap was mostly driven in
+    fn main_mode(&self) -> Option<i32> {
+        self.mode_time_s.iter().max_by(|a, b| a.1.total_cmp(b.1)).map(|(mode, _)| *mode)
+    }
+}
+
+/// Average lap time of the laps driven mostly in one deployment mode
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct DeployModeLaps {
+    pub mode: i32,
+    pub laps: u32,
+    pub avg_lap_time: f32,
+    pub avg_deployed_kj: f32,
+}
+
+/// Deployment and recovery per lap for cars with a hybrid system
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct HybridStats {
+    pub deploy_mode: i32,
+    pub battery_pct: f32,
+    pub current_lap: HybridLap,
+    /// Most recent last
+    pub laps: Vec<HybridLap>,
+    pub modes: Vec<DeployModeLaps>,
+    /// Correlation between energy deployed and lap time over the recent laps; negative
+    /// means deploying more made the laps faster. None until enough laps are complete
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deploy_lap_time_correlation: Option<f32>,
+}
+
+// Pearson correlation coefficient
+fn correlation(points: &[(f32, f32)]) -> Option<f32> {
+    if points.len() < MIN_CORRELATION_LAPS {
+        return None;
+    }
+    let n = points.len() as f32;
+    let mean_x = points.iter().map(|p| p.0).sum::<f32>() / n;
+    let mean_y = points.iter().map(|p| p.1).sum::<f32>() / n;
+    let covariance: f32 = points.iter().map(|(x, y)| (x - mean_x) * (y - mean_y)).sum();
+    let variance_x: f32 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+    let variance_y: f32 = points.iter().map(|(_, y)| (y - mean_y).powi(2)).sum();
+    let denominator = (variance_x * variance_y).sqrt();
+    (denominator > 0.0).then(|| covariance / denominator)
+}
+
+/// Derived channel that attributes MGU-K deployment and recovery to laps and deployment modes
+pub struct HybridTracker {
+    laps: LapTracker,
+    current: Option<HybridLap>,
+    history: Vec<HybridLap>,
+}
+
+impl HybridTracker {
+    pub fn new() -> Self {
+        Self {
+            laps: LapTracker::new(),
+            current: None,
+            history: Vec::new(),
+        }
+    }
+
+    fn modes(&self) -> Vec<DeployModeLaps> {
+        let mut modes: BTreeMap<i32, DeployModeLaps> = BTreeMap::new();
+        for lap in self.history.iter().filter(|lap| lap.lap_time > 0.0) {
+            let Some(mode) = lap.main_mode() else {
+                continue;
+            };
+            let entry = modes.entry(mode).or_insert_with(|| DeployModeLaps { mode, ..Default::default() });
+            entry.laps += 1;
+            entry.avg_lap_time += lap.lap_time;
+            entry.avg_deployed_kj += lap.deployed_kj;
+        }
+        modes
+            .into_values()
+            .map(|mut mode| {
+                mode.avg_lap_time /= mode.laps as f32;
+                mode.avg_deployed_kj /= mode.laps as f32;
+                mode
+            })
+            .collect()
+    }
+}
+
+impl DerivedChannel for HybridTracker {
+    fn name(&self) -> &'static str {
+        "hybrid_stats"
+    }
+
+    fn compute(&mut self, data: &mut TelemetryData) {
+        // Cars without a hybrid system don't report a deployment mode
+        if data.mguk_deploy_mode < 0 {
+            return;
+        }
+        match self.laps.update(data) {
+            LapChange::Completed { lap, lap_time } => {
+                if let Some(mut finished) = self.current.take() {
+                    finished.lap = lap;
+                    finished.lap_time = lap_time;
+                    self.history.push(finished);
+                    if self.history.len() > MAX_LAP_HISTORY {
+                        self.history.remove(0);
+                    }
+                }
+            },
+            LapChange::Reset => {
+                self.current = None;
+                self.history.clear();
+            },
+            LapChange::None => {}
+        }
+
+        let current = self.current.get_or_insert_with(|| HybridLap::start(data.lap_completed, data));
+        let dt = data.race_clock.dt;
+        if dt > 0.0 {
+            // Positive MGU-K power is deployment, negative is recovery
+            let energy_kj = data.mguk_power_kw * dt;
+            if energy_kj > 0.0 {
+                current.deployed_kj += energy_kj;
+            } else {
+                current.recovered_kj -= energy_kj;
+            }
+            *current.mode_time_s.entry(data.mguk_deploy_mode).or_insert(0.0) += dt;
+        }
+        current.battery_end_pct = data.ers_battery_pct;
+        let current_lap = current.clone();
+
+        let points: Vec<(f32, f32)> = self.history
+            .iter()
+            .filter(|lap| lap.lap_time > 0.0)
+            .map(|lap| (lap.deployed_kj, lap.lap_time))
+            .collect();
+        data.hybrid_stats = Some(HybridStats {
+            deploy_mode: data.mguk_deploy_mode,
+            battery_pct: data.ers_battery_pct,
+            current_lap,
+            laps: self.history.clone(),
+            modes: self.modes(),
+            deploy_lap_time_correlation: correlation(&points),
+        });
+    }
+}
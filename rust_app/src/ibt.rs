@@ -0,0 +1,187 @@
+//! Reader for the .ibt telemetry files iRacing records itself.
+//!
+//! An .ibt file is the SDK memory map written to disk: the irsdk header, a disk sub
+//! header, the variable headers, the session info YAML and then one fixed size record
+//! per sample. Records are converted to `TelemetryData` for the channels lap and
+//! session summaries need; everything else in the file is left alone.
+
+use crate::telemetry_fields::TelemetryData;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+const HEADER_LEN: usize = 112;
+const DISK_HEADER_LEN: usize = 32;
+const VAR_HEADER_LEN: usize = 144;
+
+// irsdk_VarType
+const TYPE_CHAR: i32 = 0;
+const TYPE_BOOL: i32 = 1;
+const TYPE_INT: i32 = 2;
+const TYPE_BITFIELD: i32 = 3;
+const TYPE_FLOAT: i32 = 4;
+const TYPE_DOUBLE: i32 = 5;
+
+/// Where a variable sits in each record
+#[derive(Clone, Copy, Debug)]
+struct Var {
+    var_type: i32,
+    offset: usize,
+}
+
+/// A parsed .ibt file
+pub struct IbtFile {
+    pub tick_rate: i32,
+    /// Unix time the recording started
+    pub start_date: i64,
+    pub lap_count: i32,
+    pub session_info: String,
+    vars: HashMap<String, Var>,
+    data: Vec<u8>,
+    records_offset: usize,
+    record_len: usize,
+    record_count: usize,
+}
+
+fn read_i32(bytes: &[u8], at: usize) -> Result<i32, String> {
+    bytes
+        .get(at..at + 4)
+        .map(|b| i32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| format!("truncated file at byte {}", at))
+}
+
+fn read_i64(bytes: &[u8], at: usize) -> Result<i64, String> {
+    bytes
+        .get(at..at + 8)
+        .and_then(|b| b.try_into().ok())
+        .map(i64::from_le_bytes)
+        .ok_or_else(|| format!("truncated file at byte {}", at))
+}
+
+// NUL padded ASCII names
+fn fixed_str(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
+
+impl IbtFile {
+    pub fn open(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let data = fs::read(path)?;
+        if data.len() < HEADER_LEN + DISK_HEADER_LEN {
+            return Err(format!("{} is too short to be an .ibt file", path.display()).into());
+        }
+
+        let tick_rate = read_i32(&data, 8)?;
+        let session_info_len = read_i32(&data, 16)? as usize;
+        let session_info_offset = read_i32(&data, 20)? as usize;
+        let num_vars = read_i32(&data, 24)? as usize;
+        let var_header_offset = read_i32(&data, 28)? as usize;
+        let record_len = read_i32(&data, 36)? as usize;
+        // First varBuf entry: tickCount, bufOffset
+        let records_offset = read_i32(&data, 52)? as usize;
+
+        let start_date = read_i64(&data, HEADER_LEN)?;
+        let lap_count = read_i32(&data, HEADER_LEN + 24)?;
+        let declared_records = read_i32(&data, HEADER_LEN + 28)?.max(0) as usize;
+
+        let mut vars = HashMap::with_capacity(num_vars);
+        for i in 0..num_vars {
+            let at = var_header_offset + i * VAR_HEADER_LEN;
+            let header = data
+                .get(at..at + VAR_HEADER_LEN)
+                .ok_or_else(|| format!("{}: variable header {} is truncated", path.display(), i))?;
+            let var = Var {
+                var_type: read_i32(header, 0)?,
+                offset: read_i32(header, 4)? as usize,
+            };
+            vars.insert(fixed_str(&header[16..48]), var);
+        }
+
+        let session_info = data
+            .get(session_info_offset..session_info_offset + session_info_len)
+            .map(|bytes| fixed_str(bytes))
+            .unwrap_or_default();
+
+        // Files from a crash don't have the record count filled in; trust the file size then
+        let available = if record_len > 0 { data.len().saturating_sub(records_offset) / record_len } else { 0 };
+        let record_count = if declared_records > 0 { declared_records.min(available) } else { available };
+
+        Ok(Self {
+            tick_rate,
+            start_date,
+            lap_count,
+            session_info,
+            vars,
+            data,
+            records_offset,
+            record_len,
+            record_count,
+        })
+    }
+
+    pub fn record_count(&self) -> usize {
+        self.record_count
+    }
+
+    /// Numeric value of a variable in a record, as f64
+    fn value(&self, record: &[u8], name: &str) -> Option<f64> {
+        let var = self.vars.get(name)?;
+        let bytes = record.get(var.offset..)?;
+        let value = match var.var_type {
+            TYPE_CHAR | TYPE_BOOL => *bytes.first()? as f64,
+            TYPE_INT | TYPE_BITFIELD => i32::from_le_bytes(bytes.get(..4)?.try_into().ok()?) as f64,
+            TYPE_FLOAT => f32::from_le_bytes(bytes.get(..4)?.try_into().ok()?) as f64,
+            TYPE_DOUBLE => f64::from_le_bytes(bytes.get(..8)?.try_into().ok()?),
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    /// One record as a frame
+    pub fn frame(&self, index: usize) -> Option<TelemetryData> {
+        let start = self.records_offset + index * self.record_len;
+        let record = self.data.get(start..start + self.record_len)?;
+        let get = |name: &str| self.value(record, name).unwrap_or(0.0) as f32;
+
+        let mut data = TelemetryData::default();
+        data.sim = "iracing".to_string();
+        data.SessionTime = self.value(record, "SessionTime").unwrap_or(0.0) as f32;
+        data.session_num = get("SessionNum") as i32;
+        data.speed_kph = get("Speed") * 3.6;
+        data.speed_mph = get("Speed") * 2.23694;
+        data.rpm = get("RPM");
+        data.gear_num = get("Gear") as i32;
+        data.throttle_pct = get("Throttle") * 100.0;
+        data.brake_pct = get("Brake") * 100.0;
+        data.fuel_level = get("FuelLevel");
+        data.lap_completed = get("Lap") as i32;
+        data.lap_dist_pct = get("LapDistPct");
+        data.lap_dist = get("LapDist");
+        data.last_lap_time = get("LapLastLapTime");
+        data.best_lap_time = get("LapBestLapTime");
+        data.on_pit_road = get("OnPitRoad") != 0.0;
+        data.oil_temp_c = get("OilTemp");
+        data.water_temp_c = get("WaterTemp");
+        data.track_temp_c = get("TrackTempCrew");
+        data.air_temp_c = get("AirTemp");
+        data.humidity_pct = get("RelativeHumidity") * 100.0;
+        data.wind_vel_ms = get("WindVel");
+        data.wind_dir_rad = get("WindDir");
+        data.track_wetness = get("TrackWetness") as i32;
+        data.lat = self.value(record, "Lat").unwrap_or(0.0);
+        data.lon = self.value(record, "Lon").unwrap_or(0.0);
+        Some(data)
+    }
+
+    /// All records in order; the first carries the session info
+    pub fn frames(&self) -> impl Iterator<Item = TelemetryData> + '_ {
+        (0..self.record_count).filter_map(move |index| {
+            let mut frame = self.frame(index)?;
+            if index == 0 {
+                frame.session_info = self.session_info.clone();
+            }
+            Some(frame)
+        })
+    }
+}
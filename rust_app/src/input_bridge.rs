@@ -0,0 +1,116 @@
+//! Wheel / button box input bridge.
+//!
+//! Listens to game controller buttons and turns mapped button presses into `marker`
+//! events so drivers can flag moments to review after the session. Only available when
+//! built with the `gamepad` feature.
+
+use crate::events::EventQueue;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Default time during which repeated presses of the same button are ignored
+pub const DEFAULT_DEBOUNCE_MS: u64 = 500;
+
+/// Which buttons create markers and how the markers are labelled
+#[derive(Clone, Debug)]
+pub struct InputBridgeConfig {
+    /// Button name (e.g. "South", "RightTrigger" or "code:305") to marker label
+    pub buttons: HashMap<String, String>,
+    pub debounce: Duration,
+}
+
+impl InputBridgeConfig {
+    /// Parse a mapping such as "South=flag_lap,code:305=review".
+    /// A button without a label produces a plain "marker" label.
+    pub fn parse(mapping: &str, debounce_ms: u64) -> Result<Self, String> {
+        let mut buttons = HashMap::new();
+        for entry in mapping.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (button, label) = match entry.split_once('=') {
+                Some((button, label)) => (button.trim(), label.trim()),
+                None => (entry, "marker"),
+            };
+            if button.is_empty() {
+                return Err(format!("Invalid button mapping: {}", entry));
+            }
+            buttons.insert(button.to_string(), label.to_string());
+        }
+        if buttons.is_empty() {
+            return Err("No buttons mapped".to_string());
+        }
+        Ok(Self {
+            buttons,
+            debounce: Duration::from_millis(debounce_ms),
+        })
+    }
+}
+
+#[cfg(feature = "gamepad")]
+mod listener {
+    use super::InputBridgeConfig;
+    use crate::events::EventQueue;
+    use gilrs::{EventType, Gilrs};
+    use std::collections::HashMap;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    pub fn run(config: InputBridgeConfig, events: EventQueue) -> Result<(), String> {
+        let mut gilrs = Gilrs::new().map_err(|e| format!("Failed to initialize controller input: {}", e))?;
+
+        for (_, gamepad) in gilrs.gamepads() {
+            tracing::info!("Found controller: {}", gamepad.name());
+        }
+
+        let mut last_press: HashMap<String, Instant> = HashMap::new();
+        loop {
+            while let Some(event) = gilrs.next_event() {
+                if let EventType::ButtonPressed(button, code) = event.event {
+                    // Match either the logical button name or the raw code (button boxes
+                    // usually only report unknown buttons with a code)
+                    let by_name = format!("{:?}", button);
+                    let by_code = format!("code:{}", code.into_u32());
+                    let (key, label) = match config.buttons.get(&by_name) {
+                        Some(label) => (by_name, label.clone()),
+                        None => match config.buttons.get(&by_code) {
+                            Some(label) => (by_code, label.clone()),
+                            None => continue,
+                        },
+                    };
+
+                    let now = Instant::now();
+                    if let Some(previous) = last_press.get(&key) && now.duration_since(*previous) < config.debounce {
+                        continue;
+                    }
+                    last_press.insert(key.clone(), now);
+
+                    let device = gilrs.gamepad(event.id).name().to_string();
+                    events.push("marker", serde_json::json!({
+                        "label": label,
+                        "source": "controller",
+                        "button": key,
+                        "device": device,
+                    }));
+                }
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
+
+/// Start listening for controller buttons on a background thread
+#[cfg(feature = "gamepad")]
+pub fn start(config: InputBridgeConfig, events: EventQueue) -> Result<(), String> {
+    std::thread::Builder::new()
+        .name("input-bridge".to_string())
+        .spawn(move || {
+            if let Err(e) = listener::run(config, events) {
+                tracing::warn!("{}", e);
+            }
+        })
+        .map(|_| ())
+        .map_err(|e| format!("Failed to start input bridge thread: {}", e))
+}
+
+#[cfg(not(feature = "gamepad"))]
+pub fn start(_config: InputBridgeConfig, _events: EventQueue) -> Result<(), String> {
+    Err("Controller input requires building with the \"gamepad\" feature".to_string())
+}
@@ -0,0 +1,250 @@
+//! `speedforge lapdiff`: two laps of a recording side by side, aligned on distance.
+//!
+//! ```text
+//! speedforge lapdiff session.jsonl --laps 12,15 --channels speed,brake,throttle [--step 25] [--sections 10] [--csv]
+//! ```
+//!
+//! Both laps are sampled every `--step` metres along the lap, so a row compares the two at
+//! the same point of the track whatever their speed. Each row has the requested channels
+//! of both laps and the running time delta (second lap minus first; negative means the
+//! second lap is ahead). The summary splits the lap into `--sections` equal parts and
+//! lists where time was gained and lost. `--csv` writes the rows for a spreadsheet or a
+//! plotting tool instead of a table.
+//!
+//! Laps are numbered as in lap summaries: lap N is driven while `lap_completed` is N.
+
+use crate::session_compare::{self, format_lap_time};
+use crate::telemetry_fields::TelemetryData;
+use serde_json::Value;
+use std::error::Error;
+use std::path::Path;
+
+/// Metres between rows by default
+pub const DEFAULT_STEP_M: f32 = 25.0;
+/// Summary sections by default
+pub const DEFAULT_SECTIONS: usize = 10;
+/// Channels compared when `--channels` isn't given
+pub const DEFAULT_CHANNELS: &[&str] = &["speed", "throttle", "brake", "gear"];
+
+/// Short names for the usual channels; any other numeric frame field can be named as is
+const ALIASES: &[(&str, &str)] = &[
+    ("speed", "speed_kph"),
+    ("throttle", "throttle_pct"),
+    ("brake", "brake_pct"),
+    ("clutch", "clutch_pct"),
+    ("steering", "steering_angle_deg"),
+    ("gear", "gear_num"),
+    ("lat_g", "g_force_lat"),
+    ("lon_g", "g_force_lon"),
+];
+
+fn field_name(channel: &str) -> &str {
+    ALIASES.iter().find(|(alias, _)| *alias == channel).map_or(channel, |(_, field)| *field)
+}
+
+/// One frame of a lap: distance into the lap, lap time so far and the channel values
+struct Sample {
+    distance: f32,
+    time: f32,
+    values: Vec<f32>,
+}
+
+/// The frames of one lap, by increasing distance
+struct Lap {
+    number: i32,
+    lap_time: f32,
+    samples: Vec<Sample>,
+}
+
+impl Lap {
+    fn length(&self) -> f32 {
+        self.samples.last().map_or(0.0, |sample| sample.distance)
+    }
+
+    // Time and channel values at `distance`, interpolated between the samples around it
+    fn at(&self, distance: f32) -> (f32, Vec<f32>) {
+        let after = self.samples.partition_point(|sample| sample.distance < distance);
+        let (a, b) = match after {
+            0 => (&self.samples[0], &self.samples[0]),
+            i if i >= self.samples.len() => (&self.samples[i - 1], &self.samples[i - 1]),
+            i => (&self.samples[i - 1], &self.samples[i]),
+        };
+        let span = b.distance - a.distance;
+        let t = if span > 0.0 { (distance - a.distance) / span } else { 0.0 };
+        let lerp = |x: f32, y: f32| x + (y - x) * t;
+        (lerp(a.time, b.time), a.values.iter().zip(&b.values).map(|(&x, &y)| lerp(x, y)).collect())
+    }
+}
+
+fn channel_values(frame: &TelemetryData, fields: &[&str]) -> Result<Vec<f32>, String> {
+    let value = serde_json::to_value(frame).map_err(|e| e.to_string())?;
+    fields
+        .iter()
+        .map(|field| match value.get(field) {
+            Some(Value::Number(number)) => Ok(number.as_f64().unwrap_or(0.0) as f32),
+            Some(Value::Bool(flag)) => Ok(if *flag { 1.0 } else { 0.0 }),
+            _ => Err(format!("'{}' is not a numeric channel", field)),
+        })
+        .collect()
+}
+
+// Collect the requested laps; a lap counts once the next one has started
+fn read_laps(path: &Path, numbers: [i32; 2], fields: &[&str]) -> Result<[Lap; 2], Box<dyn Error>> {
+    let mut laps = numbers.map(|number| Lap { number, lap_time: 0.0, samples: Vec::new() });
+    let mut complete = [false; 2];
+    for frame in session_compare::read_frames(path)? {
+        let frame = frame?;
+        for (lap, done) in laps.iter_mut().zip(complete.iter_mut()) {
+            if frame.lap_completed == lap.number + 1 && !*done {
+                *done = true;
+                lap.lap_time = if frame.last_lap_time > 0.0 { frame.last_lap_time } else { lap.samples.last().map_or(0.0, |sample| sample.time) };
+            }
+            // Frames from just before the line still carry the last lap's distance; keep
+            // only ones that move forward
+            let forward = lap.samples.last().is_none_or(|last| frame.lap_dist > last.distance);
+            if frame.lap_completed == lap.number && !*done && forward && frame.lap_dist >= 0.0 {
+                lap.samples.push(Sample {
+                    distance: frame.lap_dist,
+                    time: frame.current_lap_time,
+                    values: channel_values(&frame, fields)?,
+                });
+            }
+        }
+        if complete.iter().all(|done| *done) {
+            break;
+        }
+    }
+    for (lap, done) in laps.iter().zip(complete) {
+        if lap.samples.is_empty() {
+            return Err(format!("lap {} is not in {}", lap.number, path.display()).into());
+        }
+        if !done {
+            return Err(format!("lap {} was not completed in {}", lap.number, path.display()).into());
+        }
+    }
+    Ok(laps)
+}
+
+/// A row of the diff: distance, the channel values of both laps and the time delta
+struct Row {
+    distance: f32,
+    first: Vec<f32>,
+    second: Vec<f32>,
+    delta: f32,
+}
+
+fn rows(laps: &[Lap; 2], step: f32) -> Vec<Row> {
+    let length = laps[0].length().min(laps[1].length());
+    let count = (length / step).floor() as usize;
+    (0..=count)
+        .map(|i| {
+            let distance = i as f32 * step;
+            let (time_a, first) = laps[0].at(distance);
+            let (time_b, second) = laps[1].at(distance);
+            Row { distance, first, second, delta: time_b - time_a }
+        })
+        .collect()
+}
+
+fn format_table(laps: &[Lap; 2], channels: &[String], rows: &[Row], sections: usize) -> String {
+    let [a, b] = laps;
+    let mut out = format!(
+        "Lap {}: {}   Lap {}: {}   ({:+.3})\n\n",
+        a.number,
+        format_lap_time(a.lap_time),
+        b.number,
+        format_lap_time(b.lap_time),
+        b.lap_time - a.lap_time
+    );
+    out.push_str(&format!("{:>7}", "m"));
+    for channel in channels {
+        out.push_str(&format!(" {:>9} {:>9}", format!("{}:{}", channel, a.number), format!("{}:{}", channel, b.number)));
+    }
+    out.push_str(&format!(" {:>8}\n", "delta"));
+    for row in rows {
+        out.push_str(&format!("{:>7.0}", row.distance));
+        for (x, y) in row.first.iter().zip(&row.second) {
+            out.push_str(&format!(" {:>9.1} {:>9.1}", x, y));
+        }
+        out.push_str(&format!(" {:>+8.3}\n", row.delta));
+    }
+
+    // Time won or lost over each section, from the change in the running delta
+    let Some(last) = rows.last() else {
+        return out;
+    };
+    let section_length = last.distance / sections as f32;
+    let mut changes: Vec<(f32, f32, f32)> = (0..sections)
+        .map(|i| {
+            let (start, end) = (i as f32 * section_length, (i + 1) as f32 * section_length);
+            let delta_at = |distance: f32| rows.iter().rev().find(|row| row.distance <= distance + 0.01).map_or(0.0, |row| row.delta);
+            (start, end, delta_at(end) - delta_at(start))
+        })
+        .collect();
+    out.push_str(&format!("\n{:>15} {:>9}\n", "Section (m)", "Change"));
+    for (start, end, change) in &changes {
+        out.push_str(&format!("{:>7.0}-{:<7.0} {:>+9.3}\n", start, end, change));
+    }
+    changes.sort_by(|x, y| x.2.total_cmp(&y.2));
+    if let Some((start, end, change)) = changes.first().filter(|section| section.2 < 0.0) {
+        out.push_str(&format!("Lap {} gained most from {:.0} to {:.0} m ({:+.3} s)\n", b.number, start, end, change));
+    }
+    if let Some((start, end, change)) = changes.last().filter(|section| section.2 > 0.0) {
+        out.push_str(&format!("Lap {} lost most from {:.0} to {:.0} m ({:+.3} s)\n", b.number, start, end, change));
+    }
+    out
+}
+
+fn format_csv(laps: &[Lap; 2], channels: &[String], rows: &[Row]) -> String {
+    let mut out = "distance_m".to_string();
+    for channel in channels {
+        out.push_str(&format!(",{}_{},{}_{}", channel, laps[0].number, channel, laps[1].number));
+    }
+    out.push_str(",delta_s\n");
+    for row in rows {
+        out.push_str(&format!("{:.1}", row.distance));
+        for (x, y) in row.first.iter().zip(&row.second) {
+            out.push_str(&format!(",{:.3},{:.3}", x, y));
+        }
+        out.push_str(&format!(",{:.3}\n", row.delta));
+    }
+    out
+}
+
+/// Entry point for `speedforge lapdiff <recording> --laps a,b [--channels ...] [--step m]
+/// [--sections n] [--csv]`; returns the process exit code
+pub fn run_cli(args: &[String]) -> i32 {
+    const USAGE: &str = "Usage: speedforge lapdiff <recording> --laps <a>,<b> [--channels speed,brake,throttle] [--step <m>] [--sections <n>] [--csv]";
+    let Some(path) = args.first().filter(|arg| !arg.starts_with("--")) else {
+        eprintln!("{}", USAGE);
+        return 2;
+    };
+    let laps: Vec<i32> = crate::arg_value(args, "--laps")
+        .map(|list| list.split(',').filter_map(|lap| lap.trim().parse().ok()).collect())
+        .unwrap_or_default();
+    let [first, second] = laps[..] else {
+        eprintln!("{}", USAGE);
+        return 2;
+    };
+    let channels: Vec<String> = crate::arg_value(args, "--channels")
+        .map(|list| list.split(',').map(str::trim).filter(|channel| !channel.is_empty()).map(str::to_string).collect())
+        .unwrap_or_else(|| DEFAULT_CHANNELS.iter().map(|channel| channel.to_string()).collect());
+    let step = crate::arg_value(args, "--step").and_then(|value| value.parse().ok()).filter(|&step: &f32| step > 0.0).unwrap_or(DEFAULT_STEP_M);
+    let sections = crate::arg_value(args, "--sections").and_then(|value| value.parse().ok()).filter(|&sections| sections > 0).unwrap_or(DEFAULT_SECTIONS);
+    let fields: Vec<&str> = channels.iter().map(|channel| field_name(channel)).collect();
+
+    let laps = match read_laps(Path::new(path), [first, second], &fields) {
+        Ok(laps) => laps,
+        Err(e) => {
+            eprintln!("Failed to read laps: {}", e);
+            return 1;
+        }
+    };
+    let rows = rows(&laps, step);
+    if args.iter().any(|arg| arg == "--csv") {
+        print!("{}", format_csv(&laps, &channels, &rows));
+    } else {
+        print!("{}", format_table(&laps, &channels, &rows, sections));
+    }
+    0
+}
@@ -0,0 +1,233 @@
+use crate::consistency;
+use crate::corners::{CornerDatabase, CORNERS_PATH};
+use crate::derived::DerivedChannel;
+use crate::laps::{LapChange, LapTracker};
+use crate::straights::{CornerSpeed, SegmentSpeeds, StraightSpeed};
+use crate::telemetry_fields::TelemetryData;
+use serde::{Serialize, Deserialize};
+
+/// Number of equal-length sectors a lap is split into
+pub const SECTOR_COUNT: usize = 3;
+
+/// Summary of one completed lap
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct LapSummary {
+    pub lap: i32,
+    pub lap_time: f32,
+    /// Sector times; empty when the lap wasn't observed from the start line
+    pub sector_times: Vec<f32>,
+    pub top_speed_kph: f32,
+    pub fuel_used: f32,
+    pub avg_track_temp_c: f32,
+    pub avg_air_temp_c: f32,
+    pub max_oil_temp_c: f32,
+    pub max_water_temp_c: f32,
+    #[serde(default)]
+    pub avg_wind_vel_ms: f32,
+    /// Direction of the average wind vector
+    #[serde(default)]
+    pub avg_wind_dir_rad: f32,
+    #[serde(default)]
+    pub avg_humidity_pct: f32,
+    /// Wettest track state seen during the lap (irsdk_TrackWetness)
+    #[serde(default)]
+    pub max_track_wetness: i32,
+    /// Sky condition at the end of the lap
+    #[serde(default)]
+    pub skies: String,
+    /// Pedal smoothness indices, 100 = perfectly smooth (see consistency::smoothness_index)
+    pub throttle_smoothness: f32,
+    pub brake_smoothness: f32,
+    /// Top speed on each straight of the corner database, drafting or in clean air
+    #[serde(default)]
+    pub straights: Vec<StraightSpeed>,
+    #[serde(default)]
+    pub corner_speeds: Vec<CornerSpeed>,
+    /// The car was on pit road at some point during the lap
+    pub pitted: bool,
+    /// The whole lap was observed (not joined part way through)
+    pub complete: bool,
+}
+
+// Running totals for the lap in progress
+struct LapAccumulator {
+    start_time: f32,
+    start_fuel: f32,
+    sector_start: f32,
+    sector_times: Vec<f32>,
+    complete: bool,
+    top_speed_kph: f32,
+    track_temp_sum: f32,
+    air_temp_sum: f32,
+    humidity_sum: f32,
+    // Wind summed as a vector so directions either side of north average correctly
+    wind_x_sum: f32,
+    wind_y_sum: f32,
+    max_track_wetness: i32,
+    samples: u32,
+    max_oil_temp_c: f32,
+    max_water_temp_c: f32,
+    pitted: bool,
+    last_inputs: Option<(f32, f32)>, // (throttle, brake)
+    throttle_travel: f32,
+    brake_travel: f32,
+    input_time: f32,
+    segments: SegmentSpeeds,
+}
+
+impl LapAccumulator {
+    fn start(data: &TelemetryData, corners: &CornerDatabase) -> Self {
+        let track_id = data.session_metadata.as_ref().and_then(|m| m.track.as_ref()).map(|t| t.track_id);
+        Self {
+            start_time: data.race_clock.time as f32,
+            start_fuel: data.fuel_level,
+            sector_start: data.race_clock.time as f32,
+            sector_times: Vec::with_capacity(SECTOR_COUNT),
+            // Only laps starting near the line have meaningful sector times
+            complete: data.lap_dist_pct < 0.05,
+            top_speed_kph: 0.0,
+            track_temp_sum: 0.0,
+            air_temp_sum: 0.0,
+            humidity_sum: 0.0,
+            wind_x_sum: 0.0,
+            wind_y_sum: 0.0,
+            max_track_wetness: 0,
+            samples: 0,
+            max_oil_temp_c: 0.0,
+            max_water_temp_c: 0.0,
+            pitted: false,
+            last_inputs: None,
+            throttle_travel: 0.0,
+            brake_travel: 0.0,
+            input_time: 0.0,
+            segments: SegmentSpeeds::new(track_id.map(|id| corners.corners(id)).unwrap_or_default()),
+        }
+    }
+
+    fn add(&mut self, data: &TelemetryData) {
+        self.top_speed_kph = self.top_speed_kph.max(data.speed_kph);
+        self.track_temp_sum += data.track_temp_c;
+        self.air_temp_sum += data.air_temp_c;
+        self.humidity_sum += data.humidity_pct;
+        self.wind_x_sum += data.wind_vel_ms * data.wind_dir_rad.sin();
+        self.wind_y_sum += data.wind_vel_ms * data.wind_dir_rad.cos();
+        self.max_track_wetness = self.max_track_wetness.max(data.track_wetness);
+        self.samples += 1;
+        self.max_oil_temp_c = self.max_oil_temp_c.max(data.oil_temp_c);
+        self.max_water_temp_c = self.max_water_temp_c.max(data.water_temp_c);
+        self.pitted |= data.on_pit_road;
+
+        // Total pedal travel over time, used for the smoothness indices
+        if let Some((throttle, brake)) = self.last_inputs && data.race_clock.dt > 0.0 {
+            self.throttle_travel += (data.throttle_pct - throttle).abs();
+            self.brake_travel += (data.brake_pct - brake).abs();
+            self.input_time += data.race_clock.dt;
+        }
+        self.last_inputs = Some((data.throttle_pct, data.brake_pct));
+        self.segments.add(data);
+
+        // Close intermediate sectors as their boundaries are crossed
+        let next_boundary = (self.sector_times.len() + 1) as f32 / SECTOR_COUNT as f32;
+        if self.sector_times.len() < SECTOR_COUNT - 1 && data.lap_dist_pct >= next_boundary && data.lap_dist_pct < 0.99 {
+            self.sector_times.push(data.race_clock.time as f32 - self.sector_start);
+            self.sector_start = data.race_clock.time as f32;
+        }
+    }
+
+    fn finish(mut self, lap: i32, reported_lap_time: f32, data: &TelemetryData) -> LapSummary {
+        let measured = data.race_clock.time as f32 - self.start_time;
+        let lap_time = if reported_lap_time > 0.0 { reported_lap_time } else { measured };
+
+        if self.complete && self.sector_times.len() == SECTOR_COUNT - 1 {
+            self.sector_times.push(data.race_clock.time as f32 - self.sector_start);
+        } else {
+            self.sector_times.clear();
+        }
+
+        let samples = self.samples.max(1) as f32;
+        let (straights, corner_speeds) = self.segments.finish();
+        LapSummary {
+            lap,
+            lap_time,
+            sector_times: self.sector_times,
+            top_speed_kph: self.top_speed_kph,
+            fuel_used: (self.start_fuel - data.fuel_level).max(0.0),
+            avg_track_temp_c: self.track_temp_sum / samples,
+            avg_air_temp_c: self.air_temp_sum / samples,
+            max_oil_temp_c: self.max_oil_temp_c,
+            max_water_temp_c: self.max_water_temp_c,
+            avg_wind_vel_ms: (self.wind_x_sum.powi(2) + self.wind_y_sum.powi(2)).sqrt() / samples,
+            avg_wind_dir_rad: self.wind_x_sum.atan2(self.wind_y_sum).rem_euclid(std::f32::consts::TAU),
+            avg_humidity_pct: self.humidity_sum / samples,
+            max_track_wetness: self.max_track_wetness,
+            skies: data.skies.clone(),
+            throttle_smoothness: consistency::smoothness_index(self.throttle_travel, self.input_time),
+            brake_smoothness: consistency::smoothness_index(self.brake_travel, self.input_time),
+            straights,
+            corner_speeds,
+            pitted: self.pitted,
+            complete: self.complete,
+        }
+    }
+}
+
+/// Builds lap summaries from a stream of frames (live or recorded)
+pub struct LapSummaryBuilder {
+    laps: LapTracker,
+    current: Option<LapAccumulator>,
+    corners: CornerDatabase,
+}
+
+impl LapSummaryBuilder {
+    pub fn new() -> Self {
+        Self {
+            laps: LapTracker::new(),
+            current: None,
+            corners: CornerDatabase::load(CORNERS_PATH),
+        }
+    }
+
+    /// Feed a frame; returns the summary of a lap completed in this frame
+    pub fn update(&mut self, data: &TelemetryData) -> Option<LapSummary> {
+        let mut finished = None;
+        match self.laps.update(data) {
+            LapChange::Completed { lap, lap_time } => {
+                if let Some(current) = self.current.take() {
+                    finished = Some(current.finish(lap, lap_time, data));
+                }
+            },
+            LapChange::Reset => self.current = None,
+            LapChange::None => {}
+        }
+
+        self.current
+            .get_or_insert_with(|| LapAccumulator::start(data, &self.corners))
+            .add(data);
+        finished
+    }
+}
+
+/// Publishes the summary of each lap on the frame it completes
+pub struct LapSummaries {
+    builder: LapSummaryBuilder,
+}
+
+impl LapSummaries {
+    pub fn new() -> Self {
+        Self {
+            builder: LapSummaryBuilder::new(),
+        }
+    }
+}
+
+impl DerivedChannel for LapSummaries {
+    fn name(&self) -> &'static str {
+        "lap_summary"
+    }
+
+    fn compute(&mut self, data: &mut TelemetryData) {
+        if let Some(summary) = self.builder.update(data) {
+            data.lap_summary = Some(summary);
+        }
+    }
+}
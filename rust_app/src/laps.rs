@@ -0,0 +1,71 @@
+use crate::telemetry_fields::TelemetryData;
+
+/// What happened to the player's lap counter between two frames
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LapChange {
+    /// Still on the same lap
+    None,
+    /// The previous lap was completed
+    Completed { lap: i32, lap_time: f32 },
+    /// The counter jumped (new session, reset, tow) and lap based state should be dropped
+    Reset,
+}
+
+/// Detects lap boundaries from the player's lap counter
+#[derive(Default)]
+pub struct LapTracker {
+    last_lap: Option<i32>,
+}
+
+impl LapTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Lap number seen in the last frame
+    pub fn current_lap(&self) -> Option<i32> {
+        self.last_lap
+    }
+
+    /// Feed a frame and report any lap boundary
+    pub fn update(&mut self, data: &TelemetryData) -> LapChange {
+        let lap = data.lap_completed;
+        let change = match self.last_lap {
+            Some(_) if data.race_clock.session_reset => LapChange::Reset,
+            Some(last) if lap == last => LapChange::None,
+            Some(last) if lap == last + 1 => LapChange::Completed {
+                lap: last,
+                lap_time: data.last_lap_time,
+            },
+            Some(_) => LapChange::Reset,
+            None => LapChange::None,
+        };
+        self.last_lap = Some(lap);
+        change
+    }
+}
+
+/// Track length estimated from the current lap distance and percentage
+pub fn estimate_track_length(data: &TelemetryData) -> f32 {
+    if data.lap_dist_pct > 0.05 {
+        data.lap_dist / data.lap_dist_pct
+    } else {
+        0.0
+    }
+}
+
+/// Shortest distance between two lap positions, accounting for the start/finish wrap
+pub fn lap_pct_distance(a: f32, b: f32) -> f32 {
+    signed_lap_pct_delta(a, b).abs()
+}
+
+/// Signed difference b - a between two lap positions in the range -0.5..0.5
+pub fn signed_lap_pct_delta(a: f32, b: f32) -> f32 {
+    let mut delta = b - a;
+    if delta > 0.5 {
+        delta -= 1.0;
+    } else if delta < -0.5 {
+        delta += 1.0;
+    }
+    delta
+}
@@ -0,0 +1,217 @@
+//! Graceful degradation when a tick takes longer than the sample interval.
+//!
+//! Every derived channel belongs to a priority tier. The telemetry thread times each tick
+//! and, while the smoothed processing time stays over the budget, skips the lower tiers
+//! in a fixed order:
+//!
+//! 1. analytics (braking and traction reports, gear charts, strategy estimates...) stop
+//!    updating
+//! 2. the other-car channels (car motion, running order, hazards) also only update every
+//!    `CARS_DIVISOR` ticks
+//!
+//! Essential channels (the player's inputs and motion, laps, fuel, events) always run,
+//! and so does anything an essential channel depends on. A skipped channel repeats its
+//! last value, and the frame lists the shed tiers in `shed`. Channels not named in the
+//! priority configuration are essential.
+
+use crate::telemetry_fields::TelemetryData;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Under heavy load the other-car channels run on one tick in this many
+pub const CARS_DIVISOR: u64 = 3;
+/// Shed one more tier once the smoothed tick time passes this share of the budget
+pub const OVERLOAD: f32 = 0.9;
+/// Restore one tier once it falls below this share
+pub const RECOVERED: f32 = 0.5;
+/// Least time between two level changes, so a level gets the chance to take effect
+pub const LEVEL_HOLD: Duration = Duration::from_secs(5);
+
+// Weight of the latest tick in the smoothed tick time
+const SMOOTHING: f32 = 0.1;
+
+const DEFAULT_ANALYTICS: &[&str] = &[
+    "braking", "traction", "engine_stress", "gear_chart", "engine_map_stats", "hybrid_stats",
+    "crossover_estimate", "tire_strategy", "sun", "wind_tunnel", "damper_histogram",
+    "strength_of_field", "driver_stats", "data_api", "finish_probabilities",
+];
+const DEFAULT_CARS: &[&str] = &[
+    "car_motion", "car_order", "sector_hazards", "local_yellow", "map_state", "effective_standings",
+];
+
+/// Priority tier of a derived channel, lowest first
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Tier {
+    Analytics,
+    Cars,
+    Essential,
+}
+
+impl Tier {
+    fn name(self) -> &'static str {
+        match self {
+            Tier::Analytics => "analytics",
+            Tier::Cars => "cars",
+            Tier::Essential => "essential",
+        }
+    }
+}
+
+/// Which tier each derived channel is in. Loaded from a TOML file such as:
+///
+/// ```toml
+/// analytics = ["braking", "traction", "gear_chart"]
+/// cars = ["car_motion", "car_order"]
+/// essential = ["radar"]
+/// ```
+///
+/// Entries override the built-in tiers for the channels they name.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct PriorityConfig {
+    pub analytics: Vec<String>,
+    pub cars: Vec<String>,
+    pub essential: Vec<String>,
+}
+
+impl PriorityConfig {
+    /// Load the tiers from a TOML file
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let content = fs::read_to_string(path)?;
+        let config: PriorityConfig = toml::from_str(&content)?;
+        Ok(config)
+    }
+
+    fn tiers(&self) -> HashMap<String, Tier> {
+        let defaults = DEFAULT_ANALYTICS
+            .iter()
+            .map(|name| (name.to_string(), Tier::Analytics))
+            .chain(DEFAULT_CARS.iter().map(|name| (name.to_string(), Tier::Cars)));
+        let configured = [(&self.analytics, Tier::Analytics), (&self.cars, Tier::Cars), (&self.essential, Tier::Essential)]
+            .into_iter()
+            .flat_map(|(names, tier)| names.iter().map(move |name| (name.clone(), tier)));
+        defaults.chain(configured).collect()
+    }
+}
+
+/// Times the ticks and decides which channels to skip on each
+pub struct LoadShedder {
+    tiers: HashMap<String, Tier>,
+    budget: Duration,
+    enabled: bool,
+    // Smoothed tick time in seconds
+    average: f32,
+    // 0: nothing shed, 1: analytics, 2: analytics and most cars ticks
+    level: u8,
+    changed_at: Instant,
+    tick: u64,
+}
+
+impl LoadShedder {
+    /// `budget` is the time one tick may take, the sample interval
+    pub fn new(priorities: &PriorityConfig, budget: Duration, enabled: bool) -> Self {
+        Self {
+            tiers: priorities.tiers(),
+            budget,
+            enabled,
+            average: 0.0,
+            level: 0,
+            changed_at: Instant::now(),
+            tick: 0,
+        }
+    }
+
+    /// Tier of a derived channel
+    pub fn tier(&self, channel: &str) -> Tier {
+        self.tiers.get(channel).copied().unwrap_or(Tier::Essential)
+    }
+
+    /// Whether `channel` is skipped on the current tick
+    pub fn skips(&self, channel: &str) -> bool {
+        match (self.level, self.tier(channel)) {
+            (0, _) | (_, Tier::Essential) => false,
+            (_, Tier::Analytics) => true,
+            (1, Tier::Cars) => false,
+            (_, Tier::Cars) => !self.tick.is_multiple_of(CARS_DIVISOR),
+        }
+    }
+
+    /// Names of the tiers shed at the current level, for the frame's `shed` field
+    pub fn shed_tiers(&self) -> Vec<String> {
+        let shed: &[Tier] = match self.level {
+            0 => &[],
+            1 => &[Tier::Analytics],
+            _ => &[Tier::Analytics, Tier::Cars],
+        };
+        shed.iter().map(|tier| tier.name().to_string()).collect()
+    }
+
+    /// Smoothed tick time
+    pub fn average(&self) -> Duration {
+        Duration::from_secs_f32(self.average)
+    }
+
+    pub fn budget(&self) -> Duration {
+        self.budget
+    }
+
+    /// Note how long the tick took, adjusting the level for the next one
+    pub fn record(&mut self, elapsed: Duration) {
+        self.tick += 1;
+        self.average += (elapsed.as_secs_f32() - self.average) * SMOOTHING;
+        if !self.enabled || self.changed_at.elapsed() < LEVEL_HOLD {
+            return;
+        }
+        let load = self.average / self.budget.as_secs_f32();
+        let level = if load > OVERLOAD && self.level < 2 {
+            self.level + 1
+        } else if load < RECOVERED && self.level > 0 {
+            self.level - 1
+        } else {
+            return;
+        };
+        tracing::info!(
+            "Tick time {:.1} ms of {:.1} ms budget, {} load shedding (level {})",
+            self.average * 1000.0,
+            self.budget.as_secs_f32() * 1000.0,
+            if level > self.level { "raising" } else { "lowering" },
+            level
+        );
+        self.level = level;
+        self.changed_at = Instant::now();
+    }
+}
+
+/// Repeat the previous value of the fields a skipped `channel` fills. Channels without
+/// frame fields of their own, or unknown to this list, are left alone
+pub fn hold(channel: &str, data: &mut TelemetryData, previous: &TelemetryData) {
+    match channel {
+        "braking" => data.braking_report = previous.braking_report.clone(),
+        "traction" => data.traction_report = previous.traction_report.clone(),
+        "engine_stress" => data.engine_stress = previous.engine_stress.clone(),
+        "gear_chart" => data.gear_chart = previous.gear_chart.clone(),
+        "engine_map_stats" => data.engine_map_stats = previous.engine_map_stats.clone(),
+        "hybrid_stats" => data.hybrid_stats = previous.hybrid_stats.clone(),
+        "crossover_estimate" => data.crossover_estimate = previous.crossover_estimate.clone(),
+        "tire_strategy" => data.tire_strategy = previous.tire_strategy.clone(),
+        "sun" => {
+            data.sun = previous.sun.clone();
+            data.time_of_day = previous.time_of_day.clone();
+        },
+        "strength_of_field" => data.strength_of_field = previous.strength_of_field.clone(),
+        "driver_stats" => data.driver_stats = previous.driver_stats.clone(),
+        "data_api" => data.data_api = previous.data_api.clone(),
+        "finish_probabilities" => data.finish_probabilities = previous.finish_probabilities.clone(),
+        "car_motion" => data.car_motion = previous.car_motion.clone(),
+        "car_order" => data.car_order = previous.car_order.clone(),
+        "sector_hazards" => data.sector_hazards = previous.sector_hazards.clone(),
+        "local_yellow" => data.local_yellow = previous.local_yellow.clone(),
+        "effective_standings" => data.effective_standings = previous.effective_standings.clone(),
+        // map_state is a message of its own, sent when due; nothing to repeat
+        _ => {},
+    }
+}
@@ -0,0 +1,140 @@
+use crate::derived::DerivedChannel;
+use crate::sector_hazards::{TRACK_LOC_OFF_TRACK, TRACK_LOC_ON_TRACK};
+use crate::telemetry_fields::{TelemetryData, FLAG_CAUTION, FLAG_CAUTION_WAVING, FLAG_YELLOW};
+use serde::{Serialize, Deserialize};
+
+/// irsdk_yellowWaving session flag
+pub const FLAG_YELLOW_WAVING: u32 = 0x00000100;
+
+// A car below this fraction of the field's median speed along the lap is slow
+const SLOW_FRACTION: f32 = 0.3;
+// An anomaly must last this long before it counts, to ignore single noisy frames
+const MIN_ANOMALY_S: f32 = 1.0;
+// Incident cars closer together than this (fraction of a lap) are one incident
+const CLUSTER_GAP_PCT: f32 = 0.05;
+// Margin added around the incident cars
+const RANGE_MARGIN_PCT: f32 = 0.01;
+
+/// Estimated location of a local yellow
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct LocalYellow {
+    /// Start of the affected range; may be greater than end_pct when it spans the line
+    pub start_pct: f32,
+    pub end_pct: f32,
+    /// Cars stopped, slow or off track in the range
+    pub cars: Vec<i32>,
+    /// "high" when a yellow flag is shown, "medium" when only inferred from cars
+    pub confidence: String,
+}
+
+/// Derived channel that infers where on track a yellow is from car behaviour
+pub struct LocalYellowLocator {
+    /// SessionTime each car's anomaly started, per CarIdx
+    anomaly_since: Vec<Option<f32>>,
+}
+
+impl LocalYellowLocator {
+    pub fn new() -> Self {
+        Self { anomaly_since: Vec::new() }
+    }
+}
+
+// Group positions into clusters, handling the wrap at the start/finish line.
+// Positions must be sorted
+fn clusters(positions: &[(f32, i32)]) -> Vec<Vec<(f32, i32)>> {
+    let mut groups: Vec<Vec<(f32, i32)>> = Vec::new();
+    for &entry in positions {
+        match groups.last_mut() {
+            Some(group) if entry.0 - group.last().map_or(entry.0, |e| e.0) <= CLUSTER_GAP_PCT => group.push(entry),
+            _ => groups.push(vec![entry]),
+        }
+    }
+    // Merge the last group into the first when they meet across the line
+    if groups.len() > 1 {
+        let first_start = groups[0][0].0;
+        let last_end = groups.last().and_then(|g| g.last()).map_or(0.0, |e| e.0);
+        if first_start + 1.0 - last_end <= CLUSTER_GAP_PCT {
+            let last = groups.pop().unwrap_or_default();
+            let mut merged = last;
+            merged.extend(groups[0].drain(..));
+            groups[0] = merged;
+        }
+    }
+    groups
+}
+
+impl DerivedChannel for LocalYellowLocator {
+    fn name(&self) -> &'static str {
+        "local_yellow"
+    }
+
+    fn dependencies(&self) -> &'static [&'static str] {
+        &["car_motion"]
+    }
+
+    fn compute(&mut self, data: &mut TelemetryData) {
+        let (Some(surfaces), Some(positions), Some(motion)) = (
+            data.CarIdxTrackSurface.as_ref(),
+            data.CarIdxLapDistPct.as_ref(),
+            data.car_motion.as_ref(),
+        ) else {
+            return;
+        };
+        let rates = &motion.lap_dist_pct_per_sec;
+
+        // Field reference speed from cars running normally on track
+        let mut running: Vec<f32> = surfaces
+            .iter()
+            .zip(rates.iter())
+            .filter(|(surface, rate)| **surface == TRACK_LOC_ON_TRACK && **rate > 0.0)
+            .map(|(_, rate)| *rate)
+            .collect();
+        running.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        let median_rate = running.get(running.len() / 2).copied().unwrap_or(0.0);
+
+        if self.anomaly_since.len() < positions.len() {
+            self.anomaly_since.resize(positions.len(), None);
+        }
+
+        let mut incident_cars: Vec<(f32, i32)> = Vec::new();
+        for (idx, &pct) in positions.iter().enumerate() {
+            let surface = surfaces.get(idx).copied().unwrap_or(-1);
+            let rate = rates.get(idx).copied().unwrap_or(0.0);
+            // Pit lane and cars not in the world are never incidents
+            let off_track = surface == TRACK_LOC_OFF_TRACK;
+            let slow = surface == TRACK_LOC_ON_TRACK && median_rate > 0.0 && rate < median_rate * SLOW_FRACTION;
+            if pct < 0.0 || !(off_track || slow) {
+                self.anomaly_since[idx] = None;
+                continue;
+            }
+            let since = *self.anomaly_since[idx].get_or_insert(data.SessionTime);
+            if data.SessionTime - since >= MIN_ANOMALY_S {
+                incident_cars.push((pct, idx as i32));
+            }
+        }
+        if incident_cars.is_empty() {
+            return;
+        }
+        incident_cars.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+
+        // Report the incident the player reaches first
+        let player_pct = data.lap_dist_pct;
+        let Some(incident) = clusters(&incident_cars).into_iter().min_by(|a, b| {
+            let ahead = |group: &Vec<(f32, i32)>| (group[0].0 - player_pct).rem_euclid(1.0);
+            ahead(a).partial_cmp(&ahead(b)).unwrap_or(std::cmp::Ordering::Equal)
+        }) else {
+            return;
+        };
+
+        let flags = FLAG_YELLOW | FLAG_YELLOW_WAVING | FLAG_CAUTION | FLAG_CAUTION_WAVING;
+        let flagged = data.session_flags & flags != 0;
+        let first = incident.first().map_or(0.0, |e| e.0);
+        let last = incident.last().map_or(0.0, |e| e.0);
+        data.local_yellow = Some(LocalYellow {
+            start_pct: (first - RANGE_MARGIN_PCT).rem_euclid(1.0),
+            end_pct: (last + RANGE_MARGIN_PCT).rem_euclid(1.0),
+            cars: incident.iter().map(|&(_, idx)| idx).collect(),
+            confidence: if flagged { "high" } else { "medium" }.to_string(),
+        });
+    }
+}
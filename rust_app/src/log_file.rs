@@ -0,0 +1,267 @@
+//! The server's log: `tracing` events to the console and to rotating files, for
+//! diagnosing problems hours into a session.
+//!
+//! All runtime logging goes through `tracing` (the `log_*` macros in main.rs are thin
+//! wrappers); `init` installs the subscriber. Debug events only show in verbose mode,
+//! which can be switched at runtime. Besides the console, every event goes to
+//! `speedforge_<start>.log` in the log directory (by default `logs` under the sessions
+//! archive) once `start` is called. A new file is started each day or once the current
+//! one reaches the size limit, and only the newest `keep` files are kept. Writes go
+//! through the I/O thread like other files written while running. When a write fails,
+//! file logging pauses and a new file is tried after a backoff. The last `RECENT_LINES`
+//! lines are also kept in memory, file logging or not, for the admin panel's log view.
+
+use crate::file_io;
+use crate::sink_health;
+use chrono::{Local, NaiveDate};
+use std::fs::{self, File};
+use std::collections::VecDeque;
+use std::fmt::{self, Write as _};
+use std::io::{LineWriter, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+use tracing::field::{Field, Visit};
+use tracing::subscriber::Interest;
+use tracing::{Event, Level, Metadata, Subscriber};
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+
+/// Size at which a new file is started in size mode
+pub const DEFAULT_MAX_MB: u64 = 20;
+/// Log files kept in the directory
+pub const DEFAULT_KEEP: usize = 14;
+/// Lines kept in memory for `recent`
+pub const RECENT_LINES: usize = 1000;
+/// Name the log file reports its health under (see sink_health.rs)
+pub const SINK: &str = "log_file";
+
+const FILE_PREFIX: &str = "speedforge_";
+
+/// When to start a new log file
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rotation {
+    Daily,
+    Size,
+}
+
+impl Rotation {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "daily" | "day" => Some(Self::Daily),
+            "size" => Some(Self::Size),
+            _ => None,
+        }
+    }
+}
+
+/// Where and how the log is written
+#[derive(Clone, Debug)]
+pub struct LogFileOptions {
+    pub dir: PathBuf,
+    pub rotation: Rotation,
+    pub max_bytes: u64,
+    pub keep: usize,
+}
+
+struct LogFile {
+    options: LogFileOptions,
+    writer: Option<LineWriter<File>>,
+    path: Option<PathBuf>,
+    opened_on: NaiveDate,
+    written: u64,
+    // Failures in a row, and when to try opening a file again after the last one
+    failures: u32,
+    retry_at: Option<Instant>,
+}
+
+static LOG_FILE: OnceLock<Mutex<LogFile>> = OnceLock::new();
+static RECENT: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+static VERBOSE: AtomicBool = AtomicBool::new(false);
+
+impl LogFile {
+    fn needs_rotation(&self) -> bool {
+        match self.options.rotation {
+            Rotation::Daily => Local::now().date_naive() != self.opened_on,
+            Rotation::Size => self.written >= self.options.max_bytes,
+        }
+    }
+
+    fn open(&mut self) -> Result<(), String> {
+        fs::create_dir_all(&self.options.dir).map_err(|e| format!("Failed to create {}: {}", self.options.dir.display(), e))?;
+        let name = format!("{}{}.log", FILE_PREFIX, Local::now().format("%Y%m%d_%H%M%S"));
+        let path = self.options.dir.join(name);
+        let file = File::options().create(true).append(true).open(&path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+        self.writer = Some(LineWriter::new(file));
+        self.path = Some(path);
+        self.opened_on = Local::now().date_naive();
+        self.written = 0;
+        self.prune();
+        Ok(())
+    }
+
+    // Delete the oldest files beyond the number to keep; names sort by start time
+    fn prune(&self) {
+        let Ok(entries) = fs::read_dir(&self.options.dir) else {
+            return;
+        };
+        let mut files: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                let name = path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+                name.starts_with(FILE_PREFIX) && name.ends_with(".log")
+            })
+            .collect();
+        files.sort();
+        let excess = files.len().saturating_sub(self.options.keep.max(1));
+        for path in &files[..excess] {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    // Give up on the current file and try a new one after the backoff
+    fn fail(&mut self, error: String) {
+        self.failures += 1;
+        let backoff = sink_health::backoff(self.failures);
+        // Logging the failure would recurse, so it only goes to the console and the health report
+        eprintln!("{}; file logging paused for {}s", error, backoff.as_secs());
+        sink_health::failure(SINK, &error);
+        self.writer = None;
+        self.path = None;
+        self.retry_at = Some(Instant::now() + backoff);
+    }
+
+    fn write_line(&mut self, line: &str) {
+        if self.retry_at.is_some_and(|retry_at| Instant::now() < retry_at) {
+            return;
+        }
+        if self.writer.is_none() || self.needs_rotation() {
+            if let Err(e) = self.open() {
+                self.fail(e);
+                return;
+            }
+            if self.retry_at.take().is_some() {
+                sink_health::restarted(SINK);
+            }
+        }
+        let Some(writer) = self.writer.as_mut() else {
+            return;
+        };
+        match writeln!(writer, "{}", line) {
+            Ok(()) => {
+                self.written += line.len() as u64 + 1;
+                self.failures = 0;
+                sink_health::success(SINK);
+            },
+            Err(e) => {
+                let error = format!("Failed to write {}: {}", self.path.as_ref().map(|path| path.display().to_string()).unwrap_or_default(), e);
+                self.fail(error);
+            },
+        }
+    }
+}
+
+/// Start copying the log to files; the first file is opened right away so a bad directory shows up at startup
+pub fn start(options: LogFileOptions) -> Result<(), String> {
+    let mut log_file = LogFile {
+        options,
+        writer: None,
+        path: None,
+        opened_on: Local::now().date_naive(),
+        written: 0,
+        failures: 0,
+        retry_at: None,
+    };
+    log_file.open()?;
+    LOG_FILE.set(Mutex::new(log_file)).map_err(|_| "File logging is already running".to_string())
+}
+
+// Append a line to the recent lines and to the log file, if file logging is on
+fn write(level: &Level, message: &str) {
+    let line = format!("{} {} {}", Local::now().format("%Y-%m-%d %H:%M:%S%.3f"), level, message);
+    if let Ok(mut recent) = RECENT.lock() {
+        if recent.len() >= RECENT_LINES {
+            recent.pop_front();
+        }
+        recent.push_back(line.clone());
+    }
+    if LOG_FILE.get().is_none() {
+        return;
+    }
+    file_io::submit(move || {
+        if let Some(Ok(mut log_file)) = LOG_FILE.get().map(Mutex::lock) {
+            log_file.write_line(&line);
+        }
+    });
+}
+
+/// The last `count` lines logged, oldest first
+pub fn recent(count: usize) -> Vec<String> {
+    let Ok(recent) = RECENT.lock() else {
+        return Vec::new();
+    };
+    recent.iter().skip(recent.len().saturating_sub(count)).cloned().collect()
+}
+
+/// File currently written to; None when file logging is off or failed
+pub fn current_file() -> Option<PathBuf> {
+    LOG_FILE.get()?.lock().ok()?.path.clone()
+}
+
+pub fn is_verbose() -> bool {
+    VERBOSE.load(Ordering::Relaxed)
+}
+
+/// Show debug events or not, from now on
+pub fn set_verbose(verbose: bool) {
+    VERBOSE.store(verbose, Ordering::Relaxed);
+}
+
+// Lets debug events through in verbose mode only. Callsites are asked on every event
+// rather than once, as verbose mode can be switched while running
+struct VerbosityGate;
+
+impl<S: Subscriber> Layer<S> for VerbosityGate {
+    fn register_callsite(&self, _metadata: &'static Metadata<'static>) -> Interest {
+        Interest::sometimes()
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>, _ctx: Context<'_, S>) -> bool {
+        *metadata.level() <= Level::INFO || is_verbose()
+    }
+}
+
+// The message of an event followed by its other fields
+#[derive(Default)]
+struct MessageVisitor(String);
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.0, "{:?}", value);
+        } else {
+            let _ = write!(self.0, " {}={:?}", field.name(), value);
+        }
+    }
+}
+
+// Copies events to the recent lines and the log file
+struct FileLayer;
+
+impl<S: Subscriber> Layer<S> for FileLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut message = MessageVisitor::default();
+        event.record(&mut message);
+        write(event.metadata().level(), &format!("{}: {}", event.metadata().target(), message.0));
+    }
+}
+
+/// Install the log subscriber: the console, and the files once `start` is called
+pub fn init(verbose: bool) {
+    set_verbose(verbose);
+    let subscriber = tracing_subscriber::registry()
+        .with(VerbosityGate)
+        .with(tracing_subscriber::fmt::layer())
+        .with(FileLayer);
+    let _ = tracing::subscriber::set_global_default(subscriber);
+}
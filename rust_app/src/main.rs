@@ -1,17 +1,120 @@
 mod telemetry_fields;
 mod websocket_server;
 mod gap_calculator;
+mod thread_tuning;
+mod precision;
+mod change_detection;
+mod derived;
+mod events;
+mod wheel_slip;
+mod laps;
+mod braking_analyzer;
+mod traction_analyzer;
+mod session_yaml;
+mod engine_stress;
+mod lap_summary;
+mod session_compare;
+mod consistency;
+mod ghost_car;
+mod input_bridge;
+mod controls;
+mod hotkeys;
+mod http_api;
+mod profiles;
+mod time_sync;
+mod car_motion;
+mod gap_history;
+mod pit_stops;
+mod effective_standings;
+mod caution_advisor;
+mod drive_time;
+mod pit_board;
+mod sim_commands;
+mod webhooks;
+mod frame_schema;
+mod car_order;
+mod sector_hazards;
+mod local_yellow;
+mod metadata;
+mod shift_lights;
+mod gear_chart;
+mod engine_maps;
+mod hybrid;
+mod corners;
+mod overtakes;
+mod highlights;
+mod session_clock;
+mod replay_bookmarks;
+mod watchdog;
+mod race_clock;
+mod units;
+mod time_of_day;
+mod crossover;
+mod tire_strategy;
+mod race_phase;
+mod restart_order;
+mod driver_db;
+mod data_api;
+mod channel_presets;
+mod telemetry_source;
+mod acc;
+mod session_bundle;
+mod ibt;
+mod session_library;
+mod retention;
+mod encryption;
+mod scenario;
+mod protocol;
+mod connection_limits;
+mod endpoints;
+mod origins;
+mod codec;
+mod field_compat;
+mod car_info;
+mod head_to_head;
+mod wind_tunnel;
+mod damper_histogram;
+mod straights;
+mod strength_of_field;
+mod finish_probabilities;
+mod glitches;
+mod frame_pacing;
+mod file_io;
+mod log_file;
+mod version;
+mod config;
+mod setup_wizard;
+mod preflight;
+mod diagnostics;
+mod cli;
+mod radar;
+mod session_info;
+mod delta;
+mod map_state;
+mod field_groups;
+mod auto_export;
+mod storage;
+mod admin_panel;
+mod sink_health;
+mod snapshot;
+mod load_shedding;
+mod determinism;
+mod lap_diff;
+mod pipeline_trace;
+mod recorder;
+mod replay;
 
 use iracing::telemetry::Connection;
 use std::{thread, time::Duration};
 use std::{env, io};
 use std::io::{stdout, Write};
 use websocket_server::TelemetryWebSocketServer;
-use std::time::{SystemTime, UNIX_EPOCH};
+use thread_tuning::{ThreadPriority, ThreadTuning};
+use precision::PrecisionRules;
+use change_detection::DeadBands;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 use std::sync::{Arc, Mutex};
 use serde_json::Value;
-use chrono;
-use serde_yaml;
 
 // Create a direct wrapper for lower-level iRacing SDK access 
 // This is a workaround to bypass the ResultsPositions deserialization issue
@@ -66,6 +169,17 @@ mod iracing_wrapper {
             }
         }
     }
+    
+    /// The SDK's session info update counter, which moves whenever the YAML changes
+    #[cfg(feature = "telemetry")]
+    pub fn get_session_info_update() -> Option<i32> {
+        Some(unsafe { iracing::sys::irsdk_getSessionInfoStrUpdate() })
+    }
+    
+    #[cfg(not(feature = "telemetry"))]
+    pub fn get_session_info_update() -> Option<i32> {
+        None
+    }
 }
 
 #[cfg(not(target_os = "windows"))]
@@ -77,7 +191,7 @@ mod iracing_wrapper {
     pub fn get_raw_session_info(_conn: &mut Connection) -> Result<String, Box<dyn Error>> {
         // On non-Windows platforms, this is just a stub that returns an error
         let error_msg = "iRacing SDK not available on non-Windows platforms";
-        println!("[DEBUG] {} - Stub implementation called.", error_msg);
+        tracing::debug!("{} - Stub implementation called.", error_msg);
         
         // Create dummy YAML content without saving to file
         let yaml_content = r#"---
@@ -103,51 +217,33 @@ DriverInfo:
         // Just return an error, as this is a stub implementation
         Err(error_msg.into())
     }
+    
+    pub fn get_session_info_update() -> Option<i32> {
+        None
+    }
 }
 
-// Global flag for verbose logging
-static mut VERBOSE_LOGGING: bool = false;
-
-// Safe wrapper to check verbose flag
+// Verbose logging, switchable at runtime (see log_file.rs)
 fn is_verbose() -> bool {
-    unsafe { VERBOSE_LOGGING }
-}
-
-// Get timestamp function - reused from websocket_server.rs
-fn get_timestamp() -> String {
-    let now = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_default();
-    
-    let secs = now.as_secs();
-    let millis = now.subsec_millis();
-    
-    // Convert to hours, minutes, seconds in local time
-    let hours = (secs % 86400) / 3600;
-    let minutes = (secs % 3600) / 60;
-    let seconds = secs % 60;
-    
-    format!("{:02}:{:02}:{:02}.{:03}", hours, minutes, seconds, millis)
+    log_file::is_verbose()
 }
 
-// Enhanced logging macros
+// Logging macros, through tracing to the console and the log file (see log_file.rs)
 macro_rules! log_info {
     ($($arg:tt)*) => {
-        println!("[{}] INFO: {}", get_timestamp(), format!($($arg)*));
+        tracing::info!($($arg)*)
     };
 }
 
 macro_rules! log_debug {
     ($($arg:tt)*) => {
-        if is_verbose() {
-            println!("[{}] DEBUG: {}", get_timestamp(), format!($($arg)*));
-        }
+        tracing::debug!($($arg)*)
     };
 }
 
 macro_rules! log_error {
     ($($arg:tt)*) => {
-        eprintln!("[{}] ERROR: {}", get_timestamp(), format!($($arg)*));
+        tracing::error!($($arg)*)
     };
 }
 
@@ -195,6 +291,88 @@ fn print_startup_info() {
     }
 }
 
+// Feed frames from a non-iRacing sim to `publish`, reconnecting whenever the sim goes away
+fn run_telemetry_source(source: &mut dyn telemetry_source::TelemetrySource, publish: &mut dyn FnMut(telemetry_fields::TelemetryData)) {
+    const CONNECTION_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+    let mut connected = false;
+    loop {
+        if !connected {
+            match source.connect() {
+                Ok(()) => {
+                    log_info!("Successfully connected to {}!", source.name());
+                    connected = true;
+                },
+                Err(e) => {
+                    if should_log_telemetry_update() {
+                        log_info!("Waiting for {}: {}", source.name(), e);
+                    }
+                    thread::sleep(CONNECTION_CHECK_INTERVAL);
+                    continue;
+                }
+            }
+        }
+        let sample_started = Instant::now();
+        match source.next_frame(Duration::from_millis(100)) {
+            Ok(Some(telemetry_data)) => {
+                pipeline_trace::stage("sample", sample_started);
+                publish(telemetry_data)
+            },
+            Ok(None) => {},
+            Err(e) => {
+                log_error!("Lost connection to {}: {}", source.name(), e);
+                connected = false;
+            }
+        }
+    }
+}
+
+// Apply an action raised by a hotkey or integration to the frame being processed
+fn apply_control_action(
+    action: controls::ControlAction,
+    data: &mut telemetry_fields::TelemetryData,
+    focus_car: &mut controls::FocusCar,
+    recorder: &recorder::Recorder,
+) {
+    match action {
+        controls::ControlAction::DropMarker { label, source } => {
+            events::emit(data, "marker", serde_json::json!({ "label": label, "source": source }));
+        },
+        controls::ControlAction::ToggleVerbose => {
+            let verbose = !is_verbose();
+            log_file::set_verbose(verbose);
+            log_info!("Verbose logging {}", if verbose { "enabled" } else { "disabled" });
+        },
+        controls::ControlAction::CycleFocusCar => {
+            focus_car.cycle(data.CarIdxLapDistPct.as_ref());
+            match focus_car.car_idx() {
+                Some(car_idx) => log_info!("Focus car: #{}", car_idx),
+                None => log_info!("Focus car: player"),
+            }
+        },
+        controls::ControlAction::ToggleRecording => {
+            // The recording_toggle event goes out with the next frame
+            let recording = recorder.toggle();
+            log_info!("Recording {}", if recording { "started" } else { "stopped" });
+        },
+        controls::ControlAction::Alert { message } => {
+            events::emit(data, "alert", serde_json::json!({ "message": message }));
+        },
+        controls::ControlAction::PitCommand { command } => {
+            let result = sim_commands::PitCommand::parse(&command)
+                .and_then(|requests| requests.into_iter().try_for_each(sim_commands::send_pit_command));
+            match &result {
+                Ok(()) => log_info!("Sent pit command: {}", command),
+                Err(e) => log_error!("Pit command '{}' failed: {}", command, e),
+            }
+            events::emit(data, "pit_command", serde_json::json!({
+                "command": command,
+                "sent": result.is_ok(),
+                "error": result.err(),
+            }));
+        },
+    }
+}
+
 // Helper function to determine if we should log telemetry updates
 // This helps reduce log spam by only logging every few seconds
 fn should_log_telemetry_update() -> bool {
@@ -214,26 +392,16 @@ fn should_log_telemetry_update() -> bool {
     }
 }
 
-// Get fallback session info when real data isn't available
-fn get_fallback_session_info(
-    track_temp_c: f32, 
-    air_temp_c: f32, 
-    wind_vel_ms: f32, 
-    wind_dir_rad: f32, 
-    humidity_pct: f32, 
-    fog_level_pct: f32
-) -> String {
-    format!("\
+// Placeholder session info when the real one isn't available. It never changes, so it is
+// sent to clients once rather than as a new session info message every frame
+const FALLBACK_SESSION_INFO: &str = "\
 ---
 SessionInfo:
   Sessions:
     - SessionNum: 0
       SessionType: Practice
       SessionName: Practice
-      SessionStartTime: {session_time}
-      SessionState: Racing
-      SessionTime: {elapsed_time:.1} sec
-      SessionTimeRemain: 3600.0 sec
+      SessionTime: unlimited
   WeekendInfo:
     TrackName: Unknown
     TrackID: 0
@@ -253,13 +421,6 @@ SessionInfo:
     TrackDirection: Clockwise
     TrackWeatherType: Constant
     TrackSkies: Clear
-    TrackSurfaceTemp: {track_temp:.1}
-    TrackAirTemp: {air_temp:.1}
-    TrackAirPressure: 0
-    TrackWindVel: {wind_vel:.1}
-    TrackWindDir: {wind_dir:.1}
-    TrackRelativeHumidity: {humidity:.1}
-    TrackFogLevel: {fog:.1}
   DriverInfo:
     DriverCarIdx: 0
     DriverUserID: 0
@@ -275,35 +436,292 @@ SessionInfo:
     DriverCarSLShiftRPM: 0
     DriverCarSLLastRPM: 0
     DriverCarSLBlinkRPM: 0
-note: This is simulated session info. The actual session_info was not available.",
-        session_time = chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
-        elapsed_time = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs_f32() % 3600.0,
-        track_temp = track_temp_c,
-        air_temp = air_temp_c,
-        wind_vel = wind_vel_ms,
-        wind_dir = wind_dir_rad * 180.0 / std::f32::consts::PI,
-        humidity = humidity_pct,
-        fog = fog_level_pct
-    )
+note: This is simulated session info. The actual session_info was not available.";
+
+// Port the server listens on unless --port is given
+const DEFAULT_PORT: u16 = 8080;
+
+// Default data path for this process; named instances get their own directory
+fn instance_path(instance: Option<&str>, default: &str) -> String {
+    match instance {
+        Some(name) => std::path::Path::new("instances").join(name).join(default).to_string_lossy().to_string(),
+        None => default.to_string(),
+    }
+}
+
+// Look up the value of a `--name value` or `--name=value` style argument
+fn arg_value(args: &[String], name: &str) -> Option<String> {
+    let prefix = format!("{}=", name);
+    for (i, arg) in args.iter().enumerate() {
+        if arg == name {
+            return args.get(i + 1).cloned();
+        }
+        if let Some(value) = arg.strip_prefix(&prefix) {
+            return Some(value.to_string());
+        }
+    }
+    None
+}
+
+// Log file options from the command line; on by default next to the session archive
+// --log-dir <dir>             defaults to <sessions-dir>/logs
+// --log-rotate <daily|size>   defaults to daily
+// --log-max-mb <mb>           size limit per file in size mode, defaults to 20
+// --log-keep <n>              files kept, defaults to 14
+// --no-log-file
+fn parse_log_file(args: &[String]) -> Option<log_file::LogFileOptions> {
+    if args.iter().any(|arg| arg == "--no-log-file") {
+        return None;
+    }
+    let dir = arg_value(args, "--log-dir")
+        .map(std::path::PathBuf::from)
+        .or_else(|| arg_value(args, "--sessions-dir").map(|dir| std::path::Path::new(&dir).join("logs")))?;
+    let rotation = match arg_value(args, "--log-rotate") {
+        Some(value) => log_file::Rotation::parse(&value).unwrap_or_else(|| {
+            log_error!("Unknown log rotation '{}', using daily", value);
+            log_file::Rotation::Daily
+        }),
+        None => log_file::Rotation::Daily,
+    };
+    let max_mb = arg_value(args, "--log-max-mb").and_then(|value| value.parse().ok()).unwrap_or(log_file::DEFAULT_MAX_MB);
+    Some(log_file::LogFileOptions {
+        dir,
+        rotation,
+        max_bytes: max_mb.max(1) * 1024 * 1024,
+        keep: arg_value(args, "--log-keep").and_then(|value| value.parse().ok()).unwrap_or(log_file::DEFAULT_KEEP),
+    })
+}
+
+// Build the thread tuning options from the command line
+// --telemetry-priority <below_normal|normal|above_normal|high>
+// --telemetry-cores <list>   e.g. "6,7" or "4-7"
+// --runtime-cores <list>
+// --runtime-workers <n>
+fn parse_thread_tuning(args: &[String]) -> ThreadTuning {
+    let mut tuning = ThreadTuning::default();
+    
+    if let Some(value) = arg_value(args, "--telemetry-priority") {
+        match ThreadPriority::parse(&value) {
+            Some(priority) => tuning.telemetry_priority = priority,
+            None => log_error!("Unknown telemetry priority '{}', using normal", value),
+        }
+    }
+    
+    if let Some(value) = arg_value(args, "--telemetry-cores") {
+        match thread_tuning::parse_core_list(&value) {
+            Ok(cores) => tuning.telemetry_cores = cores,
+            Err(e) => log_error!("Ignoring --telemetry-cores: {}", e),
+        }
+    }
+    
+    if let Some(value) = arg_value(args, "--runtime-cores") {
+        match thread_tuning::parse_core_list(&value) {
+            Ok(cores) => tuning.runtime_cores = cores,
+            Err(e) => log_error!("Ignoring --runtime-cores: {}", e),
+        }
+    }
+    
+    if let Some(value) = arg_value(args, "--runtime-workers") {
+        match value.parse::<usize>() {
+            Ok(workers) if workers > 0 => tuning.runtime_workers = Some(workers),
+            _ => log_error!("Ignoring --runtime-workers '{}': expected a positive number", value),
+        }
+    }
+    
+    tuning
 }
 
-#[tokio::main]
-async fn main() {
+// Build the float rounding rules; floats are sent with full precision unless asked
+// --float-precision <decimals>   round floats to this many decimals (positions and lap
+//                                distance keep more)
+// --no-rounding                  send floats with full precision (the default)
+fn parse_precision(args: &[String]) -> Result<PrecisionRules, String> {
+    if args.iter().any(|arg| arg == "--no-rounding") {
+        return Ok(PrecisionRules::default());
+    }
+    
+    match arg_value(args, "--float-precision") {
+        Some(value) => match value.parse::<u32>() {
+            Ok(decimals) if decimals <= 10 => Ok(PrecisionRules::standard(decimals)),
+            _ => Err(format!("Invalid --float-precision '{}', expected 0 to 10 decimals", value)),
+        },
+        None => Ok(PrecisionRules::default()),
+    }
+}
+
+// Time between iRacing samples; the SDK itself updates at 60 Hz
+// --tick-rate <hz>   defaults to 20
+fn parse_sample_interval(args: &[String]) -> Result<Duration, String> {
+    match arg_value(args, "--tick-rate").map(|value| value.parse::<f32>()) {
+        Some(Ok(hz)) if (frame_pacing::MIN_TICK_RATE_HZ..=frame_pacing::SDK_TICK_RATE_HZ).contains(&hz) => Ok(Duration::from_secs_f32(1.0 / hz)),
+        Some(_) => Err(format!("Invalid --tick-rate, expected {} to {} samples per second", frame_pacing::MIN_TICK_RATE_HZ, frame_pacing::SDK_TICK_RATE_HZ)),
+        None => Ok(frame_pacing::SAMPLE_INTERVAL),
+    }
+}
+
+// Load per-channel change detection dead-bands
+// --deadbands <file.toml>
+fn parse_dead_bands(args: &[String]) -> DeadBands {
+    match arg_value(args, "--deadbands") {
+        Some(path) => match DeadBands::load(std::path::Path::new(&path)) {
+            Ok(bands) => {
+                log_info!("Loaded {} channel dead-bands from {}", bands.channels.len(), path);
+                bands
+            },
+            Err(e) => {
+                log_error!("Failed to load dead-bands from {}: {}", path, e);
+                DeadBands::default()
+            }
+        },
+        None => DeadBands::default(),
+    }
+}
+
+// Load output channel presets for third-party tools on top of the built-in ones
+// --channel-presets <file.toml>
+fn parse_channel_presets(args: &[String]) -> channel_presets::ChannelPresets {
+    match arg_value(args, "--channel-presets") {
+        Some(path) => match channel_presets::ChannelPresets::load(std::path::Path::new(&path)) {
+            Ok(presets) => {
+                log_info!("Loaded channel presets from {}: {}", path, presets.names().join(", "));
+                presets
+            },
+            Err(e) => {
+                log_error!("Failed to load channel presets from {}: {}", path, e);
+                channel_presets::ChannelPresets::default()
+            }
+        },
+        None => channel_presets::ChannelPresets::default(),
+    }
+}
+
+// Addresses from --bind, each with the server port. IPv6 addresses may be bracketed ("[::1]")
+fn parse_bind_addresses(list: &str, port: u16) -> Result<Vec<std::net::SocketAddr>, String> {
+    let mut addresses = Vec::new();
+    for entry in list.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+        let ip: std::net::IpAddr = entry.trim_start_matches('[').trim_end_matches(']').parse()
+            .map_err(|_| format!("Invalid --bind address '{}'", entry))?;
+        let address = std::net::SocketAddr::new(ip, port);
+        if !addresses.contains(&address) {
+            addresses.push(address);
+        }
+    }
+    if addresses.is_empty() {
+        return Err("--bind needs at least one address".to_string());
+    }
+    Ok(addresses)
+}
+
+// Limits on inbound connections
+// --max-connections-per-ip <n>     defaults to 16
+// --handshake-timeout-ms <ms>      defaults to 5000
+// --max-message-kb <kb>            largest inbound message, defaults to 64
+// --max-messages-per-sec <n>       defaults to 50
+// --allow <ip or cidr,...>         only these addresses may connect
+fn parse_connection_limits(args: &[String]) -> Result<connection_limits::ConnectionLimits, String> {
+    let mut limits = connection_limits::ConnectionLimits::default();
+    if let Some(value) = arg_value(args, "--max-connections-per-ip") {
+        limits.max_connections_per_ip = value.parse().map_err(|_| "Invalid --max-connections-per-ip".to_string())?;
+    }
+    if let Some(value) = arg_value(args, "--handshake-timeout-ms") {
+        let ms: u64 = value.parse().map_err(|_| "Invalid --handshake-timeout-ms".to_string())?;
+        limits.handshake_timeout = Duration::from_millis(ms);
+    }
+    if let Some(value) = arg_value(args, "--max-message-kb") {
+        let kb: usize = value.parse().map_err(|_| "Invalid --max-message-kb".to_string())?;
+        limits.max_message_bytes = kb * 1024;
+    }
+    if let Some(value) = arg_value(args, "--max-messages-per-sec") {
+        limits.max_messages_per_sec = value.parse().map_err(|_| "Invalid --max-messages-per-sec".to_string())?;
+    }
+    if let Some(list) = arg_value(args, "--allow") {
+        limits.allowlist = connection_limits::ConnectionLimits::parse_allowlist(&list)?;
+    }
+    Ok(limits)
+}
+
+fn main() {
     // Process command line arguments
     let args: Vec<String> = env::args().collect();
+    // All logging goes through tracing, the offline tools' included (see log_file.rs)
+    log_file::init(false);
     
-    // Check for verbose flag
-    for arg in &args {
-        if arg == "--verbose" || arg == "-v" {
-            // Set global verbose flag
-            unsafe {
-                VERBOSE_LOGGING = true;
-            }
+    // Offline tools and --version [--json] run here and exit; otherwise the server options
+    // come back (see cli.rs)
+    let args = match cli::parse(args) {
+        cli::Invocation::Serve(args) => args,
+        cli::Invocation::Exit(code) => std::process::exit(code),
+    };
+    
+    // Options from the config file follow the command line ones (see config.rs)
+    // --config <file>   defaults to ./speedforge.toml when present
+    let args = match config::with_config_file(args) {
+        Ok(args) => args,
+        Err(e) => {
+            log_error!("{}", e);
+            std::process::exit(2);
         }
+    };
+    
+    // --verbose can come from the config file too
+    if args.iter().any(|arg| arg == "--verbose" || arg == "-v") {
+        log_file::set_verbose(true);
     }
     
+    let tuning = parse_thread_tuning(&args);
+    file_io::start();
+    let log_options = parse_log_file(&args);
+    if let Some(options) = &log_options {
+        match log_file::start(options.clone()) {
+            Ok(()) => log_info!("Logging to {} ({:?} rotation, keeping {} files)", options.dir.display(), options.rotation, options.keep),
+            Err(e) => log_error!("File logging disabled: {}", e),
+        }
+    }
+    // A panic leaves a bug report bundle next to the logs (see also `speedforge diagnose`)
+    diagnostics::install(args.clone(), log_options.map(|options| options.dir));
+    let precision = parse_precision(&args).unwrap_or_else(|e| {
+        log_error!("{}; sending full precision", e);
+        PrecisionRules::default()
+    });
+    let dead_bands = parse_dead_bands(&args);
+    
+    // Build the runtime by hand so worker threads can be pinned when requested. Blocking
+    // file I/O of the telemetry pipeline goes to its own thread (see file_io.rs)
+    let runtime_tuning = tuning.clone();
+    let mut builder = tokio::runtime::Builder::new_multi_thread();
+    builder.enable_all().thread_name("runtime");
+    if let Some(workers) = tuning.runtime_workers {
+        builder.worker_threads(workers);
+    }
+    let runtime = builder
+        .on_thread_start(move || {
+            if let Err(e) = runtime_tuning.apply_to_runtime_thread() {
+                log_error!("Failed to apply runtime thread affinity: {}", e);
+            }
+        })
+        .build();
+    
+    match runtime {
+        Ok(runtime) => runtime.block_on(run(args, tuning, precision, dead_bands)),
+        Err(e) => log_error!("Failed to start tokio runtime: {}", e),
+    }
+}
+
+async fn run(args: Vec<String>, tuning: ThreadTuning, precision: PrecisionRules, dead_bands: DeadBands) {
     // Print startup information
     print_startup_info();
+    log_info!("{}", version::version_text(false));
+    
+    // Log (never install) a newer release
+    // --check-updates
+    if args.iter().any(|arg| arg == "--check-updates") {
+        version::check_for_update(|message| log_info!("{}", message));
+    }
+    
+    if !tuning.is_default() {
+        log_info!("Thread tuning: telemetry priority {:?}, telemetry cores {:?}, runtime cores {:?}, runtime workers {:?}",
+            tuning.telemetry_priority, tuning.telemetry_cores, tuning.runtime_cores, tuning.runtime_workers);
+    }
     
     // Check if we're running on Windows, as iRacing SDK only works on Windows
     if !cfg!(target_os = "windows") {
@@ -312,11 +730,98 @@ async fn main() {
         log_info!("Real iRacing telemetry and session data will not be available");
     }
     
-    // Initialize WebSocket server (default port 8080)
-    let server_address = "0.0.0.0:8080";
-    log_info!("Initializing WebSocket server on {}", server_address);
+    // Several processes can read the sim at once (e.g. one recording, one broadcasting).
+    // iRacing publishes a single memory map per machine, so there is nothing to choose on
+    // the sim side; each process needs its own port and, with --instance, its own data directory
+    // --instance <name>   keeps profiles under instances/<name>/
+    // --bind <addr,...>   defaults to 0.0.0.0; IPv6 works too, e.g. "::" (dual-stack on its own)
+    //                     or "0.0.0.0,::1"
+    // --port <port>       defaults to 8080
+    let instance = arg_value(&args, "--instance");
+    if let Some(name) = &instance {
+        log_info!("Running as instance '{}'", name);
+    }
+    let bind_address = arg_value(&args, "--bind").unwrap_or_else(|| "0.0.0.0".to_string());
+    let port = match arg_value(&args, "--port").map(|value| value.parse::<u16>()) {
+        Some(Ok(port)) => port,
+        Some(Err(_)) => {
+            log_error!("Invalid --port, expected a number between 0 and 65535");
+            return;
+        },
+        None => DEFAULT_PORT,
+    };
+    
+    // Simulator to read; iRacing goes through its SDK, the others through a TelemetrySource
+    // --sim <iracing|acc|scenario|replay>   defaults to iracing
+    // --scenario <file>                     scripted race for demos (see scenario.rs)
+    // --replay <file>                       recording to play back (see replay.rs, or `speedforge replay`)
+    // --replay-speed <x>   --replay-once
+    let sim = arg_value(&args, "--sim").unwrap_or_else(|| "iracing".to_string());
+    let sim_source = match telemetry_source::for_sim(&sim, &args) {
+        Ok(source) => source,
+        Err(e) => {
+            log_error!("{}", e);
+            return;
+        }
+    };
+    
+    // iRacing samples per second
+    let sample_interval = match parse_sample_interval(&args) {
+        Ok(interval) => interval,
+        Err(e) => {
+            log_error!("{}", e);
+            return;
+        },
+    };
+    
+    // Which derived channels give way first when ticks take longer than the sample interval
+    // --priority-config <file.toml>   tiers of the derived channels (see load_shedding.rs)
+    // --no-load-shedding              always compute every channel
+    let priorities = match arg_value(&args, "--priority-config") {
+        Some(path) => match load_shedding::PriorityConfig::load(std::path::Path::new(&path)) {
+            Ok(priorities) => priorities,
+            Err(e) => {
+                log_error!("Failed to load priority config from {}: {}", path, e);
+                return;
+            }
+        },
+        None => load_shedding::PriorityConfig::default(),
+    };
+    let mut load_shedder = load_shedding::LoadShedder::new(&priorities, sample_interval, !args.iter().any(|arg| arg == "--no-load-shedding"));
+    
+    // Trace the pipeline stages to an OpenTelemetry collector (see pipeline_trace.rs)
+    // --otlp-endpoint <url>   or OTEL_EXPORTER_OTLP_ENDPOINT
+    // --otlp-sample <ratio>   share of frames traced, defaults to 0.1
+    match pipeline_trace::OtlpConfig::from_args(&args) {
+        Some(Ok(config)) => {
+            let url = config.url.clone();
+            match pipeline_trace::start(config) {
+                Ok(()) => log_info!("Exporting pipeline traces to {}", url),
+                Err(e) => log_error!("Pipeline tracing disabled: {}", e),
+            }
+        },
+        Some(Err(e)) => {
+            log_error!("{}", e);
+            return;
+        },
+        None => {},
+    }
+    
+    // Session info YAML previews in the log and the copy kept for `speedforge diagnose`
+    // --no-session-dump
+    let session_dump = !args.iter().any(|arg| arg == "--no-session-dump");
+    
+    // Initialize WebSocket server
+    let server_addresses = match parse_bind_addresses(&bind_address, port) {
+        Ok(addresses) => addresses,
+        Err(e) => {
+            log_error!("{}", e);
+            return;
+        }
+    };
+    log_info!("Initializing WebSocket server on {}", bind_address);
     
-    let ws_server = match TelemetryWebSocketServer::new(server_address) {
+    let mut ws_server = match TelemetryWebSocketServer::new(server_addresses) {
         Ok(server) => server,
         Err(e) => {
             log_error!("Failed to create WebSocket server: {}", e);
@@ -326,8 +831,264 @@ async fn main() {
     
     log_debug!("WebSocket server created, starting...");
     
-    // Set WebSocket server to verbose mode if we're in verbose mode
-    ws_server.set_verbose_mode(is_verbose());
+    // Latest frame and session info for tools that poll: GET /telemetry/latest and GET /session
+    let snapshots = snapshot::SnapshotStore::new(precision.clone());
+    ws_server.add_http_handler(Arc::new(snapshots.clone()));
+    ws_server.set_precision(precision);
+    ws_server.set_dead_bands(dead_bands.clone());
+    ws_server.set_channel_presets(parse_channel_presets(&args));
+    
+    // Web pages allowed to connect besides ones served from this machine
+    // --allow-origin <origin,...>   e.g. "https://overlays.example.com", "null" for sandboxed pages and files, or "*" for any
+    ws_server.set_origin_policy(origins::OriginPolicy::parse(&arg_value(&args, "--allow-origin").unwrap_or_default()));
+    match parse_connection_limits(&args) {
+        Ok(limits) => ws_server.set_connection_limits(limits),
+        Err(e) => {
+            log_error!("{}", e);
+            return;
+        }
+    }
+    
+    // Events raised by other threads (controller buttons, client requests...) are attached to the next frame
+    let external_events = events::EventQueue::new();
+    
+    // Actions from hotkeys and webhooks are applied by the telemetry loop
+    let (control_sender, control_actions) = controls::channel();
+    
+    // Named JSON blobs (overlay layouts, preferences) shared by clients over HTTP
+    // --profiles-dir <dir>   defaults to ./profiles
+    let profiles_dir = arg_value(&args, "--profiles-dir")
+        .unwrap_or_else(|| instance_path(instance.as_deref(), profiles::DEFAULT_PROFILES_DIR));
+    let profile_store = profiles::ProfileStore::new(&profiles_dir);
+    log_info!("Storing profiles in {}", profile_store.dir().display());
+    ws_server.add_http_handler(Arc::new(profile_store));
+    ws_server.add_http_handler(Arc::new(version::InfoEndpoint));
+    
+    // HTTP actions for VoiceAttack, Stream Deck and similar tools, refused unless a token is set.
+    // The same token opens the /admin WebSocket endpoint
+    // --api-token <token>
+    let api_token = arg_value(&args, "--api-token");
+    if api_token.is_some() {
+        log_info!("HTTP actions enabled on POST /actions/{{action}}");
+    }
+    ws_server.set_admin_token(api_token.clone());
+    
+    // Web admin panel on GET /admin for rigs without a screen nearby, behind the same token
+    let admin_status = admin_panel::AdminStatus::new();
+    let config_path = config::explicit_file(&args).ok().flatten().unwrap_or_else(|| config::DEFAULT_CONFIG_FILE.to_string());
+    if api_token.is_some() {
+        log_info!("Admin panel enabled on GET /admin");
+    }
+    let admin = Arc::new(admin_panel::AdminPanel::new(
+        api_token.clone(),
+        admin_status.clone(),
+        ws_server.client_directory(),
+        std::path::PathBuf::from(config_path),
+    ));
+    ws_server.add_http_handler(admin.clone());
+    // Version, sim connection, clients and output health (see sink_health.rs)
+    ws_server.add_request_handler("server_status", admin);
+    ws_server.add_http_handler(Arc::new(webhooks::WebhookReceiver::new(api_token, control_sender.clone())));
+    
+    // Gap to leader history for charts, kept on the server so it survives reconnects
+    // --gap-history-laps <n>   defaults to 10
+    let gap_history_laps = arg_value(&args, "--gap-history-laps")
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(gap_history::DEFAULT_HISTORY_LAPS);
+    let gap_history = gap_history::GapHistoryStore::new(gap_history_laps);
+    ws_server.add_request_handler("get_gap_history", Arc::new(gap_history.clone()));
+    
+    // Head-to-head between two cars picked by the client: {"type":"compare","cars":[3,12]}
+    ws_server.add_stream("compare", Arc::new(head_to_head::HeadToHead::new(gap_history.clone())));
+    
+    // Conversions between session time, replay frames and wall-clock time
+    let clock_store = session_clock::ClockStore::new();
+    ws_server.add_request_handler("convert_time", Arc::new(clock_store.clone()));
+    
+    // Character typed SDK channels published under `strings`
+    // --string-channels <A,B,...>
+    if let Some(value) = arg_value(&args, "--string-channels") {
+        telemetry_fields::set_string_channels(value.split(',').map(|name| name.trim().to_string()).filter(|name| !name.is_empty()).collect());
+    }
+
+    // Merged into the hello sent on connect: the driver's units preference
+    let bootstrap = units::Bootstrap::new();
+    ws_server.add_request_handler("bootstrap", Arc::new(bootstrap.clone()));
+    
+    // Replay bookmarks for the player's incidents and overtakes
+    // --replay-bookmarks   disabled unless given
+    let bookmarks = args.iter().any(|arg| arg == "--replay-bookmarks").then(|| {
+        let bookmarks = replay_bookmarks::ReplayBookmarks::new();
+        for message_type in ["get_replay_bookmarks", "jump_to_bookmark"] {
+            ws_server.add_request_handler(message_type, Arc::new(bookmarks.clone()));
+        }
+        bookmarks
+    });
+    
+    // Session browser over past recordings and iRacing's own .ibt files
+    // --sessions-dir <dir>   speedforge recordings (JSON lines)
+    // --ibt-dir <dir>        watched folder for .ibt files, e.g. Documents/iRacing/telemetry
+    let sessions_dir = arg_value(&args, "--sessions-dir").map(std::path::PathBuf::from);
+    let ibt_dir = arg_value(&args, "--ibt-dir").map(std::path::PathBuf::from);
+    if sessions_dir.is_some() || ibt_dir.is_some() {
+        let library = session_library::SessionLibrary::new();
+        for message_type in ["get_sessions", "get_session"] {
+            ws_server.add_request_handler(message_type, Arc::new(library.clone()));
+        }
+        // GET /compare?a=<id>&b=<id>
+        ws_server.add_http_handler(Arc::new(library.clone()));
+        library.watch(sessions_dir.clone(), ibt_dir);
+    }
+    
+    // Recording of the extracted frames as JSON lines (see recorder.rs), toggled at runtime
+    // with the toggle_recording action or the start_recording / stop_recording commands
+    // --record                 start recording right away
+    // --record-max-mb <mb>     start a new file at this size, defaults to 500
+    // Recordings are encrypted when SPEEDFORGE_PASSPHRASE is set (see encryption.rs)
+    let record_dir = sessions_dir.clone().unwrap_or_else(|| std::path::PathBuf::from(instance_path(instance.as_deref(), recorder::DEFAULT_DIR)));
+    let record_max_mb = arg_value(&args, "--record-max-mb").and_then(|value| value.parse().ok()).filter(|&mb: &u64| mb > 0).unwrap_or(recorder::DEFAULT_MAX_MB);
+    let recorder = recorder::Recorder::new(record_dir.clone(), record_max_mb * 1024 * 1024, encryption::passphrase(), external_events.clone());
+    for message_type in ["start_recording", "stop_recording", "get_recording"] {
+        ws_server.add_request_handler(message_type, Arc::new(recorder.clone()));
+    }
+    if args.iter().any(|arg| arg == "--record") {
+        recorder.set_recording(true);
+        log_info!("Recording sessions to {}", record_dir.display());
+    }
+    
+    // Disk space limits for the sessions folder, enforced hourly (see also `speedforge prune`)
+    // --retention-max-age-days <days>   --retention-max-size-mb <mb>   --keep-best-laps
+    match retention::RetentionPolicy::from_args(&args) {
+        Ok(retention) if retention.is_enabled() => {
+            log_info!("Retention enabled for {}: {:?}", record_dir.display(), retention);
+            // The recorder writes to the same folder; its current file is left alone
+            let recorder = recorder.clone();
+            retention.start_janitor(record_dir.clone(), move || recorder.active_file());
+        },
+        Ok(_) => {},
+        Err(e) => log_error!("Retention disabled: {}", e),
+    }
+    
+    // Car numbers, class colors, liveries and stable car ids for overlays
+    // --car-assets <file>   brand and logo per CarPath or CarID (see car_info.rs)
+    let car_assets = match arg_value(&args, "--car-assets") {
+        Some(path) => match car_info::CarAssets::load(std::path::Path::new(&path)) {
+            Ok(assets) => {
+                log_info!("Loaded {} car assets from {}", assets.len(), path);
+                assets
+            },
+            Err(e) => {
+                log_error!("{}", e);
+                car_info::CarAssets::default()
+            }
+        },
+        None => car_info::CarAssets::default(),
+    };
+    let car_info_store = car_info::CarInfoStore::new();
+    ws_server.add_request_handler("get_car_info", Arc::new(car_info_store.clone()));
+    let strength_of_field = strength_of_field::StrengthOfFieldStore::new();
+    ws_server.add_request_handler("get_strength_of_field", Arc::new(strength_of_field.clone()));
+    
+    // Steady-state channel averages for setup A/B tests
+    let wind_tunnel = wind_tunnel::WindTunnel::new();
+    ws_server.add_request_handler("wind_tunnel", Arc::new(wind_tunnel.clone()));
+    
+    // Damper velocity histograms per stint, built from the full-rate shock channels
+    let damper_histograms = damper_histogram::DamperHistogramStore::new();
+    ws_server.add_request_handler("get_damper_histogram", Arc::new(damper_histograms.clone()));
+    
+    // Corrections of impossible jumps in the raw channels
+    let glitch_counters = glitches::GlitchCounters::new();
+    ws_server.add_request_handler("get_glitch_stats", Arc::new(glitch_counters.clone()));
+    
+    // Bit assignment of each frame's field_mask for native clients
+    ws_server.add_request_handler("get_frame_schema", Arc::new(frame_schema::FrameSchema));
+    
+    // Pit board messages from the engineer to the driver overlay
+    let pit_board = pit_board::PitBoard::new(external_events.clone());
+    for message_type in ["pit_board", "pit_board_ack", "get_pit_board"] {
+        ws_server.add_request_handler(message_type, Arc::new(pit_board.clone()));
+    }
+    
+    // Pit loss assumed for effective standings and caution advice until stops have been measured
+    // --pit-loss <seconds>
+    let pit_loss = arg_value(&args, "--pit-loss")
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(pit_stops::DEFAULT_PIT_LOSS_S);
+    
+    // Monte Carlo finish position probabilities, simulated on a worker thread during races
+    // --finish-sim   disabled unless given
+    // --finish-sim-interval <seconds>   defaults to 10
+    // --finish-sim-iterations <n>       defaults to 2000
+    let finish_simulator = args.iter().any(|arg| arg == "--finish-sim").then(|| {
+        let iterations = arg_value(&args, "--finish-sim-iterations")
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(finish_probabilities::DEFAULT_ITERATIONS);
+        let interval = arg_value(&args, "--finish-sim-interval")
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(finish_probabilities::DEFAULT_INTERVAL_S);
+        let simulator = finish_probabilities::FinishSimulator::new(iterations);
+        ws_server.add_request_handler("get_finish_probabilities", Arc::new(simulator.clone()));
+        simulator.start_worker(Duration::from_secs(interval.max(1)));
+        log_info!("Finish simulation enabled: {} iterations every {}s", iterations, interval);
+        simulator
+    });
+    
+    // Fair-share drive time rules for team events
+    // --drive-time-min-pct <pct>   defaults to 25
+    // --drive-time-max-pct <pct>   no maximum unless given
+    // --drive-time-warning-min <minutes>   defaults to 10
+    let mut drive_time_rules = drive_time::DriveTimeRules::default();
+    if let Some(pct) = arg_value(&args, "--drive-time-min-pct").and_then(|v| v.parse().ok()) {
+        drive_time_rules.min_share_pct = pct;
+    }
+    drive_time_rules.max_share_pct = arg_value(&args, "--drive-time-max-pct").and_then(|v| v.parse().ok());
+    if let Some(minutes) = arg_value(&args, "--drive-time-warning-min").and_then(|v| v.parse::<f32>().ok()) {
+        drive_time_rules.warning_margin_s = minutes * 60.0;
+    }
+    
+    // Highlight markers for editing stream recordings, exported at the end of each session
+    // --highlights-dir <dir>   disabled unless given
+    let highlights_dir = arg_value(&args, "--highlights-dir");
+    
+    // Export (and upload) each session's recording once it is over
+    // --auto-export <bundle,csv,motec>   disabled unless given; needs --sessions-dir; encrypted with SPEEDFORGE_PASSPHRASE
+    // --export-dir <dir>   defaults to <sessions-dir>/exports
+    // --upload-url <url>   a folder, https://... for WebDAV (--upload-user <user>, password in SPEEDFORGE_UPLOAD_PASSWORD)
+    //                      or s3://bucket/prefix (--s3-region <region>, --s3-endpoint <url>, AWS_* credentials)
+    let auto_export = match auto_export::ExportConfig::from_args(&args) {
+        Ok(config) => config,
+        Err(e) => {
+            log_error!("Auto-export disabled: {}", e);
+            None
+        }
+    };
+    
+    // Drivers met in earlier sessions, with head-to-head results against the player
+    // --driver-db <file>   defaults to ./drivers.json
+    let driver_db_path = arg_value(&args, "--driver-db")
+        .unwrap_or_else(|| instance_path(instance.as_deref(), driver_db::DEFAULT_DRIVER_DB_PATH));
+    let driver_db = driver_db::DriverDatabase::load(&driver_db_path);
+    
+    // Career stats, series and official results from the iRacing web API (needs the data-api feature)
+    // --data-api-email <email>   with the password in the IRACING_PASSWORD environment variable
+    let data_api = arg_value(&args, "--data-api-email").and_then(|email| {
+        let Ok(password) = env::var("IRACING_PASSWORD") else {
+            log_error!("--data-api-email needs the password in IRACING_PASSWORD");
+            return None;
+        };
+        let credentials = data_api::Credentials { email, password };
+        match data_api::start(credentials, instance_path(instance.as_deref(), data_api::DEFAULT_CACHE_PATH)) {
+            Ok(api) => {
+                log_info!("iRacing data API enrichment enabled");
+                Some(api)
+            },
+            Err(e) => {
+                log_error!("{}", e);
+                None
+            },
+        }
+    });
     
     if let Err(e) = ws_server.start().await {
         log_error!("Failed to start WebSocket server: {}", e);
@@ -336,6 +1097,58 @@ async fn main() {
     
     log_info!("WebSocket server started and running");
     
+    // Optional controller buttons for dropping markers
+    // --marker-buttons <Button=label,...>   e.g. "South=flag_lap,code:305=review"
+    // --marker-debounce-ms <ms>
+    if let Some(mapping) = arg_value(&args, "--marker-buttons") {
+        let debounce_ms = arg_value(&args, "--marker-debounce-ms")
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(input_bridge::DEFAULT_DEBOUNCE_MS);
+        match input_bridge::InputBridgeConfig::parse(&mapping, debounce_ms) {
+            Ok(config) => match input_bridge::start(config, external_events.clone()) {
+                Ok(()) => log_info!("Controller marker buttons enabled: {}", mapping),
+                Err(e) => log_error!("{}", e),
+            },
+            Err(e) => log_error!("Invalid --marker-buttons: {}", e),
+        }
+    }
+    let telemetry_events = external_events.clone();
+    
+    // Optional global hotkeys, disabled unless a mapping is given
+    // --hotkeys <Keys=action,...>   e.g. "Ctrl+Alt+R=toggle_recording,Ctrl+Alt+M=marker,Ctrl+Alt+V=toggle_verbose,Ctrl+Alt+F=cycle_focus"
+    if let Some(mapping) = arg_value(&args, "--hotkeys") {
+        match hotkeys::parse_mapping(&mapping) {
+            Ok(bindings) => {
+                if let Err(e) = hotkeys::start(bindings, control_sender.clone()) {
+                    log_error!("{}", e);
+                }
+            },
+            Err(e) => log_error!("Invalid --hotkeys: {}", e),
+        }
+    }
+    
+    // What the channels the server adds to the built-in ones work with
+    let server_channels = derived::ServerChannels {
+        gap_history,
+        bootstrap,
+        car_info: car_info_store,
+        car_assets,
+        wind_tunnel,
+        damper_histograms,
+        strength_of_field,
+        pit_loss,
+        finish_simulator,
+        drive_time_rules,
+        bookmarks,
+        dead_bands,
+        session_clock: Some(clock_store),
+        session_info_keeper: session_dump,
+        driver_db: Some(driver_db),
+        data_api,
+        highlights_dir,
+        auto_export,
+    };
+    
     // Create a shared WebSocket server that can be accessed from a separate thread
     let ws_server_arc = Arc::new(ws_server);
     let ws_server_clone = ws_server_arc.clone();
@@ -343,9 +1156,105 @@ async fn main() {
     log_debug!("Starting iRacing telemetry thread");
     
     // Start a separate thread (not async task) for the iRacing connection
-    let iracing_thread = thread::spawn(move || {
+    let iracing_thread = thread::Builder::new().name("telemetry".to_string()).spawn(move || {
+        if let Err(e) = tuning.apply_to_telemetry_thread() {
+            log_error!("Failed to apply telemetry thread tuning: {}", e);
+        }
+        
+        // Derived channels are computed in dependency order after extraction
+        let mut derived_pipeline = match derived::DerivedPipeline::server(server_channels) {
+            Ok(pipeline) => pipeline,
+            Err(e) => {
+                log_error!("Failed to build derived channel pipeline: {}", e);
+                return;
+            }
+        };
+        log_debug!("Derived channels: {:?}", derived_pipeline.channel_names());
+        
+        let mut focus_car = controls::FocusCar::default();
+        let mut frozen_watchdog = watchdog::FrozenDataWatchdog::new();
+        let mut glitch_filter = glitches::GlitchFilter::new(glitch_counters);
+        let mut last_live_frame: Option<telemetry_fields::TelemetryData> = None;
+        
+        // Everything after extraction is the same for every sim
+        let mut publish = |mut telemetry_data: telemetry_fields::TelemetryData| {
+            let tick_started = Instant::now();
+        
+            // While the sim is paused, loading or hung, repeat the last live frame so
+            // fuel, gap and other running averages don't absorb the frozen time
+            if frozen_watchdog.update(telemetry_data.SessionTime) {
+                if let Some(last) = &last_live_frame {
+                    telemetry_data = last.clone();
+                    telemetry_data.events.clear();
+                }
+                telemetry_data.stale = true;
+            }
+        
+            // Attach markers and other externally raised events
+            telemetry_events.drain_into(&mut telemetry_data);
+            telemetry_data.pit_board = pit_board.current();
+        
+            // Apply hotkey presses and other control actions
+            while let Ok(action) = control_actions.try_recv() {
+                apply_control_action(action, &mut telemetry_data, &mut focus_car, &recorder);
+            }
+            telemetry_data.focus_car_idx = focus_car.car_idx();
+        
+            // Compute derived channels (g-forces, slip angle, fuel per lap, gaps...)
+            // once the session info is in place, since some channels read it
+            if !telemetry_data.stale {
+                // Recordings keep the sim's frames as extracted, so replaying or
+                // analyzing them derives everything again
+                recorder.record(&telemetry_data);
+                let derive_started = Instant::now();
+                glitch_filter.apply(&mut telemetry_data);
+                let skipped = derived_pipeline.evaluate_except(&mut telemetry_data, |channel| load_shedder.skips(channel));
+                if !skipped.is_empty() {
+                    // Repeat what the skipped channels showed last rather than dropping it
+                    if let Some(last) = &last_live_frame {
+                        for channel in &skipped {
+                            load_shedding::hold(channel, &mut telemetry_data, last);
+                        }
+                    }
+                    telemetry_data.shed = load_shedder.shed_tiers();
+                }
+                last_live_frame = Some(telemetry_data.clone());
+                pipeline_trace::stage("derive", derive_started);
+            }
+        
+            // Stamp the frame on the clock clients synchronize to
+            telemetry_data.server_time_ms = time_sync::server_time_ms();
+            telemetry_data.field_mask = frame_schema::presence_mask(&telemetry_data);
+        
+            // Convert TelemetryData to serde_json::Value
+            let json_value = serde_json::to_value(&telemetry_data).unwrap_or_else(|e| {
+                log_error!("Failed to convert telemetry data to JSON: {}", e);
+                serde_json::json!({})
+            });
+        
+            // Broadcast telemetry to all WebSocket clients
+            ws_server_clone.broadcast_telemetry(&telemetry_data);
+            admin_status.frame(&telemetry_data);
+            snapshots.update(json_value);
+            pipeline_trace::finish_frame(telemetry_data.SessionTime, ws_server_clone.client_count());
+            load_shedder.record(tick_started.elapsed());
+            admin_status.load(load_shedder.average(), load_shedder.budget());
+        
+            // Only log broadcasts in verbose mode or periodically
+            if should_log_telemetry_update() {
+                log_info!("Broadcast telemetry data to {} clients", ws_server_clone.client_count());
+            }
+        };
+        
+        if let Some(mut source) = sim_source {
+            log_info!("Reading telemetry from {}", source.name());
+            run_telemetry_source(source.as_mut(), &mut publish);
+            return;
+        }
+        
         let mut last_attempt = SystemTime::now();
         const CONNECTION_CHECK_INTERVAL: u64 = 5000; // 5 seconds between connection attempts
+        const SESSION_INFO_POLL: Duration = Duration::from_secs(5); // session info re-reads without the SDK counter
         let mut connection_status = "disconnected";
         
         loop {
@@ -364,17 +1273,19 @@ async fn main() {
                         log_info!("Attempting to get raw iRacing session info directly...");
                         
                         // First get the raw session info string directly, bypassing the problematic deserialization
-                        let raw_yaml = match iracing_wrapper::get_raw_session_info(&mut conn) {
+                        let mut raw_yaml = match iracing_wrapper::get_raw_session_info(&mut conn) {
                             Ok(raw_str) => {
                                 log_info!("Successfully retrieved raw session info, length: {} bytes", raw_str.len());
                                 
                                 // Print a preview of the raw data
-                                let preview = if raw_str.len() > 200 {
-                                    &raw_str[0..200]
-                                } else {
-                                    &raw_str
-                                };
-                                log_info!("Raw session info preview: {}", preview);
+                                if session_dump {
+                                    let preview = if raw_str.len() > 200 {
+                                        &raw_str[0..200]
+                                    } else {
+                                        &raw_str
+                                    };
+                                    log_info!("Raw session info preview: {}", preview);
+                                }
                                 
                                 // Use the raw string directly, we'll handle parsing issues in the UI
                                 raw_str
@@ -388,108 +1299,70 @@ async fn main() {
                             }
                         };
                         
+                        let mut session_info_update = iracing_wrapper::get_session_info_update();
+                        let mut session_info_read_at = Instant::now();
+                        
                         // Create a blocking telemetry handle
                         if let Ok(blocking) = conn.blocking() {
                             // Start monitoring telemetry
                             log_info!("Starting telemetry monitoring...");
                             
                             // Main telemetry loop
+                            let mut pacer = frame_pacing::FramePacer::with_interval(sample_interval);
                             loop {
+                                let sample_started = Instant::now();
                                 match blocking.sample(Duration::from_millis(100)) {
                                     Ok(sample) => {
+                                        pipeline_trace::stage("sample", sample_started);
                                         // Only log samples in verbose mode
                                         if is_verbose() {
                                             log_debug!("Received telemetry sample");
                                         }
                                         
                                         // Extract basic telemetry data
+                                        let extract_started = Instant::now();
                                         let mut telemetry_data = telemetry_fields::extract_telemetry(&sample);
+                                        pipeline_trace::stage("extract", extract_started);
                                         
-                                        // Calculate gaps
-                                        gap_calculator::calculate_gaps(&mut telemetry_data);
+                                        // Report SDK frames lost while the last one was being processed
+                                        telemetry_data.frames_skipped = pacer.sampled(telemetry_data.session_tick);
+                                        if telemetry_data.frames_skipped > 0 {
+                                            log_debug!("Fell behind, skipped {} SDK frames ({} total)", telemetry_data.frames_skipped, pacer.total_skipped());
+                                        }
                                         
-                                        // Use the session info we got from the connection
-                                        if !raw_yaml.is_empty() {
-                                            telemetry_data.session_info = raw_yaml.clone();
-                                            
-                                            // Periodically log that we're using real session data
-                                            if should_log_telemetry_update() {
-                                                log_info!("Using raw session info data in telemetry");
-                                            }
+                                        // Read the session info again when the SDK's counter says it changed
+                                        // (drivers joining, results, the next session); without the counter,
+                                        // every SESSION_INFO_POLL. Until it could be read, retry every 30s
+                                        let update = iracing_wrapper::get_session_info_update();
+                                        let due = if raw_yaml.is_empty() {
+                                            session_info_read_at.elapsed() >= Duration::from_secs(30)
                                         } else {
-                                            // Periodically try to get session info again if it failed before
-                                            static mut LAST_SESSION_RETRY: u64 = 0;
-                                            let now = SystemTime::now()
-                                                .duration_since(UNIX_EPOCH)
-                                                .unwrap_or_default()
-                                                .as_secs();
-                                                
-                                            let should_retry = unsafe {
-                                                if now - LAST_SESSION_RETRY > 30 {
-                                                    LAST_SESSION_RETRY = now;
-                                                    true
-                                                } else {
-                                                    false
-                                                }
-                                            };
-                                            
-                                            if should_retry {
-                                                log_info!("Retrying to get raw session info...");
-                                                match iracing_wrapper::get_raw_session_info(&mut conn) {
-                                                    Ok(raw_str) => {
-                                                        log_info!("Retry: Raw session info length: {} bytes", raw_str.len());
-                                                        // Dump a preview of the data for debugging
-                                                        let preview = if raw_str.len() > 200 {
-                                                            &raw_str[0..200]
-                                                        } else {
-                                                            &raw_str
-                                                        };
-                                                        log_info!("Retry: Session info preview: {}", preview);
-                                                        
-                                                        // Update the telemetry data with the new session info
-                                                        telemetry_data.session_info = raw_str;
-                                                        log_info!("Updated telemetry with new session info");
-                                                    },
-                                                    Err(e) => {
-                                                        log_error!("Retry: Failed to get raw session info: {:?}", e);
-                                                        
-                                                        // Use fallback data since we don't have real session info
-                                                        telemetry_data.session_info = get_fallback_session_info(
-                                                            telemetry_data.track_temp_c,
-                                                            telemetry_data.air_temp_c,
-                                                            telemetry_data.wind_vel_ms,
-                                                            telemetry_data.wind_dir_rad,
-                                                            telemetry_data.humidity_pct,
-                                                            telemetry_data.fog_level_pct
-                                                        );
-                                                    }
-                                                }
-                                            } else {
-                                                // If we're not retrying this time, use the fallback
-                                                telemetry_data.session_info = get_fallback_session_info(
-                                                    telemetry_data.track_temp_c,
-                                                    telemetry_data.air_temp_c,
-                                                    telemetry_data.wind_vel_ms,
-                                                    telemetry_data.wind_dir_rad,
-                                                    telemetry_data.humidity_pct,
-                                                    telemetry_data.fog_level_pct
-                                                );
+                                            match (update, session_info_update) {
+                                                (Some(now), Some(before)) => now != before,
+                                                _ => session_info_read_at.elapsed() >= SESSION_INFO_POLL,
+                                            }
+                                        };
+                                        if due {
+                                            session_info_update = update;
+                                            session_info_read_at = Instant::now();
+                                            match iracing_wrapper::get_raw_session_info(&mut conn) {
+                                                Ok(raw_str) if raw_str != raw_yaml => {
+                                                    log_info!("Session info updated, length: {} bytes", raw_str.len());
+                                                    raw_yaml = raw_str;
+                                                },
+                                                Ok(_) => {},
+                                                Err(e) => log_error!("Failed to read session info again: {:?}", e),
                                             }
                                         }
                                         
-                                        // Convert TelemetryData to serde_json::Value
-                                        let json_value = serde_json::to_value(&telemetry_data).unwrap_or_else(|e| {
-                                            log_error!("Failed to convert telemetry data to JSON: {}", e);
-                                            serde_json::json!({})
-                                        });
-                                        
-                                        // Broadcast telemetry to all WebSocket clients
-                                        ws_server_clone.broadcast_telemetry(&telemetry_data);
+                                        // The placeholder is constant, so clients get it once
+                                        telemetry_data.session_info = if raw_yaml.is_empty() {
+                                            FALLBACK_SESSION_INFO.to_string()
+                                        } else {
+                                            raw_yaml.clone()
+                                        };
                                         
-                                        // Only log broadcasts in verbose mode or periodically
-                                        if should_log_telemetry_update() {
-                                            log_info!("Broadcast telemetry data to {} clients", ws_server_clone.client_count());
-                                        }
+                                        publish(telemetry_data);
                                     },
                                     Err(e) => {
                                         log_error!("Error sampling telemetry: {:?}", e);
@@ -497,7 +1370,8 @@ async fn main() {
                                         break; // Exit the telemetry loop and try reconnecting
                                     }
                                 }
-                                thread::sleep(Duration::from_millis(50));
+                                // No sleep when processing overran the interval, so the next sample is current
+                                thread::sleep(pacer.wait());
                             }
                         }
                     },
@@ -520,6 +1394,10 @@ async fn main() {
             thread::sleep(Duration::from_millis(100));
         }
     });
+    if let Err(e) = iracing_thread {
+        log_error!("Failed to start telemetry thread: {}", e);
+        return;
+    }
     
     // Start a background task to monitor WebSocket connections
     let ws_server_for_monitoring = ws_server_arc.clone();
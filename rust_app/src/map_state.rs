@@ -0,0 +1,208 @@
+//! Track map state for map overlays.
+//!
+//! Combines the traced track shape (radar.rs), off-track hazards (sector_hazards.rs), the
+//! local yellow estimate (local_yellow.rs) and how many cars are in each part of the lap
+//! into one list of track segments. Each segment carries its outline in screen coordinates
+//! and its state, so an overlay only draws polylines in the colour of `state`. Clients
+//! subscribe to the "map_state" topic and get `{"type":"map_state",...}` when a segment's
+//! state changes (at most every `MIN_INTERVAL_S`) and every `REFRESH_S` regardless, so newly
+//! connected clients soon have one. Until a lap has been traced the outline is a circle.
+
+use crate::derived::DerivedChannel;
+use crate::radar::MAP_POINTS;
+use crate::sector_hazards::{segment_of, SEGMENT_COUNT};
+use crate::telemetry_fields::TelemetryData;
+use serde::{Serialize, Deserialize};
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+/// Shortest time between two map states
+pub const MIN_INTERVAL_S: f32 = 0.25;
+/// Longest time between two map states
+pub const REFRESH_S: f32 = 5.0;
+
+// Outline points per segment, both ends included
+const SEGMENT_POINTS: usize = 9;
+// Hazard from which a segment is drawn as hazardous
+const HAZARD_THRESHOLD: f32 = 0.5;
+
+/// One part of the lap and what is happening there
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct MapSegment {
+    pub index: usize,
+    pub start_pct: f32,
+    pub end_pct: f32,
+    /// Outline from start to end as [x, y] in 0..1, y pointing down
+    pub points: Vec<[f32; 2]>,
+    /// "yellow", "hazard" or "clear", for colouring
+    pub state: String,
+    /// A car is off track here right now
+    pub yellow: bool,
+    /// Inside the estimated local yellow range
+    pub local_yellow: bool,
+    /// 0..1 from recent off-tracks, in steps of 0.1
+    pub hazard: f32,
+    /// Cars on track in this segment
+    pub cars: usize,
+    /// The player is in this segment
+    pub player: bool,
+}
+
+/// Every segment of the lap with its outline and state
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct MapState {
+    pub segment_count: usize,
+    /// Outlines follow the traced track; false while they are a placeholder circle
+    pub traced: bool,
+    pub segments: Vec<MapSegment>,
+}
+
+// Whether `pct` lies in a range that may wrap past the line
+fn in_range(pct: f32, start: f32, end: f32) -> bool {
+    if start <= end {
+        (start..=end).contains(&pct)
+    } else {
+        pct >= start || pct <= end
+    }
+}
+
+fn round(value: f32) -> f32 {
+    (value * 10000.0).round() / 10000.0
+}
+
+// Segment outlines, from the traced shape fitted into the unit square or from a circle
+fn outlines(shape: Option<&[(f32, f32)]>) -> Vec<Vec<[f32; 2]>> {
+    let point: Box<dyn Fn(f32) -> [f32; 2] + '_> = match shape.filter(|shape| shape.len() == MAP_POINTS) {
+        Some(shape) => {
+            let (min_x, max_x) = shape.iter().fold((f32::MAX, f32::MIN), |(lo, hi), p| (lo.min(p.0), hi.max(p.0)));
+            let (min_y, max_y) = shape.iter().fold((f32::MAX, f32::MIN), |(lo, hi), p| (lo.min(p.1), hi.max(p.1)));
+            let scale = (max_x - min_x).max(max_y - min_y).max(f32::EPSILON);
+            // Centre the shorter side
+            let pad_x = (1.0 - (max_x - min_x) / scale) / 2.0;
+            let pad_y = (1.0 - (max_y - min_y) / scale) / 2.0;
+            Box::new(move |pct: f32| {
+                let scaled = pct.rem_euclid(1.0) * MAP_POINTS as f32;
+                let i = (scaled as usize).min(MAP_POINTS - 1);
+                let t = scaled - i as f32;
+                let (a, b) = (shape[i], shape[(i + 1) % MAP_POINTS]);
+                let (x, y) = (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t);
+                // The traced path has y pointing up
+                [round(pad_x + (x - min_x) / scale), round(pad_y + (max_y - y) / scale)]
+            })
+        },
+        None => Box::new(|pct: f32| {
+            let angle = pct * 2.0 * PI;
+            [round(0.5 + 0.5 * angle.sin()), round(0.5 - 0.5 * angle.cos())]
+        }),
+    };
+    (0..SEGMENT_COUNT)
+        .map(|index| {
+            (0..SEGMENT_POINTS)
+                .map(|step| point((index as f32 + step as f32 / (SEGMENT_POINTS - 1) as f32) / SEGMENT_COUNT as f32))
+                .collect()
+        })
+        .collect()
+}
+
+/// Derived channel that publishes the map state for map overlays
+#[derive(Default)]
+pub struct MapStateBuilder {
+    shape: Option<Arc<Vec<(f32, f32)>>>,
+    // Per segment, for `shape`; empty until the first frame
+    outlines: Vec<Vec<[f32; 2]>>,
+    last_sent: Option<(f32, MapState)>,
+}
+
+impl MapStateBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl DerivedChannel for MapStateBuilder {
+    fn name(&self) -> &'static str {
+        "map_state"
+    }
+
+    fn dependencies(&self) -> &'static [&'static str] {
+        &["radar", "sector_hazards", "local_yellow"]
+    }
+
+    fn compute(&mut self, data: &mut TelemetryData) {
+        if data.race_clock.session_reset {
+            self.last_sent = None;
+        }
+        let same_shape = match (&self.shape, &data.track_shape) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            (None, None) => true,
+            _ => false,
+        };
+        if !same_shape || self.outlines.is_empty() {
+            self.shape = data.track_shape.clone();
+            self.outlines = outlines(self.shape.as_deref().map(Vec::as_slice));
+        }
+
+        let mut cars = [0usize; SEGMENT_COUNT];
+        if let Some(positions) = data.CarIdxLapDistPct.as_ref() {
+            let on_pit_road = data.CarIdxOnPitRoad.as_ref();
+            for (idx, &pct) in positions.iter().enumerate() {
+                if pct >= 0.0 && on_pit_road.and_then(|pits| pits.get(idx)).copied() != Some(true) {
+                    cars[segment_of(pct)] += 1;
+                }
+            }
+        }
+        let player = (data.lap_dist_pct >= 0.0).then(|| segment_of(data.lap_dist_pct));
+
+        let segments = self
+            .outlines
+            .iter()
+            .enumerate()
+            .map(|(index, points)| {
+                let start_pct = index as f32 / SEGMENT_COUNT as f32;
+                let end_pct = (index + 1) as f32 / SEGMENT_COUNT as f32;
+                let hazard = data.sector_hazards.as_ref().and_then(|hazards| hazards.segments.iter().find(|segment| segment.index == index));
+                let local_yellow = data.local_yellow.as_ref().is_some_and(|range| {
+                    in_range(start_pct, range.start_pct, range.end_pct) || in_range(range.start_pct, start_pct, end_pct)
+                });
+                let yellow = hazard.is_some_and(|segment| segment.yellow);
+                let hazard = hazard.map_or(0.0, |segment| (segment.hazard * 10.0).round() / 10.0);
+                let state = if yellow || local_yellow {
+                    "yellow"
+                } else if hazard >= HAZARD_THRESHOLD {
+                    "hazard"
+                } else {
+                    "clear"
+                };
+                MapSegment {
+                    index,
+                    start_pct,
+                    end_pct,
+                    points: points.clone(),
+                    state: state.to_string(),
+                    yellow,
+                    local_yellow,
+                    hazard,
+                    cars: cars[index],
+                    player: player == Some(index),
+                }
+            })
+            .collect();
+        let state = MapState {
+            segment_count: SEGMENT_COUNT,
+            traced: self.shape.is_some(),
+            segments,
+        };
+
+        let due = match &self.last_sent {
+            None => true,
+            Some((time, last)) => {
+                let elapsed = data.SessionTime - time;
+                elapsed < 0.0 || elapsed >= REFRESH_S || (elapsed >= MIN_INTERVAL_S && *last != state)
+            },
+        };
+        if due {
+            self.last_sent = Some((data.SessionTime, state.clone()));
+            data.map_state = Some(state);
+        }
+    }
+}
@@ -0,0 +1,261 @@
+//! Track and car metadata cached across sessions.
+//!
+//! Facts read from the session info (track length, pit speed limit, shift RPMs, tank
+//! size...) are stored by TrackID / CarID in a JSON file. When a later session omits
+//! a value, the cached one is used, so strategy and engine calculations keep working.
+//! The pit lane length isn't in the session info at all; it is measured the first time
+//! the player drives through the pit lane.
+
+use crate::derived::DerivedChannel;
+use crate::determinism;
+use crate::file_io;
+use crate::session_yaml;
+use crate::telemetry_fields::TelemetryData;
+use serde::{Serialize, Deserialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Cache file, relative to the working directory
+pub const CACHE_PATH: &str = "metadata_cache.json";
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct TrackMetadata {
+    pub track_id: i64,
+    pub name: String,
+    pub config: String,
+    pub length_km: Option<f32>,
+    pub pit_speed_limit_kph: Option<f32>,
+    pub turns: Option<u32>,
+    pub pit_lane_length_m: Option<f32>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct CarMetadata {
+    pub car_id: i64,
+    pub name: String,
+    pub redline_rpm: Option<f32>,
+    pub shift_first_rpm: Option<f32>,
+    pub shift_rpm: Option<f32>,
+    pub shift_last_rpm: Option<f32>,
+    pub shift_blink_rpm: Option<f32>,
+    pub fuel_max_ltr: Option<f32>,
+}
+
+/// Metadata for the current track and car, with gaps filled from the cache
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct SessionMetadata {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub track: Option<TrackMetadata>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub car: Option<CarMetadata>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+struct CacheFile {
+    tracks: BTreeMap<i64, TrackMetadata>,
+    cars: BTreeMap<i64, CarMetadata>,
+}
+
+// Keep the known value when the session doesn't report one (or reports 0)
+fn fill(observed: &mut Option<f32>, cached: Option<f32>) {
+    if observed.is_none_or(|value| value <= 0.0) {
+        *observed = cached;
+    }
+}
+
+fn positive(value: Option<f32>) -> Option<f32> {
+    value.filter(|v| *v > 0.0)
+}
+
+/// JSON backed cache of track and car metadata
+pub struct MetadataCache {
+    path: PathBuf,
+    cache: CacheFile,
+}
+
+impl MetadataCache {
+    /// Load the cache, starting empty when the file doesn't exist or can't be read, or in
+    /// deterministic mode where only the recorded frames count
+    pub fn load(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref().to_path_buf();
+        if !path.exists() || determinism::enabled() {
+            return Self { path, cache: CacheFile::default() };
+        }
+        Self::try_load(&path).unwrap_or_else(|e| {
+            tracing::warn!("Ignoring {}", e);
+            Self { path, cache: CacheFile::default() }
+        })
+    }
+
+    /// Load the cache, failing on a missing or unreadable file
+    pub fn try_load(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref().to_path_buf();
+        let contents = fs::read_to_string(&path).map_err(|e| format!("metadata cache {}: {}", path.display(), e))?;
+        let cache = serde_json::from_str(&contents).map_err(|e| format!("unreadable metadata cache {}: {}", path.display(), e))?;
+        Ok(Self { path, cache })
+    }
+
+    /// Number of cached tracks and cars
+    pub fn counts(&self) -> (usize, usize) {
+        (self.cache.tracks.len(), self.cache.cars.len())
+    }
+
+    // Written off the telemetry thread, through a temporary file since other instances share the cache
+    fn save(&self) {
+        if determinism::enabled() {
+            return;
+        }
+        match serde_json::to_string_pretty(&self.cache) {
+            Ok(contents) => file_io::write_atomic(self.path.clone(), contents, "metadata cache"),
+            Err(e) => tracing::error!("Failed to write metadata cache {}: {}", self.path.display(), e),
+        }
+    }
+
+    /// Merge a track seen in the session info with the cache; returns the filled in record
+    pub fn merge_track(&mut self, mut track: TrackMetadata) -> TrackMetadata {
+        let cached = self.cache.tracks.get(&track.track_id).cloned().unwrap_or_default();
+        fill(&mut track.length_km, cached.length_km);
+        fill(&mut track.pit_speed_limit_kph, cached.pit_speed_limit_kph);
+        fill(&mut track.pit_lane_length_m, cached.pit_lane_length_m);
+        if track.turns.is_none_or(|turns| turns == 0) {
+            track.turns = cached.turns;
+        }
+        if track != cached {
+            self.cache.tracks.insert(track.track_id, track.clone());
+            self.save();
+        }
+        track
+    }
+
+    /// Merge a car seen in the session info with the cache; returns the filled in record
+    pub fn merge_car(&mut self, mut car: CarMetadata) -> CarMetadata {
+        let cached = self.cache.cars.get(&car.car_id).cloned().unwrap_or_default();
+        fill(&mut car.redline_rpm, cached.redline_rpm);
+        fill(&mut car.shift_first_rpm, cached.shift_first_rpm);
+        fill(&mut car.shift_rpm, cached.shift_rpm);
+        fill(&mut car.shift_last_rpm, cached.shift_last_rpm);
+        fill(&mut car.shift_blink_rpm, cached.shift_blink_rpm);
+        fill(&mut car.fuel_max_ltr, cached.fuel_max_ltr);
+        if car != cached {
+            self.cache.cars.insert(car.car_id, car.clone());
+            self.save();
+        }
+        car
+    }
+}
+
+/// Track metadata as reported by the session info
+pub fn track_from_session(yaml: &str) -> Option<TrackMetadata> {
+    let track_id = session_yaml::number(yaml, "TrackID")? as i64;
+    Some(TrackMetadata {
+        track_id,
+        name: session_yaml::scalar(yaml, "TrackDisplayName").unwrap_or_default().to_string(),
+        config: session_yaml::scalar(yaml, "TrackConfigName").unwrap_or_default().to_string(),
+        length_km: positive(session_yaml::number(yaml, "TrackLength")),
+        pit_speed_limit_kph: positive(session_yaml::number(yaml, "TrackPitSpeedLimit")),
+        turns: session_yaml::number(yaml, "TrackNumTurns").map(|turns| turns as u32).filter(|turns| *turns > 0),
+        pit_lane_length_m: None,
+    })
+}
+
+/// Metadata of the player's car as reported by the session info
+pub fn car_from_session(yaml: &str, player_car_idx: i32) -> Option<CarMetadata> {
+    let driver = session_yaml::drivers(yaml)
+        .into_iter()
+        .find(|driver| driver.car_idx == player_car_idx)?;
+    if driver.car_id <= 0 {
+        return None;
+    }
+    Some(CarMetadata {
+        car_id: driver.car_id,
+        name: driver.car_screen_name,
+        redline_rpm: positive(session_yaml::number(yaml, "DriverCarRedLine")),
+        shift_first_rpm: positive(session_yaml::number(yaml, "DriverCarSLFirstRPM")),
+        shift_rpm: positive(session_yaml::number(yaml, "DriverCarSLShiftRPM")),
+        shift_last_rpm: positive(session_yaml::number(yaml, "DriverCarSLLastRPM")),
+        shift_blink_rpm: positive(session_yaml::number(yaml, "DriverCarSLBlinkRPM")),
+        fuel_max_ltr: positive(session_yaml::number(yaml, "DriverCarFuelMaxLtr")),
+    })
+}
+
+/// Derived channel that publishes the current track and car metadata
+pub struct Metadata {
+    cache: MetadataCache,
+    last_session_info: String,
+    current: SessionMetadata,
+    /// Lap position where the player entered pit road, while driving through it
+    pit_entry_pct: Option<f32>,
+}
+
+impl Metadata {
+    pub fn new() -> Self {
+        Self {
+            cache: MetadataCache::load(CACHE_PATH),
+            last_session_info: String::new(),
+            current: SessionMetadata::default(),
+            pit_entry_pct: None,
+        }
+    }
+
+    // Measure the pit lane from where the player enters to where they leave pit road
+    fn measure_pit_lane(&mut self, data: &TelemetryData) {
+        let was_on_pit_road = self.pit_entry_pct.is_some();
+        if data.on_pit_road && !was_on_pit_road {
+            self.pit_entry_pct = Some(data.lap_dist_pct);
+        } else if !data.on_pit_road && was_on_pit_road {
+            let entry = self.pit_entry_pct.take().unwrap_or_default();
+            let Some(track) = self.current.track.as_ref() else {
+                return;
+            };
+            if track.pit_lane_length_m.is_some() {
+                return;
+            }
+            let Some(length_km) = track.length_km else {
+                return;
+            };
+            // Pit lanes usually straddle the line, so measure forward from the entry
+            let fraction = (data.lap_dist_pct - entry).rem_euclid(1.0);
+            if fraction > 0.0 && fraction < 0.5 {
+                let mut measured = track.clone();
+                measured.pit_lane_length_m = Some(fraction * length_km * 1000.0);
+                self.current.track = Some(self.cache.merge_track(measured));
+            }
+        }
+    }
+}
+
+impl DerivedChannel for Metadata {
+    fn name(&self) -> &'static str {
+        "metadata"
+    }
+
+    fn compute(&mut self, data: &mut TelemetryData) {
+        if data.session_info != self.last_session_info {
+            self.last_session_info = data.session_info.clone();
+            let track = track_from_session(&data.session_info).map(|mut track| {
+                // Keep a pit lane measured earlier in this session
+                track.pit_lane_length_m = self.current.track.as_ref()
+                    .filter(|current| current.track_id == track.track_id)
+                    .and_then(|current| current.pit_lane_length_m);
+                self.cache.merge_track(track)
+            });
+            let car = car_from_session(&data.session_info, data.player_car_idx).map(|car| self.cache.merge_car(car));
+            self.current = SessionMetadata { track, car };
+        }
+
+        // Resets and tows leave the world briefly, which would spoil the measurement
+        if data.lap_dist_pct < 0.0 {
+            self.pit_entry_pct = None;
+        } else {
+            self.measure_pit_lane(data);
+        }
+
+        if self.current.track.is_some() || self.current.car.is_some() {
+            data.session_metadata = Some(self.current.clone());
+        }
+    }
+}
@@ -0,0 +1,91 @@
+//! Which web pages may talk to the server.
+//!
+//! Browsers attach an `Origin` header to WebSocket upgrades and cross-origin HTTP
+//! requests, and let any page open a socket to localhost. Without a check, any website
+//! the driver visits could read the local telemetry. Requests without an `Origin`
+//! (native apps, curl) are not from a web page and are always allowed, as are pages
+//! served from a loopback host. Overlays hosted elsewhere have to be listed with
+//! `--allow-origin https://overlays.example.com,...`, or `*` for anyone.
+//!
+//! Browsers send `Origin: null` for local files but also for sandboxed iframes and `data:`
+//! documents on any website, so `null` is only allowed when listed. Pages this server
+//! served itself are same-origin, but only when the `Host` they were loaded from is one a
+//! DNS rebinding attack can't take over: a loopback name, an address the server is bound
+//! to, or a host from the allowlist.
+
+use std::net::{IpAddr, SocketAddr};
+
+/// Origins allowed to connect besides the local machine
+#[derive(Clone, Debug, Default)]
+pub struct OriginPolicy {
+    allowed: Vec<String>,
+    allow_any: bool,
+    // Addresses the server listens on, for checking `Host`
+    bound: Vec<IpAddr>,
+}
+
+impl OriginPolicy {
+    /// Parse a comma separated list of origins such as "https://a.example,http://b.example:8000"
+    pub fn parse(list: &str) -> Self {
+        let mut policy = Self::default();
+        for origin in list.split(',').map(str::trim).filter(|origin| !origin.is_empty()) {
+            if origin == "*" {
+                policy.allow_any = true;
+            } else {
+                policy.allowed.push(origin.trim_end_matches('/').to_ascii_lowercase());
+            }
+        }
+        policy
+    }
+
+    /// Whether a request with this `Origin` header (None when absent) may be served
+    pub fn is_allowed(&self, origin: Option<&str>) -> bool {
+        let Some(origin) = origin.map(|origin| origin.trim().to_ascii_lowercase()) else {
+            return true;
+        };
+        self.allow_any || is_local(&origin) || self.allowed.iter().any(|allowed| *allowed == origin)
+    }
+
+    /// The same policy for a server listening on `addresses`
+    pub fn bound_to(mut self, addresses: &[SocketAddr]) -> Self {
+        self.bound = addresses.iter().map(SocketAddr::ip).collect();
+        self
+    }
+
+    /// Whether a `Host` header names this server in a way a foreign site can't point at
+    /// it: a loopback name, a bound address (any address when bound to all interfaces)
+    /// or the host of an allowed origin
+    pub fn is_trusted_host(&self, host: &str) -> bool {
+        let name = host_name(&host.trim().to_ascii_lowercase()).to_string();
+        if is_loopback_name(&name) {
+            return true;
+        }
+        if let Ok(ip) = name.parse::<IpAddr>() {
+            return self.bound.iter().any(|bound| bound.is_unspecified() || *bound == ip);
+        }
+        self.allowed
+            .iter()
+            .filter_map(|allowed| allowed.split_once("://"))
+            .any(|(_, authority)| host_name(authority) == name)
+    }
+}
+
+// Host of an authority such as "example.com:8080" or "[::1]:8080", without the port
+fn host_name(authority: &str) -> &str {
+    match authority.strip_prefix('[') {
+        Some(v6) => v6.split(']').next().unwrap_or_default(),
+        None => authority.split(':').next().unwrap_or_default(),
+    }
+}
+
+fn is_loopback_name(host: &str) -> bool {
+    host == "localhost" || host.ends_with(".localhost") || host.parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback())
+}
+
+// Pages from this machine: files and anything served from a loopback host
+fn is_local(origin: &str) -> bool {
+    if origin.starts_with("file://") {
+        return true;
+    }
+    origin.split_once("://").is_some_and(|(_, authority)| is_loopback_name(host_name(authority)))
+}
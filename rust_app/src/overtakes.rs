@@ -0,0 +1,106 @@
+use crate::corners::{CornerDatabase, CORNERS_PATH};
+use crate::derived::DerivedChannel;
+use crate::events;
+use crate::laps;
+use crate::telemetry_fields::TelemetryData;
+use serde_json::json;
+use std::collections::HashMap;
+
+// Cars further apart than this (fraction of a lap) swapped positions through
+// something other than a pass on track, e.g. a pit stop cycle
+const MAX_PASS_DISTANCE_PCT: f32 = 0.02;
+// A pair swapping back and forth within this time reports only the first swap
+const REPEAT_SUPPRESS_S: f32 = 5.0;
+
+/// Derived channel that emits an `overtake` event whenever two cars swap positions
+pub struct OvertakeDetector {
+    corners: CornerDatabase,
+    last_positions: Vec<i32>,
+    /// SessionTime of the last reported swap per (passing, passed) pair
+    last_swap: HashMap<(usize, usize), f32>,
+}
+
+impl OvertakeDetector {
+    pub fn new() -> Self {
+        Self {
+            corners: CornerDatabase::load(CORNERS_PATH),
+            last_positions: Vec::new(),
+            last_swap: HashMap::new(),
+        }
+    }
+}
+
+impl DerivedChannel for OvertakeDetector {
+    fn name(&self) -> &'static str {
+        "overtakes"
+    }
+
+    fn dependencies(&self) -> &'static [&'static str] {
+        // Positions come from the gap calculator, the track id from the metadata
+        &["gaps", "metadata"]
+    }
+
+    fn compute(&mut self, data: &mut TelemetryData) {
+        if data.race_clock.session_reset {
+            self.last_positions.clear();
+            self.last_swap.clear();
+        }
+
+        let (Some(positions), Some(lap_dist)) = (data.CarIdxPosition.clone(), data.CarIdxLapDistPct.as_ref()) else {
+            return;
+        };
+        let previous = std::mem::replace(&mut self.last_positions, positions.clone());
+        if previous.len() != positions.len() {
+            return;
+        }
+        let in_world = |idx: usize| lap_dist.get(idx).is_some_and(|&pct| pct >= 0.0);
+        let gained: Vec<usize> = (0..positions.len())
+            .filter(|&idx| in_world(idx) && positions[idx] > 0 && previous[idx] > 0 && positions[idx] < previous[idx])
+            .collect();
+        if gained.is_empty() {
+            return;
+        }
+
+        let track_id = data.session_metadata.as_ref().and_then(|m| m.track.as_ref()).map(|t| t.track_id);
+        let mut overtakes = Vec::new();
+        for &passing in &gained {
+            for passed in 0..positions.len() {
+                if passed == passing || !in_world(passed) || positions[passed] <= 0 || previous[passed] <= 0 {
+                    continue;
+                }
+                // Was ahead, now behind
+                if !(previous[passed] < previous[passing] && positions[passed] > positions[passing]) {
+                    continue;
+                }
+                let pct = lap_dist[passing];
+                if laps::lap_pct_distance(pct, lap_dist[passed]) > MAX_PASS_DISTANCE_PCT {
+                    continue;
+                }
+                if self.last_swap.get(&(passed, passing)).is_some_and(|&t| data.SessionTime - t < REPEAT_SUPPRESS_S) {
+                    // Just the pair trading places back, e.g. side by side over a timing line
+                    continue;
+                }
+                self.last_swap.insert((passing, passed), data.SessionTime);
+
+                let on_pit_road = |idx: usize| {
+                    data.CarIdxOnPitRoad.as_ref().and_then(|p| p.get(idx)).copied().unwrap_or(false)
+                };
+                let corner = track_id.and_then(|id| self.corners.corner_at(id, pct)).map(|c| c.name.clone());
+                overtakes.push(json!({
+                    "car_idx": passing,
+                    "passed_car_idx": passed,
+                    "position": positions[passing],
+                    "lap": data.CarIdxLap.as_ref().and_then(|l| l.get(passing)).copied(),
+                    "lap_dist_pct": pct,
+                    "corner": corner,
+                    "in_pits": on_pit_road(passing) || on_pit_road(passed),
+                }));
+            }
+        }
+        self.last_swap.retain(|_, &mut t| data.SessionTime - t < REPEAT_SUPPRESS_S);
+
+        for overtake in overtakes {
+            events::emit(data, "overtake", overtake);
+        }
+    }
+}
@@ -0,0 +1,305 @@
+//! OpenTelemetry traces of the telemetry pipeline, exported over OTLP/HTTP.
+//!
+//! Each traced frame becomes one trace: a `frame` span with a child span per stage, as
+//! the telemetry thread went through them:
+//!
+//! - `sample`: waiting for and reading the sim's frame
+//! - `extract`: turning the SDK sample into `TelemetryData` (iRacing only; other sims
+//!   extract while sampling)
+//! - `derive`: the derived channels
+//! - `encode`: serializing the frame and the per-frame messages
+//! - `fan_out`: building each client's variant and queueing it to every client
+//!
+//! Spans go to an exporter thread that posts them in batches as OTLP JSON to
+//! `<endpoint>/v1/traces`, so any OpenTelemetry collector, Jaeger or Tempo can show where
+//! a slow frame spent its time. The telemetry thread never waits on it: when the
+//! collector is down, traces beyond `QUEUE_LIMIT` are dropped, and the exporter shows
+//! up as the `otlp` output in the admin panel (see sink_health.rs).
+//!
+//! Enabled with `--otlp-endpoint <url>` or `OTEL_EXPORTER_OTLP_ENDPOINT`; needs the
+//! "otel" feature. `--otlp-sample <ratio>` picks the share of frames traced,
+//! `OTEL_EXPORTER_OTLP_HEADERS` (`key=value,...`) adds headers such as an API key and
+//! `OTEL_SERVICE_NAME` renames the service.
+
+use crate::sink_health;
+use serde_json::{json, Value};
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, SyncSender};
+use std::sync::OnceLock;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Share of frames traced by default; at 60 Hz every frame would be a lot of spans
+pub const DEFAULT_SAMPLE_RATIO: f32 = 0.1;
+/// Traces waiting for the exporter beyond which new ones are dropped
+pub const QUEUE_LIMIT: usize = 1000;
+/// Longest a trace waits before its batch is sent
+pub const BATCH_INTERVAL: Duration = Duration::from_secs(2);
+/// Most traces in one request
+pub const MAX_BATCH: usize = 200;
+
+const SINK: &str = "otlp";
+// More stages than a frame goes through
+const MAX_STAGES: usize = 32;
+
+/// Where and how to export
+#[derive(Clone, Debug)]
+pub struct OtlpConfig {
+    /// Full URL of the traces endpoint
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub service_name: String,
+    pub sample_ratio: f32,
+}
+
+impl OtlpConfig {
+    /// Settings from the command line and the standard OTEL_* variables; None when no
+    /// endpoint is configured
+    pub fn from_args(args: &[String]) -> Option<Result<Self, String>> {
+        let endpoint = crate::arg_value(args, "--otlp-endpoint").or_else(|| std::env::var("OTEL_EXPORTER_OTLP_ENDPOINT").ok())?;
+        let endpoint = endpoint.trim_end_matches('/');
+        let url = if endpoint.ends_with("/v1/traces") { endpoint.to_string() } else { format!("{}/v1/traces", endpoint) };
+        let sample_ratio = match crate::arg_value(args, "--otlp-sample").map(|value| value.parse::<f32>()) {
+            None => DEFAULT_SAMPLE_RATIO,
+            Some(Ok(ratio)) if ratio > 0.0 && ratio <= 1.0 => ratio,
+            Some(_) => return Some(Err("--otlp-sample must be a share of frames above 0 and up to 1".to_string())),
+        };
+        let headers = std::env::var("OTEL_EXPORTER_OTLP_HEADERS")
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|pair| pair.split_once('='))
+            .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+            .collect();
+        let service_name = std::env::var("OTEL_SERVICE_NAME").unwrap_or_else(|_| "speedforge".to_string());
+        Some(Ok(Self { url, headers, service_name, sample_ratio }))
+    }
+}
+
+/// A finished stage of a frame
+struct Span {
+    name: &'static str,
+    start: Instant,
+    end: Instant,
+}
+
+/// The stages of one frame, on its way to the exporter
+struct FrameTrace {
+    spans: Vec<Span>,
+    session_time: f32,
+    clients: usize,
+}
+
+struct Exporter {
+    sender: SyncSender<FrameTrace>,
+    // Trace one frame in this many
+    every: u64,
+    // Wall clock reading for an Instant, to convert span times
+    epoch: (Instant, u128),
+}
+
+static EXPORTER: OnceLock<Exporter> = OnceLock::new();
+static FRAMES: AtomicU64 = AtomicU64::new(0);
+static QUEUED: AtomicUsize = AtomicUsize::new(0);
+static ID_STATE: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    static CURRENT: RefCell<Vec<Span>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Whether this build can export traces
+pub fn available() -> Result<(), String> {
+    transport::check()
+}
+
+/// Start exporting; stages are only recorded from then on
+pub fn start(config: OtlpConfig) -> Result<(), String> {
+    available()?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos();
+    ID_STATE.store(now as u64, Ordering::Relaxed);
+    let (sender, receiver) = mpsc::sync_channel(QUEUE_LIMIT);
+    let exporter = Exporter {
+        sender,
+        every: (1.0 / config.sample_ratio).round().max(1.0) as u64,
+        epoch: (Instant::now(), now),
+    };
+    if EXPORTER.set(exporter).is_err() {
+        return Err("OTLP export already started".to_string());
+    }
+    thread::Builder::new()
+        .name("otlp".to_string())
+        .spawn(move || {
+            let mut failures = 0;
+            while let Ok(first) = receiver.recv() {
+                let mut batch = vec![first];
+                let deadline = Instant::now() + BATCH_INTERVAL;
+                while batch.len() < MAX_BATCH {
+                    match receiver.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                        Ok(trace) => batch.push(trace),
+                        Err(RecvTimeoutError::Timeout | RecvTimeoutError::Disconnected) => break,
+                    }
+                }
+                let queued = QUEUED.fetch_sub(batch.len(), Ordering::Relaxed) - batch.len();
+                sink_health::set_queue_depth(SINK, queued);
+                let body = export_request(&batch, &config).to_string().into_bytes();
+                if sink_health::run_guarded(SINK, || transport::post(&config.url, &config.headers, body)) {
+                    failures = 0;
+                } else {
+                    // Wait for the collector to come back; frames meanwhile fill the queue and then drop
+                    failures += 1;
+                    thread::sleep(sink_health::backoff(failures));
+                }
+            }
+        })
+        .map_err(|e| format!("Failed to start the OTLP exporter: {}", e))?;
+    Ok(())
+}
+
+/// Note that the current frame went through stage `name` from `started` until now
+pub fn stage(name: &'static str, started: Instant) {
+    if EXPORTER.get().is_none() {
+        return;
+    }
+    let end = Instant::now();
+    CURRENT.with(|spans| {
+        let mut spans = spans.borrow_mut();
+        // A thread that never finishes a frame must not collect spans forever
+        if spans.len() >= MAX_STAGES {
+            spans.clear();
+        }
+        spans.push(Span { name, start: started, end });
+    });
+}
+
+/// The current frame was published; send its stages on if it is sampled
+pub fn finish_frame(session_time: f32, clients: usize) {
+    let Some(exporter) = EXPORTER.get() else {
+        return;
+    };
+    let spans = CURRENT.with(|spans| std::mem::take(&mut *spans.borrow_mut()));
+    if spans.is_empty() || !FRAMES.fetch_add(1, Ordering::Relaxed).is_multiple_of(exporter.every) {
+        return;
+    }
+    // Dropped when the queue is full rather than holding up the telemetry thread
+    if exporter.sender.try_send(FrameTrace { spans, session_time, clients }).is_ok() {
+        QUEUED.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+// splitmix64 over a shared counter, seeded from the clock; ids only need to be unique
+fn random_id() -> u64 {
+    let mut z = ID_STATE.fetch_add(0x9E37_79B9_7F4A_7C15, Ordering::Relaxed).wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+fn unix_nanos(instant: Instant) -> String {
+    let Some(exporter) = EXPORTER.get() else {
+        return "0".to_string();
+    };
+    let (epoch, epoch_nanos) = exporter.epoch;
+    let nanos = match instant.checked_duration_since(epoch) {
+        Some(after) => epoch_nanos + after.as_nanos(),
+        None => epoch_nanos.saturating_sub(epoch.duration_since(instant).as_nanos()),
+    };
+    nanos.to_string()
+}
+
+fn attribute(key: &str, value: Value) -> Value {
+    let value = match value {
+        Value::String(text) => json!({ "stringValue": text }),
+        Value::Number(number) if number.is_f64() => json!({ "doubleValue": number }),
+        Value::Number(number) => json!({ "intValue": number.to_string() }),
+        other => json!({ "stringValue": other.to_string() }),
+    };
+    json!({ "key": key, "value": value })
+}
+
+// The OTLP/JSON ExportTraceServiceRequest for a batch of frames
+fn export_request(batch: &[FrameTrace], config: &OtlpConfig) -> Value {
+    const INTERNAL: u32 = 1;
+    let mut spans = Vec::new();
+    for trace in batch {
+        let (Some(first), Some(last)) = (trace.spans.iter().map(|span| span.start).min(), trace.spans.iter().map(|span| span.end).max()) else {
+            continue;
+        };
+        let trace_id = format!("{:016x}{:016x}", random_id(), random_id());
+        let root_id = format!("{:016x}", random_id());
+        spans.push(json!({
+            "traceId": trace_id,
+            "spanId": root_id,
+            "name": "frame",
+            "kind": INTERNAL,
+            "startTimeUnixNano": unix_nanos(first),
+            "endTimeUnixNano": unix_nanos(last),
+            "attributes": [
+                attribute("speedforge.session_time", json!(trace.session_time as f64)),
+                attribute("speedforge.clients", json!(trace.clients)),
+            ],
+        }));
+        for span in &trace.spans {
+            spans.push(json!({
+                "traceId": trace_id,
+                "spanId": format!("{:016x}", random_id()),
+                "parentSpanId": root_id,
+                "name": span.name,
+                "kind": INTERNAL,
+                "startTimeUnixNano": unix_nanos(span.start),
+                "endTimeUnixNano": unix_nanos(span.end),
+            }));
+        }
+    }
+    json!({
+        "resourceSpans": [{
+            "resource": {
+                "attributes": [
+                    attribute("service.name", json!(config.service_name)),
+                    attribute("service.version", json!(crate::version::VERSION)),
+                ],
+            },
+            "scopeSpans": [{
+                "scope": { "name": "speedforge.pipeline" },
+                "spans": spans,
+            }],
+        }],
+    })
+}
+
+#[cfg(feature = "otel")]
+mod transport {
+    use std::time::Duration;
+
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    pub fn check() -> Result<(), String> {
+        Ok(())
+    }
+
+    pub fn post(url: &str, headers: &[(String, String)], body: Vec<u8>) -> Result<(), String> {
+        let client = reqwest::blocking::Client::builder()
+            .timeout(TIMEOUT)
+            .build()
+            .map_err(|e| e.to_string())?;
+        let mut request = client.post(url).header("Content-Type", "application/json").body(body);
+        for (name, value) in headers {
+            request = request.header(name.as_str(), value.as_str());
+        }
+        let response = request.send().map_err(|e| format!("{}: {}", url, e))?;
+        if !response.status().is_success() {
+            return Err(format!("{} answered {}", url, response.status()));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "otel"))]
+mod transport {
+    pub fn check() -> Result<(), String> {
+        Err("Exporting traces requires building with the \"otel\" feature".to_string())
+    }
+
+    pub fn post(_url: &str, _headers: &[(String, String)], _body: Vec<u8>) -> Result<(), String> {
+        check()
+    }
+}
@@ -0,0 +1,127 @@
+//! Pit board messages from the race engineer to the driver.
+//!
+//! The engineer client sends `{"type":"pit_board","text":"P3 +2.1 L12 BOX"}`. The
+//! message is shown to the driver overlay as a `pit_board` event and stays in the
+//! frame's `pit_board` field until the overlay answers with
+//! `{"type":"pit_board_ack","id":N}`. `{"type":"get_pit_board"}` returns the latest
+//! messages with their acknowledgment state.
+//!
+//! There is no client identity yet, so any connected client may post or acknowledge.
+
+use crate::events::EventQueue;
+use crate::protocol;
+use crate::time_sync::server_time_ms;
+use crate::websocket_server::RequestHandler;
+use serde::{Serialize, Deserialize};
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+/// Longest message accepted; pit boards only fit a few short lines
+pub const MAX_MESSAGE_LEN: usize = 64;
+// Messages kept for get_pit_board
+const HISTORY_LEN: usize = 20;
+
+/// A message on the pit board
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PitBoardMessage {
+    pub id: u64,
+    pub text: String,
+    /// Server clock (see time_sync) when the message was posted
+    pub posted_ms: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub acknowledged_ms: Option<f64>,
+}
+
+#[derive(Default)]
+struct PitBoardState {
+    next_id: u64,
+    messages: VecDeque<PitBoardMessage>,
+}
+
+/// Shared pit board, written by client requests and read by the telemetry thread
+#[derive(Clone)]
+pub struct PitBoard {
+    state: Arc<Mutex<PitBoardState>>,
+    events: EventQueue,
+}
+
+impl PitBoard {
+    pub fn new(events: EventQueue) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(PitBoardState::default())),
+            events,
+        }
+    }
+
+    /// Post a message and return it
+    pub fn post(&self, text: &str) -> Result<PitBoardMessage, String> {
+        let text = text.trim();
+        if text.is_empty() {
+            return Err("Pit board message is empty".to_string());
+        }
+        if text.chars().count() > MAX_MESSAGE_LEN {
+            return Err(format!("Pit board message longer than {} characters", MAX_MESSAGE_LEN));
+        }
+
+        let mut state = self.state.lock().map_err(|_| "Pit board unavailable".to_string())?;
+        state.next_id += 1;
+        let message = PitBoardMessage {
+            id: state.next_id,
+            text: text.to_string(),
+            posted_ms: server_time_ms(),
+            acknowledged_ms: None,
+        };
+        state.messages.push_back(message.clone());
+        if state.messages.len() > HISTORY_LEN {
+            state.messages.pop_front();
+        }
+        drop(state);
+
+        self.events.push("pit_board", json!({ "id": message.id, "text": message.text }));
+        Ok(message)
+    }
+
+    /// Mark a message as seen by the driver
+    pub fn acknowledge(&self, id: u64) -> Result<PitBoardMessage, String> {
+        let mut state = self.state.lock().map_err(|_| "Pit board unavailable".to_string())?;
+        let message = state
+            .messages
+            .iter_mut()
+            .find(|message| message.id == id)
+            .ok_or_else(|| format!("No pit board message with id {}", id))?;
+        if message.acknowledged_ms.is_none() {
+            message.acknowledged_ms = Some(server_time_ms());
+            self.events.push("pit_board_ack", json!({ "id": id }));
+        }
+        Ok(message.clone())
+    }
+
+    /// Latest message while the driver hasn't acknowledged it
+    pub fn current(&self) -> Option<PitBoardMessage> {
+        let state = self.state.lock().ok()?;
+        state.messages.back().filter(|message| message.acknowledged_ms.is_none()).cloned()
+    }
+
+    fn history(&self) -> Vec<PitBoardMessage> {
+        self.state.lock().map(|state| state.messages.iter().cloned().collect()).unwrap_or_default()
+    }
+}
+
+impl RequestHandler for PitBoard {
+    fn handle(&self, request: &Value) -> Value {
+        let result = match request.get("type").and_then(Value::as_str) {
+            Some("pit_board") => {
+                let text = request.get("text").and_then(Value::as_str).unwrap_or_default();
+                self.post(text).map(|message| json!({ "type": "pit_board_posted", "message": message }))
+            },
+            Some("pit_board_ack") => match request.get("id").and_then(Value::as_u64) {
+                Some(id) => self.acknowledge(id).map(|message| json!({ "type": "pit_board_acknowledged", "message": message })),
+                None => Err("Missing message id".to_string()),
+            },
+            _ => Ok(json!({ "type": "pit_board_history", "messages": self.history() })),
+        };
+        let request_type = request.get("type").and_then(Value::as_str);
+        result.unwrap_or_else(|e| protocol::error(protocol::ERROR_FAILED, e, request_type))
+    }
+}
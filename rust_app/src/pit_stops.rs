@@ -0,0 +1,162 @@
+use crate::consistency::lap_time_spread;
+use crate::telemetry_fields::TelemetryData;
+use std::collections::VecDeque;
+
+/// Pit loss assumed until one has been measured
+pub const DEFAULT_PIT_LOSS_S: f32 = 25.0;
+
+// Clean lap times kept per car for the pace reference
+const MAX_CLEAN_LAPS: usize = 10;
+// Measured losses outside this range are tows, drive-throughs stacked with repairs, etc.
+const MIN_PLAUSIBLE_LOSS_S: f32 = 5.0;
+const MAX_PLAUSIBLE_LOSS_S: f32 = 120.0;
+// Measured losses kept for the session estimate
+const MAX_LOSS_SAMPLES: usize = 20;
+
+/// Pit state of a single car
+#[derive(Clone, Debug, Default)]
+pub struct CarPitState {
+    pub on_pit_road: bool,
+    pub stops: u32,
+    /// SessionTime of the most recent pit road entry
+    pub last_entry_time: Option<f32>,
+    /// Laps completed when the car last entered pit road
+    pub last_stop_lap: Option<i32>,
+    /// Laps completed when the car last left pit road
+    pub last_exit_lap: Option<i32>,
+    touched_pit_this_lap: bool,
+    last_lap_completed: Option<i32>,
+    last_lap_time: f32,
+    // Pit flags of completed laps waiting for their lap time to be published
+    completed_pit_flags: VecDeque<bool>,
+    clean_laps: VecDeque<f32>,
+    // Lap times of laps that touched pit road since the last clean lap
+    pending_pit_laps: Vec<f32>,
+}
+
+impl CarPitState {
+    /// Typical green flag lap time from recent laps that didn't touch pit road
+    pub fn pace(&self) -> Option<f32> {
+        let laps: Vec<f32> = self.clean_laps.iter().copied().collect();
+        lap_time_spread(&laps).map(|(mean, _, _)| mean)
+    }
+
+    /// Mean and standard deviation of recent laps that didn't touch pit road
+    pub fn pace_spread(&self) -> Option<(f32, f32)> {
+        let laps: Vec<f32> = self.clean_laps.iter().copied().collect();
+        lap_time_spread(&laps).map(|(mean, std_dev, _)| (mean, std_dev))
+    }
+
+    /// Laps completed since the car last left pit road
+    pub fn laps_since_stop(&self, laps_completed: i32) -> Option<i32> {
+        self.last_exit_lap.map(|exit| laps_completed - exit)
+    }
+}
+
+/// Follows pit stops of every car and measures how much time a stop costs
+pub struct PitTracker {
+    cars: Vec<CarPitState>,
+    loss_samples: VecDeque<f32>,
+    default_loss: f32,
+}
+
+impl PitTracker {
+    pub fn new(default_loss: f32) -> Self {
+        Self {
+            cars: Vec::new(),
+            loss_samples: VecDeque::new(),
+            default_loss,
+        }
+    }
+
+    pub fn car(&self, car_idx: usize) -> Option<&CarPitState> {
+        self.cars.get(car_idx)
+    }
+
+    pub fn cars(&self) -> &[CarPitState] {
+        &self.cars
+    }
+
+    /// Median measured pit loss, or the configured default before any stop was measured
+    pub fn pit_loss(&self) -> f32 {
+        if self.loss_samples.is_empty() {
+            return self.default_loss;
+        }
+        let mut samples: Vec<f32> = self.loss_samples.iter().copied().collect();
+        samples.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        samples[samples.len() / 2]
+    }
+
+    /// Whether the pit loss comes from stops seen this session
+    pub fn is_measured(&self) -> bool {
+        !self.loss_samples.is_empty()
+    }
+
+    /// Feed a frame. Returns the cars that entered pit road in this frame
+    pub fn update(&mut self, data: &TelemetryData) -> Vec<usize> {
+        if data.race_clock.session_reset {
+            self.cars.clear();
+        }
+
+        let (Some(on_pit_road), Some(laps_completed)) = (&data.CarIdxOnPitRoad, &data.CarIdxLapCompleted) else {
+            return Vec::new();
+        };
+        if self.cars.len() < on_pit_road.len() {
+            self.cars.resize_with(on_pit_road.len(), CarPitState::default);
+        }
+
+        let mut entries = Vec::new();
+        for (idx, car) in self.cars.iter_mut().enumerate() {
+            let in_pits = on_pit_road.get(idx).copied().unwrap_or(false);
+            let lap = laps_completed.get(idx).copied().unwrap_or(-1);
+
+            if in_pits && !car.on_pit_road {
+                car.stops += 1;
+                car.last_entry_time = Some(data.SessionTime);
+                car.last_stop_lap = Some(lap);
+                entries.push(idx);
+            } else if !in_pits && car.on_pit_road {
+                car.last_exit_lap = Some(lap);
+            }
+            car.on_pit_road = in_pits;
+
+            // Remember whether each completed lap touched pit road until its time arrives
+            if car.last_lap_completed.is_some_and(|last| lap > last) {
+                car.completed_pit_flags.push_back(car.touched_pit_this_lap);
+                car.touched_pit_this_lap = false;
+            }
+            car.last_lap_completed = Some(lap);
+            car.touched_pit_this_lap |= in_pits;
+
+            let lap_time = data.CarIdxLastLapTime.as_ref().and_then(|t| t.get(idx)).copied().unwrap_or(-1.0);
+            if lap_time > 0.0 && lap_time != car.last_lap_time {
+                let touched_pit = car.completed_pit_flags.pop_front().unwrap_or(false);
+                if touched_pit {
+                    car.pending_pit_laps.push(lap_time);
+                } else {
+                    // First clean lap after a stop: the stop cost the pit laps minus normal pace
+                    if !car.pending_pit_laps.is_empty() {
+                        if let Some(pace) = car.pace() {
+                            let loss = car.pending_pit_laps.iter().sum::<f32>() - pace * car.pending_pit_laps.len() as f32;
+                            if (MIN_PLAUSIBLE_LOSS_S..=MAX_PLAUSIBLE_LOSS_S).contains(&loss) {
+                                self.loss_samples.push_back(loss);
+                                if self.loss_samples.len() > MAX_LOSS_SAMPLES {
+                                    self.loss_samples.pop_front();
+                                }
+                            }
+                        }
+                        car.pending_pit_laps.clear();
+                    }
+                    car.clean_laps.push_back(lap_time);
+                    if car.clean_laps.len() > MAX_CLEAN_LAPS {
+                        car.clean_laps.pop_front();
+                    }
+                }
+            }
+            if lap_time > 0.0 {
+                car.last_lap_time = lap_time;
+            }
+        }
+        entries
+    }
+}
@@ -0,0 +1,301 @@
+use serde::ser::{self, Serialize, Serializer};
+use serde_json::Value;
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+/// Rounding rules applied to floats when telemetry is serialized for clients.
+///
+/// Rounding happens in a serializer layer (`Rounded`), so the `TelemetryData` itself is
+/// never modified and derived calculations keep working with full precision. The default
+/// rounds nothing.
+#[derive(Clone, Debug, Default)]
+pub struct PrecisionRules {
+    /// Decimals used for any float without a more specific rule (None = leave untouched)
+    pub default_decimals: Option<u32>,
+    /// Decimals for specific field names (exact match)
+    pub fields: HashMap<String, u32>,
+    /// Decimals for fields whose name ends with the given suffix
+    pub suffixes: Vec<(String, u32)>,
+}
+
+impl PrecisionRules {
+    /// Round floats to `decimals`, keeping more resolution where clients need it
+    pub fn standard(decimals: u32) -> Self {
+        let mut fields = HashMap::new();
+        fields.insert("lat".to_string(), 5);
+        fields.insert("lon".to_string(), 5);
+        // Lap distance percentages need more resolution than other percentages so that
+        // track maps don't visibly step on long tracks
+        fields.insert("lap_dist_pct".to_string(), 5);
+        fields.insert("CarIdxLapDistPct".to_string(), 5);
+        fields.insert("lap_dist_pct_per_sec".to_string(), 6);
+        fields.insert("SessionTime".to_string(), 3);
+
+        Self {
+            default_decimals: Some(decimals),
+            fields,
+            suffixes: vec![("_pct".to_string(), 3)],
+        }
+    }
+
+    fn decimals_for(&self, key: Option<&str>) -> Option<u32> {
+        if let Some(key) = key {
+            if let Some(&decimals) = self.fields.get(key) {
+                return Some(decimals);
+            }
+            for (suffix, decimals) in &self.suffixes {
+                if key.ends_with(suffix.as_str()) {
+                    return Some(*decimals);
+                }
+            }
+        }
+        self.default_decimals
+    }
+
+    /// `value`, serializing with the rounding rules applied
+    pub fn rounded<'a, T: ?Sized + Serialize>(&'a self, value: &'a T) -> Rounded<'a, T> {
+        Rounded { rules: self, value, key: None }
+    }
+
+    /// Serialize a value to a JSON string with the rounding rules applied
+    pub fn to_string<T: ?Sized + Serialize>(&self, value: &T) -> serde_json::Result<String> {
+        serde_json::to_string(&self.rounded(value))
+    }
+
+    /// Serialize a value to a JSON value with the rounding rules applied
+    pub fn to_value<T: ?Sized + Serialize>(&self, value: &T) -> serde_json::Result<Value> {
+        serde_json::to_value(self.rounded(value))
+    }
+}
+
+/// Round a float to a fixed number of decimals
+pub fn round_to(value: f64, decimals: u32) -> f64 {
+    let factor = 10f64.powi(decimals as i32);
+    let rounded = (value * factor).round() / factor;
+    // Avoid emitting "-0.0" for tiny negative values
+    if rounded == 0.0 { 0.0 } else { rounded }
+}
+
+/// A value serialized through `RoundingSerializer`; `key` is the name of the field
+/// holding it, which picks the rule. Array elements inherit the key of their array
+pub struct Rounded<'a, T: ?Sized> {
+    rules: &'a PrecisionRules,
+    value: &'a T,
+    key: Option<&'a str>,
+}
+
+impl<T: ?Sized + Serialize> Serialize for Rounded<'_, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.value.serialize(RoundingSerializer { inner: serializer, rules: self.rules, key: self.key })
+    }
+}
+
+/// Serializer passing everything on to `inner`, with floats rounded
+struct RoundingSerializer<'a, S> {
+    inner: S,
+    rules: &'a PrecisionRules,
+    key: Option<&'a str>,
+}
+
+/// Sequences, maps and structs of a `RoundingSerializer`
+struct Compound<'a, C> {
+    inner: C,
+    rules: &'a PrecisionRules,
+    // Key for the next value: the parent's for sequences, the last map key for maps
+    key: Option<Cow<'a, str>>,
+}
+
+impl<'a, C> Compound<'a, C> {
+    fn new(inner: C, rules: &'a PrecisionRules, key: Option<&'a str>) -> Self {
+        Self { inner, rules, key: key.map(Cow::Borrowed) }
+    }
+}
+
+macro_rules! forward {
+    ($($method:ident($($arg:ident: $ty:ty),*);)*) => {
+        $(fn $method(self, $($arg: $ty),*) -> Result<S::Ok, S::Error> {
+            self.inner.$method($($arg),*)
+        })*
+    };
+}
+
+impl<'a, S: Serializer> Serializer for RoundingSerializer<'a, S> {
+    type Ok = S::Ok;
+    type Error = S::Error;
+    type SerializeSeq = Compound<'a, S::SerializeSeq>;
+    type SerializeTuple = Compound<'a, S::SerializeTuple>;
+    type SerializeTupleStruct = Compound<'a, S::SerializeTupleStruct>;
+    type SerializeTupleVariant = Compound<'a, S::SerializeTupleVariant>;
+    type SerializeMap = Compound<'a, S::SerializeMap>;
+    type SerializeStruct = Compound<'a, S::SerializeStruct>;
+    type SerializeStructVariant = Compound<'a, S::SerializeStructVariant>;
+
+    forward! {
+        serialize_bool(v: bool);
+        serialize_i8(v: i8);
+        serialize_i16(v: i16);
+        serialize_i32(v: i32);
+        serialize_i64(v: i64);
+        serialize_i128(v: i128);
+        serialize_u8(v: u8);
+        serialize_u16(v: u16);
+        serialize_u32(v: u32);
+        serialize_u64(v: u64);
+        serialize_u128(v: u128);
+        serialize_char(v: char);
+        serialize_str(v: &str);
+        serialize_bytes(v: &[u8]);
+        serialize_none();
+        serialize_unit();
+        serialize_unit_struct(name: &'static str);
+        serialize_unit_variant(name: &'static str, index: u32, variant: &'static str);
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<S::Ok, S::Error> {
+        match self.rules.decimals_for(self.key) {
+            Some(decimals) if v.is_finite() => self.inner.serialize_f64(round_to(v as f64, decimals)),
+            _ => self.inner.serialize_f32(v),
+        }
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<S::Ok, S::Error> {
+        match self.rules.decimals_for(self.key) {
+            Some(decimals) if v.is_finite() => self.inner.serialize_f64(round_to(v, decimals)),
+            _ => self.inner.serialize_f64(v),
+        }
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_some(&Rounded { rules: self.rules, value, key: self.key })
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(self, name: &'static str, value: &T) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_newtype_struct(name, &Rounded { rules: self.rules, value, key: self.key })
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        name: &'static str,
+        index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_newtype_variant(name, index, variant, &Rounded { rules: self.rules, value, key: self.key })
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, S::Error> {
+        self.inner.serialize_seq(len).map(|inner| Compound::new(inner, self.rules, self.key))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, S::Error> {
+        self.inner.serialize_tuple(len).map(|inner| Compound::new(inner, self.rules, self.key))
+    }
+
+    fn serialize_tuple_struct(self, name: &'static str, len: usize) -> Result<Self::SerializeTupleStruct, S::Error> {
+        self.inner.serialize_tuple_struct(name, len).map(|inner| Compound::new(inner, self.rules, self.key))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        name: &'static str,
+        index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, S::Error> {
+        self.inner.serialize_tuple_variant(name, index, variant, len).map(|inner| Compound::new(inner, self.rules, self.key))
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, S::Error> {
+        self.inner.serialize_map(len).map(|inner| Compound::new(inner, self.rules, self.key))
+    }
+
+    fn serialize_struct(self, name: &'static str, len: usize) -> Result<Self::SerializeStruct, S::Error> {
+        self.inner.serialize_struct(name, len).map(|inner| Compound::new(inner, self.rules, self.key))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant, S::Error> {
+        self.inner.serialize_struct_variant(name, index, variant, len).map(|inner| Compound::new(inner, self.rules, self.key))
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.inner.is_human_readable()
+    }
+}
+
+macro_rules! sequence {
+    ($($trait:ident::$method:ident;)*) => {
+        $(impl<C: ser::$trait> ser::$trait for Compound<'_, C> {
+            type Ok = C::Ok;
+            type Error = C::Error;
+
+            fn $method<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), C::Error> {
+                self.inner.$method(&Rounded { rules: self.rules, value, key: self.key.as_deref() })
+            }
+
+            fn end(self) -> Result<C::Ok, C::Error> {
+                self.inner.end()
+            }
+        })*
+    };
+}
+
+sequence! {
+    SerializeSeq::serialize_element;
+    SerializeTuple::serialize_element;
+    SerializeTupleStruct::serialize_field;
+    SerializeTupleVariant::serialize_field;
+}
+
+impl<C: ser::SerializeMap> ser::SerializeMap for Compound<'_, C> {
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), C::Error> {
+        // Only string keys name fields; values under other keys use the fallback
+        self.key = match serde_json::to_value(key) {
+            Ok(Value::String(name)) => Some(Cow::Owned(name)),
+            _ => None,
+        };
+        self.inner.serialize_key(key)
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), C::Error> {
+        self.inner.serialize_value(&Rounded { rules: self.rules, value, key: self.key.as_deref() })
+    }
+
+    fn end(self) -> Result<C::Ok, C::Error> {
+        self.inner.end()
+    }
+}
+
+macro_rules! fields {
+    ($($trait:ident;)*) => {
+        $(impl<C: ser::$trait> ser::$trait for Compound<'_, C> {
+            type Ok = C::Ok;
+            type Error = C::Error;
+
+            fn serialize_field<T: ?Sized + Serialize>(&mut self, name: &'static str, value: &T) -> Result<(), C::Error> {
+                self.inner.serialize_field(name, &Rounded { rules: self.rules, value, key: Some(name) })
+            }
+
+            fn skip_field(&mut self, name: &'static str) -> Result<(), C::Error> {
+                self.inner.skip_field(name)
+            }
+
+            fn end(self) -> Result<C::Ok, C::Error> {
+                self.inner.end()
+            }
+        })*
+    };
+}
+
+fields! {
+    SerializeStruct;
+    SerializeStructVariant;
+}
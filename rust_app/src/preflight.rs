@@ -0,0 +1,273 @@
+//! `speedforge check`: validate a rig's setup without connecting to the sim.
+//!
+//! Reads the config file and command line the same way the server does, then checks the
+//! listen address and port, the connection limits, the files the server would load (corner
+//! database, track metadata cache, scenario script, car assets, dead-bands, channel presets),
+//! hotkey and button mappings, and that output folders can be written. Every problem is
+//! printed with what to change; the exit code is 1 when any check failed.
+
+use crate::corners::{CornerDatabase, CORNERS_PATH};
+use crate::metadata::{MetadataCache, CACHE_PATH};
+use crate::{auto_export, car_info, change_detection, channel_presets, config, hotkeys, input_bridge, load_shedding, log_file, pipeline_trace, profiles, recorder, retention, scenario, thread_tuning};
+use crate::{arg_value, instance_path, parse_bind_addresses, setup_wizard, DEFAULT_PORT};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Default)]
+struct Report {
+    errors: usize,
+    warnings: usize,
+}
+
+impl Report {
+    fn ok(&mut self, message: impl AsRef<str>) {
+        println!("  ok     {}", message.as_ref());
+    }
+
+    fn warn(&mut self, message: impl AsRef<str>) {
+        self.warnings += 1;
+        println!("  WARN   {}", message.as_ref());
+    }
+
+    fn error(&mut self, message: impl AsRef<str>) {
+        self.errors += 1;
+        println!("  ERROR  {}", message.as_ref());
+    }
+
+    fn result<T>(&mut self, result: Result<T, String>, ok: impl FnOnce(T) -> String) {
+        match result {
+            Ok(value) => self.ok(ok(value)),
+            Err(e) => self.error(e),
+        }
+    }
+}
+
+// An existing folder must take a file; a missing one is created at startup, so its nearest
+// existing parent has to be a folder
+fn check_output_dir(report: &mut Report, flag: &str, dir: &Path) {
+    if dir.is_dir() {
+        let probe = dir.join(format!(".speedforge_check_{}", std::process::id()));
+        match fs::write(&probe, b"") {
+            Ok(()) => {
+                let _ = fs::remove_file(&probe);
+                report.ok(format!("{} {} is writable", flag, dir.display()));
+            },
+            Err(e) => report.error(format!("{} {} is not writable ({}); pick another folder or fix its permissions", flag, dir.display(), e)),
+        }
+        return;
+    }
+    if dir.exists() {
+        report.error(format!("{} {} is a file, not a folder", flag, dir.display()));
+        return;
+    }
+    let parent = dir.ancestors().skip(1).find(|parent| parent.as_os_str().is_empty() || parent.exists());
+    match parent {
+        Some(parent) if parent.as_os_str().is_empty() || parent.is_dir() => {
+            report.ok(format!("{} {} will be created", flag, dir.display()));
+        },
+        _ => report.error(format!("{} {} can't be created; a parent is not a folder", flag, dir.display())),
+    }
+}
+
+fn check_server(report: &mut Report, args: &[String]) {
+    println!("Server");
+    let port = match arg_value(args, "--port").map(|value| value.parse::<u16>()) {
+        Some(Ok(port)) => port,
+        Some(Err(_)) => {
+            report.error("--port must be a number between 0 and 65535");
+            return;
+        },
+        None => DEFAULT_PORT,
+    };
+    let bind = arg_value(args, "--bind").unwrap_or_else(|| "0.0.0.0".to_string());
+    match parse_bind_addresses(&bind, port) {
+        Ok(addresses) => {
+            for address in addresses {
+                if setup_wizard::port_is_free(address.ip(), address.port()) {
+                    report.ok(format!("{} is free", address));
+                } else {
+                    report.warn(format!("{} is in use; stop the program using it (or a running speedforge) or change --port", address));
+                }
+            }
+        },
+        Err(e) => report.error(format!("{}; use IP addresses such as 0.0.0.0, 127.0.0.1 or ::", e)),
+    }
+    report.result(crate::parse_connection_limits(args), |_| "connection limits".to_string());
+    report.result(crate::parse_sample_interval(args), |interval| format!("{:.1} samples per second", 1.0 / interval.as_secs_f32()));
+    if let Err(e) = crate::parse_precision(args) {
+        report.error(e);
+    }
+
+    for flag in ["--telemetry-cores", "--runtime-cores"] {
+        if let Some(value) = arg_value(args, flag) {
+            report.result(thread_tuning::parse_core_list(&value), |cores| format!("{} {:?}", flag, cores));
+        }
+    }
+    if let Some(value) = arg_value(args, "--telemetry-priority") {
+        match thread_tuning::ThreadPriority::parse(&value) {
+            Some(_) => report.ok(format!("--telemetry-priority {}", value)),
+            None => report.error(format!("--telemetry-priority '{}' is not one of below_normal, normal, above_normal, high", value)),
+        }
+    }
+}
+
+fn check_files(report: &mut Report, args: &[String], instance: Option<&str>) {
+    println!("Files");
+    if Path::new(CORNERS_PATH).exists() {
+        match CornerDatabase::try_load(CORNERS_PATH) {
+            Ok(corners) => {
+                report.ok(format!("{}: corners for {} tracks", CORNERS_PATH, corners.track_count()));
+                for problem in corners.problems() {
+                    report.warn(format!("{} {}", CORNERS_PATH, problem));
+                }
+            },
+            Err(e) => report.error(format!("{}; fix the JSON or remove the file", e)),
+        }
+    } else {
+        report.ok(format!("no {}, corner names are off", CORNERS_PATH));
+    }
+
+    if Path::new(CACHE_PATH).exists() {
+        match MetadataCache::try_load(CACHE_PATH) {
+            Ok(cache) => {
+                let (tracks, cars) = cache.counts();
+                report.ok(format!("{}: {} tracks, {} cars", CACHE_PATH, tracks, cars));
+            },
+            Err(e) => report.error(format!("{}; delete it and it is rebuilt from the next sessions", e)),
+        }
+    }
+
+    if let Some(path) = arg_value(args, "--scenario") {
+        report.result(scenario::Scenario::load(Path::new(&path)).map_err(|e| format!("--scenario {}: {}", path, e)), |_| format!("--scenario {}", path));
+    } else if arg_value(args, "--sim").as_deref() == Some("scenario") {
+        report.error("--sim scenario needs --scenario <file>");
+    }
+    if let Some(path) = arg_value(args, "--replay") {
+        if Path::new(&path).is_file() {
+            report.ok(format!("--replay {}", path));
+        } else {
+            report.error(format!("--replay {} doesn't exist", path));
+        }
+    } else if arg_value(args, "--sim").as_deref() == Some("replay") {
+        report.error("--sim replay needs --replay <file>");
+    }
+    if let Some(path) = arg_value(args, "--car-assets") {
+        report.result(car_info::CarAssets::load(Path::new(&path)), |assets| format!("--car-assets {}: {} cars", path, assets.len()));
+    }
+    if let Some(path) = arg_value(args, "--deadbands") {
+        let bands = change_detection::DeadBands::load(Path::new(&path)).map_err(|e| format!("--deadbands {}: {}", path, e));
+        report.result(bands, |bands| format!("--deadbands {}: {} channels", path, bands.channels.len()));
+    }
+    if let Some(path) = arg_value(args, "--channel-presets") {
+        let presets = channel_presets::ChannelPresets::load(Path::new(&path)).map_err(|e| format!("--channel-presets {}: {}", path, e));
+        report.result(presets, |presets| format!("--channel-presets {}: {}", path, presets.names().join(", ")));
+    }
+    if let Some(path) = arg_value(args, "--priority-config") {
+        let priorities = load_shedding::PriorityConfig::load(Path::new(&path)).map_err(|e| format!("--priority-config {}: {}", path, e));
+        report.result(priorities, |priorities| {
+            format!("--priority-config {}: {} analytics, {} cars, {} essential", path, priorities.analytics.len(), priorities.cars.len(), priorities.essential.len())
+        });
+    }
+    match pipeline_trace::OtlpConfig::from_args(args) {
+        Some(Ok(config)) => report.result(pipeline_trace::available().map(|()| config), |config| {
+            format!("OTLP traces to {}: {:.0}% of frames", config.url, config.sample_ratio * 100.0)
+        }),
+        Some(Err(e)) => report.error(e),
+        None => {},
+    }
+    if let Some(mapping) = arg_value(args, "--hotkeys") {
+        report.result(hotkeys::parse_mapping(&mapping).map_err(|e| format!("--hotkeys: {}", e)), |bindings| format!("--hotkeys: {} bindings", bindings.len()));
+    }
+    if let Some(mapping) = arg_value(args, "--marker-buttons") {
+        let parsed = input_bridge::InputBridgeConfig::parse(&mapping, input_bridge::DEFAULT_DEBOUNCE_MS).map_err(|e| format!("--marker-buttons: {}", e));
+        report.result(parsed, |_| "--marker-buttons".to_string());
+    }
+    let driver_db = arg_value(args, "--driver-db").unwrap_or_else(|| instance_path(instance, crate::driver_db::DEFAULT_DRIVER_DB_PATH));
+    if Path::new(&driver_db).exists() {
+        let parsed = fs::read_to_string(&driver_db)
+            .map_err(|e| e.to_string())
+            .and_then(|text| serde_json::from_str::<serde_json::Value>(&text).map_err(|e| e.to_string()));
+        match parsed {
+            Ok(_) => report.ok(format!("driver database {}", driver_db)),
+            Err(e) => report.error(format!("driver database {}: {}; fix or remove it, it starts empty otherwise", driver_db, e)),
+        }
+    }
+}
+
+fn check_folders(report: &mut Report, args: &[String], instance: Option<&str>) {
+    println!("Folders");
+    let sessions_dir = arg_value(args, "--sessions-dir").map(PathBuf::from);
+    if let Some(dir) = &sessions_dir {
+        check_output_dir(report, "--sessions-dir", dir);
+    }
+    if sessions_dir.is_none() && args.iter().any(|arg| arg == "--record") {
+        check_output_dir(report, "recording folder", Path::new(&instance_path(instance, recorder::DEFAULT_DIR)));
+    }
+    if let Some(dir) = arg_value(args, "--ibt-dir") {
+        if Path::new(&dir).is_dir() {
+            report.ok(format!("--ibt-dir {}", dir));
+        } else {
+            report.error(format!("--ibt-dir {} doesn't exist; point it at Documents/iRacing/telemetry", dir));
+        }
+    }
+    if let Err(e) = retention::RetentionPolicy::from_args(args) {
+        report.error(e);
+    }
+    if !args.iter().any(|arg| arg == "--no-log-file") {
+        let log_dir = arg_value(args, "--log-dir").map(PathBuf::from).or_else(|| sessions_dir.map(|dir| dir.join("logs")));
+        if let Some(dir) = log_dir {
+            check_output_dir(report, "log folder", &dir);
+        }
+        if let Some(value) = arg_value(args, "--log-rotate").filter(|value| log_file::Rotation::parse(value).is_none()) {
+            report.error(format!("--log-rotate '{}' must be daily or size", value));
+        }
+    }
+    if let Some(dir) = arg_value(args, "--highlights-dir") {
+        check_output_dir(report, "--highlights-dir", Path::new(&dir));
+    }
+    match auto_export::ExportConfig::from_args(args) {
+        Ok(Some(export)) => {
+            check_output_dir(report, "export folder", &export.output_dir);
+            if let Some(storage) = &export.upload {
+                report.ok(format!("exports are uploaded to {}", storage.describe()));
+            }
+        },
+        Ok(None) => {},
+        Err(e) => report.error(e),
+    }
+    let profiles_dir = arg_value(args, "--profiles-dir").unwrap_or_else(|| instance_path(instance, profiles::DEFAULT_PROFILES_DIR));
+    check_output_dir(report, "profiles folder", Path::new(&profiles_dir));
+}
+
+/// Run all checks; returns the process exit code
+pub fn run_cli(args: &[String]) -> i32 {
+    let mut report = Report::default();
+    println!("Configuration");
+    let args = match config::with_config_file(args.to_vec()) {
+        Ok(merged) => {
+            if arg_value(args, "--config").is_some() || Path::new(config::DEFAULT_CONFIG_FILE).exists() {
+                report.ok(format!("config file read, {} options", merged.len() - args.len()));
+            } else {
+                report.ok(format!("no config file ({} not found, run `speedforge init` to create one)", config::DEFAULT_CONFIG_FILE));
+            }
+            merged
+        },
+        Err(e) => {
+            report.error(e);
+            args.to_vec()
+        },
+    };
+    let instance = arg_value(&args, "--instance");
+
+    check_server(&mut report, &args);
+    check_files(&mut report, &args, instance.as_deref());
+    check_folders(&mut report, &args, instance.as_deref());
+    println!();
+    if report.errors > 0 {
+        println!("{} errors, {} warnings", report.errors, report.warnings);
+        1
+    } else {
+        println!("Setup looks good ({} warnings)", report.warnings);
+        0
+    }
+}
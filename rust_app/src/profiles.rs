@@ -0,0 +1,125 @@
+//! Named JSON blobs (overlay layouts, preferences) shared between clients.
+//!
+//! `GET /profiles` lists stored names, `GET /profiles/{name}` returns a profile and
+//! `PUT /profiles/{name}` stores one. Each profile is a `{name}.json` file in the
+//! profiles directory.
+
+use crate::http_api::{HttpHandler, HttpRequest, HttpResponse};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Directory used when none is configured
+pub const DEFAULT_PROFILES_DIR: &str = "profiles";
+
+/// File backed profile storage
+pub struct ProfileStore {
+    dir: PathBuf,
+}
+
+impl ProfileStore {
+    pub fn new(dir: impl AsRef<Path>) -> Self {
+        Self { dir: dir.as_ref().to_path_buf() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    // Names end up as file names, so keep them to a safe character set
+    fn is_valid_name(name: &str) -> bool {
+        !name.is_empty()
+            && name.len() <= 64
+            && !name.starts_with('.')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+    }
+
+    fn path_for(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", name))
+    }
+
+    /// Names of all stored profiles, sorted
+    pub fn list(&self) -> Result<Vec<String>, String> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(format!("Failed to read {}: {}", self.dir.display(), e)),
+        };
+        let mut names: Vec<String> = entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let path = entry.path();
+                if path.extension()? != "json" {
+                    return None;
+                }
+                path.file_stem()?.to_str().map(str::to_string)
+            })
+            .collect();
+        names.sort();
+        Ok(names)
+    }
+
+    /// Raw JSON of a profile, None when it doesn't exist
+    pub fn get(&self, name: &str) -> Result<Option<Vec<u8>>, String> {
+        if !Self::is_valid_name(name) {
+            return Err(format!("Invalid profile name: {}", name));
+        }
+        match fs::read(self.path_for(name)) {
+            Ok(contents) => Ok(Some(contents)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(format!("Failed to read profile {}: {}", name, e)),
+        }
+    }
+
+    /// Store a profile. Returns true when it replaced an existing one
+    pub fn put(&self, name: &str, value: &serde_json::Value) -> Result<bool, String> {
+        if !Self::is_valid_name(name) {
+            return Err(format!("Invalid profile name: {}", name));
+        }
+        fs::create_dir_all(&self.dir)
+            .map_err(|e| format!("Failed to create {}: {}", self.dir.display(), e))?;
+
+        let path = self.path_for(name);
+        let existed = path.exists();
+        let contents = serde_json::to_vec_pretty(value)
+            .map_err(|e| format!("Failed to serialize profile {}: {}", name, e))?;
+
+        // Write to a temporary file first so readers never see a half written profile
+        let tmp_path = self.dir.join(format!(".{}.json.tmp", name));
+        fs::write(&tmp_path, contents)
+            .and_then(|_| fs::rename(&tmp_path, &path))
+            .map_err(|e| format!("Failed to write profile {}: {}", name, e))?;
+        Ok(existed)
+    }
+}
+
+impl HttpHandler for ProfileStore {
+    fn handle(&self, request: &HttpRequest) -> Option<HttpResponse> {
+        let segments = request.segments();
+        if segments.first() != Some(&"profiles") {
+            return None;
+        }
+
+        let response = match (request.method.as_str(), &segments[1..]) {
+            ("GET", []) => match self.list() {
+                Ok(names) => HttpResponse::json(200, &serde_json::json!({ "profiles": names })),
+                Err(e) => HttpResponse::error(500, &e),
+            },
+            ("GET", [name]) => match self.get(name) {
+                Ok(Some(contents)) => HttpResponse::raw_json(200, contents),
+                Ok(None) => HttpResponse::error(404, &format!("No profile named {}", name)),
+                Err(e) => HttpResponse::error(400, &e),
+            },
+            ("PUT", [name]) => match serde_json::from_slice::<serde_json::Value>(&request.body) {
+                Ok(value) => match self.put(name, &value) {
+                    Ok(true) => HttpResponse::no_content(),
+                    Ok(false) => HttpResponse::json(201, &serde_json::json!({ "name": name })),
+                    Err(e) => HttpResponse::error(400, &e),
+                },
+                Err(e) => HttpResponse::error(400, &format!("Profile body must be JSON: {}", e)),
+            },
+            (_, [] | [_]) => HttpResponse::error(405, "Method not allowed"),
+            _ => HttpResponse::error(404, "Not found"),
+        };
+        Some(response)
+    }
+}
@@ -0,0 +1,273 @@
+//! Connection handshake and protocol versioning.
+//!
+//! On connect the server sends `hello` with the protocol and frame schema versions and
+//! what it offers (topics, encodings, commands, channel presets). A client may answer
+//! with its own `hello` naming the options it wants:
+//!
+//! ```json
+//! {"type":"hello","protocol_version":1,"schema_version":3,"topics":["telemetry","session_info"],"encoding":"json","channel_preset":"simhub","delta":true,"groups":["timing"],"rate_hz":10}
+//! ```
+//!
+//! and gets `welcome` with the options in effect, or an `error`. Clients that never send
+//! `hello` keep the defaults (every topic, JSON unless the URL asked for another encoding,
+//! plain full frames). Errors always have the same shape:
+//! `{"type":"error","code":"...","message":"...","request":"<type>"}`.
+//!
+//! A command may carry a `request_id` (any JSON value); the reply to it, success or
+//! error, carries the same `request_id` so clients can match replies to commands.
+
+use crate::channel_presets::ChannelPresets;
+use crate::codec;
+use crate::delta;
+use crate::field_groups;
+use crate::frame_schema::{MIN_SCHEMA_VERSION, SCHEMA_VERSION};
+use crate::version;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+/// Bumped on any change clients have to know about; older clients are refused rather
+/// than fed messages they would misread
+pub const PROTOCOL_VERSION: u32 = 1;
+/// Oldest client protocol still served
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
+/// Highest per-client frame rate a client can ask for
+pub const MAX_RATE_HZ: f32 = 360.0;
+
+/// Streams a client can subscribe to; request replies are always delivered.
+/// "events" sends `{"type":"events","session_time":...,"events":[...]}` for frames that raised any,
+/// "map_state" sends `{"type":"map_state","session_time":...,"segments":[...]}` (see map_state.rs),
+/// "session_info" sends `{"type":"session_info","session_info":"<yaml>","session_info_parsed":{...}}`
+/// on subscribing and whenever it changes; frames on schema 3 and later don't carry it
+pub const TOPICS: &[&str] = &["telemetry", "events", "map_state", "session_info"];
+
+// Error codes
+pub const ERROR_INVALID_JSON: &str = "invalid_json";
+pub const ERROR_MISSING_TYPE: &str = "missing_type";
+pub const ERROR_UNKNOWN_TYPE: &str = "unknown_type";
+pub const ERROR_UNSUPPORTED_VERSION: &str = "unsupported_protocol_version";
+pub const ERROR_UNSUPPORTED_SCHEMA: &str = "unsupported_schema_version";
+pub const ERROR_UNKNOWN_TOPIC: &str = "unknown_topic";
+pub const ERROR_UNSUPPORTED_ENCODING: &str = "unsupported_encoding";
+pub const ERROR_UNKNOWN_PRESET: &str = "unknown_preset";
+pub const ERROR_UNKNOWN_GROUP: &str = "unknown_group";
+/// A field the command needs is missing or malformed
+pub const ERROR_INVALID_REQUEST: &str = "invalid_request";
+/// The command referred to something that doesn't exist
+pub const ERROR_NOT_FOUND: &str = "not_found";
+/// The command was understood but couldn't be carried out
+pub const ERROR_FAILED: &str = "request_failed";
+/// The command isn't available on this endpoint, or the token is missing
+pub const ERROR_FORBIDDEN: &str = "forbidden";
+/// The client sent more messages than the server accepts per second
+pub const ERROR_RATE_LIMITED: &str = "rate_limited";
+
+/// Options negotiated by one client
+#[derive(Serialize, Clone, Debug)]
+pub struct ClientOptions {
+    pub protocol_version: u32,
+    /// Frame field names this client reads (see field_compat.rs)
+    pub schema_version: u32,
+    pub topics: Vec<String>,
+    /// Codec for streamed messages (see codec.rs)
+    pub encoding: String,
+    /// Channel preset applied to this client's frames, None for plain frames
+    pub channel_preset: Option<String>,
+    /// Per-client streams (see `StreamProvider`) with the parameters the client picked
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub streams: BTreeMap<String, Value>,
+    /// Only changed channels between keyframes (see delta.rs)
+    pub delta: bool,
+    /// Field groups in this client's frames, empty for whole frames (see field_groups.rs)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub groups: Vec<String>,
+    /// Frames per second sent to this client, None for every frame
+    pub rate_hz: Option<f32>,
+}
+
+impl Default for ClientOptions {
+    fn default() -> Self {
+        Self {
+            protocol_version: PROTOCOL_VERSION,
+            schema_version: SCHEMA_VERSION,
+            topics: TOPICS.iter().map(|topic| topic.to_string()).collect(),
+            encoding: codec::CODECS[0].name().to_string(),
+            channel_preset: None,
+            streams: BTreeMap::new(),
+            delta: false,
+            groups: Vec::new(),
+            rate_hz: None,
+        }
+    }
+}
+
+impl ClientOptions {
+    pub fn wants(&self, topic: &str) -> bool {
+        self.topics.iter().any(|wanted| wanted == topic)
+    }
+}
+
+/// Structured error reply
+pub fn error(code: &str, message: impl Into<String>, request_type: Option<&str>) -> Value {
+    json!({
+        "type": "error",
+        "code": code,
+        "message": message.into(),
+        "request": request_type,
+    })
+}
+
+/// Greeting sent to every client on connect. `extra` fields (e.g. the units preference)
+/// are merged in
+pub fn hello(mut commands: Vec<String>, presets: &ChannelPresets, extra: Option<Value>) -> Value {
+    commands.sort();
+    let mut hello = json!({
+        "type": "hello",
+        "server": "speedforge",
+        "server_version": version::VERSION,
+        "version": version::build_info(),
+        "protocol_version": PROTOCOL_VERSION,
+        "min_protocol_version": MIN_PROTOCOL_VERSION,
+        "schema_version": SCHEMA_VERSION,
+        "min_schema_version": MIN_SCHEMA_VERSION,
+        "topics": TOPICS,
+        "encodings": codec::names(),
+        "commands": commands,
+        "channel_presets": presets.names(),
+        "delta_keyframe_interval": delta::KEYFRAME_INTERVAL,
+        "field_groups": field_groups::names(),
+    });
+    if let (Some(Value::Object(extra)), Value::Object(fields)) = (extra, &mut hello) {
+        for (key, value) in extra {
+            if key != "type" {
+                fields.entry(key).or_insert(value);
+            }
+        }
+    }
+    hello
+}
+
+/// Apply a client's `hello` to its current options; Err holds the error reply
+pub fn negotiate(request: &Value, current: &ClientOptions, presets: &ChannelPresets) -> Result<ClientOptions, Value> {
+    let mut options = current.clone();
+
+    if let Some(version) = request.get("protocol_version").and_then(Value::as_u64) {
+        let version = version as u32;
+        if !(MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION).contains(&version) {
+            return Err(error(
+                ERROR_UNSUPPORTED_VERSION,
+                format!("Protocol version {} is not supported, this server speaks {} to {}", version, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION),
+                Some("hello"),
+            ));
+        }
+        options.protocol_version = version;
+    }
+
+    if let Some(version) = request.get("schema_version").and_then(Value::as_u64) {
+        let version = version as u32;
+        if !(MIN_SCHEMA_VERSION..=SCHEMA_VERSION).contains(&version) {
+            return Err(error(
+                ERROR_UNSUPPORTED_SCHEMA,
+                format!("Schema version {} is not supported, this server sends {} to {}", version, MIN_SCHEMA_VERSION, SCHEMA_VERSION),
+                Some("hello"),
+            ));
+        }
+        options.schema_version = version;
+    }
+
+    if let Some(topics) = request.get("topics").and_then(Value::as_array) {
+        let mut wanted = Vec::new();
+        for topic in topics {
+            let topic = topic.as_str().unwrap_or_default();
+            if !TOPICS.contains(&topic) {
+                return Err(error(ERROR_UNKNOWN_TOPIC, format!("Unknown topic '{}'", topic), Some("hello")));
+            }
+            wanted.push(topic.to_string());
+        }
+        options.topics = wanted;
+    }
+
+    if let Some(encoding) = request.get("encoding").and_then(Value::as_str) {
+        if codec::by_name(encoding).is_none() {
+            return Err(error(ERROR_UNSUPPORTED_ENCODING, format!("Encoding '{}' is not supported", encoding), Some("hello")));
+        }
+        options.encoding = encoding.to_string();
+    }
+
+    match request.get("channel_preset") {
+        Some(Value::String(name)) if presets.get(name).is_none() => {
+            return Err(error(ERROR_UNKNOWN_PRESET, format!("Unknown preset '{}'", name), Some("hello")));
+        },
+        Some(Value::String(name)) => options.channel_preset = Some(name.clone()),
+        Some(Value::Null) => options.channel_preset = None,
+        _ => {},
+    }
+
+    if let Some(delta) = request.get("delta").and_then(Value::as_bool) {
+        options.delta = delta;
+    }
+
+    if let Some(groups) = request.get("groups") {
+        options.groups = parse_groups(groups, "hello")?;
+    }
+
+    if let Some(rate_hz) = request.get("rate_hz") {
+        options.rate_hz = parse_rate(rate_hz, "hello")?;
+    }
+    Ok(options)
+}
+
+/// Field group names from a `subscribe` or `hello` list; Err holds the error reply
+pub fn parse_groups(list: &Value, request_type: &str) -> Result<Vec<String>, Value> {
+    let Some(list) = list.as_array() else {
+        return Err(error(ERROR_INVALID_REQUEST, "Field groups must be a list of names", Some(request_type)));
+    };
+    let mut groups = Vec::new();
+    for group in list {
+        let group = group.as_str().unwrap_or_default();
+        if !field_groups::is_known(group) {
+            let mut error = error(ERROR_UNKNOWN_GROUP, format!("Unknown field group '{}'", group), Some(request_type));
+            error["available"] = json!(field_groups::names());
+            return Err(error);
+        }
+        if !groups.iter().any(|known| known == group) {
+            groups.push(group.to_string());
+        }
+    }
+    Ok(groups)
+}
+
+/// Frame rate from a `set_rate` or `hello` request: a positive number, or null for every
+/// frame; Err holds the error reply
+pub fn parse_rate(value: &Value, request_type: &str) -> Result<Option<f32>, Value> {
+    match value {
+        Value::Null => Ok(None),
+        Value::Number(rate) => match rate.as_f64() {
+            Some(rate) if rate > 0.0 && rate <= MAX_RATE_HZ as f64 => Ok(Some(rate as f32)),
+            _ => Err(error(ERROR_INVALID_REQUEST, format!("rate_hz must be above 0 and at most {}", MAX_RATE_HZ), Some(request_type))),
+        },
+        _ => Err(error(ERROR_INVALID_REQUEST, "rate_hz must be a number or null", Some(request_type))),
+    }
+}
+
+/// Copy the command's `request_id` onto its reply
+pub fn correlate(request: &Value, reply: &mut Value) {
+    if let (Some(request_id), Value::Object(fields)) = (request.get("request_id"), reply) {
+        fields.insert("request_id".to_string(), request_id.clone());
+    }
+}
+
+/// Reply to an accepted `hello`
+pub fn welcome(options: &ClientOptions) -> Value {
+    json!({
+        "type": "welcome",
+        "protocol_version": options.protocol_version,
+        "schema_version": options.schema_version,
+        "topics": options.topics,
+        "encoding": options.encoding,
+        "channel_preset": options.channel_preset,
+        "delta": options.delta,
+        "groups": options.groups,
+        "rate_hz": options.rate_hz,
+    })
+}
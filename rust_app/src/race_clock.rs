@@ -0,0 +1,65 @@
+//! Race time shared by all derived channels.
+//!
+//! Raw SessionTime starts over between sessions, stands still while the sim is paused
+//! and jumps around while a replay is scrubbed. Instead of every channel guessing from
+//! SessionTime, the pipeline advances one clock per frame and channels read `dt` for
+//! elapsed time (0 while frozen or after a jump) and `session_reset` to start over.
+
+use crate::telemetry_fields::TelemetryData;
+
+/// Larger SessionTime steps are jumps (replay seek, reconnect), not elapsed race time
+pub const MAX_STEP_S: f32 = 1.0;
+
+/// The race clock for one frame
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RaceClock {
+    /// Race time elapsed in this session, excluding pauses and jumps
+    pub time: f64,
+    /// Race time elapsed since the previous frame
+    pub dt: f32,
+    /// A new session started; accumulated history should be discarded
+    pub session_reset: bool,
+    /// SessionTime skipped ahead (replay seek, reconnect); short histories should be dropped
+    pub jumped: bool,
+}
+
+/// Advances the race clock from each frame's SessionTime
+#[derive(Default)]
+pub struct RaceClockTracker {
+    last_session_time: Option<f32>,
+    last_session_num: i32,
+    time: f64,
+}
+
+impl RaceClockTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn update(&mut self, data: &TelemetryData) -> RaceClock {
+        let previous = self.last_session_time.replace(data.SessionTime);
+        let session_changed = std::mem::replace(&mut self.last_session_num, data.session_num) != data.session_num;
+        let Some(previous) = previous else {
+            return RaceClock::default();
+        };
+
+        // Scrubbing a replay moves SessionTime both ways without starting a new session
+        if data.is_replay_playing {
+            return RaceClock { time: self.time, ..RaceClock::default() };
+        }
+        if session_changed || data.SessionTime < previous {
+            self.time = 0.0;
+            return RaceClock { session_reset: true, ..RaceClock::default() };
+        }
+
+        let step = data.SessionTime - previous;
+        let dt = if step > MAX_STEP_S { 0.0 } else { step };
+        self.time += dt as f64;
+        RaceClock {
+            time: self.time,
+            dt,
+            session_reset: false,
+            jumped: step > MAX_STEP_S,
+        }
+    }
+}
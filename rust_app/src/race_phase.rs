@@ -0,0 +1,106 @@
+//! Phase of the session: before the start, formation/pace laps, green, caution, finish.
+//!
+//! During formation laps cars cross the line out of order and the lap count still ticks,
+//! so gaps and fuel per lap from those laps are misleading; channels check the phase
+//! instead of reading SessionState and PaceMode themselves.
+
+use crate::derived::DerivedChannel;
+use crate::telemetry_fields::{TelemetryData, FLAG_CAUTION, FLAG_CAUTION_WAVING};
+use serde::{Serialize, Deserialize};
+
+// irsdk_SessionState values
+const STATE_GET_IN_CAR: i32 = 1;
+const STATE_WARMUP: i32 = 2;
+const STATE_PARADE_LAPS: i32 = 3;
+const STATE_RACING: i32 = 4;
+const STATE_CHECKERED: i32 = 5;
+const STATE_COOL_DOWN: i32 = 6;
+
+// irsdk_PaceMode values
+const PACE_SINGLE_FILE_START: i32 = 0;
+const PACE_DOUBLE_FILE_START: i32 = 1;
+const PACE_SINGLE_FILE_RESTART: i32 = 2;
+const PACE_DOUBLE_FILE_RESTART: i32 = 3;
+
+/// Current phase of the session
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    /// Getting in cars and warming up
+    PreRace,
+    /// Parade/formation laps up to the start
+    Formation,
+    Green,
+    /// Behind the pace car or under a full course yellow
+    Caution,
+    Checkered,
+    CoolDown,
+}
+
+/// How the field is lined up behind the pace car
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PaceFormation {
+    SingleFile,
+    DoubleFile,
+}
+
+/// Race phase published with every frame
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RacePhase {
+    pub phase: Phase,
+    /// Formation while pacing for a start or restart
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pace_formation: Option<PaceFormation>,
+    /// Field is following the pace car (formation laps or a restart)
+    pub pacing: bool,
+}
+
+impl RacePhase {
+    /// Laps run in this phase don't represent racing pace or consumption
+    pub fn is_pacing(&self) -> bool {
+        self.pacing || matches!(self.phase, Phase::PreRace | Phase::Formation | Phase::Caution)
+    }
+
+    /// Cars aren't racing for position, so timing based positions and gaps are meaningless
+    pub fn suppresses_gaps(&self) -> bool {
+        matches!(self.phase, Phase::PreRace | Phase::Formation)
+    }
+}
+
+/// Phase from SessionState, PaceMode and the session flags; None when the sim reports no state
+pub fn phase_of(session_state: i32, pace_mode: i32, session_flags: u32) -> Option<RacePhase> {
+    let caution = session_flags & (FLAG_CAUTION | FLAG_CAUTION_WAVING) != 0;
+    let phase = match session_state {
+        STATE_GET_IN_CAR | STATE_WARMUP => Phase::PreRace,
+        STATE_PARADE_LAPS => Phase::Formation,
+        STATE_RACING if caution => Phase::Caution,
+        STATE_RACING => Phase::Green,
+        STATE_CHECKERED => Phase::Checkered,
+        STATE_COOL_DOWN => Phase::CoolDown,
+        _ => return None,
+    };
+    let pace_formation = match pace_mode {
+        PACE_SINGLE_FILE_START | PACE_SINGLE_FILE_RESTART => Some(PaceFormation::SingleFile),
+        PACE_DOUBLE_FILE_START | PACE_DOUBLE_FILE_RESTART => Some(PaceFormation::DoubleFile),
+        _ => None,
+    };
+    Some(RacePhase {
+        phase,
+        pace_formation,
+        pacing: pace_formation.is_some() && matches!(phase, Phase::Formation | Phase::Caution),
+    })
+}
+
+/// Derived channel that sets `race_phase` for the channels that depend on it
+pub struct RacePhaseDetector;
+
+impl DerivedChannel for RacePhaseDetector {
+    fn name(&self) -> &'static str {
+        "race_phase"
+    }
+
+    fn compute(&mut self, data: &mut TelemetryData) {
+        data.race_phase = phase_of(data.session_state, data.pace_mode, data.session_flags);
+    }
+}
@@ -0,0 +1,268 @@
+//! Proximity radar: where nearby cars are relative to the player, in metres.
+//!
+//! CarLeftRight only says that someone is alongside. For a radar widget each car within
+//! `RANGE_M` gets an x (right of the player) and y (ahead of the player) position. Positions
+//! along the lap come from CarIdxLapDistPct; to place them around corners a track map is
+//! traced from the player's own driving (speed and yaw rate integrated over a clean lap).
+//! The map only has to be right locally, so drift over the lap doesn't matter. Until a lap
+//! has been traced cars are placed on a straight line ahead and behind. Cars alongside get
+//! their side from CarLeftRight, since the lap position says nothing about lateral offset.
+//! The finished map is also published as `track_shape` for the map overlay (map_state.rs).
+
+use crate::derived::DerivedChannel;
+use crate::laps;
+use crate::telemetry_fields::{CarLeftRight, TelemetryData};
+use serde::{Serialize, Deserialize};
+use std::sync::Arc;
+
+/// Cars further away than this are left out
+pub const RANGE_M: f32 = 50.0;
+/// Track map resolution (points per lap)
+pub const MAP_POINTS: usize = 2000;
+
+// Cars closer than this along the track overlap the player
+const CAR_LENGTH_M: f32 = 5.0;
+// Lateral offset used for a car reported alongside
+const LANE_OFFSET_M: f32 = 2.5;
+// Below this speed the integrated path is mostly noise
+const MIN_TRACE_SPEED_MS: f32 = 5.0;
+// Frame gaps longer than this break the traced path
+const MAX_TRACE_DT_S: f32 = 0.5;
+// Share of map points a traced lap must cover before it is used
+const MIN_COVERAGE: f32 = 0.95;
+
+/// A car near the player
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct RadarCar {
+    pub car_idx: usize,
+    /// Metres to the right of the player (negative is left)
+    pub x_m: f32,
+    /// Metres ahead of the player (negative is behind)
+    pub y_m: f32,
+    /// Distance from the player
+    pub distance_m: f32,
+    /// Overlapping the player
+    pub alongside: bool,
+}
+
+/// Nearby cars for proximity radar overlays
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Radar {
+    pub range_m: f32,
+    /// Positions follow the track shape; false while cars are placed on a straight line
+    pub track_map: bool,
+    pub cars: Vec<RadarCar>,
+}
+
+// Player's path over one lap, by lap position
+struct TrackMap {
+    points: Vec<Option<(f32, f32)>>,
+    complete: bool,
+}
+
+impl TrackMap {
+    fn new() -> Self {
+        Self { points: vec![None; MAP_POINTS], complete: false }
+    }
+
+    fn index(pct: f32) -> usize {
+        ((pct.rem_euclid(1.0) * MAP_POINTS as f32) as usize).min(MAP_POINTS - 1)
+    }
+
+    // Fill the points a trace skipped from their neighbours; fails when too many are missing
+    fn finish(&mut self) -> bool {
+        let known: Vec<usize> = (0..MAP_POINTS).filter(|&i| self.points[i].is_some()).collect();
+        if (known.len() as f32) < MAP_POINTS as f32 * MIN_COVERAGE {
+            return false;
+        }
+        for (n, &start) in known.iter().enumerate() {
+            let end = known[(n + 1) % known.len()];
+            let gap = (end + MAP_POINTS - start) % MAP_POINTS;
+            let (Some(a), Some(b)) = (self.points[start], self.points[end]) else {
+                continue;
+            };
+            for step in 1..gap {
+                let t = step as f32 / gap as f32;
+                self.points[(start + step) % MAP_POINTS] = Some((a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t));
+            }
+        }
+        self.complete = true;
+        true
+    }
+
+    // Interpolated position at a lap position
+    fn position(&self, pct: f32) -> Option<(f32, f32)> {
+        let scaled = pct.rem_euclid(1.0) * MAP_POINTS as f32;
+        let i = (scaled as usize).min(MAP_POINTS - 1);
+        let t = scaled - i as f32;
+        let a = self.points[i]?;
+        let b = self.points[(i + 1) % MAP_POINTS]?;
+        Some((a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t))
+    }
+}
+
+// Traces the player's path until it covers a full lap
+#[derive(Default)]
+struct Trace {
+    map: Option<TrackMap>,
+    position: (f32, f32),
+    heading_rad: f32,
+    start_pct: Option<f32>,
+    travelled_pct: f32,
+    last_pct: f32,
+}
+
+/// Derived channel that places nearby cars around the player
+#[derive(Default)]
+pub struct ProximityRadar {
+    map: Option<TrackMap>,
+    // Finished map as published to other channels
+    shape: Option<Arc<Vec<(f32, f32)>>>,
+    trace: Trace,
+    track_length_m: f32,
+    last_time: Option<f32>,
+}
+
+impl ProximityRadar {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn restart_trace(&mut self) {
+        self.trace = Trace::default();
+    }
+
+    fn trace(&mut self, data: &TelemetryData, dt: f32) {
+        let speed_ms = data.speed_kph / 3.6;
+        let clean = !data.on_pit_road && !data.is_replay_playing && data.lap_dist_pct >= 0.0 && speed_ms >= MIN_TRACE_SPEED_MS;
+        if !clean || dt <= 0.0 || dt > MAX_TRACE_DT_S {
+            self.restart_trace();
+            return;
+        }
+        let trace = &mut self.trace;
+        let map = trace.map.get_or_insert_with(TrackMap::new);
+        match trace.start_pct {
+            None => {
+                trace.start_pct = Some(data.lap_dist_pct);
+                trace.last_pct = data.lap_dist_pct;
+            },
+            Some(_) => {
+                let step = laps::signed_lap_pct_delta(trace.last_pct, data.lap_dist_pct);
+                // Going backwards or jumping means a spin or a reset; start over
+                if !(0.0..0.05).contains(&step) {
+                    self.restart_trace();
+                    return;
+                }
+                trace.travelled_pct += step;
+                trace.last_pct = data.lap_dist_pct;
+                trace.heading_rad += data.yaw_rate_deg_s.to_radians() * dt;
+                trace.position.0 += speed_ms * dt * trace.heading_rad.cos();
+                trace.position.1 += speed_ms * dt * trace.heading_rad.sin();
+            },
+        }
+        let index = TrackMap::index(data.lap_dist_pct);
+        if map.points[index].is_none() {
+            map.points[index] = Some(trace.position);
+        }
+        if trace.travelled_pct >= 1.0 {
+            let mut map = trace.map.take().unwrap_or_else(TrackMap::new);
+            if map.finish() {
+                self.shape = Some(Arc::new(map.points.iter().map(|point| point.unwrap_or_default()).collect()));
+                self.map = Some(map);
+            }
+            self.restart_trace();
+        }
+    }
+
+    // Position of a car relative to the player: (right, ahead) in metres
+    fn relative(&self, player_pct: f32, car_pct: f32) -> (f32, f32) {
+        let along_m = laps::signed_lap_pct_delta(player_pct, car_pct) * self.track_length_m;
+        let Some(map) = self.map.as_ref().filter(|map| map.complete) else {
+            return (0.0, along_m);
+        };
+        let (Some(player), Some(car), Some(ahead)) = (
+            map.position(player_pct),
+            map.position(car_pct),
+            map.position(player_pct + 1.0 / MAP_POINTS as f32),
+        ) else {
+            return (0.0, along_m);
+        };
+        let (fx, fy) = (ahead.0 - player.0, ahead.1 - player.1);
+        let length = (fx * fx + fy * fy).sqrt();
+        if length <= f32::EPSILON {
+            return (0.0, along_m);
+        }
+        let (fx, fy) = (fx / length, fy / length);
+        let (dx, dy) = (car.0 - player.0, car.1 - player.1);
+        // Heading integrates yaw rate counter-clockwise, so the right of (fx, fy) is (fy, -fx)
+        (dx * fy - dy * fx, dx * fx + dy * fy)
+    }
+}
+
+impl DerivedChannel for ProximityRadar {
+    fn name(&self) -> &'static str {
+        "radar"
+    }
+
+    fn compute(&mut self, data: &mut TelemetryData) {
+        let dt = self.last_time.replace(data.SessionTime).map_or(0.0, |last| data.SessionTime - last);
+        let length = laps::estimate_track_length(data);
+        if length > 0.0 {
+            // A different length is a different track; its map has to be traced again
+            if self.track_length_m > 0.0 && (length - self.track_length_m).abs() > self.track_length_m * 0.02 {
+                self.map = None;
+                self.shape = None;
+                self.restart_trace();
+            }
+            self.track_length_m = length;
+        }
+        if self.map.is_none() {
+            self.trace(data, dt);
+        }
+        data.track_shape = self.shape.clone();
+
+        let Some(positions) = data.CarIdxLapDistPct.as_ref() else {
+            return;
+        };
+        if self.track_length_m <= 0.0 || data.lap_dist_pct < 0.0 {
+            return;
+        }
+        let player = data.player_car_idx as usize;
+        let on_pit_road = data.CarIdxOnPitRoad.as_ref();
+        let mut cars: Vec<RadarCar> = positions
+            .iter()
+            .enumerate()
+            .filter(|&(idx, &pct)| idx != player && pct >= 0.0)
+            .filter(|&(idx, _)| on_pit_road.and_then(|p| p.get(idx)).copied() != Some(true) || data.on_pit_road)
+            .filter(|&(_, &pct)| laps::lap_pct_distance(data.lap_dist_pct, pct) * self.track_length_m <= RANGE_M * 1.5)
+            .map(|(idx, &pct)| {
+                let (x_m, y_m) = self.relative(data.lap_dist_pct, pct);
+                RadarCar { car_idx: idx, x_m, y_m, distance_m: x_m.hypot(y_m), alongside: y_m.abs() < CAR_LENGTH_M }
+            })
+            .filter(|car| car.distance_m <= RANGE_M)
+            .collect();
+
+        // Sides of overlapping cars from the spotter, nearest first
+        let sides: &[f32] = match data.car_left_right {
+            CarLeftRight::CarLeft => &[-1.0],
+            CarLeftRight::CarRight => &[1.0],
+            CarLeftRight::CarLeftRight => &[-1.0, 1.0],
+            CarLeftRight::TwoCarsLeft => &[-1.0, -2.0],
+            CarLeftRight::TwoCarsRight => &[1.0, 2.0],
+            _ => &[],
+        };
+        let mut alongside: Vec<&mut RadarCar> = cars.iter_mut().filter(|car| car.alongside).collect();
+        alongside.sort_by(|a, b| a.y_m.abs().total_cmp(&b.y_m.abs()));
+        for (car, side) in alongside.into_iter().zip(sides) {
+            car.x_m = side * LANE_OFFSET_M;
+            car.distance_m = car.x_m.hypot(car.y_m);
+        }
+
+        cars.sort_by(|a, b| a.distance_m.total_cmp(&b.distance_m));
+        data.radar = Some(Radar {
+            range_m: RANGE_M,
+            track_map: self.map.is_some(),
+            cars,
+        });
+    }
+}
@@ -0,0 +1,293 @@
+//! Recording of the sim's frames to JSON lines files, for analysis after the session.
+//!
+//! While recording, every live frame is appended as one line to
+//! `speedforge_<start>_<track>.jsonl` in the sessions folder, as extracted from the sim:
+//! before the glitch filter and without the derived channels, so `replay` and `analyze`
+//! derive them again from the same input the live run had. Frames repeated while the sim
+//! is paused or frozen are left out. It is the format the session browser, `compare`,
+//! `analyze`, `lapdiff` and `export-session` read.
+//! The session info YAML is written on the first frame of a file and when it changes
+//! only. A new file is started for each session (a new SessionNum or subsession) and once
+//! the current one reaches the size limit.
+//!
+//! Recording starts at launch with `--record`, or at runtime with the `toggle_recording`
+//! action (hotkeys, webhooks) or the WebSocket commands `{"type":"start_recording"}`,
+//! `{"type":"stop_recording"}` and `{"type":"get_recording"}`, which all answer with the
+//! recorder's state. Each start and stop raises a `recording_toggle` event. Writes go
+//! through the I/O thread (file_io.rs); when one fails, recording pauses and a new file
+//! is tried after a backoff.
+//!
+//! When SPEEDFORGE_PASSPHRASE is set, recordings are encrypted as they are written (see
+//! encryption.rs), in chunks; a recording cut short loses at most its last chunk.
+
+use crate::encryption::SealedWriter;
+use crate::events::EventQueue;
+use crate::file_io;
+use crate::session_yaml;
+use crate::sink_health;
+use crate::telemetry_fields::TelemetryData;
+use crate::websocket_server::RequestHandler;
+use chrono::Local;
+use serde_json::{json, Value};
+use std::fs::{self, File};
+use std::io::{LineWriter, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// Folder recordings go to without `--sessions-dir`
+pub const DEFAULT_DIR: &str = "sessions";
+/// Size at which a new file is started
+pub const DEFAULT_MAX_MB: u64 = 500;
+/// Name the recorder reports its health under (see sink_health.rs)
+pub const SINK: &str = "recorder";
+
+const FILE_PREFIX: &str = "speedforge_";
+
+struct RecorderState {
+    dir: PathBuf,
+    max_bytes: u64,
+    recording: bool,
+    writer: Option<Box<dyn Write + Send>>,
+    // Encrypt new files with this passphrase
+    passphrase: Option<String>,
+    path: Option<PathBuf>,
+    written: u64,
+    frames: u64,
+    // Subsession and SessionNum the current file is for
+    session: Option<(Option<i64>, i32)>,
+    // Session info last written to the current file
+    session_info: String,
+    // Track and subsession read from the last session info seen; frames are recorded
+    // before the derived channels run, so session_info_parsed isn't filled in yet
+    session_yaml: String,
+    track: Option<String>,
+    sub_session: Option<i64>,
+    // Failures in a row, and when to try opening a file again after the last one
+    failures: u32,
+    retry_at: Option<Instant>,
+}
+
+impl RecorderState {
+    fn open(&mut self) -> Result<(), String> {
+        self.close();
+        fs::create_dir_all(&self.dir).map_err(|e| format!("Failed to create {}: {}", self.dir.display(), e))?;
+        let track = self.track.as_deref().unwrap_or("session");
+        let track: String = track.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '-' }).collect();
+        let stem = format!("{}{}_{}", FILE_PREFIX, Local::now().format("%Y%m%d_%H%M%S"), track);
+        // Two files started within a second (a short session, or size rotation) get a suffix
+        let mut path = self.dir.join(format!("{}.jsonl", stem));
+        let mut suffix = 2;
+        while path.exists() {
+            path = self.dir.join(format!("{}_{}.jsonl", stem, suffix));
+            suffix += 1;
+        }
+        let writer: Box<dyn Write + Send> = match &self.passphrase {
+            Some(passphrase) => Box::new(SealedWriter::create(&path, passphrase)?),
+            None => {
+                let file = File::options().create(true).append(true).open(&path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+                Box::new(LineWriter::new(file))
+            },
+        };
+        tracing::info!("Recording to {}{}", path.display(), if self.passphrase.is_some() { " (encrypted)" } else { "" });
+        self.writer = Some(writer);
+        self.path = Some(path);
+        self.written = 0;
+        self.session_info.clear();
+        Ok(())
+    }
+
+    fn close(&mut self) {
+        if let Some(mut writer) = self.writer.take() {
+            let _ = writer.flush();
+        }
+    }
+
+    // Give up on the current file and try a new one after the backoff
+    fn fail(&mut self, error: String) {
+        self.failures += 1;
+        let backoff = sink_health::backoff(self.failures);
+        tracing::warn!("{}; recording paused for {}s", error, backoff.as_secs());
+        sink_health::failure(SINK, &error);
+        self.writer = None;
+        self.retry_at = Some(Instant::now() + backoff);
+    }
+
+    fn write_frame(&mut self, mut frame: TelemetryData) {
+        if self.retry_at.is_some_and(|retry_at| Instant::now() < retry_at) {
+            return;
+        }
+        if !frame.session_info.is_empty() && frame.session_info != self.session_yaml {
+            self.track = session_yaml::scalar(&frame.session_info, "TrackName").map(str::to_string);
+            self.sub_session = session_yaml::scalar(&frame.session_info, "SubSessionID").and_then(|id| id.parse().ok());
+            self.session_yaml = frame.session_info.clone();
+        }
+        let sub_session = self.sub_session.or_else(|| self.session.and_then(|(sub_session, _)| sub_session));
+        let session = (sub_session, frame.session_num);
+        if self.writer.is_none() || self.session != Some(session) || self.written >= self.max_bytes {
+            if let Err(e) = self.open() {
+                self.fail(e);
+                return;
+            }
+            if self.retry_at.take().is_some() {
+                sink_health::restarted(SINK);
+            }
+            self.session = Some(session);
+        }
+
+        // The YAML is large and rarely changes; readers carry it forward
+        if frame.session_info == self.session_info {
+            frame.session_info.clear();
+            frame.session_info_parsed = None;
+        } else {
+            self.session_info = frame.session_info.clone();
+        }
+        let line = match serde_json::to_string(&frame) {
+            Ok(line) => line,
+            Err(e) => {
+                sink_health::failure(SINK, &format!("Failed to serialize frame: {}", e));
+                return;
+            }
+        };
+        let Some(writer) = self.writer.as_mut() else {
+            return;
+        };
+        match writeln!(writer, "{}", line) {
+            Ok(()) => {
+                self.written += line.len() as u64 + 1;
+                self.frames += 1;
+                self.failures = 0;
+                sink_health::success(SINK);
+            },
+            Err(e) => {
+                let error = format!("Failed to write {}: {}", self.path.as_ref().map(|path| path.display().to_string()).unwrap_or_default(), e);
+                self.fail(error);
+            },
+        }
+    }
+}
+
+/// Recorder shared between the telemetry thread, control actions and client requests
+#[derive(Clone)]
+pub struct Recorder {
+    state: Arc<Mutex<RecorderState>>,
+    events: EventQueue,
+}
+
+impl Recorder {
+    /// Record to `dir`, starting a new file every `max_bytes`, encrypted with `passphrase`
+    /// when given; not recording until started
+    pub fn new(dir: PathBuf, max_bytes: u64, passphrase: Option<String>, events: EventQueue) -> Self {
+        let state = RecorderState {
+            dir,
+            max_bytes,
+            recording: false,
+            writer: None,
+            passphrase,
+            path: None,
+            written: 0,
+            frames: 0,
+            session: None,
+            session_info: String::new(),
+            session_yaml: String::new(),
+            track: None,
+            sub_session: None,
+            failures: 0,
+            retry_at: None,
+        };
+        Self {
+            state: Arc::new(Mutex::new(state)),
+            events,
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.state.lock().is_ok_and(|state| state.recording)
+    }
+
+    /// Start or stop recording; returns whether it changed
+    pub fn set_recording(&self, recording: bool) -> bool {
+        let Ok(mut state) = self.state.lock() else {
+            return false;
+        };
+        if state.recording == recording {
+            return false;
+        }
+        state.recording = recording;
+        state.retry_at = None;
+        if recording {
+            state.frames = 0;
+        } else {
+            // After the frames still queued, so the file ends with the last one recorded
+            let state = self.state.clone();
+            file_io::submit(move || {
+                if let Ok(mut state) = state.lock() && !state.recording {
+                    state.close();
+                    state.session = None;
+                }
+            });
+        }
+        drop(state);
+        self.events.push("recording_toggle", json!({ "recording": recording }));
+        true
+    }
+
+    /// Start recording when stopped and stop it when recording; returns the new state
+    pub fn toggle(&self) -> bool {
+        let recording = !self.is_recording();
+        self.set_recording(recording);
+        recording
+    }
+
+    /// Queue `frame` for writing when recording
+    pub fn record(&self, frame: &TelemetryData) {
+        if !self.is_recording() {
+            return;
+        }
+        let frame = frame.clone();
+        let state = self.state.clone();
+        file_io::submit(move || {
+            if let Ok(mut state) = state.lock() {
+                state.write_frame(frame);
+            }
+        });
+    }
+
+    /// File being written, if any
+    pub fn active_file(&self) -> Option<PathBuf> {
+        let state = self.state.lock().ok()?;
+        state.writer.as_ref().and(state.path.clone())
+    }
+
+    /// State for the `recording` reply
+    pub fn status(&self) -> Value {
+        let Ok(state) = self.state.lock() else {
+            return json!({ "type": "recording", "recording": false });
+        };
+        json!({
+            "type": "recording",
+            "recording": state.recording,
+            "file": state.path.as_ref().map(|path| path.display().to_string()),
+            "frames": state.frames,
+            "bytes": state.written,
+            "dir": state.dir.display().to_string(),
+            "encrypted": state.passphrase.is_some(),
+        })
+    }
+}
+
+impl RequestHandler for Recorder {
+    /// `{"type":"start_recording"}`, `{"type":"stop_recording"}` or `{"type":"get_recording"}`
+    fn handle(&self, request: &Value) -> Value {
+        match request.get("type").and_then(Value::as_str) {
+            Some("start_recording") => {
+                self.set_recording(true);
+            },
+            Some("stop_recording") => {
+                self.set_recording(false);
+            },
+            _ => {},
+        }
+        self.status()
+    }
+}
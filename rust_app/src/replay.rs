@@ -0,0 +1,233 @@
+//! Playback of recorded sessions, for working on overlays without the sim running.
+//!
+//! ```text
+//! speedforge replay <recording.jsonl|file.ibt> [--speed <x>] [--once] [server options]
+//! ```
+//!
+//! runs the server with a recording as its telemetry source (`--sim replay --replay
+//! <file>`): frames are published at the pace they were recorded, scaled by `--speed`
+//! (2 plays twice as fast), and go through the derived channels and out to clients like
+//! live ones. Recordings are speedforge JSON lines (see recorder.rs, possibly encrypted)
+//! or iRacing .ibt files. Playback starts over at the end unless `--once` is given, in
+//! which case the last frame stays up.
+//!
+//! Recordings hold the frames as extracted, before the derived channels; recorded events
+//! (markers) are dropped, and finish probabilities in older recordings too, since the
+//! derived channels raise them again. Gaps of more than `MAX_GAP_S` of session time, such as a paused
+//! recording or a new session, are skipped rather than waited out.
+
+use crate::session_compare;
+use crate::telemetry_fields::TelemetryData;
+use crate::telemetry_source::{TelemetrySource, CAP_ALL};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Longest wait between two frames; longer gaps in session time are skipped
+pub const MAX_GAP_S: f64 = 1.0;
+// Frames read ahead of playback
+const PREFETCH: usize = 256;
+// Further behind than this and playback carries on from now instead of catching up
+const MAX_LAG: Duration = Duration::from_millis(250);
+
+/// Telemetry source playing back a recording
+pub struct ReplaySource {
+    path: PathBuf,
+    speed: f64,
+    looping: bool,
+    frames: Option<Receiver<Result<TelemetryData, String>>>,
+    // Next frame, read but not yet due
+    pending: Option<TelemetryData>,
+    session_info: String,
+    last_session_time: Option<f32>,
+    next_frame_at: Instant,
+    finished: bool,
+}
+
+impl ReplaySource {
+    pub fn new(path: PathBuf, speed: f64, looping: bool) -> Self {
+        Self {
+            path,
+            speed,
+            looping,
+            frames: None,
+            pending: None,
+            session_info: String::new(),
+            last_session_time: None,
+            next_frame_at: Instant::now(),
+            finished: false,
+        }
+    }
+
+    /// Source for `--replay <file>` with `--replay-speed <x>` and `--replay-once`
+    pub fn from_args(args: &[String]) -> Result<Self, String> {
+        let path = crate::arg_value(args, "--replay").ok_or("--sim replay needs --replay <recording>")?;
+        let speed = match crate::arg_value(args, "--replay-speed").map(|value| value.parse::<f64>()) {
+            None => 1.0,
+            Some(Ok(speed)) if speed > 0.0 => speed,
+            Some(_) => return Err("--replay-speed must be above 0".to_string()),
+        };
+        let once = args.iter().any(|arg| arg == "--replay-once");
+        Ok(Self::new(PathBuf::from(path), speed, !once))
+    }
+
+    // Read the recording on a thread of its own, as readers aren't Send and the
+    // telemetry thread shouldn't wait on the disk
+    fn start_reader(&mut self) -> Result<(), String> {
+        let (sender, receiver) = mpsc::sync_channel(PREFETCH);
+        let path = self.path.clone();
+        thread::Builder::new()
+            .name("replay-reader".to_string())
+            .spawn(move || {
+                let frames = match session_compare::read_frames(&path) {
+                    Ok(frames) => frames,
+                    Err(e) => {
+                        let _ = sender.send(Err(format!("{}: {}", path.display(), e)));
+                        return;
+                    }
+                };
+                for frame in frames {
+                    if sender.send(frame).is_err() {
+                        return;
+                    }
+                }
+            })
+            .map_err(|e| format!("Failed to start the replay reader: {}", e))?;
+        self.frames = Some(receiver);
+        self.pending = None;
+        self.session_info.clear();
+        self.last_session_time = None;
+        self.finished = false;
+        Ok(())
+    }
+
+    // Next recorded frame, skipping lines that don't parse; Ok(None) when none came in
+    // time or the recording is over
+    fn read(&mut self, timeout: Duration) -> Result<Option<TelemetryData>, String> {
+        let Some(frames) = &self.frames else {
+            return Ok(None);
+        };
+        loop {
+            match frames.recv_timeout(timeout) {
+                Ok(Ok(frame)) => return Ok(Some(frame)),
+                Ok(Err(e)) if self.last_session_time.is_none() && self.pending.is_none() => return Err(e),
+                Ok(Err(e)) => tracing::warn!("Skipping frame: {}", e),
+                Err(RecvTimeoutError::Timeout) => return Ok(None),
+                Err(RecvTimeoutError::Disconnected) => {
+                    self.frames = None;
+                    self.finished = true;
+                    return Ok(None);
+                },
+            }
+        }
+    }
+
+    // Undo what recording and the earlier run left on the frame
+    fn prepare(&mut self, frame: &mut TelemetryData) {
+        if frame.session_info.is_empty() {
+            frame.session_info = self.session_info.clone();
+        } else {
+            self.session_info = frame.session_info.clone();
+        }
+        frame.events.clear();
+        frame.finish_probabilities = None;
+        frame.stale = false;
+        frame.shed.clear();
+    }
+}
+
+impl TelemetrySource for ReplaySource {
+    fn name(&self) -> &'static str {
+        "replay"
+    }
+
+    fn capabilities(&self) -> u32 {
+        CAP_ALL
+    }
+
+    fn connect(&mut self) -> Result<(), String> {
+        if !self.path.is_file() {
+            return Err(format!("{} not found", self.path.display()));
+        }
+        self.start_reader()?;
+        // Fail here rather than on every frame when the file can't be read at all
+        match self.read(Duration::from_secs(30))? {
+            Some(frame) => self.pending = Some(frame),
+            None if self.finished => return Err(format!("{} contains no telemetry frames", self.path.display())),
+            None => {},
+        }
+        self.next_frame_at = Instant::now();
+        Ok(())
+    }
+
+    fn next_frame(&mut self, timeout: Duration) -> Result<Option<TelemetryData>, String> {
+        let mut frame = match self.pending.take() {
+            Some(frame) => frame,
+            None => match self.read(timeout)? {
+                Some(mut frame) => {
+                    // Wait as long as the recording did between the two frames
+                    let gap = self.last_session_time.map_or(0.0, |last| (frame.SessionTime - last) as f64);
+                    if gap > 0.0 && gap <= MAX_GAP_S {
+                        self.next_frame_at += Duration::from_secs_f64(gap / self.speed);
+                    }
+                    self.last_session_time = Some(frame.SessionTime);
+                    self.prepare(&mut frame);
+                    frame
+                },
+                None if self.finished && self.looping => {
+                    tracing::info!("Replay of {} finished, starting over", self.path.display());
+                    self.start_reader()?;
+                    return Ok(None);
+                },
+                None => {
+                    if self.finished {
+                        thread::sleep(timeout);
+                    }
+                    return Ok(None);
+                },
+            },
+        };
+        if self.last_session_time.is_none() {
+            // First frame of a playback, read by connect or start_reader
+            self.last_session_time = Some(frame.SessionTime);
+            self.prepare(&mut frame);
+        }
+
+        let now = Instant::now();
+        if self.next_frame_at > now + timeout {
+            thread::sleep(timeout);
+            self.pending = Some(frame);
+            return Ok(None);
+        }
+        thread::sleep(self.next_frame_at.saturating_duration_since(now));
+        if now.saturating_duration_since(self.next_frame_at) > MAX_LAG {
+            self.next_frame_at = now;
+        }
+        Ok(Some(frame))
+    }
+}
+
+/// Server arguments for `speedforge replay <recording> [--speed <x>] [--once] [options]`
+pub fn server_args(args: &[String]) -> Result<Vec<String>, String> {
+    const USAGE: &str = "Usage: speedforge replay <recording.jsonl|file.ibt> [--speed <x>] [--once] [server options]";
+    let Some(path) = args.first().filter(|arg| !arg.starts_with("--")) else {
+        return Err(USAGE.to_string());
+    };
+    let mut server = vec!["--sim".to_string(), "replay".to_string(), "--replay".to_string(), path.clone()];
+    let mut rest = args[1..].iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--speed" => {
+                let speed = rest.next().ok_or(USAGE)?;
+                server.extend(["--replay-speed".to_string(), speed.clone()]);
+            },
+            "--once" => server.push("--replay-once".to_string()),
+            _ => match arg.strip_prefix("--speed=") {
+                Some(speed) => server.extend(["--replay-speed".to_string(), speed.to_string()]),
+                None => server.push(arg.clone()),
+            },
+        }
+    }
+    Ok(server)
+}
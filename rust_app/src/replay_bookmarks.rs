@@ -0,0 +1,138 @@
+//! Replay bookmarks for notable moments involving the player.
+//!
+//! The iRacing broadcast API can move the replay but has no command to add a marker
+//! to the replay file, so bookmarks are kept here instead: each records the session
+//! number, session time and replay frame, and `{"type":"jump_to_bookmark","index":3}`
+//! moves the sim's replay to it with the ReplaySearchSessionTime broadcast.
+
+use crate::derived::DerivedChannel;
+use crate::events;
+use crate::protocol;
+use crate::sim_commands;
+use crate::telemetry_fields::TelemetryData;
+use crate::websocket_server::RequestHandler;
+use serde::{Serialize, Deserialize};
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+
+// Rewind this far before the moment so the lead-up is visible
+const LEAD_IN_S: f64 = 5.0;
+
+/// A moment to come back to in the replay
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ReplayBookmark {
+    pub index: usize,
+    pub kind: String,
+    pub description: String,
+    pub session_num: i32,
+    pub session_time: f64,
+    pub replay_frame_num: i32,
+}
+
+/// Bookmarks shared between the telemetry thread and the server
+#[derive(Clone, Default)]
+pub struct ReplayBookmarks {
+    bookmarks: Arc<Mutex<Vec<ReplayBookmark>>>,
+}
+
+impl ReplayBookmarks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn add(&self, kind: &str, description: String, data: &TelemetryData) -> Option<ReplayBookmark> {
+        let mut bookmarks = self.bookmarks.lock().ok()?;
+        let bookmark = ReplayBookmark {
+            index: bookmarks.len(),
+            kind: kind.to_string(),
+            description,
+            session_num: data.session_num,
+            session_time: data.SessionTime as f64,
+            replay_frame_num: data.replay_frame_num,
+        };
+        bookmarks.push(bookmark.clone());
+        Some(bookmark)
+    }
+
+    fn jump(&self, index: usize) -> Result<ReplayBookmark, String> {
+        let bookmark = self
+            .bookmarks
+            .lock()
+            .map_err(|_| "Bookmarks unavailable".to_string())?
+            .get(index)
+            .cloned()
+            .ok_or_else(|| format!("No bookmark {}", index))?;
+        sim_commands::replay_search_session_time(bookmark.session_num, bookmark.session_time - LEAD_IN_S)?;
+        Ok(bookmark)
+    }
+}
+
+impl RequestHandler for ReplayBookmarks {
+    /// `{"type":"get_replay_bookmarks"}` or `{"type":"jump_to_bookmark","index":3}`
+    fn handle(&self, request: &Value) -> Value {
+        match request.get("type").and_then(Value::as_str) {
+            Some("jump_to_bookmark") => {
+                let Some(index) = request.get("index").and_then(Value::as_u64) else {
+                    return protocol::error(protocol::ERROR_INVALID_REQUEST, "Missing index", Some("jump_to_bookmark"));
+                };
+                match self.jump(index as usize) {
+                    Ok(bookmark) => json!({ "type": "jump_to_bookmark", "bookmark": bookmark }),
+                    Err(e) => protocol::error(protocol::ERROR_FAILED, e, Some("jump_to_bookmark")),
+                }
+            },
+            _ => {
+                let bookmarks = self.bookmarks.lock().map(|b| b.clone()).unwrap_or_default();
+                json!({ "type": "replay_bookmarks", "bookmarks": bookmarks })
+            },
+        }
+    }
+}
+
+/// Derived channel that bookmarks the player's incidents and position changes
+pub struct ReplayBookmarker {
+    bookmarks: ReplayBookmarks,
+    last_incidents: Option<i32>,
+}
+
+impl ReplayBookmarker {
+    pub fn new(bookmarks: ReplayBookmarks) -> Self {
+        Self {
+            bookmarks,
+            last_incidents: None,
+        }
+    }
+}
+
+impl DerivedChannel for ReplayBookmarker {
+    fn name(&self) -> &'static str {
+        "replay_bookmarks"
+    }
+
+    fn dependencies(&self) -> &'static [&'static str] {
+        &["overtakes"]
+    }
+
+    fn compute(&mut self, data: &mut TelemetryData) {
+        let mut moments = Vec::new();
+        if let Some(last) = self.last_incidents.replace(data.incident_count) && data.incident_count > last {
+            moments.push(("incident", format!("Incident ({}x)", data.incident_count - last)));
+        }
+        let player = data.player_car_idx as i64;
+        for event in data.events.iter().filter(|event| event.kind == "overtake") {
+            let car = event.data["car_idx"].as_i64();
+            let passed = event.data["passed_car_idx"].as_i64();
+            if car == Some(player) {
+                moments.push(("overtake", format!("Passed #{}", passed.unwrap_or(-1))));
+            } else if passed == Some(player) {
+                moments.push(("overtake", format!("Passed by #{}", car.unwrap_or(-1))));
+            }
+        }
+
+        for (kind, description) in moments {
+            if let Some(bookmark) = self.bookmarks.add(kind, description, data) {
+                let details = json!(bookmark);
+                events::emit(data, "replay_bookmark", details);
+            }
+        }
+    }
+}
@@ -0,0 +1,179 @@
+//! Expected restart order while a caution is out.
+//!
+//! Follows the usual oval procedure: lead lap cars that stay out keep their running order,
+//! lead lap cars that pit follow in the order they leave pit road, then the free pass car,
+//! the wave-arounds (lapped cars that stayed out) and finally lapped cars that pitted.
+//! The running order and laps down are frozen when the caution comes out.
+
+use crate::derived::DerivedChannel;
+use crate::race_phase::Phase;
+use crate::telemetry_fields::TelemetryData;
+use serde::{Serialize, Deserialize};
+
+/// How a car got its restart slot
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RestartStatus {
+    StayedOut,
+    Pitted,
+    /// On pit road now; placed by pit entry order until it leaves
+    InPits,
+    FreePass,
+    WaveAround,
+    LappedPitted,
+}
+
+/// One car's place in the restart line
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RestartSlot {
+    pub car_idx: usize,
+    pub restart_position: usize,
+    /// Position when the caution came out
+    pub caution_position: i32,
+    /// Laps down to the leader when the caution came out
+    pub laps_down: i32,
+    pub status: RestartStatus,
+}
+
+/// Restart line up while a caution is out
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct RestartOrder {
+    pub cars: Vec<RestartSlot>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub free_pass_car_idx: Option<usize>,
+}
+
+#[derive(Clone, Debug)]
+struct CautionCar {
+    car_idx: usize,
+    position: i32,
+    laps_down: i32,
+    /// Order of pit entry and exit during this caution
+    pit_entry: Option<usize>,
+    pit_exit: Option<usize>,
+    on_pit_road: bool,
+}
+
+/// Derived channel that publishes `restart_order` during cautions
+pub struct RestartOrderResolver {
+    /// Running order frozen when the caution came out; None while green
+    caution: Option<Vec<CautionCar>>,
+    pit_events: usize,
+}
+
+impl RestartOrderResolver {
+    pub fn new() -> Self {
+        Self {
+            caution: None,
+            pit_events: 0,
+        }
+    }
+
+    fn freeze(data: &TelemetryData) -> Option<Vec<CautionCar>> {
+        let positions = data.CarIdxPosition.as_ref()?;
+        let laps = data.CarIdxLapCompleted.as_ref()?;
+        let lap_dist = data.CarIdxLapDistPct.as_ref()?;
+        let progress = |idx: usize| laps.get(idx).copied().unwrap_or(0) as f32 + lap_dist.get(idx).copied().unwrap_or(0.0);
+        let on_pit_road = |idx: usize| data.CarIdxOnPitRoad.as_ref().and_then(|p| p.get(idx)).copied().unwrap_or(false);
+
+        let mut cars: Vec<usize> = (0..positions.len())
+            .filter(|&idx| positions[idx] > 0 && lap_dist.get(idx).is_some_and(|&pct| pct >= 0.0))
+            .collect();
+        cars.sort_by_key(|&idx| positions[idx]);
+        let leader = progress(*cars.first()?);
+        Some(
+            cars.into_iter()
+                .map(|idx| CautionCar {
+                    car_idx: idx,
+                    position: positions[idx],
+                    laps_down: (leader - progress(idx)).floor().max(0.0) as i32,
+                    pit_entry: None,
+                    pit_exit: None,
+                    on_pit_road: on_pit_road(idx),
+                })
+                .collect(),
+        )
+    }
+}
+
+impl DerivedChannel for RestartOrderResolver {
+    fn name(&self) -> &'static str {
+        "restart_order"
+    }
+
+    fn dependencies(&self) -> &'static [&'static str] {
+        &["race_phase", "gaps"]
+    }
+
+    fn compute(&mut self, data: &mut TelemetryData) {
+        if data.race_clock.session_reset || data.race_phase.is_none_or(|phase| phase.phase != Phase::Caution) {
+            self.caution = None;
+            return;
+        }
+        if self.caution.is_none() {
+            self.caution = Self::freeze(data);
+            self.pit_events = 0;
+        }
+        let Some(cars) = self.caution.as_mut() else {
+            return;
+        };
+
+        for car in cars.iter_mut() {
+            let in_pits = data.CarIdxOnPitRoad.as_ref().and_then(|p| p.get(car.car_idx)).copied().unwrap_or(false);
+            if in_pits && !car.on_pit_road {
+                self.pit_events += 1;
+                car.pit_entry = Some(self.pit_events);
+                car.pit_exit = None;
+            } else if !in_pits && car.on_pit_road && car.pit_entry.is_some() {
+                self.pit_events += 1;
+                car.pit_exit = Some(self.pit_events);
+            }
+            car.on_pit_road = in_pits;
+        }
+
+        // The first lapped car that stays out gets its lap back
+        let free_pass = cars.iter().find(|car| car.laps_down > 0 && car.pit_entry.is_none()).map(|car| car.car_idx);
+        let status = |car: &CautionCar| match (car.laps_down > 0, car.pit_entry.is_some()) {
+            (false, false) => RestartStatus::StayedOut,
+            (false, true) if car.pit_exit.is_some() => RestartStatus::Pitted,
+            (false, true) => RestartStatus::InPits,
+            (true, false) if Some(car.car_idx) == free_pass => RestartStatus::FreePass,
+            (true, false) => RestartStatus::WaveAround,
+            (true, true) => RestartStatus::LappedPitted,
+        };
+        let mut order: Vec<&CautionCar> = cars.iter().collect();
+        order.sort_by_key(|car| {
+            let group = match status(car) {
+                RestartStatus::StayedOut => 0,
+                RestartStatus::Pitted => 1,
+                RestartStatus::InPits => 2,
+                RestartStatus::FreePass => 3,
+                RestartStatus::WaveAround => 4,
+                RestartStatus::LappedPitted => 5,
+            };
+            // Pitted cars line up by pit exit, cars still in the pits by entry
+            let within = match status(car) {
+                RestartStatus::Pitted => car.pit_exit.unwrap_or(0),
+                RestartStatus::InPits => car.pit_entry.unwrap_or(0),
+                RestartStatus::LappedPitted => car.pit_exit.or(car.pit_entry).unwrap_or(0),
+                _ => car.position as usize,
+            };
+            (group, within)
+        });
+
+        data.restart_order = Some(RestartOrder {
+            cars: order
+                .iter()
+                .enumerate()
+                .map(|(slot, car)| RestartSlot {
+                    car_idx: car.car_idx,
+                    restart_position: slot + 1,
+                    caution_position: car.position,
+                    laps_down: car.laps_down,
+                    status: status(car),
+                })
+                .collect(),
+            free_pass_car_idx: free_pass,
+        });
+    }
+}
@@ -0,0 +1,204 @@
+//! Disk space management for recordings and session bundles.
+//!
+//! A janitor thread (or `speedforge prune`) deletes files from the sessions folder and its
+//! `exports` folder (see auto_export.rs) that are older than the maximum age, then the
+//! oldest files until both fit the size limit. The recording still being written is never
+//! deleted, and with `keep_best_laps` neither is a recording holding the best lap for its
+//! track and car. .ibt files belong to iRacing and are left alone, as is the driver and
+//! metadata JSON, which stays small.
+
+use crate::auto_export;
+use crate::session_compare::SessionSummary;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// How often the janitor runs
+pub const JANITOR_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+// Recordings and bundles made by speedforge
+const MANAGED_EXTENSIONS: &[&str] = &["jsonl", "zip"];
+// Bundles and CSV files in the exports folder
+const EXPORT_EXTENSIONS: &[&str] = &["zip", "csv"];
+
+/// Limits for the sessions folder; nothing is deleted while both limits are unset
+#[derive(Clone, Debug, Default)]
+pub struct RetentionPolicy {
+    pub max_age_days: Option<f32>,
+    pub max_total_mb: Option<f32>,
+    /// Never delete the recording holding the best lap for a track and car
+    pub keep_best_laps: bool,
+}
+
+struct ManagedFile {
+    path: PathBuf,
+    size: u64,
+    modified: SystemTime,
+}
+
+/// Outcome of a pass
+#[derive(Debug, Default)]
+pub struct PruneReport {
+    pub deleted: Vec<PathBuf>,
+    pub freed_bytes: u64,
+    pub kept_bytes: u64,
+}
+
+// Limit given with `flag`; None when the flag isn't given
+fn limit_value(args: &[String], flag: &str, unit: &str) -> Result<Option<f32>, String> {
+    match crate::arg_value(args, flag) {
+        Some(value) => match value.parse::<f32>() {
+            Ok(limit) if limit.is_finite() && limit >= 0.0 => Ok(Some(limit)),
+            _ => Err(format!("{} must be a number of {}, not '{}'", flag, unit, value)),
+        },
+        None if args.iter().any(|arg| arg == flag) => Err(format!("{} needs a number of {}", flag, unit)),
+        None => Ok(None),
+    }
+}
+
+// Files in `dir` with one of `extensions`
+fn list_files(dir: &Path, extensions: &[&str]) -> Result<Vec<ManagedFile>, String> {
+    let listing = fs::read_dir(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    Ok(listing
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let path = entry.path();
+            let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+            if !extensions.contains(&extension.as_str()) {
+                return None;
+            }
+            let meta = entry.metadata().ok()?;
+            if !meta.is_file() {
+                return None;
+            }
+            Some(ManagedFile { path, size: meta.len(), modified: meta.modified().ok()? })
+        })
+        .collect())
+}
+
+impl RetentionPolicy {
+    /// --retention-max-age-days <days>, --retention-max-size-mb <mb> and --keep-best-laps
+    pub fn from_args(args: &[String]) -> Result<Self, String> {
+        Ok(Self {
+            max_age_days: limit_value(args, "--retention-max-age-days", "days")?,
+            max_total_mb: limit_value(args, "--retention-max-size-mb", "MB")?,
+            keep_best_laps: args.iter().any(|arg| arg == "--keep-best-laps"),
+        })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.max_age_days.is_some() || self.max_total_mb.is_some()
+    }
+
+    /// Apply the policy to `dir`, leaving `in_use` (the recording being written) alone; with
+    /// `dry_run` the report lists what would be deleted
+    pub fn prune(&self, dir: &Path, dry_run: bool, in_use: Option<&Path>) -> Result<PruneReport, String> {
+        let mut files = list_files(dir, MANAGED_EXTENSIONS)?;
+        let exports = dir.join(auto_export::EXPORTS_DIR);
+        if exports.is_dir() {
+            files.extend(list_files(&exports, EXPORT_EXTENSIONS)?);
+        }
+        // Oldest first, so the size limit removes those first
+        files.sort_by_key(|file| file.modified);
+
+        let mut protected = if self.keep_best_laps { best_lap_recordings(&files) } else { HashSet::new() };
+        protected.extend(in_use.map(Path::to_path_buf));
+        let now = SystemTime::now();
+        let max_age = self.max_age_days.map(|days| Duration::from_secs_f32(days.max(0.0) * 86_400.0));
+        let max_bytes = self.max_total_mb.map(|mb| (mb.max(0.0) * 1024.0 * 1024.0) as u64);
+        let mut total: u64 = files.iter().map(|file| file.size).sum();
+
+        let mut report = PruneReport::default();
+        for file in &files {
+            if protected.contains(&file.path) {
+                continue;
+            }
+            let expired = max_age.is_some_and(|max_age| now.duration_since(file.modified).unwrap_or_default() > max_age);
+            let over_size = max_bytes.is_some_and(|max_bytes| total > max_bytes);
+            if !expired && !over_size {
+                continue;
+            }
+            if !dry_run && let Err(e) = fs::remove_file(&file.path) {
+                tracing::error!("Failed to delete {}: {}", file.path.display(), e);
+                continue;
+            }
+            total -= file.size;
+            report.freed_bytes += file.size;
+            report.deleted.push(file.path.clone());
+        }
+        report.kept_bytes = total;
+        Ok(report)
+    }
+
+    /// Run `prune` on `dir` now and then every JANITOR_INTERVAL on a background thread;
+    /// `in_use` names the recording being written at the time
+    pub fn start_janitor(self, dir: PathBuf, in_use: impl Fn() -> Option<PathBuf> + Send + 'static) {
+        std::thread::spawn(move || loop {
+            match self.prune(&dir, false, in_use().as_deref()) {
+                Ok(report) if !report.deleted.is_empty() => tracing::info!(
+                    "Deleted {} files ({:.1} MB), {:.1} MB kept",
+                    report.deleted.len(), mb(report.freed_bytes), mb(report.kept_bytes)),
+                Ok(_) => {},
+                Err(e) => tracing::error!("{}", e),
+            }
+            std::thread::sleep(JANITOR_INTERVAL);
+        });
+    }
+}
+
+fn mb(bytes: u64) -> f64 {
+    bytes as f64 / (1024.0 * 1024.0)
+}
+
+// Recordings holding the best green-flag lap for each track and car
+fn best_lap_recordings(files: &[ManagedFile]) -> HashSet<PathBuf> {
+    let mut best: HashMap<(String, String), (f32, &Path)> = HashMap::new();
+    for file in files.iter().filter(|file| file.path.extension().is_some_and(|ext| ext == "jsonl")) {
+        let Ok(summary) = SessionSummary::from_file(&file.path) else {
+            continue;
+        };
+        let Some(lap) = summary.best_lap else {
+            continue;
+        };
+        let key = (summary.track, summary.car);
+        if best.get(&key).is_none_or(|(time, _)| lap.lap_time < *time) {
+            best.insert(key, (lap.lap_time, file.path.as_path()));
+        }
+    }
+    best.into_values().map(|(_, path)| path.to_path_buf()).collect()
+}
+
+/// `speedforge prune --sessions-dir <dir> [--retention-max-age-days <days>] [--retention-max-size-mb <mb>] [--keep-best-laps] [--dry-run]`
+pub fn run_prune_cli(args: &[String]) -> i32 {
+    let policy = match RetentionPolicy::from_args(args) {
+        Ok(policy) => policy,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 2;
+        }
+    };
+    let Some(dir) = crate::arg_value(args, "--sessions-dir") else {
+        eprintln!("Usage: speedforge prune --sessions-dir <dir> [--retention-max-age-days <days>] [--retention-max-size-mb <mb>] [--keep-best-laps] [--dry-run]");
+        return 2;
+    };
+    if !policy.is_enabled() {
+        eprintln!("Nothing to do: set --retention-max-age-days and/or --retention-max-size-mb");
+        return 2;
+    }
+    let dry_run = args.iter().any(|arg| arg == "--dry-run");
+
+    match policy.prune(Path::new(&dir), dry_run, None) {
+        Ok(report) => {
+            for path in &report.deleted {
+                println!("{} {}", if dry_run { "Would delete" } else { "Deleted" }, path.display());
+            }
+            println!("{:.1} MB freed, {:.1} MB kept", mb(report.freed_bytes), mb(report.kept_bytes));
+            0
+        },
+        Err(e) => {
+            eprintln!("Failed to prune: {}", e);
+            1
+        }
+    }
+}
@@ -0,0 +1,618 @@
+//! Scripted race scenarios.
+//!
+//! A scenario describes a field of cars and a list of timed events in YAML:
+//!
+//! ```yaml
+//! track_name: Scenario Speedway
+//! track_length_m: 4000
+//! lap_time_s: 90
+//! cars: 20
+//! race_laps: 15
+//! events:
+//!   - { lap: 0, action: start }
+//!   - { lap: 4, action: pit, car_idx: 3, stop_s: 25 }
+//!   - { lap: 7, action: yellow }
+//!   - { lap: 9, action: green }
+//!   - { minute: 20, action: rain, wetness: 4 }
+//! expect_events: [overtake]
+//! ```
+//!
+//! Laps in triggers are laps completed by the leader. The engine drives every car around
+//! the track at its own pace and produces iRacing-like frames (CarIdx arrays, session
+//! state and flags, session info YAML). It runs as a telemetry source (`--sim scenario
+//! --scenario <file>`) for frontend demos, looping at the end, or headless through
+//! `speedforge run-scenario <file>`, which runs the derived pipeline over the whole race
+//! as fast as possible and fails when an expected event never happens.
+//! `cargo test` runs the scenarios in tests/scenarios this way.
+
+use crate::determinism;
+use crate::events::TelemetryEvent;
+use crate::telemetry_fields::{TelemetryData, FLAG_CAUTION, FLAG_CAUTION_WAVING, FLAG_CHECKERED, FLAG_GREEN};
+use crate::telemetry_source::{TelemetrySource, CAP_ALL};
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+// irsdk_SessionState
+const STATE_PARADE_LAPS: i32 = 3;
+const STATE_RACING: i32 = 4;
+const STATE_CHECKERED: i32 = 5;
+const STATE_COOL_DOWN: i32 = 6;
+// irsdk_PaceMode
+const PACE_SINGLE_FILE_START: i32 = 0;
+const PACE_SINGLE_FILE_RESTART: i32 = 2;
+const PACE_NOT_PACING: i32 = 4;
+// irsdk_TrkLoc
+const TRACK_IN_PIT_STALL: i32 = 1;
+const TRACK_APROACHING_PITS: i32 = 2;
+const TRACK_ON_TRACK: i32 = 3;
+
+// Speed relative to racing pace behind the pace car and in the pit lane
+const PACING_FACTOR: f64 = 0.6;
+const PIT_LANE_FACTOR: f64 = 0.35;
+// Pit entry and exit around the start/finish line, as lap fractions
+const PIT_ENTRY_PCT: f64 = 0.97;
+const PIT_EXIT_PCT: f64 = 0.05;
+// Grid spacing as a lap fraction
+const GRID_GAP: f64 = 0.004;
+// Time after the checkered flag before cool down, and after cool down before the end
+const CHECKERED_S: f32 = 60.0;
+const COOL_DOWN_S: f32 = 30.0;
+
+/// Something that happens during the scenario
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Action {
+    /// Green flag; without one the race starts right away
+    Start,
+    Yellow,
+    Green,
+    Checkered,
+    /// The car pits at the end of its current lap
+    Pit {
+        car_idx: usize,
+        #[serde(default = "default_stop_s")]
+        stop_s: f32,
+    },
+    /// Track wetness from 1 (dry) to 7
+    Rain {
+        #[serde(default = "default_wetness")]
+        wetness: i32,
+    },
+    Dry,
+    Weather {
+        air_temp_c: Option<f32>,
+        track_temp_c: Option<f32>,
+    },
+}
+
+fn default_stop_s() -> f32 {
+    30.0
+}
+
+fn default_wetness() -> i32 {
+    4
+}
+
+/// An action and when it fires: at a leader lap, a minute or a session time
+#[derive(Clone, Debug, Deserialize)]
+pub struct ScenarioEvent {
+    pub lap: Option<i32>,
+    pub minute: Option<f32>,
+    pub time_s: Option<f32>,
+    #[serde(flatten)]
+    pub action: Action,
+}
+
+impl ScenarioEvent {
+    fn due(&self, leader_laps: i32, session_time: f32) -> bool {
+        self.lap.is_some_and(|lap| leader_laps >= lap)
+            || self.minute.is_some_and(|minute| session_time >= minute * 60.0)
+            || self.time_s.is_some_and(|time| session_time >= time)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct Scenario {
+    pub track_name: String,
+    pub track_length_m: f32,
+    /// Racing lap time of the fastest car
+    pub lap_time_s: f32,
+    pub cars: usize,
+    pub player_car_idx: usize,
+    /// Each car is this much slower than the one gridded ahead of it, as a fraction
+    pub pace_spread: f32,
+    /// 0 for a race that only ends with a checkered event
+    pub race_laps: i32,
+    pub rate_hz: f32,
+    pub fuel_start_l: f32,
+    pub fuel_per_lap_l: f32,
+    pub air_temp_c: f32,
+    pub track_temp_c: f32,
+    pub events: Vec<ScenarioEvent>,
+    /// Event kinds `run-scenario` requires the analytics to have emitted
+    pub expect_events: Vec<String>,
+}
+
+impl Default for Scenario {
+    fn default() -> Self {
+        Self {
+            track_name: "Scenario Speedway".to_string(),
+            track_length_m: 4000.0,
+            lap_time_s: 90.0,
+            cars: 20,
+            player_car_idx: 0,
+            pace_spread: 0.003,
+            race_laps: 10,
+            rate_hz: 60.0,
+            fuel_start_l: 60.0,
+            fuel_per_lap_l: 2.5,
+            air_temp_c: 22.0,
+            track_temp_c: 30.0,
+            events: Vec::new(),
+            expect_events: Vec::new(),
+        }
+    }
+}
+
+impl Scenario {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let scenario: Scenario = serde_yaml::from_str(&fs::read_to_string(path)?)?;
+        if scenario.cars == 0 || scenario.player_car_idx >= scenario.cars {
+            return Err(format!("{}: player_car_idx must be below cars", path.display()).into());
+        }
+        if scenario.lap_time_s <= 0.0 || scenario.rate_hz <= 0.0 {
+            return Err(format!("{}: lap_time_s and rate_hz must be positive", path.display()).into());
+        }
+        Ok(scenario)
+    }
+
+    // Session info YAML in the shape the SDK publishes, with what the analytics read
+    fn session_info(&self) -> String {
+        let mut yaml = format!(
+            "WeekendInfo:\n TrackName: {}\n TrackDisplayName: {}\n TrackLength: {:.2} km\n TrackID: 0\n\
+             SessionInfo:\n Sessions:\n - SessionNum: 0\n   SessionType: Race\n   SessionLaps: {}\n\
+             DriverInfo:\n DriverCarIdx: {}\n Drivers:\n",
+            self.track_name.to_lowercase().replace(' ', "_"),
+            self.track_name,
+            self.track_length_m / 1000.0,
+            if self.race_laps > 0 { self.race_laps.to_string() } else { "unlimited".to_string() },
+            self.player_car_idx,
+        );
+        for idx in 0..self.cars {
+            yaml.push_str(&format!(
+                " - CarIdx: {}\n   UserName: Driver {}\n   UserID: {}\n   CarNumber: \"{}\"\n   CarID: 1\n   CarScreenName: Scenario Car\n   CarClassID: 1\n",
+                idx, idx + 1, 1000 + idx, idx + 1,
+            ));
+        }
+        yaml
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+struct SimCar {
+    /// Laps driven, negative on the grid
+    progress: f64,
+    pace_s: f32,
+    pit_pending: Option<f32>,
+    /// Remaining stationary time while in the pit stall
+    stop_remaining: f32,
+    on_pit_road: bool,
+    in_stall: bool,
+    lap_start: f32,
+    last_lap_time: f32,
+    best_lap_time: f32,
+    finished: bool,
+}
+
+impl SimCar {
+    // -1 on the grid, as iRacing reports it
+    fn laps_completed(&self) -> i32 {
+        self.progress.floor() as i32
+    }
+
+    fn lap_dist_pct(&self) -> f64 {
+        self.progress.rem_euclid(1.0)
+    }
+}
+
+/// Scenario state; each `step` advances it by one frame
+pub struct ScenarioSource {
+    scenario: Scenario,
+    session_info: String,
+    cars: Vec<SimCar>,
+    fired: Vec<bool>,
+    session_time: f32,
+    session_state: i32,
+    caution: bool,
+    track_wetness: i32,
+    air_temp_c: f32,
+    track_temp_c: f32,
+    fuel_l: f32,
+    /// Session time the checkered flag came out
+    checkered_at: Option<f32>,
+    cool_down_at: Option<f32>,
+    /// Pace frames to wall time, for demos
+    real_time: bool,
+    next_frame_at: Instant,
+}
+
+impl ScenarioSource {
+    pub fn new(scenario: Scenario, real_time: bool) -> Self {
+        let session_info = scenario.session_info();
+        let mut source = Self {
+            scenario,
+            session_info,
+            cars: Vec::new(),
+            fired: Vec::new(),
+            session_time: 0.0,
+            session_state: STATE_PARADE_LAPS,
+            caution: false,
+            track_wetness: 1,
+            air_temp_c: 0.0,
+            track_temp_c: 0.0,
+            fuel_l: 0.0,
+            checkered_at: None,
+            cool_down_at: None,
+            real_time,
+            next_frame_at: Instant::now(),
+        };
+        source.reset();
+        source
+    }
+
+    fn reset(&mut self) {
+        let scenario = &self.scenario;
+        self.cars = (0..scenario.cars)
+            .map(|idx| SimCar {
+                progress: -(idx as f64 + 1.0) * GRID_GAP,
+                pace_s: scenario.lap_time_s * (1.0 + scenario.pace_spread * idx as f32),
+                ..SimCar::default()
+            })
+            .collect();
+        self.fired = vec![false; scenario.events.len()];
+        self.session_time = 0.0;
+        self.session_state = STATE_PARADE_LAPS;
+        self.caution = false;
+        self.track_wetness = 1;
+        self.air_temp_c = scenario.air_temp_c;
+        self.track_temp_c = scenario.track_temp_c;
+        self.fuel_l = scenario.fuel_start_l;
+        self.checkered_at = None;
+        self.cool_down_at = None;
+        // Without a scripted start the race goes green immediately
+        if !scenario.events.iter().any(|event| matches!(event.action, Action::Start)) {
+            self.session_state = STATE_RACING;
+        }
+    }
+
+    /// True once the cool down is over
+    pub fn finished(&self) -> bool {
+        self.cool_down_at.is_some_and(|at| self.session_time - at >= COOL_DOWN_S)
+    }
+
+    fn leader_laps(&self) -> i32 {
+        self.cars.iter().map(SimCar::laps_completed).max().unwrap_or(0)
+    }
+
+    fn apply(&mut self, action: Action) {
+        match action {
+            Action::Start | Action::Green => {
+                self.session_state = STATE_RACING;
+                self.caution = false;
+            },
+            Action::Yellow => self.caution = true,
+            Action::Checkered => self.throw_checkered(),
+            Action::Pit { car_idx, stop_s } => {
+                if let Some(car) = self.cars.get_mut(car_idx) {
+                    car.pit_pending = Some(stop_s);
+                }
+            },
+            Action::Rain { wetness } => self.track_wetness = wetness.clamp(1, 7),
+            Action::Dry => self.track_wetness = 1,
+            Action::Weather { air_temp_c, track_temp_c } => {
+                self.air_temp_c = air_temp_c.unwrap_or(self.air_temp_c);
+                self.track_temp_c = track_temp_c.unwrap_or(self.track_temp_c);
+            },
+        }
+    }
+
+    fn throw_checkered(&mut self) {
+        if self.checkered_at.is_none() {
+            self.session_state = STATE_CHECKERED;
+            self.caution = false;
+            self.checkered_at = Some(self.session_time);
+        }
+    }
+
+    /// Advance by one frame
+    pub fn step(&mut self) {
+        let dt = 1.0 / self.scenario.rate_hz;
+        self.session_time += dt;
+
+        let leader_laps = self.leader_laps();
+        for i in 0..self.scenario.events.len() {
+            if !self.fired[i] && self.scenario.events[i].due(leader_laps, self.session_time) {
+                self.fired[i] = true;
+                let action = self.scenario.events[i].action.clone();
+                self.apply(action);
+            }
+        }
+        if self.scenario.race_laps > 0 && leader_laps >= self.scenario.race_laps {
+            self.throw_checkered();
+        }
+        if let Some(at) = self.checkered_at {
+            let everyone_in = self.cars.iter().all(|car| car.finished);
+            if self.cool_down_at.is_none() && (everyone_in || self.session_time - at >= CHECKERED_S) {
+                self.session_state = STATE_COOL_DOWN;
+                self.cool_down_at = Some(self.session_time);
+            }
+        }
+
+        let pacing = self.session_state == STATE_PARADE_LAPS || self.caution;
+        let wet_factor = 1.0 - 0.02 * (self.track_wetness - 1) as f64;
+        let session_time = self.session_time;
+        let checkered = self.checkered_at.is_some();
+        for car in &mut self.cars {
+            if car.stop_remaining > 0.0 {
+                car.stop_remaining -= dt;
+                continue;
+            }
+            car.in_stall = false;
+            let factor = if car.on_pit_road {
+                PIT_LANE_FACTOR
+            } else if pacing || car.finished {
+                PACING_FACTOR
+            } else {
+                wet_factor
+            };
+            let before = car.progress;
+            car.progress += dt as f64 / car.pace_s as f64 * factor;
+
+            let crossed_line = before.floor() < car.progress.floor() && car.progress >= 1.0;
+            if crossed_line {
+                let lap_time = session_time - car.lap_start;
+                if car.progress >= 2.0 {
+                    car.last_lap_time = lap_time;
+                    if car.best_lap_time <= 0.0 || lap_time < car.best_lap_time {
+                        car.best_lap_time = lap_time;
+                    }
+                }
+                car.lap_start = session_time;
+                if checkered {
+                    car.finished = true;
+                }
+            }
+
+            // Pit entry before the line, the stall just after it and the exit further on
+            let dist = car.lap_dist_pct();
+            if car.pit_pending.is_some() && !car.on_pit_road && dist >= PIT_ENTRY_PCT {
+                car.on_pit_road = true;
+            }
+            if car.on_pit_road && crossed_line && let Some(stop_s) = car.pit_pending.take() {
+                car.stop_remaining = stop_s;
+                car.in_stall = true;
+            }
+            if car.on_pit_road && car.pit_pending.is_none() && !car.in_stall && dist >= PIT_EXIT_PCT && dist < PIT_ENTRY_PCT {
+                car.on_pit_road = false;
+            }
+        }
+
+        let player = &self.cars[self.scenario.player_car_idx];
+        if player.in_stall {
+            self.fuel_l = self.scenario.fuel_start_l;
+        } else if player.progress > 0.0 {
+            let driven = dt as f64 / player.pace_s as f64;
+            self.fuel_l = (self.fuel_l - (driven * self.scenario.fuel_per_lap_l as f64) as f32).max(0.0);
+        }
+    }
+
+    /// The current state as a frame
+    pub fn frame(&self) -> TelemetryData {
+        let scenario = &self.scenario;
+        let mut data = TelemetryData::default();
+        data.sim = "scenario".to_string();
+        data.capabilities = CAP_ALL;
+        data.SessionTime = self.session_time;
+        data.session_info = self.session_info.clone();
+        data.session_state = self.session_state;
+        data.pace_mode = match (self.session_state, self.caution) {
+            (STATE_PARADE_LAPS, _) => PACE_SINGLE_FILE_START,
+            (_, true) => PACE_SINGLE_FILE_RESTART,
+            _ => PACE_NOT_PACING,
+        };
+        data.session_flags = match self.session_state {
+            STATE_CHECKERED | STATE_COOL_DOWN => FLAG_CHECKERED,
+            _ if self.caution => FLAG_CAUTION | FLAG_CAUTION_WAVING,
+            STATE_RACING => FLAG_GREEN,
+            _ => 0,
+        };
+        data.track_wetness = self.track_wetness;
+        data.skies = if self.track_wetness > 1 { "Overcast".to_string() } else { "Clear".to_string() };
+        data.air_temp_c = self.air_temp_c;
+        data.track_temp_c = self.track_temp_c;
+        let leader_laps = self.leader_laps();
+        data.session_laps_remain = if scenario.race_laps > 0 { (scenario.race_laps - leader_laps).max(0) } else { 32767 };
+
+        // Running order by distance covered
+        let mut order: Vec<usize> = (0..self.cars.len()).collect();
+        order.sort_by(|&a, &b| self.cars[b].progress.total_cmp(&self.cars[a].progress));
+        let mut positions = vec![0; self.cars.len()];
+        for (rank, &idx) in order.iter().enumerate() {
+            positions[idx] = rank as i32 + 1;
+        }
+        let leader = &self.cars[order[0]];
+
+        data.CarIdxPosition = Some(positions.clone());
+        data.CarIdxClassPosition = Some(positions.clone());
+        data.CarIdxClass = Some(vec![1; self.cars.len()]);
+        data.CarIdxLapDistPct = Some(self.cars.iter().map(|car| car.lap_dist_pct() as f32).collect());
+        data.CarIdxLap = Some(self.cars.iter().map(|car| car.laps_completed() + 1).collect());
+        data.CarIdxLapCompleted = Some(self.cars.iter().map(SimCar::laps_completed).collect());
+        data.CarIdxOnPitRoad = Some(self.cars.iter().map(|car| car.on_pit_road).collect());
+        data.CarIdxTrackSurface = Some(self.cars.iter().map(|car| match (car.in_stall, car.on_pit_road) {
+            (true, _) => TRACK_IN_PIT_STALL,
+            (false, true) => TRACK_APROACHING_PITS,
+            _ => TRACK_ON_TRACK,
+        }).collect());
+        data.CarIdxEstTime = Some(self.cars.iter().map(|car| car.lap_dist_pct() as f32 * car.pace_s).collect());
+        data.CarIdxF2Time = Some(self.cars.iter().map(|car| ((leader.progress - car.progress) * leader.pace_s as f64) as f32).collect());
+        data.CarIdxLastLapTime = Some(self.cars.iter().map(|car| car.last_lap_time).collect());
+        data.CarIdxBestLapTime = Some(self.cars.iter().map(|car| car.best_lap_time).collect());
+        data.CarIdxGear = Some(vec![4; self.cars.len()]);
+
+        // Player car
+        let idx = scenario.player_car_idx;
+        let player = &self.cars[idx];
+        let pacing = self.session_state == STATE_PARADE_LAPS || self.caution;
+        let speed_ms = if player.stop_remaining > 0.0 {
+            0.0
+        } else {
+            let factor = if player.on_pit_road { PIT_LANE_FACTOR as f32 } else if pacing { PACING_FACTOR as f32 } else { 1.0 };
+            scenario.track_length_m / player.pace_s * factor
+        };
+        data.player_car_idx = idx as i32;
+        data.position = positions[idx];
+        data.lap_completed = player.laps_completed() + 1;
+        data.lap_dist_pct = player.lap_dist_pct() as f32;
+        data.lap_dist = data.lap_dist_pct * scenario.track_length_m;
+        data.current_lap_time = self.session_time - player.lap_start;
+        data.last_lap_time = player.last_lap_time;
+        data.best_lap_time = player.best_lap_time;
+        data.on_pit_road = player.on_pit_road;
+        data.speed_kph = speed_ms * 3.6;
+        data.speed_mph = speed_ms * 2.23694;
+        data.VelocityX = speed_ms;
+        data.rpm = if speed_ms > 0.0 { 4000.0 + speed_ms * 40.0 } else { 900.0 };
+        data.gear_num = if speed_ms > 0.0 { 4 } else { 0 };
+        data.gear = data.gear_num.to_string();
+        data.throttle_pct = if pacing || player.on_pit_road { 35.0 } else { 85.0 };
+        data.fuel_level = self.fuel_l;
+        if scenario.fuel_start_l > 0.0 {
+            data.fuel_pct = self.fuel_l / scenario.fuel_start_l;
+        }
+        data.oil_temp_c = 95.0;
+        data.water_temp_c = 88.0;
+        data.tire_temps_c = [80.0; 4];
+        data
+    }
+}
+
+impl TelemetrySource for ScenarioSource {
+    fn name(&self) -> &'static str {
+        "scenario"
+    }
+
+    fn capabilities(&self) -> u32 {
+        CAP_ALL
+    }
+
+    fn connect(&mut self) -> Result<(), String> {
+        self.reset();
+        self.next_frame_at = Instant::now();
+        Ok(())
+    }
+
+    fn next_frame(&mut self, timeout: Duration) -> Result<Option<TelemetryData>, String> {
+        let mut skipped = 0;
+        if self.real_time {
+            let now = Instant::now();
+            if self.next_frame_at > now + timeout {
+                std::thread::sleep(timeout);
+                return Ok(None);
+            }
+            std::thread::sleep(self.next_frame_at.saturating_duration_since(now));
+            let period = Duration::from_secs_f32(1.0 / self.scenario.rate_hz);
+            self.next_frame_at += period;
+            // After a stall, simulate the missed frames without publishing them so the
+            // next frame is the current one rather than a backlog played out late
+            let behind = now.saturating_duration_since(self.next_frame_at);
+            skipped = (behind.as_secs_f64() / period.as_secs_f64()) as u32;
+            for _ in 0..skipped {
+                self.step();
+            }
+            self.next_frame_at += period * skipped;
+        }
+        // Demos play the scenario over and over
+        if self.finished() {
+            self.reset();
+        }
+        self.step();
+        let mut frame = self.frame();
+        frame.frames_skipped = skipped;
+        Ok(Some(frame))
+    }
+}
+
+/// `speedforge run-scenario <scenario.yaml> [--json]`: run the race through the derived
+/// channels and report the events they raised; exits 1 when an expected event is missing
+pub fn run_cli(args: &[String]) -> i32 {
+    let Some(path) = args.iter().find(|arg| !arg.starts_with("--")) else {
+        eprintln!("Usage: speedforge run-scenario <scenario.yaml> [--json]");
+        return 2;
+    };
+    let json_output = args.iter().any(|arg| arg == "--json");
+    let scenario = match Scenario::load(Path::new(path)) {
+        Ok(scenario) => scenario,
+        Err(e) => {
+            eprintln!("Failed to load scenario: {}", e);
+            return 2;
+        }
+    };
+    let mut pipeline = match determinism::pipeline() {
+        Ok(pipeline) => pipeline,
+        Err(e) => {
+            eprintln!("Failed to build derived channel pipeline: {}", e);
+            return 1;
+        }
+    };
+
+    let expected = scenario.expect_events.clone();
+    let mut source = ScenarioSource::new(scenario, false);
+    let mut events: Vec<TelemetryEvent> = Vec::new();
+    let mut frames = 0usize;
+    let mut last = TelemetryData::default();
+    while !source.finished() {
+        source.step();
+        let mut frame = source.frame();
+        pipeline.evaluate(&mut frame);
+        events.append(&mut frame.events);
+        frames += 1;
+        last = frame;
+    }
+
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for event in &events {
+        *counts.entry(event.kind.as_str()).or_default() += 1;
+    }
+    let missing: Vec<&String> = expected.iter().filter(|kind| !counts.contains_key(kind.as_str())).collect();
+
+    if json_output {
+        let report = json!({
+            "frames": frames,
+            "session_time": last.SessionTime,
+            "event_counts": counts,
+            "events": events,
+            "missing_events": missing,
+            "final_positions": last.CarIdxPosition,
+        });
+        match serde_json::to_string_pretty(&report) {
+            Ok(output) => println!("{}", output),
+            Err(e) => eprintln!("Failed to serialize report: {}", e),
+        }
+    } else {
+        println!("{} frames, {:.0} s of session time", frames, last.SessionTime);
+        for (kind, count) in &counts {
+            println!("  {:<24} {}", kind, count);
+        }
+        for kind in &missing {
+            println!("Missing expected event: {}", kind);
+        }
+    }
+    if missing.is_empty() { 0 } else { 1 }
+}
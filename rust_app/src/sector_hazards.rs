@@ -0,0 +1,129 @@
+use crate::derived::DerivedChannel;
+use crate::telemetry_fields::TelemetryData;
+use serde::{Serialize, Deserialize};
+use std::collections::VecDeque;
+
+/// Number of equal track segments hazards are reported for
+pub const SEGMENT_COUNT: usize = 50;
+
+// irsdk_TrkLoc values from CarIdxTrackSurface
+pub const TRACK_LOC_NOT_IN_WORLD: i32 = -1;
+pub const TRACK_LOC_OFF_TRACK: i32 = 0;
+pub const TRACK_LOC_ON_TRACK: i32 = 3;
+
+// Off-track excursions older than this no longer count towards a segment's hazard
+const HISTORY_WINDOW_S: f32 = 300.0;
+// Recent excursions at which a segment is considered fully hazardous
+const HAZARD_SATURATION: f32 = 5.0;
+
+/// Conditions in one track segment
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct SegmentHazard {
+    pub index: usize,
+    pub start_pct: f32,
+    pub end_pct: f32,
+    /// Cars that left the track here within the history window
+    pub recent_off_tracks: usize,
+    /// Cars currently off the track in this segment
+    pub cars_off_track: Vec<i32>,
+    /// 0..1, recent excursions weighted towards the latest ones
+    pub hazard: f32,
+    /// A car is off track here right now
+    pub yellow: bool,
+}
+
+/// Segments with current or recent off-track activity
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct SectorHazards {
+    pub segment_count: usize,
+    pub segments: Vec<SegmentHazard>,
+}
+
+/// Segment of the lap a position falls in
+pub fn segment_of(lap_dist_pct: f32) -> usize {
+    ((lap_dist_pct.rem_euclid(1.0) * SEGMENT_COUNT as f32) as usize).min(SEGMENT_COUNT - 1)
+}
+
+/// Derived channel that maps off-track excursions of every car onto track segments
+pub struct SectorHazardMap {
+    last_surface: Vec<i32>,
+    /// (session time, segment) of each excursion in the history window
+    excursions: VecDeque<(f32, usize)>,
+}
+
+impl SectorHazardMap {
+    pub fn new() -> Self {
+        Self {
+            last_surface: Vec::new(),
+            excursions: VecDeque::new(),
+        }
+    }
+}
+
+impl DerivedChannel for SectorHazardMap {
+    fn name(&self) -> &'static str {
+        "sector_hazards"
+    }
+
+    fn compute(&mut self, data: &mut TelemetryData) {
+        if data.race_clock.session_reset {
+            self.last_surface.clear();
+            self.excursions.clear();
+        }
+
+        let (Some(surfaces), Some(positions)) = (data.CarIdxTrackSurface.as_ref(), data.CarIdxLapDistPct.as_ref()) else {
+            return;
+        };
+
+        let mut segments: Vec<SegmentHazard> = (0..SEGMENT_COUNT)
+            .map(|index| SegmentHazard {
+                index,
+                start_pct: index as f32 / SEGMENT_COUNT as f32,
+                end_pct: (index + 1) as f32 / SEGMENT_COUNT as f32,
+                ..SegmentHazard::default()
+            })
+            .collect();
+
+        for (idx, (&surface, &pct)) in surfaces.iter().zip(positions.iter()).enumerate() {
+            if surface == TRACK_LOC_NOT_IN_WORLD || pct < 0.0 {
+                continue;
+            }
+            let segment = segment_of(pct);
+            let previous = self.last_surface.get(idx).copied().unwrap_or(TRACK_LOC_NOT_IN_WORLD);
+            // Only leaving the racing surface counts; pit lane transitions don't
+            if surface == TRACK_LOC_OFF_TRACK && previous == TRACK_LOC_ON_TRACK {
+                self.excursions.push_back((data.SessionTime, segment));
+            }
+            if surface == TRACK_LOC_OFF_TRACK {
+                segments[segment].cars_off_track.push(idx as i32);
+                segments[segment].yellow = true;
+            }
+        }
+        self.last_surface = surfaces.clone();
+
+        while self.excursions.front().is_some_and(|&(t, _)| data.SessionTime - t > HISTORY_WINDOW_S) {
+            self.excursions.pop_front();
+        }
+        for &(time, segment) in &self.excursions {
+            let age = (data.SessionTime - time) / HISTORY_WINDOW_S;
+            segments[segment].recent_off_tracks += 1;
+            segments[segment].hazard += (1.0 - age).max(0.0) / HAZARD_SATURATION;
+        }
+
+        let active: Vec<SegmentHazard> = segments
+            .into_iter()
+            .filter(|segment| segment.yellow || segment.recent_off_tracks > 0)
+            .map(|mut segment| {
+                segment.hazard = if segment.yellow { 1.0 } else { segment.hazard.min(1.0) };
+                segment
+            })
+            .collect();
+
+        if !active.is_empty() {
+            data.sector_hazards = Some(SectorHazards {
+                segment_count: SEGMENT_COUNT,
+                segments: active,
+            });
+        }
+    }
+}
@@ -0,0 +1,266 @@
+//! Shareable session bundles.
+//!
+//! `speedforge export-session <recording> --bundle out.zip` packs a recorded session
+//! (one TelemetryData JSON object per line) into a zip that is small enough to pass
+//! around: the session info YAML, lap summaries, events and the telemetry downsampled
+//! to a few frames per second. `speedforge import-session <bundle> --out <recording>`
+//! turns a bundle back into a recording that `compare` and the frontend can open.
+//! With `--encrypt`, the bundle or recording is written encrypted (see encryption.rs);
+//! encrypted inputs are recognized and decrypted either way.
+
+use crate::encryption;
+use crate::events::TelemetryEvent;
+use crate::lap_summary::{LapSummary, LapSummaryBuilder};
+use crate::session_yaml;
+use crate::telemetry_fields::TelemetryData;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::io::{BufRead, Cursor, Read, Write};
+use std::path::Path;
+use zip::write::FileOptions;
+
+/// Bumped when the bundle layout changes
+pub const BUNDLE_VERSION: u32 = 1;
+/// Telemetry frames kept per second of session time
+pub const DEFAULT_RATE_HZ: f32 = 10.0;
+
+const MANIFEST: &str = "manifest.json";
+const SESSION_INFO: &str = "session_info.yaml";
+const LAPS: &str = "laps.json";
+const EVENTS: &str = "events.json";
+const TELEMETRY: &str = "telemetry.jsonl";
+
+/// Description of a bundle, stored as manifest.json
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct BundleManifest {
+    pub version: u32,
+    /// File name of the recording the bundle was made from
+    pub source: String,
+    pub track: String,
+    pub car: String,
+    pub exported_at: String,
+    /// Frames in the original recording
+    pub recorded_frames: usize,
+    /// Frames kept in telemetry.jsonl
+    pub frames: usize,
+    pub rate_hz: f32,
+    pub laps: usize,
+    pub events: usize,
+}
+
+/// Contents of a bundle
+pub struct SessionBundle {
+    pub manifest: BundleManifest,
+    pub session_info: String,
+    pub laps: Vec<LapSummary>,
+    pub events: Vec<TelemetryEvent>,
+    pub frames: Vec<TelemetryData>,
+}
+
+impl SessionBundle {
+    /// Build a bundle from a recording, keeping at most `rate_hz` frames per second
+    pub fn from_recording(path: &Path, rate_hz: f32) -> Result<Self, Box<dyn Error>> {
+        let reader = encryption::open_lines(path)?;
+        let interval = if rate_hz > 0.0 { 1.0 / rate_hz } else { 0.0 };
+        let mut bundle = SessionBundle {
+            manifest: BundleManifest {
+                version: BUNDLE_VERSION,
+                source: path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default(),
+                exported_at: chrono::Local::now().to_rfc3339(),
+                rate_hz,
+                ..Default::default()
+            },
+            session_info: String::new(),
+            laps: Vec::new(),
+            events: Vec::new(),
+            frames: Vec::new(),
+        };
+        let mut builder = LapSummaryBuilder::new();
+        let mut last_kept: Option<f32> = None;
+
+        for (line_number, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let mut frame: TelemetryData = serde_json::from_str(&line)
+                .map_err(|e| format!("{}:{}: {}", path.display(), line_number + 1, e))?;
+            bundle.manifest.recorded_frames += 1;
+
+            // The session info grows during a session (results, joining drivers); keep the latest
+            if !frame.session_info.is_empty() {
+                bundle.session_info = std::mem::take(&mut frame.session_info);
+            }
+            if let Some(lap) = builder.update(&frame) {
+                bundle.laps.push(lap);
+            }
+            bundle.events.append(&mut frame.events);
+
+            // A time going backwards is a new session or a replay jump; start sampling again
+            let due = match last_kept {
+                Some(kept) => frame.SessionTime < kept || frame.SessionTime - kept >= interval,
+                None => true,
+            };
+            if due {
+                last_kept = Some(frame.SessionTime);
+                frame.raw_values.clear();
+                bundle.frames.push(frame);
+            }
+        }
+
+        if bundle.manifest.recorded_frames == 0 {
+            return Err(format!("{} contains no telemetry frames", path.display()).into());
+        }
+        bundle.manifest.track = session_yaml::scalar(&bundle.session_info, "TrackDisplayName").unwrap_or_default().to_string();
+        // The player's car; the first CarScreenName in the YAML is the pace car's
+        let player = session_yaml::scalar(&bundle.session_info, "DriverCarIdx").and_then(|idx| idx.parse::<i32>().ok());
+        bundle.manifest.car = session_yaml::drivers(&bundle.session_info)
+            .into_iter()
+            .find(|driver| Some(driver.car_idx) == player)
+            .map(|driver| driver.car_screen_name)
+            .unwrap_or_default();
+        bundle.manifest.frames = bundle.frames.len();
+        bundle.manifest.laps = bundle.laps.len();
+        bundle.manifest.events = bundle.events.len();
+        Ok(bundle)
+    }
+
+    /// Write the bundle as a zip file, encrypted when a passphrase is given
+    pub fn write(&self, path: &Path, passphrase: Option<&str>) -> Result<(), Box<dyn Error>> {
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let options = FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+
+        zip.start_file(MANIFEST, options)?;
+        zip.write_all(&serde_json::to_vec_pretty(&self.manifest)?)?;
+        zip.start_file(SESSION_INFO, options)?;
+        zip.write_all(self.session_info.as_bytes())?;
+        zip.start_file(LAPS, options)?;
+        zip.write_all(&serde_json::to_vec_pretty(&self.laps)?)?;
+        zip.start_file(EVENTS, options)?;
+        zip.write_all(&serde_json::to_vec_pretty(&self.events)?)?;
+
+        zip.start_file(TELEMETRY, options)?;
+        for frame in &self.frames {
+            serde_json::to_writer(&mut zip, frame)?;
+            zip.write_all(b"\n")?;
+        }
+        let contents = zip.finish()?.into_inner();
+        encryption::write_file(path, &contents, passphrase)
+    }
+
+    /// Read a bundle written by `write`
+    pub fn open(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let mut archive = zip::ZipArchive::new(Cursor::new(encryption::read_file(path)?))?;
+        let mut read = |name: &str| -> Result<String, Box<dyn Error>> {
+            let mut contents = String::new();
+            archive
+                .by_name(name)
+                .map_err(|e| format!("{}: {}: {}", path.display(), name, e))?
+                .read_to_string(&mut contents)?;
+            Ok(contents)
+        };
+
+        let manifest: BundleManifest = serde_json::from_str(&read(MANIFEST)?)?;
+        if manifest.version > BUNDLE_VERSION {
+            return Err(format!("{} is a version {} bundle, this build reads up to {}", path.display(), manifest.version, BUNDLE_VERSION).into());
+        }
+        let session_info = read(SESSION_INFO)?;
+        let laps = serde_json::from_str(&read(LAPS)?)?;
+        let events = serde_json::from_str(&read(EVENTS)?)?;
+        let frames = read(TELEMETRY)?
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(serde_json::from_str)
+            .collect::<Result<Vec<TelemetryData>, _>>()?;
+        Ok(SessionBundle { manifest, session_info, laps, events, frames })
+    }
+
+    /// Write the frames back out as a recording. The session info goes on every frame and
+    /// each event on the first kept frame at or after it, as in a live recording
+    pub fn write_recording(&self, path: &Path, passphrase: Option<&str>) -> Result<(), Box<dyn Error>> {
+        let mut writer = Vec::new();
+        let mut events = self.events.iter().peekable();
+        for (i, frame) in self.frames.iter().enumerate() {
+            let mut frame = frame.clone();
+            frame.session_info = self.session_info.clone();
+            let next_time = self.frames.get(i + 1).map(|next| next.SessionTime);
+            while let Some(event) = events.next_if(|event| next_time.is_none_or(|next| event.session_time < next)) {
+                frame.events.push(event.clone());
+            }
+            serde_json::to_writer(&mut writer, &frame)?;
+            writer.write_all(b"\n")?;
+        }
+        encryption::write_file(path, &writer, passphrase)
+    }
+}
+
+// Passphrase for --encrypt, which has to come from the environment
+fn output_passphrase(args: &[String]) -> Result<Option<String>, String> {
+    if !args.iter().any(|arg| arg == "--encrypt") {
+        return Ok(None);
+    }
+    encryption::passphrase()
+        .map(Some)
+        .ok_or_else(|| format!("--encrypt needs the passphrase in {}", encryption::PASSPHRASE_ENV))
+}
+
+/// `speedforge export-session <recording> --bundle out.zip [--rate <hz>] [--encrypt]`
+pub fn run_export_cli(args: &[String]) -> i32 {
+    let (Some(recording), Some(bundle_path)) = (args.first().filter(|arg| !arg.starts_with("--")), crate::arg_value(args, "--bundle")) else {
+        eprintln!("Usage: speedforge export-session <recording> --bundle <out.zip> [--rate <hz>] [--encrypt]");
+        return 2;
+    };
+    let passphrase = match output_passphrase(args) {
+        Ok(passphrase) => passphrase,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 2;
+        }
+    };
+    let rate_hz = crate::arg_value(args, "--rate").and_then(|value| value.parse().ok()).unwrap_or(DEFAULT_RATE_HZ);
+
+    let result = SessionBundle::from_recording(Path::new(recording), rate_hz)
+        .and_then(|bundle| bundle.write(Path::new(&bundle_path), passphrase.as_deref()).map(|_| bundle));
+    match result {
+        Ok(bundle) => {
+            println!("Exported {} ({} laps, {} events, {} of {} frames) to {}",
+                recording, bundle.manifest.laps, bundle.manifest.events,
+                bundle.manifest.frames, bundle.manifest.recorded_frames, bundle_path);
+            0
+        },
+        Err(e) => {
+            eprintln!("Failed to export session: {}", e);
+            1
+        }
+    }
+}
+
+/// `speedforge import-session <bundle.zip> --out <recording> [--encrypt]`
+pub fn run_import_cli(args: &[String]) -> i32 {
+    let (Some(bundle_path), Some(recording)) = (args.first().filter(|arg| !arg.starts_with("--")), crate::arg_value(args, "--out")) else {
+        eprintln!("Usage: speedforge import-session <bundle.zip> --out <recording> [--encrypt]");
+        return 2;
+    };
+    let passphrase = match output_passphrase(args) {
+        Ok(passphrase) => passphrase,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 2;
+        }
+    };
+
+    let result = SessionBundle::open(Path::new(bundle_path))
+        .and_then(|bundle| bundle.write_recording(Path::new(&recording), passphrase.as_deref()).map(|_| bundle));
+    match result {
+        Ok(bundle) => {
+            println!("Imported {} at {} ({} laps) to {}",
+                bundle.manifest.car, bundle.manifest.track, bundle.manifest.laps, recording);
+            0
+        },
+        Err(e) => {
+            eprintln!("Failed to import session: {}", e);
+            1
+        }
+    }
+}
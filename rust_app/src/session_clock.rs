@@ -0,0 +1,156 @@
+//! Correlation between the sim's clocks and wall-clock time.
+//!
+//! Every frame carries `session_clock` with SessionNum, SessionTime, the replay frame
+//! and the wall clock at which the frame was read. Anchors are also kept on the
+//! server so a client can convert a timestamp from one clock to the others later
+//! with `{"type":"convert_time","from":"wall_clock_ms","value":1700000000000}`,
+//! e.g. to line telemetry up with an external video or the replay file.
+
+use crate::derived::DerivedChannel;
+use crate::protocol;
+use crate::telemetry_fields::TelemetryData;
+use crate::time_sync;
+use crate::websocket_server::RequestHandler;
+use serde::{Serialize, Deserialize};
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+/// Replay frames per second of sim time
+pub const REPLAY_FPS: f64 = 60.0;
+// Seconds between anchors while the clocks run together
+const ANCHOR_INTERVAL_S: f64 = 1.0;
+// Anchors kept, about two hours at one per second
+const MAX_ANCHORS: usize = 7200;
+// Drift between the clocks that marks a pause, replay jump or new session (seconds)
+const DISCONTINUITY_S: f64 = 0.5;
+
+/// The sim's clocks and the wall clock at one moment
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default)]
+pub struct SessionClock {
+    pub session_num: i32,
+    pub session_time: f64,
+    pub replay_frame_num: i32,
+    /// Unix time in milliseconds
+    pub wall_clock_ms: f64,
+    /// Same clock as `server_time_ms` and time sync replies
+    pub server_time_ms: f64,
+}
+
+impl SessionClock {
+    // Move along all clocks by an amount of wall time, assuming they run at real time
+    fn advanced(&self, seconds: f64) -> Self {
+        Self {
+            session_time: self.session_time + seconds,
+            replay_frame_num: self.replay_frame_num + (seconds * REPLAY_FPS).round() as i32,
+            wall_clock_ms: self.wall_clock_ms + seconds * 1000.0,
+            server_time_ms: self.server_time_ms + seconds * 1000.0,
+            ..*self
+        }
+    }
+}
+
+/// Recent clock anchors shared between the telemetry thread and the server
+#[derive(Clone, Default)]
+pub struct ClockStore {
+    anchors: Arc<Mutex<VecDeque<SessionClock>>>,
+}
+
+impl ClockStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn record(&self, clock: SessionClock) {
+        let Ok(mut anchors) = self.anchors.lock() else {
+            return;
+        };
+        let due = match anchors.back() {
+            Some(last) => {
+                let wall_s = (clock.wall_clock_ms - last.wall_clock_ms) / 1000.0;
+                let session_s = clock.session_time - last.session_time;
+                clock.session_num != last.session_num
+                    || (wall_s - session_s).abs() > DISCONTINUITY_S
+                    || wall_s >= ANCHOR_INTERVAL_S
+            },
+            None => true,
+        };
+        if due {
+            anchors.push_back(clock);
+            if anchors.len() > MAX_ANCHORS {
+                anchors.pop_front();
+            }
+        }
+    }
+
+    /// Convert a time on one clock ("session_time", "replay_frame", "wall_clock_ms")
+    /// to all clocks, using the closest anchor. Session times need the session number
+    /// since each session starts from zero; the latest session is assumed otherwise.
+    pub fn convert(&self, from: &str, value: f64, session_num: Option<i32>) -> Result<SessionClock, String> {
+        let anchors = self.anchors.lock().map_err(|_| "Clock anchors unavailable".to_string())?;
+        let session_num = session_num.or_else(|| anchors.back().map(|a| a.session_num));
+        let position: fn(&SessionClock) -> f64 = match from {
+            "session_time" => |a| a.session_time,
+            "replay_frame" => |a| a.replay_frame_num as f64 / REPLAY_FPS,
+            "wall_clock_ms" => |a| a.wall_clock_ms / 1000.0,
+            _ => return Err(format!("Unknown clock '{}'", from)),
+        };
+        let target = match from {
+            "replay_frame" => value / REPLAY_FPS,
+            "wall_clock_ms" => value / 1000.0,
+            _ => value,
+        };
+        let anchor = anchors
+            .iter()
+            .filter(|a| from == "wall_clock_ms" || Some(a.session_num) == session_num)
+            .min_by(|a, b| {
+                (position(a) - target).abs().partial_cmp(&(position(b) - target).abs()).unwrap_or(std::cmp::Ordering::Equal)
+            })
+            .ok_or("No clock anchors recorded for that session yet")?;
+        Ok(anchor.advanced(target - position(anchor)))
+    }
+}
+
+impl RequestHandler for ClockStore {
+    /// `{"type":"convert_time","from":"session_time","value":1234.5,"session_num":2}`
+    fn handle(&self, request: &Value) -> Value {
+        let from = request.get("from").and_then(Value::as_str).unwrap_or("session_time");
+        let Some(value) = request.get("value").and_then(Value::as_f64) else {
+            return protocol::error(protocol::ERROR_INVALID_REQUEST, "Missing value", Some("convert_time"));
+        };
+        let session_num = request.get("session_num").and_then(Value::as_i64).map(|n| n as i32);
+        match self.convert(from, value, session_num) {
+            Ok(clock) => json!({ "type": "converted_time", "from": from, "value": value, "clock": clock }),
+            Err(e) => protocol::error(protocol::ERROR_FAILED, e, Some("convert_time")),
+        }
+    }
+}
+
+/// Derived channel that stamps each frame with the clock correlation and keeps anchors
+pub struct SessionClockRecorder {
+    store: ClockStore,
+}
+
+impl SessionClockRecorder {
+    pub fn new(store: ClockStore) -> Self {
+        Self { store }
+    }
+}
+
+impl DerivedChannel for SessionClockRecorder {
+    fn name(&self) -> &'static str {
+        "session_clock"
+    }
+
+    fn compute(&mut self, data: &mut TelemetryData) {
+        let clock = SessionClock {
+            session_num: data.session_num,
+            session_time: data.SessionTime as f64,
+            replay_frame_num: data.replay_frame_num,
+            wall_clock_ms: chrono::Utc::now().timestamp_millis() as f64,
+            server_time_ms: time_sync::server_time_ms(),
+        };
+        self.store.record(clock);
+        data.session_clock = Some(clock);
+    }
+}
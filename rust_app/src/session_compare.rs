@@ -0,0 +1,311 @@
+use crate::consistency;
+use crate::encryption;
+use crate::ibt::IbtFile;
+use crate::lap_summary::{LapSummary, LapSummaryBuilder};
+use crate::race_clock::RaceClockTracker;
+use crate::session_yaml;
+use crate::telemetry_fields::TelemetryData;
+use serde::Serialize;
+use std::error::Error;
+use std::io::BufRead;
+use std::path::Path;
+
+/// Headline numbers for one recorded session
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct SessionSummary {
+    pub path: String,
+    pub track: String,
+    pub car: String,
+    pub frames: usize,
+    pub laps: Vec<LapSummary>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub best_lap: Option<LapSummary>,
+    pub top_speed_kph: f32,
+    /// Average fuel used over green-flag laps (no pit visit)
+    pub avg_fuel_per_lap: f32,
+    pub avg_track_temp_c: f32,
+    pub avg_air_temp_c: f32,
+    pub max_oil_temp_c: f32,
+    pub max_water_temp_c: f32,
+    /// Lap time standard deviation over green-flag laps, outliers removed
+    pub lap_time_std_dev: f32,
+    pub throttle_smoothness: f32,
+    pub brake_smoothness: f32,
+}
+
+/// Differences between two sessions (second minus first)
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct ComparisonReport {
+    pub first: SessionSummary,
+    pub second: SessionSummary,
+    /// Whether both sessions were recorded with the same track and car
+    pub same_track_and_car: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub best_lap_delta: Option<f32>,
+    /// Sector deltas between the two best laps
+    pub sector_deltas: Vec<f32>,
+    pub top_speed_delta_kph: f32,
+    pub fuel_per_lap_delta: f32,
+    pub track_temp_delta_c: f32,
+    pub air_temp_delta_c: f32,
+}
+
+impl SessionSummary {
+    /// Load a recorded session (one TelemetryData JSON object per line, possibly encrypted) and summarize it
+    pub fn from_file(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let reader = encryption::open_lines(path)?;
+        let mut summary = SessionSummary {
+            path: path.display().to_string(),
+            ..Default::default()
+        };
+        let mut builder = LapSummaryBuilder::new();
+
+        for (line_number, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let frame: TelemetryData = serde_json::from_str(&line)
+                .map_err(|e| format!("{}:{}: {}", path.display(), line_number + 1, e))?;
+            summary.add_frame(&frame, &mut builder);
+        }
+
+        if summary.frames == 0 {
+            return Err(format!("{} contains no telemetry frames", path.display()).into());
+        }
+        summary.finish();
+        Ok(summary)
+    }
+
+    /// Summarize frames read from another format (e.g. an .ibt file). The race clock is
+    /// rebuilt here since these frames never went through the derived pipeline
+    pub fn from_frames(path: &Path, frames: impl IntoIterator<Item = TelemetryData>) -> Result<Self, Box<dyn Error>> {
+        let mut summary = SessionSummary {
+            path: path.display().to_string(),
+            ..Default::default()
+        };
+        let mut builder = LapSummaryBuilder::new();
+        let mut clock = RaceClockTracker::new();
+        for mut frame in frames {
+            frame.race_clock = clock.update(&frame);
+            summary.add_frame(&frame, &mut builder);
+        }
+
+        if summary.frames == 0 {
+            return Err(format!("{} contains no telemetry frames", path.display()).into());
+        }
+        summary.finish();
+        Ok(summary)
+    }
+
+    fn add_frame(&mut self, frame: &TelemetryData, builder: &mut LapSummaryBuilder) {
+        self.frames += 1;
+        if self.track.is_empty() && let Some(track) = session_yaml::scalar(&frame.session_info, "TrackDisplayName") {
+            self.track = track.to_string();
+        }
+        // The first CarScreenName in the YAML is the pace car's; take the player's
+        if self.car.is_empty() {
+            let player = session_yaml::scalar(&frame.session_info, "DriverCarIdx").and_then(|idx| idx.parse::<i32>().ok());
+            if let Some(driver) = session_yaml::drivers(&frame.session_info).into_iter().find(|driver| Some(driver.car_idx) == player) {
+                self.car = driver.car_screen_name;
+            }
+        }
+        if let Some(lap) = builder.update(frame) {
+            self.laps.push(lap);
+        }
+    }
+
+    fn finish(&mut self) {
+        self.best_lap = self.laps
+            .iter()
+            .filter(|lap| lap.complete && !lap.pitted && lap.lap_time > 0.0)
+            .min_by(|a, b| a.lap_time.partial_cmp(&b.lap_time).unwrap_or(std::cmp::Ordering::Equal))
+            .cloned();
+
+        self.top_speed_kph = self.laps.iter().map(|lap| lap.top_speed_kph).fold(0.0, f32::max);
+        self.max_oil_temp_c = self.laps.iter().map(|lap| lap.max_oil_temp_c).fold(0.0, f32::max);
+        self.max_water_temp_c = self.laps.iter().map(|lap| lap.max_water_temp_c).fold(0.0, f32::max);
+
+        let green_laps: Vec<&LapSummary> = self.laps.iter().filter(|lap| lap.complete && !lap.pitted).collect();
+        if !green_laps.is_empty() {
+            let count = green_laps.len() as f32;
+            self.avg_fuel_per_lap = green_laps.iter().map(|lap| lap.fuel_used).sum::<f32>() / count;
+            self.throttle_smoothness = green_laps.iter().map(|lap| lap.throttle_smoothness).sum::<f32>() / count;
+            self.brake_smoothness = green_laps.iter().map(|lap| lap.brake_smoothness).sum::<f32>() / count;
+
+            let lap_times: Vec<f32> = green_laps.iter().map(|lap| lap.lap_time).collect();
+            if let Some((_, std_dev, _)) = consistency::lap_time_spread(&lap_times) {
+                self.lap_time_std_dev = std_dev;
+            }
+        }
+        if !self.laps.is_empty() {
+            let count = self.laps.len() as f32;
+            self.avg_track_temp_c = self.laps.iter().map(|lap| lap.avg_track_temp_c).sum::<f32>() / count;
+            self.avg_air_temp_c = self.laps.iter().map(|lap| lap.avg_air_temp_c).sum::<f32>() / count;
+        }
+    }
+}
+
+/// Compare two summarized sessions
+pub fn compare(first: SessionSummary, second: SessionSummary) -> ComparisonReport {
+    let (best_lap_delta, sector_deltas) = match (&first.best_lap, &second.best_lap) {
+        (Some(a), Some(b)) => {
+            let sectors = if a.sector_times.len() == b.sector_times.len() {
+                a.sector_times.iter().zip(&b.sector_times).map(|(x, y)| y - x).collect()
+            } else {
+                Vec::new()
+            };
+            (Some(b.lap_time - a.lap_time), sectors)
+        },
+        _ => (None, Vec::new()),
+    };
+
+    ComparisonReport {
+        same_track_and_car: first.track == second.track && first.car == second.car,
+        best_lap_delta,
+        sector_deltas,
+        top_speed_delta_kph: second.top_speed_kph - first.top_speed_kph,
+        fuel_per_lap_delta: second.avg_fuel_per_lap - first.avg_fuel_per_lap,
+        track_temp_delta_c: second.avg_track_temp_c - first.avg_track_temp_c,
+        air_temp_delta_c: second.avg_air_temp_c - first.avg_air_temp_c,
+        first,
+        second,
+    }
+}
+
+/// Frames of a recording: JSON lines (possibly encrypted) or an iRacing .ibt file
+pub fn read_frames(path: &Path) -> Result<Box<dyn Iterator<Item = Result<TelemetryData, String>>>, Box<dyn Error>> {
+    if path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("ibt")) {
+        let frames: Vec<TelemetryData> = IbtFile::open(path)?.frames().collect();
+        return Ok(Box::new(frames.into_iter().map(Ok)));
+    }
+    let display = path.display().to_string();
+    let lines = encryption::open_lines(path)?.lines().enumerate();
+    Ok(Box::new(lines.filter_map(move |(line_number, line)| match line {
+        Ok(line) if line.trim().is_empty() => None,
+        Ok(line) => Some(serde_json::from_str(&line).map_err(|e| format!("{}:{}: {}", display, line_number + 1, e))),
+        Err(e) => Some(Err(format!("{}:{}: {}", display, line_number + 1, e))),
+    })))
+}
+
+/// Load and compare two recorded sessions
+pub fn compare_files(first: &Path, second: &Path) -> Result<ComparisonReport, Box<dyn Error>> {
+    let first = SessionSummary::from_file(first)?;
+    let second = SessionSummary::from_file(second)?;
+    Ok(compare(first, second))
+}
+
+/// Lap time as mm:ss.fff, N/A when not set
+pub fn format_lap_time(time: f32) -> String {
+    if time <= 0.0 {
+        return "N/A".to_string();
+    }
+    let minutes = (time / 60.0) as i32;
+    format!("{:02}:{:06.3}", minutes, time % 60.0)
+}
+
+fn format_delta(delta: f32) -> String {
+    let sign = if delta >= 0.0 { "+" } else { "" };
+    format!("{}{:.3}", sign, delta)
+}
+
+/// Format a comparison report as a human-readable table
+pub fn format_report(report: &ComparisonReport) -> String {
+    let mut out = String::new();
+    let (a, b) = (&report.first, &report.second);
+
+    out.push_str(&format!("A: {} ({} / {}, {} laps)\n", a.path, a.track, a.car, a.laps.len()));
+    out.push_str(&format!("B: {} ({} / {}, {} laps)\n", b.path, b.track, b.car, b.laps.len()));
+    if !report.same_track_and_car {
+        out.push_str("WARNING: sessions were recorded with a different track or car\n");
+    }
+
+    out.push_str(&format!("\n{:<20} {:>12} {:>12} {:>10}\n", "", "A", "B", "Delta"));
+    let best_a = a.best_lap.as_ref().map_or(0.0, |lap| lap.lap_time);
+    let best_b = b.best_lap.as_ref().map_or(0.0, |lap| lap.lap_time);
+    out.push_str(&format!("{:<20} {:>12} {:>12} {:>10}\n", "Best lap",
+        format_lap_time(best_a), format_lap_time(best_b),
+        report.best_lap_delta.map_or("-".to_string(), format_delta)));
+
+    if let (Some(lap_a), Some(lap_b)) = (&a.best_lap, &b.best_lap) {
+        for (i, delta) in report.sector_deltas.iter().enumerate() {
+            out.push_str(&format!("{:<20} {:>12.3} {:>12.3} {:>10}\n", format!("  Sector {}", i + 1),
+                lap_a.sector_times[i], lap_b.sector_times[i], format_delta(*delta)));
+        }
+    }
+
+    out.push_str(&format!("{:<20} {:>12.1} {:>12.1} {:>10}\n", "Top speed (km/h)",
+        a.top_speed_kph, b.top_speed_kph, format_delta(report.top_speed_delta_kph)));
+    out.push_str(&format!("{:<20} {:>12.2} {:>12.2} {:>10}\n", "Fuel per lap (L)",
+        a.avg_fuel_per_lap, b.avg_fuel_per_lap, format_delta(report.fuel_per_lap_delta)));
+    out.push_str(&format!("{:<20} {:>12.1} {:>12.1} {:>10}\n", "Track temp (°C)",
+        a.avg_track_temp_c, b.avg_track_temp_c, format_delta(report.track_temp_delta_c)));
+    out.push_str(&format!("{:<20} {:>12.1} {:>12.1} {:>10}\n", "Air temp (°C)",
+        a.avg_air_temp_c, b.avg_air_temp_c, format_delta(report.air_temp_delta_c)));
+    out.push_str(&format!("{:<20} {:>12.3} {:>12.3}\n", "Lap time std dev (s)",
+        a.lap_time_std_dev, b.lap_time_std_dev));
+    out.push_str(&format!("{:<20} {:>12.1} {:>12.1}\n", "Throttle smoothness",
+        a.throttle_smoothness, b.throttle_smoothness));
+    out.push_str(&format!("{:<20} {:>12.1} {:>12.1}\n", "Brake smoothness",
+        a.brake_smoothness, b.brake_smoothness));
+    out.push_str(&format!("{:<20} {:>12.1} {:>12.1}\n", "Max oil temp (°C)",
+        a.max_oil_temp_c, b.max_oil_temp_c));
+    out.push_str(&format!("{:<20} {:>12.1} {:>12.1}\n", "Max water temp (°C)",
+        a.max_water_temp_c, b.max_water_temp_c));
+
+    out
+}
+
+/// Format the lap by lap conditions of a session, for correlating pace with weather
+pub fn format_lap_log(summary: &SessionSummary) -> String {
+    let mut out = format!("\nLaps of {}\n", summary.path);
+    out.push_str(&format!("{:>4} {:>10} {:>9} {:>8} {:>9} {:>8} {:>8} {:<14} {}\n",
+        "Lap", "Time", "Track °C", "Air °C", "Wind m/s", "Humid %", "Wetness", "Skies", ""));
+    for lap in &summary.laps {
+        let flags = match (lap.pitted, lap.complete) {
+            (true, _) => "pit",
+            (false, false) => "partial",
+            _ => "",
+        };
+        out.push_str(&format!("{:>4} {:>10} {:>9.1} {:>8.1} {:>9.1} {:>8.0} {:>8} {:<14} {}\n",
+            lap.lap, format_lap_time(lap.lap_time), lap.avg_track_temp_c, lap.avg_air_temp_c,
+            lap.avg_wind_vel_ms, lap.avg_humidity_pct, lap.max_track_wetness, lap.skies, flags));
+    }
+    out
+}
+
+/// Entry point for `speedforge compare <a> <b> [--json] [--laps]`; returns the process exit code
+pub fn run_cli(args: &[String]) -> i32 {
+    let json = args.iter().any(|arg| arg == "--json");
+    let lap_log = args.iter().any(|arg| arg == "--laps");
+    let files: Vec<&String> = args.iter().filter(|arg| !arg.starts_with("--")).collect();
+    if files.len() != 2 {
+        eprintln!("Usage: speedforge compare <first recording> <second recording> [--json] [--laps]");
+        return 2;
+    }
+
+    match compare_files(Path::new(files[0]), Path::new(files[1])) {
+        Ok(report) => {
+            if json {
+                match serde_json::to_string_pretty(&report) {
+                    Ok(output) => println!("{}", output),
+                    Err(e) => {
+                        eprintln!("Failed to serialize report: {}", e);
+                        return 1;
+                    }
+                }
+            } else {
+                print!("{}", format_report(&report));
+                if lap_log {
+                    print!("{}", format_lap_log(&report.first));
+                    print!("{}", format_lap_log(&report.second));
+                }
+            }
+            0
+        },
+        Err(e) => {
+            eprintln!("Failed to compare sessions: {}", e);
+            1
+        }
+    }
+}
@@ -0,0 +1,301 @@
+//! Typed view of the session info YAML.
+//!
+//! The iracing crate's own session types fail as a whole when one field has an unexpected
+//! type (LicLevel, ResultsPositions), which is why the raw string is passed around. Here
+//! every field is optional and a value that doesn't fit its type is dropped instead of
+//! failing the parse. When the document as a whole isn't valid YAML (unquoted team names
+//! with colons) each top-level section is parsed on its own, so one broken section only
+//! loses that section. The result is sent as `session_info_parsed` next to the raw string.
+
+use crate::derived::DerivedChannel;
+use crate::telemetry_fields::TelemetryData;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize};
+
+// A value that doesn't fit the field's type becomes None instead of an error
+fn lenient<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: DeserializeOwned,
+{
+    let value = serde_yaml::Value::deserialize(deserializer)?;
+    Ok(serde_yaml::from_value(value).ok())
+}
+
+// Text fields also accept numbers ("CarNumber: 7", "TeamName: 1984")
+fn lenient_text<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(match serde_yaml::Value::deserialize(deserializer)? {
+        serde_yaml::Value::String(text) => Some(text),
+        serde_yaml::Value::Number(number) => Some(number.to_string()),
+        serde_yaml::Value::Bool(value) => Some(value.to_string()),
+        _ => None,
+    })
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct WeekendInfo {
+    #[serde(deserialize_with = "lenient_text", skip_serializing_if = "Option::is_none")]
+    pub track_name: Option<String>,
+    #[serde(rename = "TrackID", deserialize_with = "lenient", skip_serializing_if = "Option::is_none")]
+    pub track_id: Option<i64>,
+    /// With unit, e.g. "4.5 km"
+    #[serde(deserialize_with = "lenient_text", skip_serializing_if = "Option::is_none")]
+    pub track_length: Option<String>,
+    #[serde(deserialize_with = "lenient_text", skip_serializing_if = "Option::is_none")]
+    pub track_display_name: Option<String>,
+    #[serde(deserialize_with = "lenient_text", skip_serializing_if = "Option::is_none")]
+    pub track_config_name: Option<String>,
+    #[serde(deserialize_with = "lenient_text", skip_serializing_if = "Option::is_none")]
+    pub track_city: Option<String>,
+    #[serde(deserialize_with = "lenient_text", skip_serializing_if = "Option::is_none")]
+    pub track_country: Option<String>,
+    #[serde(deserialize_with = "lenient", skip_serializing_if = "Option::is_none")]
+    pub track_num_turns: Option<i32>,
+    /// With unit, e.g. "72.00 kph"
+    #[serde(deserialize_with = "lenient_text", skip_serializing_if = "Option::is_none")]
+    pub track_pit_speed_limit: Option<String>,
+    #[serde(deserialize_with = "lenient_text", skip_serializing_if = "Option::is_none")]
+    pub track_type: Option<String>,
+    #[serde(rename = "SeriesID", deserialize_with = "lenient", skip_serializing_if = "Option::is_none")]
+    pub series_id: Option<i64>,
+    #[serde(rename = "SeasonID", deserialize_with = "lenient", skip_serializing_if = "Option::is_none")]
+    pub season_id: Option<i64>,
+    #[serde(rename = "SessionID", deserialize_with = "lenient", skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<i64>,
+    #[serde(rename = "SubSessionID", deserialize_with = "lenient", skip_serializing_if = "Option::is_none")]
+    pub sub_session_id: Option<i64>,
+    #[serde(rename = "LeagueID", deserialize_with = "lenient", skip_serializing_if = "Option::is_none")]
+    pub league_id: Option<i64>,
+    #[serde(deserialize_with = "lenient", skip_serializing_if = "Option::is_none")]
+    pub official: Option<i32>,
+    #[serde(deserialize_with = "lenient", skip_serializing_if = "Option::is_none")]
+    pub race_week: Option<i32>,
+    #[serde(deserialize_with = "lenient_text", skip_serializing_if = "Option::is_none")]
+    pub event_type: Option<String>,
+    #[serde(deserialize_with = "lenient_text", skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    #[serde(deserialize_with = "lenient", skip_serializing_if = "Option::is_none")]
+    pub num_car_classes: Option<i32>,
+    #[serde(deserialize_with = "lenient", skip_serializing_if = "Option::is_none")]
+    pub num_car_types: Option<i32>,
+}
+
+/// One row of a session's results
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct ResultPosition {
+    #[serde(deserialize_with = "lenient", skip_serializing_if = "Option::is_none")]
+    pub position: Option<i32>,
+    #[serde(deserialize_with = "lenient", skip_serializing_if = "Option::is_none")]
+    pub class_position: Option<i32>,
+    #[serde(deserialize_with = "lenient", skip_serializing_if = "Option::is_none")]
+    pub car_idx: Option<i32>,
+    #[serde(deserialize_with = "lenient", skip_serializing_if = "Option::is_none")]
+    pub lap: Option<i32>,
+    #[serde(deserialize_with = "lenient", skip_serializing_if = "Option::is_none")]
+    pub time: Option<f64>,
+    #[serde(deserialize_with = "lenient", skip_serializing_if = "Option::is_none")]
+    pub fastest_lap: Option<i32>,
+    #[serde(deserialize_with = "lenient", skip_serializing_if = "Option::is_none")]
+    pub fastest_time: Option<f64>,
+    #[serde(deserialize_with = "lenient", skip_serializing_if = "Option::is_none")]
+    pub last_time: Option<f64>,
+    #[serde(deserialize_with = "lenient", skip_serializing_if = "Option::is_none")]
+    pub laps_led: Option<i32>,
+    #[serde(deserialize_with = "lenient", skip_serializing_if = "Option::is_none")]
+    pub laps_complete: Option<i32>,
+    #[serde(deserialize_with = "lenient", skip_serializing_if = "Option::is_none")]
+    pub incidents: Option<i32>,
+    #[serde(deserialize_with = "lenient", skip_serializing_if = "Option::is_none")]
+    pub reason_out_id: Option<i32>,
+    #[serde(deserialize_with = "lenient_text", skip_serializing_if = "Option::is_none")]
+    pub reason_out_str: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct Session {
+    #[serde(deserialize_with = "lenient", skip_serializing_if = "Option::is_none")]
+    pub session_num: Option<i32>,
+    /// Number of laps or "unlimited"
+    #[serde(deserialize_with = "lenient_text", skip_serializing_if = "Option::is_none")]
+    pub session_laps: Option<String>,
+    /// With unit, e.g. "600.0000 sec", or "unlimited"
+    #[serde(deserialize_with = "lenient_text", skip_serializing_if = "Option::is_none")]
+    pub session_time: Option<String>,
+    #[serde(deserialize_with = "lenient_text", skip_serializing_if = "Option::is_none")]
+    pub session_type: Option<String>,
+    #[serde(deserialize_with = "lenient_text", skip_serializing_if = "Option::is_none")]
+    pub session_name: Option<String>,
+    #[serde(deserialize_with = "lenient", skip_serializing_if = "Option::is_none")]
+    pub results_positions: Option<Vec<ResultPosition>>,
+    #[serde(deserialize_with = "lenient", skip_serializing_if = "Option::is_none")]
+    pub results_official: Option<i32>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct SessionInfo {
+    #[serde(deserialize_with = "lenient_list")]
+    pub sessions: Vec<Session>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct Driver {
+    #[serde(deserialize_with = "lenient", skip_serializing_if = "Option::is_none")]
+    pub car_idx: Option<i32>,
+    #[serde(deserialize_with = "lenient_text", skip_serializing_if = "Option::is_none")]
+    pub user_name: Option<String>,
+    #[serde(deserialize_with = "lenient_text", skip_serializing_if = "Option::is_none")]
+    pub abbrev_name: Option<String>,
+    #[serde(deserialize_with = "lenient_text", skip_serializing_if = "Option::is_none")]
+    pub initials: Option<String>,
+    #[serde(rename = "UserID", deserialize_with = "lenient", skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<i64>,
+    #[serde(rename = "TeamID", deserialize_with = "lenient", skip_serializing_if = "Option::is_none")]
+    pub team_id: Option<i64>,
+    #[serde(deserialize_with = "lenient_text", skip_serializing_if = "Option::is_none")]
+    pub team_name: Option<String>,
+    /// As painted, leading zeros kept
+    #[serde(deserialize_with = "lenient_text", skip_serializing_if = "Option::is_none")]
+    pub car_number: Option<String>,
+    #[serde(deserialize_with = "lenient_text", skip_serializing_if = "Option::is_none")]
+    pub car_path: Option<String>,
+    #[serde(rename = "CarID", deserialize_with = "lenient", skip_serializing_if = "Option::is_none")]
+    pub car_id: Option<i64>,
+    #[serde(rename = "CarClassID", deserialize_with = "lenient", skip_serializing_if = "Option::is_none")]
+    pub car_class_id: Option<i32>,
+    #[serde(deserialize_with = "lenient_text", skip_serializing_if = "Option::is_none")]
+    pub car_screen_name: Option<String>,
+    #[serde(deserialize_with = "lenient_text", skip_serializing_if = "Option::is_none")]
+    pub car_class_short_name: Option<String>,
+    /// Hex color as written in the YAML, e.g. "0xffda59"
+    #[serde(deserialize_with = "lenient_text", skip_serializing_if = "Option::is_none")]
+    pub car_class_color: Option<String>,
+    #[serde(rename = "IRating", deserialize_with = "lenient", skip_serializing_if = "Option::is_none")]
+    pub i_rating: Option<i32>,
+    #[serde(deserialize_with = "lenient", skip_serializing_if = "Option::is_none")]
+    pub lic_level: Option<i32>,
+    #[serde(deserialize_with = "lenient", skip_serializing_if = "Option::is_none")]
+    pub lic_sub_level: Option<i32>,
+    #[serde(deserialize_with = "lenient_text", skip_serializing_if = "Option::is_none")]
+    pub lic_string: Option<String>,
+    #[serde(deserialize_with = "lenient", skip_serializing_if = "Option::is_none")]
+    pub car_is_pace_car: Option<i32>,
+    #[serde(deserialize_with = "lenient", skip_serializing_if = "Option::is_none")]
+    pub is_spectator: Option<i32>,
+    #[serde(deserialize_with = "lenient_text", skip_serializing_if = "Option::is_none")]
+    pub club_name: Option<String>,
+    #[serde(deserialize_with = "lenient", skip_serializing_if = "Option::is_none")]
+    pub cur_driver_incident_count: Option<i32>,
+    #[serde(deserialize_with = "lenient", skip_serializing_if = "Option::is_none")]
+    pub team_incident_count: Option<i32>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct DriverInfo {
+    #[serde(deserialize_with = "lenient", skip_serializing_if = "Option::is_none")]
+    pub driver_car_idx: Option<i32>,
+    #[serde(rename = "DriverUserID", deserialize_with = "lenient", skip_serializing_if = "Option::is_none")]
+    pub driver_user_id: Option<i64>,
+    #[serde(deserialize_with = "lenient", skip_serializing_if = "Option::is_none")]
+    pub pace_car_idx: Option<i32>,
+    #[serde(deserialize_with = "lenient", skip_serializing_if = "Option::is_none")]
+    pub driver_car_red_line: Option<f32>,
+    #[serde(deserialize_with = "lenient", skip_serializing_if = "Option::is_none")]
+    pub driver_car_fuel_max_ltr: Option<f32>,
+    #[serde(rename = "DriverCarSLShiftRPM", deserialize_with = "lenient", skip_serializing_if = "Option::is_none")]
+    pub driver_car_sl_shift_rpm: Option<f32>,
+    #[serde(deserialize_with = "lenient_list")]
+    pub drivers: Vec<Driver>,
+}
+
+// A list whose entries are parsed one by one, so a bad entry is skipped rather than the list
+fn lenient_list<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: DeserializeOwned,
+{
+    Ok(match serde_yaml::Value::deserialize(deserializer)? {
+        serde_yaml::Value::Sequence(items) => items.into_iter().filter_map(|item| serde_yaml::from_value(item).ok()).collect(),
+        _ => Vec::new(),
+    })
+}
+
+/// The parts of the session info YAML clients use
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct ParsedSessionInfo {
+    #[serde(deserialize_with = "lenient", skip_serializing_if = "Option::is_none")]
+    pub weekend_info: Option<WeekendInfo>,
+    #[serde(deserialize_with = "lenient", skip_serializing_if = "Option::is_none")]
+    pub session_info: Option<SessionInfo>,
+    #[serde(deserialize_with = "lenient", skip_serializing_if = "Option::is_none")]
+    pub driver_info: Option<DriverInfo>,
+}
+
+/// Parse the YAML, falling back to one top-level section at a time when the document as a
+/// whole is invalid
+pub fn parse(yaml: &str) -> ParsedSessionInfo {
+    if let Ok(parsed) = serde_yaml::from_str(yaml) {
+        return parsed;
+    }
+    let mut parsed = ParsedSessionInfo::default();
+    let mut sections: Vec<(usize, &str)> = Vec::new();
+    let mut offset = 0;
+    for line in yaml.split_inclusive('\n') {
+        if !line.starts_with([' ', '\t', '-', '#', '\r', '\n']) && line.contains(':') {
+            sections.push((offset, line.split(':').next().unwrap_or_default().trim()));
+        }
+        offset += line.len();
+    }
+    for (n, &(start, name)) in sections.iter().enumerate() {
+        let end = sections.get(n + 1).map_or(yaml.len(), |&(next, _)| next);
+        let Ok(section) = serde_yaml::from_str::<ParsedSessionInfo>(&yaml[start..end]) else {
+            continue;
+        };
+        match name {
+            "WeekendInfo" => parsed.weekend_info = section.weekend_info,
+            "SessionInfo" => parsed.session_info = section.session_info,
+            "DriverInfo" => parsed.driver_info = section.driver_info,
+            _ => {},
+        }
+    }
+    parsed
+}
+
+/// Derived channel that parses the session info when it changes and attaches it to every frame
+#[derive(Default)]
+pub struct SessionInfoParser {
+    last_session_info: String,
+    parsed: Option<ParsedSessionInfo>,
+}
+
+impl SessionInfoParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl DerivedChannel for SessionInfoParser {
+    fn name(&self) -> &'static str {
+        "session_info_parsed"
+    }
+
+    fn compute(&mut self, data: &mut TelemetryData) {
+        if data.session_info.is_empty() {
+            return;
+        }
+        if data.session_info != self.last_session_info {
+            self.last_session_info = data.session_info.clone();
+            self.parsed = Some(parse(&data.session_info));
+        }
+        data.session_info_parsed = self.parsed.clone();
+    }
+}
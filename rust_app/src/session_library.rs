@@ -0,0 +1,204 @@
+//! Historical sessions for the session browser.
+//!
+//! Watches a folder of speedforge recordings (JSON lines) and, optionally, a folder of
+//! .ibt files recorded by iRacing itself. Each new or changed file is summarized once
+//! (laps, sectors, best lap, conditions) and listed next to the others, so archives from
+//! before speedforge show up in the browser without converting them first.
+//!
+//! `GET /compare?a=<id>&b=<id>` compares two speedforge recordings from the sessions
+//! folder (see session_compare.rs), by the ids the library lists them under.
+
+use crate::http_api::{HttpHandler, HttpRequest, HttpResponse};
+use crate::ibt::IbtFile;
+use crate::protocol;
+use crate::session_compare::{self, SessionSummary};
+use crate::websocket_server::RequestHandler;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How often the watched folders are rescanned
+pub const SCAN_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionSource {
+    /// Recorded by speedforge
+    Speedforge,
+    /// Recorded by iRacing (.ibt)
+    Ibt,
+}
+
+/// One session in the library
+#[derive(Serialize, Clone, Debug)]
+pub struct LibraryEntry {
+    /// File name, unique within the library
+    pub id: String,
+    pub source: SessionSource,
+    /// Unix time of the last modification of the file
+    pub modified: u64,
+    pub summary: SessionSummary,
+}
+
+#[derive(Default)]
+struct Library {
+    entries: BTreeMap<String, LibraryEntry>,
+    // Files that failed to parse, by modification time, so they aren't retried every scan
+    failed: BTreeMap<String, u64>,
+}
+
+/// Sessions found in the watched folders, shared with the server
+#[derive(Clone, Default)]
+pub struct SessionLibrary {
+    library: Arc<Mutex<Library>>,
+}
+
+fn modified_secs(path: &Path) -> u64 {
+    fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |age| age.as_secs())
+}
+
+impl SessionLibrary {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Scan `recordings_dir` and `ibt_dir` now and then every SCAN_INTERVAL on a background thread
+    pub fn watch(&self, recordings_dir: Option<PathBuf>, ibt_dir: Option<PathBuf>) {
+        let library = self.clone();
+        std::thread::spawn(move || loop {
+            if let Some(dir) = &recordings_dir {
+                library.scan(dir, "jsonl", SessionSource::Speedforge);
+            }
+            if let Some(dir) = &ibt_dir {
+                library.scan(dir, "ibt", SessionSource::Ibt);
+            }
+            std::thread::sleep(SCAN_INTERVAL);
+        });
+    }
+
+    /// Summarize files in `dir` with the given extension that are new or changed
+    pub fn scan(&self, dir: &Path, extension: &str, source: SessionSource) {
+        let Ok(listing) = fs::read_dir(dir) else {
+            return;
+        };
+        let paths: Vec<PathBuf> = listing
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case(extension)))
+            .collect();
+        let file_id = |path: &Path| path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+
+        // Forget files that were deleted (by hand or by retention)
+        {
+            let present: std::collections::HashSet<String> = paths.iter().map(|path| file_id(path)).collect();
+            let mut library = self.library.lock().unwrap();
+            library.entries.retain(|id, entry| entry.source != source || present.contains(id));
+        }
+
+        for path in paths {
+            let id = file_id(&path);
+            let modified = modified_secs(&path);
+            // iRacing keeps writing the .ibt while on track; wait until it has been left alone
+            let settled = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .is_none_or(|now| now.as_secs().saturating_sub(modified) >= SCAN_INTERVAL.as_secs());
+            {
+                let library = self.library.lock().unwrap();
+                let known = library.entries.get(&id).map(|entry| entry.modified).or_else(|| library.failed.get(&id).copied());
+                if known == Some(modified) || !settled {
+                    continue;
+                }
+            }
+
+            let summary = match source {
+                SessionSource::Speedforge => SessionSummary::from_file(&path),
+                SessionSource::Ibt => IbtFile::open(&path).and_then(|ibt| SessionSummary::from_frames(&path, ibt.frames())),
+            };
+            let mut library = self.library.lock().unwrap();
+            match summary {
+                Ok(summary) => {
+                    tracing::info!("Added {} ({} laps)", id, summary.laps.len());
+                    library.failed.remove(&id);
+                    library.entries.insert(id.clone(), LibraryEntry { id, source, modified, summary });
+                },
+                Err(e) => {
+                    tracing::warn!("Skipping {}: {}", path.display(), e);
+                    library.failed.insert(id, modified);
+                },
+            }
+        }
+    }
+
+    /// Entries without their lap tables, newest first
+    fn list(&self) -> Vec<Value> {
+        let library = self.library.lock().unwrap();
+        let mut entries: Vec<&LibraryEntry> = library.entries.values().collect();
+        entries.sort_by(|a, b| b.modified.cmp(&a.modified));
+        entries
+            .into_iter()
+            .map(|entry| json!({
+                "id": entry.id,
+                "source": entry.source,
+                "modified": entry.modified,
+                "track": entry.summary.track,
+                "car": entry.summary.car,
+                "laps": entry.summary.laps.len(),
+                "best_lap": entry.summary.best_lap.as_ref().map(|lap| lap.lap_time),
+            }))
+            .collect()
+    }
+
+    fn get(&self, id: &str) -> Option<LibraryEntry> {
+        self.library.lock().unwrap().entries.get(id).cloned()
+    }
+
+    // Comparison of two recordings from the sessions folder, from their cached summaries
+    fn compare(&self, request: &HttpRequest) -> HttpResponse {
+        let (Some(a), Some(b)) = (request.query.get("a"), request.query.get("b")) else {
+            return HttpResponse::error(400, "Usage: /compare?a=<session id>&b=<session id>");
+        };
+        let recording = |id: &str| self.get(id).filter(|entry| entry.source == SessionSource::Speedforge);
+        let (Some(first), Some(second)) = (recording(a), recording(b)) else {
+            return HttpResponse::error(404, "Both sessions must be recordings in the sessions folder");
+        };
+        match serde_json::to_value(session_compare::compare(first.summary, second.summary)) {
+            Ok(report) => HttpResponse::json(200, &report),
+            Err(e) => HttpResponse::error(500, &e.to_string()),
+        }
+    }
+}
+
+impl HttpHandler for SessionLibrary {
+    fn handle(&self, request: &HttpRequest) -> Option<HttpResponse> {
+        let response = match request.segments().as_slice() {
+            ["compare"] => match request.method.as_str() {
+                "GET" => self.compare(request),
+                _ => HttpResponse::error(405, "Method not allowed"),
+            },
+            _ => return None,
+        };
+        Some(response)
+    }
+}
+
+impl RequestHandler for SessionLibrary {
+    /// `{"type":"get_sessions"}` lists the library; `{"type":"get_session","id":"..."}`
+    /// returns one session with its lap and sector table
+    fn handle(&self, request: &Value) -> Value {
+        match request.get("id").and_then(Value::as_str) {
+            Some(id) => match self.get(id) {
+                Some(entry) => json!({ "type": "session", "session": entry }),
+                None => protocol::error(protocol::ERROR_NOT_FOUND, format!("Unknown session '{}'", id), Some("get_session")),
+            },
+            None => json!({ "type": "sessions", "sessions": self.list() }),
+        }
+    }
+}
@@ -0,0 +1,171 @@
+//! Lightweight lookups into the raw session info YAML.
+//!
+//! The session string is large and parsing it fully every frame is wasteful, so modules
+//! that only need a handful of scalars (shift RPMs, track length...) read them here.
+
+/// Find the first `Key: value` line for the given key and return the trimmed value
+pub fn scalar<'a>(yaml: &'a str, key: &str) -> Option<&'a str> {
+    for line in yaml.lines() {
+        let trimmed = line.trim_start().trim_start_matches("- ");
+        if let Some(rest) = trimmed.strip_prefix(key) && let Some(value) = rest.strip_prefix(':') {
+            let value = value.trim().trim_matches('"');
+            if !value.is_empty() {
+                return Some(value);
+            }
+        }
+    }
+    None
+}
+
+/// Find a numeric scalar, ignoring any unit suffix ("4.5 km", "3600.0 sec")
+pub fn number(yaml: &str, key: &str) -> Option<f32> {
+    let value = scalar(yaml, key)?;
+    value.split_whitespace().next()?.parse().ok()
+}
+
+/// One entry of the `DriverInfo: Drivers:` list
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DriverEntry {
+    pub car_idx: i32,
+    pub user_name: String,
+    pub user_id: i64,
+    pub team_name: String,
+    pub car_id: i64,
+    pub car_screen_name: String,
+    pub car_class_id: i32,
+    pub car_class_short_name: String,
+    pub i_rating: i32,
+    /// License class and safety rating, e.g. "A 3.41"
+    pub license: String,
+    /// Class color as "#rrggbb"
+    pub car_class_color: String,
+    /// Number as painted, e.g. "007" (CarNumberRaw loses the leading zeros)
+    pub car_number: String,
+    /// Car folder name, e.g. "mx5 mx52016"
+    pub car_path: String,
+    pub team_id: i64,
+    /// Livery as "pattern,color,color,color"
+    pub car_design: String,
+    pub car_number_design: String,
+    pub is_pace_car: bool,
+    pub is_spectator: bool,
+}
+
+/// Current driver of every car, read from the `Drivers:` list without a full YAML parse
+pub fn drivers(yaml: &str) -> Vec<DriverEntry> {
+    let mut drivers = Vec::new();
+    let mut current: Option<(usize, DriverEntry)> = None;
+    let mut in_drivers = false;
+
+    for line in yaml.lines() {
+        let indent = line.len() - line.trim_start().len();
+        let trimmed = line.trim();
+        if trimmed == "Drivers:" {
+            in_drivers = true;
+            continue;
+        }
+        if !in_drivers || trimmed.is_empty() {
+            continue;
+        }
+
+        if let Some(idx) = trimmed.strip_prefix("- CarIdx:") {
+            drivers.extend(current.take().map(|(_, entry)| entry));
+            let entry = DriverEntry {
+                car_idx: idx.trim().parse().unwrap_or(-1),
+                ..DriverEntry::default()
+            };
+            current = Some((indent, entry));
+            continue;
+        }
+
+        let Some((list_indent, entry)) = current.as_mut() else {
+            continue;
+        };
+        // A key indented no deeper than the list markers ends the list
+        if indent <= *list_indent && !trimmed.starts_with('-') {
+            drivers.extend(current.take().map(|(_, entry)| entry));
+            in_drivers = false;
+            continue;
+        }
+        if let Some((key, value)) = trimmed.split_once(':') {
+            let value = value.trim().trim_matches('"');
+            match key.trim() {
+                "UserName" => entry.user_name = value.to_string(),
+                "UserID" => entry.user_id = value.parse().unwrap_or(0),
+                "TeamName" => entry.team_name = value.to_string(),
+                "CarID" => entry.car_id = value.parse().unwrap_or(0),
+                "CarScreenName" => entry.car_screen_name = value.to_string(),
+                "CarClassID" => entry.car_class_id = value.parse().unwrap_or(0),
+                "CarClassShortName" => entry.car_class_short_name = value.to_string(),
+                "IRating" => entry.i_rating = value.parse().unwrap_or(0),
+                "LicString" => entry.license = value.to_string(),
+                "CarNumber" => entry.car_number = value.to_string(),
+                "CarPath" => entry.car_path = value.to_string(),
+                "TeamID" => entry.team_id = value.parse().unwrap_or(0),
+                "CarDesignStr" => entry.car_design = value.to_string(),
+                "CarNumberDesignStr" => entry.car_number_design = value.to_string(),
+                "CarIsPaceCar" => entry.is_pace_car = value == "1",
+                "IsSpectator" => entry.is_spectator = value == "1",
+                "CarClassColor" => {
+                    // Usually a hex literal (0xffda59), occasionally a plain number
+                    let color = match value.strip_prefix("0x") {
+                        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                        None => value.parse::<u32>().ok(),
+                    };
+                    if let Some(color) = color {
+                        entry.car_class_color = format!("#{:06x}", color & 0xffffff);
+                    }
+                },
+                _ => {},
+            }
+        }
+    }
+    drivers.extend(current.map(|(_, entry)| entry));
+    drivers
+}
+
+/// Tire compounds available to the player's car from `DriverTires:`, as (TireIndex, TireCompoundType)
+pub fn tire_compounds(yaml: &str) -> Vec<(i32, String)> {
+    let mut compounds = Vec::new();
+    let mut in_tires = false;
+    let mut index = None;
+
+    for line in yaml.lines() {
+        let trimmed = line.trim();
+        if trimmed == "DriverTires:" {
+            in_tires = true;
+            continue;
+        }
+        if !in_tires || trimmed.is_empty() {
+            continue;
+        }
+        if let Some(value) = trimmed.strip_prefix("- TireIndex:") {
+            index = value.trim().parse().ok();
+        } else if let Some(value) = trimmed.strip_prefix("TireCompoundType:") {
+            if let Some(index) = index.take() {
+                compounds.push((index, value.trim().trim_matches('"').to_string()));
+            }
+        } else if !trimmed.starts_with('-') && index.is_none() {
+            // Next key after the list
+            break;
+        }
+    }
+    compounds
+}
+
+/// SessionType ("Race", "Practice"...) of the given entry in the `Sessions:` list
+pub fn session_type(yaml: &str, session_num: i32) -> Option<&str> {
+    let marker = format!("- SessionNum: {}", session_num);
+    let mut in_session = false;
+    for line in yaml.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("- SessionNum:") {
+            in_session = trimmed == marker;
+        } else if in_session {
+            if let Some(value) = trimmed.strip_prefix("SessionType:") {
+                return Some(value.trim().trim_matches('"'));
+            }
+        }
+    }
+    None
+}
@@ -0,0 +1,154 @@
+//! `speedforge init`: interactive first-run setup that writes the config file.
+//!
+//! Asks for the bind address and WebSocket port (checking the port is free), where session
+//! recordings go, whether to protect HTTP actions and `/admin` with a token, and which
+//! outputs to write (log files, highlight exports). The answers are saved as a config file
+//! (see config.rs) that the server reads on its next start. Pressing enter keeps the default.
+
+use crate::config;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, BufRead, Write};
+use std::net::{IpAddr, SocketAddr, TcpListener};
+use std::path::Path;
+use toml::{Table, Value};
+
+const DEFAULT_BIND: &str = "0.0.0.0";
+const DEFAULT_PORT: u16 = 8080;
+const DEFAULT_SESSIONS_DIR: &str = "sessions";
+const DEFAULT_HIGHLIGHTS_DIR: &str = "highlights";
+
+// Reads answers from stdin; end of input takes the defaults for the remaining questions
+struct Prompter<R> {
+    input: R,
+}
+
+impl<R: BufRead> Prompter<R> {
+    fn ask(&mut self, question: &str, default: &str) -> String {
+        if default.is_empty() {
+            print!("{}: ", question);
+        } else {
+            print!("{} [{}]: ", question, default);
+        }
+        let _ = io::stdout().flush();
+        let mut line = String::new();
+        match self.input.read_line(&mut line) {
+            Ok(0) | Err(_) => {
+                println!();
+                default.to_string()
+            },
+            _ if line.trim().is_empty() => default.to_string(),
+            _ => line.trim().to_string(),
+        }
+    }
+
+    fn confirm(&mut self, question: &str, default: bool) -> bool {
+        let hint = if default { "Y/n" } else { "y/N" };
+        loop {
+            // An empty answer comes back as the hint
+            let answer = self.ask(question, hint);
+            match answer.to_ascii_lowercase().as_str() {
+                _ if answer == hint => return default,
+                "y" | "yes" => return true,
+                "n" | "no" => return false,
+                _ => println!("Please answer yes or no"),
+            }
+        }
+    }
+}
+
+/// Whether the server could listen on `port` at `bind`
+pub fn port_is_free(bind: IpAddr, port: u16) -> bool {
+    TcpListener::bind(SocketAddr::new(bind, port)).is_ok()
+}
+
+// 128 random bits as hex, seeded by the OS through the standard library's hasher keys
+fn generate_token() -> String {
+    (0..2)
+        .map(|part| {
+            let mut hasher = RandomState::new().build_hasher();
+            hasher.write_u64(part);
+            format!("{:016x}", hasher.finish())
+        })
+        .collect()
+}
+
+/// Run the wizard; returns the process exit code
+pub fn run_cli(args: &[String]) -> i32 {
+    let path = args.iter().find(|arg| !arg.starts_with("--")).map(String::as_str).unwrap_or(config::DEFAULT_CONFIG_FILE);
+    let force = args.iter().any(|arg| arg == "--force");
+    let stdin = io::stdin();
+    let mut prompter = Prompter { input: stdin.lock() };
+
+    println!("speedforge setup - press enter to keep the value in brackets");
+    if Path::new(path).exists() && !force && !prompter.confirm(&format!("{} exists, overwrite it?", path), false) {
+        println!("Nothing written");
+        return 1;
+    }
+
+    let mut table = Table::new();
+
+    let bind = loop {
+        let answer = prompter.ask("Address to listen on (0.0.0.0 for the whole network, 127.0.0.1 for this PC only)", DEFAULT_BIND);
+        match answer.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() {
+            Ok(ip) => break (answer, ip),
+            Err(_) => println!("'{}' is not an IP address", answer),
+        }
+    };
+    let port = loop {
+        let answer = prompter.ask("WebSocket port", &DEFAULT_PORT.to_string());
+        match answer.parse::<u16>() {
+            Ok(0) | Err(_) => println!("'{}' is not a port number (1-65535)", answer),
+            Ok(port) if !port_is_free(bind.1, port) => {
+                println!("Port {} is already in use on {}; close the program using it or pick another", port, bind.0);
+            },
+            Ok(port) => break port,
+        }
+    };
+    if bind.0 != DEFAULT_BIND {
+        table.insert("bind".to_string(), Value::String(bind.0));
+    }
+    table.insert("port".to_string(), Value::Integer(port as i64));
+
+    let sessions_dir = prompter.ask("Folder for session recordings and logs", DEFAULT_SESSIONS_DIR);
+    table.insert("output-dir".to_string(), Value::String(sessions_dir));
+
+    if prompter.confirm("Allow HTTP actions and the admin endpoint (Stream Deck, VoiceAttack)? They need a token", false) {
+        let token = prompter.ask("Token (leave empty to generate one)", "");
+        let token = if token.is_empty() { generate_token() } else { token };
+        println!("Token: {}", token);
+        table.insert("api-token".to_string(), Value::String(token));
+    }
+
+    println!("Outputs:");
+    if !prompter.confirm("  Write log files", true) {
+        table.insert("no-log-file".to_string(), Value::Boolean(true));
+    }
+    if prompter.confirm("  Export highlight markers at the end of each session", false) {
+        let dir = prompter.ask("  Folder for highlight exports", DEFAULT_HIGHLIGHTS_DIR);
+        table.insert("highlights-dir".to_string(), Value::String(dir));
+    }
+    if prompter.confirm("  Publish finish position probabilities in races", false) {
+        table.insert("finish-sim".to_string(), Value::Boolean(true));
+    }
+
+    let content = match toml::to_string_pretty(&table) {
+        Ok(content) => content,
+        Err(e) => {
+            eprintln!("Failed to build the config: {}", e);
+            return 1;
+        }
+    };
+    let content = format!("# Written by `speedforge init`; keys are command line flags without the dashes\n{}", content);
+    if let Err(e) = std::fs::write(path, content) {
+        eprintln!("Failed to write {}: {}", path, e);
+        return 1;
+    }
+    println!("Saved {}", path);
+    if path == config::DEFAULT_CONFIG_FILE {
+        println!("Start the server with `speedforge` from this folder");
+    } else {
+        println!("Start the server with `speedforge --config {}`", path);
+    }
+    0
+}
@@ -0,0 +1,77 @@
+use crate::derived::DerivedChannel;
+use crate::telemetry_fields::TelemetryData;
+use serde::{Serialize, Deserialize};
+
+/// LEDs in the bar reported to clients; dashboards with a different count can scale `fraction`
+pub const LED_COUNT: u32 = 10;
+// Blink rate once the blink RPM is reached
+const BLINK_HZ: f32 = 4.0;
+
+/// Shift light state of the player's car for the current frame
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ShiftLights {
+    /// "off" below the first light, "on" while filling, "shift" at the shift point, "blink" past the blink point
+    pub state: String,
+    pub leds_lit: u32,
+    pub led_count: u32,
+    /// 0..1 progress from the first light to the shift point
+    pub fraction: f32,
+    /// Whether blinking lights are currently lit
+    pub blink_on: bool,
+    pub first_rpm: f32,
+    pub shift_rpm: f32,
+    pub blink_rpm: f32,
+}
+
+/// Derived channel that turns the car's shift light RPMs into LED states
+pub struct ShiftLightCalculator;
+
+impl DerivedChannel for ShiftLightCalculator {
+    fn name(&self) -> &'static str {
+        "shift_lights"
+    }
+
+    fn dependencies(&self) -> &'static [&'static str] {
+        &["metadata"]
+    }
+
+    fn compute(&mut self, data: &mut TelemetryData) {
+        let Some(car) = data.session_metadata.as_ref().and_then(|metadata| metadata.car.as_ref()) else {
+            return;
+        };
+        let (Some(first_rpm), Some(shift_rpm)) = (car.shift_first_rpm, car.shift_rpm) else {
+            return;
+        };
+        if shift_rpm <= first_rpm {
+            return;
+        }
+        let blink_rpm = car.shift_blink_rpm.filter(|&blink| blink >= shift_rpm).unwrap_or(shift_rpm);
+
+        let fraction = ((data.rpm - first_rpm) / (shift_rpm - first_rpm)).clamp(0.0, 1.0);
+        let state = if data.rpm < first_rpm {
+            "off"
+        } else if data.rpm >= blink_rpm && blink_rpm > shift_rpm {
+            "blink"
+        } else if data.rpm >= shift_rpm {
+            "shift"
+        } else {
+            "on"
+        };
+        let leds_lit = match state {
+            "off" => 0,
+            "on" => ((fraction * LED_COUNT as f32).ceil() as u32).clamp(1, LED_COUNT),
+            _ => LED_COUNT,
+        };
+
+        data.shift_lights = Some(ShiftLights {
+            state: state.to_string(),
+            leds_lit,
+            led_count: LED_COUNT,
+            fraction,
+            blink_on: state == "blink" && ((data.SessionTime * BLINK_HZ * 2.0) as u64).is_multiple_of(2),
+            first_rpm,
+            shift_rpm,
+            blink_rpm,
+        });
+    }
+}
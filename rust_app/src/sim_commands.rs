@@ -0,0 +1,120 @@
+//! Commands sent to the sim through the iRacing broadcast message.
+//!
+//! iRacing listens for the registered window message `IRSDK_BROADCASTMSG` sent to all
+//! top level windows; the command and its first argument are packed into wParam and
+//! the second argument goes in lParam.
+
+// irsdk_BroadcastMsg
+const BROADCAST_PIT_COMMAND: u16 = 9;
+const BROADCAST_REPLAY_SEARCH_SESSION_TIME: u16 = 12;
+
+/// A pit service request (irsdk_PitCommandMode)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PitCommand {
+    /// Clear all pit checkboxes
+    Clear,
+    Windshield,
+    /// Add fuel, liters (0 keeps the current amount)
+    Fuel(u32),
+    /// Change one tire, optionally setting its pressure in kPa
+    LeftFront(u32),
+    RightFront(u32),
+    LeftRear(u32),
+    RightRear(u32),
+    ClearTires,
+    FastRepair,
+    ClearWindshield,
+    ClearFastRepair,
+    ClearFuel,
+}
+
+impl PitCommand {
+    /// Parse commands like "fuel 40", "lf", "tires", "fast_repair" or "clear"
+    pub fn parse(command: &str) -> Result<Vec<Self>, String> {
+        let mut parts = command.split_whitespace();
+        let name = parts.next().ok_or("Empty pit command")?.to_ascii_lowercase();
+        let value = match parts.next() {
+            Some(value) => value.parse::<u32>().map_err(|_| format!("Invalid value in pit command: {}", command))?,
+            None => 0,
+        };
+        let commands = match name.as_str() {
+            "clear" => vec![Self::Clear],
+            "windshield" | "ws" | "tearoff" => vec![Self::Windshield],
+            "fuel" => vec![Self::Fuel(value)],
+            "lf" => vec![Self::LeftFront(value)],
+            "rf" => vec![Self::RightFront(value)],
+            "lr" => vec![Self::LeftRear(value)],
+            "rr" => vec![Self::RightRear(value)],
+            "tires" | "tyres" => vec![Self::LeftFront(value), Self::RightFront(value), Self::LeftRear(value), Self::RightRear(value)],
+            "clear_tires" | "clear_tyres" => vec![Self::ClearTires],
+            "fast_repair" | "fr" => vec![Self::FastRepair],
+            "clear_windshield" | "clear_ws" => vec![Self::ClearWindshield],
+            "clear_fast_repair" | "clear_fr" => vec![Self::ClearFastRepair],
+            "clear_fuel" => vec![Self::ClearFuel],
+            _ => return Err(format!("Unknown pit command: {}", name)),
+        };
+        Ok(commands)
+    }
+
+    // (mode, value) as expected by the sim
+    fn encode(self) -> (u16, u32) {
+        match self {
+            Self::Clear => (0, 0),
+            Self::Windshield => (1, 0),
+            Self::Fuel(liters) => (2, liters),
+            Self::LeftFront(kpa) => (3, kpa),
+            Self::RightFront(kpa) => (4, kpa),
+            Self::LeftRear(kpa) => (5, kpa),
+            Self::RightRear(kpa) => (6, kpa),
+            Self::ClearTires => (7, 0),
+            Self::FastRepair => (8, 0),
+            Self::ClearWindshield => (9, 0),
+            Self::ClearFastRepair => (10, 0),
+            Self::ClearFuel => (11, 0),
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod win32 {
+    use std::ffi::c_void;
+
+    pub const HWND_BROADCAST: *mut c_void = 0xffff as *mut c_void;
+
+    #[link(name = "user32")]
+    unsafe extern "system" {
+        pub fn RegisterWindowMessageA(name: *const u8) -> u32;
+        pub fn SendNotifyMessageA(hwnd: *mut c_void, msg: u32, w_param: usize, l_param: isize) -> i32;
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn broadcast(message: u16, var1: u16, var2: u32) -> Result<(), String> {
+    let msg_id = unsafe { win32::RegisterWindowMessageA(b"IRSDK_BROADCASTMSG\0".as_ptr()) };
+    if msg_id == 0 {
+        return Err("Failed to register the iRacing broadcast message".to_string());
+    }
+    let w_param = (message as usize) | ((var1 as usize) << 16);
+    let ok = unsafe { win32::SendNotifyMessageA(win32::HWND_BROADCAST, msg_id, w_param, var2 as isize) };
+    if ok == 0 {
+        return Err("Failed to send broadcast message to iRacing".to_string());
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "windows"))]
+fn broadcast(_message: u16, _var1: u16, _var2: u32) -> Result<(), String> {
+    Err("Sim commands are only supported on Windows".to_string())
+}
+
+/// Send a pit service request to the sim
+pub fn send_pit_command(command: PitCommand) -> Result<(), String> {
+    let (mode, value) = command.encode();
+    broadcast(BROADCAST_PIT_COMMAND, mode, value)
+}
+
+/// Move the replay to a moment in a session
+pub fn replay_search_session_time(session_num: i32, session_time_s: f64) -> Result<(), String> {
+    let session_num = u16::try_from(session_num).map_err(|_| format!("Invalid session number {}", session_num))?;
+    broadcast(BROADCAST_REPLAY_SEARCH_SESSION_TIME, session_num, (session_time_s.max(0.0) * 1000.0) as u32)
+}
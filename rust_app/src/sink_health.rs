@@ -0,0 +1,122 @@
+//! Health of the outputs that run beside the broadcast.
+//!
+//! File writes (file_io.rs), the log file (log_file.rs) and session exports and uploads
+//! (auto_export.rs) report each success and failure here under their sink name. None of
+//! them can hold up the telemetry thread or the WebSocket broadcast: their work is queued
+//! to threads of their own, a panicking job is caught and counted instead of killing its
+//! thread (`run_guarded`), and a sink that keeps failing is retried after `backoff`, so
+//! it comes back by itself once the disk or server does. The state is shown by the
+//! `server_status` command and the admin panel.
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Wait before the first retry of a failed sink; doubles with each failure
+pub const MIN_BACKOFF: Duration = Duration::from_secs(1);
+/// Longest wait between retries
+pub const MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+/// State of one sink
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct SinkHealth {
+    /// False while the last attempt failed
+    pub ok: bool,
+    /// Local time of the last successful write
+    pub last_success: Option<String>,
+    pub last_error: Option<String>,
+    pub last_error_at: Option<String>,
+    /// Failures since start
+    pub errors: u64,
+    /// Failures since the last success
+    pub consecutive_failures: u32,
+    /// Work waiting to be done
+    pub queue_depth: usize,
+    /// Times the sink was restarted after failing
+    pub restarts: u32,
+}
+
+static SINKS: Mutex<BTreeMap<String, SinkHealth>> = Mutex::new(BTreeMap::new());
+
+fn now() -> String {
+    chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+fn update(name: &str, change: impl FnOnce(&mut SinkHealth)) {
+    if let Ok(mut sinks) = SINKS.lock() {
+        change(sinks.entry(name.to_string()).or_default());
+    }
+}
+
+/// A write to `name` succeeded
+pub fn success(name: &str) {
+    update(name, |health| {
+        health.ok = true;
+        health.last_success = Some(now());
+        health.consecutive_failures = 0;
+    });
+}
+
+/// A write to `name` failed
+pub fn failure(name: &str, error: &str) {
+    update(name, |health| {
+        health.ok = false;
+        health.last_error = Some(error.to_string());
+        health.last_error_at = Some(now());
+        health.errors += 1;
+        health.consecutive_failures += 1;
+    });
+}
+
+/// `name` was restarted after failing
+pub fn restarted(name: &str) {
+    update(name, |health| health.restarts += 1);
+}
+
+pub fn set_queue_depth(name: &str, depth: usize) {
+    update(name, |health| health.queue_depth = depth);
+}
+
+/// Every sink that reported so far, by name
+pub fn snapshot() -> BTreeMap<String, SinkHealth> {
+    SINKS.lock().map(|sinks| sinks.clone()).unwrap_or_default()
+}
+
+/// Wait before retrying a sink after `failures` failures in a row
+pub fn backoff(failures: u32) -> Duration {
+    MIN_BACKOFF.saturating_mul(1 << failures.saturating_sub(1).min(16)).min(MAX_BACKOFF)
+}
+
+/// Run `job` of `name`, catching a panic: it counts as a failure and gives None, and the
+/// calling thread carries on
+pub fn catch<T>(name: &str, job: impl FnOnce() -> T) -> Option<T> {
+    match panic::catch_unwind(AssertUnwindSafe(job)) {
+        Ok(value) => Some(value),
+        Err(panic) => {
+            let message = panic
+                .downcast_ref::<&str>()
+                .map(|text| text.to_string())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_string());
+            failure(name, &format!("panicked: {}", message));
+            None
+        },
+    }
+}
+
+/// Run one job of `name` and report its outcome. Returns whether it succeeded
+pub fn run_guarded(name: &str, job: impl FnOnce() -> Result<(), String>) -> bool {
+    match catch(name, job) {
+        Some(Ok(())) => {
+            success(name);
+            true
+        },
+        Some(Err(e)) => {
+            failure(name, &e);
+            false
+        },
+        None => false,
+    }
+}
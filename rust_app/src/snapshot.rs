@@ -0,0 +1,106 @@
+//! Latest frame over plain HTTP, for tools that poll now and then instead of streaming.
+//!
+//! - `GET /telemetry/latest` returns the most recent frame as the /telemetry endpoint sends
+//!   it: current field names, without the session info. `?schema=1` gives the original
+//!   names (see field_compat.rs) and `?groups=timing,fuel` only those field groups (see
+//!   field_groups.rs).
+//! - `GET /session` returns the session info: `{"session_time":..., "session_info":"<yaml>",
+//!   "session_info_parsed":{...}}`.
+//!
+//! Both answer 503 until the first frame has been published.
+
+use crate::field_compat;
+use crate::field_groups;
+use crate::frame_schema::{MIN_SCHEMA_VERSION, SCHEMA_VERSION};
+use crate::http_api::{HttpHandler, HttpRequest, HttpResponse};
+use crate::precision::PrecisionRules;
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+
+/// Latest published frame, shared between the telemetry thread and the HTTP handler
+#[derive(Clone)]
+pub struct SnapshotStore {
+    frame: Arc<Mutex<Option<Arc<Value>>>>,
+    precision: PrecisionRules,
+}
+
+impl SnapshotStore {
+    /// `precision` is applied to the frames served, as for WebSocket clients
+    pub fn new(precision: PrecisionRules) -> Self {
+        Self {
+            frame: Arc::new(Mutex::new(None)),
+            precision,
+        }
+    }
+
+    /// Keep `frame` (the serialized `TelemetryData`) as the latest
+    pub fn update(&self, frame: Value) {
+        if let Ok(mut latest) = self.frame.lock() {
+            *latest = Some(Arc::new(frame));
+        }
+    }
+
+    fn latest(&self) -> Option<Arc<Value>> {
+        self.frame.lock().ok()?.clone()
+    }
+
+    fn telemetry(&self, request: &HttpRequest) -> HttpResponse {
+        let schema_version = match request.query.get("schema").map(|value| value.parse::<u32>()) {
+            None => SCHEMA_VERSION,
+            Some(Ok(version)) if (MIN_SCHEMA_VERSION..=SCHEMA_VERSION).contains(&version) => version,
+            Some(_) => {
+                let message = format!("schema must be {} to {}", MIN_SCHEMA_VERSION, SCHEMA_VERSION);
+                return HttpResponse::error(400, &message);
+            },
+        };
+        let groups: Vec<String> = request
+            .query
+            .get("groups")
+            .map(|list| list.split(',').map(str::trim).filter(|group| !group.is_empty()).map(str::to_string).collect())
+            .unwrap_or_default();
+        if let Some(unknown) = groups.iter().find(|group| !field_groups::is_known(group)) {
+            let message = format!("Unknown field group '{}' (available: {})", unknown, field_groups::names().join(", "));
+            return HttpResponse::error(400, &message);
+        }
+        let Some(frame) = self.latest() else {
+            return HttpResponse::error(503, "No telemetry yet");
+        };
+        let frame = field_compat::for_schema(&field_groups::filter(&frame, &groups), schema_version);
+        match self.precision.to_string(&frame) {
+            Ok(body) => HttpResponse::raw_json(200, body.into_bytes()),
+            Err(e) => HttpResponse::error(500, &e.to_string()),
+        }
+    }
+
+    fn session(&self) -> HttpResponse {
+        let Some(frame) = self.latest() else {
+            return HttpResponse::error(503, "No telemetry yet");
+        };
+        let session_info = frame.get("session_info").and_then(Value::as_str).unwrap_or_default();
+        if session_info.is_empty() {
+            return HttpResponse::error(503, "No session info yet");
+        }
+        HttpResponse::json(200, &json!({
+            "session_time": frame.get("SessionTime"),
+            "session_info": session_info,
+            "session_info_parsed": frame.get("session_info_parsed"),
+        }))
+    }
+}
+
+impl HttpHandler for SnapshotStore {
+    fn handle(&self, request: &HttpRequest) -> Option<HttpResponse> {
+        let response = match request.segments().as_slice() {
+            ["telemetry", "latest"] => match request.method.as_str() {
+                "GET" => self.telemetry(request),
+                _ => HttpResponse::error(405, "Method not allowed"),
+            },
+            ["session"] => match request.method.as_str() {
+                "GET" => self.session(),
+                _ => HttpResponse::error(405, "Method not allowed"),
+            },
+            _ => return None,
+        };
+        Some(response)
+    }
+}
@@ -0,0 +1,417 @@
+//! Storage backends for recordings and exports.
+//!
+//! A backend stores files under a key (a relative path with `/` separators) and is picked
+//! from a URL: a folder path or `file://...` for a local or network disk, `https://...` for a
+//! WebDAV folder (`--upload-user`, password in SPEEDFORGE_UPLOAD_PASSWORD) and
+//! `s3://bucket/prefix` for S3 or a compatible service (`--s3-region`, `--s3-endpoint`,
+//! AWS_ACCESS_KEY_ID / AWS_SECRET_ACCESS_KEY). Files are streamed from disk rather than read
+//! into memory. S3 files over `PART_SIZE` go up as a multipart upload; finished parts are
+//! recorded next to the file in `<file>.upload.json`, so putting the same file again after a
+//! failure carries on where the upload stopped. The WebDAV and S3 backends need the "upload"
+//! feature. `speedforge upload <file>... --to <url>` pushes files by hand, e.g. from each
+//! driver's rig to a shared bucket.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Size of each part of a multipart upload; S3 wants at least 5 MiB
+#[cfg_attr(not(feature = "upload"), allow(dead_code))]
+pub const PART_SIZE: u64 = 8 * 1024 * 1024;
+/// Environment variable holding the WebDAV password
+#[cfg_attr(not(feature = "upload"), allow(dead_code))]
+pub const UPLOAD_PASSWORD_ENV: &str = "SPEEDFORGE_UPLOAD_PASSWORD";
+
+/// Somewhere files can be stored
+pub trait Storage: Send + Sync {
+    /// Where files go, for logs
+    fn describe(&self) -> String;
+    /// Store the file at `path` under `key`; returns where it went
+    fn put_file(&self, path: &Path, key: &str) -> Result<String, String>;
+}
+
+/// A folder on a local or network disk
+pub struct LocalDisk {
+    dir: PathBuf,
+}
+
+impl LocalDisk {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+}
+
+impl Storage for LocalDisk {
+    fn describe(&self) -> String {
+        self.dir.display().to_string()
+    }
+
+    fn put_file(&self, path: &Path, key: &str) -> Result<String, String> {
+        let target = key.split('/').filter(|part| !part.is_empty() && *part != "..").fold(self.dir.clone(), |dir, part| dir.join(part));
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        // Copy under a temporary name so readers never see half a file
+        let partial = target.with_extension("partial");
+        fs::copy(path, &partial)
+            .and_then(|_| fs::rename(&partial, &target))
+            .map_err(|e| {
+                let _ = fs::remove_file(&partial);
+                format!("Failed to copy {} to {}: {}", path.display(), target.display(), e)
+            })?;
+        Ok(target.display().to_string())
+    }
+}
+
+/// Progress of a multipart upload, kept next to the file being uploaded
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[cfg_attr(not(feature = "upload"), allow(dead_code))]
+pub struct UploadState {
+    pub key: String,
+    pub upload_id: String,
+    /// Size and modification time of the file, to notice when it changed
+    pub size: u64,
+    pub modified_s: u64,
+    /// ETags of the parts uploaded so far, in order
+    pub parts: Vec<String>,
+}
+
+#[cfg_attr(not(feature = "upload"), allow(dead_code))]
+impl UploadState {
+    pub fn path_for(file: &Path) -> PathBuf {
+        let mut name = file.file_name().unwrap_or_default().to_os_string();
+        name.push(".upload.json");
+        file.with_file_name(name)
+    }
+
+    /// The saved state for uploading `file` to `key`, unless the file changed since
+    pub fn load(file: &Path, key: &str, size: u64, modified_s: u64) -> Option<Self> {
+        let text = fs::read_to_string(Self::path_for(file)).ok()?;
+        let state: Self = serde_json::from_str(&text).ok()?;
+        (state.key == key && state.size == size && state.modified_s == modified_s).then_some(state)
+    }
+
+    pub fn save(&self, file: &Path) -> Result<(), String> {
+        let path = Self::path_for(file);
+        let text = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        fs::write(&path, text).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
+    pub fn remove(file: &Path) {
+        let _ = fs::remove_file(Self::path_for(file));
+    }
+}
+
+/// The backend for a storage URL, configured from `args`
+pub fn open(url: &str, args: &[String]) -> Result<Arc<dyn Storage>, String> {
+    if let Some(dir) = url.strip_prefix("file://") {
+        return Ok(Arc::new(LocalDisk::new(PathBuf::from(dir))));
+    }
+    if !url.contains("://") {
+        return Ok(Arc::new(LocalDisk::new(PathBuf::from(url))));
+    }
+    let supported = url.starts_with("s3://") || url.starts_with("http://") || url.starts_with("https://");
+    if !supported {
+        return Err(format!("Storage URL '{}' must be a folder or start with file://, https://, http:// or s3://", url));
+    }
+    remote::open(url, args)
+}
+
+/// `speedforge upload <file>... --to <url> [--prefix <dir>]`
+pub fn run_cli(args: &[String]) -> i32 {
+    let files: Vec<&String> = args.iter().take_while(|arg| !arg.starts_with("--")).collect();
+    let url = crate::arg_value(args, "--to").or_else(|| crate::arg_value(args, "--upload-url"));
+    let (false, Some(url)) = (files.is_empty(), url) else {
+        eprintln!("Usage: speedforge upload <file>... --to <folder|https://...|s3://bucket/prefix> [--prefix <dir>]");
+        return 2;
+    };
+    let storage = match open(&url, args) {
+        Ok(storage) => storage,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 2;
+        }
+    };
+    let prefix = crate::arg_value(args, "--prefix").map(|prefix| format!("{}/", prefix.trim_matches('/'))).unwrap_or_default();
+    println!("Uploading {} file(s) to {}", files.len(), storage.describe());
+    let mut failed = 0;
+    for file in files {
+        let path = Path::new(file);
+        let name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+        match storage.put_file(path, &format!("{}{}", prefix, name)) {
+            Ok(location) => println!("Uploaded {} to {}", file, location),
+            Err(e) => {
+                eprintln!("Failed to upload {}: {}", file, e);
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 { 1 } else { 0 }
+}
+
+#[cfg(feature = "upload")]
+mod remote {
+    use super::{Storage, UploadState, PART_SIZE, UPLOAD_PASSWORD_ENV};
+    use reqwest::blocking::{Body, Client, Response};
+    use reqwest::Method;
+    use sha2::{Digest, Sha256};
+    use std::fs::File;
+    use std::io::{Read, Seek, SeekFrom};
+    use std::path::Path;
+    use std::sync::Arc;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    const UNSIGNED_PAYLOAD: &str = "UNSIGNED-PAYLOAD";
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    fn hmac(key: &[u8], message: &[u8]) -> Vec<u8> {
+        const BLOCK: usize = 64;
+        let mut key = if key.len() > BLOCK { Sha256::digest(key).to_vec() } else { key.to_vec() };
+        key.resize(BLOCK, 0);
+        let inner: Vec<u8> = key.iter().map(|byte| byte ^ 0x36).collect();
+        let outer: Vec<u8> = key.iter().map(|byte| byte ^ 0x5c).collect();
+        let inner_hash = Sha256::new().chain_update(&inner).chain_update(message).finalize();
+        Sha256::new().chain_update(&outer).chain_update(inner_hash).finalize().to_vec()
+    }
+
+    // Percent-encode everything but unreserved characters, and '/' unless `slash` is false
+    fn encode(text: &str, slash: bool) -> String {
+        text.bytes()
+            .map(|byte| match byte {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (byte as char).to_string(),
+                b'/' if slash => "/".to_string(),
+                _ => format!("%{:02X}", byte),
+            })
+            .collect()
+    }
+
+    fn client() -> Result<Client, String> {
+        Client::builder()
+            .user_agent(format!("speedforge/{}", crate::version::VERSION))
+            .timeout(Duration::from_secs(600))
+            .build()
+            .map_err(|e| e.to_string())
+    }
+
+    // The response, or an error with the status and the start of the body
+    fn check(response: Result<Response, reqwest::Error>) -> Result<Response, String> {
+        let response = response.map_err(|e| e.to_string())?;
+        if response.status().is_success() {
+            return Ok(response);
+        }
+        let status = response.status();
+        let body = response.text().unwrap_or_default();
+        Err(format!("{}: {}", status, body.chars().take(300).collect::<String>()))
+    }
+
+    fn file_info(path: &Path) -> Result<(u64, u64), String> {
+        let metadata = std::fs::metadata(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let modified_s = metadata.modified().ok().and_then(|time| time.duration_since(UNIX_EPOCH).ok()).map_or(0, |age| age.as_secs());
+        Ok((metadata.len(), modified_s))
+    }
+
+    // Body streaming `len` bytes of the file from `offset`
+    fn file_body(path: &Path, offset: u64, len: u64) -> Result<Body, String> {
+        let mut file = File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+        file.seek(SeekFrom::Start(offset)).map_err(|e| e.to_string())?;
+        Ok(Body::sized(file.take(len), len))
+    }
+
+    // Text between the first <tag> and </tag>
+    fn xml_value(xml: &str, tag: &str) -> Option<String> {
+        let start = xml.find(&format!("<{}>", tag))? + tag.len() + 2;
+        let end = start + xml[start..].find(&format!("</{}>", tag))?;
+        Some(xml[start..end].to_string())
+    }
+
+    /// WebDAV folder; files are PUT below it
+    pub struct WebDav {
+        url: String,
+        user: Option<String>,
+        password: Option<String>,
+        client: Client,
+    }
+
+    impl Storage for WebDav {
+        fn describe(&self) -> String {
+            self.url.clone()
+        }
+
+        fn put_file(&self, path: &Path, key: &str) -> Result<String, String> {
+            let (size, _) = file_info(path)?;
+            let location = format!("{}/{}", self.url.trim_end_matches('/'), encode(key, true));
+            let request = self.client.put(&location).body(file_body(path, 0, size)?);
+            let request = match &self.user {
+                Some(user) => request.basic_auth(user, self.password.as_ref()),
+                None => request,
+            };
+            check(request.send()).map(|_| location)
+        }
+    }
+
+    /// S3 bucket or a compatible service, path-style when `endpoint` is given
+    pub struct S3 {
+        bucket: String,
+        prefix: String,
+        region: String,
+        endpoint: Option<String>,
+        access_key: String,
+        secret_key: String,
+        client: Client,
+    }
+
+    impl S3 {
+        // Signed request for the object `key`; `query` is (name, value) pairs
+        fn request(&self, method: Method, key: &str, query: &[(&str, String)]) -> (String, reqwest::blocking::RequestBuilder) {
+            let object = encode(&format!("{}{}", self.prefix, key), true);
+            let (base, path) = match &self.endpoint {
+                Some(endpoint) => (endpoint.trim_end_matches('/').to_string(), format!("/{}/{}", self.bucket, object)),
+                None => (format!("https://{}.s3.{}.amazonaws.com", self.bucket, self.region), format!("/{}", object)),
+            };
+            let host = base.split_once("://").map_or(base.as_str(), |(_, rest)| rest).to_string();
+            let mut pairs: Vec<(String, String)> = query.iter().map(|(name, value)| (encode(name, false), encode(value, false))).collect();
+            pairs.sort();
+            let query = pairs.iter().map(|(name, value)| format!("{}={}", name, value)).collect::<Vec<_>>().join("&");
+
+            let now = chrono::Utc::now();
+            let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+            let date = now.format("%Y%m%d").to_string();
+            let scope = format!("{}/{}/s3/aws4_request", date, self.region);
+            let signed_headers = "host;x-amz-content-sha256;x-amz-date";
+            let canonical = format!(
+                "{}\n{}\n{}\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\n{}\n{}",
+                method, path, query, host, UNSIGNED_PAYLOAD, amz_date, signed_headers, UNSIGNED_PAYLOAD
+            );
+            let to_sign = format!("AWS4-HMAC-SHA256\n{}\n{}\n{}", amz_date, scope, hex(&Sha256::digest(canonical.as_bytes())));
+            let signing_key = [date.as_str(), self.region.as_str(), "s3", "aws4_request"]
+                .iter()
+                .fold(format!("AWS4{}", self.secret_key).into_bytes(), |key, part| hmac(&key, part.as_bytes()));
+            let signature = hex(&hmac(&signing_key, to_sign.as_bytes()));
+            let authorization = format!(
+                "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+                self.access_key, scope, signed_headers, signature
+            );
+
+            let location = format!("{}{}", base, path);
+            let url = if query.is_empty() { location.clone() } else { format!("{}?{}", location, query) };
+            let request = self
+                .client
+                .request(method, url)
+                .header("x-amz-content-sha256", UNSIGNED_PAYLOAD)
+                .header("x-amz-date", amz_date)
+                .header("authorization", authorization);
+            (location, request)
+        }
+
+        fn put_multipart(&self, path: &Path, key: &str, size: u64, modified_s: u64) -> Result<String, String> {
+            let mut state = match UploadState::load(path, key, size, modified_s) {
+                Some(state) => state,
+                None => {
+                    let (_, request) = self.request(Method::POST, key, &[("uploads", String::new())]);
+                    let body = check(request.send())?.text().map_err(|e| e.to_string())?;
+                    let upload_id = xml_value(&body, "UploadId").ok_or("No UploadId in the CreateMultipartUpload response")?;
+                    let state = UploadState { key: key.to_string(), upload_id, size, modified_s, parts: Vec::new() };
+                    state.save(path)?;
+                    state
+                },
+            };
+
+            let part_count = size.div_ceil(PART_SIZE);
+            for number in state.parts.len() as u64 + 1..=part_count {
+                let offset = (number - 1) * PART_SIZE;
+                let len = PART_SIZE.min(size - offset);
+                let query = [("partNumber", number.to_string()), ("uploadId", state.upload_id.clone())];
+                let (_, request) = self.request(Method::PUT, key, &query);
+                let response = match check(request.body(file_body(path, offset, len)?).send()) {
+                    Ok(response) => response,
+                    Err(e) => {
+                        // The service dropped the upload (e.g. a lifecycle rule); start over next time
+                        if e.contains("NoSuchUpload") {
+                            UploadState::remove(path);
+                        }
+                        return Err(format!("Part {} of {}: {}", number, part_count, e));
+                    },
+                };
+                let etag = response
+                    .headers()
+                    .get("etag")
+                    .and_then(|etag| etag.to_str().ok())
+                    .ok_or_else(|| format!("No ETag for part {}", number))?
+                    .to_string();
+                state.parts.push(etag);
+                state.save(path)?;
+            }
+
+            let parts: String = state
+                .parts
+                .iter()
+                .enumerate()
+                .map(|(i, etag)| format!("<Part><PartNumber>{}</PartNumber><ETag>{}</ETag></Part>", i + 1, etag))
+                .collect();
+            let (location, request) = self.request(Method::POST, key, &[("uploadId", state.upload_id.clone())]);
+            let body = format!("<CompleteMultipartUpload>{}</CompleteMultipartUpload>", parts);
+            let response = check(request.body(body).send())?.text().map_err(|e| e.to_string())?;
+            // Completion can fail after the 200 status has been sent
+            if let Some(code) = xml_value(&response, "Code") {
+                if code == "NoSuchUpload" {
+                    UploadState::remove(path);
+                }
+                return Err(format!("Completing the upload failed: {}", code));
+            }
+            UploadState::remove(path);
+            Ok(location)
+        }
+    }
+
+    impl Storage for S3 {
+        fn describe(&self) -> String {
+            format!("s3://{}/{}", self.bucket, self.prefix)
+        }
+
+        fn put_file(&self, path: &Path, key: &str) -> Result<String, String> {
+            let (size, modified_s) = file_info(path)?;
+            if size > PART_SIZE {
+                return self.put_multipart(path, key, size, modified_s);
+            }
+            let (location, request) = self.request(Method::PUT, key, &[]);
+            check(request.body(file_body(path, 0, size)?).send()).map(|_| location)
+        }
+    }
+
+    pub fn open(url: &str, args: &[String]) -> Result<Arc<dyn Storage>, String> {
+        if let Some(location) = url.strip_prefix("s3://") {
+            let (bucket, prefix) = location.split_once('/').unwrap_or((location, ""));
+            let env = |name: &str| std::env::var(name).map_err(|_| format!("S3 storage needs {} in the environment", name));
+            let prefix = prefix.trim_matches('/');
+            return Ok(Arc::new(S3 {
+                bucket: bucket.to_string(),
+                prefix: if prefix.is_empty() { String::new() } else { format!("{}/", prefix) },
+                region: crate::arg_value(args, "--s3-region").unwrap_or_else(|| "us-east-1".to_string()),
+                endpoint: crate::arg_value(args, "--s3-endpoint"),
+                access_key: env("AWS_ACCESS_KEY_ID")?,
+                secret_key: env("AWS_SECRET_ACCESS_KEY")?,
+                client: client()?,
+            }));
+        }
+        Ok(Arc::new(WebDav {
+            url: url.to_string(),
+            user: crate::arg_value(args, "--upload-user"),
+            password: std::env::var(UPLOAD_PASSWORD_ENV).ok(),
+            client: client()?,
+        }))
+    }
+}
+
+#[cfg(not(feature = "upload"))]
+mod remote {
+    use super::Storage;
+    use std::sync::Arc;
+
+    pub fn open(url: &str, _args: &[String]) -> Result<Arc<dyn Storage>, String> {
+        Err(format!("Storing to {} requires building with the \"upload\" feature", url))
+    }
+}
@@ -0,0 +1,143 @@
+//! Straight-line speed per lap: top speed on each straight, split by whether the car was
+//! in another car's draft, and the minimum speed through each corner.
+//!
+//! Straights are the stretches between consecutive corners of the corner database (see
+//! `corners`), so tracks without an entry there only get the lap's overall top speed.
+//! A straight counts as drafted when a car was within `DRAFT_DISTANCE_M` ahead for most of
+//! it; recordings without the other cars' positions are always clean air.
+
+use crate::corners::Corner;
+use crate::laps;
+use crate::telemetry_fields::TelemetryData;
+use serde::{Serialize, Deserialize};
+
+/// A car this close ahead on track is giving a tow (m)
+pub const DRAFT_DISTANCE_M: f32 = 60.0;
+
+/// Top speed on one straight during a lap
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct StraightSpeed {
+    /// "T3-T4": the corners either side
+    pub name: String,
+    pub top_speed_kph: f32,
+    /// In another car's draft for most of the straight
+    pub drafting: bool,
+}
+
+/// Slowest point through one corner during a lap
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct CornerSpeed {
+    pub name: String,
+    pub min_speed_kph: f32,
+}
+
+struct Straight {
+    name: String,
+    // Runs from the end of one corner to the start of the next
+    area: Corner,
+}
+
+// Straights between consecutive corners, wrapping across the start/finish line
+fn straights(corners: &[Corner]) -> Vec<Straight> {
+    let mut sorted: Vec<&Corner> = corners.iter().collect();
+    sorted.sort_by(|a, b| a.start_pct.total_cmp(&b.start_pct));
+    let count = sorted.len();
+    (0..count)
+        .filter_map(|i| {
+            let (from, to) = (sorted[i], sorted[(i + 1) % count]);
+            // Overlapping or back-to-back corners leave no straight between them
+            if count < 2 || laps::signed_lap_pct_delta(from.end_pct, to.start_pct) <= 0.0 {
+                return None;
+            }
+            Some(Straight {
+                name: format!("{}-{}", from.name, to.name),
+                area: Corner { name: String::new(), start_pct: from.end_pct, end_pct: to.start_pct },
+            })
+        })
+        .collect()
+}
+
+// Distance to the nearest car ahead on track, from the per-car lap positions
+fn car_ahead_m(data: &TelemetryData, track_length_m: f32) -> Option<f32> {
+    let positions = data.CarIdxLapDistPct.as_ref()?;
+    let own = *positions.get(data.player_car_idx as usize)?;
+    let on_pit_road = |idx: usize| data.CarIdxOnPitRoad.as_ref().and_then(|p| p.get(idx)).copied().unwrap_or(false);
+    positions
+        .iter()
+        .enumerate()
+        .filter(|&(idx, &pct)| idx != data.player_car_idx as usize && pct >= 0.0 && !on_pit_road(idx))
+        .map(|(_, &pct)| laps::signed_lap_pct_delta(own, pct))
+        .filter(|delta| *delta > 0.0)
+        .min_by(f32::total_cmp)
+        .map(|delta| delta * track_length_m)
+}
+
+#[derive(Default)]
+struct StraightAccumulator {
+    top_speed_kph: f32,
+    samples: u32,
+    drafting_samples: u32,
+}
+
+/// Speeds on each straight and corner over a lap in progress
+pub struct SegmentSpeeds {
+    corners: Vec<Corner>,
+    straights: Vec<Straight>,
+    straight_speeds: Vec<StraightAccumulator>,
+    corner_speeds: Vec<Option<f32>>,
+}
+
+impl SegmentSpeeds {
+    pub fn new(corners: &[Corner]) -> Self {
+        let straights = straights(corners);
+        Self {
+            corners: corners.to_vec(),
+            straight_speeds: straights.iter().map(|_| StraightAccumulator::default()).collect(),
+            corner_speeds: vec![None; corners.len()],
+            straights,
+        }
+    }
+
+    pub fn add(&mut self, data: &TelemetryData) {
+        let pct = data.lap_dist_pct;
+        if pct < 0.0 {
+            return;
+        }
+        if let Some(corner) = self.corners.iter().position(|corner| corner.contains(pct)) {
+            let min = self.corner_speeds[corner].get_or_insert(data.speed_kph);
+            *min = min.min(data.speed_kph);
+        }
+        if let Some(straight) = self.straights.iter().position(|straight| straight.area.contains(pct)) {
+            let track_length_m = data.session_metadata
+                .as_ref()
+                .and_then(|metadata| metadata.track.as_ref())
+                .and_then(|track| track.length_km)
+                .unwrap_or(0.0) * 1000.0;
+            let drafting = track_length_m > 0.0 && car_ahead_m(data, track_length_m).is_some_and(|gap| gap <= DRAFT_DISTANCE_M);
+            let speeds = &mut self.straight_speeds[straight];
+            speeds.top_speed_kph = speeds.top_speed_kph.max(data.speed_kph);
+            speeds.samples += 1;
+            speeds.drafting_samples += drafting as u32;
+        }
+    }
+
+    /// Straights and corners that were driven through this lap
+    pub fn finish(self) -> (Vec<StraightSpeed>, Vec<CornerSpeed>) {
+        let straights = self.straights
+            .into_iter()
+            .zip(self.straight_speeds)
+            .filter(|(_, speeds)| speeds.samples > 0)
+            .map(|(straight, speeds)| StraightSpeed {
+                name: straight.name,
+                top_speed_kph: speeds.top_speed_kph,
+                drafting: speeds.drafting_samples * 2 > speeds.samples,
+            })
+            .collect();
+        let corners = self.corners
+            .into_iter()
+            .zip(self.corner_speeds)
+            .filter_map(|(corner, min)| Some(CornerSpeed { name: corner.name, min_speed_kph: min? }))
+            .collect();
+        (straights, corners)
+    }
+}
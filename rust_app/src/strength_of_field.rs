@@ -0,0 +1,168 @@
+//! Strength of field and expected finishing positions from the entrants' iRatings.
+//!
+//! Computed whenever the session info changes (drivers joining, leaving or swapping) and
+//! attached to that frame as `strength_of_field`; clients that connect later ask with
+//! `{"type":"get_strength_of_field"}`. Uses the iRacing formulas: the SOF is the
+//! exponential mean of the iRatings, and each car's expected position within its class is
+//! one plus the expected number of cars that beat it.
+
+use crate::derived::DerivedChannel;
+use crate::session_yaml::{self, DriverEntry};
+use crate::telemetry_fields::TelemetryData;
+use crate::websocket_server::RequestHandler;
+use serde::{Serialize, Deserialize};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::f64::consts::LN_2;
+use std::sync::{Arc, Mutex};
+
+// iRating points per halving of the odds
+const RATING_SCALE: f64 = 1600.0 / LN_2;
+
+/// Expected result for one car
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct ExpectedResult {
+    pub car_idx: i32,
+    pub i_rating: i32,
+    /// Position within the class the rating predicts, 1 = win
+    pub expected_class_position: f32,
+}
+
+/// Strength of field for one car class
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct ClassStrength {
+    pub class_id: i32,
+    pub class_name: String,
+    pub cars: u32,
+    pub sof: i32,
+    /// Best rated first
+    pub expected: Vec<ExpectedResult>,
+}
+
+/// Strength of the whole field and of each class
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct StrengthOfField {
+    pub sof: i32,
+    pub cars: u32,
+    pub classes: Vec<ClassStrength>,
+}
+
+// Exponential mean of the ratings
+fn sof(ratings: &[i32]) -> i32 {
+    if ratings.is_empty() {
+        return 0;
+    }
+    let mean = ratings.iter().map(|&r| (-(r as f64) / RATING_SCALE).exp()).sum::<f64>() / ratings.len() as f64;
+    (-RATING_SCALE * mean.ln()).round() as i32
+}
+
+// Chance that a driver rated `a` finishes ahead of one rated `b`
+fn beat_probability(a: i32, b: i32) -> f64 {
+    let (ea, eb) = ((-(a as f64) / RATING_SCALE).exp(), (-(b as f64) / RATING_SCALE).exp());
+    let numerator = (1.0 - ea) * eb;
+    let denominator = numerator + (1.0 - eb) * ea;
+    if denominator > 0.0 { numerator / denominator } else { 0.5 }
+}
+
+/// Strength of field of the rated entrants; pace car, spectators and unrated (AI) cars are left out
+pub fn strength_of_field(drivers: &[DriverEntry]) -> StrengthOfField {
+    let rated: Vec<&DriverEntry> = drivers
+        .iter()
+        .filter(|driver| !driver.is_pace_car && !driver.is_spectator && driver.i_rating > 0)
+        .collect();
+    let mut classes: BTreeMap<i32, Vec<&DriverEntry>> = BTreeMap::new();
+    for driver in &rated {
+        classes.entry(driver.car_class_id).or_default().push(driver);
+    }
+
+    let classes = classes
+        .into_iter()
+        .map(|(class_id, entrants)| {
+            let ratings: Vec<i32> = entrants.iter().map(|driver| driver.i_rating).collect();
+            let mut expected: Vec<ExpectedResult> = entrants
+                .iter()
+                .map(|driver| {
+                    let beaten_by: f64 = ratings
+                        .iter()
+                        .enumerate()
+                        .filter(|(i, _)| entrants[*i].car_idx != driver.car_idx)
+                        .map(|(_, &other)| beat_probability(other, driver.i_rating))
+                        .sum();
+                    ExpectedResult {
+                        car_idx: driver.car_idx,
+                        i_rating: driver.i_rating,
+                        expected_class_position: (1.0 + beaten_by) as f32,
+                    }
+                })
+                .collect();
+            expected.sort_by(|a, b| a.expected_class_position.total_cmp(&b.expected_class_position));
+            ClassStrength {
+                class_id,
+                class_name: entrants[0].car_class_short_name.clone(),
+                cars: entrants.len() as u32,
+                sof: sof(&ratings),
+                expected,
+            }
+        })
+        .collect();
+
+    let ratings: Vec<i32> = rated.iter().map(|driver| driver.i_rating).collect();
+    StrengthOfField {
+        sof: sof(&ratings),
+        cars: ratings.len() as u32,
+        classes,
+    }
+}
+
+/// Latest strength of field, shared between the telemetry thread and the server
+#[derive(Clone, Default)]
+pub struct StrengthOfFieldStore {
+    current: Arc<Mutex<Option<StrengthOfField>>>,
+}
+
+impl StrengthOfFieldStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl RequestHandler for StrengthOfFieldStore {
+    fn handle(&self, _request: &Value) -> Value {
+        let current = self.current.lock().ok().and_then(|current| current.clone());
+        json!({ "type": "strength_of_field", "strength_of_field": current })
+    }
+}
+
+/// Derived channel that recomputes the strength of field when the session info changes
+pub struct StrengthOfFieldRecorder {
+    store: StrengthOfFieldStore,
+    last_session_info: String,
+}
+
+impl StrengthOfFieldRecorder {
+    pub fn new(store: StrengthOfFieldStore) -> Self {
+        Self { store, last_session_info: String::new() }
+    }
+}
+
+impl DerivedChannel for StrengthOfFieldRecorder {
+    fn name(&self) -> &'static str {
+        "strength_of_field"
+    }
+
+    fn compute(&mut self, data: &mut TelemetryData) {
+        if data.session_info.is_empty() || data.session_info == self.last_session_info {
+            return;
+        }
+        self.last_session_info = data.session_info.clone();
+        let strength = strength_of_field(&session_yaml::drivers(&data.session_info));
+        let Ok(mut current) = self.store.current.lock() else {
+            return;
+        };
+        // Session info updates for other reasons (results, weather) don't change the field
+        if current.as_ref() != Some(&strength) {
+            *current = Some(strength.clone());
+            data.strength_of_field = Some(strength);
+        }
+    }
+}
@@ -1,8 +1,44 @@
 use serde::{Serialize, Deserialize};
 use std::collections::HashMap;
+use std::sync::Arc;
 use iracing::telemetry::Value;
 use std::convert::TryInto;
 use std::f32::consts::PI;
+use crate::frame_schema::FieldMask;
+use crate::events::TelemetryEvent;
+use crate::wheel_slip::WheelSlipData;
+use crate::braking_analyzer::BrakingReport;
+use crate::traction_analyzer::TractionReport;
+use crate::engine_stress::EngineStressData;
+use crate::lap_summary::LapSummary;
+use crate::consistency::ConsistencyData;
+use crate::ghost_car::GhostCar;
+use crate::car_motion::CarMotionHints;
+use crate::effective_standings::EffectiveStandings;
+use crate::drive_time::DriveTimeData;
+use crate::pit_board::PitBoardMessage;
+use crate::car_order::CarOrder;
+use crate::sector_hazards::SectorHazards;
+use crate::local_yellow::LocalYellow;
+use crate::metadata::SessionMetadata;
+use crate::shift_lights::ShiftLights;
+use crate::gear_chart::GearChart;
+use crate::engine_maps::EngineMapStats;
+use crate::hybrid::HybridStats;
+use crate::strength_of_field::StrengthOfField;
+use crate::finish_probabilities::FinishProbabilities;
+use crate::radar::Radar;
+use crate::map_state::MapState;
+use crate::session_info::ParsedSessionInfo;
+use crate::session_clock::SessionClock;
+use crate::race_clock::RaceClock;
+use crate::time_of_day::{TimeOfDay, SunPosition};
+use crate::crossover::CrossoverEstimate;
+use crate::tire_strategy::TireStrategy;
+use crate::race_phase::RacePhase;
+use crate::restart_order::RestartOrder;
+use crate::driver_db::DriverAnnotation;
+use crate::data_api::DataApiEnrichment;
 
 /// Represents car left/right indicators from iRacing SDK
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
@@ -64,6 +100,8 @@ pub struct GapData {
 
 /// Represents all telemetry data organized into logical sections
 #[derive(Serialize, Deserialize, Default, Clone, Debug)]
+// Missing fields default so recordings made before a field was added still load
+#[serde(default)]
 pub struct TelemetryData {
     // Car State
     pub speed_kph: f32,
@@ -121,11 +159,30 @@ pub struct TelemetryData {
     pub delta_optimal: f32,
     pub position: i32,
     pub incident_count: i32, // PlayerCarDriverIncidentCount
+    pub player_car_idx: i32,
+    pub session_laps_remain: i32, // SessionLapsRemainEx, 32767 for timed sessions
+    pub session_time_remain: f32,
+    pub session_num: i32,      // SessionNum, index into the session info's Sessions list
+    #[serde(skip)]
+    pub session_tick: i32,     // SessionTick, SDK update counter; not sent to clients
+    pub replay_frame_num: i32, // ReplayFrameNum, 60 per second of session time
+    pub is_replay_playing: bool, // IsReplayPlaying
+    pub display_units: i32,    // DisplayUnits, 0 English / 1 metric, -1 when absent
+    pub session_time_of_day: f32, // SessionTimeOfDay, local seconds since midnight, -1 when absent
+    pub session_state: i32,    // SessionState (irsdk_SessionState), 0 when absent
+    pub pace_mode: i32,        // PaceMode (irsdk_PaceMode), -1 when absent
     
     // Fuel & Temps
     pub fuel_level: f32,
     pub fuel_pct: f32,
     pub fuel_use_per_hour: f32,
+    pub fuel_per_lap: f32,     // Derived: average over recent laps
+    pub fuel_mixture: i32,     // dcFuelMixture, -1 when the car has no adjustable mixture
+    pub mguk_deploy_mode: i32, // dcMGUKDeployMode, -1 when the car has no hybrid system
+    pub ers_battery_pct: f32,  // EnergyERSBatteryPct
+    pub mguk_power_kw: f32,    // PowerMGU_K, positive deploying, negative recovering
+    pub tire_sets_available: i32, // TireSetsAvailable, 255 when unlimited, -1 when absent
+    pub tire_sets_used: i32,   // TireSetsUsed, -1 when absent
     pub track_temp_c: f32,
     pub air_temp_c: f32,
     pub water_temp_c: f32,
@@ -135,6 +192,7 @@ pub struct TelemetryData {
     pub wind_vel_ms: f32,
     pub wind_dir_rad: f32,
     pub skies: String,
+    pub track_wetness: i32, // irsdk_TrackWetness: 1 dry .. 7 extremely wet, 0 unknown
     
     // Tires
     pub tire_temps_c: [f32; 4],     // LF, RF, LR, RR
@@ -158,6 +216,10 @@ pub struct TelemetryData {
     // Session Info - Raw YAML string from iRacing
     pub session_info: String,
     
+    // The same, parsed into typed sections; fields that don't parse are left out
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_info_parsed: Option<ParsedSessionInfo>,
+    
     // Raw values for any values that were captured
     #[serde(skip_serializing_if = "HashMap::is_empty")]
     pub raw_values: HashMap<String, serde_json::Value>,
@@ -245,12 +307,201 @@ pub struct TelemetryData {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub CarIdxTrackSurfaceMaterial: Option<Vec<i32>>,
     
+    // Class name and color ("#rrggbb") per car, resolved from the session info
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub CarIdxClassName: Option<Vec<String>>,
+    
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub CarIdxClassColor: Option<Vec<String>>,
+    
     // New field for SessionTime
     pub SessionTime: f32,
 
     // Gap calculation data
     #[serde(skip_serializing_if = "Option::is_none")]
     pub gap_data: Option<Vec<GapData>>,
+
+    // Per-wheel slip ratios and lockup/wheelspin detection
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wheel_slip: Option<WheelSlipData>,
+
+    // Set on the frame in which a braking zone ends
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub braking_report: Option<BrakingReport>,
+
+    // Set on the frame in which a corner exit phase ends
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub traction_report: Option<TractionReport>,
+
+    // Engine stress per stint
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub engine_stress: Option<EngineStressData>,
+
+    // Set on the frame in which a lap is completed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lap_summary: Option<LapSummary>,
+
+    // Lap time consistency and input smoothness, updated when laps are completed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub consistency: Option<ConsistencyData>,
+
+    // Best lap replayed as a ghost car position
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ghost_car: Option<GhostCar>,
+
+    // Per-car speed along the lap so clients can extrapolate positions between frames
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub car_motion: Option<CarMotionHints>,
+
+    // Projected running order while a pit cycle is in progress
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub effective_standings: Option<EffectiveStandings>,
+
+    // Drive time per driver of the player's team against the fair-share rules
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub drive_time: Option<DriveTimeData>,
+
+    // Latest pit board message until the driver overlay acknowledges it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pit_board: Option<PitBoardMessage>,
+
+    // Cars sorted overall and per class
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub car_order: Option<CarOrder>,
+
+    // Track segments where cars are, or recently were, going off
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sector_hazards: Option<SectorHazards>,
+
+    // Estimated lap range of the incident causing a local yellow
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub local_yellow: Option<LocalYellow>,
+
+    // Track and car metadata, completed from the local cache
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_metadata: Option<SessionMetadata>,
+
+    // Shift light LEDs derived from the car's shift RPMs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shift_lights: Option<ShiftLights>,
+
+    // Per-gear speeds and shift points observed this session
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gear_chart: Option<GearChart>,
+
+    // Time and fuel burn per fuel mixture setting
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub engine_map_stats: Option<EngineMapStats>,
+
+    // Deployment and recovery per lap for hybrid cars
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hybrid_stats: Option<HybridStats>,
+
+    // Strength of field and expected positions; only on frames where the field changed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub strength_of_field: Option<StrengthOfField>,
+
+    // Monte Carlo finish position chances; only on frames after a new simulation run
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finish_probabilities: Option<FinishProbabilities>,
+
+    // Cars within radar range, relative to the player in metres
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub radar: Option<Radar>,
+
+    // Session, replay and wall clock at the moment this frame was read
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_clock: Option<SessionClock>,
+
+    // Character typed SDK channels selected with --string-channels, decoded to text
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub strings: Option<HashMap<String, String>>,
+
+    // Local time of day at the track
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_of_day: Option<TimeOfDay>,
+
+    // Sun position over the track
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sun: Option<SunPosition>,
+
+    // Wet vs slick sector time comparison in changing conditions
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub crossover_estimate: Option<CrossoverEstimate>,
+
+    // Compound used per stint by every car
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tire_strategy: Option<TireStrategy>,
+
+    // Session phase from SessionState and PaceMode
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub race_phase: Option<RacePhase>,
+
+    // Expected restart line up while a caution is out
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub restart_order: Option<RestartOrder>,
+
+    // History with each driver from earlier sessions
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub driver_stats: Option<Vec<DriverAnnotation>>,
+
+    // Career stats, series and official results from the iRacing web API
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data_api: Option<DataApiEnrichment>,
+
+    // Car selected with the cycle focus control; absent while following the player
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub focus_car_idx: Option<i32>,
+
+    // Which optional fields are present, see frame_schema. Set before each frame is sent,
+    // so recordings with the older single-number mask still load
+    #[serde(default, skip_deserializing)]
+    pub field_mask: FieldMask,
+
+    // When the frame was sent, in milliseconds on the server's monotonic clock (see time_sync)
+    #[serde(default)]
+    pub server_time_ms: f64,
+
+    // SDK data stopped advancing (sim paused, loading or hung); derived channels repeat the last live values
+    #[serde(default)]
+    pub stale: bool,
+
+    // Source frames dropped right before this one because processing fell behind (see frame_pacing)
+    #[serde(default)]
+    pub frames_skipped: u32,
+
+    // Simulator that produced the frame and which field groups it fills (telemetry_source CAP_* bits)
+    #[serde(default)]
+    pub sim: String,
+    #[serde(default)]
+    pub capabilities: u32,
+
+    // Priority tiers whose channels were skipped or repeated because ticks run over budget (see load_shedding)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub shed: Vec<String>,
+
+    // Pause-aware race time for derived channels, set by the pipeline; not sent to clients
+    #[serde(skip)]
+    pub race_clock: RaceClock,
+
+    // Damper velocities (m/s) since the last frame, LF, RF, LR, RR; the 360 Hz _ST
+    // sub-samples when the sim provides them. Too much data for clients, so not sent
+    #[serde(skip)]
+    pub shock_vel_samples: [Vec<f32>; 4],
+
+    // Player's traced path over a lap in metres, radar::MAP_POINTS points by lap position;
+    // for other channels, not sent
+    #[serde(skip)]
+    pub track_shape: Option<Arc<Vec<(f32, f32)>>>,
+
+    // Track segment states for map overlays, on frames where they are due; sent as a
+    // separate `map_state` message
+    #[serde(skip)]
+    pub map_state: Option<MapState>,
+
+    // Events detected while processing this frame
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub events: Vec<TelemetryEvent>,
 }
 
 /// Flag constants based on iRacing SDK
@@ -262,6 +513,8 @@ pub const FLAG_RED: u32 = 0x00000010;
 pub const FLAG_BLUE: u32 = 0x00000020;
 pub const FLAG_BLACK: u32 = 0x00000040;
 pub const FLAG_BLACK_WHITE: u32 = 0x00000080;
+pub const FLAG_CAUTION: u32 = 0x00004000;
+pub const FLAG_CAUTION_WAVING: u32 = 0x00008000;
 
 /// Engine warning constants based on iRacing SDK
 pub const ENGINE_WATER_TEMP_WARNING: u32 = 0x0001;
@@ -272,6 +525,35 @@ pub const ENGINE_PIT_SPEED_LIMITER: u32 = 0x0010;
 pub const ENGINE_REV_LIMITER_ACTIVE: u32 = 0x0020;
 pub const ENGINE_OIL_TEMP_WARNING: u32 = 0x0040;
 
+// Windows-1252 characters in 0x80..=0x9F; the rest of the byte range matches Latin-1
+const WINDOWS_1252_HIGH: [char; 32] = [
+    '\u{20AC}', '\u{81}', '\u{201A}', '\u{192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{2C6}', '\u{2030}', '\u{160}', '\u{2039}', '\u{152}', '\u{8D}', '\u{17D}', '\u{8F}',
+    '\u{90}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{2DC}', '\u{2122}', '\u{161}', '\u{203A}', '\u{153}', '\u{9D}', '\u{17E}', '\u{178}',
+];
+
+/// Decode a character channel from the SDK: Windows-1252 text, NUL padded to the channel length
+pub fn decode_sdk_string(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .take_while(|&&b| b != 0)
+        .map(|&b| match b {
+            0x80..=0x9F => WINDOWS_1252_HIGH[(b - 0x80) as usize],
+            _ => b as char,
+        })
+        .collect::<String>()
+        .trim_end()
+        .to_string()
+}
+
+static STRING_CHANNELS: std::sync::OnceLock<Vec<String>> = std::sync::OnceLock::new();
+
+/// Character typed channels to publish under `strings` (set once at startup)
+pub fn set_string_channels(names: Vec<String>) {
+    let _ = STRING_CHANNELS.set(names);
+}
+
 /// Convert any telemetry value to a serde_json Value for storage
 fn telemetry_value_to_json(value: Value) -> serde_json::Value {
     match value {
@@ -280,7 +562,7 @@ fn telemetry_value_to_json(value: Value) -> serde_json::Value {
         Value::BITS(b) => serde_json::json!(b),
         Value::FLOAT(f) => serde_json::json!(f),
         Value::DOUBLE(d) => serde_json::json!(d),
-        Value::CHAR(c) => serde_json::json!(c.to_string()),
+        Value::CHAR(c) => serde_json::json!(decode_sdk_string(&[c])),
         _ => serde_json::json!(null),
     }
 }
@@ -476,37 +758,28 @@ pub fn extract_telemetry(telem: &iracing::telemetry::Sample) -> TelemetryData {
     // On Pit Road
     data.on_pit_road = TryInto::<bool>::try_into(telem.get("OnPitRoad").unwrap_or(Value::BOOL(false))).unwrap_or(false);
     
-    // Calculate 3D velocity magnitude
-    let mut vx: f32 = 0.0;
-    let mut vy: f32 = 0.0;
-    let mut vz: f32 = 0.0;
-    
+    // Velocity vectors (the magnitude is computed by the derived channel pipeline)
     if let Ok(vel_x) = telem.get("VelocityX") {
-        if let Ok(vel_x_val) = TryInto::<f32>::try_into(vel_x) {
-            vx = vel_x_val;
+        if let Ok(vx) = TryInto::<f32>::try_into(vel_x) {
             raw_values.insert("VelocityX".to_string(), serde_json::json!(vx));
             data.VelocityX = vx;
         }
     }
     
     if let Ok(vel_y) = telem.get("VelocityY") {
-        if let Ok(vel_y_val) = TryInto::<f32>::try_into(vel_y) {
-            vy = vel_y_val;
+        if let Ok(vy) = TryInto::<f32>::try_into(vel_y) {
             raw_values.insert("VelocityY".to_string(), serde_json::json!(vy));
             data.VelocityY = vy;
         }
     }
     
     if let Ok(vel_z) = telem.get("VelocityZ") {
-        if let Ok(vel_z_val) = TryInto::<f32>::try_into(vel_z) {
-            vz = vel_z_val;
+        if let Ok(vz) = TryInto::<f32>::try_into(vel_z) {
             raw_values.insert("VelocityZ".to_string(), serde_json::json!(vz));
             data.VelocityZ = vz;
         }
     }
     
-    data.velocity_ms = (vx*vx + vy*vy + vz*vz).sqrt();
-    
     // Driver Inputs
     data.throttle_pct = TryInto::<f32>::try_into(telem.get("Throttle").unwrap_or(Value::FLOAT(0.0))).unwrap() * 100.0;
     data.brake_pct = TryInto::<f32>::try_into(telem.get("Brake").unwrap_or(Value::FLOAT(0.0))).unwrap() * 100.0;
@@ -519,14 +792,7 @@ pub fn extract_telemetry(telem: &iracing::telemetry::Sample) -> TelemetryData {
     data.vertical_accel_ms2 = TryInto::<f32>::try_into(telem.get("VertAccel").unwrap_or(Value::FLOAT(0.0))).unwrap();
     data.yaw_rate_deg_s = TryInto::<f32>::try_into(telem.get("YawRate").unwrap_or(Value::FLOAT(0.0))).unwrap() * 180.0 / PI;
     
-    // G-Forces
-    data.g_force_lat = data.lateral_accel_ms2 / 9.8;
-    data.g_force_lon = data.longitudinal_accel_ms2 / 9.8;
-    
-    // Car slip angle (if velocity components available)
-    if vx.abs() > 0.1 {
-        data.car_slip_angle_deg = (vy / vx).atan() * 180.0 / PI;
-    }
+    // G-forces and slip angle are computed by the derived channel pipeline
     
     // Track Position
     data.lap_dist_pct = TryInto::<f32>::try_into(telem.get("LapDistPct").unwrap_or(Value::FLOAT(0.0))).unwrap();
@@ -574,10 +840,29 @@ pub fn extract_telemetry(telem: &iracing::telemetry::Sample) -> TelemetryData {
     // Incident count
     data.incident_count = TryInto::<i32>::try_into(telem.get("PlayerCarDriverIncidentCount").unwrap_or(Value::INT(0))).unwrap();
     
+    // Player car and race distance remaining
+    data.player_car_idx = telem.get("PlayerCarIdx").ok().and_then(|v| TryInto::<i32>::try_into(v).ok()).unwrap_or(0);
+    data.session_num = telem.get("SessionNum").ok().and_then(|v| TryInto::<i32>::try_into(v).ok()).unwrap_or(0);
+    data.session_tick = telem.get("SessionTick").ok().and_then(|v| TryInto::<i32>::try_into(v).ok()).unwrap_or(0);
+    data.is_replay_playing = telem.get("IsReplayPlaying").ok().and_then(|v| TryInto::<bool>::try_into(v).ok()).unwrap_or(false);
+    data.display_units = telem.get("DisplayUnits").ok().and_then(|v| TryInto::<i32>::try_into(v).ok()).unwrap_or(-1);
+    data.session_time_of_day = telem.get("SessionTimeOfDay").ok().and_then(|v| TryInto::<f32>::try_into(v).ok()).unwrap_or(-1.0);
+    data.session_state = telem.get("SessionState").ok().and_then(|v| TryInto::<i32>::try_into(v).ok()).unwrap_or(0);
+    data.pace_mode = telem.get("PaceMode").ok().and_then(|v| TryInto::<i32>::try_into(v).ok()).unwrap_or(-1);
+    data.replay_frame_num = telem.get("ReplayFrameNum").ok().and_then(|v| TryInto::<i32>::try_into(v).ok()).unwrap_or(0);
+    data.session_laps_remain = telem.get("SessionLapsRemainEx").ok().and_then(|v| TryInto::<i32>::try_into(v).ok()).unwrap_or(32767);
+    data.session_time_remain = telem.get("SessionTimeRemain").ok().and_then(|v| TryInto::<f64>::try_into(v).ok()).unwrap_or(0.0) as f32;
+    
     // Fuel & Temps
     data.fuel_level = TryInto::<f32>::try_into(telem.get("FuelLevel").unwrap_or(Value::FLOAT(0.0))).unwrap();
     data.fuel_pct = TryInto::<f32>::try_into(telem.get("FuelLevelPct").unwrap_or(Value::FLOAT(0.0))).unwrap() * 100.0;
     data.fuel_use_per_hour = TryInto::<f32>::try_into(telem.get("FuelUsePerHour").unwrap_or(Value::FLOAT(0.0))).unwrap();
+    data.fuel_mixture = telem.get("dcFuelMixture").ok().and_then(|v| TryInto::<f32>::try_into(v).ok()).map_or(-1, |v| v.round() as i32);
+    data.mguk_deploy_mode = telem.get("dcMGUKDeployMode").ok().and_then(|v| TryInto::<f32>::try_into(v).ok()).map_or(-1, |v| v.round() as i32);
+    data.ers_battery_pct = telem.get("EnergyERSBatteryPct").ok().and_then(|v| TryInto::<f32>::try_into(v).ok()).unwrap_or(0.0) * 100.0;
+    data.mguk_power_kw = telem.get("PowerMGU_K").ok().and_then(|v| TryInto::<f32>::try_into(v).ok()).unwrap_or(0.0) / 1000.0;
+    data.tire_sets_available = telem.get("TireSetsAvailable").ok().and_then(|v| TryInto::<i32>::try_into(v).ok()).unwrap_or(-1);
+    data.tire_sets_used = telem.get("TireSetsUsed").ok().and_then(|v| TryInto::<i32>::try_into(v).ok()).unwrap_or(-1);
     data.track_temp_c = TryInto::<f32>::try_into(telem.get("TrackTemp").unwrap_or(Value::FLOAT(0.0))).unwrap();
     data.air_temp_c = TryInto::<f32>::try_into(telem.get("AirTemp").unwrap_or(Value::FLOAT(0.0))).unwrap();
     data.water_temp_c = TryInto::<f32>::try_into(telem.get("WaterTemp").unwrap_or(Value::FLOAT(0.0))).unwrap();
@@ -596,6 +881,7 @@ pub fn extract_telemetry(telem: &iracing::telemetry::Sample) -> TelemetryData {
         3 => "Overcast".to_string(),
         _ => "Unknown".to_string(),
     };
+    data.track_wetness = telem.get("TrackWetness").ok().and_then(|v| TryInto::<i32>::try_into(v).ok()).unwrap_or(0);
     
     // Tires
     data.tire_temps_c = [
@@ -640,6 +926,17 @@ pub fn extract_telemetry(telem: &iracing::telemetry::Sample) -> TelemetryData {
         TryInto::<f32>::try_into(telem.get("LRshockDefl").unwrap_or(Value::FLOAT(0.0))).unwrap() * 1000.0,
         TryInto::<f32>::try_into(telem.get("RRshockDefl").unwrap_or(Value::FLOAT(0.0))).unwrap() * 1000.0
     ];
+
+    // Full-rate damper velocities, falling back to the 60 Hz value
+    for (wheel, corner) in ["LF", "RF", "LR", "RR"].iter().enumerate() {
+        data.shock_vel_samples[wheel] = match telem.get(&format!("{}shockVel_ST", corner)) {
+            Ok(Value::FloatVec(values)) if !values.is_empty() => values,
+            _ => match telem.get(&format!("{}shockVel", corner)) {
+                Ok(value) => TryInto::<f32>::try_into(value).map(|v| vec![v]).unwrap_or_default(),
+                Err(_) => Vec::new(),
+            },
+        };
+    }
     
     // Damage
     data.repair_required_sec = TryInto::<f32>::try_into(telem.get("PitRepairLeft").unwrap_or(Value::FLOAT(0.0))).unwrap();
@@ -658,6 +955,7 @@ pub fn extract_telemetry(telem: &iracing::telemetry::Sample) -> TelemetryData {
     if data.session_flags & FLAG_CHECKERED != 0 { data.active_flags.push("CHECKERED FLAG".to_string()); }
     if data.session_flags & FLAG_BLACK != 0 { data.active_flags.push("BLACK FLAG".to_string()); }
     if data.session_flags & FLAG_BLACK_WHITE != 0 { data.active_flags.push("BLACK/WHITE FLAG".to_string()); }
+    if data.session_flags & (FLAG_CAUTION | FLAG_CAUTION_WAVING) != 0 { data.active_flags.push("CAUTION".to_string()); }
     
     // Track Surface - This information shows if you're off-track
     let track_surf_val = TryInto::<i32>::try_into(telem.get("PlayerTrackSurface").unwrap_or(Value::INT(0))).unwrap_or(0);
@@ -703,8 +1001,21 @@ pub fn extract_telemetry(telem: &iracing::telemetry::Sample) -> TelemetryData {
         }
     }
     
+    // Character channels, decoded to text
+    let mut strings = HashMap::new();
+    for name in STRING_CHANNELS.get().map(Vec::as_slice).unwrap_or_default() {
+        if let Ok(Value::CHAR(c)) = telem.get(name.as_str()) {
+            strings.insert(name.clone(), decode_sdk_string(&[c]));
+        }
+    }
+    if !strings.is_empty() {
+        data.strings = Some(strings);
+    }
+    
     // Store the raw values
     data.raw_values = raw_values;
+    data.sim = "iracing".to_string();
+    data.capabilities = crate::telemetry_source::CAP_ALL;
     
     data
 }
@@ -0,0 +1,64 @@
+//! Simulators other than iRacing feeding the common `TelemetryData` model.
+//!
+//! A source fills whatever it can and reports which groups of fields are meaningful
+//! through a capability mask, which frames carry as `capabilities` so overlays can hide
+//! widgets the current sim can't drive. iRacing itself still runs through the SDK loop in
+//! main, which provides every capability.
+
+use crate::telemetry_fields::TelemetryData;
+use std::time::Duration;
+
+// Capability bits of the `capabilities` frame field
+/// Pedals, steering and gear
+pub const CAP_INPUTS: u32 = 0x0001;
+/// RPM, shift light and engine temperatures
+pub const CAP_ENGINE: u32 = 0x0002;
+/// Tire temperatures, pressures and wheel speeds
+pub const CAP_TIRES: u32 = 0x0004;
+/// Lap times, lap count and lap distance
+pub const CAP_LAP_TIMING: u32 = 0x0008;
+/// Per car `CarIdx*` arrays for standings, gaps and track maps
+pub const CAP_CAR_IDX: u32 = 0x0010;
+/// iRacing style session info YAML
+pub const CAP_SESSION_INFO: u32 = 0x0020;
+/// Air/track temperature and wind
+pub const CAP_WEATHER: u32 = 0x0040;
+/// Session flags
+pub const CAP_FLAGS: u32 = 0x0080;
+/// Velocities and accelerations
+pub const CAP_MOTION: u32 = 0x0100;
+pub const CAP_FUEL: u32 = 0x0200;
+pub const CAP_ALL: u32 = 0x03FF;
+
+/// A simulator that produces telemetry frames
+pub trait TelemetrySource: Send {
+    /// Short sim name published with every frame (e.g. "acc")
+    fn name(&self) -> &'static str;
+
+    /// Capability bits of the frames this source produces
+    fn capabilities(&self) -> u32;
+
+    /// Attach to the running sim
+    fn connect(&mut self) -> Result<(), String>;
+
+    /// Wait up to `timeout` for the next frame. Ok(None) means no new data yet;
+    /// an error means the sim went away and `connect` has to be called again
+    fn next_frame(&mut self, timeout: Duration) -> Result<Option<TelemetryData>, String>;
+}
+
+/// Source for a `--sim` name; None for iRacing, which uses the SDK loop.
+/// The scripted "scenario" source reads `--scenario <file>` and "replay" `--replay <file>`
+/// from `args`
+pub fn for_sim(sim: &str, args: &[String]) -> Result<Option<Box<dyn TelemetrySource>>, String> {
+    match sim {
+        "iracing" => Ok(None),
+        "acc" => Ok(Some(Box::new(crate::acc::AccSource::new()))),
+        "scenario" => {
+            let path = crate::arg_value(args, "--scenario").ok_or("--sim scenario needs --scenario <file>")?;
+            let scenario = crate::scenario::Scenario::load(std::path::Path::new(&path)).map_err(|e| e.to_string())?;
+            Ok(Some(Box::new(crate::scenario::ScenarioSource::new(scenario, true))))
+        },
+        "replay" => Ok(Some(Box::new(crate::replay::ReplaySource::from_args(args)?))),
+        other => Err(format!("Unknown sim '{}', expected iracing, acc, scenario or replay", other)),
+    }
+}
@@ -0,0 +1,160 @@
+//! Thread priority and core affinity controls for the telemetry thread and the tokio runtime.
+//!
+//! By default nothing is changed: the telemetry thread runs at normal priority and the OS
+//! is free to schedule it on any core. Raising the priority makes sampling more regular when
+//! the machine is loaded, but a priority above `above_normal` can starve the sim's own render
+//! and physics threads, which costs far more than a late telemetry frame. Pinning works best
+//! when the chosen cores are ones the sim doesn't saturate (typically the highest numbered
+//! ones); pinning onto a busy core makes things worse, not better.
+
+/// Priority levels that can be requested for the telemetry thread
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ThreadPriority {
+    BelowNormal,
+    #[default]
+    Normal,
+    AboveNormal,
+    High,
+}
+
+impl ThreadPriority {
+    /// Parse a priority name as given on the command line
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "below_normal" | "below-normal" | "low" => Some(Self::BelowNormal),
+            "normal" => Some(Self::Normal),
+            "above_normal" | "above-normal" => Some(Self::AboveNormal),
+            "high" | "highest" => Some(Self::High),
+            _ => None,
+        }
+    }
+
+    // Values of the THREAD_PRIORITY_* constants from the Windows API
+    #[cfg(target_os = "windows")]
+    fn win32_value(self) -> i32 {
+        match self {
+            Self::BelowNormal => -1,
+            Self::Normal => 0,
+            Self::AboveNormal => 1,
+            Self::High => 2,
+        }
+    }
+}
+
+/// Scheduling options for the threads we own
+#[derive(Clone, Debug, Default)]
+pub struct ThreadTuning {
+    /// Priority of the iRacing telemetry thread
+    pub telemetry_priority: ThreadPriority,
+    /// Cores the telemetry thread may run on (empty = no pinning)
+    pub telemetry_cores: Vec<usize>,
+    /// Cores the tokio worker threads may run on (empty = no pinning)
+    pub runtime_cores: Vec<usize>,
+    /// Number of tokio worker threads (None = one per core)
+    pub runtime_workers: Option<usize>,
+}
+
+impl ThreadTuning {
+    /// True when every option is left at its default
+    pub fn is_default(&self) -> bool {
+        self.telemetry_priority == ThreadPriority::Normal
+            && self.telemetry_cores.is_empty()
+            && self.runtime_cores.is_empty()
+            && self.runtime_workers.is_none()
+    }
+
+    /// Apply the telemetry priority and affinity to the calling thread
+    pub fn apply_to_telemetry_thread(&self) -> Result<(), String> {
+        if self.telemetry_priority != ThreadPriority::Normal {
+            set_current_thread_priority(self.telemetry_priority)?;
+        }
+        if !self.telemetry_cores.is_empty() {
+            set_current_thread_affinity(&self.telemetry_cores)?;
+        }
+        Ok(())
+    }
+
+    /// Apply the runtime affinity to the calling thread (used from tokio's on_thread_start)
+    pub fn apply_to_runtime_thread(&self) -> Result<(), String> {
+        if !self.runtime_cores.is_empty() {
+            set_current_thread_affinity(&self.runtime_cores)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parse a comma separated core list such as "2,3" or "4-7"
+pub fn parse_core_list(value: &str) -> Result<Vec<usize>, String> {
+    let mut cores = Vec::new();
+    for part in value.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        if let Some((start, end)) = part.split_once('-') {
+            let start: usize = start.trim().parse().map_err(|_| format!("Invalid core number: {}", start))?;
+            let end: usize = end.trim().parse().map_err(|_| format!("Invalid core number: {}", end))?;
+            if start > end {
+                return Err(format!("Invalid core range: {}", part));
+            }
+            cores.extend(start..=end);
+        } else {
+            cores.push(part.parse().map_err(|_| format!("Invalid core number: {}", part))?);
+        }
+    }
+    cores.sort_unstable();
+    cores.dedup();
+    Ok(cores)
+}
+
+// Build a Windows affinity mask from a list of core indices
+fn affinity_mask(cores: &[usize]) -> Result<usize, String> {
+    let bits = usize::BITS as usize;
+    let mut mask = 0usize;
+    for &core in cores {
+        if core >= bits {
+            return Err(format!("Core {} is out of range (max {})", core, bits - 1));
+        }
+        mask |= 1 << core;
+    }
+    Ok(mask)
+}
+
+#[cfg(target_os = "windows")]
+mod win32 {
+    use std::ffi::c_void;
+
+    #[link(name = "kernel32")]
+    unsafe extern "system" {
+        pub fn GetCurrentThread() -> *mut c_void;
+        pub fn SetThreadPriority(thread: *mut c_void, priority: i32) -> i32;
+        pub fn SetThreadAffinityMask(thread: *mut c_void, mask: usize) -> usize;
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn set_current_thread_priority(priority: ThreadPriority) -> Result<(), String> {
+    let ok = unsafe { win32::SetThreadPriority(win32::GetCurrentThread(), priority.win32_value()) };
+    if ok == 0 {
+        return Err(format!("SetThreadPriority failed: {}", std::io::Error::last_os_error()));
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "windows")]
+fn set_current_thread_affinity(cores: &[usize]) -> Result<(), String> {
+    let mask = affinity_mask(cores)?;
+    let previous = unsafe { win32::SetThreadAffinityMask(win32::GetCurrentThread(), mask) };
+    if previous == 0 {
+        return Err(format!("SetThreadAffinityMask failed: {}", std::io::Error::last_os_error()));
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "windows"))]
+fn set_current_thread_priority(_priority: ThreadPriority) -> Result<(), String> {
+    Err("Thread priority is only supported on Windows".to_string())
+}
+
+#[cfg(not(target_os = "windows"))]
+fn set_current_thread_affinity(cores: &[usize]) -> Result<(), String> {
+    // Validate the list anyway so typos are reported on every platform
+    affinity_mask(cores)?;
+    Err("Core affinity is only supported on Windows".to_string())
+}
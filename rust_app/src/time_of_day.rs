@@ -0,0 +1,159 @@
+//! Time of day in the sim and the sun's position over the track.
+//!
+//! SessionTimeOfDay is local clock time at the track. The session info gives the track's
+//! latitude/longitude and the weekend date but no time zone, so the zone is taken from the
+//! longitude (15° per hour, no daylight saving); sunrise/sunset can be off by up to an hour
+//! where civil time differs, while elevation and azimuth only shift along with them.
+
+use crate::derived::DerivedChannel;
+use crate::session_yaml;
+use crate::telemetry_fields::TelemetryData;
+use chrono::{Datelike, Days, NaiveDate};
+use serde::{Serialize, Deserialize};
+use std::f64::consts::PI;
+
+const SECONDS_PER_DAY: f32 = 86400.0;
+// Sun center below the horizon at sunrise/sunset, including refraction
+const HORIZON_ZENITH_DEG: f64 = 90.833;
+
+/// Local time of day at the track
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct TimeOfDay {
+    /// Seconds since local midnight of the current day
+    pub seconds: f32,
+    /// "HH:MM"
+    pub clock: String,
+    /// Current sim date as "YYYY-MM-DD", when the session info gives one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub date: Option<String>,
+}
+
+/// Sun position over the track
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct SunPosition {
+    /// Degrees above the horizon, negative at night
+    pub elevation_deg: f32,
+    /// Degrees clockwise from north
+    pub azimuth_deg: f32,
+    /// Local time of sunrise/sunset in seconds since midnight; None during polar day or night
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sunrise_s: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sunset_s: Option<f32>,
+    /// Seconds until today's sunset, None once it has passed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub until_sunset_s: Option<f32>,
+    pub is_night: bool,
+}
+
+/// Where and when the session takes place, from the session info
+#[derive(Clone, Copy, Debug)]
+struct TrackLocation {
+    latitude_deg: f64,
+    longitude_deg: f64,
+    date: Option<NaiveDate>,
+}
+
+fn location_from_session(yaml: &str) -> Option<TrackLocation> {
+    Some(TrackLocation {
+        latitude_deg: session_yaml::number(yaml, "TrackLatitude")? as f64,
+        longitude_deg: session_yaml::number(yaml, "TrackLongitude")? as f64,
+        date: session_yaml::scalar(yaml, "Date").and_then(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()),
+    })
+}
+
+// Equation of time (minutes) and declination (radians) for a day of the year (NOAA)
+fn solar_terms(day_of_year: u32, hour: f64) -> (f64, f64) {
+    let g = 2.0 * PI / 365.0 * (day_of_year as f64 - 1.0 + (hour - 12.0) / 24.0);
+    let eqtime = 229.18
+        * (0.000075 + 0.001868 * g.cos() - 0.032077 * g.sin() - 0.014615 * (2.0 * g).cos() - 0.040849 * (2.0 * g).sin());
+    let decl = 0.006918 - 0.399912 * g.cos() + 0.070257 * g.sin() - 0.006758 * (2.0 * g).cos()
+        + 0.000907 * (2.0 * g).sin() - 0.002697 * (3.0 * g).cos() + 0.00148 * (3.0 * g).sin();
+    (eqtime, decl)
+}
+
+fn sun_position(location: &TrackLocation, day_of_year: u32, seconds: f32) -> SunPosition {
+    let hour = seconds as f64 / 3600.0;
+    let (eqtime, decl) = solar_terms(day_of_year, hour);
+    let zone_hours = (location.longitude_deg / 15.0).round();
+    // Minutes from local clock time to true solar time
+    let offset_min = eqtime + 4.0 * location.longitude_deg - 60.0 * zone_hours;
+    let lat = location.latitude_deg.to_radians();
+
+    let hour_angle = ((hour * 60.0 + offset_min) / 4.0 - 180.0).to_radians();
+    let cos_zenith = (lat.sin() * decl.sin() + lat.cos() * decl.cos() * hour_angle.cos()).clamp(-1.0, 1.0);
+    let elevation = 90.0 - cos_zenith.acos().to_degrees();
+    let azimuth = (-hour_angle.sin())
+        .atan2(decl.tan() * lat.cos() - lat.sin() * hour_angle.cos())
+        .to_degrees()
+        .rem_euclid(360.0);
+
+    let cos_sunset_angle = HORIZON_ZENITH_DEG.to_radians().cos() / (lat.cos() * decl.cos()) - lat.tan() * decl.tan();
+    let (sunrise, sunset) = if (-1.0..=1.0).contains(&cos_sunset_angle) {
+        let noon_min = 720.0 - offset_min;
+        let half_day_min = 4.0 * cos_sunset_angle.acos().to_degrees();
+        (Some(((noon_min - half_day_min) * 60.0) as f32), Some(((noon_min + half_day_min) * 60.0) as f32))
+    } else {
+        (None, None)
+    };
+
+    SunPosition {
+        elevation_deg: elevation as f32,
+        azimuth_deg: azimuth as f32,
+        sunrise_s: sunrise,
+        sunset_s: sunset,
+        until_sunset_s: sunset.map(|sunset| sunset - seconds).filter(|until| *until > 0.0),
+        is_night: elevation < 90.0 - HORIZON_ZENITH_DEG,
+    }
+}
+
+/// Derived channel that publishes `time_of_day` and, once the track location is known, `sun`
+pub struct SunTracker {
+    last_session_info: String,
+    location: Option<TrackLocation>,
+}
+
+impl SunTracker {
+    pub fn new() -> Self {
+        Self {
+            last_session_info: String::new(),
+            location: None,
+        }
+    }
+}
+
+impl DerivedChannel for SunTracker {
+    fn name(&self) -> &'static str {
+        "sun"
+    }
+
+    fn compute(&mut self, data: &mut TelemetryData) {
+        if data.session_time_of_day < 0.0 {
+            return;
+        }
+        if data.session_info != self.last_session_info {
+            self.last_session_info = data.session_info.clone();
+            self.location = location_from_session(&data.session_info);
+        }
+
+        // Endurance races run past midnight
+        let days = (data.session_time_of_day / SECONDS_PER_DAY).floor();
+        let seconds = data.session_time_of_day - days * SECONDS_PER_DAY;
+        let date = self
+            .location
+            .and_then(|location| location.date)
+            .and_then(|date| date.checked_add_days(Days::new(days as u64)));
+        let minutes = (seconds / 60.0) as u32;
+        data.time_of_day = Some(TimeOfDay {
+            seconds,
+            clock: format!("{:02}:{:02}", minutes / 60, minutes % 60),
+            date: date.map(|date| date.format("%Y-%m-%d").to_string()),
+        });
+
+        // Without a date the sun is placed at the equinox
+        if let Some(location) = &self.location {
+            let day_of_year = date.map(|date| date.ordinal()).unwrap_or(80);
+            data.sun = Some(sun_position(location, day_of_year, seconds));
+        }
+    }
+}
@@ -0,0 +1,116 @@
+//! NTP style clock synchronization over the WebSocket.
+//!
+//! A client sends `{"type":"time_sync","client_send_time":t0}` using its own clock
+//! (milliseconds). The server answers with `server_receive_time` (t1) and
+//! `server_send_time` (t2) on its monotonic clock, so the client can compute
+//! `offset = ((t1 - t0) + (t2 - t3)) / 2` and `round_trip = (t3 - t0) - (t2 - t1)`
+//! where t3 is when the reply arrived.
+//!
+//! When a request also carries `last_receive_time` (t3 of the previous reply) the
+//! server completes that sample itself and includes its running estimate of the
+//! offset and jitter in the reply, so simple clients don't have to do the filtering.
+//! Telemetry frames are stamped with `server_time_ms` on the same clock.
+
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::sync::OnceLock;
+use std::time::Instant;
+
+/// Number of completed samples kept per client
+const MAX_SAMPLES: usize = 8;
+
+static SERVER_EPOCH: OnceLock<Instant> = OnceLock::new();
+
+/// Milliseconds on the server's monotonic clock (starts at 0 when first used)
+pub fn server_time_ms() -> f64 {
+    SERVER_EPOCH.get_or_init(Instant::now).elapsed().as_secs_f64() * 1000.0
+}
+
+/// One completed exchange
+#[derive(Clone, Copy, Debug)]
+struct Sample {
+    /// Server clock minus client clock
+    offset_ms: f64,
+    round_trip_ms: f64,
+}
+
+/// Exchange waiting for the client to report when the reply arrived
+#[derive(Clone, Copy, Debug)]
+struct PendingExchange {
+    client_send_time: f64,
+    server_receive_time: f64,
+    server_send_time: f64,
+}
+
+/// Time sync state for one connection
+#[derive(Default)]
+pub struct TimeSyncSession {
+    pending: Option<PendingExchange>,
+    samples: VecDeque<Sample>,
+}
+
+impl TimeSyncSession {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answer a time_sync request received at `receive_time` (server clock)
+    pub fn handle_request(&mut self, request: &Value, receive_time: f64) -> Value {
+        let client_send_time = request.get("client_send_time").and_then(Value::as_f64);
+
+        // Complete the previous exchange when the client tells us when its reply arrived
+        if let (Some(pending), Some(client_receive_time)) =
+            (self.pending.take(), request.get("last_receive_time").and_then(Value::as_f64))
+        {
+            let round_trip_ms = (client_receive_time - pending.client_send_time)
+                - (pending.server_send_time - pending.server_receive_time);
+            if round_trip_ms >= 0.0 {
+                let offset_ms = ((pending.server_receive_time - pending.client_send_time)
+                    + (pending.server_send_time - client_receive_time)) / 2.0;
+                self.samples.push_back(Sample { offset_ms, round_trip_ms });
+                if self.samples.len() > MAX_SAMPLES {
+                    self.samples.pop_front();
+                }
+            }
+        }
+
+        let mut response = json!({
+            "type": "time_sync",
+            "client_send_time": client_send_time,
+            "server_receive_time": receive_time,
+        });
+        if let Some(estimate) = self.estimate() {
+            response["estimate"] = estimate;
+        }
+
+        // Stamp the send time last so it is as close to the actual send as possible
+        let server_send_time = server_time_ms();
+        response["server_send_time"] = json!(server_send_time);
+        self.pending = client_send_time.map(|client_send_time| PendingExchange {
+            client_send_time,
+            server_receive_time: receive_time,
+            server_send_time,
+        });
+        response
+    }
+
+    /// Offset from the sample with the shortest round trip (least affected by queuing)
+    /// and jitter as the spread of the offsets seen
+    fn estimate(&self) -> Option<Value> {
+        let best = self
+            .samples
+            .iter()
+            .min_by(|a, b| a.round_trip_ms.partial_cmp(&b.round_trip_ms).unwrap_or(std::cmp::Ordering::Equal))?;
+
+        let count = self.samples.len() as f64;
+        let mean = self.samples.iter().map(|s| s.offset_ms).sum::<f64>() / count;
+        let variance = self.samples.iter().map(|s| (s.offset_ms - mean).powi(2)).sum::<f64>() / count;
+
+        Some(json!({
+            "offset_ms": best.offset_ms,
+            "round_trip_ms": best.round_trip_ms,
+            "jitter_ms": variance.sqrt(),
+            "samples": self.samples.len(),
+        }))
+    }
+}
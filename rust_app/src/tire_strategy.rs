@@ -0,0 +1,152 @@
+//! Tire compound history of every car.
+//!
+//! A stint starts when a car is first seen and at every pit exit, with the compound it
+//! leaves on. The SDK reports tire set allocations for the player only (TireSetsAvailable,
+//! 255 when the session has no limit), so remaining sets are not known for other cars.
+
+use crate::derived::DerivedChannel;
+use crate::session_yaml;
+use crate::telemetry_fields::TelemetryData;
+use serde::{Serialize, Deserialize};
+
+// TireSetsAvailable when the session doesn't limit tire sets
+const UNLIMITED_TIRE_SETS: i32 = 255;
+
+/// One stint on a single set of tires
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct TireStint {
+    pub compound: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compound_name: Option<String>,
+    /// Lap the stint started on
+    pub start_lap: i32,
+    /// Lap the car pitted at the end of the stint; None for the current stint
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_lap: Option<i32>,
+}
+
+/// Tire history of one car
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct CarTireHistory {
+    pub car_idx: usize,
+    pub current_compound: i32,
+    pub stints: Vec<TireStint>,
+    /// Distinct compounds used this session, in order of first use
+    pub compounds_used: Vec<i32>,
+}
+
+/// Compound usage across the grid
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct TireStrategy {
+    pub cars: Vec<CarTireHistory>,
+    /// Tire sets the player may still fit, when the session limits them
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub player_sets_available: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub player_sets_used: Option<i32>,
+}
+
+#[derive(Clone, Debug, Default)]
+struct CarState {
+    history: Option<CarTireHistory>,
+    on_pit_road: bool,
+}
+
+/// Derived channel that records the compound of every car's stints
+pub struct TireStrategyTracker {
+    last_session_info: String,
+    compound_names: Vec<(i32, String)>,
+    cars: Vec<CarState>,
+}
+
+impl TireStrategyTracker {
+    pub fn new() -> Self {
+        Self {
+            last_session_info: String::new(),
+            compound_names: Vec::new(),
+            cars: Vec::new(),
+        }
+    }
+
+    fn compound_name(&self, compound: i32) -> Option<String> {
+        self.compound_names.iter().find(|(index, _)| *index == compound).map(|(_, name)| name.clone())
+    }
+}
+
+impl DerivedChannel for TireStrategyTracker {
+    fn name(&self) -> &'static str {
+        "tire_strategy"
+    }
+
+    fn compute(&mut self, data: &mut TelemetryData) {
+        if data.race_clock.session_reset {
+            self.cars.clear();
+        }
+        if data.session_info != self.last_session_info {
+            self.last_session_info = data.session_info.clone();
+            self.compound_names = session_yaml::tire_compounds(&data.session_info);
+        }
+        let (Some(compounds), Some(positions)) = (data.CarIdxTireCompound.as_ref(), data.CarIdxLapDistPct.as_ref()) else {
+            return;
+        };
+        if self.cars.len() < compounds.len() {
+            self.cars.resize_with(compounds.len(), CarState::default);
+        }
+
+        for idx in 0..self.cars.len() {
+            let compound = compounds.get(idx).copied().unwrap_or(-1);
+            let in_world = positions.get(idx).is_some_and(|&pct| pct >= 0.0);
+            let in_pits = data.CarIdxOnPitRoad.as_ref().and_then(|p| p.get(idx)).copied().unwrap_or(false);
+            let lap = data.CarIdxLap.as_ref().and_then(|l| l.get(idx)).copied().unwrap_or(0);
+            if compound < 0 || !in_world {
+                continue;
+            }
+            let name = self.compound_name(compound);
+            let car = &mut self.cars[idx];
+            let left_pits = car.on_pit_road && !in_pits;
+            car.on_pit_road = in_pits;
+
+            let history = car.history.get_or_insert_with(|| CarTireHistory {
+                car_idx: idx,
+                current_compound: compound,
+                ..CarTireHistory::default()
+            });
+            if history.stints.is_empty() || left_pits {
+                if let Some(last) = history.stints.last_mut() {
+                    last.end_lap = Some(lap);
+                }
+                history.stints.push(TireStint {
+                    compound,
+                    compound_name: name.clone(),
+                    start_lap: lap,
+                    end_lap: None,
+                });
+            } else if compound != history.current_compound {
+                // The new compound can show up a few frames after pit exit
+                if let Some(last) = history.stints.last_mut() {
+                    last.compound = compound;
+                    last.compound_name = name;
+                }
+            }
+            history.current_compound = compound;
+            if !history.compounds_used.contains(&compound) {
+                history.compounds_used = Vec::new();
+                for stint in &history.stints {
+                    if !history.compounds_used.contains(&stint.compound) {
+                        history.compounds_used.push(stint.compound);
+                    }
+                }
+            }
+        }
+
+        let cars: Vec<CarTireHistory> = self.cars.iter().filter_map(|car| car.history.clone()).collect();
+        if cars.is_empty() {
+            return;
+        }
+        data.tire_strategy = Some(TireStrategy {
+            cars,
+            player_sets_available: Some(data.tire_sets_available).filter(|&sets| sets >= 0 && sets < UNLIMITED_TIRE_SETS),
+            player_sets_used: Some(data.tire_sets_used).filter(|&sets| sets >= 0),
+        });
+    }
+}
@@ -0,0 +1,252 @@
+use crate::derived::DerivedChannel;
+use crate::laps::{self, LapChange, LapTracker};
+use crate::telemetry_fields::TelemetryData;
+use serde::{Serialize, Deserialize};
+
+// Brake pressure that marks the car as being in a corner phase
+const BRAKE_ON_PCT: f32 = 10.0;
+// Throttle that counts as the application point
+const THROTTLE_ON_PCT: f32 = 10.0;
+// Throttle that counts as full throttle
+const FULL_THROTTLE_PCT: f32 = 95.0;
+// How long full throttle must be held for the exit phase to end
+const FULL_THROTTLE_HOLD_S: f32 = 0.5;
+// Give up on an exit that never reaches full throttle (slow corner sequences)
+const MAX_EXIT_TIME_S: f32 = 8.0;
+// Ignore corner exits below this speed (pit lane, spins)
+const MIN_EXIT_SPEED_KPH: f32 = 30.0;
+// Maximum distance (fraction of a lap) between application points to treat them as the same corner
+const EXIT_MATCH_PCT: f32 = 0.02;
+
+/// Measurements for one corner exit
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct CornerExit {
+    /// Index of the exit within its lap
+    pub exit: usize,
+    pub lap: i32,
+    /// Lap position where throttle was first applied after the corner
+    pub throttle_on_pct: f32,
+    pub throttle_on_speed_kph: f32,
+    /// Time from the application point to full throttle (None if never reached)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_to_full_throttle_s: Option<f32>,
+    /// Wheelspin occurrences during the exit
+    pub wheelspins: u32,
+    /// Times the throttle was lifted again before reaching full throttle
+    pub throttle_lifts: u32,
+    pub exit_speed_kph: f32,
+    pub exit_pct: f32,
+}
+
+/// Differences between an exit and the matching exit of the reference lap
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct TractionDeltas {
+    /// Positive = throttle applied later than the reference
+    pub throttle_on_m: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_to_full_throttle_s: Option<f32>,
+    pub exit_speed_kph: f32,
+}
+
+/// Report broadcast when a corner exit phase finishes
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct TractionReport {
+    pub exit: CornerExit,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reference: Option<CornerExit>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deltas: Option<TractionDeltas>,
+}
+
+// Where we are within the corner
+enum Phase {
+    /// Not in a corner
+    Straight,
+    /// Braked for a corner, waiting for the throttle
+    WaitingForThrottle,
+    /// Throttle applied, measuring the exit
+    Exiting {
+        exit: CornerExit,
+        elapsed: f32,
+        full_for: f32,
+        was_lifting: bool,
+    },
+}
+
+/// Measures corner exits (throttle application, traction, exit speed) against the best lap
+pub struct TractionAnalyzer {
+    laps: LapTracker,
+    phase: Phase,
+    spinning: [bool; 4],
+    current_lap_exits: Vec<CornerExit>,
+    reference_exits: Vec<CornerExit>,
+    reference_lap_time: Option<f32>,
+    pitted_this_lap: bool,
+}
+
+impl TractionAnalyzer {
+    pub fn new() -> Self {
+        Self {
+            laps: LapTracker::new(),
+            phase: Phase::Straight,
+            spinning: [false; 4],
+            current_lap_exits: Vec::new(),
+            reference_exits: Vec::new(),
+            reference_lap_time: None,
+            pitted_this_lap: false,
+        }
+    }
+
+    fn find_reference(&self, throttle_on_pct: f32) -> Option<&CornerExit> {
+        self.reference_exits
+            .iter()
+            .map(|exit| (exit, laps::lap_pct_distance(exit.throttle_on_pct, throttle_on_pct)))
+            .filter(|(_, distance)| *distance <= EXIT_MATCH_PCT)
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+            .map(|(exit, _)| exit)
+    }
+
+    fn finish_exit(&mut self, mut exit: CornerExit, data: &TelemetryData) -> TractionReport {
+        exit.exit_speed_kph = data.speed_kph;
+        exit.exit_pct = data.lap_dist_pct;
+        exit.exit = self.current_lap_exits.len();
+        self.current_lap_exits.push(exit.clone());
+
+        let track_length_m = laps::estimate_track_length(data);
+        let reference = self.find_reference(exit.throttle_on_pct).cloned();
+        let deltas = reference.as_ref().map(|reference| TractionDeltas {
+            throttle_on_m: laps::signed_lap_pct_delta(reference.throttle_on_pct, exit.throttle_on_pct) * track_length_m,
+            time_to_full_throttle_s: match (exit.time_to_full_throttle_s, reference.time_to_full_throttle_s) {
+                (Some(mine), Some(theirs)) => Some(mine - theirs),
+                _ => None,
+            },
+            exit_speed_kph: exit.exit_speed_kph - reference.exit_speed_kph,
+        });
+
+        TractionReport { exit, reference, deltas }
+    }
+
+    fn on_lap_completed(&mut self, lap_time: f32) {
+        let exits = std::mem::take(&mut self.current_lap_exits);
+        // In/out laps never become the reference
+        if self.pitted_this_lap || lap_time <= 0.0 || exits.is_empty() {
+            return;
+        }
+        if self.reference_lap_time.is_none_or(|best| lap_time < best) {
+            self.reference_lap_time = Some(lap_time);
+            self.reference_exits = exits;
+        }
+    }
+
+    // Count wheelspin onsets reported by the wheel slip channel
+    fn new_wheelspins(&mut self, data: &TelemetryData) -> u32 {
+        let mut count = 0;
+        if let Some(slip) = &data.wheel_slip {
+            for wheel in 0..4 {
+                if slip.spinning[wheel] && !self.spinning[wheel] {
+                    count += 1;
+                }
+                self.spinning[wheel] = slip.spinning[wheel];
+            }
+        }
+        count
+    }
+}
+
+impl DerivedChannel for TractionAnalyzer {
+    fn name(&self) -> &'static str {
+        "traction"
+    }
+
+    fn dependencies(&self) -> &'static [&'static str] {
+        &["wheel_slip"]
+    }
+
+    fn compute(&mut self, data: &mut TelemetryData) {
+        let dt = data.race_clock.dt;
+
+        match self.laps.update(data) {
+            LapChange::Completed { lap_time, .. } => {
+                self.on_lap_completed(lap_time);
+                self.pitted_this_lap = false;
+            },
+            LapChange::Reset => {
+                self.phase = Phase::Straight;
+                self.current_lap_exits.clear();
+            },
+            LapChange::None => {}
+        }
+        self.pitted_this_lap |= data.on_pit_road;
+
+        let wheelspins = self.new_wheelspins(data);
+
+        if data.on_pit_road {
+            self.phase = Phase::Straight;
+            return;
+        }
+
+        let phase = std::mem::replace(&mut self.phase, Phase::Straight);
+        self.phase = match phase {
+            Phase::Straight => {
+                if data.brake_pct >= BRAKE_ON_PCT {
+                    Phase::WaitingForThrottle
+                } else {
+                    Phase::Straight
+                }
+            },
+            Phase::WaitingForThrottle => {
+                if data.throttle_pct >= THROTTLE_ON_PCT && data.brake_pct < BRAKE_ON_PCT {
+                    Phase::Exiting {
+                        exit: CornerExit {
+                            lap: data.lap_completed,
+                            throttle_on_pct: data.lap_dist_pct,
+                            throttle_on_speed_kph: data.speed_kph,
+                            wheelspins,
+                            ..Default::default()
+                        },
+                        elapsed: 0.0,
+                        full_for: 0.0,
+                        was_lifting: false,
+                    }
+                } else {
+                    Phase::WaitingForThrottle
+                }
+            },
+            Phase::Exiting { mut exit, mut elapsed, mut full_for, mut was_lifting } => {
+                elapsed += dt;
+                exit.wheelspins += wheelspins;
+
+                if data.throttle_pct >= FULL_THROTTLE_PCT {
+                    if exit.time_to_full_throttle_s.is_none() {
+                        exit.time_to_full_throttle_s = Some(elapsed);
+                    }
+                    full_for += dt;
+                } else {
+                    full_for = 0.0;
+                }
+
+                // A lift is a drop back below the application threshold
+                let lifting = data.throttle_pct < THROTTLE_ON_PCT;
+                if lifting && !was_lifting {
+                    exit.throttle_lifts += 1;
+                }
+                was_lifting = lifting;
+
+                if data.brake_pct >= BRAKE_ON_PCT {
+                    // Braking again before reaching full throttle: the next corner starts
+                    if data.speed_kph >= MIN_EXIT_SPEED_KPH {
+                        data.traction_report = Some(self.finish_exit(exit, data));
+                    }
+                    Phase::WaitingForThrottle
+                } else if full_for >= FULL_THROTTLE_HOLD_S || elapsed >= MAX_EXIT_TIME_S {
+                    if data.speed_kph >= MIN_EXIT_SPEED_KPH {
+                        data.traction_report = Some(self.finish_exit(exit, data));
+                    }
+                    Phase::Straight
+                } else {
+                    Phase::Exiting { exit, elapsed, full_for, was_lifting }
+                }
+            },
+        };
+    }
+}
@@ -0,0 +1,85 @@
+//! The driver's display units, so overlays can default to the driver's iRacing setting.
+//!
+//! iRacing exposes the preference only as the DisplayUnits telemetry channel; the session
+//! info has no unit setting of its own. Clients receive it in the `hello` message sent on
+//! connect (see protocol.rs) and on `{"type":"bootstrap"}`; it is empty until the sim has been seen.
+
+use crate::derived::DerivedChannel;
+use crate::frame_schema::SCHEMA_VERSION;
+use crate::telemetry_fields::TelemetryData;
+use crate::websocket_server::RequestHandler;
+use serde::{Serialize, Deserialize};
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+
+/// Unit system selected in the driver's iRacing options
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum UnitsPreference {
+    Metric,
+    Imperial,
+}
+
+impl UnitsPreference {
+    /// From the DisplayUnits channel: 0 is English (imperial), 1 is metric
+    pub fn from_display_units(display_units: i32) -> Option<Self> {
+        match display_units {
+            0 => Some(UnitsPreference::Imperial),
+            1 => Some(UnitsPreference::Metric),
+            _ => None,
+        }
+    }
+}
+
+/// Connection bootstrap info shared between the telemetry thread and the server
+#[derive(Clone, Default)]
+pub struct Bootstrap {
+    units_preference: Arc<Mutex<Option<UnitsPreference>>>,
+}
+
+impl Bootstrap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn units_preference(&self) -> Option<UnitsPreference> {
+        self.units_preference.lock().ok().and_then(|units| *units)
+    }
+}
+
+impl RequestHandler for Bootstrap {
+    /// `{"type":"bootstrap"}`
+    fn handle(&self, _request: &Value) -> Value {
+        json!({
+            "type": "bootstrap",
+            "schema_version": SCHEMA_VERSION,
+            "units_preference": self.units_preference(),
+        })
+    }
+}
+
+/// Derived channel that keeps the bootstrap's units preference in line with DisplayUnits
+pub struct UnitsRecorder {
+    bootstrap: Bootstrap,
+}
+
+impl UnitsRecorder {
+    pub fn new(bootstrap: Bootstrap) -> Self {
+        Self { bootstrap }
+    }
+}
+
+impl DerivedChannel for UnitsRecorder {
+    fn name(&self) -> &'static str {
+        "units_preference"
+    }
+
+    fn compute(&mut self, data: &mut TelemetryData) {
+        let Some(units) = UnitsPreference::from_display_units(data.display_units) else {
+            return;
+        };
+        if let Ok(mut current) = self.bootstrap.units_preference.lock() {
+            *current = Some(units);
+        }
+    }
+}
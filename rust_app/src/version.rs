@@ -0,0 +1,110 @@
+//! Build identification and the optional check for a newer release.
+//!
+//! The version is sent in the WebSocket `hello`, answered on `GET /info`, and printed by
+//! `speedforge --version` (`--version --json` for the full build metadata), so support
+//! can tell which build a user runs. With `--check-updates` the server asks GitHub for the
+//! latest release at startup and logs when it is newer; nothing is ever downloaded.
+
+use crate::http_api::{HttpHandler, HttpRequest, HttpResponse};
+use serde_json::{json, Value};
+
+/// Version of this build
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+/// Latest release, as returned by the GitHub API
+#[cfg(feature = "update-check")]
+pub const RELEASES_URL: &str = "https://api.github.com/repos/hodlthedoor/speedforge/releases/latest";
+
+// Optional features compiled into this build
+const FEATURES: &[(&str, bool)] = &[
+    ("gamepad", cfg!(feature = "gamepad")),
+    ("data-api", cfg!(feature = "data-api")),
+    ("encryption", cfg!(feature = "encryption")),
+    ("update-check", cfg!(feature = "update-check")),
+    ("upload", cfg!(feature = "upload")),
+];
+
+/// Version and build metadata
+pub fn build_info() -> Value {
+    let features: Vec<&str> = FEATURES.iter().filter(|(_, enabled)| *enabled).map(|(name, _)| *name).collect();
+    json!({
+        "name": env!("CARGO_PKG_NAME"),
+        "version": VERSION,
+        // Set by release builds, e.g. SPEEDFORGE_GIT_HASH=$(git rev-parse --short HEAD) cargo build
+        "git_hash": option_env!("SPEEDFORGE_GIT_HASH"),
+        "profile": if cfg!(debug_assertions) { "debug" } else { "release" },
+        "target_os": std::env::consts::OS,
+        "target_arch": std::env::consts::ARCH,
+        "features": features,
+    })
+}
+
+/// Text printed by `speedforge --version`
+pub fn version_text(as_json: bool) -> String {
+    if as_json {
+        return build_info().to_string();
+    }
+    match option_env!("SPEEDFORGE_GIT_HASH") {
+        Some(hash) => format!("speedforge {} ({})", VERSION, hash),
+        None => format!("speedforge {}", VERSION),
+    }
+}
+
+// "v1.2.3" or "1.2.3-beta" as (1, 2, 3); pre-release suffixes are ignored
+#[cfg(feature = "update-check")]
+fn parse_version(tag: &str) -> Option<(u64, u64, u64)> {
+    let mut parts = tag.trim().trim_start_matches('v').split(['.', '-', '+']);
+    Some((parts.next()?.parse().ok()?, parts.next()?.parse().ok()?, parts.next().unwrap_or("0").parse().ok()?))
+}
+
+/// Whether a release tag is newer than this build
+#[cfg(feature = "update-check")]
+pub fn is_newer(tag: &str) -> bool {
+    match (parse_version(tag), parse_version(VERSION)) {
+        (Some(release), Some(current)) => release > current,
+        _ => false,
+    }
+}
+
+/// Look up the latest release on a background thread and report it through `log`
+#[cfg(feature = "update-check")]
+pub fn check_for_update(log: impl Fn(String) + Send + 'static) {
+    std::thread::spawn(move || {
+        let release = reqwest::blocking::Client::builder()
+            .user_agent(format!("speedforge/{}", VERSION))
+            .timeout(std::time::Duration::from_secs(10))
+            .build()
+            .and_then(|client| client.get(RELEASES_URL).send())
+            .and_then(|response| response.error_for_status())
+            .and_then(|response| response.json::<Value>());
+        match release {
+            Ok(release) => {
+                let tag = release.get("tag_name").and_then(Value::as_str).unwrap_or_default();
+                if is_newer(tag) {
+                    let url = release.get("html_url").and_then(Value::as_str).unwrap_or(RELEASES_URL);
+                    log(format!("A newer version is available: {} (running {}), see {}", tag, VERSION, url));
+                }
+            },
+            Err(e) => log(format!("Update check failed: {}", e)),
+        }
+    });
+}
+
+#[cfg(not(feature = "update-check"))]
+pub fn check_for_update(log: impl Fn(String) + Send + 'static) {
+    log("Update checks require building with the \"update-check\" feature".to_string());
+}
+
+/// `GET /info` with the version and build metadata
+pub struct InfoEndpoint;
+
+impl HttpHandler for InfoEndpoint {
+    fn handle(&self, request: &HttpRequest) -> Option<HttpResponse> {
+        if request.segments() != ["info"] {
+            return None;
+        }
+        Some(match request.method.as_str() {
+            "GET" => HttpResponse::json(200, &build_info()),
+            _ => HttpResponse::error(405, "Method not allowed"),
+        })
+    }
+}
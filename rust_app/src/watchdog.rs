@@ -0,0 +1,28 @@
+use std::time::{Duration, Instant};
+
+/// SessionTime must stand still this long before frames count as stale; a live sim
+/// advances it every tick, so this only trips when paused, loading or hung
+pub const STALE_AFTER: Duration = Duration::from_millis(250);
+
+/// Detects when the SDK keeps returning the same SessionTime
+#[derive(Default)]
+pub struct FrozenDataWatchdog {
+    last_session_time: Option<f32>,
+    unchanged_since: Option<Instant>,
+}
+
+impl FrozenDataWatchdog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed the SessionTime of a new sample; returns true while the data is frozen
+    pub fn update(&mut self, session_time: f32) -> bool {
+        if self.last_session_time.replace(session_time) != Some(session_time) {
+            self.unchanged_since = None;
+            return false;
+        }
+        let since = *self.unchanged_since.get_or_insert_with(Instant::now);
+        since.elapsed() >= STALE_AFTER
+    }
+}
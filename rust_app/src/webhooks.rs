@@ -0,0 +1,82 @@
+//! Fire-and-forget HTTP actions for tools like VoiceAttack or Stream Deck.
+//!
+//! `POST /actions/{action}` queues an action for the telemetry loop:
+//! `toggle_recording`, `marker`, `toggle_verbose`, `cycle_focus`, `alert` and
+//! `pit_command`. The optional JSON body carries details, e.g.
+//! `{"label":"lap 12 lockup"}`, `{"message":"Box this lap"}` or `{"command":"fuel 40"}`.
+//!
+//! Requests must carry the API token as `Authorization: Bearer <token>` or `?token=`.
+//! Without a configured token every action is refused.
+
+use crate::controls::{ControlAction, ControlSender};
+use crate::http_api::{HttpHandler, HttpRequest, HttpResponse};
+use crate::sim_commands::PitCommand;
+use serde_json::{json, Value};
+
+/// HTTP handler turning webhook calls into control actions
+pub struct WebhookReceiver {
+    token: Option<String>,
+    actions: ControlSender,
+}
+
+impl WebhookReceiver {
+    pub fn new(token: Option<String>, actions: ControlSender) -> Self {
+        Self { token, actions }
+    }
+
+    fn parse_action(name: &str, body: &Value) -> Result<ControlAction, String> {
+        let text = |key: &str| body.get(key).and_then(Value::as_str).map(str::to_string);
+        match name {
+            "marker" | "drop_marker" => Ok(ControlAction::DropMarker {
+                label: text("label").unwrap_or_else(|| "marker".to_string()),
+                source: "webhook".to_string(),
+            }),
+            "alert" => Ok(ControlAction::Alert {
+                message: text("message").ok_or("Alert needs a message")?,
+            }),
+            "pit_command" => {
+                let command = text("command").ok_or("Pit command needs a command")?;
+                // Validate here so the caller gets the error instead of the log
+                PitCommand::parse(&command)?;
+                Ok(ControlAction::PitCommand { command })
+            },
+            other => ControlAction::parse(other, "webhook").ok_or_else(|| format!("Unknown action: {}", other)),
+        }
+    }
+}
+
+impl HttpHandler for WebhookReceiver {
+    fn handle(&self, request: &HttpRequest) -> Option<HttpResponse> {
+        let segments = request.segments();
+        if segments.first() != Some(&"actions") {
+            return None;
+        }
+        if !request.has_token(self.token.as_deref()) {
+            let message = if self.token.is_some() { "Invalid or missing token" } else { "Actions are disabled until an API token is configured" };
+            return Some(HttpResponse::error(401, message));
+        }
+
+        let response = match (request.method.as_str(), &segments[1..]) {
+            ("POST", [name]) => {
+                let body: Value = if request.body.is_empty() {
+                    Value::Null
+                } else {
+                    match serde_json::from_slice(&request.body) {
+                        Ok(body) => body,
+                        Err(e) => return Some(HttpResponse::error(400, &format!("Body must be JSON: {}", e))),
+                    }
+                };
+                match Self::parse_action(name, &body) {
+                    Ok(action) => match self.actions.send(action) {
+                        Ok(()) => HttpResponse::json(202, &json!({ "queued": name })),
+                        Err(_) => HttpResponse::error(500, "Telemetry loop is not running"),
+                    },
+                    Err(e) => HttpResponse::error(400, &e),
+                }
+            },
+            (_, [_]) => HttpResponse::error(405, "Method not allowed"),
+            _ => HttpResponse::error(404, "Not found"),
+        };
+        Some(response)
+    }
+}
@@ -1,42 +1,108 @@
 use crate::telemetry_fields::TelemetryData;
+use crate::precision::PrecisionRules;
+use crate::change_detection::DeadBands;
+use crate::channel_presets::ChannelPresets;
+use crate::codec::{self, EncodedCache};
+use crate::delta::{self, DeltaEncoder, DeltaFrame};
+use crate::field_compat;
+use crate::field_groups;
+use crate::origins::OriginPolicy;
+use crate::endpoints::{Endpoint, SESSION_COMMANDS};
+use crate::connection_limits::{ConnectionLimits, ConnectionTracker, MessageRateLimiter, MAX_RATE_VIOLATIONS};
+use crate::http_api::{self, HttpHandler};
+use crate::pipeline_trace;
+use crate::protocol::{self, ClientOptions};
+use crate::time_sync::{server_time_ms, TimeSyncSession};
 use futures_util::{SinkExt, StreamExt};
-use std::collections::HashSet;
+use socket2::{Domain, Protocol, Socket, Type};
+use std::collections::{HashMap, HashSet};
 use std::net::SocketAddr;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::sync::atomic::{AtomicU64, Ordering};
 use tokio::net::{TcpListener, TcpStream};
 use tokio::sync::mpsc::{self, UnboundedSender};
-use tokio_tungstenite::{accept_async, tungstenite::Message};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tokio_tungstenite::tungstenite::http::StatusCode;
+use tokio_tungstenite::tungstenite::protocol::WebSocketConfig;
 use std::hash::Hasher;
-use std::time::{SystemTime, UNIX_EPOCH};
-use std::io::{self, Write};
+use std::time::{Instant, SystemTime};
+use std::io;
 use std::error::Error;
+use tracing::{debug, error, info, warn};
 
 // Remove incorrect import
 // extern crate crate as main_crate;
 // use main_crate::is_verbose;
 
-// Track verbose mode
-static mut WEBSOCKET_VERBOSE_MODE: bool = false;
+/// Time between keep-alive comments on an idle event stream
+const EVENT_STREAM_KEEPALIVE: std::time::Duration = std::time::Duration::from_secs(15);
 
-// Safe wrapper for verbose mode
-fn ws_is_verbose() -> bool {
-    unsafe { WEBSOCKET_VERBOSE_MODE }
+/// Options a client negotiated with `hello` or `set_channel_preset`
+type ClientState = Arc<Mutex<ClientOptions>>;
+
+/// What broadcasting remembers about each client between frames
+#[derive(Default)]
+struct ClientStream {
+    /// Values last sent in delta mode; None until the first delta frame
+    delta: Option<DeltaEncoder>,
+    /// Server time (ms) the next frame is due when the client limited its rate
+    next_frame_ms: Option<f64>,
 }
 
-/// A wrapper for UnboundedSender that implements Hash and Eq
+impl ClientStream {
+    // Whether a frame is due at `now_ms` for a client limited to `rate_hz`. Frames within a
+    // quarter interval of the due time count, so jitter in the source rate doesn't halve it
+    fn frame_due(&mut self, now_ms: f64, rate_hz: f32) -> bool {
+        let interval = 1000.0 / rate_hz as f64;
+        match self.next_frame_ms {
+            Some(next) if now_ms + interval / 4.0 < next => false,
+            next => {
+                self.next_frame_ms = Some(next.unwrap_or(now_ms).max(now_ms - interval) + interval);
+                true
+            },
+        }
+    }
+
+    // Start over: a keyframe next and no pacing history
+    fn reset(&mut self) {
+        self.delta = None;
+        self.next_frame_ms = None;
+    }
+}
+
+type StreamState = Arc<Mutex<ClientStream>>;
+
+/// Who a client is, for status pages
+struct ClientInfo {
+    addr: SocketAddr,
+    endpoint: Endpoint,
+    connected_at: SystemTime,
+}
+
+// Source of client ids, unique for the life of the process
+static NEXT_CLIENT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// A connected client: its queue, options and stream state, identified by a unique id so
+/// clones compare equal to the original
 #[derive(Clone)]
-struct ClientSender(UnboundedSender<Message>);
+struct ClientSender(UnboundedSender<Message>, ClientState, StreamState, Arc<ClientInfo>, u64);
 
 impl ClientSender {
-    fn new(tx: UnboundedSender<Message>) -> Self {
-        ClientSender(tx)
+    fn new(tx: UnboundedSender<Message>, options: ClientOptions, info: ClientInfo) -> Self {
+        let id = NEXT_CLIENT_ID.fetch_add(1, Ordering::Relaxed);
+        ClientSender(tx, Arc::new(Mutex::new(options)), StreamState::default(), Arc::new(info), id)
+    }
+
+    // The connection task has ended and nothing reads the queue any more
+    fn is_closed(&self) -> bool {
+        self.0.is_closed()
     }
 }
 
 impl PartialEq for ClientSender {
     fn eq(&self, other: &Self) -> bool {
-        // Each sender has a unique address in memory that we can use for comparison
-        std::ptr::eq(&self.0, &other.0)
+        self.4 == other.4
     }
 }
 
@@ -44,180 +110,708 @@ impl Eq for ClientSender {}
 
 impl std::hash::Hash for ClientSender {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-        // Hash based on the memory address of the sender
-        let ptr = &self.0 as *const _ as usize;
-        ptr.hash(state);
+        self.4.hash(state);
     }
 }
 
+/// Answers a client request message (`{"type": "...", ...}`) with a reply for that client
+pub trait RequestHandler: Send + Sync {
+    fn handle(&self, request: &serde_json::Value) -> serde_json::Value;
+}
+
+/// Builds a message from each frame for the clients subscribed to it, with parameters
+/// each client picked (e.g. the two cars of a head-to-head)
+pub trait StreamProvider: Send + Sync {
+    /// Check a subscription request and return the parameters to keep for the client
+    fn subscribe(&self, request: &serde_json::Value) -> Result<serde_json::Value, String>;
+    /// Message for one set of parameters, None when there is nothing to send this frame
+    fn message(&self, telemetry: &TelemetryData, params: &serde_json::Value) -> Option<serde_json::Value>;
+}
+
+/// What every connection task needs from the server
+struct ConnectionContext {
+    clients: Clients,
+    http_handlers: Vec<Arc<dyn HttpHandler>>,
+    request_handlers: HashMap<String, Arc<dyn RequestHandler>>,
+    streams: HashMap<String, Arc<dyn StreamProvider>>,
+    channel_presets: Arc<ChannelPresets>,
+    limits: Arc<ConnectionLimits>,
+    admin_token: Arc<Option<String>>,
+    origins: Arc<OriginPolicy>,
+    session_info: SessionInfoState,
+}
+
+/// Latest session info, for clients that subscribe after it was broadcast
+#[derive(Default)]
+struct LatestSessionInfo {
+    yaml: String,
+    message: Option<serde_json::Value>,
+}
+
+type SessionInfoState = Arc<Mutex<LatestSessionInfo>>;
+
 /// Type alias for a set of WebSocket clients
 type Clients = Arc<Mutex<HashSet<ClientSender>>>;
 
+/// Read-only view of the connected clients, for status pages
+#[derive(Clone)]
+pub struct ClientDirectory(Clients);
+
+impl ClientDirectory {
+    /// Address, endpoint, connection time and negotiated options of each client, oldest first
+    pub fn list(&self) -> Vec<serde_json::Value> {
+        let Ok(mut clients) = self.0.lock() else {
+            return Vec::new();
+        };
+        clients.retain(|client| !client.is_closed());
+        let mut infos: Vec<&ClientSender> = clients.iter().collect();
+        infos.sort_by_key(|client| client.3.connected_at);
+        infos
+            .into_iter()
+            .map(|client| {
+                let info = &client.3;
+                let connected_s = info.connected_at.elapsed().unwrap_or_default().as_secs();
+                let options = client.1.lock().ok().and_then(|options| serde_json::to_value(&*options).ok());
+                serde_json::json!({
+                    "addr": info.addr.to_string(),
+                    "endpoint": info.endpoint.name(),
+                    "connected_s": connected_s,
+                    "options": options,
+                })
+            })
+            .collect()
+    }
+}
+
 /// Represents a WebSocket server that broadcasts telemetry data
 #[derive(Clone)]
 pub struct TelemetryWebSocketServer {
     clients: Arc<Mutex<HashSet<ClientSender>>>,
-    address: String,
+    addresses: Vec<SocketAddr>,
+    precision: PrecisionRules,
+    dead_bands: DeadBands,
+    http_handlers: Vec<Arc<dyn HttpHandler>>,
+    request_handlers: HashMap<String, Arc<dyn RequestHandler>>,
+    streams: HashMap<String, Arc<dyn StreamProvider>>,
+    channel_presets: Arc<ChannelPresets>,
+    limits: Arc<ConnectionLimits>,
+    connections: ConnectionTracker,
+    admin_token: Arc<Option<String>>,
+    origins: Arc<OriginPolicy>,
+    session_info: SessionInfoState,
 }
 
 impl TelemetryWebSocketServer {
-    /// Create a new WebSocket server
-    pub fn new(address: &str) -> Result<Self, Box<dyn Error>> {
-        println!("[{}] Creating WebSocket server on {}", get_timestamp(), address);
+    /// Create a new WebSocket server listening on each of `addresses`
+    pub fn new(addresses: Vec<SocketAddr>) -> Result<Self, Box<dyn Error>> {
+        if addresses.is_empty() {
+            return Err("No address to listen on".into());
+        }
+        let list: Vec<String> = addresses.iter().map(SocketAddr::to_string).collect();
+        info!("Creating WebSocket server on {}", list.join(", "));
         Ok(TelemetryWebSocketServer {
-            address: address.to_string(),
+            addresses,
             clients: Arc::new(Mutex::new(HashSet::new())),
+            precision: PrecisionRules::default(),
+            dead_bands: DeadBands::default(),
+            http_handlers: Vec::new(),
+            request_handlers: HashMap::new(),
+            streams: HashMap::new(),
+            channel_presets: Arc::new(ChannelPresets::default()),
+            limits: Arc::new(ConnectionLimits::default()),
+            connections: ConnectionTracker::new(),
+            admin_token: Arc::new(None),
+            origins: Arc::new(OriginPolicy::default()),
+            session_info: SessionInfoState::default(),
         })
     }
     
-    /// Set verbose mode for WebSocket server
-    pub fn set_verbose_mode(&self, verbose: bool) {
-        unsafe {
-            WEBSOCKET_VERBOSE_MODE = verbose;
-        }
+    /// Set the float rounding rules used when serializing telemetry
+    pub fn set_precision(&mut self, precision: PrecisionRules) {
+        self.precision = precision;
+    }
+    
+    /// Set the per-channel dead-bands used to decide which fields changed between frames
+    pub fn set_dead_bands(&mut self, dead_bands: DeadBands) {
+        self.dead_bands = dead_bands;
+    }
+    
+    /// Answer plain HTTP requests on the server port with this handler (call before `start`)
+    pub fn add_http_handler(&mut self, handler: Arc<dyn HttpHandler>) {
+        self.http_handlers.push(handler);
+    }
+    
+    /// Answer client messages of the given type with this handler (call before `start`)
+    pub fn add_request_handler(&mut self, message_type: &str, handler: Arc<dyn RequestHandler>) {
+        self.request_handlers.insert(message_type.to_string(), handler);
+    }
+    
+    /// Per-client stream clients start with `{"type":"<name>",...}` (call before `start`)
+    pub fn add_stream(&mut self, name: &str, provider: Arc<dyn StreamProvider>) {
+        self.streams.insert(name.to_string(), provider);
+    }
+    
+    /// Channel presets clients can pick with `set_channel_preset` (call before `start`)
+    pub fn set_channel_presets(&mut self, presets: ChannelPresets) {
+        self.channel_presets = Arc::new(presets);
+    }
+    
+    /// Per-IP, handshake and inbound message limits (call before `start`)
+    pub fn set_connection_limits(&mut self, limits: ConnectionLimits) {
+        self.limits = Arc::new(limits);
+    }
+    
+    /// Token the /admin endpoint requires; without one /admin is closed (call before `start`)
+    pub fn set_admin_token(&mut self, token: Option<String>) {
+        self.admin_token = Arc::new(token);
+    }
+    
+    /// Web page origins allowed besides the local machine (call before `start`)
+    pub fn set_origin_policy(&mut self, origins: OriginPolicy) {
+        self.origins = Arc::new(origins.bound_to(&self.addresses));
     }
     
-    /// Start the WebSocket server
+    /// Start the WebSocket server on every configured address
     pub async fn start(&self) -> Result<(), Box<dyn Error>> {
-        // Parse the address string to a SocketAddr
-        let addr: SocketAddr = self.address.parse()
-            .map_err(|e| {
-                eprintln!("[{}] Failed to parse address {}: {}", get_timestamp(), self.address, e);
-                e
-            })?;
-
-        // Clone clients for the task
-        let clients = self.clients.clone();
+        // Shared by every connection task
+        let context = Arc::new(ConnectionContext {
+            clients: self.clients.clone(),
+            http_handlers: self.http_handlers.clone(),
+            request_handlers: self.request_handlers.clone(),
+            streams: self.streams.clone(),
+            channel_presets: self.channel_presets.clone(),
+            limits: self.limits.clone(),
+            admin_token: self.admin_token.clone(),
+            origins: self.origins.clone(),
+            session_info: self.session_info.clone(),
+        });
+        let connections = self.connections.clone();
 
-        println!("[{}] Starting WebSocket server on: {}", get_timestamp(), self.address);
+        // An IPv6 wildcard alone also takes IPv4 clients; next to an IPv4 address it is kept
+        // to IPv6 so both can hold the same port
+        let has_ipv4 = self.addresses.iter().any(SocketAddr::is_ipv4);
         
-        // Spawn a task to listen for incoming WebSocket connections
-        tokio::spawn(async move {
-            // Create the TCP listener
-            let listener = match TcpListener::bind(addr).await {
-                Ok(listener) => {
-                    println!("[{}] WebSocket server listening on: {}", get_timestamp(), addr);
-                    listener
-                },
-                Err(e) => {
-                    eprintln!("[{}] Failed to bind WebSocket server to {}: {}", get_timestamp(), addr, e);
-                    return;
-                }
-            };
-
-            // Accept connections in a loop
-            loop {
-                match listener.accept().await {
-                    Ok((stream, addr)) => {
-                        // Only log new connections if verbose
-                        if ws_is_verbose() {
-                            let timestamp = get_timestamp();
-                            println!("\n[{}] 🔌 New WebSocket connection attempt from: {}", timestamp, addr);
-                        }
-                        
-                        // Clone clients for this connection
-                        let clients = clients.clone();
-                        
-                        // Handle the connection in a separate task
-                        tokio::spawn(async move {
-                            if let Err(e) = handle_connection(stream, addr, clients).await {
-                                eprintln!("[{}] Error handling WebSocket connection from {}: {}", 
-                                    get_timestamp(), addr, e);
+        // Bind everything before spawning so a port held by another instance is reported to the caller
+        let mut listeners = Vec::new();
+        for &addr in &self.addresses {
+            info!("Starting WebSocket server on: {}", addr);
+            let listener = bind_listener(addr, has_ipv4).map_err(|e| {
+                error!("Failed to bind WebSocket server to {}: {}", addr, e);
+                format!("Port {} on {} is unavailable ({}); another instance may be running, choose one with --port", addr.port(), addr.ip(), e)
+            })?;
+            info!("WebSocket server listening on: {}", addr);
+            listeners.push(listener);
+        }
+        
+        for listener in listeners {
+            let context = context.clone();
+            let connections = connections.clone();
+            
+            // Spawn a task to listen for incoming WebSocket connections
+            tokio::spawn(async move {
+                // Accept connections in a loop
+                loop {
+                    match listener.accept().await {
+                        Ok((stream, addr)) => {
+                            let addr = unmap_ipv4(addr);
+                            // Only log new connections if verbose
+                            debug!("🔌 New WebSocket connection attempt from: {}", addr);
+                            
+                            // Refuse addresses outside the allowlist and IPs that already hold too many connections
+                            if !context.limits.is_allowed(addr.ip()) {
+                                info!("Refused connection from {}: not in the allowlist", addr);
+                                continue;
                             }
-                        });
-                    },
-                    Err(e) => {
-                        eprintln!("[{}] Error accepting connection: {}", get_timestamp(), e);
-                        // Short sleep to avoid spinning in case of persistent errors
-                        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+                            let Some(slot) = connections.acquire(addr.ip(), context.limits.max_connections_per_ip) else {
+                                info!("Refused connection from {}: too many connections from this address", addr);
+                                continue;
+                            };
+                            
+                            let context = context.clone();
+                            
+                            // Handle the connection in a separate task
+                            tokio::spawn(async move {
+                                let _slot = slot;
+                                // Plain HTTP requests (profiles...) share the port with WebSocket clients
+                                let is_upgrade = tokio::time::timeout(context.limits.handshake_timeout, http_api::is_websocket_upgrade(&stream)).await;
+                                let Ok(is_upgrade) = is_upgrade else {
+                                    debug!("Dropping {}: no request within the handshake timeout", addr);
+                                    return;
+                                };
+                                if !is_upgrade {
+                                    let mut stream = stream;
+                                    let request = http_api::read_request(&mut stream).await;
+                                    // GET /events without an upgrade streams Server-Sent Events
+                                    let result = match request {
+                                        Ok(request) if request.method == "GET" && Endpoint::from_path(&request.path) == Some(Endpoint::Events) => {
+                                            handle_event_stream(stream, request, addr, &context).await
+                                        },
+                                        request => http_api::serve(stream, request, &context.http_handlers, &context.origins).await,
+                                    };
+                                    if let Err(e) = result {
+                                        error!("Error handling HTTP request from {}: {}", addr, e);
+                                    }
+                                    return;
+                                }
+                                if let Err(e) = handle_connection(stream, addr, context).await {
+                                    error!("Error handling WebSocket connection from {}: {}", addr, e);
+                                }
+                            });
+                        },
+                        Err(e) => {
+                            error!("Error accepting connection: {}", e);
+                            // Short sleep to avoid spinning in case of persistent errors
+                            tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+                        }
                     }
                 }
-            }
-        });
+            });
+        }
 
         Ok(())
     }
     
     /// Broadcast telemetry data to all connected clients
     pub fn broadcast_telemetry(&self, telemetry: &TelemetryData) {
-        let clients = self.clients.lock().unwrap();
+        let encode_started = Instant::now();
+        // Kept up to date without clients too, for the ones that connect later
+        let session_info = self.session_info_update(telemetry);
+        let mut clients = self.clients.lock().unwrap_or_else(PoisonError::into_inner);
+        // Clients whose connection ended without removing itself get nothing more
+        clients.retain(|client| !client.is_closed());
         if clients.is_empty() {
             return;
         }
 
-        let message = serde_json::to_string(&telemetry).unwrap();
+        let frame = match self.precision.to_value(telemetry) {
+            Ok(frame) => frame,
+            Err(e) => {
+                error!("Error serializing telemetry: {:?}", e);
+                return;
+            }
+        };
+        let events = (!telemetry.events.is_empty()).then(|| {
+            serde_json::json!({ "type": "events", "session_time": telemetry.SessionTime, "events": telemetry.events })
+        });
+        let map_state = telemetry.map_state.as_ref().map(|state| {
+            serde_json::json!({
+                "type": "map_state",
+                "session_time": telemetry.SessionTime,
+                "segment_count": state.segment_count,
+                "traced": state.traced,
+                "segments": state.segments,
+            })
+        });
+        pipeline_trace::stage("encode", encode_started);
         
-        // Send to each connected client
-        for client in clients.iter() {
-            if let Err(e) = client.0.send(Message::Text(message.clone())) {
-                eprintln!("Error sending telemetry: {:?}", e);
+        // Send to each connected client, building and encoding each variant once per tick
+        let fan_out_started = Instant::now();
+        let mut encoded = EncodedCache::new();
+        let mut variants: HashMap<String, serde_json::Value> = HashMap::new();
+        let mut stream_messages: HashMap<String, Option<serde_json::Value>> = HashMap::new();
+        // A send only fails once the client's connection is gone
+        let mut closed: Vec<ClientSender> = Vec::new();
+        'clients: for client in clients.iter() {
+            let Ok(options) = client.1.lock().map(|options| options.clone()) else {
+                continue;
+            };
+            if let Some(events) = events.as_ref().filter(|_| options.wants("events"))
+                && let Some(message) = encoded.get("events", &options.encoding, || events.clone())
+                && client.0.send(message).is_err()
+            {
+                closed.push(client.clone());
+                continue 'clients;
             }
+            if let Some(session_info) = session_info.as_ref().filter(|_| options.wants("session_info"))
+                && let Some(message) = encoded.get("session_info", &options.encoding, || session_info.clone())
+                && client.0.send(message).is_err()
+            {
+                closed.push(client.clone());
+                continue 'clients;
+            }
+            if let Some(map_state) = map_state.as_ref().filter(|_| options.wants("map_state"))
+                && let Some(message) = encoded.get("map_state", &options.encoding, || map_state.clone())
+                && client.0.send(message).is_err()
+            {
+                closed.push(client.clone());
+                continue 'clients;
+            }
+            for (name, params) in &options.streams {
+                let key = format!("stream:{}:{}", name, params);
+                let value = stream_messages
+                    .entry(key.clone())
+                    .or_insert_with(|| self.streams.get(name).and_then(|provider| provider.message(telemetry, params)));
+                let Some(value) = value.as_ref() else {
+                    continue;
+                };
+                if let Some(message) = encoded.get(&key, &options.encoding, || value.clone())
+                    && client.0.send(message).is_err()
+                {
+                    closed.push(client.clone());
+                    continue 'clients;
+                }
+            }
+            if !options.wants("telemetry") {
+                continue;
+            }
+            let Ok(mut stream) = client.2.lock() else {
+                continue;
+            };
+            if options.rate_hz.is_some_and(|rate_hz| !stream.frame_due(telemetry.server_time_ms, rate_hz)) {
+                continue;
+            }
+            let preset = options.channel_preset.and_then(|name| self.channel_presets.get(&name).map(|preset| (name, preset)));
+            let variant = match &preset {
+                Some((name, _)) => format!("preset:{}", name),
+                None if options.groups.is_empty() => format!("telemetry:v{}", options.schema_version),
+                None => format!("telemetry:v{}:{}", options.schema_version, options.groups.join(",")),
+            };
+            let value = variants.entry(variant.clone()).or_insert_with(|| match preset {
+                // Scaled channels are rounded again under their new names
+                Some((_, preset)) => self.precision.to_value(&preset.apply(&frame)).unwrap_or_default(),
+                None => field_compat::for_schema(&field_groups::filter(&frame, &options.groups), options.schema_version),
+            });
+            let message = if options.delta {
+                let encoder = stream.delta.get_or_insert_with(|| DeltaEncoder::new(self.dead_bands.clone()));
+                match encoder.next(&variant, value) {
+                    DeltaFrame::Keyframe => encoded.get(&format!("{}:keyframe", variant), &options.encoding, || delta::keyframe(value)),
+                    DeltaFrame::Delta(delta) => codec::encode_once(&options.encoding, "delta", &delta),
+                }
+            } else {
+                encoded.get(&variant, &options.encoding, || value.clone())
+            };
+            let Some(message) = message else {
+                continue;
+            };
+            if client.0.send(message).is_err() {
+                closed.push(client.clone());
+            }
+        }
+        for client in &closed {
+            clients.remove(client);
+        }
+        pipeline_trace::stage("fan_out", fan_out_started);
+    }
+    
+    // The session info message when the YAML changed since the last frame
+    fn session_info_update(&self, telemetry: &TelemetryData) -> Option<serde_json::Value> {
+        let mut latest = self.session_info.lock().ok()?;
+        if telemetry.session_info.is_empty() || telemetry.session_info == latest.yaml {
+            return None;
         }
+        let message = serde_json::json!({
+            "type": "session_info",
+            "session_time": telemetry.SessionTime,
+            "session_info": telemetry.session_info,
+            "session_info_parsed": telemetry.session_info_parsed,
+        });
+        latest.yaml = telemetry.session_info.clone();
+        latest.message = Some(message.clone());
+        Some(message)
+    }
+    
+    /// View of the connected clients that can be kept by other components
+    pub fn client_directory(&self) -> ClientDirectory {
+        ClientDirectory(self.clients.clone())
     }
     
     /// Get the current number of connected clients
     pub fn client_count(&self) -> usize {
-        if let Ok(clients) = self.clients.lock() {
-            clients.len()
-        } else {
-            0
-        }
+        let mut clients = self.clients.lock().unwrap_or_else(PoisonError::into_inner);
+        clients.retain(|client| !client.is_closed());
+        clients.len()
     }
 }
 
-// Helper function to get a timestamp string
-fn get_timestamp() -> String {
-    let now = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_default();
+/// Handle a request sent by a client, e.g. `{"type":"time_sync",...}`
+fn handle_client_message(
+    text: &str,
+    addr: SocketAddr,
+    endpoint: Endpoint,
+    client: &ClientSender,
+    time_sync: &mut TimeSyncSession,
+    context: &ConnectionContext,
+) {
+    let ClientSender(reply, options, stream, ..) = client;
+    let channel_presets = &context.channel_presets;
+    let request_handlers = &context.request_handlers;
+    // Take the receive time before parsing so it isn't skewed by our own work
+    let receive_time = server_time_ms();
     
-    let secs = now.as_secs();
-    let millis = now.subsec_millis();
+    let request: serde_json::Value = match serde_json::from_str(text) {
+        Ok(request) => request,
+        Err(e) => {
+            debug!("Rejecting non-JSON message from {}: {}", addr, e);
+            let _ = reply.send(Message::Text(protocol::error(protocol::ERROR_INVALID_JSON, e.to_string(), None).to_string()));
+            return;
+        }
+    };
     
-    // Convert to hours, minutes, seconds in local time
-    let hours = (secs % 86400) / 3600;
-    let minutes = (secs % 3600) / 60;
-    let seconds = secs % 60;
+    // Clients that just subscribed to the session info get the current one after the reply
+    let mut catch_up = None;
+    // `{"subscribe":[...]}` is accepted without a type
+    let message_type = request
+        .get("type")
+        .and_then(|t| t.as_str())
+        .or_else(|| request.get("subscribe").map(|_| "subscribe"))
+        .or_else(|| request.get("rate_hz").map(|_| "set_rate"));
+    let mut response = match message_type {
+        // The client's answer to our hello
+        Some("hello") => {
+            let mut options = options.lock().unwrap_or_else(PoisonError::into_inner);
+            match protocol::negotiate(&request, &options, channel_presets) {
+                Ok(negotiated) => {
+                    if negotiated.wants("session_info") && !options.wants("session_info") {
+                        catch_up = Some(negotiated.encoding.clone());
+                    }
+                    *options = negotiated;
+                    // Frames after a new hello start over from a keyframe
+                    if let Ok(mut stream) = stream.lock() {
+                        stream.reset();
+                    }
+                    protocol::welcome(&options)
+                },
+                Err(error) => error,
+            }
+        },
+        Some("time_sync") => time_sync.handle_request(&request, receive_time),
+        // Delta clients that missed a message ask for the full frame again
+        Some("keyframe") => {
+            if let Ok(mut stream) = stream.lock() {
+                stream.delta = None;
+            }
+            serde_json::json!({ "type": "keyframe_requested" })
+        },
+        // {"rate_hz":10} limits this client's frames, null for every frame
+        Some("set_rate") => match protocol::parse_rate(request.get("rate_hz").unwrap_or(&serde_json::Value::Null), "set_rate") {
+            Ok(rate_hz) => {
+                if let Ok(mut options) = options.lock() {
+                    options.rate_hz = rate_hz;
+                }
+                if let Ok(mut stream) = stream.lock() {
+                    stream.next_frame_ms = None;
+                }
+                serde_json::json!({ "type": "rate", "rate_hz": rate_hz })
+            },
+            Err(error) => error,
+        },
+        // {"subscribe":["timing","tires"]} limits frames to those field groups, [] for whole frames
+        Some("subscribe") => {
+            let list = request.get("subscribe").or_else(|| request.get("groups")).unwrap_or(&serde_json::Value::Null);
+            match protocol::parse_groups(list, "subscribe") {
+                Ok(groups) => {
+                    if let Ok(mut options) = options.lock() {
+                        options.groups = groups.clone();
+                    }
+                    serde_json::json!({ "type": "groups", "groups": groups, "available": field_groups::names() })
+                },
+                Err(error) => error,
+            }
+        },
+        // {"type":"set_channel_preset","preset":"simhub"}, or null for plain frames
+        Some("set_channel_preset") => {
+            let name = request.get("preset").and_then(|p| p.as_str());
+            match name {
+                Some(name) if channel_presets.get(name).is_none() => {
+                    let mut error = protocol::error(protocol::ERROR_UNKNOWN_PRESET, format!("Unknown preset '{}'", name), Some("set_channel_preset"));
+                    error["available"] = serde_json::json!(channel_presets.names());
+                    error
+                },
+                _ => {
+                    if let Ok(mut options) = options.lock() {
+                        options.channel_preset = name.map(str::to_string);
+                    }
+                    serde_json::json!({ "type": "channel_preset", "preset": name, "available": channel_presets.names() })
+                },
+            }
+        },
+        // {"type":"compare","cars":[3,12]} starts or retargets a stream, {"type":"compare","stop":true} ends it
+        Some(message_type) if context.streams.contains_key(message_type) => {
+            let mut options = options.lock().unwrap_or_else(PoisonError::into_inner);
+            if request.get("stop").and_then(|stop| stop.as_bool()) == Some(true) {
+                options.streams.remove(message_type);
+                serde_json::json!({ "type": "unsubscribed", "stream": message_type })
+            } else {
+                match context.streams[message_type].subscribe(&request) {
+                    Ok(params) => {
+                        options.streams.insert(message_type.to_string(), params.clone());
+                        serde_json::json!({ "type": "subscribed", "stream": message_type, "params": params })
+                    },
+                    Err(message) => protocol::error(protocol::ERROR_INVALID_REQUEST, message, Some(message_type)),
+                }
+            }
+        },
+        Some(message_type) if request_handlers.contains_key(message_type) && !endpoint.allows_commands() => {
+            protocol::error(protocol::ERROR_FORBIDDEN, format!("'{}' is only available on /admin", message_type), Some(message_type))
+        },
+        Some(message_type) if request_handlers.contains_key(message_type) => {
+            request_handlers[message_type].handle(&request)
+        },
+        Some(message_type) => {
+            debug!("Rejecting unknown message type {:?} from {}", message_type, addr);
+            protocol::error(protocol::ERROR_UNKNOWN_TYPE, format!("Unknown message type '{}'", message_type), Some(message_type))
+        },
+        None => protocol::error(protocol::ERROR_MISSING_TYPE, "Messages need a string \"type\"", None),
+    };
+    protocol::correlate(&request, &mut response);
     
-    format!("{:02}:{:02}:{:02}.{:03}", hours, minutes, seconds, millis)
+    let _ = reply.send(Message::Text(response.to_string()));
+    if let Some(encoding) = catch_up {
+        send_session_info(&context.session_info, reply, &encoding);
+    }
+}
+
+/// Send the latest session info, if there is one yet
+fn send_session_info(session_info: &SessionInfoState, reply: &UnboundedSender<Message>, encoding: &str) {
+    let message = session_info.lock().ok().and_then(|latest| latest.message.clone());
+    if let Some(message) = message.and_then(|message| codec::encode_once(encoding, "session_info", &message)) {
+        let _ = reply.send(message);
+    }
+}
+
+// Handshake refusal with a short plain text body
+fn refusal(status: StatusCode, message: &str) -> ErrorResponse {
+    let mut response = ErrorResponse::new(Some(message.to_string()));
+    *response.status_mut() = status;
+    response
+}
+
+// Value of `name` in the upgrade request's query string
+fn query_param<'a>(request: &'a Request, name: &str) -> Option<&'a str> {
+    request
+        .uri()
+        .query()
+        .unwrap_or_default()
+        .split('&')
+        .find_map(|pair| pair.strip_prefix(name)?.strip_prefix('='))
+}
+
+// Whether the upgrade request carries `token`, as a bearer token or `?token=`
+fn has_token(request: &Request, token: Option<&str>) -> bool {
+    let Some(token) = token else {
+        return false;
+    };
+    let bearer = request
+        .headers()
+        .get("authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    bearer == Some(token) || query_param(request, "token") == Some(token)
+}
+
+// Listening socket for `addr`. IPv6 sockets are dual-stack unless `v6_only`; the OS
+// default differs (Linux: dual-stack, Windows: IPv6 only), so it is always set explicitly
+fn bind_listener(addr: SocketAddr, v6_only: bool) -> io::Result<TcpListener> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+    if addr.is_ipv6() {
+        socket.set_only_v6(v6_only)?;
+    }
+    #[cfg(not(target_os = "windows"))]
+    socket.set_reuse_address(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(1024)?;
+    TcpListener::from_std(socket.into())
+}
+
+// IPv4 clients of a dual-stack socket arrive as ::ffff:a.b.c.d; log and track them as a.b.c.d
+fn unmap_ipv4(addr: SocketAddr) -> SocketAddr {
+    match addr {
+        SocketAddr::V6(v6) => match v6.ip().to_ipv4_mapped() {
+            Some(v4) => SocketAddr::new(v4.into(), v6.port()),
+            None => addr,
+        },
+        v4 => v4,
+    }
 }
 
 /// Handle an individual WebSocket connection
 async fn handle_connection(
     stream: TcpStream, 
     addr: SocketAddr, 
-    clients: Arc<Mutex<HashSet<ClientSender>>>
+    context: Arc<ConnectionContext>,
 ) -> Result<(), Box<dyn Error>> {
-    let timestamp = get_timestamp();
     
-    // Perform WebSocket handshake
-    let ws_stream = match tokio_tungstenite::accept_async(stream).await {
+    // Perform WebSocket handshake; oversized inbound messages fail the connection
+    let mut config = WebSocketConfig::default();
+    config.max_message_size = Some(context.limits.max_message_bytes);
+    config.max_frame_size = Some(context.limits.max_message_bytes);
+    // The request path picks the endpoint; unknown paths, /admin without the token and pages
+    // from origins that aren't allowed are refused. `?encoding=` picks the codec up front,
+    // so binary clients never need to parse a JSON frame
+    let mut endpoint = None;
+    let mut encoding = None;
+    let route = |request: &Request, response: Response| -> Result<Response, ErrorResponse> {
+        let origin = request.headers().get("origin").and_then(|value| value.to_str().ok());
+        if !context.origins.is_allowed(origin) {
+            return Err(refusal(StatusCode::FORBIDDEN, "Origin not allowed"));
+        }
+        let Some(requested) = Endpoint::from_path(request.uri().path()) else {
+            return Err(refusal(StatusCode::NOT_FOUND, "No WebSocket endpoint at this path"));
+        };
+        if requested.requires_token() && !has_token(request, context.admin_token.as_deref()) {
+            return Err(refusal(StatusCode::UNAUTHORIZED, "Invalid or missing token"));
+        }
+        if let Some(requested) = query_param(request, "encoding") {
+            if codec::by_name(requested).is_none() {
+                return Err(refusal(StatusCode::BAD_REQUEST, "Unsupported encoding"));
+            }
+            encoding = Some(requested.to_string());
+        }
+        endpoint = Some(requested);
+        Ok(response)
+    };
+    let handshake = tokio_tungstenite::accept_hdr_async_with_config(stream, route, Some(config));
+    let Ok(handshake) = tokio::time::timeout(context.limits.handshake_timeout, handshake).await else {
+        return Err(format!("WebSocket handshake with {} timed out", addr).into());
+    };
+    let ws_stream = match handshake {
         Ok(ws_stream) => {
             // Only log handshake completion if verbose
-            if ws_is_verbose() {
-                println!("[{}] 🤝 WebSocket handshake completed with {}", timestamp, addr);
-            }
+            debug!("🤝 WebSocket handshake completed with {}", addr);
             ws_stream
         },
         Err(e) => {
-            println!("[{}] ❌ Error during WebSocket handshake with {}: {}", timestamp, addr, e);
+            warn!("❌ Error during WebSocket handshake with {}: {}", addr, e);
             return Err(Box::new(e));
         }
     };
     
+    let endpoint = endpoint.unwrap_or(Endpoint::Legacy);
+    debug!("{} connected to {}", addr, endpoint.name());
+    
     // Create a channel for sending messages to this client
     let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
-    let client_sender = ClientSender::new(tx);
+    let mut options = endpoint.client_options();
+    if let Some(encoding) = encoding {
+        options.encoding = encoding;
+    }
+    let client_sender = ClientSender::new(tx, options, ClientInfo { addr, endpoint, connected_at: SystemTime::now() });
     
     // Add the new client to our client set
     {
         // Only log client addition if verbose
-        if ws_is_verbose() {
-            println!("[{}] 👨‍👩‍👧‍👦 Adding client {} to client pool", timestamp, addr);
-        }
-        let mut clients = clients.lock().unwrap();
+        debug!("👨‍👩‍👧‍👦 Adding client {} to client pool", addr);
+        let mut clients = context.clients.lock().unwrap_or_else(PoisonError::into_inner);
         clients.insert(client_sender.clone());
-        println!("[{}] ℹ️ Now serving {} clients", timestamp, clients.len());
+        info!("ℹ️ Now serving {} clients", clients.len());
+    }
+    
+    // Greet the client before any telemetry with what the server offers, plus the bootstrap
+    // info (units preference) so clients that don't negotiate still get it
+    let bootstrap = context.request_handlers
+        .get("bootstrap")
+        .map(|bootstrap| bootstrap.handle(&serde_json::json!({ "type": "bootstrap" })));
+    let mut commands: Vec<String> = SESSION_COMMANDS.iter().map(|command| command.to_string()).collect();
+    if endpoint.allows_commands() {
+        commands.extend(context.request_handlers.keys().cloned());
+    }
+    commands.extend(context.streams.keys().cloned());
+    let hello = protocol::hello(commands, &context.channel_presets, bootstrap);
+    let _ = client_sender.0.send(Message::Text(hello.to_string()));
+    let initial = client_sender.1.lock().ok().filter(|options| options.wants("session_info")).map(|options| options.encoding.clone());
+    if let Some(encoding) = initial {
+        send_session_info(&context.session_info, &client_sender.0, &encoding);
     }
     
     // Split WebSocket stream into sender and receiver
@@ -228,41 +822,56 @@ async fn handle_connection(
         let mut ws_sender = ws_sender;
         while let Some(msg) = rx.recv().await {
             if let Err(e) = ws_sender.send(msg).await {
-                println!("[{}] 📤 Error sending message to {}: {}", get_timestamp(), addr, e);
+                warn!("📤 Error sending message to {}: {}", addr, e);
                 break;
             }
         }
     });
     
     // Process incoming WebSocket messages
+    let client = client_sender.clone();
+    let recv_context = context.clone();
     let mut recv_task = tokio::spawn(async move {
         let mut ws_receiver = ws_receiver;
+        let mut time_sync = TimeSyncSession::new();
+        let max_messages_per_sec = endpoint.max_messages_per_sec(&recv_context.limits);
+        let mut rate_limiter = MessageRateLimiter::new(max_messages_per_sec);
         while let Some(result) = ws_receiver.next().await {
             match result {
                 Ok(msg) => {
                     if msg.is_close() {
-                        if ws_is_verbose() {
-                            println!("[{}] 👋 Received close message from {}", get_timestamp(), addr);
-                        }
+                        debug!("👋 Received close message from {}", addr);
                         break;
                     }
                     
                     // Handle other message types as needed, only log if verbose
-                    if ws_is_verbose() && (msg.is_text() || msg.is_binary()) {
-                        println!("[{}] 📥 Received message from {}", get_timestamp(), addr);
-                        // In the future we might process client messages here
+                    if msg.is_text() || msg.is_binary() {
+                        debug!("📥 Received message from {}", addr);
+                    }
+                    
+                    // Over the rate limit: refuse the message, and the client if it keeps going
+                    if (msg.is_text() || msg.is_binary()) && !rate_limiter.allow() {
+                        if rate_limiter.violations >= MAX_RATE_VIOLATIONS {
+                            info!("Disconnecting {}: too many messages", addr);
+                            break;
+                        }
+                        let error = protocol::error(protocol::ERROR_RATE_LIMITED, format!("More than {} messages per second", max_messages_per_sec), None);
+                        let _ = client.0.send(Message::Text(error.to_string()));
+                        continue;
+                    }
+                    
+                    if let Message::Text(text) = msg {
+                        handle_client_message(&text, addr, endpoint, &client, &mut time_sync, &recv_context);
                     }
                 },
                 Err(e) => {
-                    println!("[{}] ❌ Error receiving message from {}: {}", get_timestamp(), addr, e);
+                    warn!("❌ Error receiving message from {}: {}", addr, e);
                     break;
                 }
             }
         }
         
-        if ws_is_verbose() {
-            println!("[{}] 🔌 Client {} disconnected", get_timestamp(), addr);
-        }
+        debug!("🔌 Client {} disconnected", addr);
     });
     
     // Wait for either task to complete - this means the connection is closing
@@ -273,14 +882,101 @@ async fn handle_connection(
     
     // Clean up the client when they disconnect
     {
-        let mut clients = clients.lock().unwrap();
+        let mut clients = context.clients.lock().unwrap_or_else(PoisonError::into_inner);
         clients.remove(&client_sender);
         // Only log client removal if verbose
-        if ws_is_verbose() {
-            println!("[{}] 👋 Removed client {}. Now serving {} clients", 
-                    get_timestamp(), addr, clients.len());
-        }
+        debug!("👋 Removed client {}. Now serving {} clients", addr, clients.len());
     }
     
     Ok(())
-} 
\ No newline at end of file
+} 
+
+// Options for an event stream client from its query string, with the names `hello` takes;
+// Err holds the error reply
+fn event_stream_options(request: &http_api::HttpRequest, presets: &ChannelPresets) -> Result<ClientOptions, serde_json::Value> {
+    let list = |value: &String| value.split(',').filter(|item| !item.is_empty()).map(|item| serde_json::json!(item)).collect::<Vec<_>>();
+    let number = |value: &String| value.parse::<f64>().map_or_else(|_| serde_json::json!(value), |number| serde_json::json!(number));
+    let mut hello = serde_json::Map::new();
+    for (name, value) in &request.query {
+        let value = match name.as_str() {
+            "topics" | "groups" => serde_json::Value::Array(list(value)),
+            "rate_hz" | "schema_version" | "protocol_version" => number(value),
+            "delta" => serde_json::json!(value == "true" || value == "1"),
+            "channel_preset" => serde_json::json!(value),
+            "encoding" if value != "json" => {
+                return Err(protocol::error(protocol::ERROR_UNSUPPORTED_ENCODING, "Event streams are JSON only", Some("hello")));
+            },
+            _ => continue,
+        };
+        hello.insert(name.clone(), value);
+    }
+    protocol::negotiate(&serde_json::Value::Object(hello), &Endpoint::EventStream.client_options(), presets)
+}
+
+/// Serve `GET /events` as Server-Sent Events: the client joins the broadcast like a
+/// WebSocket client, and its messages are written as events until it goes away
+async fn handle_event_stream(
+    mut stream: TcpStream,
+    request: http_api::HttpRequest,
+    addr: SocketAddr,
+    context: &ConnectionContext,
+) -> Result<(), String> {
+    let allowed = request.origin_allowed(&context.origins);
+    let allow_origin = request.headers.get("origin").map(String::as_str).filter(|_| allowed);
+    let refusal = match event_stream_options(&request, &context.channel_presets) {
+        _ if !allowed => Err(http_api::HttpResponse::error(403, "Origin not allowed")),
+        Ok(options) => Ok(options),
+        Err(error) => Err(http_api::HttpResponse::json(400, &error)),
+    };
+    let options = match refusal {
+        Ok(options) => options,
+        Err(response) => {
+            return http_api::write_response(&mut stream, &response, allow_origin)
+                .await
+                .map_err(|e| format!("Failed to write response: {}", e));
+        },
+    };
+    http_api::write_event_stream_head(&mut stream, allow_origin)
+        .await
+        .map_err(|e| format!("Failed to start event stream: {}", e))?;
+
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let welcome = protocol::welcome(&options);
+    let wants_session_info = options.wants("session_info");
+    let client_sender = ClientSender::new(tx, options, ClientInfo { addr, endpoint: Endpoint::EventStream, connected_at: SystemTime::now() });
+    let _ = client_sender.0.send(Message::Text(welcome.to_string()));
+    if wants_session_info {
+        send_session_info(&context.session_info, &client_sender.0, "json");
+    }
+    {
+        let mut clients = context.clients.lock().unwrap_or_else(PoisonError::into_inner);
+        clients.insert(client_sender.clone());
+        info!("{} connected to /events (SSE). Now serving {} clients", addr, clients.len());
+    }
+
+    // A comment now and then, so proxies don't close an idle stream and a client that
+    // went away is noticed while the sim is not running
+    let mut keepalive = tokio::time::interval(EVENT_STREAM_KEEPALIVE);
+    keepalive.tick().await;
+    loop {
+        let written = tokio::select! {
+            message = rx.recv() => match message {
+                Some(Message::Text(text)) => http_api::write_event(&mut stream, Some(&text)).await,
+                // Only JSON is negotiated for event streams, so nothing else should arrive
+                Some(_) => continue,
+                None => break,
+            },
+            _ = keepalive.tick() => http_api::write_event(&mut stream, None).await,
+        };
+        if let Err(e) = written {
+            // The client closing the stream is how it ends
+            debug!("Event stream to {} ended: {}", addr, e);
+            break;
+        }
+    }
+
+    let mut clients = context.clients.lock().unwrap_or_else(PoisonError::into_inner);
+    clients.remove(&client_sender);
+    debug!("👋 Removed client {}. Now serving {} clients", addr, clients.len());
+    Ok(())
+}
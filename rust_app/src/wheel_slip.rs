@@ -0,0 +1,161 @@
+use crate::derived::DerivedChannel;
+use crate::events;
+use crate::telemetry_fields::TelemetryData;
+use serde::{Serialize, Deserialize};
+
+/// Wheel order used by all per-wheel arrays
+pub const WHEEL_NAMES: [&str; 4] = ["LF", "RF", "LR", "RR"];
+
+// Below this ground speed slip ratios are dominated by noise
+const MIN_SPEED_MS: f32 = 5.0;
+// Slip ratio thresholds for lockups (braking) and wheelspin (throttle)
+const LOCK_SLIP_RATIO: f32 = -0.20;
+const SPIN_SLIP_RATIO: f32 = 0.15;
+// A wheel must recover past these values before a new lockup/wheelspin is counted
+const LOCK_RELEASE_RATIO: f32 = -0.10;
+const SPIN_RELEASE_RATIO: f32 = 0.08;
+// Smoothing factor for the rolling radius estimate
+const RADIUS_SMOOTHING: f32 = 0.02;
+// Plausible rolling radius range for calibration samples (meters)
+const MIN_RADIUS_M: f32 = 0.20;
+const MAX_RADIUS_M: f32 = 0.45;
+
+/// Per-wheel slip information for the current frame
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct WheelSlipData {
+    /// (wheel surface speed - ground speed) / ground speed, LF, RF, LR, RR
+    pub slip_ratio: [f32; 4],
+    pub locked: [bool; 4],
+    pub spinning: [bool; 4],
+    /// Lockups started on the current lap
+    pub lap_lockups: [u32; 4],
+    /// Wheelspin occurrences started on the current lap
+    pub lap_wheelspins: [u32; 4],
+    /// Rolling radius in use for each wheel (meters, 0 until calibrated)
+    pub tire_radius_m: [f32; 4],
+}
+
+/// Computes slip ratios from wheel RPM and ground speed and detects lockups and wheelspin.
+///
+/// Neither the telemetry nor the session info gives the tire size, so the rolling radius is
+/// calibrated on the fly from frames where the car is coasting in a straight line and slip
+/// is negligible.
+pub struct WheelSlip {
+    estimated_radius: [f32; 4],
+    state: WheelSlipData,
+    current_lap: i32,
+}
+
+impl WheelSlip {
+    pub fn new() -> Self {
+        Self {
+            estimated_radius: [0.0; 4],
+            state: WheelSlipData::default(),
+            current_lap: 0,
+        }
+    }
+
+    // Coasting straight with no pedal input is the best moment to learn the rolling radius
+    fn is_calibration_frame(data: &TelemetryData) -> bool {
+        data.VelocityX > 15.0
+            && data.brake_pct < 2.0
+            && data.throttle_pct < 60.0
+            && data.steering_angle_deg.abs() < 5.0
+            && data.g_force_lat.abs() < 0.2
+    }
+
+    fn calibrate(&mut self, wheel: usize, ground_speed: f32, omega: f32) {
+        let sample = ground_speed / omega;
+        if !(MIN_RADIUS_M..=MAX_RADIUS_M).contains(&sample) {
+            return;
+        }
+        let current = self.estimated_radius[wheel];
+        self.estimated_radius[wheel] = if current == 0.0 {
+            sample
+        } else {
+            current + (sample - current) * RADIUS_SMOOTHING
+        };
+    }
+}
+
+impl DerivedChannel for WheelSlip {
+    fn name(&self) -> &'static str {
+        "wheel_slip"
+    }
+
+    fn dependencies(&self) -> &'static [&'static str] {
+        &["velocity", "g_forces"]
+    }
+
+    fn compute(&mut self, data: &mut TelemetryData) {
+        if data.lap_completed != self.current_lap {
+            self.current_lap = data.lap_completed;
+            self.state.lap_lockups = [0; 4];
+            self.state.lap_wheelspins = [0; 4];
+        }
+
+        // Longitudinal ground speed; reversing isn't interesting here
+        let ground_speed = data.VelocityX;
+        let calibrating = Self::is_calibration_frame(data);
+
+        for wheel in 0..4 {
+            let omega = data.wheel_rpm[wheel] * std::f32::consts::TAU / 60.0;
+
+            if calibrating && omega > 1.0 {
+                self.calibrate(wheel, ground_speed, omega);
+            }
+
+            let radius = self.estimated_radius[wheel];
+            if radius <= 0.0 || ground_speed < MIN_SPEED_MS {
+                self.state.slip_ratio[wheel] = 0.0;
+                self.state.locked[wheel] = false;
+                self.state.spinning[wheel] = false;
+                continue;
+            }
+
+            let slip = (omega * radius - ground_speed) / ground_speed;
+            self.state.slip_ratio[wheel] = slip;
+
+            // Lockup: strongly negative slip under braking, with hysteresis
+            let was_locked = self.state.locked[wheel];
+            let locked = if was_locked {
+                slip < LOCK_RELEASE_RATIO
+            } else {
+                slip < LOCK_SLIP_RATIO && data.brake_pct > 5.0
+            };
+            if locked && !was_locked {
+                self.state.lap_lockups[wheel] += 1;
+                let details = serde_json::json!({
+                    "wheel": WHEEL_NAMES[wheel],
+                    "slip_ratio": slip,
+                    "speed_kph": data.speed_kph,
+                    "lap_dist_pct": data.lap_dist_pct,
+                });
+                events::emit(data, "wheel_lock", details);
+            }
+            self.state.locked[wheel] = locked;
+
+            // Wheelspin: strongly positive slip on throttle, with hysteresis
+            let was_spinning = self.state.spinning[wheel];
+            let spinning = if was_spinning {
+                slip > SPIN_RELEASE_RATIO
+            } else {
+                slip > SPIN_SLIP_RATIO && data.throttle_pct > 10.0
+            };
+            if spinning && !was_spinning {
+                self.state.lap_wheelspins[wheel] += 1;
+                let details = serde_json::json!({
+                    "wheel": WHEEL_NAMES[wheel],
+                    "slip_ratio": slip,
+                    "speed_kph": data.speed_kph,
+                    "lap_dist_pct": data.lap_dist_pct,
+                });
+                events::emit(data, "wheelspin", details);
+            }
+            self.state.spinning[wheel] = spinning;
+        }
+
+        self.state.tire_radius_m = self.estimated_radius;
+        data.wheel_slip = Some(self.state.clone());
+    }
+}
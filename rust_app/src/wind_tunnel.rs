@@ -0,0 +1,224 @@
+//! Steady-state averaging for setup A/B tests ("wind tunnel" runs).
+//!
+//! The driver holds a constant speed down a straight; the engineer then asks for the
+//! average of the channels that matter (ride heights, speed, RPM, temperatures) over that
+//! stretch. The last few minutes of those channels are buffered so the request can come
+//! after the fact:
+//!
+//! - `{"type":"wind_tunnel","seconds":8}` averages the last 8 seconds,
+//! - `{"type":"wind_tunnel","lap_dist_from":0.62,"lap_dist_to":0.78}` the most recent pass
+//!   through that part of the lap,
+//! - `{"type":"wind_tunnel","action":"start"}` ... `{"type":"wind_tunnel","action":"stop"}`
+//!   everything in between.
+//!
+//! `channels` picks the channels by name (see `CHANNELS`); all of them by default.
+
+use crate::derived::DerivedChannel;
+use crate::protocol;
+use crate::telemetry_fields::TelemetryData;
+use crate::websocket_server::RequestHandler;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+/// Seconds of history kept
+pub const BUFFER_SECONDS: f64 = 300.0;
+
+/// Channels that can be averaged, by name
+pub const CHANNELS: &[(&str, fn(&TelemetryData) -> f32)] = &[
+    ("speed_kph", |d| d.speed_kph),
+    ("rpm", |d| d.rpm),
+    ("throttle_pct", |d| d.throttle_pct),
+    ("brake_pct", |d| d.brake_pct),
+    ("steering_angle_deg", |d| d.steering_angle_deg),
+    ("ride_height_lf_mm", |d| d.ride_height_mm[0]),
+    ("ride_height_rf_mm", |d| d.ride_height_mm[1]),
+    ("ride_height_lr_mm", |d| d.ride_height_mm[2]),
+    ("ride_height_rr_mm", |d| d.ride_height_mm[3]),
+    ("shock_defl_lf_mm", |d| d.shock_defl_mm[0]),
+    ("shock_defl_rf_mm", |d| d.shock_defl_mm[1]),
+    ("shock_defl_lr_mm", |d| d.shock_defl_mm[2]),
+    ("shock_defl_rr_mm", |d| d.shock_defl_mm[3]),
+    ("water_temp_c", |d| d.water_temp_c),
+    ("oil_temp_c", |d| d.oil_temp_c),
+    ("tire_temp_lf_c", |d| d.tire_temps_c[0]),
+    ("tire_temp_rf_c", |d| d.tire_temps_c[1]),
+    ("tire_temp_lr_c", |d| d.tire_temps_c[2]),
+    ("tire_temp_rr_c", |d| d.tire_temps_c[3]),
+];
+
+struct Sample {
+    time: f64,
+    lap: i32,
+    lap_dist_pct: f32,
+    values: Vec<f32>,
+}
+
+/// Statistics of one channel over the averaged stretch
+#[derive(Serialize, Debug)]
+pub struct ChannelAverage {
+    pub mean: f32,
+    pub min: f32,
+    pub max: f32,
+    pub std_dev: f32,
+}
+
+#[derive(Default)]
+struct Buffer {
+    samples: VecDeque<Sample>,
+    /// Session time of an open start/stop capture
+    started_at: Option<f64>,
+}
+
+/// Recent channel history shared between the telemetry thread and the server
+#[derive(Clone, Default)]
+pub struct WindTunnel {
+    buffer: Arc<Mutex<Buffer>>,
+}
+
+impl WindTunnel {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn average(samples: &[&Sample], channels: &[usize]) -> BTreeMap<&'static str, ChannelAverage> {
+        let n = samples.len() as f32;
+        channels
+            .iter()
+            .map(|&channel| {
+                let values = samples.iter().map(|sample| sample.values[channel]);
+                let mean = values.clone().sum::<f32>() / n;
+                let variance = values.clone().map(|v| (v - mean).powi(2)).sum::<f32>() / n;
+                let average = ChannelAverage {
+                    mean,
+                    min: values.clone().fold(f32::INFINITY, f32::min),
+                    max: values.fold(f32::NEG_INFINITY, f32::max),
+                    std_dev: variance.sqrt(),
+                };
+                (CHANNELS[channel].0, average)
+            })
+            .collect()
+    }
+}
+
+// Samples of the most recent pass through [from, to] of the lap
+fn last_pass(samples: &VecDeque<Sample>, from: f32, to: f32) -> Vec<&Sample> {
+    let inside = |sample: &Sample| {
+        if from <= to {
+            sample.lap_dist_pct >= from && sample.lap_dist_pct <= to
+        } else {
+            // A range across the line, e.g. 0.95 to 0.05
+            sample.lap_dist_pct >= from || sample.lap_dist_pct <= to
+        }
+    };
+    let mut pass: Vec<&Sample> = samples
+        .iter()
+        .rev()
+        .skip_while(|sample| !inside(sample))
+        .take_while(|sample| inside(sample))
+        .collect();
+    pass.reverse();
+    pass
+}
+
+impl RequestHandler for WindTunnel {
+    fn handle(&self, request: &Value) -> Value {
+        let channels: Vec<usize> = match request.get("channels").and_then(Value::as_array) {
+            Some(names) => {
+                let mut channels = Vec::new();
+                for name in names.iter().filter_map(Value::as_str) {
+                    match CHANNELS.iter().position(|(channel, _)| *channel == name) {
+                        Some(channel) => channels.push(channel),
+                        None => return protocol::error(protocol::ERROR_INVALID_REQUEST, format!("Unknown channel '{}'", name), Some("wind_tunnel")),
+                    }
+                }
+                channels
+            },
+            None => (0..CHANNELS.len()).collect(),
+        };
+
+        let Ok(mut buffer) = self.buffer.lock() else {
+            return protocol::error(protocol::ERROR_FAILED, "History unavailable", Some("wind_tunnel"));
+        };
+        let now = buffer.samples.back().map_or(0.0, |sample| sample.time);
+        let action = request.get("action").and_then(Value::as_str);
+        if action == Some("start") {
+            buffer.started_at = Some(now);
+            return json!({ "type": "wind_tunnel", "capturing": true, "session_time": now });
+        }
+
+        let samples: Vec<&Sample> = if action == Some("stop") {
+            let Some(start) = buffer.started_at.take() else {
+                return protocol::error(protocol::ERROR_INVALID_REQUEST, "No capture was started", Some("wind_tunnel"));
+            };
+            buffer.samples.iter().filter(|sample| sample.time >= start).collect()
+        } else if let (Some(from), Some(to)) = (
+            request.get("lap_dist_from").and_then(Value::as_f64),
+            request.get("lap_dist_to").and_then(Value::as_f64),
+        ) {
+            last_pass(&buffer.samples, from as f32, to as f32)
+        } else if let Some(seconds) = request.get("seconds").and_then(Value::as_f64) {
+            buffer.samples.iter().filter(|sample| sample.time >= now - seconds).collect()
+        } else {
+            return protocol::error(
+                protocol::ERROR_INVALID_REQUEST,
+                "wind_tunnel needs \"seconds\", \"lap_dist_from\"/\"lap_dist_to\" or \"action\"",
+                Some("wind_tunnel"),
+            );
+        };
+
+        let (Some(first), Some(last)) = (samples.first(), samples.last()) else {
+            return protocol::error(protocol::ERROR_NOT_FOUND, "No samples in that range", Some("wind_tunnel"));
+        };
+        json!({
+            "type": "wind_tunnel",
+            "from_session_time": first.time,
+            "to_session_time": last.time,
+            "lap": last.lap,
+            "samples": samples.len(),
+            "channels": Self::average(&samples, &channels),
+        })
+    }
+}
+
+/// Derived channel that fills the wind tunnel's history buffer
+pub struct WindTunnelRecorder {
+    tunnel: WindTunnel,
+}
+
+impl WindTunnelRecorder {
+    pub fn new(tunnel: WindTunnel) -> Self {
+        Self { tunnel }
+    }
+}
+
+impl DerivedChannel for WindTunnelRecorder {
+    fn name(&self) -> &'static str {
+        "wind_tunnel"
+    }
+
+    fn compute(&mut self, data: &mut TelemetryData) {
+        let Ok(mut buffer) = self.tunnel.buffer.lock() else {
+            return;
+        };
+        if data.race_clock.session_reset {
+            buffer.samples.clear();
+            buffer.started_at = None;
+        }
+        // Nothing steady about the garage or a replay jump
+        if data.lap_dist_pct < 0.0 || data.is_replay_playing {
+            return;
+        }
+        let time = data.SessionTime as f64;
+        buffer.samples.push_back(Sample {
+            time,
+            lap: data.lap_completed,
+            lap_dist_pct: data.lap_dist_pct,
+            values: CHANNELS.iter().map(|(_, read)| read(data)).collect(),
+        });
+        while buffer.samples.front().is_some_and(|sample| sample.time < time - BUFFER_SECONDS) {
+            buffer.samples.pop_front();
+        }
+    }
+}
@@ -0,0 +1,50 @@
+//! Runs the scenarios in tests/scenarios through `speedforge run-scenario`.
+
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+fn scenarios() -> Vec<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("scenarios");
+    let mut paths: Vec<PathBuf> = fs::read_dir(&dir)
+        .expect("scenario folder")
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|extension| extension == "yaml"))
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty(), "no scenarios in {}", dir.display());
+    paths
+}
+
+// The `--json` report of a scenario, asserting it ran with every expected event
+fn run(path: &Path) -> Value {
+    let output = Command::new(env!("CARGO_BIN_EXE_speedforge"))
+        .args(["run-scenario", "--json"])
+        .arg(path)
+        .output()
+        .expect("run speedforge");
+    // Log lines go to stdout too; the report is the pretty printed object after them
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<&str> = stdout.lines().collect();
+    let start = lines.iter().position(|line| *line == "{").unwrap_or(lines.len());
+    let report: Value = serde_json::from_str(&lines[start..].join("\n"))
+        .unwrap_or_else(|e| panic!("{}: no report ({}): {}{}", path.display(), e, stdout, String::from_utf8_lossy(&output.stderr)));
+    assert!(output.status.success(), "{}: missing events {}", path.display(), report["missing_events"]);
+    report
+}
+
+#[test]
+fn scenarios_raise_expected_events() {
+    for path in scenarios() {
+        run(&path);
+    }
+}
+
+#[test]
+fn grid_start_keeps_positions() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("scenarios").join("grid_start.yaml");
+    let report = run(&path);
+    let overtakes = report["event_counts"]["overtake"].as_u64().unwrap_or(0);
+    assert_eq!(overtakes, 0, "cars passed each other without changing order: {}", report["events"]);
+}